@@ -115,6 +115,11 @@ impl Default for AuthService {
     }
 }
 
+/// The id of the API key a request authenticated with, set by
+/// `api_key_middleware`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ApiKeyId(pub Uuid);
+
 pub struct AuthContext {
     pub user_id: Option<String>,
     pub email: Option<String>,
@@ -252,6 +257,7 @@ pub async fn api_key_middleware(
                     req.extensions_mut().insert(user.role.clone());
                     req.extensions_mut().insert(user.id.to_string());
                     req.extensions_mut().insert(user.email.clone());
+                    req.extensions_mut().insert(ApiKeyId(key_id));
 
                     trace!("API key validated successfully for user: {}", user.email);
                     return Ok(next.run(req).await);
@@ -383,13 +383,24 @@ pub fn create_final_router(state: AppState, rate_limit_state: RateLimitState, au
         .route("/", get(crate::routes::root))
         .route("/ui", get(crate::routes::web_ui))
         .route("/v1/convert-sync", post(crate::routes::convert_sync))
+        .route("/v1/extract-sync", post(crate::routes::extract_sync))
+        .route("/v1/extract-images", post(crate::routes::extract_images))
+        .route("/v1/convert-batch", post(crate::routes::convert_batch))
         .route("/auth/register", post(register))
         .route("/auth/login", post(login))
         .route("/health", get(crate::routes::health_check))
+        .route("/health/ready", get(crate::routes::readiness_check))
         .merge(crate::routes::create_swagger_router());
 
     // Authenticated routes with JWT middleware
-    let authenticated_routes = create_router()
+    let mut api_routes = create_router();
+    if crate::routes::debug_routes_enabled() {
+        api_routes = api_routes.merge(
+            crate::routes::create_debug_router()
+                .route_layer(middleware::from_fn(rbac_middleware(Role::Developer)))
+        );
+    }
+    let authenticated_routes = api_routes
         .merge(
             Router::new()
                 .route("/auth/users/:user_id/api-keys", post(create_api_key))
@@ -401,16 +412,21 @@ pub fn create_final_router(state: AppState, rate_limit_state: RateLimitState, au
                         .route_layer(middleware::from_fn(rbac_middleware(Role::Admin)))
                 )
         )
+        // Inside the JWT check, so clients are limited by their role's tier
+        .route_layer(middleware::from_fn_with_state(
+            rate_limit_state.clone(),
+            rate_limit_middleware
+        ))
         .route_layer(middleware::from_fn_with_state(
             auth_state.clone(),
             jwt_middleware
         ));
 
     public_routes
-        .merge(authenticated_routes)
         .route_layer(middleware::from_fn_with_state(
             rate_limit_state.clone(),
             rate_limit_middleware
         ))
+        .merge(authenticated_routes)
         .with_state(state)
 }
\ No newline at end of file
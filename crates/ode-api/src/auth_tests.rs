@@ -392,4 +392,62 @@ mod auth_tests {
             .expect("Rate limit check failed");
         assert!(result, "Request after window reset should be allowed");
     }
+
+    #[tokio::test]
+    async fn test_us_015_developer_and_admin_keys_get_distinct_allowances() {
+        use crate::auth::ApiKeyId;
+        use crate::rate_limit::rate_limit_middleware;
+        use axum::{middleware, middleware::Next, response::Response, routing::get, Router};
+        use uuid::Uuid;
+
+        let state = RateLimitState::new()
+            .with_tier(Role::Developer, 2, 60)
+            .with_tier(Role::Admin, 4, 60);
+        let (developer_key, admin_key) = (Uuid::new_v4(), Uuid::new_v4());
+        // Stands in for the API key middleware, which runs before rate limiting
+        let identify = move |mut req: Request, next: Next| async move {
+            let tier = if req.headers().contains_key("x-admin") {
+                (Role::Admin, admin_key)
+            } else {
+                (Role::Developer, developer_key)
+            };
+            req.extensions_mut().insert(tier.0);
+            req.extensions_mut().insert(ApiKeyId(tier.1));
+            next.run(req).await
+        };
+        let app = Router::new()
+            .route("/", get(|| async { "ok" }))
+            .route_layer(middleware::from_fn_with_state(state, rate_limit_middleware))
+            .layer(middleware::from_fn(identify));
+
+        let send = |admin: bool| {
+            let mut request = Request::builder().uri("/");
+            if admin {
+                request = request.header("x-admin", "1");
+            }
+            app.clone().oneshot(request.body(Body::empty()).unwrap())
+        };
+        let header = |response: &Response, name: &str| {
+            response.headers().get(name).unwrap().to_str().unwrap().to_string()
+        };
+
+        for remaining in ["1", "0"] {
+            let response = send(false).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(header(&response, "X-RateLimit-Limit"), "2");
+            assert_eq!(header(&response, "X-RateLimit-Remaining"), remaining);
+        }
+        let response = send(false).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(header(&response, "Retry-After"), "60");
+
+        // The developer key being exhausted leaves the admin key's allowance intact
+        for _ in 0..4 {
+            let response = send(true).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(header(&response, "X-RateLimit-Limit"), "4");
+        }
+        let response = send(true).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
 }
@@ -2,6 +2,7 @@ use sqlx::{PgPool, Row};
 use uuid::Uuid;
 use chrono::Utc;
 use crate::models::{JobStatus, JobMetadata, ConversionProfile, ConversionOptions, CreateProfileRequest, UpdateProfileRequest, User, ApiKey, Role};
+use crate::webhooks::DeliveryAttempt;
 
 #[derive(Clone)]
 pub struct Database {
@@ -31,6 +32,17 @@ impl Database {
             "CREATE INDEX IF NOT EXISTS idx_jobs_status ON jobs(status)",
             "CREATE INDEX IF NOT EXISTS idx_jobs_created_at ON jobs(created_at)",
             "CREATE INDEX IF NOT EXISTS idx_jobs_profile_id ON jobs(profile_id)",
+            "ALTER TABLE jobs ADD COLUMN IF NOT EXISTS webhook_secret TEXT",
+            r#"CREATE TABLE IF NOT EXISTS webhook_deliveries (
+                id UUID PRIMARY KEY,
+                job_id UUID NOT NULL REFERENCES jobs(id) ON DELETE CASCADE,
+                webhook_url TEXT NOT NULL,
+                attempt INTEGER NOT NULL,
+                status_code INTEGER,
+                error TEXT,
+                attempted_at TIMESTAMPTZ NOT NULL
+            )"#,
+            "CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_job_id ON webhook_deliveries(job_id)",
             r#"CREATE TABLE IF NOT EXISTS conversion_profiles (
                 id UUID PRIMARY KEY,
                 name VARCHAR(255) NOT NULL,
@@ -75,6 +87,12 @@ impl Database {
         &self.pool
     }
 
+    /// Run a trivial query to check the database is reachable.
+    pub async fn ping(&self) -> Result<(), sqlx::Error> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+
     pub async fn create_job(
         &self,
         id: Uuid,
@@ -83,14 +101,15 @@ impl Database {
         pdf_data: &[u8],
         config: serde_json::Value,
         webhook_url: Option<String>,
+        webhook_secret: Option<String>,
         profile_id: Option<Uuid>,
     ) -> Result<(), sqlx::Error> {
         let now = Utc::now();
         
         sqlx::query(
             r#"
-            INSERT INTO jobs (id, status, created_at, updated_at, file_name, file_size, webhook_url, pdf_data, config, profile_id)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            INSERT INTO jobs (id, status, created_at, updated_at, file_name, file_size, webhook_url, webhook_secret, pdf_data, config, profile_id)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
             "#
         )
         .bind(id)
@@ -100,6 +119,7 @@ impl Database {
         .bind(&file_name)
         .bind(file_size as i64)
         .bind(&webhook_url)
+        .bind(&webhook_secret)
         .bind(pdf_data)
         .bind(&config)
         .bind(profile_id)
@@ -110,14 +130,14 @@ impl Database {
     }
 
     pub async fn get_job(&self, id: Uuid) -> Result<Option<JobMetadata>, sqlx::Error> {
-        let row = sqlx::query_as::<_, (Uuid, String, chrono::DateTime<Utc>, chrono::DateTime<Utc>, String, i64, Option<String>, Option<String>, Option<Uuid>, Option<String>)>(
-            "SELECT id, status, created_at, updated_at, file_name, file_size, webhook_url, error_message, profile_id, result_url FROM jobs WHERE id = $1"
+        let row = sqlx::query_as::<_, (Uuid, String, chrono::DateTime<Utc>, chrono::DateTime<Utc>, String, i64, Option<String>, Option<String>, Option<String>, Option<Uuid>, Option<String>)>(
+            "SELECT id, status, created_at, updated_at, file_name, file_size, webhook_url, webhook_secret, error_message, profile_id, result_url FROM jobs WHERE id = $1"
         )
         .bind(id)
         .fetch_optional(&self.pool)
         .await?;
 
-        if let Some((id, status, created_at, updated_at, file_name, file_size, webhook_url, error_message, profile_id, result_url)) = row {
+        if let Some((id, status, created_at, updated_at, file_name, file_size, webhook_url, webhook_secret, error_message, profile_id, result_url)) = row {
             let status = match status.as_str() {
                 "pending" => JobStatus::Pending,
                 "processing" => JobStatus::Processing,
@@ -134,6 +154,7 @@ impl Database {
                 file_name,
                 file_size: file_size as u64,
                 webhook_url,
+                webhook_secret,
                 error_message,
                 profile_id,
                 result_url,
@@ -207,6 +228,27 @@ impl Database {
         Ok(())
     }
 
+    /// Put a failed job back to pending, clearing its error and result.
+    /// False when the job doesn't exist or isn't failed.
+    pub async fn reset_failed_job(&self, id: Uuid) -> Result<bool, sqlx::Error> {
+        let now = Utc::now();
+
+        let result = sqlx::query(
+            r#"
+            UPDATE jobs SET status = $1, updated_at = $2, error_message = NULL, result_url = NULL
+            WHERE id = $3 AND status = $4
+            "#
+        )
+        .bind(JobStatus::Pending.to_string())
+        .bind(now)
+        .bind(id)
+        .bind(JobStatus::Failed.to_string())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
     pub async fn delete_job(&self, id: Uuid) -> Result<bool, sqlx::Error> {
         let result = sqlx::query("DELETE FROM jobs WHERE id = $1")
             .bind(id)
@@ -216,6 +258,51 @@ impl Database {
         Ok(result.rows_affected() > 0)
     }
 
+    pub async fn record_webhook_attempt(
+        &self,
+        job_id: Uuid,
+        webhook_url: &str,
+        attempt: &DeliveryAttempt,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO webhook_deliveries (id, job_id, webhook_url, attempt, status_code, error, attempted_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#
+        )
+        .bind(Uuid::new_v4())
+        .bind(job_id)
+        .bind(webhook_url)
+        .bind(attempt.attempt as i32)
+        .bind(attempt.status_code.map(i32::from))
+        .bind(&attempt.error)
+        .bind(attempt.attempted_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// A job's webhook delivery attempts, oldest first.
+    pub async fn list_webhook_attempts(&self, job_id: Uuid) -> Result<Vec<DeliveryAttempt>, sqlx::Error> {
+        let rows = sqlx::query_as::<_, (i32, chrono::DateTime<Utc>, Option<i32>, Option<String>)>(
+            "SELECT attempt, attempted_at, status_code, error FROM webhook_deliveries WHERE job_id = $1 ORDER BY attempted_at, attempt"
+        )
+        .bind(job_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(attempt, attempted_at, status_code, error)| DeliveryAttempt {
+                attempt: attempt as u32,
+                attempted_at,
+                status_code: status_code.map(|code| code as u16),
+                error,
+            })
+            .collect())
+    }
+
     pub async fn create_profile(&self, request: CreateProfileRequest) -> Result<ConversionProfile, sqlx::Error> {
         let id = Uuid::new_v4();
         let now = Utc::now();
@@ -7,10 +7,19 @@ pub mod auth;
 pub mod auth_routes;
 pub mod storage;
 pub mod rate_limit;
+pub mod worker;
 #[cfg(test)]
 mod auth_tests;
 #[cfg(test)]
 mod routes_tests;
+#[cfg(test)]
+mod storage_tests;
+#[cfg(test)]
+mod test_pdf;
+#[cfg(test)]
+mod webhook_tests;
+#[cfg(test)]
+mod worker_tests;
 
 pub use models::*;
 pub use routes::*;
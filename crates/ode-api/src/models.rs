@@ -34,6 +34,9 @@ pub struct JobMetadata {
     pub file_name: String,
     pub file_size: u64,
     pub webhook_url: Option<String>,
+    /// Key for signing this job's webhook payloads; never sent back out.
+    #[serde(skip)]
+    pub webhook_secret: Option<String>,
     pub error_message: Option<String>,
     pub profile_id: Option<Uuid>,
     pub result_url: Option<String>,
@@ -89,6 +92,23 @@ impl Default for ConversionOptions {
     }
 }
 
+impl ConversionOptions {
+    /// The core conversion settings these options ask for: the page range
+    /// and the output scale from `dpi` and `zoom`. The rest stay at their
+    /// defaults.
+    pub fn to_conversion_config(&self) -> ode_core::ConversionConfig {
+        let mut config = ode_core::ConversionConfig::default();
+        if let Some((first, last)) = self.page_range {
+            config.page_range = (first as usize, last as usize);
+        }
+        if let Some(dpi) = self.dpi {
+            config = config.with_target_dpi(dpi);
+        }
+        config.output_scale *= self.zoom.unwrap_or(1.0);
+        config
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ConversionProfile {
     pub id: Uuid,
@@ -125,6 +145,45 @@ pub struct ConvertResponse {
     pub result_url: Option<String>,
 }
 
+/// The `multipart/form-data` body of `convert-sync` and `extract-images`.
+#[derive(Debug, ToSchema)]
+pub struct PdfUpload {
+    /// The PDF to convert.
+    #[schema(value_type = String, format = Binary)]
+    pub file: Vec<u8>,
+}
+
+/// The `multipart/form-data` body of `extract-sync`.
+#[derive(Debug, ToSchema)]
+pub struct ConfiguredPdfUpload {
+    /// The PDF to convert.
+    #[schema(value_type = String, format = Binary)]
+    pub file: Vec<u8>,
+    /// `ConversionOptions` as JSON; the defaults when absent.
+    pub config: Option<String>,
+}
+
+/// The `multipart/form-data` body of `convert-batch`: one `file` part per PDF.
+#[derive(Debug, ToSchema)]
+pub struct BatchUpload {
+    /// The PDFs to convert, each converted on its own.
+    #[schema(value_type = Vec<String>, format = Binary)]
+    pub file: Vec<Vec<u8>>,
+}
+
+/// How one file of a `convert-batch` request went.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BatchFileResult {
+    pub filename: String,
+    /// `completed` or `failed`.
+    pub status: JobStatus,
+    /// The converted document as a single self-contained HTML file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub html: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct StatusResponse {
     pub job_id: Uuid,
@@ -145,6 +204,26 @@ pub struct DocumentResponse {
     pub created_at: DateTime<Utc>,
 }
 
+/// One page of a converted document, for viewers that load pages as they
+/// are scrolled to.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DocumentPageResponse {
+    pub job_id: Uuid,
+    /// 1-based.
+    pub page_number: usize,
+    /// Pages in the job's converted output.
+    pub page_count: usize,
+    /// The page as an HTML fragment, laid out like the pages `convert-sync`
+    /// streams, loading its images from the job's stored files.
+    pub html_content: String,
+    /// The rules every page uses, for the viewer to load once, loading the
+    /// fonts from the job's stored files.
+    pub css_content: String,
+}
+
+/// The body POSTed to a job's webhook. Every field is always present, with
+/// `null` for a missing `result_url` or `error_message`, so receivers can
+/// rely on one shape.
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct WebhookPayload {
     pub job_id: Uuid,
@@ -152,9 +231,7 @@ pub struct WebhookPayload {
     pub created_at: DateTime<Utc>,
     pub completed_at: DateTime<Utc>,
     pub file_name: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub result_url: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub error_message: Option<String>,
     pub duration_ms: i64,
 }
@@ -204,7 +281,25 @@ pub struct HealthResponse {
     pub timestamp: DateTime<Utc>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+/// Whether the service can reach each dependency it needs in full mode.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ReadinessResponse {
+    /// `ready` when every dependency answered, `not_ready` otherwise.
+    pub status: String,
+    pub database: DependencyStatus,
+    pub redis: DependencyStatus,
+    pub storage: DependencyStatus,
+}
+
+/// The outcome of pinging one dependency.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DependencyStatus {
+    pub healthy: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq, Hash)]
 #[serde(rename_all = "lowercase")]
 pub enum Role {
     Admin,
@@ -1,8 +1,8 @@
 use axum::{
     extract::{Request, State},
-    http::{HeaderMap, StatusCode},
+    http::{header::RETRY_AFTER, HeaderMap, HeaderValue, StatusCode},
     middleware::Next,
-    response::Response,
+    response::{IntoResponse, Response},
 };
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -10,6 +10,9 @@ use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 use tracing::{warn, trace};
 
+use crate::auth::{ApiKeyId, Claims};
+use crate::models::Role;
+
 #[derive(Clone)]
 pub struct RateLimitEntry {
     pub count: u32,
@@ -33,26 +36,33 @@ impl RateLimiter {
     }
 
     pub async fn check_rate_limit(&self, key: &str) -> Result<bool, String> {
+        Ok(self.check(key).await.allowed)
+    }
+
+    /// Count a request from `key` and report whether it fits in the window.
+    pub async fn check(&self, key: &str) -> RateLimitDecision {
         let mut entries = self.entries.write().await;
         let now = Instant::now();
 
-        if let Some(entry) = entries.get_mut(key) {
-            if now.duration_since(entry.window_start) >= self.window_duration {
-                entry.count = 1;
-                entry.window_start = now;
-                Ok(true)
-            } else if entry.count < self.max_requests {
-                entry.count += 1;
-                Ok(true)
-            } else {
-                Ok(false)
-            }
-        } else {
-            entries.insert(key.to_string(), RateLimitEntry {
-                count: 1,
-                window_start: now,
-            });
-            Ok(true)
+        let entry = entries.entry(key.to_string()).or_insert(RateLimitEntry {
+            count: 0,
+            window_start: now,
+        });
+        if now.duration_since(entry.window_start) >= self.window_duration {
+            entry.count = 0;
+            entry.window_start = now;
+        }
+        let allowed = entry.count < self.max_requests;
+        if allowed {
+            entry.count += 1;
+        }
+
+        let elapsed = now.duration_since(entry.window_start);
+        RateLimitDecision {
+            allowed,
+            limit: self.max_requests,
+            remaining: self.max_requests.saturating_sub(entry.count),
+            reset_after: self.window_duration.saturating_sub(elapsed),
         }
     }
 
@@ -65,17 +75,52 @@ impl RateLimiter {
     }
 }
 
+/// The outcome of counting one request against a client's allowance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitDecision {
+    pub allowed: bool,
+    /// Requests allowed per window.
+    pub limit: u32,
+    /// Requests left in the current window.
+    pub remaining: u32,
+    /// Time until the window resets.
+    pub reset_after: Duration,
+}
+
 #[derive(Clone)]
 pub struct RateLimitState {
+    /// Limits unauthenticated clients, keyed by `extract_client_key`.
     pub rate_limiter: RateLimiter,
+    /// Limits authenticated clients by the role of their user, keyed by
+    /// API key or user id.
+    pub tier_limiters: HashMap<Role, RateLimiter>,
 }
 
 impl RateLimitState {
     pub fn new() -> Self {
         Self {
             rate_limiter: RateLimiter::new(100, 60),
+            tier_limiters: HashMap::from([
+                (Role::Viewer, RateLimiter::new(100, 60)),
+                (Role::Developer, RateLimiter::new(300, 60)),
+                (Role::Admin, RateLimiter::new(1000, 60)),
+            ]),
         }
     }
+
+    /// Allow clients whose user has `role` `max_requests` per window.
+    pub fn with_tier(mut self, role: Role, max_requests: u32, window_duration_secs: u64) -> Self {
+        self.tier_limiters
+            .insert(role, RateLimiter::new(max_requests, window_duration_secs));
+        self
+    }
+
+    /// The limiter for a client with `role`; the unauthenticated one for
+    /// clients without a role or roles without a tier.
+    pub fn limiter_for(&self, role: Option<&Role>) -> &RateLimiter {
+        role.and_then(|role| self.tier_limiters.get(role))
+            .unwrap_or(&self.rate_limiter)
+    }
 }
 
 impl Default for RateLimitState {
@@ -98,25 +143,46 @@ pub fn extract_client_key(headers: &HeaderMap) -> String {
     }
 }
 
+/// The counter key of a client the auth middleware has identified: its API
+/// key, or the user its token was issued to.
+pub fn authenticated_client_key(req: &Request) -> Option<String> {
+    if let Some(ApiKeyId(key_id)) = req.extensions().get::<ApiKeyId>() {
+        Some(format!("api_key:{}", key_id))
+    } else {
+        req.extensions()
+            .get::<Claims>()
+            .map(|claims| format!("user:{}", claims.user_id))
+    }
+}
+
+/// Count the request against its client's allowance. Behind the auth
+/// middleware, clients are limited by the tier of their role; otherwise by
+/// `extract_client_key`. Responses carry `X-RateLimit-Limit` and
+/// `X-RateLimit-Remaining`, and rejections a `Retry-After`.
 pub async fn rate_limit_middleware(
     State(state): State<RateLimitState>,
     req: Request,
     next: Next,
-) -> Result<Response, StatusCode> {
-    let client_key = extract_client_key(req.headers());
+) -> Response {
+    let role = req.extensions().get::<Role>();
+    let (client_key, limiter) = match authenticated_client_key(&req) {
+        Some(key) => (key, state.limiter_for(role)),
+        None => (extract_client_key(req.headers()), &state.rate_limiter),
+    };
 
-    match state.rate_limiter.check_rate_limit(&client_key).await {
-        Ok(true) => {
-            trace!("Rate limit check passed for: {}", client_key);
-            Ok(next.run(req).await)
-        }
-        Ok(false) => {
-            warn!("Rate limit exceeded for: {}", client_key);
-            Err(StatusCode::TOO_MANY_REQUESTS)
-        }
-        Err(e) => {
-            warn!("Rate limit check error: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
-    }
-}
\ No newline at end of file
+    let decision = limiter.check(&client_key).await;
+    let mut response = if decision.allowed {
+        trace!("Rate limit check passed for: {}", client_key);
+        next.run(req).await
+    } else {
+        warn!("Rate limit exceeded for: {}", client_key);
+        let mut response = StatusCode::TOO_MANY_REQUESTS.into_response();
+        let retry_after = decision.reset_after.as_secs_f64().ceil() as u64;
+        response.headers_mut().insert(RETRY_AFTER, HeaderValue::from(retry_after));
+        response
+    };
+    let headers = response.headers_mut();
+    headers.insert("X-RateLimit-Limit", HeaderValue::from(decision.limit));
+    headers.insert("X-RateLimit-Remaining", HeaderValue::from(decision.remaining));
+    response
+}
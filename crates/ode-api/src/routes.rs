@@ -10,6 +10,7 @@ use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 use std::sync::Arc;
 use tokio::sync::Mutex;
+use tokio_stream::StreamExt;
 use validator::Validate;
 
 use crate::{
@@ -17,13 +18,20 @@ use crate::{
     db::Database,
     task_queue::TaskQueue,
     webhooks::WebhookService,
-    storage::S3Storage,
+    storage::Storage,
     rate_limit::RateLimitState,
     models::{
+        BatchFileResult,
+        BatchUpload,
+        ConfiguredPdfUpload,
         ConvertResponse,
+        PdfUpload,
         StatusResponse,
         DocumentResponse,
+        DocumentPageResponse,
         HealthResponse,
+        ReadinessResponse,
+        DependencyStatus,
         ConversionOptions,
         ConversionProfile,
         CreateProfileRequest,
@@ -38,7 +46,7 @@ pub struct AppState {
     pub db: Database,
     pub task_queue: Arc<Mutex<TaskQueue>>,
     pub webhook_service: WebhookService,
-    pub storage: Arc<S3Storage>,
+    pub storage: Arc<dyn Storage>,
     pub auth_state: AuthState,
     pub rate_limit_state: RateLimitState,
 }
@@ -49,20 +57,36 @@ pub struct AppState {
         submit_conversion,
         get_status,
         get_document,
+        get_document_archive,
+        get_document_page,
+        convert_sync,
+        extract_images,
+        extract_sync,
+        convert_batch,
         delete_job,
+        retry_job,
         health_check,
+        readiness_check,
         create_profile,
         get_profile,
         list_profiles,
         update_profile,
         delete_profile,
+        debug_content,
     ),
     components(
         schemas(
+            BatchFileResult,
+            BatchUpload,
+            ConfiguredPdfUpload,
             ConvertResponse,
+            PdfUpload,
             StatusResponse,
             DocumentResponse,
+            DocumentPageResponse,
             HealthResponse,
+            ReadinessResponse,
+            DependencyStatus,
             ConversionOptions,
             ConversionProfile,
             CreateProfileRequest,
@@ -77,8 +101,47 @@ pub struct AppState {
 )]
 pub struct ApiDoc;
 
-pub fn create_swagger_router() -> Router<AppState> {
-    let api_docs = ApiDoc::openapi();
+/// The endpoints standalone mode serves, which need no database, queue or
+/// storage.
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        convert_sync,
+        extract_sync,
+        extract_images,
+        convert_batch,
+        health_check,
+    ),
+    components(
+        schemas(
+            BatchFileResult,
+            BatchUpload,
+            ConfiguredPdfUpload,
+            PdfUpload,
+            HealthResponse,
+            ConversionOptions,
+            JobStatus,
+            ApiError,
+        )
+    ),
+    tags(
+        (name = "ode", description = "ODE PDF Conversion API")
+    )
+)]
+pub struct StandaloneApiDoc;
+
+pub fn create_swagger_router<S: Clone + Send + Sync + 'static>() -> Router<S> {
+    swagger_router(ApiDoc::openapi())
+}
+
+/// Swagger UI for standalone mode, describing only its endpoints.
+pub fn create_standalone_swagger_router<S: Clone + Send + Sync + 'static>() -> Router<S> {
+    swagger_router(StandaloneApiDoc::openapi())
+}
+
+fn swagger_router<S: Clone + Send + Sync + 'static>(
+    api_docs: utoipa::openapi::OpenApi,
+) -> Router<S> {
     SwaggerUi::new("/docs")
         .url("/api-docs/openapi.json", api_docs)
         .into()
@@ -87,20 +150,23 @@ pub fn create_swagger_router() -> Router<AppState> {
 pub fn create_router() -> Router<AppState> {
     Router::new()
         .route("/v1/convert", post(submit_conversion))
-        .route("/v1/status/:id", get(get_status))
-        .route("/v1/documents/:id", get(get_document))
-        .route("/v1/jobs/:id", delete(delete_job))
+        .route("/v1/status/{id}", get(get_status))
+        .route("/v1/documents/{id}", get(get_document))
+        .route("/v1/documents/{id}/archive", get(get_document_archive))
+        .route("/v1/documents/{id}/pages/{page}", get(get_document_page))
+        .route("/v1/jobs/{id}", delete(delete_job))
+        .route("/v1/jobs/{id}/retry", post(retry_job))
         .route("/v1/profiles", post(create_profile))
         .route("/v1/profiles", get(list_profiles))
-        .route("/v1/profiles/:id", get(get_profile))
-        .route("/v1/profiles/:id", patch(update_profile))
-        .route("/v1/profiles/:id", delete(delete_profile))
+        .route("/v1/profiles/{id}", get(get_profile))
+        .route("/v1/profiles/{id}", patch(update_profile))
+        .route("/v1/profiles/{id}", delete(delete_profile))
 }
 
 #[utoipa::path(
     post,
     path = "/v1/convert",
-    request_body(content = Option<String>, description = "Multipart form data with 'file' field", content_type = "multipart/form-data"),
+    request_body(content = Option<String>, description = "Multipart form data with a 'file' field and optional 'config', 'profile_id', 'webhook_url' and 'webhook_secret' fields", content_type = "multipart/form-data"),
     responses(
         (status = 202, description = "Job accepted for processing", body = ConvertResponse),
         (status = 400, description = "Invalid request", body = ApiError),
@@ -117,6 +183,7 @@ pub async fn submit_conversion(
     let mut config: Option<ConversionOptions> = None;
     let mut profile_id: Option<Uuid> = None;
     let mut webhook_url: Option<String> = None;
+    let mut webhook_secret: Option<String> = None;
 
     while let Some(field) = multipart.next_field().await
         .map_err(|e| {
@@ -172,6 +239,16 @@ pub async fn submit_conversion(
                         )
                     })?);
             },
+            "webhook_secret" => {
+                webhook_secret = Some(field.text().await
+                    .map_err(|e| {
+                        (
+                            StatusCode::BAD_REQUEST,
+                            Json(ApiError::new("webhook_parse_error", format!("Failed to read webhook secret: {}", e)))
+                        )
+                    })?)
+                    .filter(|secret| !secret.is_empty());
+            },
             _ => {}
         }
     }
@@ -246,6 +323,7 @@ pub async fn submit_conversion(
         &file_data,
         config_json,
         webhook_url.clone(),
+        webhook_secret,
         final_profile_id,
     ).await
     .map_err(|e| {
@@ -387,6 +465,307 @@ pub async fn get_document(
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/v1/documents/{id}/archive",
+    params(
+        ("id" = Uuid, Path, description = "Job ID")
+    ),
+    responses(
+        (status = 200, description = "ZIP of index.html and its font and image files", content_type = "application/zip"),
+        (status = 404, description = "Job not found or not completed", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "ode"
+)]
+pub async fn get_document_archive(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
+    let metadata = state.db.get_job(id).await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiError::new("database_error", format!("Failed to fetch job: {}", e)))
+            )
+        })?
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                Json(ApiError::new("job_not_found", format!("Job {} not found", id)))
+            )
+        })?;
+
+    if metadata.status != JobStatus::Completed {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ApiError::new("job_not_ready", format!("Job {} is not completed", id)))
+        ));
+    }
+
+    let pdf_data = state.db.get_job_pdf_data(id).await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiError::new("database_error", format!("Failed to fetch job: {}", e)))
+            )
+        })?
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                Json(ApiError::new("result_not_found", "Conversion result not available"))
+            )
+        })?;
+
+    let config = state.db.get_job_config(id).await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiError::new("database_error", format!("Failed to fetch job: {}", e)))
+            )
+        })?
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                Json(ApiError::new("result_not_found", "Conversion result not available"))
+            )
+        })?;
+    let options: ConversionOptions = serde_json::from_value(config).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiError::new("config_parse_error", format!("Invalid job config: {}", e)))
+        )
+    })?;
+
+    // The bundle's font data isn't persisted, so rebuild it from the stored
+    // PDF, converted the way the job was
+    let archive = tokio::task::spawn_blocking(move || document_archive(&pdf_data, &options))
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiError::new("internal_error", format!("Archive task failed: {}", e)))
+            )
+        })??;
+
+    Ok((
+        [
+            (axum::http::header::CONTENT_TYPE, "application/zip".to_string()),
+            (
+                axum::http::header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", archive_file_name(&metadata.file_name)),
+            ),
+        ],
+        archive,
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/documents/{id}/pages/{page}",
+    params(
+        ("id" = Uuid, Path, description = "Job ID"),
+        ("page" = usize, Path, description = "Page number, 1-based")
+    ),
+    responses(
+        (status = 200, description = "Page retrieved", body = DocumentPageResponse),
+        (status = 404, description = "Job not found or not completed, or no such page", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "ode"
+)]
+pub async fn get_document_page(
+    State(state): State<AppState>,
+    Path((id, page)): Path<(Uuid, usize)>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
+    let database_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiError::new("database_error", format!("Failed to fetch job: {}", e)))
+        )
+    };
+    let metadata = state.db.get_job(id).await
+        .map_err(database_error)?
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                Json(ApiError::new("job_not_found", format!("Job {} not found", id)))
+            )
+        })?;
+
+    if metadata.status != JobStatus::Completed {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ApiError::new("job_not_ready", format!("Job {} is not completed", id)))
+        ));
+    }
+
+    let storage_error = |e: crate::storage::StorageError| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiError::new("storage_error", format!("Failed to read result: {}", e)))
+        )
+    };
+    let prefix = format!("jobs/{}/", id);
+    let pages: StoredPages = state.storage.get_object(&format!("{}pages/pages.json", prefix)).await
+        .map_err(storage_error)?
+        .and_then(|json| serde_json::from_slice(&json).ok())
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                Json(ApiError::new("result_not_found", "Conversion result not available"))
+            )
+        })?;
+    let html = state.storage.get_object(&format!("{}pages/{}.html", prefix, page)).await
+        .map_err(storage_error)?
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                Json(ApiError::new(
+                    "page_not_found",
+                    format!("Page {} is not in the converted output", page),
+                )),
+            )
+        })?;
+
+    // The page loads its fonts and images from the job's stored files
+    let mut html_content = String::from_utf8_lossy(&html).into_owned();
+    let mut css_content = pages.css;
+    for asset in &pages.assets {
+        let url = state.storage
+            .presigned_url(&format!("{}{}", prefix, asset), PAGE_ASSET_URL_EXPIRY).await
+            .map_err(storage_error)?;
+        html_content = repoint_asset(&html_content, asset, &url);
+        css_content = repoint_asset(&css_content, asset, &url);
+    }
+
+    Ok((
+        StatusCode::OK,
+        Json(DocumentPageResponse {
+            job_id: id,
+            page_number: page,
+            page_count: pages.page_count,
+            html_content,
+            css_content,
+        }),
+    ))
+}
+
+/// How long the asset URLs in a served page stay valid.
+const PAGE_ASSET_URL_EXPIRY: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// What a job stores under `pages/pages.json` for serving its pages one
+/// at a time.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub(crate) struct StoredPages {
+    /// Pages in the converted output.
+    pub page_count: usize,
+    /// The rules every page uses, loading fonts by filename.
+    pub css: String,
+    /// The font and image files the pages and CSS load by filename.
+    pub assets: Vec<String>,
+}
+
+/// The files a job stores alongside its `index.html` for
+/// `GET /v1/documents/{id}/pages/{page}`: each page as an HTML fragment
+/// under `pages/{n}.html`, and the [`StoredPages`] they share.
+pub(crate) fn stored_page_files(bundle: &ode_core::OutputBundle) -> Vec<(String, Vec<u8>)> {
+    let mut files: Vec<(String, Vec<u8>)> = bundle
+        .pages
+        .iter()
+        .map(|page| {
+            let html = sync_page_html(page);
+            (format!("pages/{}.html", page.page_number), html.into_bytes())
+        })
+        .collect();
+    let mut assets: Vec<String> = Vec::new();
+    let fonts = bundle.fonts.iter().map(|f| &f.filename);
+    for name in fonts.chain(bundle.images.iter().map(|i| &i.filename)) {
+        if !assets.contains(name) {
+            assets.push(name.clone());
+        }
+    }
+    let pages = StoredPages {
+        page_count: bundle.pages.len(),
+        css: bundle.css.clone(),
+        assets,
+    };
+    files.push(("pages/pages.json".to_string(), serde_json::to_vec(&pages).unwrap_or_default()));
+    files
+}
+
+/// `text` with the `src` attributes and CSS `url()`s loading `filename`
+/// pointed at `url` instead.
+pub(crate) fn repoint_asset(text: &str, filename: &str, url: &str) -> String {
+    text.replace(&format!("src=\"{}\"", filename), &format!("src=\"{}\"", url))
+        .replace(&format!("url('{}')", filename), &format!("url('{}')", url))
+}
+
+/// Convert `pdf_data` with `options` and pack `index.html` with its font
+/// and image files into a ZIP archive.
+pub(crate) fn document_archive(
+    pdf_data: &[u8],
+    options: &ConversionOptions,
+) -> Result<Vec<u8>, (StatusCode, Json<ApiError>)> {
+    let bundle = ode_core::convert_pdf(pdf_data, &options.to_conversion_config()).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiError::new("conversion_error", format!("PDF conversion failed: {}", e))),
+        )
+    })?;
+
+    zip_files(bundle.to_asset_files())
+}
+
+/// Pack `(name, data)` pairs into a ZIP archive.
+fn zip_files(
+    files: impl IntoIterator<Item = (String, Vec<u8>)>,
+) -> Result<Vec<u8>, (StatusCode, Json<ApiError>)> {
+    use std::io::Write;
+
+    let internal_error = |e: &dyn std::fmt::Display| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiError::new("archive_error", format!("Failed to build archive: {}", e))),
+        )
+    };
+
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+    for (name, data) in files {
+        zip.start_file(name, options).map_err(|e| internal_error(&e))?;
+        zip.write_all(&data).map_err(|e| internal_error(&e))?;
+    }
+    let cursor = zip.finish().map_err(|e| internal_error(&e))?;
+    Ok(cursor.into_inner())
+}
+
+/// `report.pdf` becomes `report.zip`, limited to characters that are safe in
+/// a quoted `Content-Disposition` filename.
+pub(crate) fn archive_file_name(file_name: &str) -> String {
+    format!("{}.zip", safe_file_stem(file_name))
+}
+
+/// `report.pdf` becomes `report`, limited to characters that are safe in a
+/// quoted `Content-Disposition` filename or a ZIP entry name.
+fn safe_file_stem(file_name: &str) -> String {
+    let stem = file_name
+        .rsplit_once('.')
+        .map(|(stem, _)| stem)
+        .unwrap_or(file_name);
+    let safe: String = stem
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ' '))
+        .collect();
+    let safe = safe.trim();
+    if safe.is_empty() {
+        "document".to_string()
+    } else {
+        safe.to_string()
+    }
+}
+
 #[utoipa::path(
     get,
     path = "/health",
@@ -404,6 +783,60 @@ pub async fn health_check() -> impl IntoResponse {
     (StatusCode::OK, Json(response))
 }
 
+/// How long `/health/ready` waits for each dependency to answer.
+const READINESS_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+#[utoipa::path(
+    get,
+    path = "/health/ready",
+    responses(
+        (status = 200, description = "The database, Redis and storage are all reachable", body = ReadinessResponse),
+        (status = 503, description = "At least one dependency is unreachable", body = ReadinessResponse)
+    ),
+    tag = "ode"
+)]
+pub async fn readiness_check(State(state): State<AppState>) -> impl IntoResponse {
+    let (database, redis, storage) = tokio::join!(
+        dependency_status(state.db.ping()),
+        dependency_status(async { state.task_queue.lock().await.ping().await }),
+        dependency_status(state.storage.ping()),
+    );
+    readiness_response(database, redis, storage)
+}
+
+/// Await a dependency's ping, giving up after `READINESS_TIMEOUT`.
+async fn dependency_status<E: std::fmt::Display>(
+    ping: impl std::future::Future<Output = Result<(), E>>,
+) -> DependencyStatus {
+    let error = match tokio::time::timeout(READINESS_TIMEOUT, ping).await {
+        Ok(Ok(())) => None,
+        Ok(Err(e)) => Some(e.to_string()),
+        Err(_) => Some("timed out".to_string()),
+    };
+    DependencyStatus { healthy: error.is_none(), error }
+}
+
+/// 200 when every dependency is healthy, 503 otherwise.
+pub(crate) fn readiness_response(
+    database: DependencyStatus,
+    redis: DependencyStatus,
+    storage: DependencyStatus,
+) -> (StatusCode, Json<ReadinessResponse>) {
+    let ready = database.healthy && redis.healthy && storage.healthy;
+    let (status, label) = if ready {
+        (StatusCode::OK, "ready")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "not_ready")
+    };
+    let response = ReadinessResponse {
+        status: label.to_string(),
+        database,
+        redis,
+        storage,
+    };
+    (status, Json(response))
+}
+
 pub async fn root() -> impl IntoResponse {
     Json(serde_json::json!({
         "name": "ODE - Oxidized Document Engine",
@@ -414,17 +847,20 @@ pub async fn root() -> impl IntoResponse {
             "convert": "POST /v1/convert",
             "status": "GET /v1/status/:id",
             "document": "GET /v1/documents/:id",
+            "document_page": "GET /v1/documents/:id/pages/:page",
             "register": "POST /auth/register",
             "login": "POST /auth/login"
         }
     }))
 }
 
-pub async fn convert_sync(
-    mut multipart: Multipart,
-) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
+/// Read the `file` field of a multipart upload, which must be a PDF, and
+/// the text of its `config` field if it has one.
+async fn read_pdf_upload(
+    multipart: &mut Multipart,
+) -> Result<(Vec<u8>, Option<String>), (StatusCode, Json<ApiError>)> {
     let mut file_data = Vec::new();
-    let mut _file_name = String::new();
+    let mut config = None;
 
     while let Some(field) = multipart.next_field().await
         .map_err(|e| {
@@ -433,12 +869,16 @@ pub async fn convert_sync(
     {
         let name = field.name().unwrap_or("").to_string();
         if name == "file" {
-            _file_name = field.file_name().unwrap_or("upload.pdf").to_string();
             file_data = field.bytes().await
                 .map_err(|e| {
                     (StatusCode::BAD_REQUEST, Json(ApiError::new("file_read_error", format!("Failed to read file: {}", e))))
                 })?
                 .to_vec();
+        } else if name == "config" {
+            config = Some(field.text().await
+                .map_err(|e| {
+                    (StatusCode::BAD_REQUEST, Json(ApiError::new("config_parse_error", format!("Failed to read config: {}", e))))
+                })?);
         }
     }
 
@@ -450,53 +890,510 @@ pub async fn convert_sync(
         return Err((StatusCode::UNSUPPORTED_MEDIA_TYPE, Json(ApiError::new("invalid_file", "Not a valid PDF file"))));
     }
 
-    let config = ode_core::ConversionConfig::default();
-    let result = ode_core::convert_pdf(&file_data, &config)
-        .map_err(|e| {
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiError::new("conversion_error", format!("PDF conversion failed: {}", e))))
-        })?;
+    Ok((file_data, config))
+}
 
-    let mut combined_html = String::new();
-    let mut combined_css = String::new();
+/// How long a `convert-sync` request may keep a blocking thread rendering.
+const SYNC_CONVERSION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+/// Most bytes of fonts and images a sync conversion's output may carry.
+const SYNC_MAX_OUTPUT_BYTES: usize = 256 * 1024 * 1024;
 
-    for page in &result.pages {
-        if !page.css.is_empty() {
-            combined_css.push_str(&page.css);
-            combined_css.push('\n');
+#[utoipa::path(
+    post,
+    path = "/v1/convert-sync",
+    request_body(content = PdfUpload, description = "Multipart form data with a 'file' field", content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "The converted document as HTML, streamed a page at a time and closed by a JSON conversion summary", content_type = "text/html"),
+        (status = 400, description = "Invalid request", body = ApiError),
+        (status = 408, description = "Conversion timed out", body = ApiError),
+        (status = 413, description = "The output's fonts and images would exceed the server's limit", body = ApiError),
+        (status = 415, description = "Unsupported media type", body = ApiError),
+        (status = 422, description = "A PDF that can't be read", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "ode"
+)]
+pub async fn convert_sync(
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
+    let (file_data, _) = read_pdf_upload(&mut multipart).await?;
+
+    // Render on a blocking thread and stream each page out as soon as it is
+    // done, so large documents don't have to be held in memory as one string.
+    let config = ode_core::ConversionConfig {
+        timeout: Some(SYNC_CONVERSION_TIMEOUT),
+        max_output_bytes: Some(SYNC_MAX_OUTPUT_BYTES),
+        ..Default::default()
+    };
+    let shared_css = ode_core::shared_css(&config);
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<Result<SyncMessage, ode_core::OdeError>>(4);
+    tokio::task::spawn_blocking(move || {
+        let start_tx = tx.clone();
+        let page_tx = tx.clone();
+        let mut summary = ode_core::ConversionSummary::default();
+        let mut finished = None;
+        let result = ode_core::convert_pdf_each(
+            &file_data,
+            &config,
+            |bundle| {
+                let language = bundle.language.clone();
+                let _ = start_tx.blocking_send(Ok(SyncMessage::Started { language }));
+            },
+            |page| {
+                summary.record_page(&page);
+                page_tx.blocking_send(Ok(SyncMessage::Page(page)))
+                    .map_err(|_| ode_core::OdeError::RenderError("Client disconnected".to_string()))
+            },
+            |bundle| finished = Some(bundle),
+        );
+        match result {
+            Ok(()) => {
+                if let Some(bundle) = finished {
+                    summary.record_document(&bundle);
+                }
+                let _ = tx.blocking_send(Ok(SyncMessage::Finished(summary)));
+            }
+            Err(e) => {
+                let _ = tx.blocking_send(Err(e));
+            }
+        }
+    });
+
+    // Wait for the first page so failures before it still get a proper error response
+    let head = sync_messages_before_first_page(&mut rx)
+        .await
+        .map_err(sync_conversion_error)?;
+
+    let body = tokio_stream::iter(head.into_iter().map(Ok))
+        .chain(tokio_stream::wrappers::ReceiverStream::new(rx))
+        .map(move |msg| {
+            msg.map(|message| sync_message_html(&message, &shared_css))
+                .map_err(std::io::Error::other)
+        });
+
+    Ok((
+        [(axum::http::header::CONTENT_TYPE, "text/html; charset=utf-8")],
+        axum::body::Body::from_stream(body),
+    ))
+}
+
+/// The messages of a `convert-sync` conversion up to and including its
+/// first page, or its end if it has none, or the error it failed with
+/// before getting that far. The status of the response is chosen once
+/// these are in, as it is sent ahead of the streamed pages.
+pub(crate) async fn sync_messages_before_first_page(
+    rx: &mut tokio::sync::mpsc::Receiver<Result<SyncMessage, ode_core::OdeError>>,
+) -> Result<Vec<SyncMessage>, ode_core::OdeError> {
+    let mut head = Vec::new();
+    while let Some(message) = rx.recv().await {
+        let message = message?;
+        let opening = matches!(message, SyncMessage::Started { .. });
+        head.push(message);
+        if !opening {
+            break;
         }
     }
+    Ok(head)
+}
+
+/// The response for a `convert-sync` conversion that failed before its
+/// first page: 408 when it ran out of time, 413 when its output grew too
+/// large, 400 when the upload isn't a PDF, 422 when it is one that can't be
+/// read, 500 otherwise.
+pub(crate) fn sync_conversion_error(e: ode_core::OdeError) -> (StatusCode, Json<ApiError>) {
+    use ode_core::ParseErrorKind;
 
-    combined_html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"UTF-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<style>\n");
-    combined_html.push_str("* { margin:0; padding:0; box-sizing:border-box; }\n");
-    combined_html.push_str("body { background:#f0f0f0; padding:20px 0; }\n");
-    combined_html.push_str(".page-wrapper { width:100%; max-width:1480px; margin:20px auto; }\n");
-    combined_html.push_str(".page { transform-origin:top left; box-shadow:0 2px 8px rgba(0,0,0,0.15); }\n");
-    combined_html.push_str(&combined_css);
-    if !result.css.is_empty() {
-        combined_html.push_str(&result.css);
+    match e {
+        ode_core::OdeError::Timeout(msg) => {
+            (StatusCode::REQUEST_TIMEOUT, Json(ApiError::new("conversion_timeout", msg)))
+        }
+        e @ ode_core::OdeError::OutputTooLarge { .. } => {
+            (StatusCode::PAYLOAD_TOO_LARGE, Json(ApiError::new("output_too_large", e.to_string())))
+        }
+        ode_core::OdeError::PdfParseError { kind, message } => {
+            let (status, code) = match kind {
+                ParseErrorKind::NotPdf => (StatusCode::BAD_REQUEST, "invalid_file"),
+                ParseErrorKind::Encrypted => (StatusCode::UNPROCESSABLE_ENTITY, "encrypted_pdf"),
+                ParseErrorKind::UnsupportedFilter(_) => {
+                    (StatusCode::UNPROCESSABLE_ENTITY, "unsupported_pdf")
+                }
+                _ => (StatusCode::UNPROCESSABLE_ENTITY, "malformed_pdf"),
+            };
+            (status, Json(ApiError::new(code, message)))
+        }
+        e => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiError::new("conversion_error", format!("PDF conversion failed: {}", e))),
+        ),
     }
-    combined_html.push_str("\n</style>\n</head>\n<body>\n");
+}
 
-    for page in &result.pages {
-        let bg = page.background_color.as_deref().unwrap_or("white");
-        // Wrap each page in a container that scales it to fit the viewport
-        combined_html.push_str(&format!(
-            "<div class=\"page-wrapper\" style=\"aspect-ratio:{}/{};\"><div class=\"page\" id=\"page-{}\" style=\"width:{}px;height:{}px;position:relative;background:{};overflow:hidden;transform:scale(var(--s));\" data-w=\"{}\">\n",
-            page.width, page.height, page.page_number, page.width, page.height, bg, page.width
+#[utoipa::path(
+    post,
+    path = "/v1/extract-sync",
+    request_body(content = ConfiguredPdfUpload, description = "Multipart form data with a 'file' field and an optional 'config' field of ConversionOptions JSON", content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "The conversion's output bundle: pages with their sizes and text spans, and font and image metadata without their bytes", content_type = "application/json"),
+        (status = 400, description = "Invalid request or config", body = ApiError),
+        (status = 408, description = "Conversion timed out", body = ApiError),
+        (status = 413, description = "The output's fonts and images would exceed the server's limit", body = ApiError),
+        (status = 415, description = "Unsupported media type", body = ApiError),
+        (status = 422, description = "A PDF that can't be read", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "ode"
+)]
+pub async fn extract_sync(
+    mut multipart: Multipart,
+) -> Result<Json<ode_core::OutputBundle>, (StatusCode, Json<ApiError>)> {
+    let (file_data, config) = read_pdf_upload(&mut multipart).await?;
+    let options: ConversionOptions = match config {
+        Some(config) => serde_json::from_str(&config).map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(ApiError::new("config_parse_error", format!("Invalid config: {}", e))),
+            )
+        })?,
+        None => ConversionOptions::default(),
+    };
+    if let Err(errors) = options.validate() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError::with_details(
+                "validation_error",
+                "Invalid conversion options",
+                errors.to_string()
+            ))
         ));
-        combined_html.push_str(&page.html);
-        combined_html.push_str("\n</div></div>\n");
     }
 
-    combined_html.push_str("<script>\n");
-    combined_html.push_str("function resize(){document.querySelectorAll('.page-wrapper').forEach(w=>{const p=w.querySelector('.page');const s=Math.min(1,w.clientWidth/parseFloat(p.dataset.w));p.style.setProperty('--s',s);w.style.height=(parseFloat(p.style.height)*s)+'px';});}\n");
-    combined_html.push_str("window.addEventListener('resize',resize);resize();\n");
-    combined_html.push_str("</script>\n");
-    combined_html.push_str("</body>\n</html>");
+    // Only the spans are wanted, so the pages skip building their HTML
+    let config = ode_core::ConversionConfig {
+        timeout: Some(SYNC_CONVERSION_TIMEOUT),
+        max_output_bytes: Some(SYNC_MAX_OUTPUT_BYTES),
+        output_format: ode_core::config::OutputFormat::Json,
+        ..options.to_conversion_config()
+    };
+    let bundle = tokio::task::spawn_blocking(move || ode_core::convert_pdf(&file_data, &config))
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiError::new("internal_error", format!("Extraction task failed: {}", e)))
+            )
+        })?
+        .map_err(sync_conversion_error)?;
+    Ok(Json(bundle))
+}
+
+/// Most `file` parts a `convert-batch` request may carry.
+const MAX_BATCH_FILES: usize = 20;
+/// Most bytes the files of a `convert-batch` request may add up to.
+const MAX_BATCH_BYTES: usize = 32 * 1024 * 1024;
+
+#[utoipa::path(
+    post,
+    path = "/v1/convert-batch",
+    request_body(content = BatchUpload, description = "Multipart form data with one or more 'file' fields", content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "One result per file in upload order, or with 'Accept: application/zip' a ZIP with a folder of output files (or an error.txt) per file", body = [BatchFileResult]),
+        (status = 400, description = "Invalid request", body = ApiError),
+        (status = 413, description = "Too many files or too many bytes in the batch", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "ode"
+)]
+pub async fn convert_batch(
+    headers: axum::http::HeaderMap,
+    mut multipart: Multipart,
+) -> Result<axum::response::Response, (StatusCode, Json<ApiError>)> {
+    let files = read_batch_upload(&mut multipart).await?;
+    let wants_zip = headers
+        .get(axum::http::header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("application/zip"));
+
+    tokio::task::spawn_blocking(move || batch_response(files, wants_zip))
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiError::new("internal_error", format!("Batch task failed: {}", e)))
+            )
+        })?
+}
+
+/// Read every `file` field of a multipart upload with its file name,
+/// enforcing the batch limits as the files come in.
+async fn read_batch_upload(
+    multipart: &mut Multipart,
+) -> Result<Vec<(String, Vec<u8>)>, (StatusCode, Json<ApiError>)> {
+    let too_large = |message: String| {
+        (StatusCode::PAYLOAD_TOO_LARGE, Json(ApiError::new("batch_too_large", message)))
+    };
+    let mut files = Vec::new();
+    let mut total_bytes = 0;
+
+    while let Some(field) = multipart.next_field().await
+        .map_err(|e| {
+            (StatusCode::BAD_REQUEST, Json(ApiError::new("invalid_request", format!("Failed to parse multipart: {}", e))))
+        })?
+    {
+        if field.name() != Some("file") {
+            continue;
+        }
+        if files.len() == MAX_BATCH_FILES {
+            return Err(too_large(format!("A batch may hold at most {} files", MAX_BATCH_FILES)));
+        }
+        let file_name = field
+            .file_name()
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("document-{}.pdf", files.len() + 1));
+        let data = field.bytes().await
+            .map_err(|e| {
+                (StatusCode::BAD_REQUEST, Json(ApiError::new("file_read_error", format!("Failed to read file: {}", e))))
+            })?;
+        total_bytes += data.len();
+        if total_bytes > MAX_BATCH_BYTES {
+            return Err(too_large(format!(
+                "The files of a batch may add up to at most {} MiB",
+                MAX_BATCH_BYTES / (1024 * 1024)
+            )));
+        }
+        files.push((file_name, data.to_vec()));
+    }
+
+    if files.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, Json(ApiError::new("empty_file", "No file provided"))));
+    }
+    Ok(files)
+}
+
+/// Convert each file of a batch on its own, so one bad file doesn't fail
+/// the others, and answer with JSON results or a ZIP archive.
+pub(crate) fn batch_response(
+    files: Vec<(String, Vec<u8>)>,
+    wants_zip: bool,
+) -> Result<axum::response::Response, (StatusCode, Json<ApiError>)> {
+    let config = ode_core::ConversionConfig {
+        timeout: Some(SYNC_CONVERSION_TIMEOUT),
+        max_output_bytes: Some(SYNC_MAX_OUTPUT_BYTES),
+        ..Default::default()
+    };
+    let converted = files.into_iter().map(|(file_name, data)| {
+        let result = if is_valid_pdf(&data) {
+            ode_core::convert_pdf(&data, &config)
+                .map_err(|e| format!("PDF conversion failed: {}", e))
+        } else {
+            Err("Not a valid PDF file".to_string())
+        };
+        (file_name, result)
+    });
+
+    if !wants_zip {
+        let results: Vec<BatchFileResult> = converted
+            .map(|(filename, result)| match result {
+                Ok(bundle) => BatchFileResult {
+                    filename,
+                    status: JobStatus::Completed,
+                    html: Some(bundle.to_single_file_html()),
+                    error: None,
+                },
+                Err(error) => BatchFileResult {
+                    filename,
+                    status: JobStatus::Failed,
+                    html: None,
+                    error: Some(error),
+                },
+            })
+            .collect();
+        return Ok(Json(results).into_response());
+    }
+
+    // One folder per file, named after it and numbered when names repeat
+    let mut folders = std::collections::HashSet::new();
+    let mut entries = Vec::new();
+    for (file_name, result) in converted {
+        let stem = safe_file_stem(&file_name);
+        let mut folder = stem.clone();
+        for n in 2.. {
+            if folders.insert(folder.clone()) {
+                break;
+            }
+            folder = format!("{}-{}", stem, n);
+        }
+        match result {
+            Ok(bundle) => entries.extend(
+                bundle
+                    .to_asset_files()
+                    .into_iter()
+                    .map(|(name, data)| (format!("{}/{}", folder, name), data)),
+            ),
+            Err(error) => entries.push((format!("{}/error.txt", folder), error.into_bytes())),
+        }
+    }
+    let archive = zip_files(entries)?;
+    Ok((
+        [
+            (axum::http::header::CONTENT_TYPE, "application/zip"),
+            (axum::http::header::CONTENT_DISPOSITION, "attachment; filename=\"batch.zip\""),
+        ],
+        archive,
+    )
+        .into_response())
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/extract-images",
+    request_body(content = PdfUpload, description = "Multipart form data with 'file' field", content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "ZIP of the page images and a manifest.json listing their pages and placements", content_type = "application/zip"),
+        (status = 400, description = "Invalid request", body = ApiError),
+        (status = 415, description = "Unsupported media type", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "ode"
+)]
+pub async fn extract_images(
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
+    let (file_data, _) = read_pdf_upload(&mut multipart).await?;
+
+    let archive = tokio::task::spawn_blocking(move || images_archive(&file_data))
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiError::new("internal_error", format!("Extraction task failed: {}", e)))
+            )
+        })??;
+
+    Ok((
+        [
+            (axum::http::header::CONTENT_TYPE, "application/zip"),
+            (axum::http::header::CONTENT_DISPOSITION, "attachment; filename=\"images.zip\""),
+        ],
+        archive,
+    ))
+}
+
+/// Extract the images of `pdf_data` into a ZIP archive with one file per
+/// placement and a `manifest.json` describing each file.
+pub(crate) fn images_archive(pdf_data: &[u8]) -> Result<Vec<u8>, (StatusCode, Json<ApiError>)> {
+    #[derive(serde::Serialize)]
+    struct ManifestEntry<'a> {
+        file: &'a str,
+        #[serde(flatten)]
+        image: &'a ode_core::ExtractedImage,
+    }
+
+    let config = ode_core::ConversionConfig::default();
+    let images = ode_core::extract_images(pdf_data, &config).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiError::new("extraction_error", format!("Image extraction failed: {}", e))),
+        )
+    })?;
+
+    let names: Vec<String> = images
+        .iter()
+        .enumerate()
+        .map(|(i, image)| image_file_name(i, image))
+        .collect();
+    let manifest: Vec<ManifestEntry> = names
+        .iter()
+        .zip(&images)
+        .map(|(file, image)| ManifestEntry { file, image })
+        .collect();
+    let manifest = serde_json::to_vec_pretty(&manifest).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiError::new("archive_error", format!("Failed to build manifest: {}", e))),
+        )
+    })?;
+
+    let files = names.iter().cloned().zip(images.into_iter().map(|image| image.data));
+    zip_files(files.chain([("manifest.json".to_string(), manifest)]))
+}
+
+/// `page-1-0-Im0.png` for the first image, drawn on page 1 from resource
+/// `/Im0`. The index keeps repeated placements of one image apart.
+pub(crate) fn image_file_name(index: usize, image: &ode_core::ExtractedImage) -> String {
+    let name: String = image
+        .name
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+        .collect();
+    format!("page-{}-{}-{}.{}", image.page_index + 1, index, name, image.format)
+}
+
+fn sync_document_head(shared_css: &str, language: Option<&str>) -> String {
+    let mut head = String::new();
+    head.push_str("<!DOCTYPE html>\n");
+    head.push_str(&format!("<html{}>\n", ode_core::lang_attribute(language)));
+    head.push_str("<head>\n<meta charset=\"UTF-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<style>\n");
+    head.push_str("* { margin:0; padding:0; box-sizing:border-box; }\n");
+    head.push_str("body { background:#f0f0f0; padding:20px 0; }\n");
+    head.push_str(".page-wrapper { width:100%; max-width:1480px; margin:20px auto; }\n");
+    head.push_str(".page { transform-origin:top left; box-shadow:0 2px 8px rgba(0,0,0,0.15); }\n");
+    head.push_str(shared_css);
+    head.push_str("\n</style>\n</head>\n<body>\n");
+    head
+}
+
+/// What the blocking conversion task streams to a `convert-sync` response.
+pub(crate) enum SyncMessage {
+    /// Sent once the document is parsed, before any page; opens the document.
+    Started { language: Option<String> },
+    Page(ode_core::RenderedPage),
+    /// Sent once every page is out; closes the document.
+    Finished(ode_core::ConversionSummary),
+}
+
+fn sync_message_html(message: &SyncMessage, shared_css: &str) -> String {
+    match message {
+        SyncMessage::Started { language } => sync_document_head(shared_css, language.as_deref()),
+        SyncMessage::Page(page) => sync_page_html(page),
+        SyncMessage::Finished(summary) => sync_document_tail(summary),
+    }
+}
+
+/// Close the document, embedding the summary as JSON for callers that want
+/// the numbers without parsing the pages.
+fn sync_document_tail(summary: &ode_core::ConversionSummary) -> String {
+    let json = serde_json::to_string(summary)
+        .unwrap_or_default()
+        .replace("</", "<\\/");
+    format!(
+        "<script type=\"application/json\" id=\"conversion-summary\">{}</script>\n{}",
+        json, SYNC_DOCUMENT_TAIL
+    )
+}
 
-    Ok(axum::response::Html(combined_html))
+fn sync_page_html(page: &ode_core::RenderedPage) -> String {
+    let bg = page.background_color.as_deref().unwrap_or("white");
+    // Wrap each page in a container that scales it to fit the viewport
+    let mut html = format!(
+        "<div class=\"page-wrapper\" style=\"aspect-ratio:{}/{};\"><div class=\"page\" id=\"page-{}\" style=\"width:{}px;height:{}px;position:relative;background:{};overflow:hidden;transform:scale(var(--s));\" data-w=\"{}\">\n",
+        page.width, page.height, page.page_number, page.width, page.height, bg, page.width
+    );
+    if !page.css.is_empty() {
+        html.push_str(&format!("<style>\n{}\n</style>\n", page.css));
+    }
+    html.push_str(&page.standalone_html());
+    html.push_str("\n</div></div>\n");
+    html
 }
 
+const SYNC_DOCUMENT_TAIL: &str = concat!(
+    "<script>\n",
+    "function resize(){document.querySelectorAll('.page-wrapper').forEach(w=>{const p=w.querySelector('.page');const s=Math.min(1,w.clientWidth/parseFloat(p.dataset.w));p.style.setProperty('--s',s);w.style.height=(parseFloat(p.style.height)*s)+'px';});}\n",
+    "window.addEventListener('resize',resize);resize();\n",
+    "</script>\n",
+    "</body>\n</html>",
+);
+
 pub async fn web_ui() -> impl IntoResponse {
     axum::response::Html(include_str!("ui.html"))
 }
@@ -557,6 +1454,90 @@ pub async fn delete_job(
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/v1/jobs/{id}/retry",
+    params(
+        ("id" = Uuid, Path, description = "Job ID")
+    ),
+    responses(
+        (status = 202, description = "Failed job queued again", body = ConvertResponse),
+        (status = 404, description = "Job not found", body = ApiError),
+        (status = 409, description = "Job has not failed", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "ode"
+)]
+pub async fn retry_job(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
+    let job_metadata = state.db.get_job(id).await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiError::new("database_error", format!("Failed to fetch job: {}", e)))
+            )
+        })?
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                Json(ApiError::new("job_not_found", format!("Job {} not found", id)))
+            )
+        })?;
+    if let Some(conflict) = retry_conflict(id, &job_metadata.status) {
+        return Err(conflict);
+    }
+
+    // Only one of two concurrent retries gets to reset the job
+    let reset = state.db.reset_failed_job(id).await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiError::new("database_error", format!("Failed to reset job: {}", e)))
+            )
+        })?;
+    if !reset {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(ApiError::new("job_not_failed", format!("Job {} is no longer failed", id)))
+        ));
+    }
+
+    {
+        let mut queue = state.task_queue.lock().await;
+        queue.enqueue_job(id).await
+            .map_err(|e| {
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(ApiError::new("queue_error", format!("Failed to enqueue job: {}", e)))
+                )
+            })?;
+    }
+
+    let response = ConvertResponse {
+        job_id: id,
+        status: JobStatus::Pending,
+        result_url: None,
+    };
+
+    Ok((StatusCode::ACCEPTED, Json(response)))
+}
+
+/// The 409 for retrying a job in `status`, `None` when it has failed and
+/// may be retried.
+pub(crate) fn retry_conflict(
+    id: Uuid,
+    status: &JobStatus,
+) -> Option<(StatusCode, Json<ApiError>)> {
+    let message = match status {
+        JobStatus::Failed => return None,
+        JobStatus::Pending | JobStatus::Processing => format!("Job {} is still {}", id, status),
+        JobStatus::Completed => format!("Job {} has already completed", id),
+    };
+    Some((StatusCode::CONFLICT, Json(ApiError::new("job_not_failed", message))))
+}
+
 #[utoipa::path(
     post,
     path = "/v1/profiles",
@@ -734,4 +1715,84 @@ pub async fn delete_profile(
             Json(ApiError::new("profile_not_found", format!("Profile {} not found", id)))
         ))
     }
-}
\ No newline at end of file
+}
+/// Whether the developer-only debug routes are mounted (`ODE_DEBUG_ROUTES=1`).
+pub fn debug_routes_enabled() -> bool {
+    matches!(
+        std::env::var("ODE_DEBUG_ROUTES").as_deref(),
+        Ok("1") | Ok("true")
+    )
+}
+
+/// Routes for diagnosing conversions. Only mounted when
+/// `debug_routes_enabled()`, and behind JWT auth like the job routes.
+pub fn create_debug_router() -> Router<AppState> {
+    Router::new()
+        .route("/v1/debug/content/{job_id}/{page}", get(debug_content))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/debug/content/{job_id}/{page}",
+    params(
+        ("job_id" = Uuid, Path, description = "Job ID"),
+        ("page" = usize, Path, description = "1-based page number")
+    ),
+    responses(
+        (status = 200, description = "Decoded content stream operators, one per line", body = String, content_type = "text/plain"),
+        (status = 404, description = "Job or page not found", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "ode"
+)]
+pub async fn debug_content(
+    State(state): State<AppState>,
+    Path((job_id, page)): Path<(Uuid, usize)>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
+    let database_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiError::new("database_error", format!("Failed to fetch job: {}", e)))
+        )
+    };
+    let job_not_found = || {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiError::new("job_not_found", format!("Job {} not found", job_id)))
+        )
+    };
+    // Looked up like the other job routes before any of its data is read
+    state.db.get_job(job_id).await
+        .map_err(database_error)?
+        .ok_or_else(job_not_found)?;
+    let pdf_data = state.db.get_job_pdf_data(job_id).await
+        .map_err(database_error)?
+        .ok_or_else(job_not_found)?;
+
+    let text = tokio::task::spawn_blocking(move || page_operators_text(&pdf_data, page))
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiError::new("internal_error", format!("Content decoding task failed: {}", e)))
+            )
+        })??;
+
+    Ok(([(axum::http::header::CONTENT_TYPE, "text/plain; charset=utf-8")], text))
+}
+
+pub(crate) fn page_operators_text(
+    pdf_data: &[u8],
+    page: usize,
+) -> Result<String, (StatusCode, Json<ApiError>)> {
+    let config = ode_core::ConversionConfig::default();
+    ode_core::page_content_operators(pdf_data, &config, page).map_err(|e| match e {
+        ode_core::OdeError::ConfigError(msg) => {
+            (StatusCode::NOT_FOUND, Json(ApiError::new("page_not_found", msg)))
+        }
+        e => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiError::new("conversion_error", format!("Failed to decode page: {}", e))),
+        ),
+    })
+}
@@ -1,6 +1,57 @@
 #[cfg(test)]
 mod integration_tests {
-    use crate::routes::is_valid_pdf;
+    use crate::routes::{
+        archive_file_name, convert_batch, convert_sync, create_router, document_archive,
+        extract_sync, image_file_name, images_archive, is_valid_pdf, page_operators_text,
+        readiness_response, repoint_asset, retry_conflict, stored_page_files,
+        sync_conversion_error, sync_messages_before_first_page, StoredPages, SyncMessage,
+    };
+    use crate::test_pdf::{one_page_pdf, pdf_with_catalog, pdf_with_page};
+    use axum::{body::Body, http::Request, routing::post, Json, Router};
+    use tower::ServiceExt;
+
+    fn multipart_request(pdf: &[u8]) -> Request<Body> {
+        let boundary = "odeboundary";
+        let mut body = format!(
+            "--{boundary}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"test.pdf\"\r\nContent-Type: application/pdf\r\n\r\n"
+        )
+        .into_bytes();
+        body.extend_from_slice(pdf);
+        body.extend_from_slice(format!("\r\n--{boundary}--\r\n").as_bytes());
+
+        Request::builder()
+            .method("POST")
+            .uri("/v1/convert-sync")
+            .header("content-type", format!("multipart/form-data; boundary={boundary}"))
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    /// A `convert-batch` request with one `file` part per `(filename, data)`.
+    fn batch_request(files: &[(&str, &[u8])], accept: Option<&str>) -> Request<Body> {
+        let boundary = "odeboundary";
+        let mut body = Vec::new();
+        for (filename, data) in files {
+            body.extend_from_slice(
+                format!(
+                    "--{boundary}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{filename}\"\r\n\r\n"
+                )
+                .as_bytes(),
+            );
+            body.extend_from_slice(data);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(format!("--{boundary}--\r\n").as_bytes());
+
+        let mut request = Request::builder()
+            .method("POST")
+            .uri("/v1/convert-batch")
+            .header("content-type", format!("multipart/form-data; boundary={boundary}"));
+        if let Some(accept) = accept {
+            request = request.header("accept", accept);
+        }
+        request.body(Body::from(body)).unwrap()
+    }
 
     #[test]
     fn test_is_valid_pdf() {
@@ -15,4 +66,444 @@ mod integration_tests {
         assert!(is_valid_pdf(b"%PDF-2.0"));
         assert!(!is_valid_pdf(b"\x00\x00\x00\x00\x00"));
     }
+
+    #[test]
+    fn test_create_router_accepts_every_path() {
+        // axum panics on a path it can't parse, such as a pre-0.8 `:id` capture
+        let _ = create_router();
+    }
+
+    #[tokio::test]
+    async fn test_convert_sync_streams_pages_in_order() {
+        let app = Router::new().route("/v1/convert-sync", post(convert_sync));
+        let pdf = b"%PDF-1.4\n1 0 obj\n<<>>\nendobj\ntrailer\n<<>>\n%%EOF";
+
+        let response = app.oneshot(multipart_request(pdf)).await.unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let html = String::from_utf8(body.to_vec()).unwrap();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.ends_with("</html>"));
+        let first = html.find("id=\"page-1\"").unwrap();
+        let second = html.find("id=\"page-2\"").unwrap();
+        assert!(first < second);
+    }
+
+    #[tokio::test]
+    async fn test_convert_sync_ends_with_summary() {
+        let app = Router::new().route("/v1/convert-sync", post(convert_sync));
+        let pdf = one_page_pdf(b"BT /F1 12 Tf 72 700 Td (Hello) Tj ET");
+
+        let response = app.oneshot(multipart_request(&pdf)).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let html = String::from_utf8(body.to_vec()).unwrap();
+
+        let marker = "<script type=\"application/json\" id=\"conversion-summary\">";
+        let start = html.find(marker).unwrap() + marker.len();
+        let end = start + html[start..].find("</script>").unwrap();
+        let summary: ode_core::ConversionSummary = serde_json::from_str(&html[start..end]).unwrap();
+        assert_eq!(summary.page_count, 1);
+        assert_eq!(summary.span_count, 1);
+        assert!(html.ends_with("</html>"));
+    }
+
+    #[tokio::test]
+    async fn test_convert_sync_sets_the_document_language() {
+        let content = b"BT /F1 12 Tf 72 700 Td (Hallo) Tj /Span<</Lang(en-GB)>>BDC (Hi) Tj EMC ET";
+        let pdf = pdf_with_catalog("/Lang(de-DE)", "", content, &[]);
+        let app = Router::new().route("/v1/convert-sync", post(convert_sync));
+
+        let response = app.oneshot(multipart_request(&pdf)).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let html = String::from_utf8(body.to_vec()).unwrap();
+        assert!(html.contains("<html lang=\"de-DE\">"), "{}", html);
+        assert!(html.contains("<span lang=\"en-GB\""), "{}", html);
+
+        let app = Router::new().route("/v1/convert-sync", post(convert_sync));
+        let response = app.oneshot(multipart_request(&one_page_pdf(content))).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8(body.to_vec()).unwrap().contains("<html>\n"));
+    }
+
+    #[tokio::test]
+    async fn test_convert_sync_rejects_unparseable_pdf() {
+        let app = Router::new().route("/v1/convert-sync", post(convert_sync));
+        let response = app.oneshot(multipart_request(b"%PDF-\xff\xfe\n")).await.unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_convert_sync_waits_past_the_start_for_a_first_page_error() {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+        tx.send(Ok(SyncMessage::Started { language: None })).await.unwrap();
+        tx.send(Err(ode_core::OdeError::Timeout("page 1".into()))).await.unwrap();
+        let error = sync_messages_before_first_page(&mut rx).await.err().unwrap();
+        let (status, _) = sync_conversion_error(error);
+        assert_eq!(status, axum::http::StatusCode::REQUEST_TIMEOUT);
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+        tx.send(Ok(SyncMessage::Started { language: None })).await.unwrap();
+        let summary = ode_core::ConversionSummary::default();
+        tx.send(Ok(SyncMessage::Finished(summary))).await.unwrap();
+        tx.send(Err(ode_core::OdeError::Timeout("never read".into()))).await.unwrap();
+        let head = sync_messages_before_first_page(&mut rx).await.unwrap();
+        assert_eq!(head.len(), 2);
+    }
+
+    #[test]
+    fn test_convert_sync_timeout_is_request_timeout() {
+        let timeout = ode_core::OdeError::Timeout("Conversion timed out on page 3".into());
+        let (status, Json(error)) = sync_conversion_error(timeout);
+        assert_eq!(status, axum::http::StatusCode::REQUEST_TIMEOUT);
+        assert_eq!(error.error, "conversion_timeout");
+        assert!(error.message.contains("page 3"));
+
+        let (status, _) = sync_conversion_error(ode_core::OdeError::RenderError("bad".into()));
+        assert_eq!(status, axum::http::StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[test]
+    fn test_output_too_large_is_payload_too_large() {
+        let too_large = ode_core::OdeError::OutputTooLarge { limit: 1024 };
+        let (status, Json(error)) = sync_conversion_error(too_large);
+        assert_eq!(status, axum::http::StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(error.error, "output_too_large");
+        assert!(error.message.contains("1024 bytes"));
+    }
+
+    #[test]
+    fn test_convert_sync_parse_errors_map_to_client_statuses() {
+        use ode_core::{OdeError, ParseErrorKind};
+
+        let (status, Json(error)) =
+            sync_conversion_error(OdeError::parse(ParseErrorKind::NotPdf, "Not a PDF file"));
+        assert_eq!(status, axum::http::StatusCode::BAD_REQUEST);
+        assert_eq!(error.error, "invalid_file");
+
+        let encrypted = OdeError::parse(ParseErrorKind::Encrypted, "AES encryption");
+        let (status, Json(error)) = sync_conversion_error(encrypted);
+        assert_eq!(status, axum::http::StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error.error, "encrypted_pdf");
+
+        let filter = ParseErrorKind::UnsupportedFilter("JBIG2Decode".into());
+        let (status, _) = sync_conversion_error(OdeError::parse(filter, "Unsupported filter"));
+        assert_eq!(status, axum::http::StatusCode::UNPROCESSABLE_ENTITY);
+
+        let (status, Json(error)) = sync_conversion_error(OdeError::malformed("Expected token"));
+        assert_eq!(status, axum::http::StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error.error, "malformed_pdf");
+    }
+
+    #[test]
+    fn test_standalone_api_doc_describes_its_multipart_endpoints() {
+        use crate::routes::{ApiDoc, StandaloneApiDoc};
+        use utoipa::OpenApi;
+
+        let doc = serde_json::to_value(StandaloneApiDoc::openapi()).unwrap();
+        let paths = doc["paths"].as_object().unwrap();
+        let mut documented: Vec<&str> = paths.keys().map(String::as_str).collect();
+        documented.sort();
+        assert_eq!(
+            documented,
+            [
+                "/health",
+                "/v1/convert-batch",
+                "/v1/convert-sync",
+                "/v1/extract-images",
+                "/v1/extract-sync"
+            ]
+        );
+        for (path, form) in [
+            ("/v1/convert-sync", "PdfUpload"),
+            ("/v1/extract-sync", "ConfiguredPdfUpload"),
+            ("/v1/convert-batch", "BatchUpload"),
+        ] {
+            let body = &paths[path]["post"]["requestBody"]["content"]["multipart/form-data"];
+            assert_eq!(body["schema"]["$ref"], format!("#/components/schemas/{}", form));
+        }
+        let upload = &doc["components"]["schemas"]["PdfUpload"]["properties"]["file"];
+        assert_eq!(upload["format"], "binary");
+
+        let full = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        assert!(full["paths"]["/v1/convert-sync"].is_object());
+    }
+
+    #[test]
+    fn test_only_failed_jobs_can_be_retried() {
+        use crate::models::JobStatus;
+
+        let id = uuid::Uuid::new_v4();
+        assert!(retry_conflict(id, &JobStatus::Failed).is_none());
+        for status in [JobStatus::Pending, JobStatus::Processing, JobStatus::Completed] {
+            let (code, Json(error)) = retry_conflict(id, &status).unwrap();
+            assert_eq!(code, axum::http::StatusCode::CONFLICT);
+            assert_eq!(error.error, "job_not_failed");
+        }
+    }
+
+    #[test]
+    fn test_readiness_fails_when_any_dependency_is_down() {
+        use crate::models::DependencyStatus;
+
+        let up = || DependencyStatus { healthy: true, error: None };
+        let (status, Json(response)) = readiness_response(up(), up(), up());
+        assert_eq!(status, axum::http::StatusCode::OK);
+        assert_eq!(response.status, "ready");
+
+        let down = DependencyStatus { healthy: false, error: Some("timed out".to_string()) };
+        let (status, Json(response)) = readiness_response(up(), down, up());
+        assert_eq!(status, axum::http::StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.status, "not_ready");
+        assert_eq!(response.redis.error.as_deref(), Some("timed out"));
+        assert!(response.database.healthy);
+    }
+
+    /// An `extract-sync` request with `pdf` and, when given, a `config` part.
+    fn extract_request(pdf: &[u8], config: Option<&str>) -> Request<Body> {
+        let boundary = "odeboundary";
+        let mut body = Vec::new();
+        if let Some(config) = config {
+            body.extend_from_slice(
+                format!("--{boundary}\r\nContent-Disposition: form-data; name=\"config\"\r\n\r\n{config}\r\n")
+                    .as_bytes(),
+            );
+        }
+        body.extend_from_slice(
+            format!("--{boundary}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"test.pdf\"\r\n\r\n")
+                .as_bytes(),
+        );
+        body.extend_from_slice(pdf);
+        body.extend_from_slice(format!("\r\n--{boundary}--\r\n").as_bytes());
+
+        Request::builder()
+            .method("POST")
+            .uri("/v1/extract-sync")
+            .header("content-type", format!("multipart/form-data; boundary={boundary}"))
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_extract_sync_returns_spans_as_json() {
+        let app = Router::new().route("/v1/extract-sync", post(extract_sync));
+        let pdf = one_page_pdf(b"BT /F1 12 Tf 72 700 Td (Hello) Tj ET");
+
+        let response = app.oneshot(extract_request(&pdf, Some(r#"{"zoom": 2.0}"#))).await.unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let bundle: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let page = &bundle["pages"][0];
+        assert_eq!(page["width"], 1224.0);
+        assert_eq!(page["html"], "");
+        assert_eq!(page["text_spans"][0]["text"], "Hello");
+        assert!(bundle["fonts"].as_array().unwrap().iter().all(|font| font.get("data").is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_extract_sync_rejects_a_bad_config() {
+        let app = Router::new().route("/v1/extract-sync", post(extract_sync));
+        let pdf = one_page_pdf(b"BT /F1 12 Tf 72 700 Td (Hello) Tj ET");
+
+        let response = app.oneshot(extract_request(&pdf, Some("{not json"))).await.unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::BAD_REQUEST);
+
+        let app = Router::new().route("/v1/extract-sync", post(extract_sync));
+        let response = app.oneshot(extract_request(&pdf, Some(r#"{"zoom": 50.0}"#))).await.unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_convert_batch_reports_each_file() {
+        let app = Router::new().route("/v1/convert-batch", post(convert_batch));
+        let pdf = one_page_pdf(b"BT /F1 12 Tf 72 700 Td (Batched) Tj ET");
+        let request = batch_request(&[("report.pdf", &pdf), ("notes.txt", b"plain text")], None);
+
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let results: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let results = results.as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0]["filename"], "report.pdf");
+        assert_eq!(results[0]["status"], "completed");
+        assert!(results[0]["html"].as_str().unwrap().contains("Batched"));
+        assert_eq!(results[1]["filename"], "notes.txt");
+        assert_eq!(results[1]["status"], "failed");
+        assert_eq!(results[1]["error"], "Not a valid PDF file");
+        assert!(results[1].get("html").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_convert_batch_zips_a_folder_per_file() {
+        use std::io::Read;
+
+        let app = Router::new().route("/v1/convert-batch", post(convert_batch));
+        let pdf = one_page_pdf(b"BT /F1 12 Tf 72 700 Td (Zipped) Tj ET");
+        let files: [(&str, &[u8]); 3] =
+            [("report.pdf", &pdf), ("report.pdf", &pdf), ("notes.txt", b"plain text")];
+
+        let response = app
+            .oneshot(batch_request(&files, Some("application/zip")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "application/zip");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+        let mut zip = zip::ZipArchive::new(std::io::Cursor::new(body.to_vec())).unwrap();
+        for folder in ["report", "report-2"] {
+            let mut index = String::new();
+            zip.by_name(&format!("{folder}/index.html"))
+                .unwrap()
+                .read_to_string(&mut index)
+                .unwrap();
+            assert!(index.contains("Zipped"));
+        }
+        let mut error = String::new();
+        zip.by_name("notes/error.txt").unwrap().read_to_string(&mut error).unwrap();
+        assert_eq!(error, "Not a valid PDF file");
+    }
+
+    #[tokio::test]
+    async fn test_convert_batch_limits_the_file_count() {
+        let app = Router::new().route("/v1/convert-batch", post(convert_batch));
+        let files = vec![("tiny.pdf", b"%PDF-1.4".as_slice()); 21];
+
+        let response = app.oneshot(batch_request(&files, None)).await.unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::PAYLOAD_TOO_LARGE);
+
+        let app = Router::new().route("/v1/convert-batch", post(convert_batch));
+        let response = app.oneshot(batch_request(&[], None)).await.unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_debug_content_lists_page_operators() {
+        let pdf = one_page_pdf(b"BT /F1 12 Tf 72 700 Td (Hello debug) Tj ET");
+
+        let text = page_operators_text(&pdf, 1).unwrap();
+        assert_eq!(text, "BT\n/F1 12 Tf\n72 700 Td\n(Hello debug) Tj\nET\n");
+
+        let (status, _) = page_operators_text(&pdf, 2).unwrap_err();
+        assert_eq!(status, axum::http::StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_document_archive_contains_index() {
+        use crate::models::ConversionOptions;
+        use std::io::Read;
+
+        let pdf = one_page_pdf(b"BT /F1 12 Tf 72 700 Td (Archived) Tj ET");
+        let index = |options: &ConversionOptions| {
+            let archive = document_archive(&pdf, options).unwrap();
+            let mut zip = zip::ZipArchive::new(std::io::Cursor::new(archive)).unwrap();
+            let mut index = String::new();
+            zip.by_name("index.html").unwrap().read_to_string(&mut index).unwrap();
+            index
+        };
+
+        let default = index(&ConversionOptions::default());
+        assert!(default.starts_with("<!DOCTYPE html>"));
+        assert!(default.contains("Archived"));
+        assert!(default.contains("width:612px"));
+
+        // Converted with the job's options
+        let zoomed = ConversionOptions {
+            zoom: Some(2.0),
+            ..ConversionOptions::default()
+        };
+        assert!(index(&zoomed).contains("width:1224px"));
+    }
+
+    #[test]
+    fn test_stored_pages_load_their_assets_from_the_jobs_files() {
+        let jpeg = b"\xFF\xD8\xFF\xE0fake jpeg\xFF\xD9";
+        let mut image = b"<</Type/XObject/Subtype/Image/Width 4/Height 2/ColorSpace/DeviceRGB\
+/BitsPerComponent 8/Filter/DCTDecode/Length 15>>\nstream\n"
+            .to_vec();
+        image.extend_from_slice(jpeg);
+        image.extend_from_slice(b"\nendstream");
+        let pdf = pdf_with_page(
+            "/Resources<</XObject<</Im0 5 0 R>>>>",
+            b"BT /F1 12 Tf 72 700 Td (Lazy) Tj ET q 40 0 0 20 10 10 cm /Im0 Do Q",
+            &[image],
+        );
+        let bundle = ode_core::convert_pdf(&pdf, &ode_core::ConversionConfig::default()).unwrap();
+
+        let files = stored_page_files(&bundle);
+        let names: Vec<&str> = files.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["pages/1.html", "pages/pages.json"]);
+        let html = String::from_utf8(files[0].1.clone()).unwrap();
+        assert!(html.contains("id=\"page-1\"") && html.contains("Lazy"));
+        assert!(!html.contains("<html"));
+        let pages: StoredPages = serde_json::from_slice(&files[1].1).unwrap();
+        assert_eq!(pages.page_count, 1);
+        assert!(pages.css.contains(".page span"));
+
+        let image = &bundle.images[0].filename;
+        assert_eq!(pages.assets, std::slice::from_ref(image));
+        let url = "https://storage.example/jobs/1/image";
+        let repointed = repoint_asset(&html, image, url);
+        assert!(repointed.contains(&format!("src=\"{}\"", url)));
+        assert!(!repointed.contains(&format!("src=\"{}\"", image)));
+        let css = repoint_asset("src: url('a.woff2') format('woff2');", "a.woff2", url);
+        assert_eq!(css, format!("src: url('{}') format('woff2');", url));
+    }
+
+    #[test]
+    fn test_images_archive_lists_images_in_manifest() {
+        use std::io::Read;
+
+        let jpeg = b"\xFF\xD8\xFF\xE0fake jpeg\xFF\xD9";
+        let mut image = b"<</Type/XObject/Subtype/Image/Width 4/Height 2/ColorSpace/DeviceRGB\
+/BitsPerComponent 8/Filter/DCTDecode/Length 15>>\nstream\n"
+            .to_vec();
+        image.extend_from_slice(jpeg);
+        image.extend_from_slice(b"\nendstream");
+        let pdf = pdf_with_page(
+            "/Resources<</XObject<</Im0 5 0 R>>>>",
+            b"q 40 0 0 20 10 10 cm /Im0 Do Q",
+            &[image],
+        );
+
+        let archive = images_archive(&pdf).unwrap();
+        let mut zip = zip::ZipArchive::new(std::io::Cursor::new(archive)).unwrap();
+        let mut manifest = String::new();
+        zip.by_name("manifest.json").unwrap().read_to_string(&mut manifest).unwrap();
+        let manifest: serde_json::Value = serde_json::from_str(&manifest).unwrap();
+        assert_eq!(manifest[0]["file"], "page-1-0-Im0.jpg");
+        assert_eq!(manifest[0]["page_index"], 0);
+        assert_eq!(manifest[0]["format"], "jpg");
+        assert_eq!(manifest[0]["width"], 40.0);
+
+        let mut data = Vec::new();
+        zip.by_name("page-1-0-Im0.jpg").unwrap().read_to_end(&mut data).unwrap();
+        assert_eq!(data, jpeg);
+    }
+
+    #[test]
+    fn test_image_file_name_drops_unsafe_characters() {
+        let image = ode_core::ExtractedImage {
+            page_index: 2,
+            name: "../Fig 1".to_string(),
+            format: "png".to_string(),
+            x: 0.0,
+            y: 0.0,
+            width: 1.0,
+            height: 1.0,
+            pixel_width: 1,
+            pixel_height: 1,
+            data: Vec::new(),
+        };
+        assert_eq!(image_file_name(4, &image), "page-3-4-Fig1.png");
+    }
+
+    #[test]
+    fn test_archive_file_name() {
+        assert_eq!(archive_file_name("report.pdf"), "report.zip");
+        assert_eq!(archive_file_name("q3 \"final\".PDF"), "q3 final.zip");
+        assert_eq!(archive_file_name("\"\";.pdf"), "document.zip");
+    }
 }
@@ -1,9 +1,57 @@
-use uuid::Uuid;
-use tracing::info;
-use std::error::Error;
+use async_trait::async_trait;
 use std::collections::HashMap;
+use std::error::Error;
+use std::path::{Component, Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
+use tracing::info;
+use uuid::Uuid;
+
+pub type StorageError = Box<dyn Error + Send + Sync>;
+
+/// Where job inputs and converted output are kept, as objects keyed by
+/// `/`-separated paths such as `jobs/{id}/index.html`.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Store a job's output files under `jobs/{id}/`, each keyed by its
+    /// relative path, and return the URL of `index.html` among them.
+    async fn store_bundle(
+        &self,
+        job_id: Uuid,
+        files: Vec<(String, Vec<u8>)>,
+    ) -> Result<String, StorageError>;
+
+    /// The object stored under `key`, if any.
+    async fn get_object(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError>;
+
+    /// Delete everything stored for a job.
+    async fn delete_job_assets(&self, job_id: Uuid) -> Result<(), StorageError>;
+
+    /// A URL the object under `key` can be fetched from without credentials
+    /// for `expires_in`.
+    async fn presigned_url(&self, key: &str, expires_in: Duration) -> Result<String, StorageError>;
+
+    /// Check the storage is reachable.
+    async fn ping(&self) -> Result<(), StorageError>;
+}
+
+/// The storage `ODE_STORAGE_BACKEND` selects: `local` keeps files in the
+/// directory `ODE_STORAGE_DIR` (`./ode-storage` by default), and anything
+/// else the S3 bucket `S3_BUCKET` (`ode-documents` by default).
+pub async fn storage_from_env() -> Result<Arc<dyn Storage>, StorageError> {
+    match std::env::var("ODE_STORAGE_BACKEND").as_deref() {
+        Ok("local") => {
+            let root =
+                std::env::var("ODE_STORAGE_DIR").unwrap_or_else(|_| "./ode-storage".to_string());
+            Ok(Arc::new(LocalStorage::new(root).await?))
+        }
+        _ => {
+            let bucket = std::env::var("S3_BUCKET").unwrap_or_else(|_| "ode-documents".to_string());
+            Ok(Arc::new(S3Storage::new(bucket).await?))
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct S3Storage {
@@ -12,8 +60,11 @@ pub struct S3Storage {
 }
 
 impl S3Storage {
-    pub async fn new(bucket: String) -> Result<Self, Box<dyn Error>> {
-        info!("Storage service initialized with bucket: {} (mock mode)", bucket);
+    pub async fn new(bucket: String) -> Result<Self, StorageError> {
+        info!(
+            "Storage service initialized with bucket: {} (mock mode)",
+            bucket
+        );
 
         Ok(Self {
             bucket,
@@ -21,34 +72,66 @@ impl S3Storage {
         })
     }
 
-    pub async fn store_html(&self, job_id: Uuid, content: Vec<u8>) -> Result<String, Box<dyn Error>> {
+    pub async fn store_html(&self, job_id: Uuid, content: Vec<u8>) -> Result<String, StorageError> {
         let key = format!("jobs/{}/output.html", job_id);
-        
+
         let mut data = self.mock_data.write().await;
         data.insert(key.clone(), content);
-        
-        let url = format!("https://{}.s3.amazonaws.com/{}", self.bucket, key);
+
+        let url = self.object_url(&key);
         info!("Stored HTML for job {} at {}", job_id, url);
 
         Ok(url)
     }
 
-    pub async fn store_pdf(&self, job_id: Uuid, content: Vec<u8>) -> Result<String, Box<dyn Error>> {
+    pub async fn store_pdf(&self, job_id: Uuid, content: Vec<u8>) -> Result<String, StorageError> {
         let key = format!("jobs/{}/input.pdf", job_id);
-        
+
         let mut data = self.mock_data.write().await;
         data.insert(key.clone(), content);
-        
-        let url = format!("https://{}.s3.amazonaws.com/{}", self.bucket, key);
+
+        let url = self.object_url(&key);
         info!("Stored PDF for job {} at {}", job_id, url);
 
         Ok(url)
     }
 
-    pub async fn delete_job_assets(&self, job_id: Uuid) -> Result<(), Box<dyn Error>> {
+    pub async fn get_file(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        self.get_object(key).await
+    }
+
+    fn object_url(&self, key: &str) -> String {
+        format!("https://{}.s3.amazonaws.com/{}", self.bucket, key)
+    }
+}
+
+#[async_trait]
+impl Storage for S3Storage {
+    async fn store_bundle(
+        &self,
+        job_id: Uuid,
+        files: Vec<(String, Vec<u8>)>,
+    ) -> Result<String, StorageError> {
+        let prefix = format!("jobs/{}/", job_id);
+        let mut data = self.mock_data.write().await;
+        for (name, content) in files {
+            data.insert(format!("{}{}", prefix, name), content);
+        }
+
+        let url = self.object_url(&format!("{}index.html", prefix));
+        info!("Stored output for job {} at {}", job_id, url);
+        Ok(url)
+    }
+
+    async fn get_object(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        let data = self.mock_data.read().await;
+        Ok(data.get(key).cloned())
+    }
+
+    async fn delete_job_assets(&self, job_id: Uuid) -> Result<(), StorageError> {
         let prefix = format!("jobs/{}/", job_id);
         let mut data = self.mock_data.write().await;
-        
+
         let mut deleted_count = 0;
         data.retain(|k, _| {
             if k.starts_with(&prefix) {
@@ -64,8 +147,110 @@ impl S3Storage {
         Ok(())
     }
 
-    pub async fn get_file(&self, key: &str) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
-        let data = self.mock_data.read().await;
-        Ok(data.get(key).cloned())
+    /// In mock mode nothing signs the URL; it only carries the expiry.
+    async fn presigned_url(&self, key: &str, expires_in: Duration) -> Result<String, StorageError> {
+        Ok(format!(
+            "{}?X-Amz-Expires={}",
+            self.object_url(key),
+            expires_in.as_secs()
+        ))
+    }
+
+    /// Check the bucket is reachable. In mock mode there is no bucket, so
+    /// this only waits for the in-memory store.
+    async fn ping(&self) -> Result<(), StorageError> {
+        let _data = self.mock_data.read().await;
+        Ok(())
+    }
+}
+
+/// Storage in a directory on local disk, for deployments without S3. Each
+/// object is a file at its key under the directory.
+#[derive(Clone)]
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    /// Use `root`, creating it if it doesn't exist.
+    pub async fn new(root: impl Into<PathBuf>) -> Result<Self, StorageError> {
+        let root = root.into();
+        tokio::fs::create_dir_all(&root).await?;
+        let root = tokio::fs::canonicalize(&root).await?;
+        info!(
+            "Storage service initialized in directory: {}",
+            root.display()
+        );
+        Ok(Self { root })
+    }
+
+    /// The file for `key`, refusing keys that would leave the directory.
+    fn object_path(&self, key: &str) -> Result<PathBuf, StorageError> {
+        let relative = Path::new(key);
+        if !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+        {
+            return Err(format!("Invalid storage key: {}", key).into());
+        }
+        Ok(self.root.join(relative))
+    }
+
+    fn object_url(&self, path: &Path) -> String {
+        format!("file://{}", path.display())
+    }
+}
+
+#[async_trait]
+impl Storage for LocalStorage {
+    async fn store_bundle(
+        &self,
+        job_id: Uuid,
+        files: Vec<(String, Vec<u8>)>,
+    ) -> Result<String, StorageError> {
+        let prefix = format!("jobs/{}/", job_id);
+        for (name, content) in files {
+            let path = self.object_path(&format!("{}{}", prefix, name))?;
+            if let Some(dir) = path.parent() {
+                tokio::fs::create_dir_all(dir).await?;
+            }
+            tokio::fs::write(&path, content).await?;
+        }
+
+        let url = self.object_url(&self.object_path(&format!("{}index.html", prefix))?);
+        info!("Stored output for job {} at {}", job_id, url);
+        Ok(url)
+    }
+
+    async fn get_object(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        match tokio::fs::read(self.object_path(key)?).await {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
     }
-}
\ No newline at end of file
+
+    async fn delete_job_assets(&self, job_id: Uuid) -> Result<(), StorageError> {
+        let dir = self.object_path(&format!("jobs/{}", job_id))?;
+        match tokio::fs::remove_dir_all(&dir).await {
+            Ok(()) => info!("Deleted assets for job {}", job_id),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        Ok(())
+    }
+
+    /// Files need no signing; the URL is the file's, whatever `expires_in`.
+    async fn presigned_url(
+        &self,
+        key: &str,
+        _expires_in: Duration,
+    ) -> Result<String, StorageError> {
+        Ok(self.object_url(&self.object_path(key)?))
+    }
+
+    async fn ping(&self) -> Result<(), StorageError> {
+        tokio::fs::metadata(&self.root).await?;
+        Ok(())
+    }
+}
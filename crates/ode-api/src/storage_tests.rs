@@ -0,0 +1,71 @@
+#[cfg(test)]
+mod backend_tests {
+    use crate::storage::{LocalStorage, S3Storage, Storage};
+    use std::time::Duration;
+    use uuid::Uuid;
+
+    fn bundle_files() -> Vec<(String, Vec<u8>)> {
+        vec![
+            ("index.html".to_string(), b"<html></html>".to_vec()),
+            ("fonts/f0.woff2".to_string(), b"wOF2".to_vec()),
+        ]
+    }
+
+    /// Store a bundle, read it back and delete it through `storage`.
+    async fn round_trip(storage: &dyn Storage) {
+        let job_id = Uuid::new_v4();
+        let url = storage.store_bundle(job_id, bundle_files()).await.unwrap();
+        assert!(
+            url.ends_with(&format!("jobs/{}/index.html", job_id)),
+            "{}",
+            url
+        );
+
+        let font_key = format!("jobs/{}/fonts/f0.woff2", job_id);
+        assert_eq!(
+            storage.get_object(&font_key).await.unwrap(),
+            Some(b"wOF2".to_vec())
+        );
+        let presigned = storage
+            .presigned_url(&font_key, Duration::from_secs(600))
+            .await
+            .unwrap();
+        assert!(presigned.contains(&font_key), "{}", presigned);
+
+        storage.delete_job_assets(job_id).await.unwrap();
+        assert_eq!(storage.get_object(&font_key).await.unwrap(), None);
+        // Deleting what is already gone is not an error
+        storage.delete_job_assets(job_id).await.unwrap();
+        storage.ping().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_s3_storage_round_trips_a_bundle() {
+        let storage = S3Storage::new("test-bucket".to_string()).await.unwrap();
+        round_trip(&storage).await;
+    }
+
+    #[tokio::test]
+    async fn test_local_storage_round_trips_a_bundle() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = LocalStorage::new(dir.path().join("objects")).await.unwrap();
+        round_trip(&storage).await;
+    }
+
+    #[tokio::test]
+    async fn test_local_storage_keeps_keys_inside_its_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("secret"), b"key").unwrap();
+        let storage = LocalStorage::new(dir.path().join("objects")).await.unwrap();
+
+        for key in ["../secret", "/etc/passwd", "jobs/../../secret"] {
+            assert!(storage.get_object(key).await.is_err(), "{}", key);
+        }
+        let escaping = vec![("../../secret".to_string(), b"overwritten".to_vec())];
+        assert!(storage
+            .store_bundle(Uuid::new_v4(), escaping)
+            .await
+            .is_err());
+        assert_eq!(std::fs::read(dir.path().join("secret")).unwrap(), b"key");
+    }
+}
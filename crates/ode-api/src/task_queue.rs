@@ -41,6 +41,12 @@ impl TaskQueue {
         }
     }
 
+    /// Send `PING` to check Redis is reachable.
+    pub async fn ping(&mut self) -> Result<(), redis::RedisError> {
+        let _: String = redis::cmd("PING").query_async(&mut self.conn).await?;
+        Ok(())
+    }
+
     pub async fn queue_length(&mut self) -> Result<usize, redis::RedisError> {
         let len: usize = self.conn.llen(QUEUE_NAME).await?;
         Ok(len)
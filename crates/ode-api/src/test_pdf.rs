@@ -0,0 +1,57 @@
+//! PDFs built in memory for the API tests.
+
+/// A one-page PDF whose page shows `content`.
+pub(crate) fn one_page_pdf(content: &[u8]) -> Vec<u8> {
+    pdf_with_page("", content, &[])
+}
+
+/// A one-page PDF whose page has `resources` and `content`, followed by
+/// `extra` objects numbered from 5.
+pub(crate) fn pdf_with_page(resources: &str, content: &[u8], extra: &[Vec<u8>]) -> Vec<u8> {
+    pdf_with_catalog("", resources, content, extra)
+}
+
+/// Like `pdf_with_page`, with `catalog` entries added to the catalog.
+pub(crate) fn pdf_with_catalog(
+    catalog: &str,
+    resources: &str,
+    content: &[u8],
+    extra: &[Vec<u8>],
+) -> Vec<u8> {
+    let mut objects: Vec<Vec<u8>> = vec![
+        format!("<</Type/Catalog/Pages 2 0 R{catalog}>>").into_bytes(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        format!("<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]{resources}/Contents 4 0 R>>")
+            .into_bytes(),
+    ];
+    let mut stream = format!("<</Length {}>>\nstream\n", content.len()).into_bytes();
+    stream.extend_from_slice(content);
+    stream.extend_from_slice(b"\nendstream");
+    objects.push(stream);
+    objects.extend_from_slice(extra);
+
+    let mut pdf = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::new();
+    for (i, body) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+        pdf.extend_from_slice(body);
+        pdf.extend_from_slice(b"\nendobj\n");
+    }
+    let xref_offset = pdf.len();
+    pdf.extend_from_slice(
+        format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes(),
+    );
+    for offset in offsets {
+        pdf.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    pdf.extend_from_slice(
+        format!(
+            "trailer\n<</Size {}/Root 1 0 R>>\nstartxref\n{}\n%%EOF",
+            objects.len() + 1,
+            xref_offset
+        )
+        .as_bytes(),
+    );
+    pdf
+}
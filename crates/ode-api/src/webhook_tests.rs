@@ -0,0 +1,133 @@
+#[cfg(test)]
+mod delivery_tests {
+    use crate::models::{JobStatus, WebhookPayload};
+    use crate::webhooks::{
+        sign_payload, WebhookConfig, WebhookError, WebhookService, SIGNATURE_HEADER,
+    };
+    use axum::{
+        http::{HeaderMap, StatusCode},
+        routing::post,
+        Router,
+    };
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// Requests a test receiver got: the signature header and the body.
+    type Received = Arc<Mutex<Vec<(Option<String>, String)>>>;
+
+    /// Serve a webhook receiver answering with `statuses` in turn, repeating
+    /// the last one, and return its URL.
+    async fn spawn_receiver(statuses: Vec<u16>) -> (String, Received) {
+        let received: Received = Arc::default();
+        let handler = {
+            let received = received.clone();
+            move |headers: HeaderMap, body: String| {
+                let received = received.clone();
+                let statuses = statuses.clone();
+                async move {
+                    let mut received = received.lock().unwrap();
+                    let signature = headers
+                        .get(SIGNATURE_HEADER)
+                        .map(|v| v.to_str().unwrap().to_string());
+                    received.push((signature, body));
+                    let status = statuses[(received.len() - 1).min(statuses.len() - 1)];
+                    StatusCode::from_u16(status).unwrap()
+                }
+            }
+        };
+        let app = Router::new().route("/hook", post(handler));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, received)
+    }
+
+    fn service(max_attempts: u32, secret: Option<&str>) -> WebhookService {
+        WebhookService::with_config(WebhookConfig {
+            max_attempts,
+            initial_backoff: Duration::from_millis(1),
+            secret: secret.map(str::to_string),
+        })
+    }
+
+    fn payload() -> WebhookPayload {
+        let now = chrono::Utc::now();
+        WebhookPayload {
+            job_id: uuid::Uuid::new_v4(),
+            status: JobStatus::Completed,
+            created_at: now,
+            completed_at: now,
+            file_name: "report.pdf".to_string(),
+            result_url: None,
+            error_message: None,
+            duration_ms: 0,
+        }
+    }
+
+    #[test]
+    fn test_signature_is_hex_hmac_sha256() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            sign_payload("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_webhook_retries_server_errors_until_delivered() {
+        let (url, received) = spawn_receiver(vec![503, 500, 200]).await;
+        let payload = payload();
+
+        let delivery = service(5, Some("global"))
+            .send_webhook(&url, &payload, Some("per-job"))
+            .await;
+        assert!(delivery.result.is_ok());
+        let statuses: Vec<Option<u16>> = delivery.attempts.iter().map(|a| a.status_code).collect();
+        assert_eq!(statuses, [Some(503), Some(500), Some(200)]);
+        assert_eq!(delivery.attempts[2].attempt, 3);
+        assert!(delivery.attempts[2].error.is_none());
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 3);
+        let (signature, body) = &received[2];
+        assert_eq!(
+            signature.as_deref(),
+            Some(sign_payload("per-job", body.as_bytes()).as_str())
+        );
+        let json: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(json["job_id"], payload.job_id.to_string());
+        assert_eq!(json["status"], "completed");
+        assert!(json["result_url"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_webhook_gives_up_after_max_attempts() {
+        let (url, received) = spawn_receiver(vec![502]).await;
+        let delivery = service(3, None).send_webhook(&url, &payload(), None).await;
+        assert!(matches!(
+            delivery.result,
+            Err(WebhookError::MaxRetriesExceeded)
+        ));
+        assert_eq!(delivery.attempts.len(), 3);
+        // Unsigned without a secret
+        assert!(received
+            .lock()
+            .unwrap()
+            .iter()
+            .all(|(signature, _)| signature.is_none()));
+
+        // Client errors won't get better by retrying
+        let (url, received) = spawn_receiver(vec![404]).await;
+        let delivery = service(3, Some("global"))
+            .send_webhook(&url, &payload(), None)
+            .await;
+        assert!(matches!(delivery.result, Err(WebhookError::HttpError(404))));
+        assert_eq!(delivery.attempts.len(), 1);
+        let received = received.lock().unwrap();
+        let (signature, body) = &received[0];
+        assert_eq!(
+            signature.as_deref(),
+            Some(sign_payload("global", body.as_bytes()).as_str())
+        );
+    }
+}
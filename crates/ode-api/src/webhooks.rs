@@ -1,97 +1,223 @@
+use crate::db::Database;
+use crate::models::{JobMetadata, WebhookPayload};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
 use reqwest::Client;
-use uuid::Uuid;
-use chrono::Utc;
-use crate::models::{WebhookPayload, JobStatus};
-use tracing::{info, warn, error};
+use sha2::Sha256;
+use std::time::Duration;
+use tracing::{error, info, warn};
 
-const MAX_RETRIES: u32 = 5;
+/// Header carrying `sha256=<hex>`, the HMAC-SHA256 of the request body keyed
+/// with the job's webhook secret.
+pub const SIGNATURE_HEADER: &str = "X-ODE-Signature";
+
+const DEFAULT_MAX_ATTEMPTS: u32 = 5;
 const INITIAL_BACKOFF_MS: u64 = 1000;
 
+/// How webhook deliveries are retried and signed.
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    /// Deliveries tried per notification, the first one included.
+    pub max_attempts: u32,
+    /// Wait before the first retry, doubled after every failed attempt.
+    pub initial_backoff: Duration,
+    /// Signs the payloads of jobs submitted without a secret of their own.
+    pub secret: Option<String>,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            initial_backoff: Duration::from_millis(INITIAL_BACKOFF_MS),
+            secret: None,
+        }
+    }
+}
+
+impl WebhookConfig {
+    /// The defaults, overridden by `ODE_WEBHOOK_MAX_ATTEMPTS` and
+    /// `ODE_WEBHOOK_SECRET` when set.
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            max_attempts: std::env::var("ODE_WEBHOOK_MAX_ATTEMPTS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|&n| n > 0)
+                .unwrap_or(defaults.max_attempts),
+            secret: std::env::var("ODE_WEBHOOK_SECRET")
+                .ok()
+                .filter(|s| !s.is_empty()),
+            ..defaults
+        }
+    }
+}
+
+/// One try at delivering a webhook.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeliveryAttempt {
+    /// 1 for the first try.
+    pub attempt: u32,
+    pub attempted_at: DateTime<Utc>,
+    /// The receiver's HTTP status, when it answered at all.
+    pub status_code: Option<u16>,
+    /// Why the attempt failed; `None` when it was delivered.
+    pub error: Option<String>,
+}
+
+/// The attempts made at delivering a webhook and how it ended.
+#[derive(Debug)]
+pub struct WebhookDelivery {
+    pub attempts: Vec<DeliveryAttempt>,
+    pub result: Result<(), WebhookError>,
+}
+
 #[derive(Clone)]
 pub struct WebhookService {
     client: Client,
+    config: WebhookConfig,
 }
 
 impl WebhookService {
     pub fn new() -> Self {
+        Self::with_config(WebhookConfig::default())
+    }
+
+    pub fn with_config(config: WebhookConfig) -> Self {
         Self {
             client: Client::builder()
                 .timeout(std::time::Duration::from_secs(30))
                 .build()
                 .expect("Failed to create HTTP client"),
+            config,
         }
     }
 
-    pub async fn send_webhook(
-        &self,
-        webhook_url: &str,
-        job_id: Uuid,
-        status: JobStatus,
-        file_name: String,
-        result_url: Option<String>,
-        error_message: Option<String>,
-        created_at: chrono::DateTime<Utc>,
-    ) -> Result<(), WebhookError> {
+    /// Tell a finished job's webhook, if it has one, how the job ended, and
+    /// record every delivery attempt in `db`.
+    pub async fn notify_job(&self, db: &Database, job: &JobMetadata) -> Result<(), WebhookError> {
+        let Some(webhook_url) = &job.webhook_url else {
+            return Ok(());
+        };
         let payload = WebhookPayload {
-            job_id,
-            status: status.clone(),
-            created_at,
-            completed_at: Utc::now(),
-            file_name,
-            result_url,
-            error_message,
-            duration_ms: (Utc::now() - created_at).num_milliseconds(),
+            job_id: job.id,
+            status: job.status.clone(),
+            created_at: job.created_at,
+            completed_at: job.updated_at,
+            file_name: job.file_name.clone(),
+            result_url: job.result_url.clone(),
+            error_message: job.error_message.clone(),
+            duration_ms: (job.updated_at - job.created_at).num_milliseconds(),
         };
 
-        let payload_json = serde_json::to_string(&payload)
-            .map_err(|e| WebhookError::SerializationFailed(e.to_string()))?;
+        let delivery = self
+            .send_webhook(webhook_url, &payload, job.webhook_secret.as_deref())
+            .await;
+        for attempt in &delivery.attempts {
+            if let Err(e) = db
+                .record_webhook_attempt(job.id, webhook_url, attempt)
+                .await
+            {
+                warn!(job_id = %job.id, error = %e, "Failed to record webhook attempt");
+            }
+        }
+        delivery.result
+    }
 
-        let mut attempt = 0;
-        let mut backoff = INITIAL_BACKOFF_MS;
+    /// POST `payload` to `webhook_url`, retrying network errors, 408, 429
+    /// and 5xx responses with exponential backoff. The body is signed with
+    /// `secret`, or else with the configured global secret.
+    pub async fn send_webhook(
+        &self,
+        webhook_url: &str,
+        payload: &WebhookPayload,
+        secret: Option<&str>,
+    ) -> WebhookDelivery {
+        let mut attempts = Vec::new();
+        let payload_json = match serde_json::to_string(payload) {
+            Ok(json) => json,
+            Err(e) => {
+                return WebhookDelivery {
+                    attempts,
+                    result: Err(WebhookError::SerializationFailed(e.to_string())),
+                };
+            }
+        };
+        let signature = secret
+            .or(self.config.secret.as_deref())
+            .map(|secret| sign_payload(secret, payload_json.as_bytes()));
 
+        let mut backoff = self.config.initial_backoff;
         loop {
-            attempt += 1;
+            let attempt = attempts.len() as u32 + 1;
+            let attempted_at = Utc::now();
+            let result = self
+                .attempt_delivery(webhook_url, &payload_json, signature.as_deref())
+                .await;
+            attempts.push(DeliveryAttempt {
+                attempt,
+                attempted_at,
+                status_code: match &result {
+                    Ok(status_code) | Err(WebhookError::HttpError(status_code)) => {
+                        Some(*status_code)
+                    }
+                    Err(_) => None,
+                },
+                error: result.as_ref().err().map(|e| e.to_string()),
+            });
 
-            match self.attempt_delivery(webhook_url, &payload_json).await {
+            let result = match result {
                 Ok(_) => {
                     info!(webhook_url, attempt, "Webhook delivered successfully");
-                    return Ok(());
+                    Ok(())
                 }
-                Err(WebhookError::HttpError(status_code)) if status_code >= 500 => {
+                Err(e) if e.is_transient() && attempt < self.config.max_attempts => {
                     warn!(
                         webhook_url,
-                        attempt, status_code,
-                        "Webhook delivery failed with server error, retrying with backoff {}ms",
-                        backoff
+                        attempt, error = %e,
+                        "Webhook delivery failed, retrying with backoff {}ms",
+                        backoff.as_millis()
                     );
-
-                    if attempt >= MAX_RETRIES {
-                        error!(
-                            webhook_url,
-                            attempt,
-                            "Webhook delivery failed after {} retries",
-                            MAX_RETRIES
-                        );
-                        return Err(WebhookError::MaxRetriesExceeded);
-                    }
-
-                    tokio::time::sleep(std::time::Duration::from_millis(backoff)).await;
+                    tokio::time::sleep(backoff).await;
                     backoff *= 2;
+                    continue;
+                }
+                Err(e) if e.is_transient() => {
+                    error!(
+                        webhook_url,
+                        attempt,
+                        error = %e,
+                        "Webhook delivery failed after {} attempts",
+                        attempt
+                    );
+                    Err(WebhookError::MaxRetriesExceeded)
                 }
                 Err(e) => {
                     warn!(webhook_url, attempt, error = %e, "Webhook delivery failed permanently");
-                    return Err(e);
+                    Err(e)
                 }
-            }
+            };
+            return WebhookDelivery { attempts, result };
         }
     }
 
-    async fn attempt_delivery(&self, webhook_url: &str, payload_json: &str) -> Result<(), WebhookError> {
-        let response = self
+    /// POST the payload once, returning the receiver's success status.
+    async fn attempt_delivery(
+        &self,
+        webhook_url: &str,
+        payload_json: &str,
+        signature: Option<&str>,
+    ) -> Result<u16, WebhookError> {
+        let mut request = self
             .client
             .post(webhook_url)
             .header("Content-Type", "application/json")
-            .header("User-Agent", "ODE-Webhook/1.0")
+            .header("User-Agent", "ODE-Webhook/1.0");
+        if let Some(signature) = signature {
+            request = request.header(SIGNATURE_HEADER, signature);
+        }
+        let response = request
             .body(payload_json.to_string())
             .send()
             .await
@@ -100,13 +226,23 @@ impl WebhookService {
         let status = response.status();
 
         if status.is_success() {
-            Ok(())
+            Ok(status.as_u16())
         } else {
             Err(WebhookError::HttpError(status.as_u16()))
         }
     }
 }
 
+/// The `X-ODE-Signature` value for `body`: `sha256=` and the hex HMAC-SHA256
+/// of the body keyed with `secret`. Receivers recompute it over the raw body
+/// to check a callback came from ODE.
+pub fn sign_payload(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum WebhookError {
     #[error("Serialization failed: {0}")]
@@ -120,4 +256,16 @@ pub enum WebhookError {
 
     #[error("Max retries exceeded")]
     MaxRetriesExceeded,
-}
\ No newline at end of file
+}
+
+impl WebhookError {
+    /// Whether trying again later might succeed: the request didn't get
+    /// through, timed out, was throttled or hit a server error.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            WebhookError::RequestFailed(_) => true,
+            WebhookError::HttpError(status) => matches!(status, 408 | 429 | 500..=599),
+            WebhookError::SerializationFailed(_) | WebhookError::MaxRetriesExceeded => false,
+        }
+    }
+}
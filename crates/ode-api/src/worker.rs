@@ -0,0 +1,156 @@
+use crate::models::{ConversionOptions, JobStatus};
+use crate::routes::AppState;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Workers [`spawn_workers`] starts when `ODE_WORKER_CONCURRENCY` is unset.
+pub const DEFAULT_CONCURRENCY: usize = 2;
+
+/// How long a worker that found the queue empty waits before looking again.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// `ODE_WORKER_CONCURRENCY` when it is a positive number, or else
+/// [`DEFAULT_CONCURRENCY`].
+pub fn concurrency_from_env() -> usize {
+    std::env::var("ODE_WORKER_CONCURRENCY")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(DEFAULT_CONCURRENCY)
+}
+
+/// Start `concurrency` [`run_worker`] loops, so that many jobs convert at
+/// once.
+pub fn spawn_workers(state: AppState, concurrency: usize) -> Vec<JoinHandle<()>> {
+    (0..concurrency)
+        .map(|_| tokio::spawn(run_worker(state.clone())))
+        .collect()
+}
+
+/// Convert the jobs `submit_conversion` and `retry_job` queue, one at a
+/// time and forever.
+pub async fn run_worker(state: AppState) {
+    loop {
+        let task = state.task_queue.lock().await.dequeue_job().await;
+        match task {
+            Ok(Some(task)) => process_job(&state, task.job_id).await,
+            Ok(None) => tokio::time::sleep(POLL_INTERVAL).await,
+            Err(e) => {
+                warn!(error = %e, "Failed to dequeue job");
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        }
+    }
+}
+
+/// Run one queued job: mark it processing, convert its PDF with the options
+/// it was submitted with, store the output and mark it completed with the
+/// output's URL, or failed with why. Its webhook, if any, is then told.
+pub async fn process_job(state: &AppState, job_id: Uuid) {
+    // Jobs deleted while queued have nothing left to run
+    match state.db.get_job(job_id).await {
+        Ok(Some(job)) if job.status == JobStatus::Pending => {}
+        Ok(_) => return,
+        Err(e) => {
+            error!(job_id = %job_id, error = %e, "Failed to fetch job");
+            return;
+        }
+    }
+    if let Err(e) = state
+        .db
+        .update_job_status(job_id, JobStatus::Processing, None)
+        .await
+    {
+        error!(job_id = %job_id, error = %e, "Failed to mark job processing");
+        return;
+    }
+
+    let finished = match convert_and_store(state, job_id).await {
+        // The URL goes in first, so a completed job always has one
+        Ok(result_url) => match state.db.set_result_url(job_id, result_url).await {
+            Ok(()) => {
+                state
+                    .db
+                    .update_job_status(job_id, JobStatus::Completed, None)
+                    .await
+            }
+            Err(e) => {
+                let message = format!("Failed to save result: {}", e);
+                state
+                    .db
+                    .update_job_status(job_id, JobStatus::Failed, Some(message))
+                    .await
+            }
+        },
+        Err(message) => {
+            warn!(job_id = %job_id, error = %message, "Job failed");
+            state
+                .db
+                .update_job_status(job_id, JobStatus::Failed, Some(message))
+                .await
+        }
+    };
+    if let Err(e) = finished {
+        error!(job_id = %job_id, error = %e, "Failed to record job outcome");
+        return;
+    }
+
+    match state.db.get_job(job_id).await {
+        Ok(Some(job)) => {
+            info!(job_id = %job_id, status = %job.status, "Job finished");
+            // Retries back off for a while, so they mustn't hold up the queue
+            let state = state.clone();
+            tokio::spawn(async move {
+                if let Err(e) = state.webhook_service.notify_job(&state.db, &job).await {
+                    warn!(job_id = %job_id, error = %e, "Webhook delivery failed");
+                }
+            });
+        }
+        Ok(None) => {}
+        Err(e) => error!(job_id = %job_id, error = %e, "Failed to fetch finished job"),
+    }
+}
+
+/// Convert a job's stored PDF and put its output in storage, returning the
+/// URL of its `index.html`.
+async fn convert_and_store(state: &AppState, job_id: Uuid) -> Result<String, String> {
+    let database_error = |e: sqlx::Error| format!("Failed to fetch job: {}", e);
+    let pdf_data = state
+        .db
+        .get_job_pdf_data(job_id)
+        .await
+        .map_err(database_error)?
+        .ok_or("Job has no PDF data")?;
+    let config = state
+        .db
+        .get_job_config(job_id)
+        .await
+        .map_err(database_error)?
+        .ok_or("Job has no config")?;
+
+    let files = tokio::task::spawn_blocking(move || convert_job(&pdf_data, config))
+        .await
+        .map_err(|e| format!("Conversion task failed: {}", e))??;
+    state
+        .storage
+        .store_bundle(job_id, files)
+        .await
+        .map_err(|e| format!("Failed to store output: {}", e))
+}
+
+/// The output files for `pdf_data` converted with `config`, the
+/// [`ConversionOptions`] a job was submitted with.
+pub(crate) fn convert_job(
+    pdf_data: &[u8],
+    config: serde_json::Value,
+) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let options: ConversionOptions =
+        serde_json::from_value(config).map_err(|e| format!("Invalid job config: {}", e))?;
+    let bundle = ode_core::convert_pdf(pdf_data, &options.to_conversion_config())
+        .map_err(|e| format!("PDF conversion failed: {}", e))?;
+    let mut files = bundle.to_asset_files();
+    files.extend(crate::routes::stored_page_files(&bundle));
+    Ok(files)
+}
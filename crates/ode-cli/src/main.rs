@@ -0,0 +1,150 @@
+//! `ode`, converting PDFs on the command line without the API service.
+
+use clap::{Parser, Subcommand, ValueEnum};
+use ode_core::config::{OutputFormat, PageBox, PageSelection};
+use ode_core::{convert_pdf_from_path, ConversionConfig, OdeError};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+#[derive(Parser)]
+#[command(name = "ode", version, about = "Convert PDF documents to HTML")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Convert a PDF to HTML, plain text or JSON.
+    Convert(ConvertArgs),
+}
+
+#[derive(clap::Args)]
+struct ConvertArgs {
+    /// The PDF to convert.
+    input: PathBuf,
+
+    /// Where to write the output; standard output when omitted.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Pages to convert, such as `1-5` or `1,3,7-`; every page by default.
+    #[arg(long, value_parser = PageSelection::parse)]
+    pages: Option<PageSelection>,
+
+    #[arg(long, value_enum, default_value_t = Format::Html)]
+    format: Format,
+
+    /// Write `index.html` and the fonts and images it loads into this
+    /// directory instead of one self-contained HTML file.
+    #[arg(long, value_name = "DIR", conflicts_with = "output")]
+    split_assets: Option<PathBuf>,
+
+    /// Lay a tagged PDF's text out by its structure, in headings,
+    /// paragraphs, lists and tables, instead of where each page shows it.
+    #[arg(long)]
+    reflow: bool,
+
+    /// The page boundary to show, such as `trim` for the finished size of a
+    /// print-ready PDF.
+    #[arg(long, value_enum, default_value_t = Boundary::Crop)]
+    page_box: Boundary,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    /// Single-file HTML with fonts and images inlined.
+    Html,
+    /// The text in reading order.
+    Text,
+    /// Page sizes and text spans as JSON.
+    Json,
+}
+
+impl From<Format> for OutputFormat {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::Html => OutputFormat::Html,
+            Format::Text => OutputFormat::Text,
+            Format::Json => OutputFormat::Json,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Boundary {
+    Media,
+    Crop,
+    Bleed,
+    Trim,
+    Art,
+}
+
+impl From<Boundary> for PageBox {
+    fn from(boundary: Boundary) -> Self {
+        match boundary {
+            Boundary::Media => PageBox::MediaBox,
+            Boundary::Crop => PageBox::CropBox,
+            Boundary::Bleed => PageBox::BleedBox,
+            Boundary::Trim => PageBox::TrimBox,
+            Boundary::Art => PageBox::ArtBox,
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let Cli { command } = Cli::parse();
+    let result = match command {
+        Command::Convert(args) => convert(&args),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("ode: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn convert(args: &ConvertArgs) -> Result<(), OdeError> {
+    if args.split_assets.is_some() && !matches!(args.format, Format::Html) {
+        return Err(OdeError::ConfigError(
+            "--split-assets only applies to --format html".to_string(),
+        ));
+    }
+
+    let config = ConversionConfig {
+        pages: args.pages.clone(),
+        output_format: args.format.into(),
+        reflow: args.reflow,
+        page_box: args.page_box.into(),
+        ..ConversionConfig::default()
+    };
+    let bundle = convert_pdf_from_path(&args.input, &config)?;
+
+    if let Some(dir) = &args.split_assets {
+        fs::create_dir_all(dir).map_err(|e| with_path(e, dir))?;
+        for (name, data) in bundle.to_asset_files() {
+            let path = dir.join(name);
+            fs::write(&path, data).map_err(|e| with_path(e, &path))?;
+        }
+        return Ok(());
+    }
+
+    let rendered = bundle.render(config.output_format);
+    match &args.output {
+        Some(path) => fs::write(path, rendered).map_err(|e| with_path(e, path))?,
+        None => std::io::stdout().write_all(rendered.as_bytes())?,
+    }
+    Ok(())
+}
+
+/// `e` with the file it happened on, since `io::Error` doesn't say.
+fn with_path(e: std::io::Error, path: &Path) -> OdeError {
+    OdeError::IoError(std::io::Error::new(
+        e.kind(),
+        format!("{}: {}", path.display(), e),
+    ))
+}
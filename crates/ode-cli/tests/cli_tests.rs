@@ -0,0 +1,137 @@
+use std::fs;
+use std::process::Command;
+
+/// A PDF with one page per entry of `texts`, each showing it in Helvetica.
+fn sample_pdf(texts: &[&str]) -> Vec<u8> {
+    let kids: Vec<String> = (0..texts.len())
+        .map(|i| format!("{} 0 R", 4 + 2 * i))
+        .collect();
+    let mut objects: Vec<Vec<u8>> = vec![
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        format!(
+            "<</Type/Pages/Count {}/Kids[{}]>>",
+            texts.len(),
+            kids.join(" ")
+        )
+        .into_bytes(),
+        b"<</Type/Font/Subtype/Type1/BaseFont/Helvetica>>".to_vec(),
+    ];
+    for (i, text) in texts.iter().enumerate() {
+        let content = format!("BT /F1 12 Tf 72 720 Td ({}) Tj ET", text);
+        objects.push(
+            format!(
+                "<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]\
+                 /Resources<</Font<</F1 3 0 R>>>>/Contents {} 0 R>>",
+                5 + 2 * i
+            )
+            .into_bytes(),
+        );
+        objects.push(
+            format!(
+                "<</Length {}>>\nstream\n{}\nendstream",
+                content.len(),
+                content
+            )
+            .into_bytes(),
+        );
+    }
+
+    let mut pdf = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::new();
+    for (i, body) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+        pdf.extend_from_slice(body);
+        pdf.extend_from_slice(b"\nendobj\n");
+    }
+    let xref_offset = pdf.len();
+    pdf.extend_from_slice(
+        format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes(),
+    );
+    for offset in offsets {
+        pdf.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    pdf.extend_from_slice(
+        format!(
+            "trailer\n<</Size {}/Root 1 0 R>>\nstartxref\n{}\n%%EOF",
+            objects.len() + 1,
+            xref_offset
+        )
+        .as_bytes(),
+    );
+    pdf
+}
+
+fn ode() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_ode"))
+}
+
+#[test]
+fn test_convert_writes_html_and_text() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("input.pdf");
+    fs::write(&input, sample_pdf(&["First page", "Second page"])).unwrap();
+
+    let html = dir.path().join("out.html");
+    let status = ode()
+        .arg("convert")
+        .arg(&input)
+        .arg("-o")
+        .arg(&html)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let html = fs::read_to_string(html).unwrap();
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("First page"));
+    assert!(html.contains("Second page"));
+
+    let output = ode()
+        .arg("convert")
+        .arg(&input)
+        .args(["--pages", "2", "--format", "text"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let text = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(text.trim(), "Second page");
+}
+
+#[test]
+fn test_convert_splits_assets_into_a_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("input.pdf");
+    fs::write(&input, sample_pdf(&["Hello"])).unwrap();
+
+    let assets = dir.path().join("site");
+    let status = ode()
+        .arg("convert")
+        .arg(&input)
+        .arg("--split-assets")
+        .arg(&assets)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert!(fs::read_to_string(assets.join("index.html"))
+        .unwrap()
+        .contains("Hello"));
+}
+
+#[test]
+fn test_convert_reports_errors_with_a_nonzero_exit() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("broken.pdf");
+    fs::write(&input, b"not a pdf").unwrap();
+
+    let output = ode().arg("convert").arg(&input).output().unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("ode: "), "{}", stderr);
+
+    let missing = dir.path().join("missing.pdf");
+    let output = ode().arg("convert").arg(&missing).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("missing.pdf"));
+}
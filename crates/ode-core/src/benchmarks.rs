@@ -1,5 +1,5 @@
 #[cfg(test)]
-mod performance_benchmarks {
+pub(crate) mod performance_benchmarks {
     use crate::config::ConversionConfig;
     use crate::convert_pdf;
     use std::time::Instant;
@@ -50,12 +50,25 @@ mod performance_benchmarks {
             width: 612.0,
             height: 792.0,
             contents: Vec::new(),
+            contents_ref: None,
             fonts: Vec::new(),
+            font_refs: std::collections::HashMap::new(),
             rotation: 0,
+            user_unit: 1.0,
+            origin: (0.0, 0.0),
             dict: None,
             font_cmaps: std::collections::HashMap::new(),
+            font_widths: std::collections::HashMap::new(),
+            type3_fonts: std::collections::HashMap::new(),
             images: std::collections::HashMap::new(),
             form_xobjects: std::collections::HashMap::new(),
+            color_spaces: std::collections::HashMap::new(),
+            shadings: std::collections::HashMap::new(),
+            patterns: std::collections::HashMap::new(),
+            hidden_content: std::collections::HashSet::new(),
+            links: Vec::new(),
+            notes: Vec::new(),
+            form_widgets: Vec::new(),
         });
 
         let config = ConversionConfig::default();
@@ -257,11 +270,49 @@ mod performance_benchmarks {
         }
     }
 
+    #[test]
+    fn benchmark_serial_vs_parallel_rendering() {
+        let pdf_data = create_multi_page_pdf(100);
+        let serial_config = ConversionConfig::default();
+        let parallel_config = ConversionConfig {
+            parallel: true,
+            ..Default::default()
+        };
+
+        let serial = convert_pdf(&pdf_data, &serial_config).unwrap();
+        let parallel = convert_pdf(&pdf_data, &parallel_config).unwrap();
+
+        let serial_pages: Vec<_> = serial
+            .pages
+            .iter()
+            .map(|p| (p.page_number, &p.html))
+            .collect();
+        let parallel_pages: Vec<_> = parallel
+            .pages
+            .iter()
+            .map(|p| (p.page_number, &p.html))
+            .collect();
+        assert_eq!(parallel_pages.len(), 100);
+        assert_eq!(
+            serial_pages, parallel_pages,
+            "parallel rendering must keep page order"
+        );
+
+        let font_names = |bundle: &crate::renderer::OutputBundle| {
+            bundle
+                .fonts
+                .iter()
+                .map(|f| f.font_name.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(font_names(&serial), font_names(&parallel));
+    }
+
     fn create_standard_test_pdf() -> Vec<u8> {
         create_multi_page_pdf(3)
     }
 
-    fn create_multi_page_pdf(page_count: usize) -> Vec<u8> {
+    pub(crate) fn create_multi_page_pdf(page_count: usize) -> Vec<u8> {
         let mut pdf = Vec::<u8>::new();
         let mut offsets = Vec::<usize>::new();
 
@@ -1,12 +1,29 @@
+use crate::error::{OdeError, OdeResult};
+use crate::parser::FilterRegistry;
+use crate::renderer::PageCache;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConversionConfig {
+    /// First and last page to convert, 1-based and inclusive. Ignored when
+    /// `pages` is set.
     pub page_range: (usize, usize),
+    /// The pages to convert, when not a single contiguous range.
+    #[serde(default)]
+    pub pages: Option<PageSelection>,
     pub zoom: f64,
     pub fit_width: Option<f64>,
     pub fit_height: Option<f64>,
     pub use_cropbox: bool,
+    /// Width and height in points for pages with no MediaBox, their own or
+    /// inherited. Unset, such pages take the size of the first page that
+    /// has one.
+    #[serde(default)]
+    pub default_page_size: Option<(f64, f64)>,
     pub desired_dpi: f64,
     pub max_dpi: Option<f64>,
     pub text_dpi: f64,
@@ -41,6 +58,10 @@ pub struct ConversionConfig {
     pub squeeze_wide_glyph: bool,
     pub override_fstype: bool,
     pub process_type3: bool,
+    /// Cut embedded TrueType fonts down to the glyphs the converted pages
+    /// show.
+    #[serde(default = "default_subset_fonts")]
+    pub subset_fonts: bool,
 
     pub h_eps: f64,
     pub v_eps: f64,
@@ -64,6 +85,139 @@ pub struct ConversionConfig {
     pub quiet: bool,
 
     pub timeout_ms: Option<u64>,
+
+    /// How long a conversion may run before it gives up with
+    /// `OdeError::Timeout`. Unset, it runs to completion.
+    #[serde(default)]
+    pub timeout: Option<Duration>,
+
+    /// Render pages concurrently on the rayon thread pool.
+    #[serde(default)]
+    pub parallel: bool,
+
+    /// Fail the conversion on the first page that fails to render. Off, the
+    /// page is left empty and listed in `OutputBundle::page_errors`.
+    #[serde(default)]
+    pub strict: bool,
+
+    /// Longest side in pixels an embedded image may have. Larger images are
+    /// scaled down to it and, when opaque, re-encoded as JPEG; unset, images
+    /// are embedded as they are.
+    #[serde(default)]
+    pub max_image_dimension: Option<u32>,
+
+    /// JPEG quality, 1 to 100, for images scaled down to
+    /// `max_image_dimension`.
+    #[serde(default = "default_image_quality")]
+    pub image_quality: u8,
+
+    /// Most bytes one stream may decompress to. Streams that would exceed it
+    /// are rejected as zip bombs and left undecoded.
+    #[serde(default = "default_max_decompressed_size")]
+    pub max_decompressed_size: usize,
+
+    /// Highest ratio of decompressed to compressed size a stream may reach
+    /// once it has decompressed past 10 MiB.
+    #[serde(default = "default_max_compression_ratio")]
+    pub max_compression_ratio: u32,
+
+    /// Most bytes of font and image data the output may carry, counting
+    /// each file once, before base64 when inlined. Unset, there is no limit.
+    #[serde(default)]
+    pub max_output_bytes: Option<usize>,
+
+    /// What happens once `max_output_bytes` is reached.
+    #[serde(default)]
+    pub output_limit_action: OutputLimitAction,
+
+    /// Mark page images `loading="lazy"` so browsers defer off-screen ones.
+    #[serde(default = "default_lazy_images")]
+    pub lazy_images: bool,
+
+    /// Join text shown piecewise along a line into one span per run, with
+    /// spaces at word gaps. Off, every shown string keeps its own span.
+    #[serde(default = "default_merge_spans")]
+    pub merge_spans: bool,
+
+    /// Carry character and word spacing (`Tc`, `Tw`) into spans' CSS
+    /// `letter-spacing` and `word-spacing`, so condensed or spread text
+    /// keeps its width. Off, text takes the font's own spacing.
+    #[serde(default = "default_text_spacing")]
+    pub text_spacing: bool,
+
+    /// Keep runs of spaces, and spaces at either end of a span, as wide as
+    /// the PDF shows them, by setting spans `white-space: pre`. Off, they
+    /// collapse to one space as in ordinary HTML.
+    #[serde(default = "default_preserve_spaces")]
+    pub preserve_spaces: bool,
+
+    /// Mark each sticky note and markup annotation with a small box whose
+    /// `title` tooltip is the annotation's comment.
+    #[serde(default)]
+    pub note_tooltips: bool,
+
+    /// CSS pixels per PDF point in the emitted geometry. 1.0 maps one point
+    /// to one pixel; see [`ConversionConfig::with_target_dpi`].
+    #[serde(default = "default_output_scale")]
+    pub output_scale: f64,
+
+    /// What the conversion is for. Pages only carry HTML when it is
+    /// [`OutputFormat::Html`]; see [`crate::OutputBundle::render`].
+    #[serde(default)]
+    pub output_format: OutputFormat,
+
+    /// What shown text gets for a glyph code with no Unicode mapping.
+    #[serde(default)]
+    pub unmapped_glyph: UnmappedGlyph,
+
+    /// What becomes of text that opaque fills drawn after it hide entirely.
+    #[serde(default)]
+    pub covered_text: CoveredText,
+
+    /// How far, in points, a thin rule's ends may fall short of a span's, or
+    /// the rule sit below its underline position, for the rule to become the
+    /// span's underline or strike-through instead of a box of its own.
+    /// `None`, the default, draws every rule as it is.
+    #[serde(default)]
+    pub decoration_tolerance: Option<f64>,
+
+    /// Narrowest gap, in points, that sets two columns of text apart when
+    /// no span crosses it. Pages record the order their text is read in,
+    /// column by column, and plain text follows it. `None`, the default,
+    /// reads each page top to bottom as one column, which keeps the key and
+    /// value of a form or table row on one line.
+    #[serde(default)]
+    pub column_gap: Option<f64>,
+
+    /// For a tagged PDF, write HTML that follows its structure tree, with
+    /// headings, paragraphs, lists and tables in semantic tags and the text
+    /// in reading order, instead of positioning the text on each page.
+    /// Images aren't carried over. Untagged documents keep the positioned
+    /// layout.
+    #[serde(default)]
+    pub reflow: bool,
+
+    /// Which of each page's boxes is the area shown; see [`PageBox`].
+    #[serde(default)]
+    pub page_box: PageBox,
+
+    /// Whether HTML output inlines fonts and images or loads them from
+    /// separate files; see [`AssetEmbedding`].
+    #[serde(default)]
+    pub asset_embedding: AssetEmbedding,
+
+    /// Optional content groups (layers), by their `/Name`, to show even
+    /// when the document hides them by default.
+    #[serde(default)]
+    pub enabled_layers: Vec<String>,
+
+    /// Custom stream filter decoders, consulted before the built-in filters.
+    #[serde(skip)]
+    pub filters: FilterRegistry,
+
+    /// Rendered pages reused across conversions of the same document.
+    #[serde(skip)]
+    pub page_cache: Option<Arc<PageCache>>,
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
@@ -73,6 +227,243 @@ pub enum FontFormat {
     Ttf,
 }
 
+impl FontFormat {
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            FontFormat::Woff2 => "font/woff2",
+            FontFormat::Woff => "font/woff",
+            FontFormat::Ttf => "font/ttf",
+        }
+    }
+
+    /// The `format()` hint for `@font-face`. Browsers skip sources whose hint
+    /// they don't recognize, so this is the CSS keyword, not the MIME type.
+    pub fn css_format(&self) -> &'static str {
+        match self {
+            FontFormat::Woff2 => "woff2",
+            FontFormat::Woff => "woff",
+            FontFormat::Ttf => "truetype",
+        }
+    }
+}
+
+/// The text for a glyph code that neither a `/ToUnicode` CMap nor the
+/// string's own bytes map to a character.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum UnmappedGlyph {
+    /// The code read as a character itself: Latin-1 for one-byte codes
+    /// under a `/ToUnicode` map, UTF-8 otherwise, with U+FFFD for bytes
+    /// that aren't.
+    #[default]
+    Fallback,
+    /// Leave the glyph out of the text.
+    Drop,
+    /// U+FFFD REPLACEMENT CHARACTER.
+    Replacement,
+    /// U+25A1 WHITE SQUARE, keeping a visible box where the glyph was.
+    Placeholder,
+}
+
+impl UnmappedGlyph {
+    /// The character for an unmapped glyph, given what `Fallback` reads its
+    /// code as.
+    pub fn substitute(self, fallback: Option<char>) -> Option<char> {
+        match self {
+            UnmappedGlyph::Fallback => fallback,
+            UnmappedGlyph::Drop => None,
+            UnmappedGlyph::Replacement => Some('\u{FFFD}'),
+            UnmappedGlyph::Placeholder => Some('\u{25A1}'),
+        }
+    }
+}
+
+/// Text a later opaque fill covers completely, like a black box drawn over
+/// redacted words or a layer pasted over older content.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum CoveredText {
+    /// Output it like any other text.
+    #[default]
+    Keep,
+    /// Leave it out of the page.
+    Drop,
+    /// Keep it in its own spans with the `covered` CSS class, transparent
+    /// but still searchable.
+    Mark,
+}
+
+/// What a conversion does when its fonts and images reach
+/// `ConversionConfig::max_output_bytes`.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum OutputLimitAction {
+    /// Stop with `OdeError::OutputTooLarge`.
+    #[default]
+    Fail,
+    /// Leave out every image that would go past the limit, noting it in the
+    /// page's diagnostics. Fonts are always kept.
+    DropImages,
+}
+
+/// The page boundary a conversion shows, its lower-left corner becoming the
+/// page's top-left after the flip to HTML's y-down. A box the page doesn't
+/// have falls back to the CropBox, and the CropBox to the MediaBox; every
+/// box is clipped to the MediaBox.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum PageBox {
+    /// The whole medium, printer's marks and all.
+    MediaBox,
+    /// The area viewers show.
+    #[default]
+    CropBox,
+    /// The area to print, bleed included.
+    BleedBox,
+    /// The finished page after trimming.
+    TrimBox,
+    /// The page's meaningful content.
+    ArtBox,
+}
+
+impl PageBox {
+    /// The page dictionary key holding the box.
+    pub fn key(self) -> &'static str {
+        match self {
+            PageBox::MediaBox => "MediaBox",
+            PageBox::CropBox => "CropBox",
+            PageBox::BleedBox => "BleedBox",
+            PageBox::TrimBox => "TrimBox",
+            PageBox::ArtBox => "ArtBox",
+        }
+    }
+}
+
+/// How HTML output carries the fonts and images it uses, as read by
+/// [`crate::OutputBundle::to_html`] and [`crate::convert_to_html_with_assets`].
+///
+/// [`crate::OutputBundle::to_single_file_html`] and
+/// [`crate::OutputBundle::to_asset_files`] ignore it: the first always
+/// inlines, the second always writes separate files.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum AssetEmbedding {
+    /// As base64 data URIs in the one HTML file, which then works anywhere
+    /// on its own.
+    #[default]
+    Inline,
+    /// As content-addressed files the HTML loads by relative path, smaller
+    /// and cacheable when a web server hosts them next to it.
+    External,
+}
+
+/// A set of 1-based page numbers, written like `"1,3,5-10,20-"`: single
+/// pages and inclusive ranges, where a range without an end runs to the last
+/// page and one without a start begins at the first.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct PageSelection {
+    /// Inclusive `(first, last)` ranges in the order written; `None` is open.
+    ranges: Vec<(usize, Option<usize>)>,
+}
+
+impl PageSelection {
+    /// Every page.
+    pub fn all() -> Self {
+        Self {
+            ranges: vec![(1, None)],
+        }
+    }
+
+    pub fn parse(text: &str) -> OdeResult<Self> {
+        let invalid = |part: &str| {
+            OdeError::ConfigError(format!("Invalid page selection {:?} in {:?}", part, text))
+        };
+        let page = |number: &str, part: &str| match number.trim().parse::<usize>() {
+            Ok(page) if page > 0 => Ok(page),
+            _ => Err(invalid(part)),
+        };
+
+        let mut ranges = Vec::new();
+        for part in text.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let range = match part.split_once('-') {
+                None => (page(part, part)?, Some(page(part, part)?)),
+                Some((first, last)) => {
+                    let first = if first.trim().is_empty() { 1 } else { page(first, part)? };
+                    let last = if last.trim().is_empty() { None } else { Some(page(last, part)?) };
+                    if last.is_some_and(|last| last < first) {
+                        return Err(invalid(part));
+                    }
+                    (first, last)
+                }
+            };
+            ranges.push(range);
+        }
+        if ranges.is_empty() {
+            return Err(OdeError::ConfigError(format!("Empty page selection {:?}", text)));
+        }
+        Ok(Self { ranges })
+    }
+
+    /// The 0-based indices of the selected pages of a document with
+    /// `page_count` pages, in page order and each once. Pages past the end are
+    /// left out.
+    pub fn page_indices(&self, page_count: usize) -> Vec<usize> {
+        let mut pages = BTreeSet::new();
+        for &(first, last) in &self.ranges {
+            let last = last.unwrap_or(usize::MAX).min(page_count);
+            pages.extend((first..=last).map(|page| page - 1));
+        }
+        pages.into_iter().collect()
+    }
+}
+
+/// The old `page_range` tuple, first and last page inclusive.
+impl From<(usize, usize)> for PageSelection {
+    fn from((first, last): (usize, usize)) -> Self {
+        Self {
+            ranges: vec![(first.max(1), (last != usize::MAX).then_some(last))],
+        }
+    }
+}
+
+impl fmt::Display for PageSelection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, &(first, last)) in self.ranges.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            match last {
+                Some(last) if last == first => write!(f, "{}", first)?,
+                Some(last) => write!(f, "{}-{}", first, last)?,
+                None => write!(f, "{}-", first)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<String> for PageSelection {
+    type Error = OdeError;
+
+    fn try_from(text: String) -> OdeResult<Self> {
+        Self::parse(&text)
+    }
+}
+
+impl From<PageSelection> for String {
+    fn from(selection: PageSelection) -> Self {
+        selection.to_string()
+    }
+}
+
+/// The document a conversion's [`crate::OutputBundle`] is rendered as.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Positioned HTML reproducing the page layout.
+    #[default]
+    Html,
+    /// The text in reading order, for search indexing.
+    Text,
+    /// The text spans and page sizes as JSON, for data extraction.
+    Json,
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 pub enum BackgroundFormat {
     Png,
@@ -80,14 +471,52 @@ pub enum BackgroundFormat {
     Svg,
 }
 
+fn default_image_quality() -> u8 {
+    85
+}
+
+fn default_max_decompressed_size() -> usize {
+    100 * 1024 * 1024
+}
+
+fn default_max_compression_ratio() -> u32 {
+    100
+}
+
+fn default_lazy_images() -> bool {
+    true
+}
+
+fn default_subset_fonts() -> bool {
+    true
+}
+
+fn default_merge_spans() -> bool {
+    true
+}
+
+fn default_text_spacing() -> bool {
+    true
+}
+
+fn default_preserve_spaces() -> bool {
+    true
+}
+
+fn default_output_scale() -> f64 {
+    1.0
+}
+
 impl Default for ConversionConfig {
     fn default() -> Self {
         Self {
             page_range: (1, usize::MAX),
+            pages: None,
             zoom: 1.0,
             fit_width: None,
             fit_height: None,
             use_cropbox: false,
+            default_page_size: None,
             desired_dpi: 72.0,
             max_dpi: None,
             text_dpi: 72.0,
@@ -122,6 +551,7 @@ impl Default for ConversionConfig {
             squeeze_wide_glyph: false,
             override_fstype: false,
             process_type3: true,
+            subset_fonts: true,
 
             h_eps: 0.5,
             v_eps: 0.5,
@@ -145,6 +575,186 @@ impl Default for ConversionConfig {
             quiet: false,
 
             timeout_ms: Some(30000),
+            timeout: None,
+            parallel: false,
+            strict: false,
+            max_image_dimension: None,
+            image_quality: 85,
+            max_decompressed_size: 100 * 1024 * 1024,
+            max_compression_ratio: 100,
+            max_output_bytes: None,
+            output_limit_action: OutputLimitAction::default(),
+            lazy_images: true,
+            merge_spans: true,
+            text_spacing: true,
+            preserve_spaces: true,
+            note_tooltips: false,
+            output_scale: 1.0,
+            output_format: OutputFormat::Html,
+            unmapped_glyph: UnmappedGlyph::Fallback,
+            covered_text: CoveredText::Keep,
+            decoration_tolerance: None,
+            column_gap: None,
+            reflow: false,
+            page_box: PageBox::CropBox,
+            asset_embedding: AssetEmbedding::Inline,
+            enabled_layers: Vec::new(),
+
+            filters: FilterRegistry::new(),
+            page_cache: None,
+        }
+    }
+}
+
+impl ConversionConfig {
+    /// A [`ConversionConfigBuilder`] starting from the defaults.
+    pub fn builder() -> ConversionConfigBuilder {
+        ConversionConfigBuilder::default()
+    }
+
+    /// The pages to convert: `pages`, or else `page_range`.
+    pub fn page_selection(&self) -> PageSelection {
+        self.pages.clone().unwrap_or_else(|| self.page_range.into())
+    }
+
+    /// Scale output so that one CSS pixel is one dot at `dpi`, given that a
+    /// PDF point is 1/72 inch.
+    pub fn with_target_dpi(mut self, dpi: f64) -> Self {
+        self.output_scale = dpi / 72.0;
+        self
+    }
+}
+
+/// Sets up a [`ConversionConfig`] one option at a time from the defaults,
+/// checking the options make sense in [`build`](Self::build):
+///
+/// ```
+/// use ode_core::config::AssetEmbedding;
+/// use ode_core::ConversionConfig;
+/// use std::time::Duration;
+///
+/// let config = ConversionConfig::builder()
+///     .pages((1, 5))
+///     .scale(2.0)
+///     .timeout(Duration::from_secs(10))
+///     .embedding(AssetEmbedding::External)
+///     .build()
+///     .unwrap();
+/// assert_eq!(config.output_scale, 2.0);
+/// ```
+///
+/// Options without a method of their own can still be set on the built
+/// config.
+#[derive(Debug, Clone, Default)]
+pub struct ConversionConfigBuilder {
+    config: ConversionConfig,
+}
+
+impl ConversionConfigBuilder {
+    /// The pages to convert, such as `(1, 5)` or a parsed [`PageSelection`].
+    pub fn pages(mut self, pages: impl Into<PageSelection>) -> Self {
+        self.config.pages = Some(pages.into());
+        self
+    }
+
+    /// CSS pixels per PDF point; see [`ConversionConfig::output_scale`].
+    pub fn scale(mut self, scale: f64) -> Self {
+        self.config.output_scale = scale;
+        self
+    }
+
+    /// How long the conversion may run; see [`ConversionConfig::timeout`].
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeout = Some(timeout);
+        self
+    }
+
+    /// Whether HTML output inlines its fonts and images.
+    pub fn embedding(mut self, embedding: AssetEmbedding) -> Self {
+        self.config.asset_embedding = embedding;
+        self
+    }
+
+    /// Render pages concurrently.
+    pub fn parallel(mut self, parallel: bool) -> Self {
+        self.config.parallel = parallel;
+        self
+    }
+
+    /// The config, or `OdeError::ConfigError` naming the first option that
+    /// can't work: a scale that isn't a positive number, or a timeout of
+    /// zero, which no conversion could finish within.
+    pub fn build(self) -> OdeResult<ConversionConfig> {
+        let scale = self.config.output_scale;
+        if !(scale.is_finite() && scale > 0.0) {
+            return Err(OdeError::ConfigError(format!(
+                "Scale must be a positive number, not {}",
+                scale
+            )));
+        }
+        if self.config.timeout.is_some_and(|timeout| timeout.is_zero()) {
+            return Err(OdeError::ConfigError("Timeout must be longer than zero".to_string()));
+        }
+        Ok(self.config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_sets_options_and_checks_them() {
+        let config = ConversionConfig::builder()
+            .pages(PageSelection::parse("2,4-").unwrap())
+            .scale(1.5)
+            .timeout(Duration::from_secs(5))
+            .embedding(AssetEmbedding::External)
+            .parallel(true)
+            .build()
+            .unwrap();
+        assert_eq!(config.page_selection().page_indices(5), [1, 3, 4]);
+        assert_eq!(config.output_scale, 1.5);
+        assert_eq!(config.timeout, Some(Duration::from_secs(5)));
+        assert_eq!(config.asset_embedding, AssetEmbedding::External);
+        assert!(config.parallel);
+        // Everything else keeps its default
+        assert_eq!(config.column_gap, ConversionConfig::default().column_gap);
+
+        for scale in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            let built = ConversionConfig::builder().scale(scale).build();
+            assert!(matches!(built, Err(OdeError::ConfigError(_))), "{}", scale);
+        }
+        let built = ConversionConfig::builder().timeout(Duration::ZERO).build();
+        assert!(matches!(built, Err(OdeError::ConfigError(_))));
+    }
+
+    #[test]
+    fn test_page_selection_merges_overlapping_and_unordered_parts() {
+        let selection = PageSelection::parse("5-10, 3,1,8-12,3").unwrap();
+        assert_eq!(
+            selection.page_indices(20),
+            [0, 2, 4, 5, 6, 7, 8, 9, 10, 11]
+        );
+        assert_eq!(selection.to_string(), "5-10,3,1,8-12,3");
+    }
+
+    #[test]
+    fn test_page_selection_stops_at_the_page_count() {
+        let selection = PageSelection::parse("2,4-,-1,9-30").unwrap();
+        assert_eq!(selection.page_indices(6), [0, 1, 3, 4, 5]);
+        assert_eq!(PageSelection::from((2, usize::MAX)).page_indices(3), [1, 2]);
+        assert_eq!(PageSelection::from((7, 9)).page_indices(3), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_page_selection_rejects_malformed_text() {
+        for text in ["", "0", "3-1", "a", "1-2-3", ","] {
+            assert!(
+                matches!(PageSelection::parse(text), Err(OdeError::ConfigError(_))),
+                "{:?}",
+                text
+            );
         }
     }
 }
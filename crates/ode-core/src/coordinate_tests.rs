@@ -156,6 +156,17 @@ mod coordinate_accuracy_tests {
                 font_size: 12.0,
                 font_id: None,
                 color: "rgb(0,0,0)".to_string(),
+                covered: false,
+                scale_x: 1.0,
+                vertical: false,
+                width: 30.0,
+                height: 12.6,
+                underline: false,
+                line_through: false,
+                letter_spacing: 0.0,
+                word_spacing: 0.0,
+                lang: None,
+                mcid: None,
             },
             TextSpan {
                 text: "World".to_string(),
@@ -164,6 +175,17 @@ mod coordinate_accuracy_tests {
                 font_size: 12.0,
                 font_id: None,
                 color: "rgb(0,0,0)".to_string(),
+                covered: false,
+                scale_x: 1.0,
+                vertical: false,
+                width: 30.0,
+                height: 12.6,
+                underline: false,
+                line_through: false,
+                letter_spacing: 0.0,
+                word_spacing: 0.0,
+                lang: None,
+                mcid: None,
             },
         ];
 
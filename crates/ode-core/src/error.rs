@@ -2,10 +2,33 @@ use thiserror::Error;
 
 pub type OdeResult<T> = Result<T, OdeError>;
 
+/// What went wrong while parsing, so callers can tell a file that isn't a
+/// PDF from one this crate can't read yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// The input has no `%PDF-` header.
+    NotPdf,
+    /// The document is encrypted with a security handler that isn't supported.
+    Encrypted,
+    /// The file, or a stream in it, ends before its declared length.
+    TruncatedStream,
+    /// The cross-reference table or stream can't be read.
+    BadXref,
+    /// A stream the parser must read uses a filter that isn't built in.
+    UnsupportedFilter(String),
+    /// A stream's data doesn't decode with its filters.
+    CorruptStream,
+    /// Any other syntax the parser can't make sense of.
+    Malformed,
+}
+
 #[derive(Error, Debug)]
 pub enum OdeError {
-    #[error("PDF parsing error: {0}")]
-    PdfParseError(String),
+    #[error("PDF parsing error: {message}")]
+    PdfParseError {
+        kind: ParseErrorKind,
+        message: String,
+    },
 
     #[error("Font processing error: {0}")]
     FontError(String),
@@ -30,4 +53,31 @@ pub enum OdeError {
 
     #[error("Unsupported feature: {0}")]
     Unsupported(String),
+
+    #[error("Conversion cancelled")]
+    Cancelled,
+
+    #[error("Output exceeds the limit of {limit} bytes of fonts and images")]
+    OutputTooLarge { limit: usize },
+}
+
+impl OdeError {
+    pub fn parse(kind: ParseErrorKind, message: impl Into<String>) -> Self {
+        OdeError::PdfParseError {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    pub fn malformed(message: impl Into<String>) -> Self {
+        Self::parse(ParseErrorKind::Malformed, message)
+    }
+
+    /// The kind of a `PdfParseError`, `None` for every other error.
+    pub fn parse_error_kind(&self) -> Option<&ParseErrorKind> {
+        match self {
+            OdeError::PdfParseError { kind, .. } => Some(kind),
+            _ => None,
+        }
+    }
 }
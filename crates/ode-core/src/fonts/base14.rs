@@ -0,0 +1,234 @@
+/// The standard 14 fonts. Viewers must provide them, so PDFs may use them
+/// without embedding a font program or giving a `/Widths` array.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StandardFont {
+    Helvetica,
+    HelveticaBold,
+    HelveticaOblique,
+    HelveticaBoldOblique,
+    TimesRoman,
+    TimesBold,
+    TimesItalic,
+    TimesBoldItalic,
+    Courier,
+    CourierBold,
+    CourierOblique,
+    CourierBoldOblique,
+    Symbol,
+    ZapfDingbats,
+}
+
+impl StandardFont {
+    /// Recognize a `/BaseFont` name, ignoring any subset tag and accepting
+    /// the Arial, Times New Roman and Courier New aliases.
+    pub fn from_base_font(base_font: &str) -> Option<Self> {
+        let name = match base_font.split_once('+') {
+            Some((tag, name)) if tag.len() == 6 => name,
+            _ => base_font,
+        };
+        let name = name.to_ascii_lowercase();
+        let bold = name.contains("bold");
+        let italic = name.contains("italic") || name.contains("oblique");
+
+        let font = if name.starts_with("helvetica") || name.starts_with("arial") {
+            match (bold, italic) {
+                (false, false) => StandardFont::Helvetica,
+                (true, false) => StandardFont::HelveticaBold,
+                (false, true) => StandardFont::HelveticaOblique,
+                (true, true) => StandardFont::HelveticaBoldOblique,
+            }
+        } else if name.starts_with("times") {
+            match (bold, italic) {
+                (false, false) => StandardFont::TimesRoman,
+                (true, false) => StandardFont::TimesBold,
+                (false, true) => StandardFont::TimesItalic,
+                (true, true) => StandardFont::TimesBoldItalic,
+            }
+        } else if name.starts_with("courier") {
+            match (bold, italic) {
+                (false, false) => StandardFont::Courier,
+                (true, false) => StandardFont::CourierBold,
+                (false, true) => StandardFont::CourierOblique,
+                (true, true) => StandardFont::CourierBoldOblique,
+            }
+        } else if name.starts_with("symbol") {
+            StandardFont::Symbol
+        } else if name.starts_with("zapfdingbats") {
+            StandardFont::ZapfDingbats
+        } else {
+            return None;
+        };
+        Some(font)
+    }
+
+    /// AFM advance widths for every single-byte code, in thousandths of text
+    /// space units.
+    ///
+    /// Printable ASCII follows the font's built-in encoding, except that the
+    /// WinAnsi and MacRoman encodings put `quotesingle` and `grave` at 39 and
+    /// 96. Other codes, whose glyphs vary with the encoding, get the font's
+    /// average width.
+    pub fn widths(self, encoding: Option<&str>) -> Vec<f64> {
+        let printable: [u16; 95] = match self {
+            StandardFont::Helvetica | StandardFont::HelveticaOblique => HELVETICA,
+            StandardFont::HelveticaBold | StandardFont::HelveticaBoldOblique => HELVETICA_BOLD,
+            StandardFont::TimesRoman => TIMES_ROMAN,
+            StandardFont::TimesBold => TIMES_BOLD,
+            StandardFont::TimesItalic => TIMES_ITALIC,
+            StandardFont::TimesBoldItalic => TIMES_BOLD_ITALIC,
+            StandardFont::Courier
+            | StandardFont::CourierBold
+            | StandardFont::CourierOblique
+            | StandardFont::CourierBoldOblique => [600; 95],
+            StandardFont::Symbol => SYMBOL,
+            StandardFont::ZapfDingbats => ZAPF_DINGBATS,
+        };
+        let average = printable.iter().map(|w| *w as f64).sum::<f64>() / printable.len() as f64;
+
+        let mut widths = vec![average; 256];
+        for (code, width) in (32..=126).zip(printable) {
+            widths[code] = width as f64;
+        }
+        if matches!(encoding, Some("WinAnsiEncoding") | Some("MacRomanEncoding")) {
+            if let Some((quotesingle, grave)) = self.quote_widths() {
+                widths[39] = quotesingle;
+                widths[96] = grave;
+            }
+        }
+        widths
+    }
+
+    /// Widths of `quotesingle` and `grave`, for the fonts with a Latin set.
+    fn quote_widths(self) -> Option<(f64, f64)> {
+        match self {
+            StandardFont::Helvetica | StandardFont::HelveticaOblique => Some((191.0, 333.0)),
+            StandardFont::HelveticaBold | StandardFont::HelveticaBoldOblique => {
+                Some((238.0, 333.0))
+            }
+            StandardFont::TimesRoman => Some((180.0, 333.0)),
+            StandardFont::TimesItalic => Some((214.0, 333.0)),
+            StandardFont::TimesBold | StandardFont::TimesBoldItalic => Some((278.0, 333.0)),
+            StandardFont::Courier
+            | StandardFont::CourierBold
+            | StandardFont::CourierOblique
+            | StandardFont::CourierBoldOblique => Some((600.0, 600.0)),
+            StandardFont::Symbol | StandardFont::ZapfDingbats => None,
+        }
+    }
+}
+
+// AFM widths of codes 32..=126 under each font's built-in encoding
+// (StandardEncoding for the Latin fonts, whose Oblique variants share them).
+
+const HELVETICA: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 222, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667,
+    611, 778, 722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 278, 278, 278, 469, 556, 222, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500,
+    222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+const HELVETICA_BOLD: [u16; 95] = [
+    278, 333, 474, 556, 556, 889, 722, 278, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 333, 333, 584, 584, 584, 611, 975, 722, 722, 722, 722, 667,
+    611, 778, 722, 278, 556, 722, 611, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 333, 278, 333, 584, 556, 278, 556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556,
+    278, 889, 611, 611, 611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, 389, 280, 389, 584,
+];
+
+const TIMES_ROMAN: [u16; 95] = [
+    250, 333, 408, 500, 500, 833, 778, 333, 333, 333, 500, 564, 250, 333, 250, 278, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 278, 278, 564, 564, 564, 444, 921, 722, 667, 667, 722, 611,
+    556, 722, 722, 333, 389, 722, 611, 889, 722, 722, 556, 722, 667, 556, 611, 722, 722, 944, 722,
+    722, 611, 333, 278, 333, 469, 500, 333, 444, 500, 444, 500, 444, 333, 500, 500, 278, 278, 500,
+    278, 778, 500, 500, 500, 500, 333, 389, 278, 500, 500, 722, 500, 500, 444, 480, 200, 480, 541,
+];
+
+const TIMES_BOLD: [u16; 95] = [
+    250, 333, 555, 500, 500, 1000, 833, 333, 333, 333, 500, 570, 250, 333, 250, 278, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 333, 333, 570, 570, 570, 500, 930, 722, 667, 722, 722, 667,
+    611, 778, 778, 389, 500, 778, 667, 944, 722, 778, 611, 778, 722, 556, 667, 722, 722, 1000, 722,
+    722, 667, 333, 278, 333, 581, 500, 333, 500, 556, 444, 556, 444, 333, 500, 556, 278, 333, 556,
+    278, 833, 556, 500, 556, 556, 444, 389, 333, 556, 500, 722, 500, 500, 444, 394, 220, 394, 520,
+];
+
+const TIMES_ITALIC: [u16; 95] = [
+    250, 333, 420, 500, 500, 833, 778, 333, 333, 333, 500, 675, 250, 333, 250, 278, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 333, 333, 675, 675, 675, 500, 920, 611, 611, 667, 722, 611,
+    611, 722, 722, 333, 444, 667, 556, 833, 667, 722, 611, 722, 611, 500, 556, 722, 611, 833, 611,
+    556, 556, 389, 278, 389, 422, 500, 333, 500, 500, 444, 500, 444, 278, 500, 500, 278, 278, 444,
+    278, 722, 500, 500, 500, 500, 389, 389, 278, 500, 444, 667, 444, 444, 389, 400, 275, 400, 541,
+];
+
+const TIMES_BOLD_ITALIC: [u16; 95] = [
+    250, 389, 555, 500, 500, 833, 778, 333, 333, 333, 500, 570, 250, 333, 250, 278, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 333, 333, 570, 570, 570, 500, 832, 667, 667, 667, 722, 667,
+    667, 722, 778, 389, 500, 667, 611, 889, 722, 722, 611, 722, 667, 556, 611, 722, 667, 889, 667,
+    611, 611, 333, 278, 333, 570, 500, 333, 500, 500, 444, 500, 444, 333, 500, 556, 278, 278, 500,
+    278, 778, 556, 500, 500, 500, 389, 389, 278, 556, 444, 667, 500, 444, 389, 348, 220, 348, 570,
+];
+
+const SYMBOL: [u16; 95] = [
+    250, 333, 713, 500, 549, 833, 778, 439, 333, 333, 500, 549, 250, 549, 250, 278, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 278, 278, 549, 549, 549, 444, 549, 722, 667, 722, 612, 611,
+    763, 603, 722, 333, 631, 722, 686, 889, 722, 722, 768, 741, 556, 592, 611, 690, 439, 768, 645,
+    795, 611, 333, 863, 333, 658, 500, 500, 631, 549, 549, 494, 439, 521, 411, 603, 329, 603, 549,
+    549, 576, 521, 549, 549, 521, 549, 603, 439, 576, 713, 686, 493, 686, 494, 480, 200, 480, 549,
+];
+
+const ZAPF_DINGBATS: [u16; 95] = [
+    278, 974, 961, 974, 980, 719, 789, 790, 791, 690, 960, 939, 549, 855, 911, 933, 911, 945, 974,
+    755, 846, 762, 761, 571, 677, 763, 760, 759, 754, 494, 552, 537, 577, 692, 786, 788, 788, 790,
+    793, 794, 816, 823, 789, 841, 823, 833, 816, 831, 923, 744, 723, 749, 790, 792, 695, 776, 768,
+    792, 759, 707, 708, 682, 701, 826, 815, 789, 789, 707, 687, 696, 689, 786, 787, 713, 791, 785,
+    791, 873, 761, 762, 762, 759, 759, 892, 892, 788, 784, 438, 138, 277, 415, 392, 392, 668, 668,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base_font_names_and_aliases() {
+        assert_eq!(
+            StandardFont::from_base_font("Helvetica-BoldOblique"),
+            Some(StandardFont::HelveticaBoldOblique)
+        );
+        assert_eq!(
+            StandardFont::from_base_font("ABCDEF+Arial,Bold"),
+            Some(StandardFont::HelveticaBold)
+        );
+        assert_eq!(
+            StandardFont::from_base_font("TimesNewRomanPS-ItalicMT"),
+            Some(StandardFont::TimesItalic)
+        );
+        assert_eq!(
+            StandardFont::from_base_font("Times-Roman"),
+            Some(StandardFont::TimesRoman)
+        );
+        assert_eq!(
+            StandardFont::from_base_font("CourierNewPSMT"),
+            Some(StandardFont::Courier)
+        );
+        assert_eq!(StandardFont::from_base_font("Garamond"), None);
+    }
+
+    #[test]
+    fn test_afm_widths() {
+        let helvetica = StandardFont::Helvetica.widths(None);
+        assert_eq!(helvetica[b' ' as usize], 278.0);
+        assert_eq!(helvetica[b'W' as usize], 944.0);
+        assert_eq!(helvetica[b'i' as usize], 222.0);
+        assert_eq!(helvetica[b'\'' as usize], 222.0);
+
+        let win_ansi = StandardFont::Helvetica.widths(Some("WinAnsiEncoding"));
+        assert_eq!(win_ansi[b'\'' as usize], 191.0);
+        assert_eq!(win_ansi[b'`' as usize], 333.0);
+
+        assert!(StandardFont::CourierBold
+            .widths(None)
+            .iter()
+            .all(|w| *w == 600.0));
+        assert_eq!(StandardFont::TimesBold.widths(None)[b'%' as usize], 1000.0);
+    }
+}
@@ -0,0 +1,357 @@
+use crate::parser::{Dictionary, ObjectReference, PdfObject, PdfRefResolver};
+use std::collections::BTreeMap;
+
+/// Width of CIDs not covered by `/W` when the CIDFont has no `/DW`.
+const DEFAULT_CID_WIDTH: f64 = 1000.0;
+
+/// Vertical displacement of CIDs not covered by `/W2` when the CIDFont has
+/// no `/DW2`: one em downwards.
+const DEFAULT_CID_DISPLACEMENT: f64 = -1000.0;
+
+/// The descendant CIDFont of a `/Type0` font, which carries the glyph
+/// metrics and font program that the Type0 dictionary itself lacks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CidFont {
+    /// `CIDFontType0` (CFF outlines) or `CIDFontType2` (TrueType outlines).
+    pub subtype: String,
+    pub font_descriptor: Option<ObjectReference>,
+    pub widths: CidWidths,
+    /// Vertical displacements from `/W2` and `/DW2`, when the Type0 font's
+    /// CMap writes top to bottom (`/WMode 1`).
+    pub vertical: Option<CidWidths>,
+    pub cid_to_gid: CidToGidMap,
+}
+
+impl CidFont {
+    /// Follow a Type0 font's `/DescendantFonts[0]`; `None` for any other font.
+    pub fn from_type0(font_dict: &Dictionary, resolver: &PdfRefResolver) -> Option<Self> {
+        if font_dict.get("Subtype").and_then(|s| s.as_name()) != Some("Type0") {
+            return None;
+        }
+        let descendant = match resolve(font_dict.get("DescendantFonts")?, resolver)? {
+            PdfObject::Array(arr) => resolve(arr.first()?, resolver)?,
+            // Some producers write the descendant directly instead of in an array
+            other => other,
+        };
+        let cid_dict = match descendant {
+            PdfObject::Dictionary(dict) => dict,
+            _ => return None,
+        };
+
+        let default_width = cid_dict
+            .get("DW")
+            .and_then(|dw| resolve(dw, resolver))
+            .and_then(|dw| dw.as_number())
+            .unwrap_or(DEFAULT_CID_WIDTH);
+        let widths = match cid_dict.get("W").and_then(|w| resolve(w, resolver)) {
+            Some(PdfObject::Array(w)) => CidWidths::parse(&w, default_width, resolver),
+            _ => CidWidths::new(default_width),
+        };
+        let cid_to_gid = match cid_dict
+            .get("CIDToGIDMap")
+            .and_then(|m| resolve(m, resolver))
+        {
+            Some(PdfObject::Stream(data, _)) => CidToGidMap::Table(
+                data.chunks_exact(2)
+                    .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                    .collect(),
+            ),
+            _ => CidToGidMap::Identity,
+        };
+        let vertical = is_vertical(font_dict, resolver).then(|| {
+            // /DW2 is [v_y w1_y]; only the displacement is needed
+            let default = match cid_dict.get("DW2").and_then(|dw2| resolve(dw2, resolver)) {
+                Some(PdfObject::Array(dw2)) => dw2.get(1).and_then(|w| w.as_number()),
+                _ => None,
+            };
+            let default = default.unwrap_or(DEFAULT_CID_DISPLACEMENT);
+            match cid_dict.get("W2").and_then(|w2| resolve(w2, resolver)) {
+                Some(PdfObject::Array(w2)) => CidWidths::parse_vertical(&w2, default, resolver),
+                _ => CidWidths::new(default),
+            }
+        });
+
+        Some(Self {
+            subtype: cid_dict
+                .get("Subtype")
+                .and_then(|s| s.as_name())
+                .unwrap_or("CIDFontType2")
+                .to_string(),
+            font_descriptor: cid_dict
+                .get("FontDescriptor")
+                .and_then(|d| d.as_reference()),
+            widths,
+            vertical,
+            cid_to_gid,
+        })
+    }
+}
+
+/// Whether a Type0 font's `/Encoding` CMap is vertical: a predefined one
+/// named `...-V`, or an embedded one with `/WMode 1`.
+fn is_vertical(font_dict: &Dictionary, resolver: &PdfRefResolver) -> bool {
+    match font_dict.get("Encoding").and_then(|e| resolve(e, resolver)) {
+        Some(PdfObject::Name(name)) => name.ends_with("-V"),
+        Some(PdfObject::Stream(_, dict)) => {
+            dict.get("WMode").and_then(|m| m.as_number()) == Some(1.0)
+        }
+        _ => false,
+    }
+}
+
+/// Glyph widths from a CIDFont's `/W` array, in thousandths of text space units.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CidWidths {
+    default_width: f64,
+    /// Runs keyed by their first CID: the last CID covered and either one
+    /// width per CID or a single width shared by the whole range.
+    runs: BTreeMap<u32, (u32, Vec<f64>)>,
+}
+
+impl CidWidths {
+    /// Every CID gets `default_width`.
+    pub fn new(default_width: f64) -> Self {
+        Self {
+            default_width,
+            runs: BTreeMap::new(),
+        }
+    }
+
+    /// Parse `/W`, whose entries are `c [w1 w2 ...]` or `c_first c_last w`.
+    /// Parsing stops at the first malformed entry.
+    pub fn parse(w: &[PdfObject], default_width: f64, resolver: &PdfRefResolver) -> Self {
+        Self::parse_metrics(w, default_width, 1, resolver)
+    }
+
+    /// Parse the vertical displacements `w1_y` out of `/W2`, whose entries
+    /// are `c [w1_y v_x v_y ...]` or `c_first c_last w1_y v_x v_y`.
+    pub fn parse_vertical(
+        w2: &[PdfObject],
+        default_displacement: f64,
+        resolver: &PdfRefResolver,
+    ) -> Self {
+        Self::parse_metrics(w2, default_displacement, 3, resolver)
+    }
+
+    /// `/W` or `/W2`, with `per_cid` numbers for each CID of which the first
+    /// is kept.
+    fn parse_metrics(
+        w: &[PdfObject],
+        default_width: f64,
+        per_cid: usize,
+        resolver: &PdfRefResolver,
+    ) -> Self {
+        let mut widths = Self::new(default_width);
+        let number = |obj: &PdfObject| resolve(obj, resolver).and_then(|v| v.as_number());
+
+        let mut i = 0;
+        while i < w.len() {
+            let first = match number(&w[i]) {
+                Some(first) if first >= 0.0 => first as u32,
+                _ => break,
+            };
+            match w.get(i + 1).and_then(|obj| resolve(obj, resolver)) {
+                Some(PdfObject::Array(list)) => {
+                    let run: Vec<f64> = list.iter().filter_map(number).step_by(per_cid).collect();
+                    let last = u32::try_from(run.len())
+                        .ok()
+                        .and_then(|len| first.checked_add(len.checked_sub(1)?));
+                    if let Some(last) = last {
+                        widths.runs.insert(first, (last, run));
+                    }
+                    i += 2;
+                }
+                Some(last) => {
+                    let (Some(last), Some(width)) =
+                        (last.as_number(), w.get(i + 2).and_then(number))
+                    else {
+                        break;
+                    };
+                    if last >= first as f64 {
+                        widths.runs.insert(first, (last as u32, vec![width]));
+                    }
+                    i += 2 + per_cid;
+                }
+                None => break,
+            }
+        }
+        widths
+    }
+
+    pub fn default_width(&self) -> f64 {
+        self.default_width
+    }
+
+    /// The width of `cid`, falling back to `/DW`.
+    pub fn width(&self, cid: u32) -> f64 {
+        match self.runs.range(..=cid).next_back() {
+            Some((&first, (last, run))) if cid <= *last => match run.as_slice() {
+                [shared] => *shared,
+                run => run[(cid - first) as usize],
+            },
+            _ => self.default_width,
+        }
+    }
+}
+
+/// Maps CIDs to glyph indices in a `CIDFontType2` font program.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CidToGidMap {
+    Identity,
+    /// Glyph index of each CID, from the `/CIDToGIDMap` stream.
+    Table(Vec<u16>),
+}
+
+impl CidToGidMap {
+    /// The glyph for `cid`, or `.notdef` (0) when the table doesn't cover it.
+    pub fn gid(&self, cid: u32) -> u32 {
+        match self {
+            CidToGidMap::Identity => cid,
+            CidToGidMap::Table(table) => table.get(cid as usize).map_or(0, |gid| *gid as u32),
+        }
+    }
+}
+
+pub(super) fn resolve(obj: &PdfObject, resolver: &PdfRefResolver) -> Option<PdfObject> {
+    match obj.as_reference() {
+        Some(r) => resolver.dereference(r),
+        None => Some(obj.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::XRef;
+
+    fn name(s: &str) -> PdfObject {
+        PdfObject::Name(s.to_string())
+    }
+
+    fn dict(entries: Vec<(&str, PdfObject)>) -> Dictionary {
+        Dictionary {
+            entries: entries
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+        }
+    }
+
+    fn numbers(values: &[i64]) -> Vec<PdfObject> {
+        values.iter().map(|v| PdfObject::Integer(*v)).collect()
+    }
+
+    #[test]
+    fn test_w_array_lists_and_ranges() {
+        let xref = XRef::default();
+        let resolver = PdfRefResolver::new(b"", &xref);
+        let mut w = numbers(&[1]);
+        w.push(PdfObject::Array(numbers(&[500, 600, 700])));
+        w.extend(numbers(&[10, 20, 250]));
+
+        let widths = CidWidths::parse(&w, 1000.0, &resolver);
+        assert_eq!(widths.width(1), 500.0);
+        assert_eq!(widths.width(3), 700.0);
+        assert_eq!(widths.width(10), 250.0);
+        assert_eq!(widths.width(20), 250.0);
+        assert_eq!(widths.width(4), 1000.0);
+        assert_eq!(widths.width(21), 1000.0);
+    }
+
+    #[test]
+    fn test_w_array_skips_a_list_running_past_the_last_cid() {
+        let xref = XRef::default();
+        let resolver = PdfRefResolver::new(b"", &xref);
+        let mut w = numbers(&[u32::MAX as i64]);
+        w.push(PdfObject::Array(numbers(&[500, 600])));
+        w.extend(numbers(&[10, 20, 250]));
+
+        let widths = CidWidths::parse(&w, 1000.0, &resolver);
+        assert_eq!(widths.width(u32::MAX), 1000.0);
+        assert_eq!(widths.width(10), 250.0);
+    }
+
+    #[test]
+    fn test_type0_descendant_font() {
+        let xref = XRef::default();
+        let resolver = PdfRefResolver::new(b"", &xref);
+        let mut w = numbers(&[3]);
+        w.push(PdfObject::Array(numbers(&[278])));
+        let descendant = dict(vec![
+            ("Type", name("Font")),
+            ("Subtype", name("CIDFontType2")),
+            (
+                "FontDescriptor",
+                PdfObject::IndirectReference { obj_id: 9, gen: 0 },
+            ),
+            ("DW", PdfObject::Integer(500)),
+            ("W", PdfObject::Array(w)),
+            (
+                "CIDToGIDMap",
+                PdfObject::Stream(
+                    vec![0, 0, 0, 7, 0, 9],
+                    Dictionary {
+                        entries: Vec::new(),
+                    },
+                ),
+            ),
+        ]);
+        let type0 = dict(vec![
+            ("Subtype", name("Type0")),
+            ("Encoding", name("Identity-H")),
+            (
+                "DescendantFonts",
+                PdfObject::Array(vec![PdfObject::Dictionary(descendant)]),
+            ),
+        ]);
+
+        let cid_font = CidFont::from_type0(&type0, &resolver).unwrap();
+        assert_eq!(cid_font.subtype, "CIDFontType2");
+        assert_eq!(cid_font.font_descriptor, Some(ObjectReference(9, 0)));
+        assert_eq!(cid_font.widths.width(3), 278.0);
+        assert_eq!(cid_font.widths.width(4), 500.0);
+        assert_eq!(cid_font.cid_to_gid.gid(1), 7);
+        assert_eq!(cid_font.cid_to_gid.gid(5), 0);
+    }
+
+    #[test]
+    fn test_vertical_cmap_reads_w2_displacements() {
+        let xref = XRef::default();
+        let resolver = PdfRefResolver::new(b"", &xref);
+        let mut w2 = numbers(&[1]);
+        w2.push(PdfObject::Array(numbers(&[-500, 250, 880, -800, 250, 880])));
+        w2.extend(numbers(&[10, 20, -600, 500, 900]));
+        let descendant = dict(vec![
+            ("Subtype", name("CIDFontType0")),
+            ("DW2", PdfObject::Array(numbers(&[880, -900]))),
+            ("W2", PdfObject::Array(w2)),
+        ]);
+        let type0 = |encoding| {
+            dict(vec![
+                ("Subtype", name("Type0")),
+                ("Encoding", name(encoding)),
+                ("DescendantFonts", PdfObject::Dictionary(descendant.clone())),
+            ])
+        };
+
+        let vertical = CidFont::from_type0(&type0("Identity-V"), &resolver)
+            .unwrap()
+            .vertical
+            .unwrap();
+        assert_eq!(vertical.width(1), -500.0);
+        assert_eq!(vertical.width(2), -800.0);
+        assert_eq!(vertical.width(15), -600.0);
+        assert_eq!(vertical.width(3), -900.0);
+
+        let horizontal = CidFont::from_type0(&type0("Identity-H"), &resolver).unwrap();
+        assert_eq!(horizontal.vertical, None);
+    }
+
+    #[test]
+    fn test_simple_font_has_no_descendant() {
+        let xref = XRef::default();
+        let resolver = PdfRefResolver::new(b"", &xref);
+        let font = dict(vec![("Subtype", name("TrueType"))]);
+        assert!(CidFont::from_type0(&font, &resolver).is_none());
+    }
+}
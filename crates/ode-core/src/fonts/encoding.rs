@@ -0,0 +1,470 @@
+use std::collections::HashMap;
+
+/// The base encodings a simple font's `/Encoding` can name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BaseEncoding {
+    Standard,
+    WinAnsi,
+    MacRoman,
+}
+
+impl BaseEncoding {
+    /// `StandardEncoding`, `WinAnsiEncoding` or `MacRomanEncoding`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "StandardEncoding" => Some(BaseEncoding::Standard),
+            "WinAnsiEncoding" => Some(BaseEncoding::WinAnsi),
+            "MacRomanEncoding" => Some(BaseEncoding::MacRoman),
+            _ => None,
+        }
+    }
+
+    /// The character `code` stands for, when the encoding defines one.
+    pub fn char(self, code: u8) -> Option<char> {
+        let defined = |c: char| (c != '\0').then_some(c);
+        match (self, code) {
+            (_, 0..=31 | 127) => None,
+            (BaseEncoding::Standard, 39) => Some('\u{2019}'),
+            (BaseEncoding::Standard, 96) => Some('\u{2018}'),
+            (_, 32..=126) => Some(code as char),
+            (BaseEncoding::Standard, 0x80..=0x9F) => None,
+            (BaseEncoding::Standard, _) => defined(STANDARD_A0[usize::from(code - 0xA0)]),
+            (BaseEncoding::WinAnsi, 0x80..=0x9F) => defined(WIN_ANSI_80[usize::from(code - 0x80)]),
+            // The rest of WinAnsi is Latin-1
+            (BaseEncoding::WinAnsi, _) => Some(code as char),
+            (BaseEncoding::MacRoman, _) => defined(MAC_ROMAN_HIGH[usize::from(code - 0x80)]),
+        }
+    }
+}
+
+/// The text each code of a simple font stands for: `base`, if any, with the
+/// glyphs `differences` names instead for the codes it lists. Codes naming
+/// a glyph with no known character are left out.
+pub fn simple_font_unicode(
+    base: Option<BaseEncoding>,
+    differences: &[(u8, String)],
+) -> HashMap<u16, String> {
+    let mut map: HashMap<u16, String> = (0..=255u8)
+        .filter_map(|code| Some((u16::from(code), base?.char(code)?.to_string())))
+        .collect();
+    for (code, name) in differences {
+        match glyph_name_to_unicode(name) {
+            Some(text) => map.insert(u16::from(*code), text),
+            None => map.remove(&u16::from(*code)),
+        };
+    }
+    map
+}
+
+/// The text a glyph name stands for, by the Adobe Glyph List conventions:
+/// names from the list, `uniXXXX` (one or more four-digit groups) and
+/// `uXXXX` to `uXXXXXX`, with `_` joining the parts of a ligature and
+/// anything after a `.` naming a variant of the same character.
+pub fn glyph_name_to_unicode(name: &str) -> Option<String> {
+    let name = name.split('.').next().unwrap_or_default();
+    if name.is_empty() {
+        return None;
+    }
+    name.split('_').map(glyph_component_to_unicode).collect()
+}
+
+fn glyph_component_to_unicode(name: &str) -> Option<String> {
+    if name.len() == 1 && name.as_bytes()[0].is_ascii_alphabetic() {
+        return Some(name.to_string());
+    }
+    if let Ok(i) = GLYPH_LIST.binary_search_by(|(glyph, _)| (*glyph).cmp(name)) {
+        return Some(GLYPH_LIST[i].1.to_string());
+    }
+    let hex_char = |digits: &str| {
+        let code = u32::from_str_radix(digits, 16).ok()?;
+        // Surrogates aren't characters
+        char::from_u32(code)
+    };
+    let is_hex = |digits: &str| {
+        digits
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'A'..=b'F').contains(&b))
+    };
+    if let Some(digits) = name.strip_prefix("uni") {
+        if !digits.is_empty() && digits.len() % 4 == 0 && is_hex(digits) {
+            return (0..digits.len())
+                .step_by(4)
+                .map(|i| hex_char(&digits[i..i + 4]))
+                .collect();
+        }
+    }
+    if let Some(digits) = name.strip_prefix('u') {
+        if (4..=6).contains(&digits.len()) && is_hex(digits) {
+            return hex_char(digits).map(String::from);
+        }
+    }
+    None
+}
+
+/// `StandardEncoding` from 0xA0 up; `'\0'` where it defines nothing.
+const STANDARD_A0: [char; 96] = [
+    '\0', '¡', '¢', '£', '⁄', '¥', 'ƒ', '§', '¤', '\'', '“', '«', '‹', '›', 'ﬁ', 'ﬂ', '\0', '–',
+    '†', '‡', '·', '\0', '¶', '•', '‚', '„', '”', '»', '…', '‰', '\0', '¿', '\0', '`', '´', 'ˆ',
+    '˜', '¯', '˘', '˙', '¨', '\0', '˚', '¸', '\0', '˝', '˛', 'ˇ', '—', '\0', '\0', '\0', '\0',
+    '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', 'Æ', '\0', 'ª', '\0',
+    '\0', '\0', '\0', 'Ł', 'Ø', 'Œ', 'º', '\0', '\0', '\0', '\0', '\0', 'æ', '\0', '\0', '\0', 'ı',
+    '\0', '\0', 'ł', 'ø', 'œ', 'ß', '\0', '\0', '\0', '\0',
+];
+
+/// `WinAnsiEncoding` from 0x80 to 0x9F, where it departs from Latin-1;
+/// `'\0'` where it defines nothing.
+const WIN_ANSI_80: [char; 32] = [
+    '€', '\0', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\0', 'Ž', '\0', '\0', '‘',
+    '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\0', 'ž', 'Ÿ',
+];
+
+/// `MacRomanEncoding` from 0x80 up; `'\0'` where it defines nothing.
+const MAC_ROMAN_HIGH: [char; 128] = [
+    'Ä', 'Å', 'Ç', 'É', 'Ñ', 'Ö', 'Ü', 'á', 'à', 'â', 'ä', 'ã', 'å', 'ç', 'é', 'è', 'ê', 'ë', 'í',
+    'ì', 'î', 'ï', 'ñ', 'ó', 'ò', 'ô', 'ö', 'õ', 'ú', 'ù', 'û', 'ü', '†', '°', '¢', '£', '§', '•',
+    '¶', 'ß', '®', '©', '™', '´', '¨', '≠', 'Æ', 'Ø', '∞', '±', '≤', '≥', '¥', 'µ', '∂', '∑', '∏',
+    'π', '∫', 'ª', 'º', 'Ω', 'æ', 'ø', '¿', '¡', '¬', '√', 'ƒ', '≈', '∆', '«', '»', '…', '\u{A0}',
+    'À', 'Ã', 'Õ', 'Œ', 'œ', '–', '—', '“', '”', '‘', '’', '÷', '◊', 'ÿ', 'Ÿ', '⁄', '¤', '‹', '›',
+    'ﬁ', 'ﬂ', '‡', '·', '‚', '„', '‰', 'Â', 'Ê', 'Á', 'Ë', 'È', 'Í', 'Î', 'Ï', 'Ì', 'Ó', 'Ô', '\0',
+    'Ò', 'Ú', 'Û', 'Ù', 'ı', 'ˆ', '˜', '¯', '˘', '˙', '˚', '¸', '˝', '˛', 'ˇ',
+];
+
+/// The Adobe Glyph List entries for the glyphs of the base encodings, Greek
+/// and the common math symbols, sorted by name. Single letters are handled
+/// without a lookup.
+const GLYPH_LIST: &[(&str, char)] = &[
+    ("AE", '\u{00C6}'),
+    ("Aacute", '\u{00C1}'),
+    ("Acircumflex", '\u{00C2}'),
+    ("Adieresis", '\u{00C4}'),
+    ("Agrave", '\u{00C0}'),
+    ("Alpha", '\u{0391}'),
+    ("Aring", '\u{00C5}'),
+    ("Atilde", '\u{00C3}'),
+    ("Beta", '\u{0392}'),
+    ("Ccedilla", '\u{00C7}'),
+    ("Chi", '\u{03A7}'),
+    ("Dcroat", '\u{0110}'),
+    ("Delta", '\u{0394}'),
+    ("Eacute", '\u{00C9}'),
+    ("Ecircumflex", '\u{00CA}'),
+    ("Edieresis", '\u{00CB}'),
+    ("Egrave", '\u{00C8}'),
+    ("Epsilon", '\u{0395}'),
+    ("Eta", '\u{0397}'),
+    ("Eth", '\u{00D0}'),
+    ("Euro", '\u{20AC}'),
+    ("Gamma", '\u{0393}'),
+    ("Gbreve", '\u{011E}'),
+    ("Iacute", '\u{00CD}'),
+    ("Icircumflex", '\u{00CE}'),
+    ("Idieresis", '\u{00CF}'),
+    ("Idotaccent", '\u{0130}'),
+    ("Igrave", '\u{00CC}'),
+    ("Iota", '\u{0399}'),
+    ("Kappa", '\u{039A}'),
+    ("Lambda", '\u{039B}'),
+    ("Lslash", '\u{0141}'),
+    ("Mu", '\u{039C}'),
+    ("Ntilde", '\u{00D1}'),
+    ("Nu", '\u{039D}'),
+    ("OE", '\u{0152}'),
+    ("Oacute", '\u{00D3}'),
+    ("Ocircumflex", '\u{00D4}'),
+    ("Odieresis", '\u{00D6}'),
+    ("Ograve", '\u{00D2}'),
+    ("Omega", '\u{03A9}'),
+    ("Omicron", '\u{039F}'),
+    ("Oslash", '\u{00D8}'),
+    ("Otilde", '\u{00D5}'),
+    ("Phi", '\u{03A6}'),
+    ("Pi", '\u{03A0}'),
+    ("Psi", '\u{03A8}'),
+    ("Rho", '\u{03A1}'),
+    ("Scaron", '\u{0160}'),
+    ("Scedilla", '\u{015E}'),
+    ("Sigma", '\u{03A3}'),
+    ("Tau", '\u{03A4}'),
+    ("Theta", '\u{0398}'),
+    ("Thorn", '\u{00DE}'),
+    ("Uacute", '\u{00DA}'),
+    ("Ucircumflex", '\u{00DB}'),
+    ("Udieresis", '\u{00DC}'),
+    ("Ugrave", '\u{00D9}'),
+    ("Upsilon", '\u{03A5}'),
+    ("Xi", '\u{039E}'),
+    ("Yacute", '\u{00DD}'),
+    ("Ydieresis", '\u{0178}'),
+    ("Zcaron", '\u{017D}'),
+    ("Zeta", '\u{0396}'),
+    ("aacute", '\u{00E1}'),
+    ("acircumflex", '\u{00E2}'),
+    ("acute", '\u{00B4}'),
+    ("adieresis", '\u{00E4}'),
+    ("ae", '\u{00E6}'),
+    ("agrave", '\u{00E0}'),
+    ("aleph", '\u{2135}'),
+    ("alpha", '\u{03B1}'),
+    ("ampersand", '\u{0026}'),
+    ("angle", '\u{2220}'),
+    ("approxequal", '\u{2248}'),
+    ("aring", '\u{00E5}'),
+    ("arrowboth", '\u{2194}'),
+    ("arrowdblboth", '\u{21D4}'),
+    ("arrowdblleft", '\u{21D0}'),
+    ("arrowdblright", '\u{21D2}'),
+    ("arrowdown", '\u{2193}'),
+    ("arrowleft", '\u{2190}'),
+    ("arrowright", '\u{2192}'),
+    ("arrowup", '\u{2191}'),
+    ("asciicircum", '\u{005E}'),
+    ("asciitilde", '\u{007E}'),
+    ("asterisk", '\u{002A}'),
+    ("asteriskmath", '\u{2217}'),
+    ("at", '\u{0040}'),
+    ("atilde", '\u{00E3}'),
+    ("backslash", '\u{005C}'),
+    ("bar", '\u{007C}'),
+    ("beta", '\u{03B2}'),
+    ("braceleft", '\u{007B}'),
+    ("braceright", '\u{007D}'),
+    ("bracketleft", '\u{005B}'),
+    ("bracketright", '\u{005D}'),
+    ("breve", '\u{02D8}'),
+    ("brokenbar", '\u{00A6}'),
+    ("bullet", '\u{2022}'),
+    ("caron", '\u{02C7}'),
+    ("ccedilla", '\u{00E7}'),
+    ("cedilla", '\u{00B8}'),
+    ("cent", '\u{00A2}'),
+    ("chi", '\u{03C7}'),
+    ("circlemultiply", '\u{2297}'),
+    ("circleplus", '\u{2295}'),
+    ("circumflex", '\u{02C6}'),
+    ("colon", '\u{003A}'),
+    ("comma", '\u{002C}'),
+    ("congruent", '\u{2245}'),
+    ("copyright", '\u{00A9}'),
+    ("currency", '\u{00A4}'),
+    ("dagger", '\u{2020}'),
+    ("daggerdbl", '\u{2021}'),
+    ("dcroat", '\u{0111}'),
+    ("degree", '\u{00B0}'),
+    ("delta", '\u{03B4}'),
+    ("dieresis", '\u{00A8}'),
+    ("divide", '\u{00F7}'),
+    ("dollar", '\u{0024}'),
+    ("dotaccent", '\u{02D9}'),
+    ("dotlessi", '\u{0131}'),
+    ("dotlessj", '\u{0237}'),
+    ("dotmath", '\u{22C5}'),
+    ("eacute", '\u{00E9}'),
+    ("ecircumflex", '\u{00EA}'),
+    ("edieresis", '\u{00EB}'),
+    ("egrave", '\u{00E8}'),
+    ("eight", '\u{0038}'),
+    ("element", '\u{2208}'),
+    ("ellipsis", '\u{2026}'),
+    ("emdash", '\u{2014}'),
+    ("emptyset", '\u{2205}'),
+    ("endash", '\u{2013}'),
+    ("epsilon", '\u{03B5}'),
+    ("equal", '\u{003D}'),
+    ("equivalence", '\u{2261}'),
+    ("eta", '\u{03B7}'),
+    ("eth", '\u{00F0}'),
+    ("exclam", '\u{0021}'),
+    ("exclamdbl", '\u{203C}'),
+    ("exclamdown", '\u{00A1}'),
+    ("existential", '\u{2203}'),
+    ("ff", '\u{FB00}'),
+    ("ffi", '\u{FB03}'),
+    ("ffl", '\u{FB04}'),
+    ("fi", '\u{FB01}'),
+    ("figuredash", '\u{2012}'),
+    ("five", '\u{0035}'),
+    ("fl", '\u{FB02}'),
+    ("florin", '\u{0192}'),
+    ("four", '\u{0034}'),
+    ("fraction", '\u{2044}'),
+    ("gamma", '\u{03B3}'),
+    ("gbreve", '\u{011F}'),
+    ("germandbls", '\u{00DF}'),
+    ("gradient", '\u{2207}'),
+    ("grave", '\u{0060}'),
+    ("greater", '\u{003E}'),
+    ("greaterequal", '\u{2265}'),
+    ("guillemotleft", '\u{00AB}'),
+    ("guillemotright", '\u{00BB}'),
+    ("guilsinglleft", '\u{2039}'),
+    ("guilsinglright", '\u{203A}'),
+    ("hungarumlaut", '\u{02DD}'),
+    ("hyphen", '\u{002D}'),
+    ("iacute", '\u{00ED}'),
+    ("icircumflex", '\u{00EE}'),
+    ("idieresis", '\u{00EF}'),
+    ("igrave", '\u{00EC}'),
+    ("infinity", '\u{221E}'),
+    ("integral", '\u{222B}'),
+    ("intersection", '\u{2229}'),
+    ("iota", '\u{03B9}'),
+    ("kappa", '\u{03BA}'),
+    ("lambda", '\u{03BB}'),
+    ("less", '\u{003C}'),
+    ("lessequal", '\u{2264}'),
+    ("logicaland", '\u{2227}'),
+    ("logicalnot", '\u{00AC}'),
+    ("logicalor", '\u{2228}'),
+    ("lozenge", '\u{25CA}'),
+    ("lslash", '\u{0142}'),
+    ("macron", '\u{00AF}'),
+    ("minus", '\u{2212}'),
+    ("minute", '\u{2032}'),
+    ("mu", '\u{03BC}'),
+    ("multiply", '\u{00D7}'),
+    ("nbspace", '\u{00A0}'),
+    ("nine", '\u{0039}'),
+    ("notelement", '\u{2209}'),
+    ("notequal", '\u{2260}'),
+    ("ntilde", '\u{00F1}'),
+    ("nu", '\u{03BD}'),
+    ("numbersign", '\u{0023}'),
+    ("oacute", '\u{00F3}'),
+    ("ocircumflex", '\u{00F4}'),
+    ("odieresis", '\u{00F6}'),
+    ("oe", '\u{0153}'),
+    ("ogonek", '\u{02DB}'),
+    ("ograve", '\u{00F2}'),
+    ("omega", '\u{03C9}'),
+    ("omega1", '\u{03D6}'),
+    ("omicron", '\u{03BF}'),
+    ("one", '\u{0031}'),
+    ("onedotenleader", '\u{2024}'),
+    ("onehalf", '\u{00BD}'),
+    ("onequarter", '\u{00BC}'),
+    ("onesuperior", '\u{00B9}'),
+    ("ordfeminine", '\u{00AA}'),
+    ("ordmasculine", '\u{00BA}'),
+    ("oslash", '\u{00F8}'),
+    ("otilde", '\u{00F5}'),
+    ("paragraph", '\u{00B6}'),
+    ("parenleft", '\u{0028}'),
+    ("parenright", '\u{0029}'),
+    ("partialdiff", '\u{2202}'),
+    ("percent", '\u{0025}'),
+    ("period", '\u{002E}'),
+    ("periodcentered", '\u{00B7}'),
+    ("perpendicular", '\u{22A5}'),
+    ("perthousand", '\u{2030}'),
+    ("phi", '\u{03C6}'),
+    ("phi1", '\u{03D5}'),
+    ("pi", '\u{03C0}'),
+    ("plus", '\u{002B}'),
+    ("plusminus", '\u{00B1}'),
+    ("product", '\u{220F}'),
+    ("propersubset", '\u{2282}'),
+    ("propersuperset", '\u{2283}'),
+    ("proportional", '\u{221D}'),
+    ("psi", '\u{03C8}'),
+    ("question", '\u{003F}'),
+    ("questiondown", '\u{00BF}'),
+    ("quotedbl", '\u{0022}'),
+    ("quotedblbase", '\u{201E}'),
+    ("quotedblleft", '\u{201C}'),
+    ("quotedblright", '\u{201D}'),
+    ("quoteleft", '\u{2018}'),
+    ("quotereversed", '\u{201B}'),
+    ("quoteright", '\u{2019}'),
+    ("quotesinglbase", '\u{201A}'),
+    ("quotesingle", '\u{0027}'),
+    ("radical", '\u{221A}'),
+    ("reflexsubset", '\u{2286}'),
+    ("reflexsuperset", '\u{2287}'),
+    ("registered", '\u{00AE}'),
+    ("rho", '\u{03C1}'),
+    ("ring", '\u{02DA}'),
+    ("scaron", '\u{0161}'),
+    ("scedilla", '\u{015F}'),
+    ("second", '\u{2033}'),
+    ("section", '\u{00A7}'),
+    ("semicolon", '\u{003B}'),
+    ("seven", '\u{0037}'),
+    ("sfthyphen", '\u{00AD}'),
+    ("sigma", '\u{03C3}'),
+    ("sigma1", '\u{03C2}'),
+    ("similar", '\u{223C}'),
+    ("six", '\u{0036}'),
+    ("slash", '\u{002F}'),
+    ("space", '\u{0020}'),
+    ("sterling", '\u{00A3}'),
+    ("suchthat", '\u{220B}'),
+    ("summation", '\u{2211}'),
+    ("tau", '\u{03C4}'),
+    ("therefore", '\u{2234}'),
+    ("theta", '\u{03B8}'),
+    ("theta1", '\u{03D1}'),
+    ("thorn", '\u{00FE}'),
+    ("three", '\u{0033}'),
+    ("threequarters", '\u{00BE}'),
+    ("threesuperior", '\u{00B3}'),
+    ("tilde", '\u{02DC}'),
+    ("trademark", '\u{2122}'),
+    ("two", '\u{0032}'),
+    ("twodotenleader", '\u{2025}'),
+    ("twosuperior", '\u{00B2}'),
+    ("uacute", '\u{00FA}'),
+    ("ucircumflex", '\u{00FB}'),
+    ("udieresis", '\u{00FC}'),
+    ("ugrave", '\u{00F9}'),
+    ("underscore", '\u{005F}'),
+    ("union", '\u{222A}'),
+    ("universal", '\u{2200}'),
+    ("upsilon", '\u{03C5}'),
+    ("xi", '\u{03BE}'),
+    ("yacute", '\u{00FD}'),
+    ("ydieresis", '\u{00FF}'),
+    ("yen", '\u{00A5}'),
+    ("zcaron", '\u{017E}'),
+    ("zero", '\u{0030}'),
+    ("zeta", '\u{03B6}'),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glyph_list_is_sorted_for_binary_search() {
+        assert!(GLYPH_LIST.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+
+    #[test]
+    fn test_glyph_names() {
+        let unicode = |name| glyph_name_to_unicode(name);
+        assert_eq!(unicode("A").as_deref(), Some("A"));
+        assert_eq!(unicode("eacute").as_deref(), Some("é"));
+        assert_eq!(unicode("quoteright").as_deref(), Some("\u{2019}"));
+        assert_eq!(unicode("uni00410042").as_deref(), Some("AB"));
+        assert_eq!(unicode("u1F600").as_deref(), Some("\u{1F600}"));
+        assert_eq!(unicode("f_i").as_deref(), Some("fi"));
+        assert_eq!(unicode("one.oldstyle").as_deref(), Some("1"));
+        assert_eq!(unicode("uniD800"), None);
+        assert_eq!(unicode(".notdef"), None);
+        assert_eq!(unicode("g123"), None);
+    }
+
+    #[test]
+    fn test_differences_override_the_base_encoding() {
+        let differences = [(39, "quotesingle".to_string()), (200, "g123".to_string())];
+        let map = simple_font_unicode(Some(BaseEncoding::Standard), &differences);
+        assert_eq!(map[&65], "A");
+        assert_eq!(map[&39], "'");
+        assert_eq!(map[&96], "\u{2018}");
+        assert!(!map.contains_key(&200));
+
+        assert_eq!(BaseEncoding::WinAnsi.char(0x93), Some('“'));
+        assert_eq!(BaseEncoding::WinAnsi.char(0xE9), Some('é'));
+        assert_eq!(BaseEncoding::MacRoman.char(0x8E), Some('é'));
+        assert_eq!(BaseEncoding::WinAnsi.char(0x81), None);
+    }
+}
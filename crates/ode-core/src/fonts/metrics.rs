@@ -0,0 +1,177 @@
+use super::base14::StandardFont;
+use super::cid::{resolve, CidFont, CidWidths};
+use crate::parser::{Dictionary, PdfObject, PdfRefResolver};
+
+/// Advance widths a font declares, in thousandths of text space units.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GlyphWidths {
+    /// A Type0 font: shown strings are read as two-byte CIDs (the
+    /// `Identity-H`/`Identity-V` encodings) and measured with the
+    /// descendant CIDFont's `/W` and `/DW`.
+    Cid(CidWidths),
+    /// A Type0 font written top to bottom (`/WMode 1`): two-byte CIDs
+    /// measured by their vertical displacement from `/W2` and `/DW2`,
+    /// negative going down the page.
+    VerticalCid(CidWidths),
+    /// A simple font: one byte per code, widths indexed by code.
+    Simple(Vec<f64>),
+}
+
+impl GlyphWidths {
+    /// The widths `font_dict` declares, or the built-in ones of a standard font.
+    pub fn from_font_dict(font_dict: &Dictionary, resolver: &PdfRefResolver) -> Option<Self> {
+        if let Some(cid_font) = CidFont::from_type0(font_dict, resolver) {
+            return Some(match cid_font.vertical {
+                Some(vertical) => GlyphWidths::VerticalCid(vertical),
+                None => GlyphWidths::Cid(cid_font.widths),
+            });
+        }
+
+        // Type3 widths are in glyph space, scaled by the font's /FontMatrix
+        if font_dict.get("Subtype").and_then(|s| s.as_name()) == Some("Type3") {
+            let scale = match font_dict.get("FontMatrix").and_then(|m| resolve(m, resolver)) {
+                Some(PdfObject::Array(m)) => m.first().and_then(|a| a.as_number()).unwrap_or(0.001),
+                _ => 0.001,
+            };
+            let Some(GlyphWidths::Simple(widths)) =
+                Self::from_widths_array(font_dict, font_dict.get("Widths")?, resolver)
+            else {
+                return None;
+            };
+            let widths = widths.into_iter().map(|w| w * scale * 1000.0).collect();
+            return Some(GlyphWidths::Simple(widths));
+        }
+        if let Some(widths) = font_dict.get("Widths") {
+            return Self::from_widths_array(font_dict, widths, resolver);
+        }
+
+        // Only the standard 14 fonts may omit /Widths; their metrics are built in
+        let font = StandardFont::from_base_font(font_dict.get("BaseFont")?.as_name()?)?;
+        let encoding = font_dict.get("Encoding").and_then(|e| e.as_name());
+        Some(GlyphWidths::Simple(font.widths(encoding)))
+    }
+
+    /// `/Widths` covers codes `/FirstChar..=/LastChar`; other codes get the
+    /// descriptor's `/MissingWidth`.
+    fn from_widths_array(
+        font_dict: &Dictionary,
+        widths: &PdfObject,
+        resolver: &PdfRefResolver,
+    ) -> Option<Self> {
+        let number = |obj: &PdfObject| resolve(obj, resolver).and_then(|v| v.as_number());
+        let widths = match resolve(widths, resolver)? {
+            PdfObject::Array(widths) => widths,
+            _ => return None,
+        };
+        let first_char = font_dict.get("FirstChar").and_then(number).unwrap_or(0.0) as usize;
+        let missing_width = font_dict
+            .get("FontDescriptor")
+            .and_then(|d| resolve(d, resolver))
+            .and_then(|d| match d {
+                PdfObject::Dictionary(d) => d.get("MissingWidth").and_then(number),
+                _ => None,
+            })
+            .unwrap_or(0.0);
+
+        let mut table = vec![missing_width; 256];
+        for (code, width) in (first_char..256).zip(&widths) {
+            if let Some(width) = number(width) {
+                table[code] = width;
+            }
+        }
+        Some(GlyphWidths::Simple(table))
+    }
+
+    /// Whether text in the font advances down the page rather than across.
+    pub fn is_vertical(&self) -> bool {
+        matches!(self, GlyphWidths::VerticalCid(_))
+    }
+
+    /// The summed advance of a shown string's raw bytes.
+    pub fn string_width(&self, raw: &[u8]) -> f64 {
+        match self {
+            GlyphWidths::Cid(widths) | GlyphWidths::VerticalCid(widths) => raw
+                .chunks(2)
+                .map(|code| {
+                    let cid = code.iter().fold(0u32, |cid, b| (cid << 8) | *b as u32);
+                    widths.width(cid)
+                })
+                .sum(),
+            GlyphWidths::Simple(widths) => raw
+                .iter()
+                .map(|code| widths.get(*code as usize).copied().unwrap_or(0.0))
+                .sum(),
+        }
+    }
+
+    /// Width of a space, or a quarter em when the font has no usable one
+    /// (CID fonts seldom put a space at code 32).
+    pub fn space_width(&self) -> f64 {
+        match self {
+            GlyphWidths::Simple(widths) if widths.get(32).is_some_and(|w| *w > 0.0) => widths[32],
+            _ => 250.0,
+        }
+    }
+
+    /// How far a shown string moves the text matrix, in unscaled text space:
+    /// each glyph's width at `font_size` plus the `Tc` character spacing,
+    /// plus the `Tw` word spacing after every single-byte code 32, all
+    /// scaled by the `Tz` horizontal scaling (1.0 = 100%).
+    ///
+    /// For a vertical font the advance is along the text matrix's y axis,
+    /// and `Tz` doesn't apply.
+    pub fn string_advance(
+        &self,
+        raw: &[u8],
+        font_size: f64,
+        char_spacing: f64,
+        word_spacing: f64,
+        horizontal_scaling: f64,
+    ) -> f64 {
+        let (glyphs, spaces) = match self {
+            // Word spacing never applies to multi-byte codes
+            GlyphWidths::Cid(_) | GlyphWidths::VerticalCid(_) => (raw.len().div_ceil(2), 0),
+            GlyphWidths::Simple(_) => (raw.len(), raw.iter().filter(|b| **b == b' ').count()),
+        };
+        let width = self.string_width(raw) / 1000.0 * font_size;
+        let advance = width + glyphs as f64 * char_spacing + spaces as f64 * word_spacing;
+        if self.is_vertical() {
+            advance
+        } else {
+            advance * horizontal_scaling
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::XRef;
+
+    #[test]
+    fn test_widths_array_starts_at_first_char() {
+        let xref = XRef::default();
+        let resolver = PdfRefResolver::new(b"", &xref);
+        let descriptor = Dictionary {
+            entries: vec![("MissingWidth".to_string(), PdfObject::Integer(250))],
+        };
+        let font = Dictionary {
+            entries: vec![
+                ("Subtype".to_string(), PdfObject::Name("TrueType".to_string())),
+                ("FirstChar".to_string(), PdfObject::Integer(65)),
+                ("LastChar".to_string(), PdfObject::Integer(66)),
+                (
+                    "Widths".to_string(),
+                    PdfObject::Array(vec![PdfObject::Integer(500), PdfObject::Float(612.5)]),
+                ),
+                ("FontDescriptor".to_string(), PdfObject::Dictionary(descriptor)),
+            ],
+        };
+
+        let widths = GlyphWidths::from_font_dict(&font, &resolver).unwrap();
+        assert_eq!(widths.string_width(b"AB"), 1112.5);
+        assert_eq!(widths.string_width(b"C"), 250.0);
+        // 1.3625 em at 10pt, 3 × Tc 1, one space with Tw 2, at Tz 50
+        assert_eq!(widths.string_advance(b"A B", 10.0, 1.0, 2.0, 0.5), 9.3125);
+    }
+}
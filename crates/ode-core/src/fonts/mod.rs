@@ -1,6 +1,18 @@
+pub mod base14;
+pub mod cid;
+pub mod encoding;
+pub mod metrics;
+pub mod subset;
+
+pub use base14::StandardFont;
+pub use cid::{CidFont, CidToGidMap, CidWidths};
+pub use metrics::GlyphWidths;
+pub use subset::GlyphUsage;
+
 use crate::config::FontFormat;
 use crate::error::OdeError;
 use crate::parser::{PdfObject, PdfRefResolver};
+use crate::util::hash::ContentHasher;
 use base64::Engine;
 use byteorder::{BigEndian, WriteBytesExt};
 use std::io::Write;
@@ -27,13 +39,19 @@ pub struct FontInfo {
     pub name: String,
     pub is_type3: bool,
     pub embedded: bool,
-    pub ascent: f32,
-    pub descent: f32,
+    /// Ascent and descent as fractions of the em, when the font program
+    /// could be read for them.
+    pub ascent: Option<f32>,
+    pub descent: Option<f32>,
     pub em_size: u16,
+    /// The descendant CIDFont, for `/Type0` fonts.
+    pub cid_font: Option<CidFont>,
 }
 
 pub struct FontProcessor {
     extracted_fonts: Vec<ExtractedFont>,
+    /// Fonts embedded as a subset for the one font object that showed them.
+    subset_ids: std::collections::HashSet<u64>,
     font_counter: u64,
 }
 
@@ -41,14 +59,18 @@ impl FontProcessor {
     pub fn new() -> Self {
         Self {
             extracted_fonts: Vec::new(),
+            subset_ids: std::collections::HashSet::new(),
             font_counter: 0,
         }
     }
 
+    /// Extract the font program of `font_ref`. With `usage`, a TrueType
+    /// program is cut down to the glyphs it covers before conversion.
     pub fn extract_font_from_pdf(
         &mut self,
         font_ref: crate::parser::ObjectReference,
         resolver: &PdfRefResolver,
+        usage: Option<&GlyphUsage>,
     ) -> Result<FontInfo, OdeError> {
         let font_dict = if let Some(PdfObject::Dictionary(dict)) = resolver.dereference(font_ref) {
             dict
@@ -69,20 +91,43 @@ impl FontProcessor {
 
         let is_type3 = subtype == "Type3";
 
+        // Type0 fonts keep their descriptor, and so the font program, on the descendant
+        let cid_font = CidFont::from_type0(&font_dict, resolver);
+        let descriptor = match cid_font {
+            Some(ref cid_font) => cid_font.font_descriptor,
+            None => font_dict
+                .get("FontDescriptor")
+                .and_then(|d| d.as_reference()),
+        };
+
         if !is_type3 {
-            if let Some(font_stream_ref) = font_dict.get("FontDescriptor") {
-                if let Some(obj_ref) = font_stream_ref.as_reference() {
-                    if let Some(font_data) = self.extract_embedded_font_data(obj_ref, resolver)? {
-                        let id = self.font_counter;
-                        self.font_counter += 1;
-
-                        let woff2_data = if font_data.len() > 0 {
-                            convert_to_woff2(&font_data)?
+            if let Some(obj_ref) = descriptor {
+                if let Some(font_data) = self.extract_embedded_font_data(obj_ref, resolver)? {
+                    let cid_to_gid = cid_font.as_ref().map(|cid_font| &cid_font.cid_to_gid);
+                    let subset = usage.and_then(|usage| {
+                        subset::subset_font_program(&font_data, usage, cid_to_gid)
+                    });
+
+                    // Named by the embedded program, not the subset, so a font
+                    // gets the same id in every conversion whatever pages it
+                    // showed, and font objects embedding it share one
+                    let id = ContentHasher::hash_id(&font_data);
+                    let existing = self.extracted_fonts.iter().position(|f| f.id == id);
+                    let program = match existing {
+                        None => Some(subset.as_deref().unwrap_or(&font_data)),
+                        // Another font object may show glyphs the subset
+                        // dropped, so a shared program is embedded whole
+                        Some(_) if self.subset_ids.remove(&id) => Some(&font_data[..]),
+                        Some(_) => None,
+                    };
+
+                    if let Some(program) = program {
+                        let woff2_data = if program.len() > 0 {
+                            convert_to_woff2(program)?
                         } else {
                             font_data.clone()
                         };
-
-                        self.extracted_fonts.push(ExtractedFont {
+                        let font = ExtractedFont {
                             id,
                             name: name.clone(),
                             format: if woff2_data.starts_with(b"wOF2") {
@@ -92,19 +137,33 @@ impl FontProcessor {
                             },
                             is_embedded: true,
                             data: woff2_data,
-                        });
-
-                        let font_info = parse_font_info(&font_data);
-                        return Ok(FontInfo {
-                            id,
-                            name,
-                            is_type3,
-                            embedded: true,
-                            ascent: font_info.0,
-                            descent: font_info.1,
-                            em_size: font_info.2,
-                        });
+                        };
+                        match existing {
+                            Some(index) => {
+                                let first = &mut self.extracted_fonts[index];
+                                first.format = font.format;
+                                first.data = font.data;
+                            }
+                            None => {
+                                if subset.is_some() {
+                                    self.subset_ids.insert(id);
+                                }
+                                self.extracted_fonts.push(font);
+                            }
+                        }
                     }
+
+                    let metrics = parse_font_info(&font_data);
+                    return Ok(FontInfo {
+                        id,
+                        name,
+                        is_type3,
+                        embedded: true,
+                        ascent: metrics.map(|m| m.0),
+                        descent: metrics.map(|m| m.1),
+                        em_size: metrics.map_or(1000, |m| m.2),
+                        cid_font,
+                    });
                 }
             }
         }
@@ -114,9 +173,10 @@ impl FontProcessor {
             name,
             is_type3,
             embedded: false,
-            ascent: 0.8,
-            descent: -0.2,
+            ascent: None,
+            descent: None,
             em_size: 1000,
+            cid_font,
         })
     }
 
@@ -188,15 +248,11 @@ impl FontProcessor {
             .find(|f| f.id == font_id)
             .ok_or_else(|| OdeError::FontError(format!("Font not found: {}", font_id)))?;
 
-        let format_mime = match font.format {
-            FontFormat::Woff2 => "font/woff2",
-            FontFormat::Woff => "font/woff",
-            FontFormat::Ttf => "font/ttf",
-        };
-
         Ok(format!(
             "@font-face {{\n  font-family: 'ff{}';\n  src: url('{}') format('{}');\n}}\n",
-            font_id, font_filename, format_mime
+            font_id,
+            font_filename,
+            font.format.css_format()
         ))
     }
 
@@ -207,14 +263,12 @@ impl FontProcessor {
             .find(|f| f.id == font_id)
             .ok_or_else(|| OdeError::FontError(format!("Font not found: {}", font_id)))?;
 
-        let mime = match font.format {
-            FontFormat::Woff2 => "font/woff2",
-            FontFormat::Woff => "font/woff",
-            FontFormat::Ttf => "font/ttf",
-        };
-
         let encoded = base64::engine::general_purpose::STANDARD.encode(&font.data);
-        Ok(format!("data:{};base64,{}", mime, encoded))
+        Ok(format!(
+            "data:{};base64,{}",
+            font.format.mime_type(),
+            encoded
+        ))
     }
 
     pub fn get_font(&self, font_id: u64) -> Option<&ExtractedFont> {
@@ -232,16 +286,26 @@ impl Default for FontProcessor {
     }
 }
 
-fn parse_font_info(font_data: &[u8]) -> (f32, f32, u16) {
-    if let Ok(face) = ttf_parser::Face::parse(font_data, 0) {
-        let metrics = face.global_bounding_box();
-        let units_per_em = face.units_per_em();
-        let ascent = (metrics.y_max as f32) / (units_per_em as f32).max(1.0);
-        let descent = (metrics.y_min as f32) / (units_per_em as f32).max(1.0);
-        (ascent.max(0.0), descent.min(0.0), units_per_em)
-    } else {
-        (0.8, -0.2, 1000)
-    }
+/// Ascent, descent and units per em of a TrueType or OpenType program.
+///
+/// The ascent and descent are the ones browsers lay the font out with, from
+/// `hhea`/`OS/2`, or the glyph bounding box for subsets that zero those.
+fn parse_font_info(font_data: &[u8]) -> Option<(f32, f32, u16)> {
+    let face = ttf_parser::Face::parse(font_data, 0).ok()?;
+    let units_per_em = face.units_per_em();
+    let em = (units_per_em as f32).max(1.0);
+    let (ascent, descent) = match (face.ascender(), face.descender()) {
+        (ascent, descent) if ascent > 0 => (ascent, descent),
+        _ => {
+            let bbox = face.global_bounding_box();
+            (bbox.y_max, bbox.y_min)
+        }
+    };
+    Some((
+        (ascent as f32 / em).max(0.0),
+        (descent as f32 / em).min(0.0),
+        units_per_em,
+    ))
 }
 
 fn convert_to_woff2(font_data: &[u8]) -> Result<Vec<u8>, OdeError> {
@@ -306,7 +370,7 @@ mod tests {
         let id = processor.extract_font(font_data, "TestFont".to_string());
         let css = processor.generate_font_face(id, "test.woff2").unwrap();
         assert!(css.contains("'ff0'"));
-        assert!(css.contains("font/woff2"));
+        assert!(css.contains("format('woff2')"));
     }
 
     #[test]
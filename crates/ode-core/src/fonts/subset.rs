@@ -0,0 +1,371 @@
+use super::cid::CidToGidMap;
+use std::collections::BTreeSet;
+
+/// What the converted pages show in one font.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GlyphUsage {
+    /// Codes of the shown strings: bytes for simple fonts, two-byte CIDs
+    /// for Type0 fonts.
+    pub codes: BTreeSet<u32>,
+    /// The text those strings decode to, which is what the browser looks
+    /// glyphs up by.
+    pub chars: BTreeSet<char>,
+}
+
+impl GlyphUsage {
+    /// Record a shown string's raw bytes, read two at a time when
+    /// `two_byte`, and the text it decodes to.
+    pub fn record(&mut self, raw: &[u8], two_byte: bool, text: &str) {
+        if two_byte {
+            self.codes.extend(
+                raw.chunks_exact(2)
+                    .map(|pair| u32::from(u16::from_be_bytes([pair[0], pair[1]]))),
+            );
+        } else {
+            self.codes.extend(raw.iter().map(|&code| u32::from(code)));
+        }
+        self.chars.extend(text.chars());
+    }
+}
+
+/// A TrueType program cut down to the glyphs `usage` reaches, or `None`
+/// when it has no TrueType outlines (CFF programs are left whole) or the
+/// subset would not be smaller.
+///
+/// CIDs reach glyphs through `cid_to_gid`; simple font codes through the
+/// program's own `cmap`. The decoded text reaches them through its Unicode
+/// `cmap`, which is the one the browser uses.
+pub fn subset_font_program(
+    font_data: &[u8],
+    usage: &GlyphUsage,
+    cid_to_gid: Option<&CidToGidMap>,
+) -> Option<Vec<u8>> {
+    let face = ttf_parser::Face::parse(font_data, 0).ok()?;
+    let mut keep = BTreeSet::new();
+    for &code in &usage.codes {
+        match cid_to_gid {
+            Some(cid_to_gid) => {
+                keep.insert(cid_to_gid.gid(code) as u16);
+            }
+            None => {
+                let subtables = face
+                    .tables()
+                    .cmap
+                    .into_iter()
+                    .flat_map(|cmap| cmap.subtables);
+                for subtable in subtables {
+                    // Symbol fonts map their codes from U+F000
+                    let glyphs = [code, 0xF000 | code].map(|c| subtable.glyph_index(c));
+                    keep.extend(glyphs.into_iter().flatten().map(|gid| gid.0));
+                }
+            }
+        }
+    }
+    keep.extend(
+        usage
+            .chars
+            .iter()
+            .filter_map(|&c| face.glyph_index(c))
+            .map(|gid| gid.0),
+    );
+    subset_truetype(font_data, &keep)
+}
+
+/// `font_data` with every glyph outside `keep` emptied.
+///
+/// Glyph ids stay as they are, so `cmap`, `hmtx` and the layout tables
+/// still hold; `glyf` shrinks and `loca` is rewritten in the long format
+/// to match. `.notdef` and the components of kept composite glyphs are
+/// kept as well. `None` for anything but a TrueType program, or when the
+/// subset would not be smaller.
+pub fn subset_truetype(font_data: &[u8], keep: &BTreeSet<u16>) -> Option<Vec<u8>> {
+    let version: [u8; 4] = font_data.get(0..4)?.try_into().ok()?;
+    if version != [0, 1, 0, 0] && &version != b"true" {
+        return None;
+    }
+    let tables = read_tables(font_data)?;
+    let table = |tag: &[u8; 4]| tables.iter().find(|(t, _)| t == tag).map(|(_, data)| *data);
+    let (head, maxp, loca, glyf) = (
+        table(b"head")?,
+        table(b"maxp")?,
+        table(b"loca")?,
+        table(b"glyf")?,
+    );
+    if head.len() < 54 {
+        return None;
+    }
+    let num_glyphs = usize::from(read_u16(maxp, 4)?);
+    let long_offsets = read_u16(head, 50)? != 0;
+    let offsets: Vec<usize> = (0..=num_glyphs)
+        .map(|i| match long_offsets {
+            true => read_u32(loca, i * 4).map(|offset| offset as usize),
+            false => read_u16(loca, i * 2).map(|offset| usize::from(offset) * 2),
+        })
+        .collect::<Option<_>>()?;
+    let glyph = |gid: usize| glyf.get(offsets[gid]..offsets[gid + 1]).unwrap_or_default();
+
+    let mut keep: BTreeSet<u16> = keep
+        .iter()
+        .copied()
+        .filter(|&gid| usize::from(gid) < num_glyphs)
+        .chain([0])
+        .collect();
+    let mut pending: Vec<u16> = keep.iter().copied().collect();
+    while let Some(gid) = pending.pop() {
+        for component in composite_components(glyph(usize::from(gid))) {
+            if usize::from(component) < num_glyphs && keep.insert(component) {
+                pending.push(component);
+            }
+        }
+    }
+
+    let mut new_glyf = Vec::new();
+    let mut new_loca = Vec::with_capacity((num_glyphs + 1) * 4);
+    for gid in 0..num_glyphs {
+        new_loca.extend_from_slice(&(new_glyf.len() as u32).to_be_bytes());
+        if keep.contains(&(gid as u16)) {
+            new_glyf.extend_from_slice(glyph(gid));
+            new_glyf.resize(new_glyf.len().next_multiple_of(4), 0);
+        }
+    }
+    new_loca.extend_from_slice(&(new_glyf.len() as u32).to_be_bytes());
+
+    let mut new_head = head.to_vec();
+    new_head[8..12].fill(0);
+    new_head[50..52].copy_from_slice(&1u16.to_be_bytes());
+
+    let tables: Vec<([u8; 4], &[u8])> = tables
+        .iter()
+        // A signature no longer matches the font it signed
+        .filter(|(tag, _)| tag != b"DSIG")
+        .map(|&(tag, data)| match &tag {
+            b"head" => (tag, new_head.as_slice()),
+            b"loca" => (tag, new_loca.as_slice()),
+            b"glyf" => (tag, new_glyf.as_slice()),
+            _ => (tag, data),
+        })
+        .collect();
+    let subset = write_sfnt(version, tables);
+    (subset.len() < font_data.len()).then_some(subset)
+}
+
+/// The table directory: each table's tag and data.
+fn read_tables(font_data: &[u8]) -> Option<Vec<([u8; 4], &[u8])>> {
+    let num_tables = usize::from(read_u16(font_data, 4)?);
+    (0..num_tables)
+        .map(|i| {
+            let record = font_data.get(12 + i * 16..12 + (i + 1) * 16)?;
+            let offset = read_u32(record, 8)? as usize;
+            let length = read_u32(record, 12)? as usize;
+            let data = font_data.get(offset..offset.checked_add(length)?)?;
+            Some((record[0..4].try_into().ok()?, data))
+        })
+        .collect()
+}
+
+/// The glyphs a composite glyph is built from; none for simple glyphs.
+fn composite_components(glyph: &[u8]) -> Vec<u16> {
+    const ARGS_ARE_WORDS: u16 = 0x0001;
+    const HAS_SCALE: u16 = 0x0008;
+    const MORE_COMPONENTS: u16 = 0x0020;
+    const HAS_XY_SCALE: u16 = 0x0040;
+    const HAS_2X2: u16 = 0x0080;
+
+    let mut components = Vec::new();
+    if !matches!(read_u16(glyph, 0), Some(contours) if (contours as i16) < 0) {
+        return components;
+    }
+    let mut pos = 10;
+    while let (Some(flags), Some(gid)) = (read_u16(glyph, pos), read_u16(glyph, pos + 2)) {
+        components.push(gid);
+        pos += 4 + if flags & ARGS_ARE_WORDS != 0 { 4 } else { 2 };
+        pos += if flags & HAS_SCALE != 0 {
+            2
+        } else if flags & HAS_XY_SCALE != 0 {
+            4
+        } else if flags & HAS_2X2 != 0 {
+            8
+        } else {
+            0
+        };
+        if flags & MORE_COMPONENTS == 0 {
+            break;
+        }
+    }
+    components
+}
+
+/// Lay `tables` out as an sfnt file, sorted by tag and 4-byte aligned,
+/// with fresh checksums.
+fn write_sfnt(version: [u8; 4], mut tables: Vec<([u8; 4], &[u8])>) -> Vec<u8> {
+    tables.sort_by_key(|(tag, _)| *tag);
+    let num_tables = tables.len() as u16;
+    let entry_selector = (num_tables.max(1)).ilog2() as u16;
+    let search_range = (1u16 << entry_selector) * 16;
+
+    let mut font = Vec::new();
+    font.extend_from_slice(&version);
+    for value in [
+        num_tables,
+        search_range,
+        entry_selector,
+        num_tables * 16 - search_range,
+    ] {
+        font.extend_from_slice(&value.to_be_bytes());
+    }
+    let mut offset = 12 + tables.len() * 16;
+    for (tag, data) in &tables {
+        font.extend_from_slice(tag);
+        font.extend_from_slice(&checksum(data).to_be_bytes());
+        font.extend_from_slice(&(offset as u32).to_be_bytes());
+        font.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        offset += data.len().next_multiple_of(4);
+    }
+    let mut head_offset = None;
+    for (tag, data) in &tables {
+        if tag == b"head" {
+            head_offset = Some(font.len());
+        }
+        font.extend_from_slice(data);
+        font.resize(font.len().next_multiple_of(4), 0);
+    }
+    if let Some(head_offset) = head_offset {
+        let adjustment = 0xB1B0_AFBAu32.wrapping_sub(checksum(&font));
+        font[head_offset + 8..head_offset + 12].copy_from_slice(&adjustment.to_be_bytes());
+    }
+    font
+}
+
+/// The sum of `data` as big-endian words, zero-padded to a whole word.
+fn checksum(data: &[u8]) -> u32 {
+    data.chunks(4).fold(0u32, |sum, chunk| {
+        let mut word = [0u8; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(word))
+    })
+}
+
+fn read_u16(data: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(pos..pos + 2)?.try_into().ok()?))
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+/// A TrueType font of `num_glyphs` one-triangle glyphs, glyph `g`
+/// mapped from U+4E00 + g - 1, whose last glyph is a composite of
+/// glyph 1.
+#[cfg(test)]
+pub(crate) fn test_font(num_glyphs: u16) -> Vec<u8> {
+    let be16 = |values: &[u16]| {
+        values
+            .iter()
+            .flat_map(|v| v.to_be_bytes())
+            .collect::<Vec<u8>>()
+    };
+
+    let mut glyf = Vec::new();
+    let mut loca = Vec::new();
+    for gid in 0..num_glyphs {
+        loca.extend_from_slice(&(glyf.len() as u32).to_be_bytes());
+        if gid == num_glyphs - 1 {
+            // One component, glyph 1, with byte offsets
+            glyf.extend(be16(&[0xFFFF, 0, 0, 500, 500, 0x0002, 1]));
+            glyf.extend_from_slice(&[10, 10]);
+        } else {
+            glyf.extend(be16(&[1, 0, 0, 500, 500, 2, 0]));
+            glyf.extend_from_slice(&[0x01; 3]);
+            glyf.extend(be16(&[0, 250, 250, 0, 500, 0xFE0C]));
+        }
+        glyf.resize(glyf.len().next_multiple_of(4), 0);
+    }
+    loca.extend_from_slice(&(glyf.len() as u32).to_be_bytes());
+
+    let mut head = be16(&[1, 0, 1, 0, 0, 0, 0x5F0F, 0x3CF5, 0, 1000]);
+    head.resize(50, 0);
+    head.extend(be16(&[1, 0]));
+    let mut hhea = be16(&[1, 0, 800, 0xFF38, 0]);
+    hhea.resize(34, 0);
+    hhea.extend(be16(&[num_glyphs]));
+    let hmtx = be16(&vec![500; num_glyphs as usize * 2]);
+    let maxp = be16(&[0, 0x5000, num_glyphs]);
+    // Format 12, one group mapping U+4E00.. onto glyphs 1..
+    let mut cmap = be16(&[0, 1, 3, 10, 0, 12, 12, 0, 0, 28, 0, 0, 0, 1]);
+    cmap.extend(be16(&[0, 0x4E00, 0, 0x4E00 + num_glyphs - 2, 0, 1]));
+
+    let tables: Vec<([u8; 4], &[u8])> = vec![
+        (*b"cmap", &cmap),
+        (*b"glyf", &glyf),
+        (*b"head", &head),
+        (*b"hhea", &hhea),
+        (*b"hmtx", &hmtx),
+        (*b"loca", &loca),
+        (*b"maxp", &maxp),
+    ];
+    write_sfnt([0, 1, 0, 0], tables)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn has_outline(face: &ttf_parser::Face, gid: u16) -> bool {
+        struct Sink;
+        impl ttf_parser::OutlineBuilder for Sink {
+            fn move_to(&mut self, _: f32, _: f32) {}
+            fn line_to(&mut self, _: f32, _: f32) {}
+            fn quad_to(&mut self, _: f32, _: f32, _: f32, _: f32) {}
+            fn curve_to(&mut self, _: f32, _: f32, _: f32, _: f32, _: f32, _: f32) {}
+            fn close(&mut self) {}
+        }
+        face.outline_glyph(ttf_parser::GlyphId(gid), &mut Sink)
+            .is_some()
+    }
+
+    #[test]
+    fn test_subset_keeps_only_the_glyphs_shown() {
+        let font = test_font(3000);
+        let usage = GlyphUsage {
+            chars: (0..40).filter_map(|i| char::from_u32(0x4E00 + i)).collect(),
+            ..Default::default()
+        };
+
+        let subset = subset_font_program(&font, &usage, None).unwrap();
+        assert!(
+            subset.len() * 2 < font.len(),
+            "{} of {} bytes",
+            subset.len(),
+            font.len()
+        );
+
+        let face = ttf_parser::Face::parse(&subset, 0).unwrap();
+        assert_eq!(face.number_of_glyphs(), 3000);
+        assert_eq!(face.glyph_index('\u{4E27}'), Some(ttf_parser::GlyphId(40)));
+        assert!(has_outline(&face, 1));
+        assert!(has_outline(&face, 40));
+        assert!(!has_outline(&face, 41));
+        assert!(!has_outline(&face, 2998));
+    }
+
+    #[test]
+    fn test_subset_follows_cids_and_composite_glyphs() {
+        let font = test_font(100);
+        let usage = GlyphUsage {
+            codes: BTreeSet::from([5]),
+            ..Default::default()
+        };
+
+        // CID 5 is glyph 99, a composite of glyph 1
+        let cid_to_gid = CidToGidMap::Table(vec![0, 0, 0, 0, 0, 99]);
+        let subset = subset_font_program(&font, &usage, Some(&cid_to_gid)).unwrap();
+
+        let face = ttf_parser::Face::parse(&subset, 0).unwrap();
+        assert!(has_outline(&face, 99));
+        assert!(has_outline(&face, 1));
+        assert!(!has_outline(&face, 5));
+
+        // CFF and other non-TrueType programs are left alone
+        assert!(subset_truetype(b"OTTO\0\0\0\0", &BTreeSet::new()).is_none());
+    }
+}
@@ -1,5 +1,5 @@
-use crate::config::ConversionConfig;
-use crate::error::OdeError;
+use crate::config::{ConversionConfig, PageBox};
+use crate::error::{OdeError, ParseErrorKind};
 use crate::util::{TimeoutWrapper, ZipBombDetector};
 use std::thread;
 use std::time::Duration;
@@ -101,7 +101,7 @@ async fn test_timeout_wrapper_async() {
 
 #[test]
 fn test_error_types() {
-    let pdf_error = OdeError::PdfParseError("test error".to_string());
+    let pdf_error = OdeError::malformed("test error".to_string());
     assert!(pdf_error.to_string().contains("PDF parsing error"));
 
     let timeout_error = OdeError::Timeout("operation timed out".to_string());
@@ -109,4 +109,2119 @@ fn test_error_types() {
 
     let zip_bomb_error = OdeError::ZipBomb { ratio: 200 };
     assert!(zip_bomb_error.to_string().contains("zip bomb") && zip_bomb_error.to_string().contains("200"));
-}
\ No newline at end of file
+}
+/// Assemble a PDF from object bodies numbered 1..=n, with object 1 as the catalog.
+pub(crate) fn build_pdf(objects: &[Vec<u8>]) -> Vec<u8> {
+    let mut pdf = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::new();
+    for (i, body) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+        pdf.extend_from_slice(body);
+        pdf.extend_from_slice(b"\nendobj\n");
+    }
+    let xref_offset = pdf.len();
+    pdf.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes());
+    for offset in &offsets {
+        pdf.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    pdf.extend_from_slice(
+        format!(
+            "trailer\n<</Size {}/Root 1 0 R>>\nstartxref\n{}\n%%EOF",
+            objects.len() + 1,
+            xref_offset
+        )
+        .as_bytes(),
+    );
+    pdf
+}
+
+pub(crate) fn stream_object(dict: &str, data: &[u8]) -> Vec<u8> {
+    let mut obj = format!("<<{}/Length {}>>\nstream\n", dict, data.len()).into_bytes();
+    obj.extend_from_slice(data);
+    obj.extend_from_slice(b"\nendstream");
+    obj
+}
+
+#[test]
+fn test_custom_filter_decodes_page_content() {
+    let content: Vec<u8> = b"BT /F1 12 Tf 72 700 Td (Custom filter) Tj ET"
+        .iter()
+        .rev()
+        .copied()
+        .collect();
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Contents 4 0 R>>".to_vec(),
+        stream_object("/Filter/ReverseDecode", &content),
+    ]);
+
+    let mut config = ConversionConfig::default();
+    config.filters.register("ReverseDecode", |data: &[u8], _parms: &crate::parser::DecodeParms| {
+        Ok(data.iter().rev().copied().collect())
+    });
+
+    let bundle = crate::convert_pdf(&pdf, &config).unwrap();
+    let text: String = bundle.pages[0].text_spans.iter().map(|s| s.text.as_str()).collect();
+    assert_eq!(text, "Custom filter");
+
+    let without_filter = crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap();
+    assert!(without_filter.pages[0].text_spans.is_empty());
+}
+
+#[test]
+fn test_page_without_mediabox_takes_the_first_page_size() {
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Kids[3 0 R 4 0 R 5 0 R]/Count 3>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 595 842]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 595 842]>>".to_vec(),
+    ]);
+    let doc = crate::parser::parse_pdf(&pdf).unwrap();
+    let sizes: Vec<(f64, f64)> = doc.pages.iter().map(|p| (p.width, p.height)).collect();
+    assert_eq!(sizes, [(595.0, 842.0); 3]);
+
+    let config = ConversionConfig {
+        default_page_size: Some((612.0, 792.0)),
+        ..Default::default()
+    };
+    let doc = crate::parser::parse_pdf_with_config(&pdf, &config).unwrap();
+    assert_eq!((doc.pages[1].width, doc.pages[1].height), (612.0, 792.0));
+    assert_eq!((doc.pages[0].width, doc.pages[0].height), (595.0, 842.0));
+}
+
+#[test]
+fn test_page_dictionary_inlined_in_kids_is_extracted() {
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 2/MediaBox[0 0 612 792]/Resources<</Font<</F1 3 0 R>>>>\
+/Kids[<</Type/Page/Parent 2 0 R/MediaBox[0 0 300 400]/Contents 4 0 R>> 5 0 R]>>"
+            .to_vec(),
+        b"<</Type/Font/Subtype/Type1/BaseFont/Helvetica>>".to_vec(),
+        stream_object("", b"BT /F1 12 Tf 72 300 Td (Inline) Tj ET"),
+        b"<</Type/Page/Parent 2 0 R/Contents 6 0 R>>".to_vec(),
+        stream_object("", b"BT /F1 12 Tf 72 700 Td (Referenced) Tj ET"),
+    ]);
+    let doc = crate::parser::parse_pdf(&pdf).unwrap();
+    let sizes: Vec<(f64, f64)> = doc.pages.iter().map(|p| (p.width, p.height)).collect();
+    assert_eq!(sizes, [(300.0, 400.0), (612.0, 792.0)]);
+
+    let bundle = crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap();
+    let texts: Vec<&str> = bundle
+        .pages
+        .iter()
+        .map(|p| p.text_spans[0].text.as_str())
+        .collect();
+    assert_eq!(texts, ["Inline", "Referenced"]);
+}
+
+#[test]
+fn test_page_selection_picks_the_listed_pages() {
+    use crate::benchmarks::performance_benchmarks::create_multi_page_pdf;
+    use crate::config::PageSelection;
+
+    let pdf = create_multi_page_pdf(5);
+    // Selections are written as strings in serialized configs
+    let pages: PageSelection = serde_json::from_str("\"4-, 2,4\"").unwrap();
+    let config = ConversionConfig {
+        pages: Some(pages),
+        ..Default::default()
+    };
+    let bundle = crate::convert_pdf(&pdf, &config).unwrap();
+    let numbers: Vec<usize> = bundle.pages.iter().map(|p| p.page_number).collect();
+    assert_eq!(numbers, [2, 4, 5]);
+}
+
+#[test]
+fn test_page_cache_reuses_pages_across_reconversion() {
+    use crate::benchmarks::performance_benchmarks::create_multi_page_pdf;
+    use crate::renderer::PageCache;
+    use std::sync::Arc;
+
+    let pdf = create_multi_page_pdf(3);
+    let cache = Arc::new(PageCache::new());
+
+    let config = ConversionConfig {
+        page_cache: Some(cache.clone()),
+        ..Default::default()
+    };
+    let first = crate::convert_pdf(&pdf, &config).unwrap();
+    assert_eq!((cache.hits(), cache.misses()), (0, 3));
+
+    // Narrowing the range and silencing output don't change how pages render
+    let narrowed = ConversionConfig {
+        page_range: (2, 3),
+        quiet: true,
+        ..config.clone()
+    };
+    let second = crate::convert_pdf(&pdf, &narrowed).unwrap();
+    assert_eq!((cache.hits(), cache.misses()), (2, 3));
+    assert_eq!(second.pages[0].html, first.pages[1].html);
+
+    // A rendering option invalidates every page
+    let zoomed = ConversionConfig {
+        zoom: 1.5,
+        ..config.clone()
+    };
+    crate::convert_pdf(&pdf, &zoomed).unwrap();
+    assert_eq!((cache.hits(), cache.misses()), (2, 6));
+}
+
+#[test]
+fn test_pages_sharing_a_content_stream_parse_it_once() {
+    use crate::renderer::render_pdf_page;
+
+    let page = b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Contents 6 0 R>>";
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 3/Kids[3 0 R 4 0 R 5 0 R]>>".to_vec(),
+        page.to_vec(),
+        page.to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Contents 7 0 R>>".to_vec(),
+        stream_object("", b"BT /F1 12 Tf 72 700 Td (Template) Tj ET"),
+        stream_object("", b"BT /F1 12 Tf 72 700 Td (Own) Tj ET"),
+    ]);
+    let document = crate::parser::parse_pdf(&pdf).unwrap();
+    let config = ConversionConfig::default();
+
+    let texts: Vec<String> = (0..3)
+        .map(|i| render_pdf_page(&document, i, i + 1, &config).unwrap().text_spans[0].text.clone())
+        .collect();
+    assert_eq!(texts, ["Template", "Template", "Own"]);
+    assert_eq!(document.content_ops.parses(), 2);
+}
+
+#[test]
+fn test_damaged_xref_is_reconstructed_from_object_headers() {
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 2/Kids[3 0 R 4 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Contents 5 0 R>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 300 400]/Contents 5 0 R>>".to_vec(),
+        stream_object("", b"BT /F1 12 Tf 72 700 Td (Recovered) Tj ET"),
+    ]);
+    // Bytes inserted after the header shift every object past its xref offset
+    let mut shifted = pdf.clone();
+    shifted.splice(9..9, b"%junk\n".iter().copied());
+    // A file cut off before its xref has no trailer; the catalog is found by /Type
+    let xref_start = pdf.windows(4).rposition(|w| w == b"xref").unwrap() - 5;
+    let truncated = pdf[..xref_start].to_vec();
+
+    for damaged in [shifted, truncated] {
+        let document = crate::parser::parse_pdf(&damaged).unwrap();
+        let sizes: Vec<(f64, f64)> = document.pages.iter().map(|p| (p.width, p.height)).collect();
+        assert_eq!(sizes, [(612.0, 792.0), (300.0, 400.0)]);
+
+        let bundle = crate::convert_pdf(&damaged, &ConversionConfig::default()).unwrap();
+        assert!(bundle.pages[0].html.contains("Recovered"));
+    }
+}
+
+#[test]
+fn test_hybrid_file_resolves_objects_only_its_xref_stream_lists() {
+    // Page 7 is packed in object stream 6, which only the xref stream at
+    // /XRefStm lists
+    let packed = b"7 0 <</Type/Page/Parent 2 0 R/MediaBox[0 0 300 400]/Contents 5 0 R>>";
+    let objects = [
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 2/Kids[3 0 R 7 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Contents 4 0 R>>".to_vec(),
+        stream_object("", b"BT /F1 12 Tf 72 700 Td (Listed) Tj ET"),
+        stream_object("", b"BT /F1 12 Tf 72 300 Td (Packed) Tj ET"),
+        stream_object("/Type/ObjStm/N 1/First 4", packed),
+    ];
+    // `stm_object` is the object /XRefStm points at, or past the end of the
+    // file when None
+    let hybrid = |table_lists_seven: bool, stm_object: Option<usize>| {
+        let mut pdf = b"%PDF-1.5\n".to_vec();
+        let mut offsets = Vec::new();
+        for (i, body) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+            pdf.extend_from_slice(body);
+            pdf.extend_from_slice(b"\nendobj\n");
+        }
+        // Object 7 is entry 2 of a type-2 row: index 0 of object stream 6
+        let xref_stream_offset = pdf.len();
+        pdf.extend_from_slice(b"8 0 obj\n");
+        let row = [2, 0, 6, 0];
+        pdf.extend_from_slice(&stream_object("/Type/XRef/Size 9/W[1 2 1]/Index[7 1]", &row));
+        pdf.extend_from_slice(b"\nendobj\n");
+
+        let xref_offset = pdf.len();
+        let count = if table_lists_seven { 8 } else { 7 };
+        pdf.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f \n", count).as_bytes());
+        for offset in &offsets {
+            pdf.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+        }
+        if table_lists_seven {
+            pdf.extend_from_slice(b"0000000000 00001 f \n");
+        }
+        let stm_offset = match stm_object {
+            Some(8) => xref_stream_offset,
+            Some(id) => offsets[id - 1],
+            None => pdf.len() + 10_000,
+        };
+        pdf.extend_from_slice(
+            format!(
+                "trailer\n<</Size 9/Root 1 0 R/XRefStm {}>>\nstartxref\n{}\n%%EOF",
+                stm_offset, xref_offset
+            )
+            .as_bytes(),
+        );
+        pdf
+    };
+
+    for table_lists_seven in [false, true] {
+        let pdf = hybrid(table_lists_seven, Some(8));
+        let document = crate::parser::parse_pdf(&pdf).unwrap();
+        let sizes: Vec<(f64, f64)> = document.pages.iter().map(|p| (p.width, p.height)).collect();
+        assert_eq!(sizes, [(612.0, 792.0), (300.0, 400.0)], "{}", table_lists_seven);
+        let bundle = crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap();
+        assert!(bundle.pages[1].html.contains("Packed"));
+    }
+
+    // An /XRefStm past the end of the file, or at an object that isn't an
+    // xref stream, leaves the table's entries
+    for stm_object in [None, Some(3)] {
+        let pdf = hybrid(true, stm_object);
+        let xref = crate::parser::parse_pdf(&pdf).unwrap().xref.unwrap();
+        let in_use: Vec<u64> =
+            xref.entries.iter().filter(|e| e.in_use).map(|e| e.object_id).collect();
+        assert_eq!(in_use, [1, 2, 3, 4, 5, 6], "{:?}", stm_object);
+    }
+}
+
+#[test]
+fn test_acroform_values_render_in_their_widgets() {
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R/AcroForm<</Fields[5 0 R 7 0 R]/DA(/Helv 0 Tf 0 g)>>>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Contents 4 0 R/Annots[6 0 R 7 0 R]>>"
+            .to_vec(),
+        stream_object("", b""),
+        // A parent field whose value its widget kid inherits
+        b"<</T(applicant)/FT/Tx/V(Jane Doe)/DA(/Helv 10 Tf 0 g)/Kids[6 0 R]>>".to_vec(),
+        b"<</Type/Annot/Subtype/Widget/Parent 5 0 R/T(name)/Rect[72 700 272 720]>>".to_vec(),
+        b"<</Type/Annot/Subtype/Widget/T(agree)/FT/Btn/V/Yes/AS/Yes/Rect[72 660 92 680]>>".to_vec(),
+    ]);
+
+    let document = crate::parser::parse_pdf(&pdf).unwrap();
+    let names: Vec<&str> = document.pages[0].form_widgets.iter().map(|w| w.name.as_str()).collect();
+    assert_eq!(names, ["applicant.name", "agree"]);
+
+    let bundle = crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap();
+    let spans: Vec<(&str, f64, f64, f64)> = bundle.pages[0]
+        .text_spans
+        .iter()
+        .map(|span| (span.text.as_str(), span.x, span.y, span.font_size))
+        .collect();
+    // Text at its /DA size, the check box's mark sized to fit its 20pt rect,
+    // each line centered in its rect
+    assert_eq!(spans, [("Jane Doe", 74.0, 76.75, 10.0), ("\u{2611}", 74.0, 114.65, 14.0)]);
+    assert_eq!(bundle.pages[0].text_spans[0].height, 10.5);
+}
+
+#[test]
+fn test_form_fields_get_tabindex_in_declared_order() {
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R/AcroForm<</Fields[6 0 R 8 0 R 9 0 R]/CO[9 0 R 6 0 R]>>>>"
+            .to_vec(),
+        b"<</Type/Pages/Count 2/Kids[3 0 R 4 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Contents 5 0 R/Annots[7 0 R 8 0 R]>>"
+            .to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Contents 5 0 R/Annots[9 0 R]>>".to_vec(),
+        stream_object("", b""),
+        // /CO lists the parent field; its widget kid takes its place
+        b"<</T(applicant)/FT/Tx/V(Jane Doe)/Kids[7 0 R]>>".to_vec(),
+        b"<</Type/Annot/Subtype/Widget/Parent 6 0 R/T(name)/Rect[72 700 272 720]>>".to_vec(),
+        b"<</Type/Annot/Subtype/Widget/T(agree)/FT/Btn/V/Yes/AS/Yes/Rect[72 660 92 680]>>".to_vec(),
+        b"<</Type/Annot/Subtype/Widget/T(city)/FT/Tx/V(Oulu)/Rect[72 700 272 720]>>".to_vec(),
+    ]);
+
+    let document = crate::parser::parse_pdf(&pdf).unwrap();
+    let order: Vec<(&str, Option<usize>)> = document
+        .pages
+        .iter()
+        .flat_map(|page| &page.form_widgets)
+        .map(|widget| (widget.name.as_str(), widget.tab_index))
+        .collect();
+    // Fields in /CO first, the rest after them in page order
+    assert_eq!(order, [("applicant.name", Some(2)), ("agree", Some(3)), ("city", Some(1))]);
+
+    let bundle = crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap();
+    assert!(bundle.pages[0].html.contains(
+        "<div tabindex=\"2\" aria-label=\"applicant.name\" style=\"position:absolute;left:72px;top:72px;width:200px;height:20px;\"></div>"
+    ));
+    assert!(bundle.pages[1].html.contains("<div tabindex=\"1\" aria-label=\"city\""));
+}
+
+#[test]
+fn test_checked_box_renders_its_on_appearance() {
+    let appearances = b"/AP<</N<</Yes 8 0 R/Off 9 0 R>>>>/MK<</BG[1 1 0]/CA(4)>>";
+    let check_box = |name: &str, state: &str, y: u32| {
+        let mut widget = format!(
+            "<</Type/Annot/Subtype/Widget/T({name})/FT/Btn/V/{state}/AS/{state}/Rect[72 {y} 92 {}]",
+            y + 20
+        )
+        .into_bytes();
+        widget.extend_from_slice(appearances);
+        widget.extend_from_slice(b">>");
+        widget
+    };
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R/AcroForm<</Fields[5 0 R 6 0 R 7 0 R]>>>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Contents 4 0 R/Annots[5 0 R 6 0 R 7 0 R]>>"
+            .to_vec(),
+        stream_object("", b""),
+        check_box("agree", "Yes", 700),
+        check_box("subscribe", "Off", 660),
+        // No appearance streams: the /MK caption marks it
+        b"<</Type/Annot/Subtype/Widget/T(terms)/FT/Btn/V/On/Rect[72 620 92 640]/MK<</CA(8)>>>>"
+            .to_vec(),
+        // The "on" appearance is drawn at half scale into a 40pt BBox
+        stream_object("/BBox[0 0 40 40]", b"BT /ZaDb 24 Tf 8 8 Td (4) Tj ET"),
+        stream_object("/BBox[0 0 40 40]", b""),
+    ]);
+
+    let bundle = crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap();
+    let spans: Vec<(&str, f64, f64, f64)> = bundle.pages[0]
+        .text_spans
+        .iter()
+        .map(|span| (span.text.as_str(), span.x, span.y, span.font_size))
+        .collect();
+    assert_eq!(
+        spans,
+        [
+            ("\u{2714}", 76.0, 77.8, 12.0),
+            ("\u{2610}", 74.0, 114.65, 14.0),
+            ("\u{2718}", 74.0, 154.65, 14.0),
+        ]
+    );
+    // Only the box drawn without an appearance gets its /MK background
+    let html = &bundle.pages[0].html;
+    assert_eq!(html.matches("background:rgb(255, 255, 0)").count(), 1);
+    assert!(html.contains("left:72px;top:112px;width:20px;height:20px;background:rgb(255, 255, 0)"));
+}
+
+#[test]
+fn test_axial_shading_becomes_a_clipped_css_gradient() {
+    let content = b"q 0 692 612 100 re W n /Sh0 sh Q BT /F1 24 Tf 72 730 Td (Banner) Tj ET";
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Contents 4 0 R\
+/Resources<</Shading<</Sh0 5 0 R>>>>>>"
+            .to_vec(),
+        stream_object("", content),
+        b"<</ShadingType 2/ColorSpace/DeviceRGB/Coords[0 0 612 0]/Function 6 0 R>>".to_vec(),
+        b"<</FunctionType 2/Domain[0 1]/C0[1 0 0]/C1[0 0 1]/N 1>>".to_vec(),
+    ]);
+
+    let bundle = crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap();
+    assert!(bundle.pages[0].html.contains(
+        "left:0px;top:0px;width:612px;height:100px;\
+background:linear-gradient(90deg, rgb(255, 0, 0) 0%, rgb(0, 0, 255) 100%);"
+    ));
+    assert_eq!(bundle.pages[0].text_spans[0].text, "Banner");
+}
+
+#[test]
+fn test_clip_only_text_is_not_painted() {
+    let content = b"BT /F1 12 Tf 72 700 Td (Shown) Tj 7 Tr 0 -20 Td (Clip) Tj ET \
+BT /F1 12 Tf 5 Tr 72 640 Td (Painted) Tj ET";
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Contents 4 0 R>>".to_vec(),
+        stream_object("", content),
+    ]);
+
+    let bundle = crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap();
+    let spans: Vec<(&str, &str)> = bundle.pages[0]
+        .text_spans
+        .iter()
+        .map(|span| (span.text.as_str(), span.color.as_str()))
+        .collect();
+    // Mode 7 text is kept for search but not drawn; mode 5 paints as well as clipping
+    assert_eq!(
+        spans,
+        [("Shown", "rgb(0, 0, 0)"), ("Clip", "transparent"), ("Painted", "rgb(0, 0, 0)")]
+    );
+}
+
+#[test]
+fn test_rectangular_clip_hides_text_and_fills_outside_it() {
+    let content = b"q 0 0 300 792 re W n 1 0 0 rg 0 0 612 100 re f 0 g \
+BT /F1 12 Tf 72 700 Td (Left) Tj 300 0 Td (Right) Tj ET Q \
+BT /F1 12 Tf 400 600 Td (After) Tj ET";
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Contents 4 0 R\
+/Resources<</Font<</F1 5 0 R>>>>>>"
+            .to_vec(),
+        stream_object("", content),
+        b"<</Type/Font/Subtype/Type1/BaseFont/Helvetica>>".to_vec(),
+    ]);
+
+    let bundle = crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap();
+    let page = &bundle.pages[0];
+    let texts: Vec<&str> = page.text_spans.iter().map(|span| span.text.as_str()).collect();
+    // The right half of the line is clipped away; Q lifts the clip again
+    assert_eq!(texts, ["Left", "After"]);
+    assert!(page.html.contains(
+        "left:0px;top:692px;width:300px;height:100px;background:rgb(255, 0, 0);"
+    ));
+}
+
+#[test]
+fn test_note_contents_become_marker_tooltips() {
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Contents 4 0 R/Annots[5 0 R 6 0 R]>>"
+            .to_vec(),
+        stream_object("", b""),
+        b"<</Type/Annot/Subtype/Text/Rect[100 700 120 720]/Contents(Check \"Q3\" totals)>>"
+            .to_vec(),
+        b"<</Type/Annot/Subtype/Popup/Parent 5 0 R/Rect[200 600 300 700]>>".to_vec(),
+    ]);
+
+    let plain = crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap();
+    assert!(!plain.pages[0].html.contains("title="));
+
+    let config = ConversionConfig {
+        note_tooltips: true,
+        ..Default::default()
+    };
+    let bundle = crate::convert_pdf(&pdf, &config).unwrap();
+    let html = &bundle.pages[0].html;
+    assert!(html.contains(
+        "<div title=\"Check &quot;Q3&quot; totals\" style=\"position:absolute;left:100px;top:72px;width:12px;height:12px;"
+    ));
+    assert_eq!(html.matches("title=").count(), 1);
+}
+
+#[test]
+fn test_link_annotations_become_anchors() {
+    let content = b"BT /F1 12 Tf 72 700 Td (Docs) Tj ET";
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R/Names<</Dests 8 0 R>>>>".to_vec(),
+        b"<</Type/Pages/Count 2/Kids[3 0 R 4 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Contents 5 0 R/Annots[6 0 R 7 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Contents 5 0 R>>".to_vec(),
+        stream_object("", content),
+        b"<</Type/Annot/Subtype/Link/Rect[72 695 120 715]/A<</S/URI/URI(https://example.com/docs)>>>>".to_vec(),
+        b"<</Type/Annot/Subtype/Link/Rect[72 600 120 620]/Dest(chapter.2)>>".to_vec(),
+        b"<</Names[(chapter.2)[4 0 R/XYZ 0 792 0]]>>".to_vec(),
+    ]);
+
+    let bundle = crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap();
+    let html = &bundle.pages[0].html;
+
+    assert!(html.contains(
+        "<a href=\"https://example.com/docs\" style=\"position:absolute;left:72px;top:77px;width:48px;height:20px;\"></a>"
+    ));
+    assert!(html.contains("<a href=\"#page-2\""));
+    assert!(!bundle.pages[1].html.contains("<a "));
+}
+
+#[test]
+fn test_spans_reference_extracted_font_faces() {
+    let content = b"BT /F1 12 Tf 72 700 Td (Embedded) Tj /F2 12 Tf 0 -20 Td (Standard) Tj ET";
+    let resources = b"/Resources<</Font<</F1 6 0 R/F2 9 0 R>>>>";
+    let page = |contents: &[u8]| {
+        let mut obj = b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]".to_vec();
+        obj.extend_from_slice(resources);
+        obj.extend_from_slice(contents);
+        obj.extend_from_slice(b">>");
+        obj
+    };
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 2/Kids[3 0 R 4 0 R]>>".to_vec(),
+        page(b"/Contents 5 0 R"),
+        page(b"/Contents 5 0 R"),
+        stream_object("", content),
+        b"<</Type/Font/Subtype/TrueType/BaseFont/Embedded/FontDescriptor 7 0 R>>".to_vec(),
+        b"<</Type/FontDescriptor/FontName/Embedded/FontFile2 8 0 R>>".to_vec(),
+        stream_object("", b"\x00\x01\x00\x00fake font program"),
+        b"<</Type/Font/Subtype/Type1/BaseFont/Helvetica>>".to_vec(),
+    ]);
+
+    let bundle = crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap();
+
+    // Both pages share the font object, so it is extracted once
+    assert_eq!(bundle.fonts.len(), 1);
+    let font = &bundle.fonts[0];
+    assert!(bundle.css.contains(&format!(
+        "font-family: 'ff{}';\n  src: url('{}')",
+        font.font_id, font.filename
+    )));
+
+    for page in &bundle.pages {
+        let fonts: Vec<_> = page
+            .text_spans
+            .iter()
+            .map(|s| (s.text.as_str(), s.font_id))
+            .collect();
+        assert_eq!(
+            fonts,
+            vec![("Embedded", Some(font.font_id)), ("Standard", None)]
+        );
+        assert!(page
+            .html
+            .contains(&format!("font-family:'ff{}';", font.font_id)));
+    }
+}
+
+#[test]
+fn test_html_with_assets_references_only_returned_files() {
+    let content = b"BT /F1 12 Tf 72 700 Td (Embedded) Tj ET q 200 0 0 100 72 500 cm /Photo Do Q";
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]\
+/Resources<</Font<</F1 5 0 R>>/XObject<</Photo 8 0 R>>>>/Contents 4 0 R>>"
+            .to_vec(),
+        stream_object("", content),
+        b"<</Type/Font/Subtype/TrueType/BaseFont/Embedded/FontDescriptor 6 0 R>>".to_vec(),
+        b"<</Type/FontDescriptor/FontName/Embedded/FontFile2 7 0 R>>".to_vec(),
+        stream_object("", b"\x00\x01\x00\x00fake font program"),
+        stream_object(
+            "/Type/XObject/Subtype/Image/Width 4/Height 2/ColorSpace/DeviceRGB\
+/BitsPerComponent 8/Filter/DCTDecode",
+            b"\xFF\xD8\xFF\xE0fake jpeg\xFF\xD9",
+        ),
+    ]);
+
+    let (html, assets) = crate::convert_to_html_with_assets(&pdf, &ConversionConfig::default())
+        .unwrap();
+
+    assert_eq!(assets.len(), 2);
+    assert!(!html.contains("data:"));
+    let references: Vec<&str> = html
+        .split("url('")
+        .skip(1)
+        .chain(html.split("src=\"").skip(1))
+        .filter_map(|rest| rest.split(['\'', '"']).next())
+        .collect();
+    assert_eq!(references.len(), 2);
+    for reference in references {
+        assert!(assets.contains_key(reference), "{} is not an asset", reference);
+    }
+    assert!(assets.values().any(|data| data.as_slice() == b"\xFF\xD8\xFF\xE0fake jpeg\xFF\xD9"));
+}
+
+#[test]
+fn test_info_and_xmp_metadata_reach_the_bundle() {
+    let xmp = br#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF><rdf:Description rdf:about=""
+ xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title><rdf:Alt>
+<rdf:li xml:lang="x-default">XMP title</rdf:li></rdf:Alt></dc:title><dc:creator><rdf:Seq>
+<rdf:li>Ada Lovelace</rdf:li></rdf:Seq></dc:creator></rdf:Description></rdf:RDF></x:xmpmeta>"#;
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R/Metadata 5 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]>>".to_vec(),
+        b"<</Title(Q3 <Report> & Notes)/Keywords(finance)/CreationDate(D:20230115120000+01'00')>>"
+            .to_vec(),
+        stream_object("/Type/Metadata/Subtype/XML", xmp),
+    ]);
+    let pdf = String::from_utf8(pdf)
+        .unwrap()
+        .replace("/Root 1 0 R>>", "/Root 1 0 R/Info 4 0 R>>")
+        .into_bytes();
+
+    let bundle = crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap();
+
+    let metadata = &bundle.metadata;
+    // /Info wins over XMP; XMP fills what /Info lacks
+    assert_eq!(metadata.title.as_deref(), Some("Q3 <Report> & Notes"));
+    assert_eq!(metadata.author.as_deref(), Some("Ada Lovelace"));
+    assert_eq!(metadata.keywords.as_deref(), Some("finance"));
+    assert_eq!(
+        metadata.creation_date.map(|d| d.to_rfc3339()).as_deref(),
+        Some("2023-01-15T11:00:00+00:00")
+    );
+    assert_eq!(bundle.summary().metadata, *metadata);
+    assert!(bundle
+        .to_single_file_html()
+        .contains("<title>Q3 &lt;Report&gt; &amp; Notes</title>"));
+}
+
+#[test]
+fn test_outline_becomes_a_navigation_sidebar() {
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R/Outlines 6 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 3/Kids[3 0 R 4 0 R 5 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]>>".to_vec(),
+        b"<</Type/Outlines/First 7 0 R/Last 8 0 R/Count 3>>".to_vec(),
+        b"<</Title(Introduction)/Parent 6 0 R/Next 8 0 R/Dest[3 0 R/Fit]>>".to_vec(),
+        b"<</Title(Part <I>)/Parent 6 0 R/Prev 7 0 R/First 9 0 R/Last 9 0 R/Count 1\
+/A<</S/GoTo/D[4 0 R/XYZ 0 792 0]>>>>"
+            .to_vec(),
+        // A /Next pointing back at itself must not loop
+        b"<</Title(Chapter 1)/Parent 8 0 R/Next 9 0 R/Dest[5 0 R/Fit]>>".to_vec(),
+    ]);
+
+    let bundle = crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap();
+
+    let outline: Vec<(&str, Option<usize>, usize)> = bundle
+        .outline
+        .iter()
+        .map(|item| (item.title.as_str(), item.dest_page, item.children.len()))
+        .collect();
+    assert_eq!(outline, [("Introduction", Some(1), 0), ("Part <I>", Some(2), 1)]);
+    assert_eq!(bundle.outline[1].children[0].title, "Chapter 1");
+    assert_eq!(bundle.outline[1].children[0].dest_page, Some(3));
+
+    let html = bundle.to_single_file_html();
+    assert!(html.contains("<nav class=\"outline\">"));
+    assert!(html.contains("<summary><a href=\"#page-2\">Part &lt;I&gt;</a></summary>"));
+    assert!(html.contains("<li><a href=\"#page-3\">Chapter 1</a></li>"));
+
+    let config = ConversionConfig {
+        process_outline: false,
+        ..Default::default()
+    };
+    let bundle = crate::convert_pdf(&pdf, &config).unwrap();
+    assert!(!bundle.to_single_file_html().contains("<nav"));
+}
+
+/// Append an incremental update redefining `objects`, chained to the
+/// file's last xref section by `/Prev`.
+fn append_revision(mut pdf: Vec<u8>, size: usize, objects: &[(u64, Vec<u8>)]) -> Vec<u8> {
+    let tail = String::from_utf8_lossy(&pdf[pdf.len().saturating_sub(64)..]).into_owned();
+    let prev: usize = tail
+        .rsplit("startxref")
+        .next()
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(|offset| offset.parse().ok())
+        .unwrap();
+    pdf.push(b'\n');
+    let mut section = String::from("xref\n0 1\n0000000000 65535 f \n");
+    for (id, body) in objects {
+        section.push_str(&format!("{} 1\n{:010} 00000 n \n", id, pdf.len()));
+        pdf.extend_from_slice(format!("{} 0 obj\n", id).as_bytes());
+        pdf.extend_from_slice(body);
+        pdf.extend_from_slice(b"\nendobj\n");
+    }
+    let xref_offset = pdf.len();
+    pdf.extend_from_slice(section.as_bytes());
+    pdf.extend_from_slice(
+        format!(
+            "trailer\n<</Size {}/Root 1 0 R/Prev {}>>\nstartxref\n{}\n%%EOF",
+            size, prev, xref_offset
+        )
+        .as_bytes(),
+    );
+    pdf
+}
+
+#[test]
+fn test_incremental_updates_resolve_to_the_newest_revision() {
+    let page_text = |text: &str| {
+        stream_object("", format!("BT /F1 12 Tf 72 700 Td ({}) Tj ET", text).as_bytes())
+    };
+    let original = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Resources<</Font<</F1 5 0 R>>>>\
+/Contents 4 0 R>>"
+            .to_vec(),
+        page_text("Original"),
+        b"<</Type/Font/Subtype/Type1/BaseFont/Helvetica>>".to_vec(),
+    ]);
+    let revised = append_revision(original, 6, &[(4, page_text("Revised"))]);
+    // A later update that leaves object 4 alone must not resurrect the original
+    let signed = append_revision(
+        revised,
+        6,
+        &[(1, b"<</Type/Catalog/Pages 2 0 R/Lang(en)>>".to_vec())],
+    );
+
+    let bundle = crate::convert_pdf(&signed, &ConversionConfig::default()).unwrap();
+    let texts: Vec<&str> = bundle.pages[0].text_spans.iter().map(|s| s.text.as_str()).collect();
+    assert_eq!(texts, ["Revised"]);
+    assert_eq!(bundle.language.as_deref(), Some("en"));
+
+    let document = crate::parser::parse_pdf(&signed).unwrap();
+    let entries = &document.xref.unwrap().entries;
+    assert_eq!(entries.iter().filter(|e| e.object_id == 4).count(), 1);
+}
+
+#[test]
+fn test_vector_fills_become_background_rects() {
+    // A shaded table row, a hairline rule placed by the CTM and a fill
+    // painted from inside a form XObject
+    let content = b"0.9 g 72 600 468 40 re f \
+q 1 0 0 1 72 580 cm 0 0 0 1 k 0 0 468 0.5 re f Q /Cell Do";
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Contents 4 0 R\
+/Resources<</XObject<</Cell 5 0 R>>>>>>"
+            .to_vec(),
+        stream_object("", content),
+        stream_object(
+            "/Type/XObject/Subtype/Form/BBox[0 0 100 100]/Matrix[1 0 0 1 100 100]",
+            b"0 0 1 rg 10 10 50 20 re f",
+        ),
+    ]);
+
+    let bundle = crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap();
+    let page = &bundle.pages[0];
+    let rects: Vec<(f64, f64, f64, f64, &str)> = page
+        .filled_rects
+        .iter()
+        .map(|r| (r.x, r.y, r.width, r.height, r.color.as_str()))
+        .collect();
+    assert_eq!(
+        rects,
+        [
+            (72.0, 152.0, 468.0, 40.0, "rgb(229, 229, 229)"),
+            (72.0, 211.5, 468.0, 0.5, "rgb(44, 46, 53)"),
+            (110.0, 662.0, 50.0, 20.0, "rgb(0, 0, 255)"),
+        ]
+    );
+    assert!(page.html.contains(
+        "left:110px;top:662px;width:50px;height:20px;background:rgb(0, 0, 255);"
+    ));
+}
+
+#[test]
+fn test_form_content_is_clipped_to_its_bbox() {
+    // The form's second fill runs past the right edge of its BBox and its
+    // second line of text sits wholly outside it
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Contents 4 0 R\
+/Resources<</XObject<</Stamp 5 0 R>>>>>>"
+            .to_vec(),
+        stream_object("", b"/Stamp Do"),
+        stream_object(
+            "/Type/XObject/Subtype/Form/BBox[0 0 100 100]/Matrix[1 0 0 1 100 100]",
+            b"10 10 20 20 re f 50 50 100 20 re f \
+BT /F1 10 Tf 10 80 Td (Inside) Tj 0 200 Td (Outside) Tj ET",
+        ),
+    ]);
+
+    let bundle = crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap();
+    let page = &bundle.pages[0];
+    let rects: Vec<(f64, f64, f64, f64)> = page
+        .filled_rects
+        .iter()
+        .map(|r| (r.x, r.y, r.width, r.height))
+        .collect();
+    assert_eq!(rects, [(110.0, 662.0, 20.0, 20.0), (150.0, 622.0, 50.0, 20.0)]);
+    let texts: Vec<&str> = page.text_spans.iter().map(|s| s.text.as_str()).collect();
+    assert_eq!(texts, ["Inside"]);
+}
+
+#[test]
+fn test_stroked_rules_borders_and_curves_are_drawn() {
+    // A table cell border, an underline in a form field's appearance and a
+    // curved stroke
+    let content = b"1 0 0 RG 2 w 72 600 200 40 re S \
+0 G 0.5 w 100 100 m 100 100 l 300 100 l S /Field Do \
+0 0 1 RG 1 w 50 50 m 60 80 90 80 100 50 c S";
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Contents 4 0 R\
+/Resources<</XObject<</Field 5 0 R>>>>>>"
+            .to_vec(),
+        stream_object("", content),
+        stream_object(
+            "/Type/XObject/Subtype/Form/BBox[0 0 200 20]/Matrix[1 0 0 1 72 400]",
+            b"0 0 0 RG 1 w 0 2 m 200 2 l S",
+        ),
+    ]);
+
+    let bundle = crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap();
+    let page = &bundle.pages[0];
+    assert_eq!(page.strokes.len(), 4);
+    let html = &page.html;
+    assert!(html.contains(
+        "left:71px;top:151px;width:202px;height:42px;box-sizing:border-box;\
+border:2px solid rgb(255, 0, 0);"
+    ));
+    assert!(html.contains(
+        "left:100px;top:691.75px;width:200px;height:0px;border-top:0.5px solid rgb(0, 0, 0);"
+    ));
+    assert!(html.contains(
+        "left:72px;top:389.5px;width:200px;height:0px;border-top:1px solid rgb(0, 0, 0);"
+    ));
+    assert!(html.contains(
+        "<path d=\"M50 742 C60 712 90 712 100 742\" fill=\"none\" stroke=\"rgb(0, 0, 255)\" \
+stroke-width=\"1\"/>"
+    ));
+}
+
+#[test]
+fn test_large_images_are_scaled_down_and_reencoded() {
+    use flate2::write::ZlibEncoder;
+    use std::io::Write;
+
+    let deflate = |data: &[u8]| {
+        let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    };
+    let mut photo = Vec::new();
+    jpeg_encoder::Encoder::new(&mut photo, 90)
+        .encode(&[128; 8 * 8 * 3], 8, 8, jpeg_encoder::ColorType::Rgb)
+        .unwrap();
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Contents 4 0 R\
+/Resources<</XObject<</Chart 5 0 R/Photo 6 0 R/Stamp 7 0 R>>>>>>"
+            .to_vec(),
+        stream_object("", b"q 80 0 0 40 72 600 cm /Chart Do Q q 80 0 0 80 72 400 cm /Photo Do Q \
+q 80 0 0 10 72 300 cm /Stamp Do Q"),
+        stream_object(
+            "/Type/XObject/Subtype/Image/Width 8/Height 4/ColorSpace/DeviceRGB\
+/BitsPerComponent 8/Filter/FlateDecode",
+            &deflate(&[200; 8 * 4 * 3]),
+        ),
+        stream_object(
+            "/Type/XObject/Subtype/Image/Width 8/Height 8/ColorSpace/DeviceRGB\
+/BitsPerComponent 8/Filter/DCTDecode",
+            &photo,
+        ),
+        stream_object(
+            "/Type/XObject/Subtype/Image/Width 8/Height 1/ImageMask true/Filter/FlateDecode",
+            &deflate(&[0x0F]),
+        ),
+    ]);
+    let summary = |config: &ConversionConfig| {
+        let mut images: Vec<(String, String, u32, u32)> = crate::extract_images(&pdf, config)
+            .unwrap()
+            .into_iter()
+            .map(|i| (i.name, i.format, i.pixel_width, i.pixel_height))
+            .collect();
+        images.sort();
+        images
+    };
+    let image = |name: &str, format: &str, width, height| {
+        (name.to_string(), format.to_string(), width, height)
+    };
+
+    assert_eq!(
+        summary(&ConversionConfig::default()),
+        [image("Chart", "png", 8, 4), image("Photo", "jpg", 8, 8), image("Stamp", "png", 8, 1)]
+    );
+    let config = ConversionConfig {
+        max_image_dimension: Some(4),
+        image_quality: 60,
+        ..Default::default()
+    };
+    // Opaque images become JPEGs; the stencil mask keeps its transparency
+    assert_eq!(
+        summary(&config),
+        [image("Chart", "jpg", 4, 2), image("Photo", "jpg", 4, 4), image("Stamp", "png", 4, 1)]
+    );
+}
+
+#[test]
+fn test_jbig2_images_need_a_registered_decoder() {
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Contents 4 0 R\
+/Resources<</XObject<</Scan 5 0 R>>>>>>"
+            .to_vec(),
+        stream_object("", b"q 80 0 0 20 72 600 cm /Scan Do Q"),
+        stream_object(
+            "/Type/XObject/Subtype/Image/Width 8/Height 2/ColorSpace/DeviceGray\
+/BitsPerComponent 1/Filter/JBIG2Decode/DecodeParms<</JBIG2Globals 6 0 R>>",
+            b"page segments",
+        ),
+        stream_object("", b"global segments"),
+    ]);
+
+    let page = &crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap().pages[0];
+    assert_eq!(page.images.len(), 1);
+    assert_eq!((page.images[0].pixel_width, page.images[0].pixel_height), (1, 1));
+    assert_eq!(
+        page.diagnostics,
+        ["image /Scan: unsupported filter JBIG2Decode, drawn as a placeholder"]
+    );
+
+    let globals = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let mut config = ConversionConfig::default();
+    let seen = globals.clone();
+    config.filters.register("JBIG2Decode", move |data: &[u8], parms: &crate::parser::DecodeParms| {
+        assert_eq!(data, b"page segments");
+        if let Some(crate::parser::PdfObject::Stream(global_data, _)) = parms.get("JBIG2Globals") {
+            *seen.lock().unwrap() = global_data.clone();
+        }
+        // The top row black, the bottom one white
+        Ok(vec![0xFF, 0x00])
+    });
+    let page = &crate::convert_pdf(&pdf, &config).unwrap().pages[0];
+    assert_eq!(*globals.lock().unwrap(), b"global segments");
+    assert!(page.diagnostics.is_empty(), "{:?}", page.diagnostics);
+    let image = &page.images[0];
+    assert_eq!(image.mime_type, "image/png");
+    assert_eq!((image.pixel_width, image.pixel_height), (8, 2));
+}
+
+#[test]
+fn test_runaway_form_chain_times_out_with_the_page_reached() {
+    // Each form draws the next one ten times, so eight levels are 10^8
+    // fills: cheap to parse, far too slow to render
+    const DEPTH: usize = 8;
+    let draw_ten = |name: &str| format!("/{} Do ", name).repeat(10).into_bytes();
+    let mut objects = vec![
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 2/Kids[3 0 R 4 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Contents 5 0 R\
+/Resources<</XObject<</F 6 0 R>>>>>>"
+            .to_vec(),
+        stream_object("", &draw_ten("F")),
+    ];
+    for level in 0..DEPTH {
+        let form = 6 + level;
+        objects.push(if level + 1 < DEPTH {
+            stream_object(
+                &format!(
+                    "/Type/XObject/Subtype/Form/BBox[0 0 10 10]\
+/Resources<</XObject<</F {} 0 R>>>>",
+                    form + 1
+                ),
+                &draw_ten("F"),
+            )
+        } else {
+            stream_object("/Type/XObject/Subtype/Form/BBox[0 0 10 10]", b"0 0 5 5 re f")
+        });
+    }
+    let pdf = build_pdf(&objects);
+
+    let config = ConversionConfig {
+        timeout: Some(Duration::from_millis(50)),
+        ..Default::default()
+    };
+    let started = std::time::Instant::now();
+    match crate::convert_pdf(&pdf, &config) {
+        Err(OdeError::Timeout(message)) => assert!(message.contains("page 2"), "{}", message),
+        other => panic!("expected a timeout, got {:?}", other.map(|b| b.pages.len())),
+    }
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn test_high_ratio_flate_stream_is_rejected_while_decompressing() {
+    use flate2::write::ZlibEncoder;
+    use std::io::Write;
+
+    // Text followed by 12 MiB of spaces squeezes a page into a few KiB
+    let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+    encoder.write_all(b"BT /F1 12 Tf 72 700 Td (Hello) Tj ET").unwrap();
+    encoder.write_all(&vec![b' '; 12 * 1024 * 1024]).unwrap();
+    let content = encoder.finish().unwrap();
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Contents 4 0 R>>".to_vec(),
+        stream_object("/Filter/FlateDecode", &content),
+    ]);
+
+    let limits = ZipBombDetector::from_config(&ConversionConfig::default());
+    assert!(matches!(limits.inflate(&content), Err(OdeError::ZipBomb { .. })));
+
+    let bundle = crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap();
+    let page = &bundle.pages[0];
+    assert!(page.text_spans.is_empty());
+    assert!(page.diagnostics.iter().any(|d| d.contains("zip bomb")), "{:?}", page.diagnostics);
+
+    let trusting = ConversionConfig {
+        max_compression_ratio: 10_000,
+        ..Default::default()
+    };
+    let bundle = crate::convert_pdf(&pdf, &trusting).unwrap();
+    assert!(bundle.pages[0].text_spans.iter().any(|s| s.text == "Hello"));
+}
+
+#[test]
+fn test_embedded_truetype_font_is_subset_to_the_glyphs_shown() {
+    let font = crate::fonts::subset::test_font(3000);
+    // Forty CIDs, half shown directly and half through a form
+    let cids = |range: std::ops::Range<u16>| {
+        range.map(|cid| format!("{:04X}", cid)).collect::<String>()
+    };
+    let content = format!("BT /F1 12 Tf 72 700 Td <{}> Tj ET /Fm0 Do", cids(1..21));
+    let form = format!("BT /F1 12 Tf 72 600 Td <{}> Tj ET", cids(21..41));
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]\
+/Resources<</Font<</F1 5 0 R>>/XObject<</Fm0 9 0 R>>>>/Contents 4 0 R>>"
+            .to_vec(),
+        stream_object("", content.as_bytes()),
+        b"<</Type/Font/Subtype/Type0/BaseFont/CJK/Encoding/Identity-H/DescendantFonts[6 0 R]>>"
+            .to_vec(),
+        b"<</Type/Font/Subtype/CIDFontType2/BaseFont/CJK/FontDescriptor 7 0 R>>".to_vec(),
+        b"<</Type/FontDescriptor/FontName/CJK/FontFile2 8 0 R>>".to_vec(),
+        stream_object("", &font),
+        stream_object(
+            "/Type/XObject/Subtype/Form/BBox[0 0 612 792]/Resources<</Font<</F1 5 0 R>>>>",
+            form.as_bytes(),
+        ),
+    ]);
+
+    let whole = ConversionConfig {
+        subset_fonts: false,
+        ..Default::default()
+    };
+    let whole = crate::convert_pdf(&pdf, &whole).unwrap();
+    let subset = crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap();
+    let (whole, subset) = (&whole.fonts[0].data, &subset.fonts[0].data);
+    assert!(subset.len() * 2 < whole.len(), "{} of {} bytes", subset.len(), whole.len());
+
+    // The program follows the 64-byte WOFF2 header and keeps its glyph ids
+    let face = ttf_parser::Face::parse(&subset[64..], 0).unwrap();
+    assert_eq!(face.number_of_glyphs(), 3000);
+    let drawn = |gid| face.glyph_bounding_box(ttf_parser::GlyphId(gid)).is_some();
+    assert!(drawn(1) && drawn(20) && drawn(21) && drawn(40));
+    assert!(!drawn(41) && !drawn(2000));
+}
+
+#[test]
+fn test_form_text_uses_the_forms_own_fonts() {
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]\
+/Resources<</Font<</F1 5 0 R>>/XObject<</Fm0 8 0 R>>>>/Contents 4 0 R>>"
+            .to_vec(),
+        stream_object("", b"BT /F1 12 Tf 72 700 Td (Page) Tj ET /Fm0 Do"),
+        b"<</Type/Font/Subtype/TrueType/BaseFont/PageFont/FontDescriptor 6 0 R>>".to_vec(),
+        b"<</Type/FontDescriptor/FontName/PageFont/FontFile2 7 0 R>>".to_vec(),
+        stream_object("", b"\x00\x01\x00\x00page font program"),
+        // The form names another font F1
+        stream_object(
+            "/Type/XObject/Subtype/Form/BBox[0 0 612 792]/Resources<</Font<</F1 9 0 R>>>>",
+            b"BT /F1 12 Tf 72 600 Td (Form) Tj ET",
+        ),
+        b"<</Type/Font/Subtype/TrueType/BaseFont/FormFont/FontDescriptor 10 0 R>>".to_vec(),
+        b"<</Type/FontDescriptor/FontName/FormFont/FontFile2 11 0 R>>".to_vec(),
+        stream_object("", b"\x00\x01\x00\x00form font program"),
+    ]);
+
+    let bundle = crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap();
+    let font_of = |text: &str| {
+        let span = bundle.pages[0].text_spans.iter().find(|s| s.text == text).unwrap();
+        let font = bundle.fonts.iter().find(|f| Some(f.font_id) == span.font_id).unwrap();
+        font.font_name.clone()
+    };
+    assert_eq!(font_of("Page"), "PageFont");
+    assert_eq!(font_of("Form"), "FormFont");
+}
+
+#[test]
+fn test_cached_pages_name_fonts_subset_for_another_selection() {
+    use crate::renderer::PageCache;
+    use std::sync::Arc;
+
+    let font = crate::fonts::subset::test_font(3000);
+    let page = |contents: usize| {
+        format!(
+            "<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]\
+/Resources<</Font<</F1 7 0 R>>>>/Contents {} 0 R>>",
+            contents
+        )
+        .into_bytes()
+    };
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 2/Kids[3 0 R 4 0 R]>>".to_vec(),
+        page(5),
+        page(6),
+        stream_object("", b"BT /F1 12 Tf 72 700 Td <00010002> Tj ET"),
+        stream_object("", b"BT /F1 12 Tf 72 700 Td <00150016> Tj ET"),
+        b"<</Type/Font/Subtype/Type0/BaseFont/CJK/Encoding/Identity-H/DescendantFonts[8 0 R]>>"
+            .to_vec(),
+        b"<</Type/Font/Subtype/CIDFontType2/BaseFont/CJK/FontDescriptor 9 0 R>>".to_vec(),
+        b"<</Type/FontDescriptor/FontName/CJK/FontFile2 10 0 R>>".to_vec(),
+        stream_object("", &font),
+    ]);
+
+    let cache = Arc::new(PageCache::new());
+    let config = |pages: &str| ConversionConfig {
+        pages: Some(serde_json::from_str(&format!("\"{}\"", pages)).unwrap()),
+        page_cache: Some(cache.clone()),
+        ..Default::default()
+    };
+    let first = crate::convert_pdf(&pdf, &config("1")).unwrap();
+    let second = crate::convert_pdf(&pdf, &config("1-2")).unwrap();
+    assert_eq!((cache.hits(), cache.misses()), (1, 2));
+
+    // The font is subset to other glyphs, but keeps the id the cached page names
+    assert_ne!(first.fonts[0].data, second.fonts[0].data);
+    let font_ids: Vec<u64> = second.fonts.iter().map(|f| f.font_id).collect();
+    for page in &second.pages {
+        let span = &page.text_spans[0];
+        assert!(font_ids.contains(&span.font_id.unwrap()), "page {}", page.page_number);
+    }
+}
+
+#[test]
+fn test_encoding_differences_decode_text_without_tounicode() {
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Resources<</Font<</F1 5 0 R>>>>\
+/Contents 4 0 R>>"
+            .to_vec(),
+        stream_object("", b"BT /F1 12 Tf 72 700 Td <41420102E9> Tj ET"),
+        b"<</Type/Font/Subtype/Type1/BaseFont/CMR10/Encoding<</Type/Encoding\
+/BaseEncoding/WinAnsiEncoding/Differences[65/A 66/B 1/alpha/fi]>>>>"
+            .to_vec(),
+    ]);
+
+    let bundle = crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap();
+    let text: String = bundle.pages[0]
+        .text_spans
+        .iter()
+        .map(|s| s.text.as_str())
+        .collect();
+    // Codes 1 and 2 come from /Differences, 0xE9 from the WinAnsi base
+    assert_eq!(text, "AB\u{3B1}\u{FB01}\u{E9}");
+}
+
+#[test]
+fn test_encoding_differences_skip_codes_out_of_range() {
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Resources<</Font<</F1 5 0 R>>>>\
+/Contents 4 0 R>>"
+            .to_vec(),
+        stream_object("", b"BT /F1 12 Tf 72 700 Td <4142> Tj ET"),
+        b"<</Type/Font/Subtype/Type1/BaseFont/CMR10/Encoding<</Type/Encoding\
+/Differences[1e300/alpha/beta 255/gamma/delta 66/B]>>>>"
+            .to_vec(),
+    ]);
+
+    let bundle = crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap();
+    let text: String = bundle.pages[0].text_spans.iter().map(|s| s.text.as_str()).collect();
+    assert_eq!(text, "AB");
+}
+
+#[test]
+fn test_type3_font_text_comes_from_its_encoding() {
+    // The glyph procedures only draw, so the text is what /Differences names
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Resources<</Font<</T3 5 0 R>>>>\
+/Contents 4 0 R>>"
+            .to_vec(),
+        stream_object("", b"BT /T3 20 Tf 72 700 Td <0102> Tj ET"),
+        b"<</Type/Font/Subtype/Type3/FontMatrix[0.001 0 0 0.001 0 0]/FirstChar 1/LastChar 2\
+/Widths[600 600]/Encoding<</Differences[1/H/i]>>/CharProcs<</H 6 0 R/i 6 0 R>>>>"
+            .to_vec(),
+        stream_object("", b"600 0 0 0 500 700 d1 0 0 500 700 re f"),
+    ]);
+
+    let bundle = crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap();
+    let text: String = bundle.pages[0].text_spans.iter().map(|s| s.text.as_str()).collect();
+    assert_eq!(text, "Hi");
+}
+
+#[test]
+fn test_covered_text_is_dropped_or_marked() {
+    use crate::config::CoveredText;
+
+    // A box drawn under "Visible", and one drawn over "Secret"
+    let content = b"0 g 70 695 100 20 re f BT /F1 12 Tf 72 700 Td (Visible) Tj ET \
+BT /F1 12 Tf 72 650 Td (Secret) Tj ET 70 645 100 20 re f";
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Resources<</Font<</F1 5 0 R>>>>\
+/Contents 4 0 R>>"
+            .to_vec(),
+        stream_object("", content),
+        b"<</Type/Font/Subtype/Type1/BaseFont/Helvetica>>".to_vec(),
+    ]);
+    let page = |covered_text| {
+        let config = ConversionConfig {
+            covered_text,
+            ..Default::default()
+        };
+        crate::convert_pdf(&pdf, &config).unwrap().pages.remove(0)
+    };
+    let texts = |page: &crate::RenderedPage| -> Vec<(String, bool)> {
+        let spans = page.text_spans.iter();
+        spans.map(|s| (s.text.clone(), s.covered)).collect()
+    };
+
+    let kept = page(CoveredText::Keep);
+    assert_eq!(
+        texts(&kept),
+        [("Visible".to_string(), false), ("Secret".to_string(), false)]
+    );
+    assert!(kept.html.contains("Secret"));
+
+    let dropped = page(CoveredText::Drop);
+    assert_eq!(texts(&dropped), [("Visible".to_string(), false)]);
+    assert!(!dropped.html.contains("Secret"));
+
+    let marked = page(CoveredText::Mark);
+    assert_eq!(
+        texts(&marked),
+        [("Visible".to_string(), false), ("Secret".to_string(), true)]
+    );
+    assert!(marked.html.contains("<span class=\"covered\""));
+    let config = ConversionConfig {
+        covered_text: CoveredText::Mark,
+        ..Default::default()
+    };
+    assert!(crate::convert_pdf(&pdf, &config).unwrap().css.contains(".page span.covered"));
+}
+
+#[test]
+fn test_css_every_page_uses_is_in_the_bundle_once() {
+    use crate::benchmarks::performance_benchmarks::create_multi_page_pdf;
+
+    let config = ConversionConfig::default();
+    let bundle = crate::convert_pdf(&create_multi_page_pdf(3), &config).unwrap();
+    assert!(bundle.pages.iter().all(|page| page.css.is_empty()));
+    assert!(bundle.css.starts_with(&crate::shared_css(&config)));
+
+    let html = bundle.to_single_file_html();
+    assert_eq!(html.matches(".page span {").count(), 1);
+}
+
+#[test]
+fn test_runs_of_spaces_keep_their_width() {
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Contents 4 0 R>>".to_vec(),
+        stream_object("", b"BT /F1 12 Tf 72 700 Td (a   b) Tj ET"),
+    ]);
+
+    let bundle = crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap();
+    assert_eq!(bundle.pages[0].text_spans[0].text, "a   b");
+    assert!(bundle.pages[0].html.contains(">a   b</span>"));
+    assert!(bundle.css.contains(".page span { position:absolute; white-space: pre; }"));
+
+    let config = ConversionConfig {
+        preserve_spaces: false,
+        ..Default::default()
+    };
+    let bundle = crate::convert_pdf(&pdf, &config).unwrap();
+    assert!(bundle.css.contains("white-space: nowrap;"));
+}
+
+#[test]
+fn test_document_and_marked_content_languages_reach_the_html() {
+    let content = b"BT /F1 12 Tf 72 700 Td (Hallo) Tj ET \
+/P<</Lang(fr_CA)/MCID 0>>BDC BT /F1 12 Tf 72 650 Td (Bonjour) Tj ET /Note Do EMC \
+BT /F1 12 Tf 72 600 Td (Tschuss) Tj ET";
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R/Lang(de-DE)>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Contents 4 0 R\
+/Resources<</XObject<</Note 5 0 R>>>>>>"
+            .to_vec(),
+        stream_object("", content),
+        stream_object(
+            "/Type/XObject/Subtype/Form/BBox[0 0 200 20]/Matrix[1 0 0 1 72 500]",
+            b"BT /F1 12 Tf 0 5 Td (Merci) Tj ET",
+        ),
+    ]);
+
+    let bundle = crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap();
+    assert_eq!(bundle.language.as_deref(), Some("de-DE"));
+    let langs: Vec<(&str, Option<&str>)> = bundle.pages[0]
+        .text_spans
+        .iter()
+        .map(|span| (span.text.as_str(), span.lang.as_deref()))
+        .collect();
+    assert_eq!(
+        langs,
+        [
+            ("Hallo", None),
+            ("Bonjour", Some("fr-CA")),
+            ("Merci", Some("fr-CA")),
+            ("Tschuss", None),
+        ]
+    );
+    let html = bundle.to_single_file_html();
+    assert!(html.contains("<html lang=\"de-DE\">"));
+    assert!(html.contains("<span lang=\"fr-CA\" style="));
+
+    // Without a `/Lang` the root says nothing rather than guess
+    let mut bundle = bundle;
+    bundle.language = None;
+    assert!(bundle.to_single_file_html().contains("<!DOCTYPE html>\n<html>\n"));
+}
+
+#[test]
+fn test_reflow_follows_the_structure_tree() {
+    let content = b"/Heading <</MCID 0>> BDC BT /F1 18 Tf 72 720 Td (Report) Tj ET EMC \
+/P <</MCID 1>> BDC BT /F1 12 Tf 72 690 Td (First line) Tj 0 -14 Td (second line) Tj ET EMC \
+/Artifact BMC BT /F1 9 Tf 300 30 Td (Page 1) Tj ET EMC \
+/P <</MCID 2>> BDC BT /F1 12 Tf 72 640 Td (Bonjour) Tj ET EMC";
+    let tagged = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R/StructTreeRoot 5 0 R/MarkInfo<</Marked true>>>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Contents 4 0 R/StructParents 0>>"
+            .to_vec(),
+        stream_object("", content),
+        b"<</Type/StructTreeRoot/K 6 0 R/RoleMap<</Heading/H1>>>>".to_vec(),
+        b"<</Type/StructElem/S/Document/P 5 0 R/Pg 3 0 R/K[7 0 R 8 0 R 9 0 R]>>".to_vec(),
+        b"<</Type/StructElem/S/Heading/P 6 0 R/K 0>>".to_vec(),
+        b"<</Type/StructElem/S/P/P 6 0 R/K[1]>>".to_vec(),
+        b"<</Type/StructElem/S/P/P 6 0 R/Lang(fr)/K<</Type/MCR/Pg 3 0 R/MCID 2>>>>".to_vec(),
+    ]);
+    let reflow = ConversionConfig {
+        reflow: true,
+        ..Default::default()
+    };
+
+    let bundle = crate::convert_pdf(&tagged, &reflow).unwrap();
+    let html = bundle.to_single_file_html();
+    assert!(
+        html.contains(
+            "<main class=\"reflow\">\n<div><h1>Report</h1>\n<p>First line second line</p>\n\
+<p lang=\"fr\">Bonjour</p>\n</div>\n</main>\n"
+        ),
+        "{}",
+        html
+    );
+    assert!(!html.contains("Page 1"));
+    assert!(!html.contains("class=\"page\""));
+
+    // Without the flag, or without tags, pages keep their layout
+    let html = crate::convert_pdf(&tagged, &ConversionConfig::default())
+        .unwrap()
+        .to_single_file_html();
+    assert!(html.contains("class=\"page\"") && !html.contains("<main"));
+    let untagged = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Contents 4 0 R>>".to_vec(),
+        stream_object("", content),
+    ]);
+    let bundle = crate::convert_pdf(&untagged, &reflow).unwrap();
+    assert!(bundle.structure.is_empty());
+    assert!(bundle.to_single_file_html().contains("class=\"page\""));
+}
+
+#[test]
+fn test_image_drawn_on_every_page_is_stored_once() {
+    let content = b"q 100 0 0 50 72 700 cm /Logo Do Q";
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 3/Kids[3 0 R 3 0 R 3 0 R]/Resources<</XObject<</Logo 5 0 R>>>>>>"
+            .to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Contents 4 0 R>>".to_vec(),
+        stream_object("", content),
+        stream_object(
+            "/Type/XObject/Subtype/Image/Width 4/Height 2/ColorSpace/DeviceRGB\
+/BitsPerComponent 8/Filter/DCTDecode",
+            b"\xFF\xD8\xFF\xE0fake jpeg\xFF\xD9",
+        ),
+    ]);
+
+    let bundle = crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap();
+    assert_eq!(bundle.pages.len(), 3);
+    assert_eq!(bundle.images.len(), 1);
+    let filename = &bundle.images[0].filename;
+    assert!(filename.ends_with(".jpg"));
+    for page in &bundle.pages {
+        assert_eq!(&page.images[0].filename, filename);
+        assert!(page.html.contains(&format!("src=\"{}\"", filename)));
+        assert!(!page.html.contains("data:"));
+    }
+
+    let html = bundle.to_single_file_html();
+    assert_eq!(html.matches("data:image/jpeg;base64,").count(), 1);
+    let (_, assets) = bundle.to_linked_html();
+    assert_eq!(assets.iter().filter(|(name, _)| name == filename).count(), 1);
+}
+
+#[test]
+fn test_layers_hidden_by_default_are_left_out() {
+    let content = b"/OC /MC0 BDC BT /F1 12 Tf 72 700 Td (Base) Tj ET EMC \
+/OC /MC1 BDC BT /F1 12 Tf 72 650 Td (Notes) Tj ET 0 0 1 rg 72 500 100 50 re f EMC \
+BT /F1 12 Tf 72 600 Td (Always) Tj ET";
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R/OCProperties<</OCGs[6 0 R 7 0 R]/D<</OFF[7 0 R]>>>>>>"
+            .to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Contents 4 0 R\
+/Resources<</Font<</F1 5 0 R>>/Properties<</MC0 6 0 R/MC1 7 0 R>>>>>>"
+            .to_vec(),
+        stream_object("", content),
+        b"<</Type/Font/Subtype/Type1/BaseFont/Helvetica>>".to_vec(),
+        b"<</Type/OCG/Name(Base)>>".to_vec(),
+        b"<</Type/OCG/Name(Notes)>>".to_vec(),
+    ]);
+    let texts = |config: &ConversionConfig| {
+        let bundle = crate::convert_pdf(&pdf, config).unwrap();
+        let page = &bundle.pages[0];
+        let texts: Vec<String> = page.text_spans.iter().map(|s| s.text.clone()).collect();
+        (texts, page.filled_rects.len())
+    };
+
+    assert_eq!(texts(&ConversionConfig::default()), (vec!["Base".into(), "Always".into()], 0));
+
+    let config = ConversionConfig {
+        enabled_layers: vec!["Notes".to_string()],
+        ..Default::default()
+    };
+    assert_eq!(
+        texts(&config),
+        (vec!["Base".into(), "Notes".into(), "Always".into()], 1)
+    );
+}
+
+#[test]
+fn test_type0_font_extracts_descendant_program_and_advances_by_cid_widths() {
+    let content = b"BT /F1 12 Tf 72 700 Td <000100020003> Tj <0004> Tj ET";
+    let cmap = b"begincmap\n1 begincodespacerange <0000> <FFFF> endcodespacerange\n\
+1 beginbfrange <0001> <0004> <0041> endbfrange\nendcmap";
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Resources<</Font<</F1 5 0 R>>>>\
+/Contents 4 0 R>>"
+            .to_vec(),
+        stream_object("", content),
+        b"<</Type/Font/Subtype/Type0/BaseFont/CJK/Encoding/Identity-H\
+/DescendantFonts[6 0 R]/ToUnicode 9 0 R>>"
+            .to_vec(),
+        b"<</Type/Font/Subtype/CIDFontType2/BaseFont/CJK/FontDescriptor 7 0 R\
+/DW 1000/W[1[500 1500 1000]]>>"
+            .to_vec(),
+        b"<</Type/FontDescriptor/FontName/CJK/FontFile2 8 0 R>>".to_vec(),
+        stream_object("", b"\x00\x01\x00\x00fake font program"),
+        stream_object("", cmap),
+    ]);
+
+    // One span per shown string, so each string's position shows
+    let unmerged = ConversionConfig {
+        merge_spans: false,
+        ..Default::default()
+    };
+    let bundle = crate::convert_pdf(&pdf, &unmerged).unwrap();
+
+    // The font program lives on the descendant CIDFont's descriptor
+    assert_eq!(bundle.fonts.len(), 1);
+    let font_id = Some(bundle.fonts[0].font_id);
+
+    // (500 + 1500 + 1000) / 1000 × 12 puts the second string 36 units on
+    let spans: Vec<_> = bundle.pages[0]
+        .text_spans
+        .iter()
+        .map(|s| (s.text.as_str(), s.x, s.font_id))
+        .collect();
+    assert_eq!(spans, vec![("ABC", 72.0, font_id), ("D", 108.0, font_id)]);
+}
+
+#[test]
+fn test_vertical_font_advances_down_the_page() {
+    let content = b"BT /F1 12 Tf 72 700 Td <00010002> Tj <0003> Tj ET";
+    let cmap = b"begincmap\n1 begincodespacerange <0000> <FFFF> endcodespacerange\n\
+1 beginbfrange <0001> <0003> <0041> endbfrange\nendcmap";
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Resources<</Font<</F1 5 0 R>>>>\
+/Contents 4 0 R>>"
+            .to_vec(),
+        stream_object("", content),
+        b"<</Type/Font/Subtype/Type0/BaseFont/CJK/Encoding/Identity-V\
+/DescendantFonts[6 0 R]/ToUnicode 7 0 R>>"
+            .to_vec(),
+        b"<</Type/Font/Subtype/CIDFontType2/BaseFont/CJK/DW2[880 -1000]/W2[1[-500 500 880]]>>"
+            .to_vec(),
+        stream_object("", cmap),
+    ]);
+
+    let bundle = crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap();
+    let page = &bundle.pages[0];
+
+    // Centred on x = 72, and (500 + 1000) / 1000 × 12 further down per string
+    let spans: Vec<_> = page
+        .text_spans
+        .iter()
+        .map(|s| (s.text.as_str(), s.x, s.y, s.vertical))
+        .collect();
+    assert_eq!(spans, vec![("AB", 66.0, 92.0, true), ("C", 66.0, 110.0, true)]);
+    assert!(page.html.contains("writing-mode:vertical-rl;"));
+}
+
+#[test]
+fn test_unmapped_glyph_policy_applies_to_tounicode_decoding() {
+    use crate::config::UnmappedGlyph;
+
+    let content = b"BT /F1 12 Tf 72 700 Td (AB) Tj ET";
+    let cmap = b"begincmap\n1 begincodespacerange <00> <FF> endcodespacerange\n\
+1 beginbfchar <41> <0041> endbfchar\nendcmap";
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Resources<</Font<</F1 5 0 R>>>>\
+/Contents 4 0 R>>"
+            .to_vec(),
+        stream_object("", content),
+        b"<</Type/Font/Subtype/Type1/BaseFont/Custom/ToUnicode 6 0 R>>".to_vec(),
+        stream_object("", cmap),
+    ]);
+
+    // The map only covers A; B is unmapped
+    let text = |unmapped_glyph| {
+        let config = ConversionConfig {
+            unmapped_glyph,
+            ..Default::default()
+        };
+        let bundle = crate::convert_pdf(&pdf, &config).unwrap();
+        bundle.pages[0].text_spans[0].text.clone()
+    };
+    assert_eq!(text(UnmappedGlyph::Fallback), "AB");
+    assert_eq!(text(UnmappedGlyph::Replacement), "A\u{FFFD}");
+    assert_eq!(text(UnmappedGlyph::Placeholder), "A\u{25A1}");
+    assert_eq!(text(UnmappedGlyph::Drop), "A");
+}
+
+#[test]
+fn test_standard_font_without_widths_advances_by_afm_metrics() {
+    let content = b"BT /F1 10 Tf 72 700 Td (Hello world) Tj (X) Tj ET";
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Resources<</Font<</F1 5 0 R>>>>\
+/Contents 4 0 R>>"
+            .to_vec(),
+        stream_object("", content),
+        b"<</Type/Font/Subtype/Type1/BaseFont/Helvetica>>".to_vec(),
+    ]);
+
+    // One span per shown string, so each string's position shows
+    let unmerged = ConversionConfig {
+        merge_spans: false,
+        ..Default::default()
+    };
+    let bundle = crate::convert_pdf(&pdf, &unmerged).unwrap();
+
+    // Helvetica's "Hello world" is 4945/1000 em, so 49.45 units at 10pt
+    let spans = &bundle.pages[0].text_spans;
+    assert_eq!(spans.len(), 2);
+    assert_eq!(spans[0].x, 72.0);
+    assert!((spans[1].x - 121.45).abs() < 1e-9, "{}", spans[1].x);
+}
+
+#[test]
+fn test_type3_glyph_procedures_show_their_text() {
+    let content = b"BT /T3 20 Tf 72 700 Td <0102> Tj /F1 20 Tf (!) Tj ET";
+    // Each glyph's width comes first, as d0 or d1 operands
+    let alpha = b"600 0 d0 BT /F1 1000 Tf (a) Tj ET";
+    let beta = b"700 0 0 0 700 700 d1 BT /F1 1000 Tf (b) Tj ET";
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Resources<</Font<</T3 5 0 R/F1 8 0 R>>>>\
+/Contents 4 0 R>>"
+            .to_vec(),
+        stream_object("", content),
+        b"<</Type/Font/Subtype/Type3/FontMatrix[0.001 0 0 0.001 0 0]/FirstChar 1/LastChar 2\
+/Widths[600 700]/Encoding<</Differences[1/alpha/beta]>>/CharProcs<</alpha 6 0 R/beta 7 0 R>>\
+/Resources<</Font<</F1 8 0 R>>>>>>"
+            .to_vec(),
+        stream_object("", alpha),
+        stream_object("", beta),
+        b"<</Type/Font/Subtype/Type1/BaseFont/Helvetica>>".to_vec(),
+    ]);
+
+    let unmerged = ConversionConfig {
+        merge_spans: false,
+        ..Default::default()
+    };
+    let bundle = crate::convert_pdf(&pdf, &unmerged).unwrap();
+
+    // The codes advance by their /Widths through /FontMatrix: 1.3 em at 20pt
+    let spans: Vec<(&str, f64)> = bundle.pages[0]
+        .text_spans
+        .iter()
+        .map(|span| (span.text.as_str(), span.x))
+        .collect();
+    assert_eq!(spans, [("ab", 72.0), ("!", 98.0)]);
+}
+
+#[test]
+fn test_widths_array_advances_with_char_word_and_horizontal_spacing() {
+    // Wide glyphs keep each string in its own span; the space uses /MissingWidth
+    let content = b"BT /F1 10 Tf 72 700 Td (AB) Tj (A) Tj 1 Tc 2 Tw 50 Tz (A B) Tj (B) Tj ET";
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Resources<</Font<</F1 5 0 R>>>>\
+/Contents 4 0 R>>"
+            .to_vec(),
+        stream_object("", content),
+        b"<</Type/Font/Subtype/TrueType/BaseFont/Custom/FirstChar 65/LastChar 66\
+/Widths[2500 3000]/FontDescriptor 6 0 R>>"
+            .to_vec(),
+        b"<</Type/FontDescriptor/FontName/Custom/MissingWidth 2000>>".to_vec(),
+    ]);
+
+    // One span per shown string, so each string's position shows
+    let unmerged = ConversionConfig {
+        merge_spans: false,
+        ..Default::default()
+    };
+    let bundle = crate::convert_pdf(&pdf, &unmerged).unwrap();
+
+    let xs: Vec<f64> = bundle.pages[0].text_spans.iter().map(|s| s.x).collect();
+    // "AB" is 55 units; "A" 25; "A B" (75 + 3 Tc + 2 Tw) at 50% is 40
+    let expected = [72.0, 127.0, 152.0, 192.0];
+    assert_eq!(xs.len(), expected.len(), "{:?}", xs);
+    for (x, expected) in xs.iter().zip(expected) {
+        assert!((x - expected).abs() < 1e-9, "{:?}", xs);
+    }
+}
+
+#[test]
+fn test_spans_carry_their_advance_and_line_height() {
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Resources<</Font<</F1 5 0 R>>>>\
+/Contents 4 0 R>>"
+            .to_vec(),
+        stream_object("", b"BT /F1 10 Tf 72 700 Td (Hello) Tj ET"),
+        b"<</Type/Font/Subtype/TrueType/BaseFont/Custom/FirstChar 72/LastChar 72\
+/Widths[722]/FontDescriptor 6 0 R>>"
+            .to_vec(),
+        b"<</Type/FontDescriptor/FontName/Custom/MissingWidth 500>>".to_vec(),
+    ]);
+
+    let bundle = crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap();
+    let span = &bundle.pages[0].text_spans[0];
+    assert_eq!(span.text, "Hello");
+    // "H" is 722 units and "ello" 500 each; the unextracted font gets the
+    // default 0.85 ascent and 0.2 descent
+    assert!((span.width - 27.22).abs() < 1e-9, "{}", span.width);
+    assert!((span.height - 10.5).abs() < 1e-9, "{}", span.height);
+}
+
+#[test]
+fn test_rules_under_and_through_text_become_text_decoration() {
+    let content = b"BT /F1 10 Tf 72 700 Td (Hello) Tj ET 72 698 27.22 0.5 re f \
+BT /F1 10 Tf 72 600 Td (Hello) Tj ET 0.5 w 72 604 m 99 604 l S \
+BT /F1 10 Tf 72 500 Td (Hello) Tj ET q 1 0 0 rg 72 498 27.22 0.5 re f Q \
+BT /F1 10 Tf 72 400 Td (Hello) Tj ET 0 398 612 0.5 re f";
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Resources<</Font<</F1 5 0 R>>>>\
+/Contents 4 0 R>>"
+            .to_vec(),
+        stream_object("", content),
+        b"<</Type/Font/Subtype/TrueType/BaseFont/Custom/FirstChar 72/LastChar 72\
+/Widths[722]/FontDescriptor 6 0 R>>"
+            .to_vec(),
+        b"<</Type/FontDescriptor/FontName/Custom/MissingWidth 500>>".to_vec(),
+    ]);
+
+    let config = ConversionConfig {
+        decoration_tolerance: Some(2.0),
+        ..ConversionConfig::default()
+    };
+    let bundle = crate::convert_pdf(&pdf, &config).unwrap();
+    let page = &bundle.pages[0];
+    let decorations: Vec<(bool, bool)> = page
+        .text_spans
+        .iter()
+        .map(|span| (span.underline, span.line_through))
+        .collect();
+    // The red rule isn't the text's color and the page-wide one runs far
+    // past it, so both stay rules
+    assert_eq!(
+        decorations,
+        vec![(true, false), (false, true), (false, false), (false, false)]
+    );
+    assert_eq!(page.filled_rects.len(), 2);
+    assert!(page.strokes.is_empty());
+    assert!(page.html.contains("text-decoration:underline;"));
+    assert!(page.html.contains("text-decoration:line-through;"));
+
+    // Left as rules by default
+    let page = &crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap().pages[0];
+    assert!(page.text_spans.iter().all(|s| !s.underline && !s.line_through));
+    assert_eq!(page.filled_rects.len(), 3);
+    assert_eq!(page.strokes.len(), 1);
+}
+
+#[test]
+fn test_convert_pdf_from_path_maps_the_file() {
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Contents 4 0 R>>".to_vec(),
+        stream_object("", b"BT /F1 12 Tf 72 700 Td (Mapped) Tj ET"),
+    ]);
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("mapped.pdf");
+    std::fs::write(&path, &pdf).unwrap();
+
+    let config = ConversionConfig::default();
+    let mapped = crate::convert_pdf_from_path(&path, &config).unwrap();
+    let read = crate::convert_pdf(&pdf, &config).unwrap();
+    assert_eq!(mapped.pages[0].text_spans[0].text, "Mapped");
+    assert_eq!(mapped.pages[0].html, read.pages[0].html);
+
+    let missing = dir.path().join("missing.pdf");
+    let err = crate::convert_pdf_from_path(&missing, &config).unwrap_err();
+    assert!(matches!(err, OdeError::IoError(_)));
+    assert!(err.to_string().contains("missing.pdf"), "{}", err);
+}
+
+#[test]
+fn test_leading_moves_text_down_a_line() {
+    let content = b"BT /F1 12 Tf 72 700 Td 14 TL (line1) Tj T* (line2) Tj (line3) ' \
+2 1 (line4) \" 0 -20 TD (line5) Tj T* (line6) Tj ET";
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Contents 4 0 R>>".to_vec(),
+        stream_object("", content),
+    ]);
+
+    let bundle = crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap();
+    let spans = &bundle.pages[0].text_spans;
+    let texts: Vec<&str> = spans.iter().map(|s| s.text.as_str()).collect();
+    assert_eq!(texts, ["line1", "line2", "line3", "line4", "line5", "line6"]);
+    // TD sets the leading to 20 for the T* after it
+    let drops = [0.0, 14.0, 28.0, 42.0, 62.0, 82.0];
+    for (span, drop) in spans.iter().zip(drops) {
+        assert!((span.y - spans[0].y - drop).abs() < 1e-9, "{:?}", spans);
+    }
+}
+
+#[test]
+fn test_next_line_shows_keep_the_spacing_double_quote_sets() {
+    let content = b"BT /F1 10 Tf 72 700 Td 12 TL (ab) Tj 3 1 (c d) \" (e) Tj (f) ' ET";
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Resources<</Font<</F1 5 0 R>>>>\
+/Contents 4 0 R>>"
+            .to_vec(),
+        stream_object("", content),
+        b"<</Type/Font/Subtype/TrueType/BaseFont/Custom/FirstChar 32/LastChar 32\
+/Widths[500]/FontDescriptor 6 0 R>>"
+            .to_vec(),
+        b"<</Type/FontDescriptor/FontName/Custom/MissingWidth 500>>".to_vec(),
+    ]);
+    let config = ConversionConfig {
+        merge_spans: false,
+        ..Default::default()
+    };
+
+    let bundle = crate::convert_pdf(&pdf, &config).unwrap();
+    let spans = &bundle.pages[0].text_spans;
+    let placed: Vec<(&str, f64, f64)> = spans
+        .iter()
+        .map(|s| (s.text.as_str(), s.x, s.y - spans[0].y))
+        .collect();
+    // "c d" advances 5 a glyph, 1 more each for Tc and 3 for Tw at its
+    // space; ' keeps both
+    let expected = [("ab", 72.0, 0.0), ("c d", 72.0, 12.0), ("e", 93.0, 12.0), ("f", 72.0, 24.0)];
+    assert_eq!(placed.len(), expected.len(), "{:?}", placed);
+    for ((text, x, drop), (want_text, want_x, want_drop)) in placed.iter().zip(expected) {
+        assert_eq!(*text, want_text);
+        assert!((x - want_x).abs() < 1e-9, "{:?}", placed);
+        assert!((drop - want_drop).abs() < 1e-9, "{:?}", placed);
+    }
+    assert!((spans[3].width - 6.0).abs() < 1e-9, "{}", spans[3].width);
+}
+
+#[test]
+fn test_character_and_word_spacing_become_css_spacing() {
+    // Tm doubles the text, and the spacing with it
+    let content = b"BT /F1 10 Tf 2 0 0 2 72 700 Tm 0.5 Tc 1.5 Tw (spread out) Tj ET";
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Contents 4 0 R>>".to_vec(),
+        stream_object("", content),
+    ]);
+
+    let page = &crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap().pages[0];
+    let span = &page.text_spans[0];
+    assert_eq!((span.letter_spacing, span.word_spacing), (1.0, 3.0));
+    assert!(page.html.contains("letter-spacing:1px;word-spacing:3px;"), "{}", page.html);
+
+    let config = ConversionConfig {
+        text_spacing: false,
+        ..Default::default()
+    };
+    let page = &crate::convert_pdf(&pdf, &config).unwrap().pages[0];
+    assert_eq!(page.text_spans[0].letter_spacing, 0.0);
+    assert!(!page.html.contains("letter-spacing"));
+}
+
+#[test]
+fn test_user_unit_scales_the_page_and_its_content() {
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 306 396]/UserUnit 2.0/Contents 4 0 R>>"
+            .to_vec(),
+        stream_object("", b"BT /F1 10 Tf 72 300 Td (Poster) Tj ET 1 0 0 rg 36 36 50 20 re f"),
+    ]);
+
+    let page = &crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap().pages[0];
+    assert_eq!((page.width, page.height), (612.0, 792.0));
+    let span = &page.text_spans[0];
+    assert_eq!((span.x, span.font_size), (144.0, 20.0));
+    let rect = &page.filled_rects[0];
+    assert_eq!((rect.x, rect.y, rect.width, rect.height), (72.0, 680.0, 100.0, 40.0));
+}
+
+#[test]
+fn test_user_unit_that_isnt_finite_is_ignored() {
+    // 400 digits overflow to infinity
+    let page = format!(
+        "<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/UserUnit 1{}/Contents 4 0 R>>",
+        "0".repeat(400)
+    );
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        page.into_bytes(),
+        stream_object("", b"BT /F1 10 Tf 72 300 Td (Poster) Tj ET"),
+    ]);
+
+    let page = &crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap().pages[0];
+    assert_eq!((page.width, page.height), (612.0, 792.0));
+    assert_eq!(page.text_spans[0].x, 72.0);
+}
+
+#[test]
+fn test_content_is_placed_relative_to_the_box_shown() {
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]/CropBox[50 50 562 742]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/TrimBox[100 100 512 692]\
+/Contents 4 0 R>>"
+            .to_vec(),
+        stream_object("", b"BT /F1 10 Tf 72 300 Td (Trim) Tj ET 1 0 0 rg 100 100 50 20 re f"),
+    ]);
+    let placed = |page_box: PageBox| {
+        let config = ConversionConfig {
+            page_box,
+            ..Default::default()
+        };
+        let page = crate::convert_pdf(&pdf, &config).unwrap().pages.remove(0);
+        let rect = &page.filled_rects[0];
+        (page.width, page.height, page.text_spans[0].x, rect.x, rect.y)
+    };
+
+    assert_eq!(placed(PageBox::MediaBox), (612.0, 792.0, 72.0, 100.0, 672.0));
+    // The inherited CropBox, its corner moved by (50, 50)
+    assert_eq!(placed(PageBox::CropBox), (512.0, 692.0, 22.0, 50.0, 622.0));
+    assert_eq!(placed(PageBox::TrimBox), (412.0, 592.0, -28.0, 0.0, 572.0));
+    // No ArtBox, so the CropBox
+    assert_eq!(placed(PageBox::ArtBox), placed(PageBox::CropBox));
+}
+
+#[test]
+fn test_conversion_summary_reports_document_properties() {
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R/Lang(en-US)>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Contents 4 0 R>>".to_vec(),
+        stream_object("", b"BT /F1 12 Tf 72 700 Td (One) Tj 0 -20 Td (Two) Tj ET"),
+    ]);
+
+    let summary = crate::convert_pdf(&pdf, &ConversionConfig::default())
+        .unwrap()
+        .summary();
+    assert_eq!(summary.page_count, 1);
+    assert_eq!(summary.span_count, 2);
+    assert_eq!(summary.font_count, 0);
+    assert_eq!(summary.language.as_deref(), Some("en-US"));
+    assert!(!summary.encrypted);
+}
+
+#[test]
+fn test_extract_images_reports_page_format_and_placement() {
+    use flate2::write::ZlibEncoder;
+    use std::io::Write;
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(&[255, 0, 0, 0, 0, 255]).unwrap();
+    let rgb_samples = encoder.finish().unwrap();
+    let page = |image: &str, contents: u32| {
+        format!(
+            "<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Resources<</XObject<<{}>>>>\
+/Contents {} 0 R>>",
+            image, contents
+        )
+        .into_bytes()
+    };
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 2/Kids[3 0 R 4 0 R]>>".to_vec(),
+        page("/Photo 7 0 R", 5),
+        page("/Chart 8 0 R", 6),
+        stream_object("", b"q 200 0 0 100 72 592 cm /Photo Do Q"),
+        stream_object("", b"q 50 0 0 50 100 100 cm /Chart Do Q"),
+        stream_object(
+            "/Type/XObject/Subtype/Image/Width 4/Height 2/ColorSpace/DeviceRGB\
+/BitsPerComponent 8/Filter/DCTDecode",
+            b"\xFF\xD8\xFF\xE0fake jpeg\xFF\xD9",
+        ),
+        stream_object(
+            "/Type/XObject/Subtype/Image/Width 2/Height 1/ColorSpace/DeviceRGB\
+/BitsPerComponent 8/Filter/FlateDecode",
+            &rgb_samples,
+        ),
+    ]);
+
+    let images = crate::extract_images(&pdf, &ConversionConfig::default()).unwrap();
+
+    assert_eq!(images.len(), 2);
+    let (photo, chart) = (&images[0], &images[1]);
+    assert_eq!((photo.page_index, photo.name.as_str()), (0, "Photo"));
+    assert_eq!(photo.format, "jpg");
+    assert_eq!(photo.data, b"\xFF\xD8\xFF\xE0fake jpeg\xFF\xD9");
+    assert_eq!((photo.x, photo.y, photo.width, photo.height), (72.0, 100.0, 200.0, 100.0));
+    assert_eq!((chart.page_index, chart.name.as_str()), (1, "Chart"));
+    assert_eq!(chart.format, "png");
+    assert!(chart.data.starts_with(b"\x89PNG"));
+    assert_eq!((chart.pixel_width, chart.pixel_height), (2, 1));
+}
+
+#[test]
+fn test_words_shown_separately_merge_into_a_line() {
+    // Word-per-Tj output as many producers write it, split across BT blocks
+    let content = b"BT /F1 10 Tf 72 700 Td (Quarterly) Tj 46.13 0 Td (report) Tj ET \
+BT /F1 10 Tf 151.04 700 Td (2024) Tj ET BT /F1 10 Tf 400 700 Td (Page 1) Tj ET";
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Resources<</Font<</F1 5 0 R>>>>\
+/Contents 4 0 R>>"
+            .to_vec(),
+        stream_object("", content),
+        b"<</Type/Font/Subtype/Type1/BaseFont/Helvetica>>".to_vec(),
+    ]);
+
+    let bundle = crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap();
+
+    let texts: Vec<&str> = bundle.pages[0].text_spans.iter().map(|s| s.text.as_str()).collect();
+    // The far-off page number keeps its own span and position
+    assert_eq!(texts, ["Quarterly report 2024", "Page 1"]);
+    assert_eq!(bundle.pages[0].text_spans[1].x, 400.0);
+}
+
+#[test]
+fn test_parse_errors_carry_their_kind() {
+    let error = crate::convert_pdf(b"<html>not a pdf</html>", &ConversionConfig::default())
+        .expect_err("HTML should not convert");
+    assert_eq!(error.parse_error_kind(), Some(&ParseErrorKind::NotPdf));
+
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 1/Kids[3 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]>>".to_vec(),
+        b"<</Filter/Adobe.PubSec/V 4/R 4>>".to_vec(),
+    ]);
+    let pdf = String::from_utf8(pdf)
+        .unwrap()
+        .replace("/Root 1 0 R>>", "/Root 1 0 R/Encrypt 4 0 R>>");
+    let error = crate::convert_pdf(pdf.as_bytes(), &ConversionConfig::default())
+        .expect_err("certificate-encrypted PDF should not convert");
+    assert_eq!(error.parse_error_kind(), Some(&ParseErrorKind::Encrypted));
+    assert!(error.to_string().contains("Adobe.PubSec"));
+}
+
+#[test]
+fn test_a_page_that_fails_to_render_is_listed_unless_strict() {
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 2/Kids[3 0 R 4 0 R]>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Contents 5 0 R>>".to_vec(),
+        b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]/Contents 6 0 R>>".to_vec(),
+        stream_object("", b"BT /F1 12 Tf 72 700 Td (Fine) Tj ET"),
+        stream_object("", b"BT /F1 12 Tf 72 7-0 Td (Lost) Tj ET"),
+    ]);
+
+    let bundle = crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap();
+    assert_eq!(bundle.pages.len(), 2);
+    assert_eq!(bundle.pages[0].text_spans[0].text, "Fine");
+    assert_eq!(bundle.page_errors.len(), 1);
+    assert_eq!(bundle.page_errors[0].page_number, 2);
+    let placeholder = &bundle.pages[1];
+    assert!(placeholder.text_spans.is_empty());
+    assert_eq!(placeholder.diagnostics.len(), 1);
+
+    let strict = ConversionConfig {
+        strict: true,
+        ..ConversionConfig::default()
+    };
+    assert!(crate::convert_pdf(&pdf, &strict).is_err());
+}
+
+#[test]
+fn test_font_ids_are_the_same_in_every_conversion() {
+    let page = |font: &str, contents: usize| {
+        format!(
+            "<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]\
+/Resources<</Font<</F1 {} 0 R>>>>/Contents {} 0 R>>",
+            font, contents
+        )
+        .into_bytes()
+    };
+    let pdf = build_pdf(&[
+        b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+        b"<</Type/Pages/Count 2/Kids[3 0 R 4 0 R]>>".to_vec(),
+        page("6", 5),
+        page("9", 5),
+        stream_object("", b"BT /F1 12 Tf 72 700 Td (Same font) Tj ET"),
+        b"<</Type/Font/Subtype/TrueType/BaseFont/Embedded/FontDescriptor 7 0 R>>".to_vec(),
+        b"<</Type/FontDescriptor/FontName/Embedded/FontFile2 8 0 R>>".to_vec(),
+        stream_object("", b"\x00\x01\x00\x00fake font program"),
+        // A second font object embedding the same program
+        b"<</Type/Font/Subtype/TrueType/BaseFont/Embedded/FontDescriptor 10 0 R>>".to_vec(),
+        b"<</Type/FontDescriptor/FontName/Embedded/FontFile2 11 0 R>>".to_vec(),
+        stream_object("", b"\x00\x01\x00\x00fake font program"),
+    ]);
+
+    let first = crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap();
+    assert_eq!(first.fonts.len(), 1);
+    let font_id = first.fonts[0].font_id;
+    for page in &first.pages {
+        assert_eq!(page.text_spans[0].font_id, Some(font_id));
+    }
+
+    let second = crate::convert_pdf(&pdf, &ConversionConfig::default()).unwrap();
+    let ids = |bundle: &crate::OutputBundle| {
+        bundle.fonts.iter().map(|f| f.font_id).collect::<Vec<_>>()
+    };
+    assert_eq!(ids(&first), ids(&second));
+    assert_eq!(first.css, second.css);
+    let html = |bundle: &crate::OutputBundle| {
+        bundle.pages.iter().map(|p| p.html.clone()).collect::<String>()
+    };
+    assert_eq!(html(&first), html(&second));
+}
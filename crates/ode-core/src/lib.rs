@@ -20,71 +20,437 @@ mod safety_audit;
 #[cfg(test)]
 mod benchmarks;
 
-pub use config::ConversionConfig;
-pub use error::{OdeError, OdeResult};
+pub use config::{ConversionConfig, ConversionConfigBuilder};
+pub use error::{OdeError, OdeResult, ParseErrorKind};
 pub use parser::PdfDocument;
-pub use renderer::OutputBundle;
+pub use renderer::{
+    lang_attribute, shared_css, ConversionSummary, ExtractedImage, OutputBundle, PageError,
+    RenderedFont, RenderedImage, RenderedPage,
+};
 pub use render::CoveredTextDetector;
 pub use util::{LigatureMapper, TimeoutWrapper, ZipBombDetector};
 
-use crate::fonts::FontProcessor;
+use crate::config::OutputLimitAction;
 use crate::renderer::extract_fonts_from_document;
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// What a conversion is busy with when it reports progress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConversionPhase {
+    /// Reading the document; the page count isn't known yet.
+    Parsing,
+    Rendering,
+    Finished,
+}
+
+/// A progress report from `convert_pdf_with_progress`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConversionProgress {
+    /// Pages rendered so far.
+    pub current_page: usize,
+    /// Pages selected for conversion, or 0 while parsing.
+    pub total_pages: usize,
+    pub phase: ConversionPhase,
+}
+
+impl ConversionProgress {
+    /// How much of the conversion is done, from 0.0 to 1.0.
+    pub fn fraction(&self) -> f32 {
+        match self.phase {
+            ConversionPhase::Finished => 1.0,
+            _ if self.total_pages == 0 => 0.0,
+            _ => self.current_page as f32 / self.total_pages as f32,
+        }
+    }
+}
 
 pub fn convert_pdf(data: &[u8], config: &ConversionConfig) -> OdeResult<OutputBundle> {
+    let mut collected = OutputBundle::default();
+    let mut finished = OutputBundle::default();
+
+    convert_pdf_each(
+        data,
+        config,
+        |_| {},
+        |page| {
+            collected.add_page(page);
+            Ok(())
+        },
+        |bundle| finished = bundle,
+    )?;
+
+    Ok(OutputBundle {
+        pages: collected.pages,
+        images: collected.images,
+        ..finished
+    })
+}
+
+/// Convert the PDF at `path`, memory-mapped rather than read onto the heap,
+/// so a large file costs only the pages of it the parser touches.
+///
+/// The file must not be truncated or rewritten while it converts; the map
+/// would then read changed or missing bytes.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn convert_pdf_from_path(
+    path: &std::path::Path,
+    config: &ConversionConfig,
+) -> OdeResult<OutputBundle> {
+    let in_file = |e: std::io::Error| {
+        let message = format!("{}: {}", path.display(), e);
+        OdeError::IoError(std::io::Error::new(e.kind(), message))
+    };
+    let file = std::fs::File::open(path).map_err(in_file)?;
+    // SAFETY: the map is read-only and dropped before this returns; changes
+    // to the file underneath it are the caller's to rule out, as above
+    let map = unsafe { memmap2::Mmap::map(&file) }.map_err(in_file)?;
+    convert_pdf(&map, config)
+}
+
+/// Convert a PDF to one HTML document that loads its fonts and images by
+/// relative path, along with those files keyed by that path. Serving the map's
+/// entries next to the HTML, from disk, an archive or a bucket, completes it.
+///
+/// The files are always returned separately, whatever `config.asset_embedding`
+/// says; [`OutputBundle::to_html`] inlines them.
+pub fn convert_to_html_with_assets(
+    data: &[u8],
+    config: &ConversionConfig,
+) -> OdeResult<(String, HashMap<String, Vec<u8>>)> {
+    let (html, assets) = convert_pdf(data, config)?.to_linked_html();
+    Ok((html, assets.into_iter().collect()))
+}
+
+/// Like `convert_pdf`, reporting progress to `on_progress` as pages are
+/// rendered.
+///
+/// Setting `cancel` (typically an `Arc<AtomicBool>` shared with another
+/// thread) stops the conversion before the next page is handed over, and it
+/// returns `OdeError::Cancelled`; the pages rendered so far are dropped.
+pub fn convert_pdf_with_progress(
+    data: &[u8],
+    config: &ConversionConfig,
+    on_progress: &mut dyn FnMut(ConversionProgress),
+    cancel: &AtomicBool,
+) -> OdeResult<OutputBundle> {
+    let mut collected = OutputBundle::default();
+    let mut finished = OutputBundle::default();
+
+    convert_pages(
+        data,
+        config,
+        |_| {},
+        |page| {
+            collected.add_page(page);
+            Ok(())
+        },
+        |bundle| finished = bundle,
+        on_progress,
+        Some(cancel),
+    )?;
+
+    Ok(OutputBundle {
+        pages: collected.pages,
+        images: collected.images,
+        ..finished
+    })
+}
+
+/// Convert a PDF page by page without holding the whole output in memory.
+///
+/// The document is parsed once, then `on_start` gets the document-level
+/// properties, such as its language, that output streaming the pages may
+/// need ahead of them. `on_page` receives each `RenderedPage` in page order
+/// as soon as it is rendered. An error returned from `on_page` stops the
+/// conversion and is passed back to the caller. Once every page is done,
+/// `on_finish` receives the rest of the `OutputBundle`, with `pages`
+/// and `images` left empty: the extracted fonts, the CSS, and the
+/// document-level properties. The CSS is [`shared_css`], which output that
+/// streams pages can emit ahead of them, then the `@font-face` rules that
+/// load the fonts by filename.
+///
+/// Each page holds the data of the images it draws; pass it to
+/// [`OutputBundle::add_page`] to keep each image once across pages.
+pub fn convert_pdf_each<S, P, F>(
+    data: &[u8],
+    config: &ConversionConfig,
+    on_start: S,
+    on_page: P,
+    on_finish: F,
+) -> OdeResult<()>
+where
+    S: FnOnce(&OutputBundle),
+    P: FnMut(RenderedPage) -> OdeResult<()>,
+    F: FnOnce(OutputBundle),
+{
+    convert_pages(data, config, on_start, on_page, on_finish, &mut |_| {}, None)
+}
+
+fn convert_pages<S, P, F>(
+    data: &[u8],
+    config: &ConversionConfig,
+    on_start: S,
+    mut on_page: P,
+    on_finish: F,
+    on_progress: &mut dyn FnMut(ConversionProgress),
+    cancel: Option<&AtomicBool>,
+) -> OdeResult<()>
+where
+    S: FnOnce(&OutputBundle),
+    P: FnMut(RenderedPage) -> OdeResult<()>,
+    F: FnOnce(OutputBundle),
+{
+    let cancelled = || cancel.is_some_and(|flag| flag.load(Ordering::Relaxed));
+    let started = std::time::Instant::now();
+    // Rendering is synchronous, so it checks the deadline between pages and
+    // form XObjects rather than being interrupted
+    let deadline = config
+        .timeout
+        .map(|timeout| TimeoutWrapper::new(timeout.as_millis() as u64).deadline());
+    let check_deadline = |page_number: usize| match deadline {
+        Some(deadline) => deadline.check(page_number),
+        None => Ok(()),
+    };
     // Zip bomb detection happens per-stream during decompression,
     // not on the raw PDF file (which is not itself a compressed blob).
 
-    let document = parser::parse_pdf(data)?;
+    on_progress(ConversionProgress {
+        current_page: 0,
+        total_pages: 0,
+        phase: ConversionPhase::Parsing,
+    });
+    let document = parser::parse_pdf_with_config(data, config)?;
+    if cancelled() {
+        return Err(OdeError::Cancelled);
+    }
 
-    let mut output_bundle = OutputBundle::default();
+    // Fonts are extracted up front so spans can name the font they use
+    // Everything but the pages, which go to `on_page` as they are rendered
+    let mut bundle = OutputBundle {
+        css: shared_css(config),
+        encrypted: document.is_encrypted(),
+        language: document.language(),
+        metadata: document.metadata.clone(),
+        outline: if config.process_outline {
+            document.outline.clone()
+        } else {
+            Vec::new()
+        },
+        structure: document.structure.clone(),
+        ..Default::default()
+    };
+    let page_ids = config.page_selection().page_indices(document.num_pages());
+    let used_glyphs = if config.subset_fonts {
+        renderer::collect_used_glyphs(&document, &page_ids, config, deadline)?
+    } else {
+        renderer::UsedGlyphs::new()
+    };
+    let font_ids = extract_fonts_from_document(&document, &mut bundle, data, config, &used_glyphs)
+        .unwrap_or_default();
 
-    if let Some(xref) = &document.xref {
-        let mut font_processor = FontProcessor::new();
-        
-        for page in &document.pages {
-            for font_ref in &page.fonts {
-                let resolver = parser::PdfRefResolver::new(data, xref);
-                let _ = font_processor.extract_font_from_pdf(*font_ref, &resolver);
-            }
+    // Fonts are all extracted by now, so only images are left out to keep
+    // the output within `max_output_bytes`
+    let mut output_bytes: usize = bundle.fonts.iter().map(|font| font.data.len()).sum();
+    if let Some(limit) = config.max_output_bytes {
+        if output_bytes > limit && config.output_limit_action == OutputLimitAction::Fail {
+            return Err(OdeError::OutputTooLarge { limit });
         }
     }
+    on_start(&bundle);
+    let mut stored_images = HashSet::new();
+    let mut keep_within_limit = |page: &mut RenderedPage| -> OdeResult<()> {
+        let Some(limit) = config.max_output_bytes else {
+            return Ok(());
+        };
+        let mut dropped: Vec<String> = Vec::new();
+        for image in &page.images {
+            if image.data.is_empty() || stored_images.contains(&image.filename) {
+                continue;
+            }
+            if output_bytes + image.data.len() <= limit {
+                output_bytes += image.data.len();
+                stored_images.insert(image.filename.clone());
+            } else if config.output_limit_action == OutputLimitAction::Fail {
+                return Err(OdeError::OutputTooLarge { limit });
+            } else if !dropped.contains(&image.filename) {
+                dropped.push(image.filename.clone());
+            }
+        }
+        for filename in dropped {
+            page.drop_image(&filename);
+            page.diagnostics.push(format!(
+                "image {} left out: the output reached its limit of {} bytes",
+                filename, limit
+            ));
+        }
+        Ok(())
+    };
+
+    let total_pages = page_ids.len();
+    let mut pages_done = 0;
+    let mut progress = |current_page, phase| {
+        on_progress(ConversionProgress {
+            current_page,
+            total_pages,
+            phase,
+        })
+    };
+    progress(0, ConversionPhase::Rendering);
 
-    let start_page = config.page_range.0.saturating_sub(1);
-    let end_page = (config.page_range.1.min(document.num_pages()))
-        .min(start_page + 1000);
+    let cache_keys = config.page_cache.as_ref().map(|_| {
+        (
+            util::ContentHasher::hash_bytes(data),
+            renderer::PageCacheKey::config_hash(config),
+        )
+    });
 
-    for page_id in start_page..end_page {
+    let render_page = |page_id: usize| -> OdeResult<RenderedPage> {
         let page_number = page_id + 1;
-        match renderer::render_pdf_page(
+        check_deadline(page_number)?;
+
+        let cache_key = cache_keys.as_ref().map(|(doc_hash, config_hash)| {
+            renderer::PageCacheKey::new(doc_hash, page_id, config_hash)
+        });
+        if let (Some(cache), Some(key)) = (&config.page_cache, &cache_key) {
+            if let Some(cached_page) = cache.get(key) {
+                return Ok(cached_page);
+            }
+        }
+
+        let rendered_page = renderer::render_pdf_page_until(
             &document,
             page_id,
             page_number,
             config,
-        ) {
-            Ok(rendered_page) => {
-                output_bundle.add_page(rendered_page);
+            &font_ids,
+            deadline,
+        )?;
+        if let (Some(cache), Some(key)) = (&config.page_cache, cache_key) {
+            cache.insert(key, rendered_page.clone());
+        }
+        Ok(rendered_page)
+    };
+
+    // A page that fails to render is left empty and noted, unless the config
+    // is strict; running out of time stops the conversion either way
+    let page_errors = &mut bundle.page_errors;
+    let mut settle = |page_id: usize, rendered: OdeResult<RenderedPage>| match rendered {
+        Ok(rendered_page) => Ok(rendered_page),
+        Err(e @ OdeError::Timeout(_)) => Err(e),
+        Err(e) if config.strict => Err(e),
+        Err(e) => {
+            let page_number = page_id + 1;
+            page_errors.push(PageError {
+                page_number,
+                message: e.to_string(),
+            });
+            let (width, height) = document.pages[page_id].displayed_size();
+            Ok(RenderedPage {
+                page_number,
+                width,
+                height,
+                html: String::new(),
+                css: String::new(),
+                text_spans: Vec::new(),
+                reading_order: Vec::new(),
+                images: Vec::new(),
+                filled_rects: Vec::new(),
+                strokes: Vec::new(),
+                background_color: None,
+                font_ids: Vec::new(),
+                diagnostics: vec![format!("page failed to render: {}", e)],
+            })
+        }
+    };
+
+    let mut hand_over = |mut rendered_page: RenderedPage| -> OdeResult<()> {
+        if cancelled() {
+            return Err(OdeError::Cancelled);
+        }
+        keep_within_limit(&mut rendered_page)?;
+        on_page(rendered_page)?;
+        pages_done += 1;
+        progress(pages_done, ConversionPhase::Rendering);
+        Ok(())
+    };
+
+    if config.parallel {
+        // Render one batch per pool width at a time so pages are still handed
+        // to `on_page` in order without buffering the whole document.
+        let batch_size = rayon::current_num_threads().max(1);
+        for batch in page_ids.chunks(batch_size) {
+            if cancelled() {
+                return Err(OdeError::Cancelled);
             }
-            Err(_e) => {
-                // Skip pages that fail to render, add empty placeholder
-                let page = &document.pages[page_id];
-                output_bundle.add_page(renderer::RenderedPage {
-                    page_number,
-                    width: page.width,
-                    height: page.height,
-                    html: String::new(),
-                    css: String::new(),
-                    text_spans: Vec::new(),
-                    images: Vec::new(),
-                    background_color: None,
-                    font_ids: Vec::new(),
-                });
+            let rendered: Vec<OdeResult<RenderedPage>> =
+                batch.par_iter().map(|&id| render_page(id)).collect();
+            for (&page_id, rendered_page) in batch.iter().zip(rendered) {
+                hand_over(settle(page_id, rendered_page)?)?;
             }
         }
+    } else {
+        for &page_id in &page_ids {
+            if cancelled() {
+                return Err(OdeError::Cancelled);
+            }
+            hand_over(settle(page_id, render_page(page_id))?)?;
+        }
     }
 
-    let _ = extract_fonts_from_document(&document, &mut output_bundle, data);
+    bundle.duration_ms = started.elapsed().as_millis() as u64;
+    progress(total_pages, ConversionPhase::Finished);
+    on_finish(bundle);
+
+    Ok(())
+}
 
-    Ok(output_bundle)
+/// List the images drawn on the selected pages, in page order.
+///
+/// Images are decoded as for the HTML output (JPEG and JPEG 2000 pass
+/// through, raw samples become PNG) and placed where the page draws them.
+/// An image drawn twice is listed twice.
+pub fn extract_images(data: &[u8], config: &ConversionConfig) -> OdeResult<Vec<ExtractedImage>> {
+    let document = parser::parse_pdf_with_config(data, config)?;
+    let mut images = Vec::new();
+    for page_id in config.page_selection().page_indices(document.num_pages()) {
+        // Like conversion, a page that fails to render is skipped
+        let Ok(page) = renderer::render_pdf_page(&document, page_id, page_id + 1, config) else {
+            continue;
+        };
+        images.extend(
+            page.images
+                .iter()
+                .filter_map(|image| ExtractedImage::from_page_image(page_id, image)),
+        );
+    }
+    Ok(images)
+}
+
+/// Decode one page's content stream and list the operators the renderer
+/// sees, one per line in content-stream syntax.
+///
+/// Meant for diagnosing bad output. `page_number` is 1-based.
+pub fn page_content_operators(
+    data: &[u8],
+    config: &ConversionConfig,
+    page_number: usize,
+) -> OdeResult<String> {
+    let document = parser::parse_pdf_with_config(data, config)?;
+    let page = page_number
+        .checked_sub(1)
+        .and_then(|i| document.pages.get(i))
+        .ok_or_else(|| {
+            OdeError::ConfigError(format!(
+                "Page {} out of range (document has {} pages)",
+                page_number,
+                document.num_pages()
+            ))
+        })?;
+
+    let ops = parser::ContentStreamParser::new(&page.contents).parse()?;
+    Ok(parser::content_stream::format_ops(&ops))
 }
 
 #[cfg(test)]
@@ -98,4 +464,76 @@ mod tests {
         let result = convert_pdf(pdf_data, &config);
         assert!(result.is_ok());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_convert_pdf_each_stops_on_callback_error() {
+        let pdf_data = b"%PDF-1.4\n1 0 obj\n<<>>\nendobj\ntrailer\n<<>>\n%%EOF";
+        let config = ConversionConfig::default();
+        let mut rendered = 0;
+        let mut fonts_called = false;
+
+        let result = convert_pdf_each(
+            pdf_data,
+            &config,
+            |_| {},
+            |_page| {
+                rendered += 1;
+                Err(OdeError::RenderError("sink closed".to_string()))
+            },
+            |_bundle| fonts_called = true,
+        );
+
+        assert!(matches!(result, Err(OdeError::RenderError(_))));
+        assert_eq!(rendered, 1);
+        assert!(!fonts_called);
+    }
+
+    #[test]
+    fn test_cancelling_after_the_first_page_discards_the_output() {
+        use crate::benchmarks::performance_benchmarks::create_multi_page_pdf;
+        use std::sync::Arc;
+
+        let pdf_data = create_multi_page_pdf(3);
+        let config = ConversionConfig::default();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let mut reports = Vec::new();
+
+        let result = convert_pdf_with_progress(
+            &pdf_data,
+            &config,
+            &mut |progress| {
+                if progress.current_page == 1 {
+                    cancel.store(true, Ordering::Relaxed);
+                }
+                reports.push((progress.current_page, progress.total_pages, progress.phase));
+            },
+            &cancel,
+        );
+
+        assert!(matches!(result, Err(OdeError::Cancelled)));
+        assert_eq!(
+            reports,
+            [
+                (0, 0, ConversionPhase::Parsing),
+                (0, 3, ConversionPhase::Rendering),
+                (1, 3, ConversionPhase::Rendering),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_page_content_operators_lists_page_stream() {
+        use crate::benchmarks::performance_benchmarks::create_multi_page_pdf;
+
+        let pdf_data = create_multi_page_pdf(2);
+        let config = ConversionConfig::default();
+
+        let text = page_content_operators(&pdf_data, &config, 2).unwrap();
+        assert_eq!(text, "BT\n/F1 12 Tf\n100 700 Td\n(Page 2) Tj\nET\n");
+
+        assert!(matches!(
+            page_content_operators(&pdf_data, &config, 3),
+            Err(OdeError::ConfigError(_))
+        ));
+    }
+}
@@ -59,6 +59,48 @@ mod memory_usage_tests {
         );
     }
 
+    #[test]
+    fn test_streaming_conversion_holds_one_page_at_a_time() {
+        use crate::benchmarks::performance_benchmarks::create_multi_page_pdf;
+        use std::cell::Cell;
+
+        let pdf_data = create_multi_page_pdf(50);
+        let config = ConversionConfig::default();
+
+        let pages_seen = Cell::new(0usize);
+        let mut page_numbers = Vec::new();
+        let mut peak_page_memory = 0usize;
+        let mut pages_before_fonts = None;
+
+        crate::convert_pdf_each(
+            &pdf_data,
+            &config,
+            |_| {},
+            |page| {
+                pages_seen.set(pages_seen.get() + 1);
+                page_numbers.push(page.page_number);
+                peak_page_memory = peak_page_memory.max(estimate_pages_memory(std::slice::from_ref(&page)));
+                Ok(())
+            },
+            |_bundle| pages_before_fonts = Some(pages_seen.get()),
+        )
+        .unwrap();
+
+        assert_eq!(page_numbers, (1..=50).collect::<Vec<_>>());
+        assert_eq!(pages_before_fonts, Some(50), "fonts should arrive after every page");
+
+        // Only one rendered page is alive at a time, so the peak is a small
+        // fraction of what the fully buffered bundle holds.
+        let full_bundle = convert_pdf(&pdf_data, &config).unwrap();
+        let full_memory = estimate_total_memory_usage(&full_bundle);
+        assert!(
+            peak_page_memory * 10 < full_memory,
+            "peak per-page memory {} should be far below buffered total {}",
+            peak_page_memory,
+            full_memory
+        );
+    }
+
     #[test]
     fn test_empty_memory_footprint() {
         use crate::renderer::OutputBundle;
@@ -80,6 +122,17 @@ mod memory_usage_tests {
             font_size: 12.0,
             font_id: None,
             color: "rgb(0,0,0)".to_string(),
+            covered: false,
+            scale_x: 1.0,
+            vertical: false,
+            width: 66.0,
+            height: 12.6,
+            underline: false,
+            line_through: false,
+            letter_spacing: 0.0,
+            word_spacing: 0.0,
+            lang: None,
+            mcid: None,
         }];
 
         let size = estimate_text_spans_memory(&spans);
@@ -99,6 +152,17 @@ mod memory_usage_tests {
             font_size: 12.0,
             font_id: None,
             color: "rgb(0,0,0)".to_string(),
+            covered: false,
+            scale_x: 1.0,
+            vertical: false,
+            width: 6000.0,
+            height: 12.6,
+            underline: false,
+            line_through: false,
+            letter_spacing: 0.0,
+            word_spacing: 0.0,
+            lang: None,
+            mcid: None,
         }];
 
         let size = estimate_text_spans_memory(&spans);
@@ -141,9 +205,13 @@ mod memory_usage_tests {
                 html: format!("<div>Page {}</div>", i),
                 css: ".page {{ position: absolute; }}".to_string(),
                 text_spans: vec![],
+                reading_order: vec![],
                 font_ids: vec![],
                 background_color: None,
                 images: vec![],
+                filled_rects: vec![],
+                strokes: vec![],
+                diagnostics: vec![],
             };
             bundle.add_page(page);
         }
@@ -159,6 +227,59 @@ mod memory_usage_tests {
         );
     }
 
+    #[test]
+    fn test_output_stops_at_max_output_bytes() {
+        use crate::config::OutputLimitAction;
+        use crate::integration_tests::{build_pdf, stream_object};
+        use crate::OdeError;
+
+        let jpeg = |fill: u8| {
+            let mut data = b"\xFF\xD8\xFF\xE0".to_vec();
+            data.extend(std::iter::repeat_n(fill, 1000));
+            data.extend_from_slice(b"\xFF\xD9");
+            data
+        };
+        let image_dict = "/Type/XObject/Subtype/Image/Width 4/Height 2/ColorSpace/DeviceRGB\
+/BitsPerComponent 8/Filter/DCTDecode";
+        let pdf = build_pdf(&[
+            b"<</Type/Catalog/Pages 2 0 R>>".to_vec(),
+            b"<</Type/Pages/Count 2/Kids[3 0 R 4 0 R]>>".to_vec(),
+            b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]\
+/Resources<</XObject<</Im 6 0 R>>>>/Contents 5 0 R>>"
+                .to_vec(),
+            b"<</Type/Page/Parent 2 0 R/MediaBox[0 0 612 792]\
+/Resources<</XObject<</Im 7 0 R>>>>/Contents 5 0 R>>"
+                .to_vec(),
+            stream_object("", b"q 200 0 0 100 72 500 cm /Im Do Q"),
+            stream_object(image_dict, &jpeg(1)),
+            stream_object(image_dict, &jpeg(2)),
+        ]);
+
+        let unlimited = convert_pdf(&pdf, &ConversionConfig::default()).unwrap();
+        assert_eq!(unlimited.images.len(), 2);
+
+        let failing = ConversionConfig {
+            max_output_bytes: Some(1500),
+            ..ConversionConfig::default()
+        };
+        assert!(matches!(
+            convert_pdf(&pdf, &failing),
+            Err(OdeError::OutputTooLarge { limit: 1500 })
+        ));
+
+        let dropping = ConversionConfig {
+            output_limit_action: OutputLimitAction::DropImages,
+            ..failing
+        };
+        let bundle = convert_pdf(&pdf, &dropping).unwrap();
+        assert_eq!(bundle.images.len(), 1);
+        assert!(bundle.pages[0].html.contains("<img"));
+        let second = &bundle.pages[1];
+        assert!(second.images.is_empty());
+        assert!(!second.html.contains("<img"));
+        assert_eq!(second.diagnostics.len(), 1);
+    }
+
     fn estimate_total_memory_usage(bundle: &crate::renderer::OutputBundle) -> usize {
         let mut total = 0usize;
 
@@ -0,0 +1,744 @@
+use super::{ColorSpace, Dictionary, FormXObject, ObjectReference, PdfObject, PdfRefResolver};
+use crate::types::color::Color;
+use std::collections::HashMap;
+
+/// Maximum hops when following named destinations to an explicit one.
+const MAX_DEST_DEPTH: usize = 8;
+
+/// Maximum depth of a `/Names /Dests` name tree.
+const MAX_NAME_TREE_DEPTH: usize = 32;
+
+/// Maximum depth of an AcroForm field hierarchy.
+const MAX_FIELD_DEPTH: usize = 32;
+
+/// `/Ff` bit of button fields that are radio buttons.
+const FLAG_RADIO: i64 = 1 << 15;
+/// `/Ff` bit of button fields that are push buttons, which hold no value.
+const FLAG_PUSHBUTTON: i64 = 1 << 16;
+
+/// A `/Subtype /Link` annotation reduced to its clickable area and target.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkAnnotation {
+    /// `/Rect` in default user space, normalized to `[llx, lly, urx, ury]`.
+    pub rect: [f64; 4],
+    pub target: LinkTarget,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkTarget {
+    /// External link from a `/URI` action.
+    Uri(String),
+    /// Internal `GoTo` destination, as a 1-based page number.
+    Page(usize),
+}
+
+impl LinkTarget {
+    /// The `href` for this target: the URI itself, or a `#page-N` anchor.
+    pub fn href(&self) -> String {
+        match self {
+            LinkTarget::Uri(uri) => uri.clone(),
+            LinkTarget::Page(page_number) => format!("#page-{}", page_number),
+        }
+    }
+}
+
+/// A sticky note or markup annotation and the comment it carries.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoteAnnotation {
+    /// `/Rect` in default user space, normalized to `[llx, lly, urx, ury]`.
+    pub rect: [f64; 4],
+    /// `/Contents`: the text of the note or comment.
+    pub contents: String,
+}
+
+/// A form field's widget annotation with the value it shows.
+#[derive(Debug, Clone)]
+pub struct FormWidget {
+    /// `/Rect` in default user space, normalized to `[llx, lly, urx, ury]`.
+    pub rect: [f64; 4],
+    /// Fully qualified field name: the `/T` of the field and its ancestors,
+    /// joined with periods.
+    pub name: String,
+    pub value: FormValue,
+    /// Font size from the field's `/DA`; 0 means fit to the widget.
+    pub font_size: f64,
+    /// A button's `/AP /N` appearance for the state it is in, when the
+    /// widget has one.
+    pub appearance: Option<FormXObject>,
+    /// `/MK /BG`: the background color drawn when there is no appearance.
+    pub background: Option<Color>,
+    /// `/MK /BC`: the border color drawn when there is no appearance.
+    pub border: Option<Color>,
+    /// `/MK /CA`: a button's caption; for check boxes and radio buttons, the
+    /// ZapfDingbats character of their mark.
+    pub caption: Option<String>,
+    /// 1-based position in the document's keyboard order: fields listed in
+    /// `/AcroForm /CO` first, in that order, then the rest in page and
+    /// `/Annots` order.
+    pub tab_index: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FormValue {
+    /// The `/V` of a text or choice field.
+    Text(String),
+    /// Whether a check box's widget is on.
+    CheckBox(bool),
+    /// Whether a radio button's widget is the selected one.
+    RadioButton(bool),
+}
+
+/// Field attributes that kids inherit from their parent fields.
+#[derive(Default, Clone)]
+struct InheritedField {
+    name: String,
+    field_type: Option<String>,
+    value: Option<PdfObject>,
+    flags: i64,
+    default_appearance: Option<String>,
+}
+
+/// Walk the catalog's `/AcroForm /Fields` to every widget, keyed by the
+/// widget object so pages can pick theirs out of `/Annots`.
+///
+/// Signature fields, push buttons and fields without a value are skipped.
+pub fn extract_form_widgets(
+    resolver: &PdfRefResolver,
+    catalog: &Dictionary,
+) -> HashMap<ObjectReference, FormWidget> {
+    let mut widgets = HashMap::new();
+    let resolve = |obj: &PdfObject| match obj.as_reference() {
+        Some(r) => resolver.dereference(r),
+        None => Some(obj.clone()),
+    };
+    let Some(PdfObject::Dictionary(acro_form)) = catalog.get("AcroForm").and_then(resolve) else {
+        return widgets;
+    };
+    let inherited = InheritedField {
+        default_appearance: acro_form
+            .get("DA")
+            .and_then(resolve)
+            .and_then(|da| da.as_raw_bytes())
+            .map(|da| String::from_utf8_lossy(&da).into_owned()),
+        ..Default::default()
+    };
+    if let Some(PdfObject::Array(fields)) = acro_form.get("Fields").and_then(resolve) {
+        for field in fields.iter().filter_map(|f| f.as_reference()) {
+            walk_field(resolver, field, &inherited, 0, &mut widgets);
+        }
+    }
+    widgets
+}
+
+fn walk_field(
+    resolver: &PdfRefResolver,
+    node_ref: ObjectReference,
+    parent: &InheritedField,
+    depth: usize,
+    widgets: &mut HashMap<ObjectReference, FormWidget>,
+) {
+    if depth > MAX_FIELD_DEPTH || widgets.contains_key(&node_ref) {
+        return;
+    }
+    let Some(PdfObject::Dictionary(node)) = resolver.dereference(node_ref) else {
+        return;
+    };
+    let resolve = |key: &str| {
+        let obj = node.get(key)?;
+        match obj.as_reference() {
+            Some(r) => resolver.dereference(r),
+            None => Some(obj.clone()),
+        }
+    };
+
+    let mut field = parent.clone();
+    if let Some(partial) = resolve("T").and_then(|t| t.as_text_string()) {
+        field.name = if field.name.is_empty() {
+            partial
+        } else {
+            format!("{}.{}", field.name, partial)
+        };
+    }
+    if let Some(field_type) = resolve("FT").and_then(|t| t.as_name().map(str::to_string)) {
+        field.field_type = Some(field_type);
+    }
+    if let Some(value) = resolve("V") {
+        field.value = Some(value);
+    }
+    if let Some(flags) = resolve("Ff").and_then(|f| f.as_integer()) {
+        field.flags = flags;
+    }
+    if let Some(da) = resolve("DA").and_then(|da| da.as_raw_bytes()) {
+        field.default_appearance = Some(String::from_utf8_lossy(&da).into_owned());
+    }
+
+    if node.get("Subtype").and_then(|s| s.as_name()) == Some("Widget") {
+        let rect = resolve("Rect").and_then(|r| normalized_rect(&r, resolver));
+        let appearance_state = resolve("AS").and_then(|s| s.as_name().map(str::to_string));
+        if let (Some(rect), Some(value)) = (rect, form_value(&field, appearance_state)) {
+            let font_size = field
+                .default_appearance
+                .as_deref()
+                .and_then(default_appearance_font_size)
+                .unwrap_or(0.0);
+            let characteristics = match resolve("MK") {
+                Some(PdfObject::Dictionary(mk)) => Some(mk),
+                _ => None,
+            };
+            let color = |key: &str| {
+                let components: Vec<f64> = characteristics
+                    .as_ref()?
+                    .get(key)?
+                    .as_array()?
+                    .iter()
+                    .filter_map(|c| c.as_number())
+                    .collect();
+                ColorSpace::DeviceRGB.to_rgb(&components)
+            };
+            widgets.insert(
+                node_ref,
+                FormWidget {
+                    rect,
+                    name: field.name.clone(),
+                    value,
+                    font_size,
+                    appearance: None,
+                    background: color("BG"),
+                    border: color("BC"),
+                    caption: characteristics
+                        .as_ref()
+                        .and_then(|mk| mk.get("CA"))
+                        .and_then(|ca| ca.as_text_string()),
+                    tab_index: None,
+                },
+            );
+        }
+    }
+
+    if let Some(PdfObject::Array(kids)) = resolve("Kids") {
+        for kid in kids.iter().filter_map(|k| k.as_reference()) {
+            walk_field(resolver, kid, &field, depth + 1, widgets);
+        }
+    }
+}
+
+/// The annotations in a page's `/Annots` with `/Contents` text, other than
+/// links, form widgets and pop-ups, whose text belongs to their parent.
+pub fn extract_notes(resolver: &PdfRefResolver, annots: &PdfObject) -> Vec<NoteAnnotation> {
+    let resolve = |obj: &PdfObject| match obj.as_reference() {
+        Some(r) => resolver.dereference(r),
+        None => Some(obj.clone()),
+    };
+    let Some(PdfObject::Array(annots)) = resolve(annots) else {
+        return Vec::new();
+    };
+    annots
+        .iter()
+        .filter_map(|annot| {
+            let Some(PdfObject::Dictionary(dict)) = resolve(annot) else {
+                return None;
+            };
+            let subtype = dict.get("Subtype").and_then(|s| s.as_name());
+            if matches!(subtype, Some("Link" | "Widget" | "Popup")) {
+                return None;
+            }
+            let contents = resolve(dict.get("Contents")?)?.as_text_string()?;
+            if contents.trim().is_empty() {
+                return None;
+            }
+            Some(NoteAnnotation {
+                rect: normalized_rect(&resolve(dict.get("Rect")?)?, resolver)?,
+                contents,
+            })
+        })
+        .collect()
+}
+
+/// Each field's position in `/AcroForm /CO`, the order the document declares
+/// for its fields.
+pub fn calculation_order(
+    resolver: &PdfRefResolver,
+    catalog: &Dictionary,
+) -> HashMap<ObjectReference, usize> {
+    let resolve = |obj: &PdfObject| match obj.as_reference() {
+        Some(r) => resolver.dereference(r),
+        None => Some(obj.clone()),
+    };
+    let Some(PdfObject::Dictionary(acro_form)) = catalog.get("AcroForm").and_then(resolve) else {
+        return HashMap::new();
+    };
+    let Some(PdfObject::Array(fields)) = acro_form.get("CO").and_then(resolve) else {
+        return HashMap::new();
+    };
+    let mut order = HashMap::new();
+    for (position, field) in fields.iter().filter_map(|f| f.as_reference()).enumerate() {
+        order.entry(field).or_insert(position);
+    }
+    order
+}
+
+/// Where a widget's field comes in `order`: the widget's own entry, as a
+/// merged field and widget, or that of the nearest `/Parent` listed.
+pub fn field_position(
+    resolver: &PdfRefResolver,
+    widget: ObjectReference,
+    order: &HashMap<ObjectReference, usize>,
+) -> Option<usize> {
+    let mut node = widget;
+    for _ in 0..=MAX_FIELD_DEPTH {
+        if let Some(position) = order.get(&node) {
+            return Some(*position);
+        }
+        let Some(PdfObject::Dictionary(dict)) = resolver.dereference(node) else {
+            return None;
+        };
+        node = dict.get("Parent")?.as_reference()?;
+    }
+    None
+}
+
+/// The value a widget of `field` shows, given the widget's `/AS` state.
+fn form_value(field: &InheritedField, appearance_state: Option<String>) -> Option<FormValue> {
+    match field.field_type.as_deref()? {
+        "Tx" | "Ch" => {
+            let text = match field.value.as_ref()? {
+                PdfObject::Array(items) => items
+                    .iter()
+                    .filter_map(|item| item.as_text_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                value => value.as_text_string()?,
+            };
+            Some(FormValue::Text(text))
+        }
+        "Btn" if field.flags & FLAG_PUSHBUTTON != 0 => None,
+        "Btn" => {
+            // Each widget's /AS names its own state; /V names the field's
+            let state = appearance_state.or_else(|| {
+                field.value.as_ref().and_then(|v| v.as_name().map(str::to_string))
+            });
+            let on = state.is_some_and(|state| state != "Off");
+            if field.flags & FLAG_RADIO != 0 {
+                Some(FormValue::RadioButton(on))
+            } else {
+                Some(FormValue::CheckBox(on))
+            }
+        }
+        _ => None,
+    }
+}
+
+/// The `/AP /N` stream a button widget shows in its on or off state: the
+/// one its `/AS` names, else the first non-`Off` state when on.
+pub fn button_appearance(
+    resolver: &PdfRefResolver,
+    widget: &Dictionary,
+    on: bool,
+) -> Option<(Vec<u8>, Dictionary)> {
+    let resolve = |obj: &PdfObject| match obj.as_reference() {
+        Some(r) => resolver.dereference(r),
+        None => Some(obj.clone()),
+    };
+    let Some(PdfObject::Dictionary(appearances)) = widget.get("AP").and_then(resolve) else {
+        return None;
+    };
+    let Some(PdfObject::Dictionary(states)) = appearances.get("N").and_then(resolve) else {
+        return None;
+    };
+    let named = widget.get("AS").and_then(|s| s.as_name()).filter(|s| (*s != "Off") == on);
+    let state = match named {
+        Some(state) => states.get(state),
+        None if on => states.entries.iter().find(|(s, _)| s != "Off").map(|(_, a)| a),
+        None => states.get("Off"),
+    };
+    match state.and_then(resolve)? {
+        PdfObject::Stream(data, dict) => Some((data, dict)),
+        _ => None,
+    }
+}
+
+/// The size operand of the `Tf` in a `/DA` string such as `/Helv 10 Tf 0 g`.
+fn default_appearance_font_size(da: &str) -> Option<f64> {
+    let tokens: Vec<&str> = da.split_whitespace().collect();
+    let tf = tokens.iter().rposition(|t| *t == "Tf")?;
+    tokens.get(tf.checked_sub(1)?)?.parse().ok()
+}
+
+fn normalized_rect(obj: &PdfObject, resolver: &PdfRefResolver) -> Option<[f64; 4]> {
+    let arr = obj.as_array()?;
+    if arr.len() < 4 {
+        return None;
+    }
+    let mut v = [0.0; 4];
+    for (i, item) in arr.iter().take(4).enumerate() {
+        v[i] = match item.as_reference() {
+            Some(r) => resolver.dereference(r)?.as_number()?,
+            None => item.as_number()?,
+        };
+    }
+    Some([
+        v[0].min(v[2]),
+        v[1].min(v[3]),
+        v[0].max(v[2]),
+        v[1].max(v[3]),
+    ])
+}
+
+/// Resolves link destinations against the document's pages and its named
+/// destinations (`/Dests` in the catalog and the `/Names /Dests` name tree).
+pub struct DestinationResolver<'a> {
+    resolver: &'a PdfRefResolver<'a>,
+    page_numbers: HashMap<ObjectReference, usize>,
+    named: HashMap<String, PdfObject>,
+}
+
+impl<'a> DestinationResolver<'a> {
+    /// `page_refs` lists the page objects in document order, `None` for pages
+    /// written directly into a /Kids array, which nothing can point at.
+    pub fn new(
+        resolver: &'a PdfRefResolver<'a>,
+        page_refs: &[Option<ObjectReference>],
+        catalog: Option<&Dictionary>,
+    ) -> Self {
+        let page_numbers = page_refs
+            .iter()
+            .enumerate()
+            .filter_map(|(i, r)| Some(((*r)?, i + 1)))
+            .collect();
+        let mut this = Self {
+            resolver,
+            page_numbers,
+            named: HashMap::new(),
+        };
+        if let Some(catalog) = catalog {
+            this.collect_named_destinations(catalog);
+        }
+        this
+    }
+
+    /// The 1-based number of the page object `page_ref`.
+    pub fn page_number(&self, page_ref: ObjectReference) -> Option<usize> {
+        self.page_numbers.get(&page_ref).copied()
+    }
+
+    /// Parse a page's `/Annots` array into its link annotations.
+    ///
+    /// Links without a usable `/Rect`, or whose target cannot be resolved
+    /// (remote documents, launch actions, URIs outside the web and mail
+    /// schemes), are skipped.
+    pub fn extract_links(&self, annots: &PdfObject) -> Vec<LinkAnnotation> {
+        let annots = match self.resolve(annots) {
+            Some(PdfObject::Array(arr)) => arr,
+            _ => return Vec::new(),
+        };
+
+        annots
+            .iter()
+            .filter_map(|annot| match self.resolve(annot) {
+                Some(PdfObject::Dictionary(dict)) => self.parse_link(&dict),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The page an outline item jumps to, through its `/A` `GoTo` action
+    /// or its `/Dest`.
+    pub fn goto_page(&self, dict: &Dictionary) -> Option<usize> {
+        match dict.get("A").and_then(|a| self.resolve(a)) {
+            Some(PdfObject::Dictionary(action)) => match self.action_target(&action)? {
+                LinkTarget::Page(page_number) => Some(page_number),
+                LinkTarget::Uri(_) => None,
+            },
+            _ => self.destination_page(dict.get("Dest")?, 0),
+        }
+    }
+
+    fn parse_link(&self, dict: &Dictionary) -> Option<LinkAnnotation> {
+        if dict.get("Subtype").and_then(|v| v.as_name()) != Some("Link") {
+            return None;
+        }
+        let rect = self.parse_rect(dict.get("Rect")?)?;
+
+        let target = match dict.get("A").and_then(|a| self.resolve(a)) {
+            Some(PdfObject::Dictionary(action)) => self.action_target(&action)?,
+            _ => self
+                .destination_page(dict.get("Dest")?, 0)
+                .map(LinkTarget::Page)?,
+        };
+
+        Some(LinkAnnotation { rect, target })
+    }
+
+    fn parse_rect(&self, obj: &PdfObject) -> Option<[f64; 4]> {
+        normalized_rect(&self.resolve(obj)?, self.resolver)
+    }
+
+    fn action_target(&self, action: &Dictionary) -> Option<LinkTarget> {
+        match action.get("S").and_then(|s| s.as_name()) {
+            Some("URI") => {
+                let uri = self.resolve(action.get("URI")?)?;
+                link_uri(uri.as_string()?).map(LinkTarget::Uri)
+            }
+            Some("GoTo") => self
+                .destination_page(action.get("D")?, 0)
+                .map(LinkTarget::Page),
+            _ => None,
+        }
+    }
+
+    /// Follow a destination (explicit array, name, or `<< /D ... >>`) to a page number.
+    fn destination_page(&self, dest: &PdfObject, depth: usize) -> Option<usize> {
+        if depth > MAX_DEST_DEPTH {
+            return None;
+        }
+        match self.resolve(dest)? {
+            PdfObject::Array(arr) => match arr.first()? {
+                PdfObject::IndirectReference { .. } => {
+                    self.page_numbers.get(&arr[0].as_reference()?).copied()
+                }
+                // Page index, as used by remote destinations
+                PdfObject::Integer(i) if *i >= 0 => Some(*i as usize + 1),
+                _ => None,
+            },
+            PdfObject::Dictionary(d) => self.destination_page(d.get("D")?, depth + 1),
+            PdfObject::Name(name) | PdfObject::String(name) => {
+                self.destination_page(self.named.get(&name)?, depth + 1)
+            }
+            _ => None,
+        }
+    }
+
+    fn collect_named_destinations(&mut self, catalog: &Dictionary) {
+        // PDF 1.1 style: a dictionary from name to destination
+        if let Some(PdfObject::Dictionary(dests)) =
+            catalog.get("Dests").and_then(|d| self.resolve(d))
+        {
+            for (name, dest) in &dests.entries {
+                self.named.insert(name.clone(), dest.clone());
+            }
+        }
+
+        let tree = catalog
+            .get("Names")
+            .and_then(|n| self.resolve(n))
+            .and_then(|n| match n {
+                PdfObject::Dictionary(names) => names.get("Dests").and_then(|d| self.resolve(d)),
+                _ => None,
+            });
+        if let Some(PdfObject::Dictionary(root)) = tree {
+            self.walk_name_tree(&root, 0);
+        }
+    }
+
+    fn walk_name_tree(&mut self, node: &Dictionary, depth: usize) {
+        if depth > MAX_NAME_TREE_DEPTH {
+            return;
+        }
+        if let Some(PdfObject::Array(names)) = node.get("Names").and_then(|n| self.resolve(n)) {
+            for pair in names.chunks(2) {
+                if let [key, value] = pair {
+                    if let Some(key) = key.as_string() {
+                        self.named.insert(key.to_string(), value.clone());
+                    }
+                }
+            }
+        }
+        if let Some(PdfObject::Array(kids)) = node.get("Kids").and_then(|k| self.resolve(k)) {
+            for kid in &kids {
+                if let Some(PdfObject::Dictionary(kid)) = self.resolve(kid) {
+                    self.walk_name_tree(&kid, depth + 1);
+                }
+            }
+        }
+    }
+
+    fn resolve(&self, obj: &PdfObject) -> Option<PdfObject> {
+        match obj.as_reference() {
+            Some(r) => self.resolver.dereference(r),
+            None => Some(obj.clone()),
+        }
+    }
+}
+
+/// Schemes a link may use; anything else, such as `javascript:` or `data:`,
+/// could run script in the page the link is on.
+const LINK_SCHEMES: &[&str] = &["http", "https", "mailto", "ftp"];
+
+/// A `/URI` as a safe `href`: without ASCII control characters, which
+/// browsers drop from URLs, so `java\tscript:` can't slip by, and either
+/// relative or in one of [`LINK_SCHEMES`].
+fn link_uri(uri: &str) -> Option<String> {
+    let uri: String = uri.chars().filter(|c| !c.is_ascii_control()).collect();
+    let uri = uri.trim();
+    if uri.is_empty() {
+        return None;
+    }
+    // A scheme is a letter, then letters, digits, `+`, `-` or `.`, up to a
+    // colon before any `/`, `?` or `#`
+    let scheme = uri
+        .split_once(':')
+        .map(|(scheme, _)| scheme)
+        .filter(|scheme| {
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        });
+    match scheme {
+        Some(scheme) if !LINK_SCHEMES.contains(&scheme.to_ascii_lowercase().as_str()) => None,
+        _ => Some(uri.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::XRef;
+
+    fn name(s: &str) -> PdfObject {
+        PdfObject::Name(s.to_string())
+    }
+
+    fn dict(entries: Vec<(&str, PdfObject)>) -> PdfObject {
+        PdfObject::Dictionary(Dictionary {
+            entries: entries
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+        })
+    }
+
+    fn rect(values: [i64; 4]) -> PdfObject {
+        PdfObject::Array(values.iter().map(|v| PdfObject::Integer(*v)).collect())
+    }
+
+    fn page_ref(id: u64) -> PdfObject {
+        PdfObject::IndirectReference { obj_id: id, gen: 0 }
+    }
+
+    #[test]
+    fn test_uri_link() {
+        let xref = XRef::default();
+        let resolver = PdfRefResolver::new(b"", &xref);
+        let dests = DestinationResolver::new(&resolver, &[], None);
+        let annots = PdfObject::Array(vec![dict(vec![
+            ("Subtype", name("Link")),
+            ("Rect", rect([200, 700, 100, 720])),
+            (
+                "A",
+                dict(vec![
+                    ("S", name("URI")),
+                    ("URI", PdfObject::String("https://example.com/".to_string())),
+                ]),
+            ),
+        ])]);
+
+        let links = dests.extract_links(&annots);
+        assert_eq!(
+            links,
+            vec![LinkAnnotation {
+                rect: [100.0, 700.0, 200.0, 720.0],
+                target: LinkTarget::Uri("https://example.com/".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_goto_destinations_resolve_to_page_numbers() {
+        let xref = XRef::default();
+        let resolver = PdfRefResolver::new(b"", &xref);
+        let catalog = Dictionary {
+            entries: vec![(
+                "Dests".to_string(),
+                dict(vec![(
+                    "intro",
+                    PdfObject::Array(vec![page_ref(7), name("Fit")]),
+                )]),
+            )],
+        };
+        let dests = DestinationResolver::new(
+            &resolver,
+            &[Some(ObjectReference(5, 0)), Some(ObjectReference(7, 0))],
+            Some(&catalog),
+        );
+        let annots = PdfObject::Array(vec![
+            dict(vec![
+                ("Subtype", name("Link")),
+                ("Rect", rect([0, 0, 10, 10])),
+                ("Dest", PdfObject::Array(vec![page_ref(5), name("XYZ")])),
+            ]),
+            dict(vec![
+                ("Subtype", name("Link")),
+                ("Rect", rect([0, 0, 10, 10])),
+                (
+                    "A",
+                    dict(vec![
+                        ("S", name("GoTo")),
+                        ("D", PdfObject::String("intro".to_string())),
+                    ]),
+                ),
+            ]),
+        ]);
+
+        let targets: Vec<String> = dests
+            .extract_links(&annots)
+            .iter()
+            .map(|l| l.target.href())
+            .collect();
+        assert_eq!(targets, vec!["#page-1", "#page-2"]);
+    }
+
+    #[test]
+    fn test_skips_non_links_and_script_uris() {
+        let xref = XRef::default();
+        let resolver = PdfRefResolver::new(b"", &xref);
+        let dests = DestinationResolver::new(&resolver, &[], None);
+        let annots = PdfObject::Array(vec![
+            dict(vec![
+                ("Subtype", name("Text")),
+                ("Rect", rect([0, 0, 10, 10])),
+            ]),
+            dict(vec![
+                ("Subtype", name("Link")),
+                ("Rect", rect([0, 0, 10, 10])),
+                (
+                    "A",
+                    dict(vec![
+                        ("S", name("URI")),
+                        ("URI", PdfObject::String("JavaScript:alert(1)".to_string())),
+                    ]),
+                ),
+            ]),
+        ]);
+
+        assert!(dests.extract_links(&annots).is_empty());
+    }
+
+    #[test]
+    fn test_link_uri_allows_only_web_and_mail_schemes() {
+        for uri in [
+            "java\tscript:alert(1)",
+            "java\nscript:alert(1)",
+            " \u{1}javascript:alert(1)",
+            "VBScript:msgbox(1)",
+            "data:text/html,<script>alert(1)</script>",
+            "file:///etc/passwd",
+            "",
+        ] {
+            assert_eq!(link_uri(uri), None, "{:?}", uri);
+        }
+        assert_eq!(
+            link_uri(" https://example.com/a\r\n").as_deref(),
+            Some("https://example.com/a")
+        );
+        for uri in [
+            "HTTP://example.com",
+            "mailto:a@example.com",
+            "ftp://x",
+            "docs/a:b.html",
+            "#top",
+        ] {
+            assert_eq!(link_uri(uri).as_deref(), Some(uri));
+        }
+    }
+}
@@ -0,0 +1,575 @@
+use super::{Dictionary, PdfObject, PdfRefResolver};
+use crate::types::color::Color;
+
+/// Maximum nesting of color space references (`/Indexed` bases, resource names).
+const MAX_COLORSPACE_DEPTH: usize = 8;
+
+/// A resolved PDF color space, reduced to what the converter can reproduce.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColorSpace {
+    DeviceGray,
+    DeviceRGB,
+    DeviceCMYK,
+    /// `[/Indexed base hival lookup]`: samples are indices into `lookup`,
+    /// which holds `hival + 1` entries of `base.components()` bytes each.
+    Indexed {
+        base: Box<ColorSpace>,
+        hival: u8,
+        lookup: Vec<u8>,
+    },
+    /// `[/Separation name alt tint]` or `[/DeviceN names alt tint]`.
+    Separation {
+        components: u8,
+        alternate: Box<ColorSpace>,
+        tint: Option<TintTransform>,
+    },
+    /// `/Pattern` or `[/Pattern base]`: `scn` names a pattern, after the
+    /// components of its color in `base` when the pattern is uncolored.
+    Pattern {
+        base: Option<Box<ColorSpace>>,
+    },
+}
+
+/// An exponential interpolation (Type 2) function, the usual tint transform
+/// for spot colors: `out = C0 + t^N * (C1 - C0)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TintTransform {
+    pub c0: Vec<f64>,
+    pub c1: Vec<f64>,
+    pub exponent: f64,
+}
+
+impl TintTransform {
+    pub(crate) fn from_dict(dict: &Dictionary) -> Option<Self> {
+        if dict.get("FunctionType").and_then(|v| v.as_integer()) != Some(2) {
+            return None;
+        }
+        let numbers = |key: &str, default: Vec<f64>| {
+            dict.get(key)
+                .and_then(|v| v.as_array())
+                .map(|arr| arr.iter().filter_map(|n| n.as_number()).collect())
+                .unwrap_or(default)
+        };
+        let c0 = numbers("C0", vec![0.0]);
+        let c1 = numbers("C1", vec![1.0]);
+        if c0.len() != c1.len() {
+            return None;
+        }
+        Some(TintTransform {
+            c0,
+            c1,
+            exponent: dict.get("N").and_then(|v| v.as_number()).unwrap_or(1.0),
+        })
+    }
+
+    pub fn evaluate(&self, t: f64) -> Vec<f64> {
+        let factor = t.clamp(0.0, 1.0).powf(self.exponent);
+        self.c0
+            .iter()
+            .zip(&self.c1)
+            .map(|(a, b)| a + factor * (b - a))
+            .collect()
+    }
+}
+
+impl ColorSpace {
+    /// Number of color components per sample in this space.
+    pub fn components(&self) -> u8 {
+        match self {
+            ColorSpace::DeviceGray => 1,
+            ColorSpace::DeviceRGB => 3,
+            ColorSpace::DeviceCMYK => 4,
+            ColorSpace::Indexed { .. } => 1,
+            ColorSpace::Separation { components, .. } => *components,
+            ColorSpace::Pattern { base } => base.as_ref().map_or(0, |base| base.components()),
+        }
+    }
+
+    /// Convert color operands in this space to RGB.
+    ///
+    /// Operand counts that don't match the space (producers that skip `cs`)
+    /// are read by count instead: 1 gray, 3 RGB, 4 CMYK. Returns `None` when
+    /// the operands can't be interpreted.
+    pub fn to_rgb(&self, operands: &[f64]) -> Option<Color> {
+        if operands.len() != self.components() as usize {
+            return match operands.len() {
+                1 => ColorSpace::DeviceGray.to_rgb(operands),
+                3 => ColorSpace::DeviceRGB.to_rgb(operands),
+                4 => ColorSpace::DeviceCMYK.to_rgb(operands),
+                _ => None,
+            };
+        }
+
+        let channel = |v: f64| (v.clamp(0.0, 1.0) * 255.0) as u8;
+        match self {
+            ColorSpace::DeviceGray => {
+                let v = channel(operands[0]);
+                Some(Color::new(v, v, v))
+            }
+            ColorSpace::DeviceRGB => Some(Color::new(
+                channel(operands[0]),
+                channel(operands[1]),
+                channel(operands[2]),
+            )),
+            ColorSpace::DeviceCMYK => Some(Color::from_cmyk(
+                operands[0],
+                operands[1],
+                operands[2],
+                operands[3],
+            )),
+            ColorSpace::Indexed { base, .. } => {
+                let entry = self.lookup_index(operands[0].max(0.0) as usize)?;
+                let components: Vec<f64> = entry.iter().map(|&v| v as f64 / 255.0).collect();
+                base.to_rgb(&components)
+            }
+            ColorSpace::Separation {
+                alternate, tint, ..
+            } => {
+                let converted = match tint {
+                    Some(f) if operands.len() == 1 => alternate.to_rgb(&f.evaluate(operands[0])),
+                    _ => None,
+                };
+                converted.or_else(|| {
+                    // Tints are ink coverage: approximate with the matching gray
+                    let ink = operands.iter().sum::<f64>() / operands.len() as f64;
+                    ColorSpace::DeviceGray.to_rgb(&[1.0 - ink.clamp(0.0, 1.0)])
+                })
+            }
+            ColorSpace::Pattern { base } => base.as_ref()?.to_rgb(operands),
+        }
+    }
+
+    /// The color selected by `cs`/`CS` before any `sc`/`scn` (PDF 32000-1, 8.6.8).
+    pub fn initial_color(&self) -> Color {
+        let operands: Vec<f64> = match self {
+            ColorSpace::DeviceCMYK => vec![0.0, 0.0, 0.0, 1.0],
+            ColorSpace::Separation { components, .. } => vec![1.0; *components as usize],
+            other => vec![0.0; other.components() as usize],
+        };
+        self.to_rgb(&operands).unwrap_or(Color::new(0, 0, 0))
+    }
+
+    /// Map a device or CIE-based family name to its closest device space.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "DeviceGray" | "G" | "CalGray" => Some(ColorSpace::DeviceGray),
+            "DeviceRGB" | "RGB" | "CalRGB" | "Lab" => Some(ColorSpace::DeviceRGB),
+            "DeviceCMYK" | "CMYK" => Some(ColorSpace::DeviceCMYK),
+            "Pattern" => Some(ColorSpace::Pattern { base: None }),
+            _ => None,
+        }
+    }
+
+    /// Resolve a `/ColorSpace` value. Names not known as device spaces are
+    /// looked up in the `/ColorSpace` dictionary of `resources`.
+    pub fn resolve(
+        obj: &PdfObject,
+        resolver: &PdfRefResolver,
+        resources: Option<&Dictionary>,
+    ) -> Option<Self> {
+        Self::resolve_with_depth(obj, resolver, resources, 0)
+    }
+
+    fn resolve_with_depth(
+        obj: &PdfObject,
+        resolver: &PdfRefResolver,
+        resources: Option<&Dictionary>,
+        depth: usize,
+    ) -> Option<Self> {
+        if depth > MAX_COLORSPACE_DEPTH {
+            return None;
+        }
+
+        match obj {
+            PdfObject::Name(name) => Self::from_name(name).or_else(|| {
+                let named = resources?.get("ColorSpace")?;
+                let cs_dict = match named {
+                    PdfObject::Dictionary(d) => d.clone(),
+                    other => match resolver.dereference(other.as_reference()?)? {
+                        PdfObject::Dictionary(d) => d,
+                        _ => return None,
+                    },
+                };
+                let entry = cs_dict.get(name)?;
+                Self::resolve_with_depth(entry, resolver, resources, depth + 1)
+            }),
+            PdfObject::IndirectReference { .. } => {
+                let target = resolver.dereference(obj.as_reference()?)?;
+                Self::resolve_with_depth(&target, resolver, resources, depth + 1)
+            }
+            PdfObject::Array(arr) => {
+                let family = arr.first()?.as_name()?;
+                match family {
+                    "Indexed" | "I" => {
+                        let base =
+                            Self::resolve_with_depth(arr.get(1)?, resolver, resources, depth + 1)?;
+                        let hival = arr.get(2)?.as_number()?.clamp(0.0, 255.0) as u8;
+                        let lookup = match arr.get(3)? {
+                            s @ PdfObject::String(_) => s.as_raw_bytes()?,
+                            PdfObject::Stream(data, _) => data.clone(),
+                            other => match resolver.dereference(other.as_reference()?)? {
+                                PdfObject::Stream(data, _) => data,
+                                s @ PdfObject::String(_) => s.as_raw_bytes()?,
+                                _ => return None,
+                            },
+                        };
+                        Some(ColorSpace::Indexed {
+                            base: Box::new(base),
+                            hival,
+                            lookup,
+                        })
+                    }
+                    "ICCBased" => {
+                        let stream_dict = match arr.get(1)? {
+                            PdfObject::Stream(_, d) => d.clone(),
+                            other => match resolver.dereference(other.as_reference()?)? {
+                                PdfObject::Stream(_, d) => d,
+                                _ => return None,
+                            },
+                        };
+                        match stream_dict.get("N").and_then(|v| v.as_integer()) {
+                            Some(1) => Some(ColorSpace::DeviceGray),
+                            Some(3) => Some(ColorSpace::DeviceRGB),
+                            Some(4) => Some(ColorSpace::DeviceCMYK),
+                            _ => stream_dict.get("Alternate").and_then(|alt| {
+                                Self::resolve_with_depth(alt, resolver, resources, depth + 1)
+                            }),
+                        }
+                    }
+                    "CalGray" | "CalRGB" | "Lab" => Self::from_name(family),
+                    "Pattern" => {
+                        let base = arr.get(1).and_then(|base| {
+                            Self::resolve_with_depth(base, resolver, resources, depth + 1)
+                        });
+                        Some(ColorSpace::Pattern {
+                            base: base.map(Box::new),
+                        })
+                    }
+                    "Separation" | "DeviceN" => {
+                        let components = match family {
+                            "DeviceN" => arr.get(1)?.as_array()?.len().clamp(1, 32) as u8,
+                            _ => 1,
+                        };
+                        let alternate =
+                            Self::resolve_with_depth(arr.get(2)?, resolver, resources, depth + 1)
+                                .unwrap_or(ColorSpace::DeviceGray);
+                        let tint = match arr.get(3) {
+                            Some(PdfObject::Dictionary(d)) => TintTransform::from_dict(d),
+                            Some(PdfObject::Stream(_, d)) => TintTransform::from_dict(d),
+                            Some(other) => match other.as_reference().and_then(|r| resolver.dereference(r)) {
+                                Some(PdfObject::Dictionary(d)) | Some(PdfObject::Stream(_, d)) => {
+                                    TintTransform::from_dict(&d)
+                                }
+                                _ => None,
+                            },
+                            None => None,
+                        };
+                        Some(ColorSpace::Separation {
+                            components,
+                            alternate: Box::new(alternate),
+                            tint,
+                        })
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Palette entry for `index` in the base space of an Indexed color space.
+    /// Out-of-range indices are clamped to `hival` as the spec requires.
+    pub fn lookup_index(&self, index: usize) -> Option<&[u8]> {
+        match self {
+            ColorSpace::Indexed {
+                base,
+                hival,
+                lookup,
+            } => {
+                let n = base.components() as usize;
+                let start = index.min(*hival as usize) * n;
+                lookup.get(start..start + n)
+            }
+            _ => None,
+        }
+    }
+
+    /// Expand packed Indexed image samples into base-space pixels.
+    ///
+    /// Rows start on a byte boundary; `bits_per_component` may be 1, 2, 4 or 8.
+    /// Returns the expanded pixels and the number of channels per pixel, or
+    /// `None` when this is not an Indexed space.
+    pub fn expand_indexed(
+        &self,
+        samples: &[u8],
+        width: u32,
+        height: u32,
+        bits_per_component: u8,
+    ) -> Option<(Vec<u8>, u8)> {
+        let base = match self {
+            ColorSpace::Indexed { base, .. } => base,
+            _ => return None,
+        };
+        let channels = base.components();
+        let bpc = match bits_per_component {
+            1 | 2 | 4 | 8 => bits_per_component as usize,
+            _ => 8,
+        };
+        let row_bytes = (width as usize * bpc).div_ceil(8);
+        let mask = ((1u16 << bpc) - 1) as u8;
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * channels as usize);
+
+        for row in 0..height as usize {
+            let row_data = samples.get(row * row_bytes..).unwrap_or(&[]);
+            for col in 0..width as usize {
+                let bit = col * bpc;
+                let index = row_data
+                    .get(bit / 8)
+                    .map(|&byte| (byte >> (8 - bpc - bit % 8)) & mask)
+                    .unwrap_or(0);
+                match self.lookup_index(index as usize) {
+                    Some(entry) => pixels.extend_from_slice(entry),
+                    None => pixels.resize(pixels.len() + channels as usize, 0),
+                }
+            }
+        }
+
+        Some((pixels, channels))
+    }
+
+    /// Remap packed image samples through a `/Decode` array (PDF 32000-1, 8.9.5.2).
+    ///
+    /// Each sample `s` maps to `Dmin + s * (Dmax - Dmin) / (2^bpc - 1)` and is
+    /// quantized back against this space's default range (`[0 1]`, or
+    /// `[0 2^bpc-1]` for Indexed), so the packing is unchanged and the result
+    /// feeds the usual expansion. Returns `None` when the array is missing,
+    /// malformed or the identity.
+    pub fn apply_decode(
+        &self,
+        samples: &[u8],
+        width: u32,
+        bits_per_component: u8,
+        decode: &[f64],
+    ) -> Option<Vec<u8>> {
+        let components = self.components() as usize;
+        let bpc = match bits_per_component {
+            1 | 2 | 4 | 8 => bits_per_component as usize,
+            _ => return None,
+        };
+        if decode.len() < 2 * components {
+            return None;
+        }
+        let max = ((1u16 << bpc) - 1) as f64;
+        let default_max = match self {
+            ColorSpace::Indexed { .. } => max,
+            _ => 1.0,
+        };
+        let ranges: Vec<(f64, f64)> = decode
+            .chunks(2)
+            .take(components)
+            .map(|pair| (pair[0], pair[1]))
+            .collect();
+        if ranges.iter().all(|&range| range == (0.0, default_max)) {
+            return None;
+        }
+
+        let samples_per_row = width as usize * components;
+        let row_bytes = (samples_per_row * bpc).div_ceil(8);
+        if row_bytes == 0 {
+            return None;
+        }
+        let mask = ((1u16 << bpc) - 1) as u8;
+        let mut out = samples.to_vec();
+        for row in out.chunks_mut(row_bytes) {
+            for i in 0..samples_per_row {
+                let bit = i * bpc;
+                let Some(byte) = row.get_mut(bit / 8) else {
+                    break;
+                };
+                let shift = 8 - bpc - bit % 8;
+                let sample = (*byte >> shift) & mask;
+                let (d_min, d_max) = ranges[i % components];
+                let decoded = d_min + sample as f64 * (d_max - d_min) / max;
+                let quantized = (decoded / default_max * max).round().clamp(0.0, max) as u8;
+                *byte = (*byte & !(mask << shift)) | (quantized << shift);
+            }
+        }
+        Some(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::XRef;
+
+    fn palette() -> ColorSpace {
+        ColorSpace::Indexed {
+            base: Box::new(ColorSpace::DeviceRGB),
+            hival: 2,
+            lookup: vec![255, 0, 0, 0, 255, 0, 0, 0, 255],
+        }
+    }
+
+    #[test]
+    fn test_resolve_named_device_spaces() {
+        let xref = XRef::default();
+        let resolver = PdfRefResolver::new(b"", &xref);
+        let gray = PdfObject::Name("DeviceGray".to_string());
+        assert_eq!(
+            ColorSpace::resolve(&gray, &resolver, None),
+            Some(ColorSpace::DeviceGray)
+        );
+    }
+
+    #[test]
+    fn test_resolve_indexed_from_resources() {
+        let xref = XRef::default();
+        let resolver = PdfRefResolver::new(b"", &xref);
+        let indexed = PdfObject::Array(vec![
+            PdfObject::Name("Indexed".to_string()),
+            PdfObject::Name("DeviceRGB".to_string()),
+            PdfObject::Integer(1),
+            PdfObject::String("\u{ff}\u{0}\u{0}\u{0}\u{0}\u{ff}".to_string()),
+        ]);
+        let resources = Dictionary {
+            entries: vec![(
+                "ColorSpace".to_string(),
+                PdfObject::Dictionary(Dictionary {
+                    entries: vec![("CS0".to_string(), indexed)],
+                }),
+            )],
+        };
+        let cs = ColorSpace::resolve(
+            &PdfObject::Name("CS0".to_string()),
+            &resolver,
+            Some(&resources),
+        )
+        .unwrap();
+        assert_eq!(cs.components(), 1);
+        assert_eq!(cs.lookup_index(1), Some(&[0u8, 0, 255][..]));
+    }
+
+    #[test]
+    fn test_to_rgb_by_space() {
+        assert_eq!(
+            ColorSpace::DeviceGray.to_rgb(&[0.5]),
+            Some(Color::new(127, 127, 127))
+        );
+        assert_eq!(
+            ColorSpace::DeviceCMYK.to_rgb(&[0.0, 1.0, 1.0, 0.0]),
+            Some(Color::new(255, 46, 23))
+        );
+        // Operand count overrides a mismatched space
+        assert_eq!(
+            ColorSpace::DeviceGray.to_rgb(&[0.0, 0.0, 1.0]),
+            Some(Color::new(0, 0, 255))
+        );
+        assert_eq!(ColorSpace::DeviceRGB.to_rgb(&[0.1, 0.2]), None);
+    }
+
+    #[test]
+    fn test_separation_tint_transform() {
+        let xref = XRef::default();
+        let resolver = PdfRefResolver::new(b"", &xref);
+        let tint = Dictionary {
+            entries: vec![
+                ("FunctionType".to_string(), PdfObject::Integer(2)),
+                (
+                    "C0".to_string(),
+                    PdfObject::Array(vec![PdfObject::Integer(0); 4]),
+                ),
+                (
+                    "C1".to_string(),
+                    PdfObject::Array(vec![
+                        PdfObject::Integer(0),
+                        PdfObject::Integer(1),
+                        PdfObject::Integer(1),
+                        PdfObject::Integer(0),
+                    ]),
+                ),
+                ("N".to_string(), PdfObject::Integer(1)),
+            ],
+        };
+        let separation = PdfObject::Array(vec![
+            PdfObject::Name("Separation".to_string()),
+            PdfObject::Name("PANTONE 485 C".to_string()),
+            PdfObject::Name("DeviceCMYK".to_string()),
+            PdfObject::Dictionary(tint),
+        ]);
+        let cs = ColorSpace::resolve(&separation, &resolver, None).unwrap();
+        assert_eq!(cs.initial_color(), Color::new(255, 46, 23));
+        assert_eq!(cs.to_rgb(&[0.0]), Some(Color::new(255, 255, 255)));
+    }
+
+    #[test]
+    fn test_separation_without_tint_falls_back_to_gray() {
+        let cs = ColorSpace::Separation {
+            components: 2,
+            alternate: Box::new(ColorSpace::DeviceCMYK),
+            tint: None,
+        };
+        assert_eq!(cs.to_rgb(&[1.0, 0.0]), Some(Color::new(127, 127, 127)));
+    }
+
+    #[test]
+    fn test_indexed_to_rgb_uses_palette_entry() {
+        assert_eq!(palette().to_rgb(&[1.0]), Some(Color::new(0, 255, 0)));
+        assert_eq!(palette().initial_color(), Color::new(255, 0, 0));
+    }
+
+    #[test]
+    fn test_expand_indexed_8bit() {
+        let (pixels, channels) = palette().expand_indexed(&[0, 2, 1, 9], 2, 2, 8).unwrap();
+        assert_eq!(channels, 3);
+        // Index 9 is out of range and clamps to hival (blue)
+        assert_eq!(pixels, vec![255, 0, 0, 0, 0, 255, 0, 255, 0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_decode_inverts_grayscale() {
+        let decoded = ColorSpace::DeviceGray
+            .apply_decode(&[0, 64, 255, 200], 2, 8, &[1.0, 0.0])
+            .unwrap();
+        assert_eq!(decoded, vec![255, 191, 0, 55]);
+        assert_eq!(
+            ColorSpace::DeviceGray.apply_decode(&[0, 64], 2, 8, &[0.0, 1.0]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_decode_inverts_packed_mask_bits() {
+        // 3 pixels per row at 1 bit: the padding bits are left alone
+        let decoded = ColorSpace::DeviceGray
+            .apply_decode(&[0b1010_0000, 0b0110_0000], 3, 1, &[1.0, 0.0])
+            .unwrap();
+        assert_eq!(decoded, vec![0b0100_0000, 0b1000_0000]);
+    }
+
+    #[test]
+    fn test_decode_remaps_indexed_samples() {
+        // [hival 0] at 2 bits reverses the palette order
+        let decoded = palette()
+            .apply_decode(&[0b00_01_10_00], 3, 2, &[3.0, 0.0])
+            .unwrap();
+        assert_eq!(decoded, vec![0b11_10_01_00]);
+        let (pixels, _) = palette().expand_indexed(&decoded, 3, 1, 2).unwrap();
+        // Index 3 is past hival and clamps to blue
+        assert_eq!(pixels, vec![0, 0, 255, 0, 0, 255, 0, 255, 0]);
+    }
+
+    #[test]
+    fn test_expand_indexed_packed_rows() {
+        // 3 pixels at 2 bits per sample: each row is padded to a full byte
+        let samples = [0b00_01_10_00, 0b10_01_00_00];
+        let (pixels, _) = palette().expand_indexed(&samples, 3, 2, 2).unwrap();
+        assert_eq!(
+            pixels,
+            vec![
+                255, 0, 0, 0, 255, 0, 0, 0, 255, //
+                0, 0, 255, 0, 255, 0, 255, 0, 0,
+            ]
+        );
+    }
+}
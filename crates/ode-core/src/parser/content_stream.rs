@@ -1,5 +1,10 @@
+use super::{filters, Dictionary, ObjectReference, PdfObject};
 use crate::error::OdeError;
 use crate::renderer::ContentOp;
+use crate::util::ZipBombDetector;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
 #[derive(Debug, Clone)]
 pub struct ParsedOp {
@@ -8,6 +13,11 @@ pub struct ParsedOp {
     pub text: Option<String>,
     pub text_raw: Option<Vec<u8>>,
     pub font_name: Option<String>,
+    /// The `/Lang` of a `BDC`'s inline property list.
+    pub lang: Option<String>,
+    /// The `/MCID` of a `BDC`'s inline property list, which the structure
+    /// tree refers to the marked content by.
+    pub mcid: Option<u32>,
 }
 
 pub struct ContentStreamParser {
@@ -17,40 +27,37 @@ pub struct ContentStreamParser {
     text: Option<String>,
     text_raw: Option<Vec<u8>>,
     current_font_name: Option<String>,
+    /// The last inline dictionary, a property list for a `BDC` to take.
+    current_properties: Option<Dictionary>,
 }
 
 impl ContentStreamParser {
-    pub fn new(data: &[u8], compression: Option<&str>) -> Result<Self, OdeError> {
-        let decompressed = if let Some(comp_type) = compression {
-            if comp_type == "FlateDecode" || comp_type.contains("Flate") {
-                Self::decompress_flate(data)?
-            } else {
-                data.to_vec()
-            }
-        } else {
-            data.to_vec()
-        };
-
-        Ok(Self {
-            data: decompressed,
+    pub fn new(data: &[u8]) -> Self {
+        Self {
+            data: data.to_vec(),
             position: 0,
             operands: Vec::new(),
             text: None,
             text_raw: None,
             current_font_name: None,
-        })
+            current_properties: None,
+        }
     }
 
-    fn decompress_flate(data: &[u8]) -> Result<Vec<u8>, OdeError> {
-        use flate2::read::ZlibDecoder;
-        use std::io::Read;
-
-        let mut decoder = ZlibDecoder::new(data);
-        let mut decompressed = Vec::new();
-        decoder
-            .read_to_end(&mut decompressed)
-            .map_err(|e| OdeError::PdfParseError(format!("Flate decompression failed: {}", e)))?;
-        Ok(decompressed)
+    /// A parser for `data` encoded with `filter`, decoded within `limits`.
+    /// `data` is read as is when `filter` isn't built in.
+    pub fn compressed(
+        data: &[u8],
+        filter: &str,
+        limits: &ZipBombDetector,
+    ) -> Result<Self, OdeError> {
+        let parms = super::DecodeParms {
+            entries: Vec::new(),
+        };
+        match filters::decode_builtin(filter, data, &parms, limits) {
+            Some(decoded) => Ok(Self::new(&decoded?)),
+            None => Ok(Self::new(data)),
+        }
     }
 
     pub fn parse(&mut self) -> Result<Vec<ParsedOp>, OdeError> {
@@ -112,8 +119,19 @@ impl ContentStreamParser {
                 self.text_raw = Some(raw);
             } else if c == b'<' {
                 if self.peek_ahead(1) == Some(b'<') {
+                    let start = self.position;
                     self.consume(b"<<");
                     self.skip_to_matching(b'>', 2)?;
+                    // Skipping stops on the first `>` of the closing `>>`,
+                    // which mustn't run into an operator right after it
+                    self.position = (self.position + 2).min(self.data.len());
+                    let dict = &self.data[start..self.position];
+                    self.current_properties = match super::object_parser::PdfParser::new(dict)
+                        .parse_object()
+                    {
+                        Ok(PdfObject::Dictionary(dict)) => Some(dict),
+                        _ => None,
+                    };
                 } else {
                     let (text, raw) = self.parse_hex_string_with_raw()?;
                     self.text = Some(text);
@@ -138,17 +156,38 @@ impl ContentStreamParser {
                     // Unknown byte — skip to avoid infinite loop
                     self.position += 1;
                 } else if let Some(operator) = ContentOp::from_name(&op_name) {
-                    let font_name = if operator == ContentOp::Tf || operator == ContentOp::Do {
+                    let font_name = if matches!(
+                        operator,
+                        ContentOp::Tf
+                            | ContentOp::Do
+                            | ContentOp::Cs
+                            | ContentOp::CS
+                            | ContentOp::SCN
+                            | ContentOp::SCNstroke
+                            | ContentOp::Sh
+                            | ContentOp::BDC
+                    ) {
                         self.current_font_name.take()
                     } else {
                         None
                     };
+                    let properties =
+                        self.current_properties.take().filter(|_| operator == ContentOp::BDC);
+                    let lang = properties.as_ref().and_then(|dict| {
+                        super::language_tag(&dict.get("Lang")?.as_text_string()?)
+                    });
+                    let mcid = properties
+                        .as_ref()
+                        .and_then(|dict| dict.get("MCID")?.as_integer())
+                        .and_then(|mcid| u32::try_from(mcid).ok());
                     ops.push(ParsedOp {
                         operator,
                         operands: std::mem::take(&mut self.operands),
                         text: self.text.take(),
                         text_raw: self.text_raw.take(),
                         font_name,
+                        lang,
+                        mcid,
                     });
                 }
             }
@@ -230,7 +269,7 @@ impl ContentStreamParser {
 
         let s = String::from_utf8_lossy(&self.data[start..self.position]);
         s.parse()
-            .map_err(|_| OdeError::PdfParseError(format!("Invalid operand: {}", s)))
+            .map_err(|_| OdeError::malformed(format!("Invalid operand: {}", s)))
     }
 
     fn parse_literal_string_with_raw(&mut self) -> Result<(String, Vec<u8>), OdeError> {
@@ -353,7 +392,7 @@ impl ContentStreamParser {
         match c.to_ascii_uppercase() {
             '0'..='9' => Ok(c as u8 - b'0'),
             'A'..='F' => Ok(c as u8 - b'A' + 10),
-            _ => Err(OdeError::PdfParseError(format!("Invalid hex: {}", c))),
+            _ => Err(OdeError::malformed(format!("Invalid hex: {}", c))),
         }
     }
 
@@ -380,11 +419,13 @@ impl ContentStreamParser {
 
         while self.position < self.data.len() && depth_count > 0 {
             let c = self.data[self.position];
+            // A delimiter ends a name as well, as in `/Lang(en)>>`
+            if in_name && (c.is_ascii_whitespace() || b"()<>[]{}%".contains(&c)) {
+                in_name = false;
+            }
 
             if !in_string && !in_array && c == b'/' {
                 in_name = true;
-            } else if in_name && c.is_ascii_whitespace() {
-                in_name = false;
             } else if !in_name && !in_string && !in_array && c == b'<' {
                 if self.peek_ahead(1) == Some(b'<') {
                     self.position += 1;
@@ -411,7 +452,7 @@ impl ContentStreamParser {
         }
 
         if depth_count != 0 {
-            return Err(OdeError::PdfParseError(
+            return Err(OdeError::malformed(
                 "Unmatched dictionary delimiters".to_string(),
             ));
         }
@@ -420,6 +461,93 @@ impl ContentStreamParser {
     }
 }
 
+/// Parsed content streams keyed by their `/Contents` object, so pages that
+/// share one stream (templated reports) parse it only once.
+#[derive(Debug, Default)]
+pub struct ContentOpsCache {
+    ops: Mutex<HashMap<ObjectReference, Arc<Vec<ParsedOp>>>>,
+    parses: AtomicUsize,
+}
+
+impl ContentOpsCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The ops for `content`, parsed with `parse` unless the stream behind
+    /// `key` was parsed before. Content without a `key` is always parsed.
+    pub fn get_or_parse(
+        &self,
+        key: Option<ObjectReference>,
+        content: &[u8],
+        parse: impl FnOnce(&[u8]) -> Result<Vec<ParsedOp>, OdeError>,
+    ) -> Result<Arc<Vec<ParsedOp>>, OdeError> {
+        if let Some(ops) = key.and_then(|key| self.lock().get(&key).cloned()) {
+            return Ok(ops);
+        }
+        self.parses.fetch_add(1, Ordering::Relaxed);
+        let ops = Arc::new(parse(content)?);
+        if let Some(key) = key {
+            self.lock().insert(key, ops.clone());
+        }
+        Ok(ops)
+    }
+
+    /// How many content streams were actually parsed.
+    pub fn parses(&self) -> usize {
+        self.parses.load(Ordering::Relaxed)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<ObjectReference, Arc<Vec<ParsedOp>>>> {
+        self.ops.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Render parsed operators back to content-stream syntax, one per line.
+///
+/// Used to inspect what the renderer sees; `TJ` arrays appear with their
+/// strings concatenated and kerning dropped, as the parser keeps them.
+pub fn format_ops(ops: &[ParsedOp]) -> String {
+    let mut out = String::new();
+    for op in ops {
+        // Only Tf, Do, cs and CS keep a name, and it always leads
+        if let Some(ref name) = op.font_name {
+            out.push_str(&format!("/{} ", name));
+        }
+        for operand in &op.operands {
+            out.push_str(&format!("{} ", operand));
+        }
+        if let Some(ref raw) = op.text_raw {
+            let literal = format_literal(raw);
+            if op.operator == ContentOp::TJ {
+                out.push_str(&format!("[{}] ", literal));
+            } else {
+                out.push_str(&literal);
+                out.push(' ');
+            }
+        }
+        out.push_str(op.operator.name());
+        out.push('\n');
+    }
+    out
+}
+
+fn format_literal(bytes: &[u8]) -> String {
+    let mut literal = String::from("(");
+    for &b in bytes {
+        match b {
+            b'(' | b')' | b'\\' => {
+                literal.push('\\');
+                literal.push(b as char);
+            }
+            0x20..=0x7e => literal.push(b as char),
+            _ => literal.push_str(&format!("\\{:03o}", b)),
+        }
+    }
+    literal.push(')');
+    literal
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -427,15 +555,34 @@ mod tests {
     #[test]
     fn test_parse_simple_text() {
         let stream = b"BT\n/F1 12 Tf\n100 700 Td\n(Hello) Tj\nET";
-        let mut parser = ContentStreamParser::new(stream, None).unwrap();
+        let mut parser = ContentStreamParser::new(stream);
         let ops = parser.parse().unwrap();
         assert!(!ops.is_empty());
     }
 
+    #[test]
+    fn test_compressed_stream_held_to_the_given_limits() {
+        use flate2::write::ZlibEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        let stream = b"BT\n/F1 12 Tf\n(Hello) Tj\nET";
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(stream).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let default = ZipBombDetector::default();
+        let parser = ContentStreamParser::compressed(&compressed, "FlateDecode", &default);
+        assert_eq!(parser.unwrap().parse().unwrap().len(), 4);
+
+        let lowered = ZipBombDetector::default().with_max_size(stream.len() - 1);
+        assert!(ContentStreamParser::compressed(&compressed, "FlateDecode", &lowered).is_err());
+    }
+
     #[test]
     fn test_parse_empty_stream() {
         let stream = b"";
-        let mut parser = ContentStreamParser::new(stream, None).unwrap();
+        let mut parser = ContentStreamParser::new(stream);
         let ops = parser.parse().unwrap();
         assert!(ops.is_empty());
     }
@@ -443,14 +590,14 @@ mod tests {
     #[test]
     fn test_parse_whitespace() {
         let stream = b"BT  /F1  12  Tf 100  700  Td (Hello)  Tj  ET";
-        let mut parser = ContentStreamParser::new(stream, None).unwrap();
+        let mut parser = ContentStreamParser::new(stream);
         assert!(parser.parse().is_ok());
     }
 
     #[test]
     fn test_parse_literal_string() {
         let stream = b"(Hello World)";
-        let mut parser = ContentStreamParser::new(stream, None).unwrap();
+        let mut parser = ContentStreamParser::new(stream);
         let ops = parser.parse().unwrap();
         assert_eq!(ops.len(), 0);
     }
@@ -458,9 +605,59 @@ mod tests {
     #[test]
     fn test_parse_operands() {
         let stream = b"100 200 300 Td";
-        let mut parser = ContentStreamParser::new(stream, None).unwrap();
+        let mut parser = ContentStreamParser::new(stream);
         let ops = parser.parse().unwrap();
         assert_eq!(ops.len(), 1);
         assert_eq!(ops[0].operands, vec![100.0, 200.0, 300.0]);
     }
+
+    #[test]
+    fn test_format_ops_round_trips_operators() {
+        let stream = b"BT /F1 12 Tf 72 700 Td (Hello \\(world\\)) Tj [(A) -120 (B)] TJ ET 0.5 g";
+        let mut parser = ContentStreamParser::new(stream);
+        let ops = parser.parse().unwrap();
+        assert_eq!(
+            format_ops(&ops),
+            "BT\n/F1 12 Tf\n72 700 Td\n(Hello \\(world\\)) Tj\n[(AB)] TJ\nET\n0.5 g\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_next_line_show_operators() {
+        let stream = b"14 TL (One) Tj T* (Two)' 2 1(Three)\"";
+        let mut parser = ContentStreamParser::new(stream);
+        let ops = parser.parse().unwrap();
+        assert_eq!(
+            format_ops(&ops),
+            "14 TL\n(One) Tj\nT*\n(Two) '\n2 1 (Three) \"\n"
+        );
+    }
+
+    #[test]
+    fn test_format_ops_escapes_binary_strings() {
+        let mut parser = ContentStreamParser::new(b"<0041FF> Tj");
+        let ops = parser.parse().unwrap();
+        assert_eq!(format_ops(&ops), "(\\000A\\377) Tj\n");
+    }
+
+    #[test]
+    fn test_bdc_keeps_the_lang_of_its_property_list() {
+        let stream = b"/Span<</Lang(fr-CA)>>BDC (Bonjour) Tj EMC /OC /oc1 BDC EMC";
+        let ops = ContentStreamParser::new(stream).parse().unwrap();
+        let bdc: Vec<_> = ops.iter().filter(|op| op.operator == ContentOp::BDC).collect();
+        assert_eq!(bdc[0].lang.as_deref(), Some("fr-CA"));
+        assert_eq!(bdc[0].font_name.as_deref(), Some("Span"));
+        assert_eq!(bdc[0].mcid, None);
+        assert_eq!(ops[1].text.as_deref(), Some("Bonjour"));
+        assert_eq!(bdc[1].lang, None);
+    }
+
+    #[test]
+    fn test_bdc_keeps_the_mcid_of_its_property_list() {
+        let stream = b"/P <</MCID 3>> BDC (Text) Tj EMC /Artifact BMC EMC";
+        let ops = ContentStreamParser::new(stream).parse().unwrap();
+        assert_eq!(ops[0].operator, ContentOp::BDC);
+        assert_eq!(ops[0].mcid, Some(3));
+        assert!(ops[1..].iter().all(|op| op.mcid.is_none()));
+    }
 }
@@ -0,0 +1,596 @@
+use super::{Dictionary, PdfObject};
+use crate::error::{OdeError, OdeResult, ParseErrorKind};
+use crate::util::ZipBombDetector;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// The `/DecodeParms` dictionary that accompanies a filter (empty when absent).
+pub type DecodeParms = Dictionary;
+
+/// A stream decoder: takes the encoded bytes and the filter's parameters.
+pub type FilterDecoder = dyn Fn(&[u8], &DecodeParms) -> OdeResult<Vec<u8>> + Send + Sync;
+
+/// Custom stream filter decoders keyed by filter name.
+///
+/// Registered decoders are consulted before the built-in filters, so they can
+/// both add vendor-specific filters and override standard ones.
+#[derive(Clone, Default)]
+pub struct FilterRegistry {
+    decoders: HashMap<String, Arc<FilterDecoder>>,
+}
+
+impl FilterRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<F>(&mut self, name: impl Into<String>, decoder: F)
+    where
+        F: Fn(&[u8], &DecodeParms) -> OdeResult<Vec<u8>> + Send + Sync + 'static,
+    {
+        self.decoders.insert(name.into(), Arc::new(decoder));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&FilterDecoder> {
+        self.decoders.get(name).map(|d| d.as_ref())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.decoders.is_empty()
+    }
+
+    /// Registered filter names, sorted.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.decoders.keys().map(|k| k.as_str()).collect();
+        names.sort_unstable();
+        names
+    }
+}
+
+impl fmt::Debug for FilterRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FilterRegistry")
+            .field("filters", &self.names())
+            .finish()
+    }
+}
+
+/// Run a stream's `/Filter` chain over its raw bytes.
+///
+/// Filters are applied in order. Decoding stops at the first filter that is
+/// neither registered nor built in (e.g. `DCTDecode`, which images keep
+/// encoded), or that fails, and the data decoded so far is returned. The
+/// built-in decompressors fail once their output breaks `limits`.
+pub fn decode_stream(
+    data: Vec<u8>,
+    dict: &Dictionary,
+    registry: Option<&FilterRegistry>,
+    limits: &ZipBombDetector,
+) -> Vec<u8> {
+    let mut data = data;
+    for (filter, parms) in filter_chain(dict) {
+        let decoded = if let Some(custom) = registry.and_then(|r| r.get(filter)) {
+            custom(&data, parms)
+        } else {
+            match decode_builtin(filter, &data, parms, limits) {
+                Some(decoded) => decoded,
+                None => return data,
+            }
+        };
+        match decoded {
+            Ok(out) => data = out,
+            Err(_) => return data,
+        }
+    }
+    data
+}
+
+/// Run a stream's `/Filter` chain with the built-in filters only, for
+/// streams the parser reads itself, such as XRef streams. Fails with
+/// `UnsupportedFilter` when one of the filters isn't built in.
+pub(crate) fn decode_with_builtins(
+    data: &[u8],
+    dict: &Dictionary,
+    limits: &ZipBombDetector,
+) -> OdeResult<Vec<u8>> {
+    let mut data = data.to_vec();
+    for (filter, parms) in filter_chain(dict) {
+        data = decode_builtin(filter, &data, parms, limits).ok_or_else(|| {
+            OdeError::parse(
+                ParseErrorKind::UnsupportedFilter(filter.to_string()),
+                format!("Unsupported filter {}", filter),
+            )
+        })??;
+    }
+    Ok(data)
+}
+
+/// A stream's filters paired with their `/DecodeParms`.
+fn filter_chain(dict: &Dictionary) -> Vec<(&str, &DecodeParms)> {
+    static NO_PARMS: DecodeParms = Dictionary {
+        entries: Vec::new(),
+    };
+    let filters: Vec<&str> = match dict.get("Filter") {
+        Some(PdfObject::Name(name)) => vec![name.as_str()],
+        Some(PdfObject::Array(arr)) => arr.iter().filter_map(|f| f.as_name()).collect(),
+        _ => Vec::new(),
+    };
+    let parms: Vec<Option<&Dictionary>> = match dict.get("DecodeParms") {
+        Some(PdfObject::Dictionary(d)) => vec![Some(d)],
+        Some(PdfObject::Array(arr)) => arr
+            .iter()
+            .map(|p| match p {
+                PdfObject::Dictionary(d) => Some(d),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    filters
+        .into_iter()
+        .enumerate()
+        .map(|(i, filter)| (filter, parms.get(i).copied().flatten().unwrap_or(&NO_PARMS)))
+        .collect()
+}
+
+/// Decode `data` with one of the built-in filters, or `None` when `filter`
+/// isn't one of them. Abbreviated names from inline images are accepted.
+pub(crate) fn decode_builtin(
+    filter: &str,
+    data: &[u8],
+    parms: &DecodeParms,
+    limits: &ZipBombDetector,
+) -> Option<OdeResult<Vec<u8>>> {
+    let decoded = match filter {
+        "FlateDecode" | "Fl" => limits.inflate(data).and_then(|d| apply_predictor(d, parms)),
+        "LZWDecode" | "LZW" => {
+            // /EarlyChange 1, the default, widens codes one entry early
+            let early_change = parms.get("EarlyChange").and_then(|e| e.as_integer()) != Some(0);
+            decode_lzw(data, early_change, limits).and_then(|d| apply_predictor(d, parms))
+        }
+        "ASCII85Decode" | "A85" => decode_ascii85(data),
+        "ASCIIHexDecode" | "AHx" => decode_ascii_hex(data),
+        _ => return None,
+    };
+    Some(decoded)
+}
+
+/// Undo the `/Predictor` a Flate or LZW stream's data was encoded with:
+/// 2 for TIFF, 10 and up for PNG, where each row names its own filter.
+fn apply_predictor(data: Vec<u8>, parms: &DecodeParms) -> OdeResult<Vec<u8>> {
+    let int = |key: &str, default: i64| {
+        parms
+            .get(key)
+            .and_then(|v| v.as_integer())
+            .unwrap_or(default)
+    };
+    let predictor = int("Predictor", 1);
+    if predictor < 2 {
+        return Ok(data);
+    }
+    let colors = int("Colors", 1).clamp(1, 32) as usize;
+    let bits = int("BitsPerComponent", 8);
+    if ![1, 2, 4, 8, 16].contains(&bits) {
+        return Err(OdeError::parse(
+            ParseErrorKind::CorruptStream,
+            format!("Predictor: invalid /BitsPerComponent {}", bits),
+        ));
+    }
+    let bits = bits as usize;
+    let columns = int("Columns", 1).clamp(1, 1 << 20) as usize;
+    let pixel_bytes = (colors * bits).div_ceil(8);
+    let row_bytes = (colors * bits * columns).div_ceil(8);
+
+    match predictor {
+        2 => Ok(tiff_predictor(data, row_bytes, colors, bits)),
+        10..=15 => Ok(png_predictor(&data, row_bytes, pixel_bytes)),
+        _ => Err(OdeError::parse(
+            ParseErrorKind::CorruptStream,
+            format!("Predictor: unknown predictor {}", predictor),
+        )),
+    }
+}
+
+/// TIFF predictor 2: each sample is stored as its difference from the same
+/// component of the pixel to its left.
+fn tiff_predictor(mut data: Vec<u8>, row_bytes: usize, colors: usize, bits: usize) -> Vec<u8> {
+    for row in data.chunks_mut(row_bytes) {
+        match bits {
+            8 => {
+                for i in colors..row.len() {
+                    row[i] = row[i].wrapping_add(row[i - colors]);
+                }
+            }
+            16 => {
+                for i in (2 * colors..row.len() - row.len() % 2).step_by(2) {
+                    let left = u16::from_be_bytes([row[i - 2 * colors], row[i - 2 * colors + 1]]);
+                    let value = u16::from_be_bytes([row[i], row[i + 1]]).wrapping_add(left);
+                    row[i..i + 2].copy_from_slice(&value.to_be_bytes());
+                }
+            }
+            _ => {
+                let mask = (1u8 << bits) - 1;
+                let per_byte = 8 / bits;
+                let sample = |row: &[u8], i: usize| {
+                    (row[i / per_byte] >> (8 - bits * (i % per_byte + 1))) & mask
+                };
+                for i in colors..row.len() * per_byte {
+                    let value = sample(row, i).wrapping_add(sample(row, i - colors)) & mask;
+                    let shift = 8 - bits * (i % per_byte + 1);
+                    let byte = &mut row[i / per_byte];
+                    *byte = (*byte & !(mask << shift)) | (value << shift);
+                }
+            }
+        }
+    }
+    data
+}
+
+/// PNG predictors: every row starts with a byte naming the filter its bytes
+/// were encoded with, relative to the byte a pixel to the left and the row
+/// above.
+fn png_predictor(data: &[u8], row_bytes: usize, pixel_bytes: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut previous = vec![0u8; row_bytes];
+    for encoded in data.chunks(row_bytes + 1) {
+        let (filter, encoded) = (encoded[0], &encoded[1..]);
+        let mut row = encoded.to_vec();
+        for i in 0..row.len() {
+            let left = if i >= pixel_bytes {
+                row[i - pixel_bytes]
+            } else {
+                0
+            };
+            let up = previous[i];
+            let up_left = if i >= pixel_bytes {
+                previous[i - pixel_bytes]
+            } else {
+                0
+            };
+            let prediction = match filter {
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => paeth(left, up, up_left),
+                _ => 0,
+            };
+            row[i] = row[i].wrapping_add(prediction);
+        }
+        previous[..row.len()].copy_from_slice(&row);
+        out.extend_from_slice(&row);
+    }
+    out
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = left as i16 + up as i16 - up_left as i16;
+    let (to_left, to_up, to_up_left) = (
+        (estimate - left as i16).abs(),
+        (estimate - up as i16).abs(),
+        (estimate - up_left as i16).abs(),
+    );
+    if to_left <= to_up && to_left <= to_up_left {
+        left
+    } else if to_up <= to_up_left {
+        up
+    } else {
+        up_left
+    }
+}
+
+const LZW_CLEAR_TABLE: usize = 256;
+const LZW_END_OF_DATA: usize = 257;
+const LZW_MAX_ENTRIES: usize = 4096;
+
+/// `LZWDecode`: 9- to 12-bit codes, most significant bit first.
+fn decode_lzw(data: &[u8], early_change: bool, limits: &ZipBombDetector) -> OdeResult<Vec<u8>> {
+    let mut table: Vec<Vec<u8>> = (0..=255u8).map(|b| vec![b]).collect();
+    table.extend([Vec::new(), Vec::new()]);
+    let mut out = Vec::new();
+    let mut previous: Option<Vec<u8>> = None;
+    let mut width = 9;
+    let (mut bits, mut bit_count) = (0u32, 0);
+    let mut bytes = data.iter();
+
+    loop {
+        while bit_count < width {
+            let Some(byte) = bytes.next() else {
+                // Data may end without an end-of-data code
+                return Ok(out);
+            };
+            bits = (bits << 8) | *byte as u32;
+            bit_count += 8;
+        }
+        let code = ((bits >> (bit_count - width)) & ((1 << width) - 1)) as usize;
+        bit_count -= width;
+
+        match code {
+            LZW_CLEAR_TABLE => {
+                table.truncate(258);
+                previous = None;
+                width = 9;
+                continue;
+            }
+            LZW_END_OF_DATA => return Ok(out),
+            _ => {}
+        }
+        let entry = match (table.get(code), &previous) {
+            (Some(entry), _) => entry.clone(),
+            // The one code that can be used before it is defined
+            (None, Some(previous)) if code == table.len() => {
+                let mut entry = previous.clone();
+                entry.push(previous[0]);
+                entry
+            }
+            _ => {
+                return Err(OdeError::parse(
+                    ParseErrorKind::CorruptStream,
+                    format!("LZWDecode: invalid code {}", code),
+                ))
+            }
+        };
+        out.extend_from_slice(&entry);
+        limits.check_output(data.len(), out.len())?;
+        if let Some(mut previous) = previous.take() {
+            if table.len() < LZW_MAX_ENTRIES {
+                previous.push(entry[0]);
+                table.push(previous);
+            }
+        }
+        previous = Some(entry);
+
+        let next = table.len() + early_change as usize;
+        width = match next {
+            0..=511 => 9,
+            512..=1023 => 10,
+            1024..=2047 => 11,
+            _ => 12,
+        };
+    }
+}
+
+/// `ASCII85Decode`: base-85 groups of five characters, `z` for four zero
+/// bytes, ending at `~>`.
+fn decode_ascii85(data: &[u8]) -> OdeResult<Vec<u8>> {
+    let data = data.strip_prefix(b"<~").unwrap_or(data);
+    let mut out = Vec::new();
+    let mut group = [0u8; 5];
+    let mut len = 0;
+    for &c in data {
+        match c {
+            b'~' => break,
+            b'z' if len == 0 => out.extend_from_slice(&[0; 4]),
+            b'!'..=b'u' => {
+                group[len] = c - b'!';
+                len += 1;
+                if len == 5 {
+                    out.extend_from_slice(&ascii85_group(&group)?);
+                    len = 0;
+                }
+            }
+            c if c.is_ascii_whitespace() => {}
+            c => {
+                return Err(OdeError::parse(
+                    ParseErrorKind::CorruptStream,
+                    format!("ASCII85Decode: invalid character 0x{:02x}", c),
+                ))
+            }
+        }
+    }
+    if len == 1 {
+        return Err(OdeError::parse(
+            ParseErrorKind::CorruptStream,
+            "ASCII85Decode: truncated final group",
+        ));
+    }
+    if len > 1 {
+        // A short final group is padded with the highest digit
+        group[len..].fill(84);
+        out.extend_from_slice(&ascii85_group(&group)?[..len - 1]);
+    }
+    Ok(out)
+}
+
+fn ascii85_group(digits: &[u8; 5]) -> OdeResult<[u8; 4]> {
+    let value = digits.iter().fold(0u64, |value, d| value * 85 + *d as u64);
+    let value = u32::try_from(value).map_err(|_| {
+        OdeError::parse(
+            ParseErrorKind::CorruptStream,
+            "ASCII85Decode: group out of range",
+        )
+    })?;
+    Ok(value.to_be_bytes())
+}
+
+/// `ASCIIHexDecode`: pairs of hex digits, ending at `>`. A final odd digit
+/// is read as if followed by 0.
+fn decode_ascii_hex(data: &[u8]) -> OdeResult<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut high: Option<u8> = None;
+    for &c in data {
+        let digit = match c {
+            b'>' => break,
+            c if c.is_ascii_whitespace() => continue,
+            c => (c as char).to_digit(16).ok_or_else(|| {
+                OdeError::parse(
+                    ParseErrorKind::CorruptStream,
+                    format!("ASCIIHexDecode: invalid character 0x{:02x}", c),
+                )
+            })? as u8,
+        };
+        match high.take() {
+            Some(high) => out.push(high << 4 | digit),
+            None => high = Some(digit),
+        }
+    }
+    out.extend(high.map(|high| high << 4));
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream_dict(entries: Vec<(&str, PdfObject)>) -> Dictionary {
+        Dictionary {
+            entries: entries
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+        }
+    }
+
+    fn reverse_registry() -> FilterRegistry {
+        let mut registry = FilterRegistry::new();
+        registry.register("ReverseDecode", |data: &[u8], _parms: &DecodeParms| {
+            Ok(data.iter().rev().copied().collect())
+        });
+        registry
+    }
+
+    fn limits() -> ZipBombDetector {
+        ZipBombDetector::default()
+    }
+
+    #[test]
+    fn test_custom_filter_decodes_stream() {
+        let dict = stream_dict(vec![(
+            "Filter",
+            PdfObject::Name("ReverseDecode".to_string()),
+        )]);
+        let decoded = decode_stream(b"olleh".to_vec(), &dict, Some(&reverse_registry()), &limits());
+        assert_eq!(decoded, b"hello");
+    }
+
+    #[test]
+    fn test_custom_filter_receives_decode_parms() {
+        let mut registry = FilterRegistry::new();
+        registry.register("XorDecode", |data: &[u8], parms: &DecodeParms| {
+            let key = parms
+                .get("Key")
+                .and_then(|k| k.as_integer())
+                .ok_or_else(|| {
+                    OdeError::parse(ParseErrorKind::CorruptStream, "XorDecode needs /Key")
+                })?;
+            Ok(data.iter().map(|b| b ^ key as u8).collect())
+        });
+        let dict = stream_dict(vec![
+            (
+                "Filter",
+                PdfObject::Array(vec![PdfObject::Name("XorDecode".to_string())]),
+            ),
+            (
+                "DecodeParms",
+                PdfObject::Array(vec![PdfObject::Dictionary(stream_dict(vec![(
+                    "Key",
+                    PdfObject::Integer(0x20),
+                )]))]),
+            ),
+        ]);
+        assert_eq!(decode_stream(b"HI".to_vec(), &dict, Some(&registry), &limits()), b"hi");
+    }
+
+    #[test]
+    fn test_custom_filter_chained_with_builtin() {
+        use flate2::write::ZlibEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"olleh").unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let dict = stream_dict(vec![(
+            "Filter",
+            PdfObject::Array(vec![
+                PdfObject::Name("FlateDecode".to_string()),
+                PdfObject::Name("ReverseDecode".to_string()),
+            ]),
+        )]);
+        assert_eq!(
+            decode_stream(compressed, &dict, Some(&reverse_registry()), &limits()),
+            b"hello"
+        );
+    }
+
+    #[test]
+    fn test_lzw_decodes_with_early_change() {
+        // The example from the PDF specification
+        let encoded = [0x80, 0x0B, 0x60, 0x50, 0x22, 0x0C, 0x0C, 0x85, 0x01];
+        let dict = stream_dict(vec![("Filter", PdfObject::Name("LZWDecode".to_string()))]);
+        assert_eq!(decode_stream(encoded.to_vec(), &dict, None, &limits()), b"-----A---B");
+    }
+
+    #[test]
+    fn test_ascii_filters_decode() {
+        let empty = DecodeParms {
+            entries: Vec::new(),
+        };
+        let decoded = decode_builtin("ASCII85Decode", b"87cURD_*#-6q/=~>", &empty, &limits());
+        assert_eq!(decoded.unwrap().unwrap(), b"Hello, PDF!");
+        let decoded = decode_builtin("A85", b"<~z@:B~>", &empty, &limits());
+        assert_eq!(decoded.unwrap().unwrap(), b"\0\0\0\0ab");
+        let decoded = decode_builtin("ASCIIHexDecode", b"48 65\n6c6C 6f7>", &empty, &limits());
+        assert_eq!(decoded.unwrap().unwrap(), b"Hellop");
+    }
+
+    #[test]
+    fn test_ascii85_then_flate_chain() {
+        let dict = stream_dict(vec![(
+            "Filter",
+            PdfObject::Array(vec![
+                PdfObject::Name("ASCII85Decode".to_string()),
+                PdfObject::Name("FlateDecode".to_string()),
+            ]),
+        )]);
+        let encoded = b"Garg^;:'MC<%p.,#Y@tAnAs*Y#g#t`%06Gm5Qu3~>".to_vec();
+        assert_eq!(
+            decode_stream(encoded, &dict, None, &limits()),
+            b"BT /F1 12 Tf (Hi) Tj ET"
+        );
+    }
+
+    #[test]
+    fn test_png_up_predictor_after_flate() {
+        use flate2::write::ZlibEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        // Two rows of three bytes: the first with None, the second with Up
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&[0, 1, 2, 3, 2, 1, 1, 1]).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let dict = stream_dict(vec![
+            ("Filter", PdfObject::Name("FlateDecode".to_string())),
+            (
+                "DecodeParms",
+                PdfObject::Dictionary(stream_dict(vec![
+                    ("Predictor", PdfObject::Integer(12)),
+                    ("Columns", PdfObject::Integer(3)),
+                ])),
+            ),
+        ]);
+        assert_eq!(decode_stream(compressed, &dict, None, &limits()), [1, 2, 3, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_tiff_predictor_adds_the_pixel_to_the_left() {
+        let parms = stream_dict(vec![
+            ("Predictor", PdfObject::Integer(2)),
+            ("Colors", PdfObject::Integer(2)),
+            ("Columns", PdfObject::Integer(3)),
+        ]);
+        let decoded = apply_predictor(vec![10, 20, 1, 2, 1, 2], &parms).unwrap();
+        assert_eq!(decoded, [10, 20, 11, 22, 12, 24]);
+    }
+
+    #[test]
+    fn test_unknown_filter_leaves_data_encoded() {
+        let dict = stream_dict(vec![("Filter", PdfObject::Name("DCTDecode".to_string()))]);
+        assert_eq!(
+            decode_stream(vec![0xFF, 0xD8], &dict, None, &limits()),
+            vec![0xFF, 0xD8]
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use crate::config::ConversionConfig;
+
+/// How images are sized and encoded for the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageOptions {
+    /// Longest side in pixels an image keeps; larger ones are scaled down.
+    pub max_dimension: Option<u32>,
+    /// JPEG quality, 1 to 100, for opaque images that were scaled down.
+    pub jpeg_quality: u8,
+}
+
+impl Default for ImageOptions {
+    fn default() -> Self {
+        Self {
+            max_dimension: None,
+            jpeg_quality: 85,
+        }
+    }
+}
+
+impl ImageOptions {
+    pub fn from_config(config: &ConversionConfig) -> Self {
+        Self {
+            max_dimension: config.max_image_dimension,
+            jpeg_quality: config.image_quality.clamp(1, 100),
+        }
+    }
+
+    /// Encode 8-bit pixels with 1 (gray), 3 (RGB) or 4 (RGBA) channels.
+    ///
+    /// An image over `max_dimension` is scaled down to fit and, unless it
+    /// has an alpha channel, becomes a JPEG. Anything else stays a lossless
+    /// PNG. Returns the file, its mime type and its size in pixels.
+    pub fn encode_pixels(
+        &self,
+        pixels: &[u8],
+        width: u32,
+        height: u32,
+        channels: u8,
+    ) -> (Vec<u8>, &'static str, u32, u32) {
+        let complete = pixels.len() >= width as usize * height as usize * channels as usize;
+        let scaled = self
+            .max_dimension
+            .filter(|_| complete)
+            .and_then(|max| downscale(pixels, width, height, channels, max));
+        if let Some((pixels, width, height)) = &scaled {
+            if let Some(jpeg) = self.encode_jpeg(pixels, *width, *height, channels) {
+                return (jpeg, "image/jpeg", *width, *height);
+            }
+        }
+        let (pixels, width, height) = match &scaled {
+            Some((pixels, width, height)) => (pixels.as_slice(), *width, *height),
+            None => (pixels, width, height),
+        };
+        let png = super::page_tree::encode_raw_pixels_as_png(pixels, width, height, channels);
+        (png, "image/png", width, height)
+    }
+
+    /// A `DCTDecode` image scaled down to `max_dimension` and re-encoded,
+    /// or `None` when it fits already or can't be decoded. CMYK JPEGs are
+    /// left alone, since how their inks are stored varies by producer.
+    pub fn shrink_jpeg(&self, data: &[u8]) -> Option<(Vec<u8>, u32, u32)> {
+        use jpeg_decoder::{Decoder, PixelFormat};
+
+        let max = self.max_dimension?;
+        let mut decoder = Decoder::new(data);
+        decoder.read_info().ok()?;
+        let info = decoder.info()?;
+        let channels = match info.pixel_format {
+            PixelFormat::L8 => 1,
+            PixelFormat::RGB24 => 3,
+            PixelFormat::L16 | PixelFormat::CMYK32 => return None,
+        };
+        let (width, height) = (u32::from(info.width), u32::from(info.height));
+        if width.max(height) <= max {
+            return None;
+        }
+        let pixels = decoder.decode().ok()?;
+        let (pixels, width, height) = downscale(&pixels, width, height, channels, max)?;
+        let jpeg = self.encode_jpeg(&pixels, width, height, channels)?;
+        Some((jpeg, width, height))
+    }
+
+    fn encode_jpeg(&self, pixels: &[u8], width: u32, height: u32, channels: u8) -> Option<Vec<u8>> {
+        use jpeg_encoder::{ColorType, Encoder};
+
+        let color_type = match channels {
+            1 => ColorType::Luma,
+            3 => ColorType::Rgb,
+            _ => return None,
+        };
+        let (width, height) = (u16::try_from(width).ok()?, u16::try_from(height).ok()?);
+        let mut jpeg = Vec::new();
+        Encoder::new(&mut jpeg, self.jpeg_quality)
+            .encode(pixels, width, height, color_type)
+            .ok()?;
+        Some(jpeg)
+    }
+}
+
+/// Scale an image down so its longest side is `max` pixels, averaging the
+/// source pixels each output pixel covers. `None` when it fits already.
+fn downscale(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    channels: u8,
+    max: u32,
+) -> Option<(Vec<u8>, u32, u32)> {
+    let longest = width.max(height);
+    if max == 0 || longest <= max {
+        return None;
+    }
+    let fit = |side: u32| ((side as u64 * max as u64 / longest as u64) as u32).max(1);
+    let (new_width, new_height) = (fit(width), fit(height));
+    // The source span an output pixel covers, at least one pixel wide
+    let span = |i: u32, from: u32, to: u32| {
+        let start = (i as u64 * from as u64 / to as u64) as usize;
+        let end = ((i as u64 + 1) * from as u64 / to as u64) as usize;
+        start..end.max(start + 1)
+    };
+
+    let channels = channels as usize;
+    let row_bytes = width as usize * channels;
+    let mut scaled = Vec::with_capacity(new_width as usize * new_height as usize * channels);
+    let mut sums = vec![0u64; channels];
+    for y in 0..new_height {
+        let rows = span(y, height, new_height);
+        for x in 0..new_width {
+            let columns = span(x, width, new_width);
+            sums.iter_mut().for_each(|sum| *sum = 0);
+            for row in rows.clone() {
+                let start = row * row_bytes + columns.start * channels;
+                let end = row * row_bytes + columns.end * channels;
+                for pixel in pixels[start..end].chunks_exact(channels) {
+                    for (sum, &sample) in sums.iter_mut().zip(pixel) {
+                        *sum += sample as u64;
+                    }
+                }
+            }
+            let count = (rows.len() * columns.len()) as u64;
+            scaled.extend(sums.iter().map(|sum| ((sum + count / 2) / count) as u8));
+        }
+    }
+    Some((scaled, new_width, new_height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_downscale_averages_the_pixels_each_output_pixel_covers() {
+        // 4x2 gray: a black and a white half
+        let pixels = [0, 0, 255, 255, 0, 0, 255, 255];
+        let (scaled, width, height) = downscale(&pixels, 4, 2, 1, 2).unwrap();
+        assert_eq!((width, height), (2, 1));
+        assert_eq!(scaled, [0, 255]);
+        assert!(downscale(&pixels, 4, 2, 1, 4).is_none());
+    }
+}
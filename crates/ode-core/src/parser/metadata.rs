@@ -0,0 +1,154 @@
+use super::{Dictionary, PdfRefResolver};
+use crate::util::parse_pdf_date;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// Document properties from the trailer's `/Info` dictionary, with gaps
+/// filled from the Dublin Core fields of the catalog's XMP `/Metadata`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DocumentMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    pub keywords: Option<String>,
+    /// The application that created the original document.
+    pub creator: Option<String>,
+    /// The application that converted it to PDF.
+    pub producer: Option<String>,
+    pub creation_date: Option<DateTime<Utc>>,
+    pub modification_date: Option<DateTime<Utc>>,
+}
+
+impl DocumentMetadata {
+    pub fn from_info(info: &Dictionary, resolver: &PdfRefResolver) -> Self {
+        let text = |key: &str| {
+            let value = info.get(key)?;
+            let value = match value.as_reference() {
+                Some(r) => resolver.dereference(r)?,
+                None => value.clone(),
+            };
+            let text = value.as_text_string()?;
+            let text = text.trim();
+            (!text.is_empty()).then(|| text.to_string())
+        };
+        Self {
+            title: text("Title"),
+            author: text("Author"),
+            subject: text("Subject"),
+            keywords: text("Keywords"),
+            creator: text("Creator"),
+            producer: text("Producer"),
+            creation_date: text("CreationDate").and_then(|d| parse_pdf_date(&d)),
+            modification_date: text("ModDate").and_then(|d| parse_pdf_date(&d)),
+        }
+    }
+
+    /// Fill fields `/Info` left empty from an XMP packet's `dc:title`,
+    /// `dc:creator`, `dc:description` and `dc:subject`. Of several
+    /// alternatives (`rdf:Alt`) the first is taken, which XMP writers make
+    /// the `x-default` one; several authors or subjects are joined.
+    pub fn fill_from_xmp(&mut self, xmp: &str) {
+        let field = |tag: &str, separator: Option<&str>| {
+            let items = xmp_items(&xmp[xmp_element(xmp, tag)?]);
+            match separator {
+                Some(separator) => (!items.is_empty()).then(|| items.join(separator)),
+                None => items.into_iter().next(),
+            }
+        };
+        self.title = self.title.take().or_else(|| field("dc:title", None));
+        self.author = self
+            .author
+            .take()
+            .or_else(|| field("dc:creator", Some("; ")));
+        self.subject = self
+            .subject
+            .take()
+            .or_else(|| field("dc:description", None));
+        self.keywords = self
+            .keywords
+            .take()
+            .or_else(|| field("dc:subject", Some(", ")));
+    }
+}
+
+/// Where the content of the first non-empty `<tag>` element in `xml` lies.
+fn xmp_element(xml: &str, tag: &str) -> Option<Range<usize>> {
+    let open = format!("<{}", tag);
+    let close = format!("</{}>", tag);
+    let mut search = 0;
+    while let Some(found) = xml[search..].find(&open) {
+        let start = search + found + open.len();
+        search = start;
+        // Skip longer names sharing the prefix, like dc:titles
+        if !xml[start..].starts_with(|c: char| c == '>' || c.is_whitespace()) {
+            continue;
+        }
+        let content_start = start + xml[start..].find('>')? + 1;
+        if xml[..content_start].ends_with("/>") {
+            continue;
+        }
+        let content_end = content_start + xml[content_start..].find(&close)?;
+        return Some(content_start..content_end);
+    }
+    None
+}
+
+/// The `rdf:li` entries of an XMP array, or the element's own text when it
+/// holds a simple value.
+fn xmp_items(content: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut rest = content;
+    while let Some(item) = xmp_element(rest, "rdf:li") {
+        let text = unescape_xml(rest[item.clone()].trim());
+        if !text.is_empty() {
+            items.push(text);
+        }
+        rest = &rest[item.end..];
+    }
+    if items.is_empty() && !content.contains('<') {
+        let text = unescape_xml(content.trim());
+        if !text.is_empty() {
+            items.push(text);
+        }
+    }
+    items
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xmp_fills_fields_missing_from_info() {
+        let xmp = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF>
+<rdf:Description rdf:about="" xmlns:dc="http://purl.org/dc/elements/1.1/">
+<dc:title><rdf:Alt><rdf:li xml:lang="x-default">Annual &amp; Final</rdf:li>
+<rdf:li xml:lang="fi">Vuosikertomus</rdf:li></rdf:Alt></dc:title>
+<dc:creator><rdf:Seq><rdf:li>Ada</rdf:li><rdf:li>Grace</rdf:li></rdf:Seq></dc:creator>
+<dc:subject><rdf:Bag><rdf:li>finance</rdf:li><rdf:li>2023</rdf:li></rdf:Bag></dc:subject>
+</rdf:Description></rdf:RDF></x:xmpmeta>"#;
+        let mut metadata = DocumentMetadata {
+            title: Some("From Info".to_string()),
+            ..Default::default()
+        };
+        metadata.fill_from_xmp(xmp);
+
+        assert_eq!(metadata.title.as_deref(), Some("From Info"));
+        assert_eq!(metadata.author.as_deref(), Some("Ada; Grace"));
+        assert_eq!(metadata.keywords.as_deref(), Some("finance, 2023"));
+        assert_eq!(metadata.subject, None);
+
+        let mut metadata = DocumentMetadata::default();
+        metadata.fill_from_xmp(xmp);
+        assert_eq!(metadata.title.as_deref(), Some("Annual & Final"));
+    }
+}
@@ -1,15 +1,37 @@
-use crate::error::OdeError;
-use flate2::read::ZlibDecoder;
-use std::io::Read;
-
+use crate::config::{ConversionConfig, UnmappedGlyph};
+use crate::error::{OdeError, ParseErrorKind};
+use crate::fonts::GlyphWidths;
+use crate::util::math::TransformMatrix;
+use crate::util::ZipBombDetector;
+
+pub mod annotations;
+pub mod colorspace;
 pub mod content_stream;
 mod encryption;
+pub mod filters;
+mod images;
+pub mod metadata;
 mod object_parser;
+pub mod optional_content;
+pub mod outline;
 mod page_tree;
-
-pub use content_stream::{ContentStreamParser, ParsedOp};
+pub mod pattern;
+pub mod shading;
+pub mod structure;
+
+pub use annotations::{FormValue, FormWidget, LinkAnnotation, LinkTarget, NoteAnnotation};
+pub use colorspace::ColorSpace;
+pub use content_stream::{ContentOpsCache, ContentStreamParser, ParsedOp};
+pub use filters::{DecodeParms, FilterRegistry};
+pub use images::ImageOptions;
+pub use metadata::DocumentMetadata;
 pub use object_parser::{Dictionary, PdfObject, XRef, XRefEntry};
-pub use page_tree::PageTreeParser;
+pub use optional_content::OptionalContent;
+pub use outline::Outline;
+pub use page_tree::{PageTree, PageTreeParser};
+pub use pattern::Pattern;
+pub use shading::Shading;
+pub use structure::{StructElement, StructNode};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct ObjectReference(pub u64, pub u16);
@@ -19,6 +41,8 @@ pub struct PdfRefResolver<'a> {
     xref: &'a XRef,
     cache: Option<std::cell::RefCell<std::collections::HashMap<ObjectReference, PdfObject>>>,
     encryption_key: Option<Vec<u8>>,
+    filters: Option<&'a FilterRegistry>,
+    limits: ZipBombDetector,
 }
 
 impl<'a> PdfRefResolver<'a> {
@@ -28,6 +52,8 @@ impl<'a> PdfRefResolver<'a> {
             xref,
             cache: None,
             encryption_key: None,
+            filters: None,
+            limits: ZipBombDetector::default(),
         }
     }
 
@@ -41,6 +67,24 @@ impl<'a> PdfRefResolver<'a> {
         self
     }
 
+    /// Consult `filters` before the built-in decoders when decoding streams.
+    pub fn with_filters(mut self, filters: &'a FilterRegistry) -> Self {
+        self.filters = Some(filters);
+        self
+    }
+
+    /// Whether a decoder registered with [`Self::with_filters`] handles
+    /// `filter`.
+    pub fn has_decoder(&self, filter: &str) -> bool {
+        self.filters.is_some_and(|filters| filters.get(filter).is_some())
+    }
+
+    /// Hold decompressed streams to `limits` instead of the default ones.
+    pub fn with_decompression_limits(mut self, limits: ZipBombDetector) -> Self {
+        self.limits = limits;
+        self
+    }
+
     pub fn dereference(&self, obj_ref: ObjectReference) -> Option<PdfObject> {
         if let Some(ref cache) = self.cache {
             if let Some(cached) = cache.borrow().get(&obj_ref) {
@@ -209,12 +253,13 @@ impl<'a> PdfRefResolver<'a> {
                 };
 
                 // Decompress if needed
-                let filter = dict.get("Filter").and_then(|v| v.as_name()).map(|s| s.to_string());
-                let stream_data = if filter.as_deref() == Some("FlateDecode") {
-                    decompress_flate(&raw_stream).unwrap_or(raw_stream)
-                } else {
-                    raw_stream
-                };
+                let globals = self.with_jbig2_globals(dict);
+                let stream_data = filters::decode_stream(
+                    raw_stream,
+                    globals.as_ref().unwrap_or(dict),
+                    self.filters,
+                    &self.limits,
+                );
 
                 return Some(PdfObject::Stream(stream_data, dict.clone()));
             } else {
@@ -224,14 +269,51 @@ impl<'a> PdfRefResolver<'a> {
 
         Some(obj)
     }
-}
 
-fn decompress_flate(data: &[u8]) -> Result<Vec<u8>, OdeError> {
-    let mut decoder = ZlibDecoder::new(data);
-    let mut result = Vec::new();
-    decoder.read_to_end(&mut result)
-        .map_err(|e| OdeError::PdfParseError(format!("FlateDecode decompression failed: {}", e)))?;
-    Ok(result)
+    /// `dict` with the `/JBIG2Globals` reference in its `/DecodeParms`
+    /// replaced by the globals stream, for a registered JBIG2 decoder, which
+    /// can't follow references itself. `None` when there is nothing to replace.
+    fn with_jbig2_globals(&self, dict: &Dictionary) -> Option<Dictionary> {
+        if !self.has_decoder("JBIG2Decode") {
+            return None;
+        }
+        let inline = |parms: &PdfObject| -> Option<PdfObject> {
+            let PdfObject::Dictionary(parms) = parms else {
+                return None;
+            };
+            let globals = self.dereference(parms.get("JBIG2Globals")?.as_reference()?)?;
+            let mut parms = parms.clone();
+            for (key, value) in &mut parms.entries {
+                if key == "JBIG2Globals" {
+                    *value = globals.clone();
+                }
+            }
+            Some(PdfObject::Dictionary(parms))
+        };
+
+        let parms = match dict.get("DecodeParms")? {
+            PdfObject::Array(items) => {
+                let inlined: Vec<Option<PdfObject>> = items.iter().map(inline).collect();
+                if inlined.iter().all(Option::is_none) {
+                    return None;
+                }
+                let items = items.iter().zip(inlined);
+                PdfObject::Array(
+                    items
+                        .map(|(item, new)| new.unwrap_or_else(|| item.clone()))
+                        .collect(),
+                )
+            }
+            parms => inline(parms)?,
+        };
+        let mut dict = dict.clone();
+        for (key, value) in &mut dict.entries {
+            if key == "DecodeParms" {
+                *value = parms.clone();
+            }
+        }
+        Some(dict)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -241,6 +323,15 @@ pub struct PdfDocument {
     pub pages: Vec<PdfPage>,
     pub catalog: Option<Catalog>,
     pub xref: Option<XRef>,
+    /// Title, author and dates from `/Info` and the XMP metadata.
+    pub metadata: DocumentMetadata,
+    /// The bookmarks of the document outline.
+    pub outline: Vec<Outline>,
+    /// The logical structure of a tagged PDF, read when the config asks to
+    /// reflow.
+    pub structure: Vec<StructElement>,
+    /// Content streams parsed while rendering, shared by clones of the document
+    pub content_ops: std::sync::Arc<ContentOpsCache>,
 }
 
 #[derive(Debug, Clone)]
@@ -394,42 +485,47 @@ impl ToUnicodeCMap {
     }
 
     pub fn decode_bytes(&self, raw: &[u8]) -> String {
+        self.decode_bytes_with(raw, UnmappedGlyph::Fallback)
+    }
+
+    /// Decode `raw`, handling codes the map doesn't cover as `unmapped` says.
+    pub fn decode_bytes_with(&self, raw: &[u8], unmapped: UnmappedGlyph) -> String {
         if self.is_single_byte {
-            self.decode_single_byte(raw)
+            self.decode_single_byte(raw, unmapped)
         } else {
-            self.decode_two_byte(raw)
+            self.decode_two_byte(raw, unmapped)
         }
     }
 
-    fn decode_single_byte(&self, raw: &[u8]) -> String {
+    fn decode_single_byte(&self, raw: &[u8], unmapped: UnmappedGlyph) -> String {
         let mut result = String::new();
         for &byte in raw {
             let code = byte as u16;
             if let Some(unicode) = self.char_map.get(&code) {
                 result.push_str(unicode);
-            } else if byte > 0 {
-                result.push(byte as char);
+            } else if let Some(ch) = unmapped.substitute((byte > 0).then_some(byte as char)) {
+                result.push(ch);
             }
         }
         result
     }
 
-    fn decode_two_byte(&self, raw: &[u8]) -> String {
+    fn decode_two_byte(&self, raw: &[u8], unmapped: UnmappedGlyph) -> String {
         let mut result = String::new();
         let mut i = 0;
         while i + 1 < raw.len() {
             let cid = ((raw[i] as u16) << 8) | (raw[i + 1] as u16);
             if let Some(unicode) = self.char_map.get(&cid) {
                 result.push_str(unicode);
-            } else if cid > 0 {
-                if let Some(ch) = char::from_u32(cid as u32) {
-                    result.push(ch);
-                }
+            } else {
+                let fallback = char::from_u32(cid as u32).filter(|_| cid > 0);
+                result.extend(unmapped.substitute(fallback));
             }
             i += 2;
         }
-        if i < raw.len() && raw[i] > 0 {
-            result.push(raw[i] as char);
+        if i < raw.len() {
+            let byte = raw[i];
+            result.extend(unmapped.substitute((byte > 0).then_some(byte as char)));
         }
         result
     }
@@ -442,6 +538,9 @@ pub struct PageImage {
     pub width: u32,
     pub height: u32,
     pub mime_type: String,
+    /// The filter the image's data is in when nothing could decode it; the
+    /// data is then a placeholder.
+    pub unsupported_filter: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -451,10 +550,30 @@ pub struct FormXObject {
     pub resources: Option<Dictionary>,
     pub bbox: [f64; 4],
     pub matrix: Option<[f64; 6]>,
+    /// Fonts in the form's own /Resources /Font, by resource name. Names
+    /// missing here are looked up in the page's.
+    pub font_refs: std::collections::HashMap<String, ObjectReference>,
     pub font_cmaps: std::collections::HashMap<String, ToUnicodeCMap>,
+    pub font_widths: std::collections::HashMap<String, GlyphWidths>,
     pub images: std::collections::HashMap<String, PageImage>,
+    /// Named color spaces from the form's /Resources /ColorSpace
+    pub color_spaces: std::collections::HashMap<String, ColorSpace>,
     /// Nested form XObjects within this form
     pub form_xobjects: std::collections::HashMap<String, FormXObject>,
+    /// Names in the form's /Resources /Properties of hidden optional content
+    pub hidden_content: std::collections::HashSet<String>,
+}
+
+/// A Type3 font, whose glyphs are content streams drawn in glyph space.
+#[derive(Debug, Clone)]
+pub struct Type3Font {
+    /// Each code's `/CharProcs` procedure, named by the `/Encoding`
+    /// `/Differences` array, as a form whose matrix is the font's
+    /// `/FontMatrix` and whose resources are the font's `/Resources`.
+    pub glyphs: std::collections::HashMap<u8, FormXObject>,
+    /// Whether the font has a `/ToUnicode` map, whose text wins over the
+    /// text the glyph procedures show.
+    pub has_to_unicode: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -463,12 +582,107 @@ pub struct PdfPage {
     pub width: f64,
     pub height: f64,
     pub contents: Vec<u8>,
+    /// The `/Contents` object when it is a reference, which other pages may share
+    pub contents_ref: Option<ObjectReference>,
     pub fonts: Vec<ObjectReference>,
+    /// Font resource names (as used by `Tf`) mapped to their font objects
+    pub font_refs: std::collections::HashMap<String, ObjectReference>,
+    /// Clockwise turn from `/Rotate`, normalized to 0, 90, 180 or 270
     pub rotation: i32,
+    /// Points per default user space unit, from `/UserUnit`; `width`,
+    /// `height` and the content are in these units.
+    pub user_unit: f64,
+    /// Lower-left corner of the box shown, in default user space; content
+    /// is placed relative to it.
+    pub origin: (f64, f64),
     pub dict: Option<Dictionary>,
     pub font_cmaps: std::collections::HashMap<String, ToUnicodeCMap>,
+    /// Advance widths of the fonts that declare them, keyed by resource name
+    pub font_widths: std::collections::HashMap<String, GlyphWidths>,
+    /// Type3 fonts, keyed by resource name
+    pub type3_fonts: std::collections::HashMap<String, Type3Font>,
     pub images: std::collections::HashMap<String, PageImage>,
     pub form_xobjects: std::collections::HashMap<String, FormXObject>,
+    /// Named color spaces from the page's /Resources /ColorSpace
+    pub color_spaces: std::collections::HashMap<String, ColorSpace>,
+    /// Axial and radial shadings from the page's /Resources /Shading
+    pub shadings: std::collections::HashMap<String, Shading>,
+    /// Tiling and shading patterns from the page's /Resources /Pattern
+    pub patterns: std::collections::HashMap<String, Pattern>,
+    /// Names in the page's /Resources /Properties of optional content that
+    /// is hidden, whose `BDC` marked content isn't drawn
+    pub hidden_content: std::collections::HashSet<String>,
+    /// Link annotations from the page's /Annots
+    pub links: Vec<LinkAnnotation>,
+    /// Annotations from the page's /Annots with `/Contents` text to show
+    pub notes: Vec<NoteAnnotation>,
+    /// Form field widgets from the page's /Annots, in that order
+    pub form_widgets: Vec<FormWidget>,
+}
+
+impl PdfPage {
+    /// Width and height in points as displayed, after `/Rotate` and
+    /// `/UserUnit`.
+    pub fn displayed_size(&self) -> (f64, f64) {
+        let (w, h) = (self.width * self.user_unit, self.height * self.user_unit);
+        match self.rotation {
+            90 | 270 => (h, w),
+            _ => (w, h),
+        }
+    }
+
+    /// Maps default user space onto the displayed page, still y-up, moving
+    /// `origin` to the corner, turning it clockwise by `/Rotate` and scaling
+    /// it to points by `/UserUnit`. Content starts with this as its CTM and
+    /// annotation rects go through it too, so overlays land on the content
+    /// they cover.
+    pub fn user_space_matrix(&self) -> TransformMatrix {
+        let (w, h) = (self.width, self.height);
+        let [a, b, c, d, e, f] = match self.rotation {
+            90 => [0.0, -1.0, 1.0, 0.0, 0.0, w],
+            180 => [-1.0, 0.0, 0.0, -1.0, w, h],
+            270 => [0.0, 1.0, -1.0, 0.0, h, 0.0],
+            _ => [1.0, 0.0, 0.0, 1.0, 0.0, 0.0],
+        };
+        let (x0, y0) = self.origin;
+        let (e, f) = (e - x0 * a - y0 * c, f - x0 * b - y0 * d);
+        let unit = self.user_unit;
+        TransformMatrix {
+            a: a * unit,
+            b: b * unit,
+            c: c * unit,
+            d: d * unit,
+            e: e * unit,
+            f: f * unit,
+        }
+    }
+}
+
+/// `/Rotate` as one of 0, 90, 180 or 270: negative turns and turns past a
+/// full circle wrap around, and anything between quarter-turns goes to the
+/// nearest one.
+pub fn normalize_rotation(rotate: f64) -> i32 {
+    if !rotate.is_finite() {
+        return 0;
+    }
+    let quarter_turns = (rotate.rem_euclid(360.0) / 90.0).round() as i32;
+    quarter_turns % 4 * 90
+}
+
+/// A `/Lang` value as a BCP 47 language tag for HTML's `lang`, such as
+/// `en-US`: hyphen- or underscore-separated subtags of up to 8 letters and
+/// digits, the first all letters. Anything else is no usable language.
+pub fn language_tag(lang: &str) -> Option<String> {
+    let tag = lang.trim().replace('_', "-");
+    let mut subtags = tag.split('-');
+    let primary = subtags.next()?;
+    let valid = |subtag: &str| {
+        (1..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric())
+    };
+    let usable = valid(primary)
+        && primary.chars().all(|c| c.is_ascii_alphabetic())
+        && subtags.all(valid);
+    usable.then_some(tag)
 }
 
 impl PdfDocument {
@@ -483,6 +697,10 @@ impl PdfDocument {
             pages: Vec::new(),
             catalog: None,
             xref: None,
+            metadata: DocumentMetadata::default(),
+            outline: Vec::new(),
+            structure: Vec::new(),
+            content_ops: std::sync::Arc::new(ContentOpsCache::new()),
         }
     }
 
@@ -493,6 +711,20 @@ impl PdfDocument {
     pub fn num_pages(&self) -> usize {
         self.pages.len()
     }
+
+    /// Whether the trailer references an `/Encrypt` dictionary.
+    pub fn is_encrypted(&self) -> bool {
+        self.trailer
+            .dict
+            .as_ref()
+            .is_some_and(|trailer| trailer.get("Encrypt").is_some())
+    }
+
+    /// The document language from the catalog's `/Lang`, e.g. `en-US`.
+    pub fn language(&self) -> Option<String> {
+        let catalog = self.catalog.as_ref()?.dict.as_ref()?;
+        language_tag(&catalog.get("Lang")?.as_text_string()?)
+    }
 }
 
 impl Default for PdfDocument {
@@ -502,9 +734,15 @@ impl Default for PdfDocument {
 }
 
 pub fn parse_pdf(data: &[u8]) -> Result<PdfDocument, OdeError> {
+    parse_pdf_with_config(data, &ConversionConfig::default())
+}
+
+/// Parse a PDF, decoding streams with any custom filters registered in `config`.
+pub fn parse_pdf_with_config(data: &[u8], config: &ConversionConfig) -> Result<PdfDocument, OdeError> {
     if data.len() < 5 {
-        return Err(OdeError::PdfParseError(
-            "File too small to be a PDF".to_string(),
+        return Err(OdeError::parse(
+            ParseErrorKind::NotPdf,
+            "File too small to be a PDF",
         ));
     }
 
@@ -513,11 +751,12 @@ pub fn parse_pdf(data: &[u8]) -> Result<PdfDocument, OdeError> {
     let header_bytes = &data[..first_line_end];
 
     let header = std::str::from_utf8(header_bytes)
-        .map_err(|_| OdeError::PdfParseError("Invalid UTF-8 in header".to_string()))?;
+        .map_err(|_| OdeError::parse(ParseErrorKind::NotPdf, "Invalid UTF-8 in header"))?;
 
     if !header.contains("PDF-") {
-        return Err(OdeError::PdfParseError(
-            "Not a PDF file - missing %PDF- header".to_string(),
+        return Err(OdeError::parse(
+            ParseErrorKind::NotPdf,
+            "Not a PDF file - missing %PDF- header",
         ));
     }
 
@@ -530,7 +769,8 @@ pub fn parse_pdf(data: &[u8]) -> Result<PdfDocument, OdeError> {
         "1.4".to_string()
     };
 
-    let mut parser = object_parser::PdfParser::new(data);
+    let limits = ZipBombDetector::from_config(config);
+    let mut parser = object_parser::PdfParser::new(data).with_decompression_limits(limits);
     let xref = parser.parse()?;
 
     let mut doc = PdfDocument::new();
@@ -538,23 +778,35 @@ pub fn parse_pdf(data: &[u8]) -> Result<PdfDocument, OdeError> {
     doc.xref = Some(xref.clone());
 
     // Detect encryption before parsing catalog/pages
-    let encryption_key = detect_encryption(data, &xref);
+    let encryption_key = detect_encryption(data, &xref, limits)?;
 
-    parse_trailer_and_catalog(data, &mut doc, encryption_key.as_ref())?;
+    parse_trailer_and_catalog(data, &mut doc, encryption_key.as_ref(), limits)?;
 
     let xref_clone = doc.xref.clone();
     let catalog_pages_root = doc.catalog.as_ref().and_then(|c| c.pages_root);
 
     if let (Some(ref xref), Some(root_ref)) = (&xref_clone, catalog_pages_root) {
-        let mut resolver = PdfRefResolver::new(data, xref).with_cache();
+        let mut resolver = PdfRefResolver::new(data, xref)
+            .with_cache()
+            .with_filters(&config.filters)
+            .with_decompression_limits(limits);
         if let Some(ref key) = encryption_key {
             resolver = resolver.with_encryption_key(key.clone());
         }
-        let page_parser = PageTreeParser::new(&resolver);
-
-        match page_parser.parse_all_pages(root_ref) {
-            Ok(pages) => {
-                doc.pages = pages;
+        let catalog_dict = doc.catalog.as_ref().and_then(|c| c.dict.as_ref());
+        let layers = OptionalContent::from_catalog(&resolver, catalog_dict, &config.enabled_layers);
+        let page_parser = PageTreeParser::new(&resolver)
+            .with_catalog(catalog_dict)
+            .with_image_options(ImageOptions::from_config(config))
+            .with_optional_content(layers)
+            .with_structure(config.reflow)
+            .with_page_box(config.page_box);
+
+        match page_parser.parse_page_tree(root_ref) {
+            Ok(tree) => {
+                doc.pages = tree.pages;
+                doc.outline = tree.outline;
+                doc.structure = tree.structure;
             }
             Err(_e) => {
                 if let Some(ref xref_cloned_for_fallback) = xref_clone {
@@ -575,40 +827,103 @@ pub fn parse_pdf(data: &[u8]) -> Result<PdfDocument, OdeError> {
                 width: 612.0,
                 height: 792.0,
                 contents: Vec::new(),
+                contents_ref: None,
                 fonts: Vec::new(),
+                font_refs: std::collections::HashMap::new(),
                 rotation: 0,
+                user_unit: 1.0,
+                origin: (0.0, 0.0),
                 dict: None,
                 font_cmaps: std::collections::HashMap::new(),
+                font_widths: std::collections::HashMap::new(),
+                type3_fonts: std::collections::HashMap::new(),
                 images: std::collections::HashMap::new(),
                 form_xobjects: std::collections::HashMap::new(),
+                color_spaces: std::collections::HashMap::new(),
+                shadings: std::collections::HashMap::new(),
+                patterns: std::collections::HashMap::new(),
+                hidden_content: std::collections::HashSet::new(),
+                links: Vec::new(),
+                notes: Vec::new(),
+                form_widgets: Vec::new(),
             });
         }
     }
+    size_unsized_pages(&mut doc.pages, config.default_page_size);
 
     Ok(doc)
 }
 
+/// US Letter, in points.
+const LETTER_SIZE: (f64, f64) = (612.0, 792.0);
+
+/// Give pages that found no MediaBox, their own or inherited, `default_size`,
+/// or else the size of the document's first page that has one, or else US
+/// Letter.
+fn size_unsized_pages(pages: &mut [PdfPage], default_size: Option<(f64, f64)>) {
+    let no_size = |page: &PdfPage| page.width <= 0.0 || page.height <= 0.0;
+    let (width, height) = default_size
+        .or_else(|| {
+            pages
+                .iter()
+                .find(|page| !no_size(page))
+                .map(|page| (page.width, page.height))
+        })
+        .unwrap_or(LETTER_SIZE);
+    for page in pages.iter_mut().filter(|page| no_size(page)) {
+        page.width = width;
+        page.height = height;
+    }
+}
+
 /// Detect PDF encryption and compute the file encryption key.
-/// Returns None if the PDF is not encrypted.
-fn detect_encryption(data: &[u8], xref: &XRef) -> Option<Vec<u8>> {
-    let trailer = xref.trailer.as_ref()?;
-    let encrypt_ref = trailer.get("Encrypt")?.as_reference()?;
+/// Returns None if the PDF is not encrypted, and an `Encrypted` error when
+/// it is encrypted in a way that can't be decrypted.
+fn detect_encryption(
+    data: &[u8],
+    xref: &XRef,
+    limits: ZipBombDetector,
+) -> Result<Option<Vec<u8>>, OdeError> {
+    let Some(encrypt_ref) = xref
+        .trailer
+        .as_ref()
+        .and_then(|trailer| trailer.get("Encrypt"))
+        .and_then(|e| e.as_reference())
+    else {
+        return Ok(None);
+    };
 
     // Use a resolver WITHOUT encryption to read the Encrypt dictionary
-    let resolver = PdfRefResolver::new(data, xref);
-    let encrypt_obj = resolver.dereference(encrypt_ref)?;
-    let encrypt_dict = match &encrypt_obj {
-        PdfObject::Dictionary(d) => d,
-        _ => return None,
+    let resolver = PdfRefResolver::new(data, xref).with_decompression_limits(limits);
+    let Some(PdfObject::Dictionary(encrypt_dict)) = resolver.dereference(encrypt_ref) else {
+        return Ok(None);
     };
 
-    // Only support Standard security handler
+    // Only the Standard security handler with RC4 can be decrypted
     let filter = encrypt_dict.get("Filter").and_then(|v| v.as_name());
     if filter != Some("Standard") {
-        return None;
+        return Err(OdeError::parse(
+            ParseErrorKind::Encrypted,
+            format!(
+                "Unsupported security handler {}",
+                filter.unwrap_or("(none)")
+            ),
+        ));
     }
 
     let r = encrypt_dict.get("R").and_then(|v| v.as_number()).unwrap_or(2.0) as u32;
+    if r >= 5 || uses_aes(&encrypt_dict) {
+        return Err(OdeError::parse(
+            ParseErrorKind::Encrypted,
+            format!("AES encryption (revision {}) is not supported", r),
+        ));
+    }
+    Ok(encryption_key(&encrypt_dict, xref, r))
+}
+
+/// The file encryption key for the empty user password.
+fn encryption_key(encrypt_dict: &Dictionary, xref: &XRef, r: u32) -> Option<Vec<u8>> {
+    let trailer = xref.trailer.as_ref()?;
     let length_bits = encrypt_dict.get("Length").and_then(|v| v.as_number()).unwrap_or(40.0) as usize;
     let key_length = length_bits / 8;
     let p_value = encrypt_dict.get("P").and_then(|v| v.as_number()).unwrap_or(0.0) as i32;
@@ -632,7 +947,28 @@ fn detect_encryption(data: &[u8], xref: &XRef) -> Option<Vec<u8>> {
     Some(key)
 }
 
-fn parse_trailer_and_catalog(data: &[u8], doc: &mut PdfDocument, encryption_key: Option<&Vec<u8>>) -> Result<(), OdeError> {
+/// Whether a revision 4 handler's crypt filters use AES rather than RC4.
+fn uses_aes(encrypt_dict: &Dictionary) -> bool {
+    let Some(PdfObject::Dictionary(filters)) = encrypt_dict.get("CF") else {
+        return false;
+    };
+    filters.entries.iter().any(|(_, filter)| match filter {
+        PdfObject::Dictionary(filter) => {
+            matches!(
+                filter.get("CFM").and_then(|m| m.as_name()),
+                Some("AESV2" | "AESV3")
+            )
+        }
+        _ => false,
+    })
+}
+
+fn parse_trailer_and_catalog(
+    data: &[u8],
+    doc: &mut PdfDocument,
+    encryption_key: Option<&Vec<u8>>,
+    limits: ZipBombDetector,
+) -> Result<(), OdeError> {
     let mut size = 0u64;
     let mut root_ref: Option<ObjectReference> = None;
 
@@ -675,12 +1011,12 @@ fn parse_trailer_and_catalog(data: &[u8], doc: &mut PdfDocument, encryption_key:
     doc.trailer = Trailer {
         size,
         root: root_ref,
-        dict: None,
+        dict: doc.xref.as_ref().and_then(|xref| xref.trailer.clone()),
     };
 
     // Resolve Catalog → Pages: /Root points to Catalog obj, which has /Pages N gen R
     if let (Some(root), Some(ref xref)) = (root_ref, &doc.xref) {
-        let mut resolver = PdfRefResolver::new(data, xref);
+        let mut resolver = PdfRefResolver::new(data, xref).with_decompression_limits(limits);
         if let Some(key) = encryption_key {
             resolver = resolver.with_encryption_key(key.clone());
         }
@@ -700,11 +1036,36 @@ fn parse_trailer_and_catalog(data: &[u8], doc: &mut PdfDocument, encryption_key:
                 });
             }
         }
+        doc.metadata = read_metadata(&resolver, doc.trailer.dict.as_ref(), doc.catalog.as_ref());
     }
 
     Ok(())
 }
 
+/// The trailer's `/Info`, completed from the catalog's XMP `/Metadata`.
+fn read_metadata(
+    resolver: &PdfRefResolver,
+    trailer: Option<&Dictionary>,
+    catalog: Option<&Catalog>,
+) -> DocumentMetadata {
+    let resolve = |obj: &PdfObject| match obj.as_reference() {
+        Some(r) => resolver.dereference(r),
+        None => Some(obj.clone()),
+    };
+    let mut metadata = match trailer.and_then(|t| t.get("Info")).and_then(resolve) {
+        Some(PdfObject::Dictionary(info)) => DocumentMetadata::from_info(&info, resolver),
+        _ => DocumentMetadata::default(),
+    };
+    let xmp = catalog
+        .and_then(|c| c.dict.as_ref())
+        .and_then(|c| c.get("Metadata"))
+        .and_then(resolve);
+    if let Some(PdfObject::Stream(xmp, _)) = xmp {
+        metadata.fill_from_xmp(&String::from_utf8_lossy(&xmp));
+    }
+    metadata
+}
+
 fn extract_pages_from_xref(
     xref: &XRef,
     data: &[u8],
@@ -774,8 +1135,8 @@ fn extract_page_at_offset(
 ) -> Result<PdfPage, OdeError> {
     let page_data = &data[offset..offset.min(data.len())];
 
-    let mut width = 612.0;
-    let mut height = 792.0;
+    let mut width = 0.0;
+    let mut height = 0.0;
     let mut rotation = 0;
     let mut contents = Vec::new();
 
@@ -795,8 +1156,8 @@ fn extract_page_at_offset(
         }
 
         if line.contains("/Rotate") {
-            if let Some(rot_str) = line.split_whitespace().find(|s| s.parse::<i32>().is_ok()) {
-                rotation = rot_str.parse().unwrap_or(0);
+            if let Some(rot) = line.split_whitespace().find_map(|s| s.parse::<f64>().ok()) {
+                rotation = normalize_rotation(rot);
             }
         }
 
@@ -821,11 +1182,24 @@ fn extract_page_at_offset(
         width,
         height,
         contents,
+        contents_ref: None,
         fonts: Vec::new(),
+        font_refs: std::collections::HashMap::new(),
         rotation,
+        user_unit: 1.0,
+        origin: (0.0, 0.0),
         font_cmaps: std::collections::HashMap::new(),
+        font_widths: std::collections::HashMap::new(),
+        type3_fonts: std::collections::HashMap::new(),
         images: std::collections::HashMap::new(),
         form_xobjects: std::collections::HashMap::new(),
+        color_spaces: std::collections::HashMap::new(),
+        shadings: std::collections::HashMap::new(),
+        patterns: std::collections::HashMap::new(),
+        hidden_content: std::collections::HashSet::new(),
+        links: Vec::new(),
+        notes: Vec::new(),
+        form_widgets: Vec::new(),
         dict: None,
     })
 }
@@ -856,6 +1230,17 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_language_tag() {
+        assert_eq!(language_tag("en-US").as_deref(), Some("en-US"));
+        assert_eq!(language_tag(" de_CH ").as_deref(), Some("de-CH"));
+        assert_eq!(language_tag("zh-Hant-TW").as_deref(), Some("zh-Hant-TW"));
+        assert_eq!(language_tag(""), None);
+        assert_eq!(language_tag("en US"), None);
+        assert_eq!(language_tag("\"><script>"), None);
+        assert_eq!(language_tag("1en"), None);
+    }
+
     #[test]
     fn test_skip_pdf_header() {
         let data = b"%PDF-1.4\n";
@@ -884,4 +1269,14 @@ mod tests {
         assert_eq!(entries[1].offset, 9);
         assert!(entries[1].in_use);
     }
+
+    #[test]
+    fn test_normalize_rotation() {
+        assert_eq!(normalize_rotation(-90.0), 270);
+        assert_eq!(normalize_rotation(450.0), 90);
+        assert_eq!(normalize_rotation(270.0), 270);
+        assert_eq!(normalize_rotation(-720.0), 0);
+        assert_eq!(normalize_rotation(100.0), 90);
+        assert_eq!(normalize_rotation(350.0), 0);
+    }
 }
@@ -1,6 +1,8 @@
-use crate::error::OdeError;
-use flate2::read::ZlibDecoder;
-use std::io::Read;
+use crate::error::{OdeError, ParseErrorKind};
+use crate::util::{decode_pdf_text_string, ZipBombDetector};
+
+/// Default limit on arrays and dictionaries nested inside one another.
+const DEFAULT_MAX_NESTING_DEPTH: usize = 256;
 
 #[derive(Debug, Clone, Default)]
 pub struct XRef {
@@ -97,6 +99,13 @@ impl PdfObject {
         }
     }
 
+    /// Decode a String object meant for people to read, such as a title or
+    /// a bookmark: UTF-16 after a byte order mark, PDFDocEncoding otherwise.
+    pub fn as_text_string(&self) -> Option<String> {
+        self.as_raw_bytes()
+            .map(|bytes| decode_pdf_text_string(&bytes))
+    }
+
     pub fn as_integer(&self) -> Option<i64> {
         match self {
             PdfObject::Integer(n) => Some(*n),
@@ -109,15 +118,16 @@ pub struct PdfParser<'a> {
     data: &'a [u8],
     pos: usize,
     xref: Option<XRef>,
+    /// Arrays and dictionaries currently open in `parse_object`.
+    depth: usize,
+    max_depth: usize,
+    /// Limits for the streams this parser decodes itself.
+    limits: ZipBombDetector,
 }
 
 impl<'a> PdfParser<'a> {
     pub fn new(data: &'a [u8]) -> Self {
-        Self {
-            data,
-            pos: 0,
-            xref: None,
-        }
+        Self::with_position(data, 0)
     }
 
     pub fn with_position(data: &'a [u8], pos: usize) -> Self {
@@ -125,9 +135,26 @@ impl<'a> PdfParser<'a> {
             data,
             pos,
             xref: None,
+            depth: 0,
+            max_depth: DEFAULT_MAX_NESTING_DEPTH,
+            limits: ZipBombDetector::default(),
         }
     }
 
+    /// Fail objects whose arrays and dictionaries nest more than `max_depth`
+    /// levels deep, rather than recursing until the stack overflows.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Hold the XRef and recovered streams this parser decodes to `limits`
+    /// instead of the default ones.
+    pub fn with_decompression_limits(mut self, limits: ZipBombDetector) -> Self {
+        self.limits = limits;
+        self
+    }
+
     pub fn position(&self) -> usize {
         self.pos
     }
@@ -142,7 +169,14 @@ impl<'a> PdfParser<'a> {
 
     pub fn parse(&mut self) -> Result<XRef, OdeError> {
         self.skip_pdf_header()?;
-        self.find_and_parse_xref()?;
+        let parsed = self.find_and_parse_xref();
+        if !self.has_usable_xref() {
+            if let Some(xref) = self.reconstruct_xref() {
+                self.xref = Some(xref);
+                return Ok(self.xref.clone().unwrap_or_default());
+            }
+        }
+        parsed?;
         Ok(self.xref.clone().unwrap_or_default())
     }
 
@@ -154,10 +188,10 @@ impl<'a> PdfParser<'a> {
         // Only check the first line — real PDFs have binary bytes on line 2
         let first_line_end = self.data.iter().position(|&b| b == b'\n' || b == b'\r').unwrap_or(self.data.len().min(20));
         let header = std::str::from_utf8(&self.data[..first_line_end])
-            .map_err(|_| OdeError::PdfParseError("Invalid UTF-8 in header".to_string()))?;
+            .map_err(|_| OdeError::parse(ParseErrorKind::NotPdf, "Invalid UTF-8 in header"))?;
 
         if !header.contains("PDF-") {
-            return Err(OdeError::PdfParseError("Not a PDF file".to_string()));
+            return Err(OdeError::parse(ParseErrorKind::NotPdf, "Not a PDF file"));
         }
 
         self.pos = first_line_end;
@@ -212,7 +246,8 @@ impl<'a> PdfParser<'a> {
         } else if self.try_consume(b"trailer") {
             self.parse_trailer_only()?;
         } else {
-            return Err(OdeError::PdfParseError(
+            return Err(OdeError::parse(
+                ParseErrorKind::BadXref,
                 format!("Invalid xref section at offset {}", offset),
             ));
         }
@@ -220,91 +255,47 @@ impl<'a> PdfParser<'a> {
         Ok(())
     }
 
+    /// Merge the earlier sections of an incrementally updated file into the
+    /// newest one, walking `/Prev` back from it. An object listed in a newer
+    /// section hides every entry for it in older ones, whatever their
+    /// generation, so objects redefined or freed by an update never resolve
+    /// to a stale version. The newest trailer is kept.
     fn follow_prev_chain(&mut self) -> Result<(), OdeError> {
+        let Some(newest) = self.xref.take() else {
+            return Ok(());
+        };
+        let mut newer_ids: std::collections::HashSet<u64> =
+            newest.entries.iter().map(|e| e.object_id).collect();
+        let mut entries = newest.entries;
+        let mut prev_offset = prev_section_offset(newest.trailer.as_ref());
         let mut visited = std::collections::HashSet::new();
-        loop {
-            let prev_offset = self.xref.as_ref()
-                .and_then(|x| x.trailer.as_ref())
-                .and_then(|t| t.get("Prev"))
-                .and_then(|v| v.as_number())
-                .map(|n| n as usize);
-
-            let prev_offset = match prev_offset {
-                Some(o) if o > 0 && !visited.contains(&o) => o,
-                _ => break,
-            };
-            visited.insert(prev_offset);
-
-            // Save current entries
-            let current_entries = self.xref.as_ref()
-                .map(|x| x.entries.clone())
-                .unwrap_or_default();
-            let current_trailer = self.xref.as_ref()
-                .and_then(|x| x.trailer.clone());
-
-            // Parse xref at Prev offset
-            if self.parse_xref_at(prev_offset).is_ok() {
-                // Merge: current entries override prev entries
-                let mut merged = std::collections::HashMap::new();
-                // Add older (prev) entries first
-                if let Some(ref xref) = self.xref {
-                    for e in &xref.entries {
-                        merged.insert((e.object_id, e.generation), *e);
-                    }
-                }
-                // Override with newer (current) entries
-                for e in &current_entries {
-                    merged.insert((e.object_id, e.generation), *e);
-                }
-                let entries: Vec<XRefEntry> = merged.into_values().collect();
-
-                // Also check /XRefStm in prev trailer
-                let prev_xrefstm = self.xref.as_ref()
-                    .and_then(|x| x.trailer.as_ref())
-                    .and_then(|t| t.get("XRefStm"))
-                    .and_then(|v| v.as_number())
-                    .map(|n| n as usize);
-
-                self.xref = Some(XRef {
-                    entries,
-                    trailer: current_trailer.or_else(|| self.xref.as_ref().and_then(|x| x.trailer.clone())),
-                });
 
-                // If there's an XRefStm (hybrid xref), parse that too
-                if let Some(stm_offset) = prev_xrefstm {
-                    if !visited.contains(&stm_offset) {
-                        visited.insert(stm_offset);
-                        let saved = self.xref.clone();
-                        if self.parse_xref_at(stm_offset).is_ok() {
-                            if let Some(ref stm_xref) = self.xref {
-                                let mut merged = std::collections::HashMap::new();
-                                for e in &stm_xref.entries {
-                                    merged.insert((e.object_id, e.generation), *e);
-                                }
-                                if let Some(ref saved_xref) = saved {
-                                    for e in &saved_xref.entries {
-                                        merged.insert((e.object_id, e.generation), *e);
-                                    }
-                                }
-                                self.xref = Some(XRef {
-                                    entries: merged.into_values().collect(),
-                                    trailer: saved.and_then(|s| s.trailer),
-                                });
-                            }
-                        } else {
-                            self.xref = saved;
-                        }
-                    }
-                }
-            } else {
-                // Restore current state if prev parsing fails
-                self.xref = Some(XRef {
-                    entries: current_entries,
-                    trailer: current_trailer,
-                });
+        while let Some(offset) = prev_offset {
+            // A /Prev cycle would otherwise walk the same sections forever
+            if offset == 0 || !visited.insert(offset) {
                 break;
             }
+            if self.parse_xref_at(offset).is_err() {
+                break;
+            }
+            let Some(older) = self.xref.take() else {
+                break;
+            };
+            let section_ids: Vec<u64> = older.entries.iter().map(|e| e.object_id).collect();
+            entries.extend(
+                older
+                    .entries
+                    .into_iter()
+                    .filter(|e| !newer_ids.contains(&e.object_id)),
+            );
+            newer_ids.extend(section_ids);
+            prev_offset = prev_section_offset(older.trailer.as_ref());
         }
+
+        self.xref = Some(XRef {
+            entries,
+            trailer: newest.trailer,
+        });
         Ok(())
     }
 
@@ -336,7 +327,8 @@ impl<'a> PdfParser<'a> {
                 let offset = match self.parse_number() {
                     Ok(n) => n as u64,
                     Err(_) => {
-                        return Err(OdeError::PdfParseError(
+                        return Err(OdeError::parse(
+                            ParseErrorKind::BadXref,
                             format!("Unexpected end of xref entries at position {}", i),
                         ))
                     }
@@ -344,7 +336,8 @@ impl<'a> PdfParser<'a> {
                 let gen = match self.parse_number() {
                     Ok(n) => n as u16,
                     Err(_) => {
-                        return Err(OdeError::PdfParseError(
+                        return Err(OdeError::parse(
+                            ParseErrorKind::BadXref,
                             format!("Unexpected end of xref entries at position {}", i),
                         ))
                     }
@@ -352,7 +345,8 @@ impl<'a> PdfParser<'a> {
                 let in_use = match self.parse_keyword() {
                     Ok(k) => k == "n",
                     Err(_) => {
-                        return Err(OdeError::PdfParseError(
+                        return Err(OdeError::parse(
+                            ParseErrorKind::BadXref,
                             format!("Unexpected end of xref entries at position {}", i),
                         ))
                     }
@@ -377,18 +371,23 @@ impl<'a> PdfParser<'a> {
             None
         };
 
-        // Handle /XRefStm in hybrid-reference PDFs
-        if let Some(ref t) = trailer {
-            if let Some(stm_offset) = t.get("XRefStm").and_then(|v| v.as_number()).map(|n| n as usize) {
-                let saved_pos = self.pos;
-                self.pos = stm_offset;
-                if self.data[self.pos..].first().map_or(false, |b| b.is_ascii_digit()) {
-                    if let Ok(stm_entries) = self.parse_xref_stream_object_entries() {
-                        entries.extend(stm_entries);
-                    }
+        // A hybrid-reference file's /XRefStm lists the objects packed in
+        // object streams, which readers of the table alone aren't meant to
+        // see. One that can't be read leaves the table's entries as they are.
+        let stm_offset = trailer
+            .as_ref()
+            .and_then(|t| t.get("XRefStm"))
+            .and_then(|v| v.as_number())
+            .map(|n| n as usize);
+        if let Some(stm_offset) = stm_offset {
+            let saved_pos = self.pos;
+            self.pos = stm_offset;
+            if self.data.get(stm_offset).is_some_and(|b| b.is_ascii_digit()) {
+                if let Ok(stm_entries) = self.parse_xref_stream_object_entries() {
+                    merge_hybrid_entries(&mut entries, stm_entries);
                 }
-                self.pos = saved_pos;
             }
+            self.pos = saved_pos;
         }
 
         self.xref = Some(XRef { entries, trailer });
@@ -431,7 +430,10 @@ impl<'a> PdfParser<'a> {
         let _gen_num = self.parse_number()?;
         self.skip_whitespace();
         if !self.try_consume(b"obj") {
-            return Err(OdeError::PdfParseError("Expected 'obj' in XRef stream".to_string()));
+            return Err(OdeError::parse(
+                ParseErrorKind::BadXref,
+                "Expected 'obj' in XRef stream",
+            ));
         }
 
         self.skip_whitespace();
@@ -440,7 +442,10 @@ impl<'a> PdfParser<'a> {
         // Read stream data
         self.skip_whitespace();
         if !self.try_consume(b"stream") {
-            return Err(OdeError::PdfParseError("Expected 'stream' in XRef stream".to_string()));
+            return Err(OdeError::parse(
+                ParseErrorKind::BadXref,
+                "Expected 'stream' in XRef stream",
+            ));
         }
         // Skip \r\n or \n after "stream"
         if self.pos < self.data.len() && self.data[self.pos] == b'\r' {
@@ -452,32 +457,37 @@ impl<'a> PdfParser<'a> {
 
         let length = dict.get("Length")
             .and_then(|v| v.as_number())
+            .filter(|length| length.is_finite() && *length >= 0.0)
             .unwrap_or(0.0) as usize;
 
-        if self.pos + length > self.data.len() {
-            return Err(OdeError::PdfParseError("XRef stream length exceeds file".to_string()));
-        }
+        let Some(end) = self.pos.checked_add(length).filter(|&end| end <= self.data.len()) else {
+            return Err(OdeError::parse(
+                ParseErrorKind::TruncatedStream,
+                "XRef stream length exceeds file",
+            ));
+        };
 
-        let raw_stream = &self.data[self.pos..self.pos + length];
+        let raw_stream = &self.data[self.pos..end];
 
-        // Decompress if needed
-        let filter = dict.get("Filter").and_then(|v| v.as_name()).unwrap_or("");
-        let stream_data = if filter == "FlateDecode" {
-            let mut decoder = ZlibDecoder::new(raw_stream);
-            let mut buf = Vec::new();
-            decoder.read_to_end(&mut buf)
-                .map_err(|e| OdeError::PdfParseError(format!("Failed to decompress XRef stream: {}", e)))?;
-            buf
-        } else {
-            raw_stream.to_vec()
-        };
+        // Decompress if needed, undoing any /DecodeParms predictor
+        let decoded = super::filters::decode_with_builtins(raw_stream, &dict, &self.limits);
+        let stream_data = decoded.map_err(|e| match e.parse_error_kind() {
+            Some(ParseErrorKind::UnsupportedFilter(_)) => e,
+            _ => OdeError::parse(
+                ParseErrorKind::CorruptStream,
+                format!("Failed to decompress XRef stream: {}", e),
+            ),
+        })?;
 
         // Parse /W array (field widths)
         let w = dict.get("W")
             .and_then(|v| v.as_array())
-            .ok_or_else(|| OdeError::PdfParseError("Missing /W in XRef stream".to_string()))?;
+            .ok_or_else(|| OdeError::parse(ParseErrorKind::BadXref, "Missing /W in XRef stream"))?;
         if w.len() != 3 {
-            return Err(OdeError::PdfParseError("Invalid /W array length".to_string()));
+            return Err(OdeError::parse(
+                ParseErrorKind::BadXref,
+                "Invalid /W array length",
+            ));
         }
         let w1 = w[0].as_number().unwrap_or(0.0) as usize;
         let w2 = w[1].as_number().unwrap_or(0.0) as usize;
@@ -579,6 +589,174 @@ impl<'a> PdfParser<'a> {
         Ok(entries)
     }
 
+    /// Whether the parsed xref reaches the document catalog: the trailer
+    /// names a /Root whose entry is compressed or points at that object's
+    /// `N G obj` header.
+    fn has_usable_xref(&self) -> bool {
+        let Some(xref) = &self.xref else {
+            return false;
+        };
+        let Some(root) = xref
+            .trailer
+            .as_ref()
+            .and_then(|t| t.get("Root"))
+            .and_then(|r| r.as_reference())
+        else {
+            return false;
+        };
+        xref.entries
+            .iter()
+            .find(|e| e.object_id == root.0)
+            .is_some_and(|e| {
+                e.in_use
+                    && (e.objstm_num.is_some()
+                        || self.object_header_at(e.offset as usize).map(|h| h.0) == Some(root.0))
+            })
+    }
+
+    /// The object number and generation of an `N G obj` header at `offset`.
+    fn object_header_at(&self, offset: usize) -> Option<(u64, u16)> {
+        let mut parser = PdfParser::with_position(self.data, offset);
+        let obj_id = parser.parse_token().ok()?.parse().ok()?;
+        let gen = parser.parse_token().ok()?.parse().ok()?;
+        parser.skip_whitespace();
+        parser.try_consume(b"obj").then_some((obj_id, gen))
+    }
+
+    /// Rebuild the cross-reference table by scanning the whole file for
+    /// `N G obj` headers, for PDFs whose xref is missing or points at the
+    /// wrong bytes. Objects packed in object streams get compressed entries.
+    /// The trailer is the last `trailer` or `/Type /XRef` dictionary naming a
+    /// recovered /Root, or else a synthetic one pointing at the
+    /// `/Type /Catalog` object. `None` when no catalog can be found.
+    fn reconstruct_xref(&self) -> Option<XRef> {
+        // Later definitions win, as they do in incremental updates
+        let mut offsets = std::collections::BTreeMap::new();
+        for (obj_id, gen, offset) in scan_object_headers(self.data) {
+            offsets.insert(obj_id, (gen, offset));
+        }
+        if offsets.is_empty() {
+            return None;
+        }
+
+        let mut entries = Vec::new();
+        let mut compressed = std::collections::BTreeMap::new();
+        // Trailer dictionaries and catalogs found, keyed by file offset
+        let mut trailers = Vec::new();
+        let mut catalogs = Vec::new();
+
+        for (&obj_id, &(gen, offset)) in &offsets {
+            entries.push(XRefEntry {
+                object_id: obj_id,
+                generation: gen,
+                offset: offset as u64,
+                in_use: true,
+                objstm_num: None,
+                objstm_idx: None,
+            });
+
+            let Some((dict, stream)) = self.recovered_object_at(offset) else {
+                continue;
+            };
+            match dict.get("Type").and_then(|t| t.as_name()) {
+                Some("Catalog") => catalogs.push((offset, obj_id, gen)),
+                Some("XRef") => trailers.push((offset, dict)),
+                Some("ObjStm") => {
+                    let Some(stream) = stream else { continue };
+                    for (idx, (packed_id, packed)) in object_stream_members(&dict, &stream)
+                        .into_iter()
+                        .enumerate()
+                    {
+                        if offsets.contains_key(&packed_id) {
+                            continue;
+                        }
+                        compressed.insert(packed_id, (obj_id, idx as u64));
+                        if is_catalog(&packed) {
+                            catalogs.push((offset, packed_id, 0));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        entries.extend(compressed.iter().map(|(&obj_id, &(stm_num, stm_idx))| XRefEntry {
+            object_id: obj_id,
+            generation: 0,
+            offset: 0,
+            in_use: true,
+            objstm_num: Some(stm_num),
+            objstm_idx: Some(stm_idx),
+        }));
+
+        for offset in find_all(self.data, b"trailer") {
+            let mut parser = PdfParser::with_position(self.data, offset + b"trailer".len())
+                .with_max_depth(self.max_depth);
+            if let Ok(dict) = parser.parse_dictionary() {
+                trailers.push((offset, dict));
+            }
+        }
+
+        let recovered =
+            |obj_id: u64| offsets.contains_key(&obj_id) || compressed.contains_key(&obj_id);
+        let size = entries.iter().map(|e| e.object_id + 1).max().unwrap_or(0);
+        let trailer = trailers
+            .into_iter()
+            .filter(|(_, dict)| {
+                dict.get("Root")
+                    .and_then(|r| r.as_reference())
+                    .is_some_and(|r| recovered(r.0))
+            })
+            .max_by_key(|(offset, _)| *offset)
+            .map(|(_, dict)| dict)
+            .or_else(|| {
+                let (_, obj_id, gen) = catalogs.into_iter().max_by_key(|c| c.0)?;
+                Some(Dictionary {
+                    entries: vec![
+                        ("Root".to_string(), PdfObject::IndirectReference { obj_id, gen }),
+                        ("Size".to_string(), PdfObject::Integer(size as i64)),
+                    ],
+                })
+            })?;
+
+        Some(XRef { entries, trailer: Some(trailer) })
+    }
+
+    /// The dictionary of the object at `offset`, plus its decoded stream
+    /// data when it is a stream this parser can decode on its own.
+    fn recovered_object_at(&self, offset: usize) -> Option<(Dictionary, Option<Vec<u8>>)> {
+        let mut parser = PdfParser::with_position(self.data, offset).with_max_depth(self.max_depth);
+        parser.parse_token().ok()?;
+        parser.parse_token().ok()?;
+        parser.consume(b"obj").ok()?;
+        let dict = match parser.parse_object().ok()? {
+            PdfObject::Dictionary(dict) => dict,
+            _ => return None,
+        };
+
+        parser.skip_whitespace();
+        if !parser.try_consume(b"stream") {
+            return Some((dict, None));
+        }
+        parser.try_consume(b"\r");
+        parser.try_consume(b"\n");
+        let start = parser.pos;
+        // /Length may be an indirect reference, which can't be resolved yet
+        let declared_end = dict
+            .get("Length")
+            .and_then(|v| v.as_number())
+            .filter(|length| length.is_finite() && *length >= 0.0)
+            .and_then(|length| start.checked_add(length as usize))
+            .filter(|&end| end <= self.data.len());
+        let end = match declared_end {
+            Some(end) => end,
+            None => find_all(&self.data[start..], b"endstream").next()? + start,
+        };
+        let raw = &self.data[start..end];
+
+        let stream = super::filters::decode_with_builtins(raw, &dict, &self.limits).ok();
+        Some((dict, stream))
+    }
+
     fn parse_dictionary(&mut self) -> Result<Dictionary, OdeError> {
         self.skip_whitespace();
         self.consume(b"<<")?;
@@ -611,9 +789,9 @@ impl<'a> PdfParser<'a> {
         if peek == Some(b'(') {
             Ok(PdfObject::String(self.parse_literal_string()?))
         } else if peek == Some(b'<') && self.peek_bytes(2).map_or(false, |b| b == [b'<', b'<']) {
-            Ok(PdfObject::Dictionary(self.parse_dictionary()?))
+            Ok(PdfObject::Dictionary(self.nested(Self::parse_dictionary)?))
         } else if peek == Some(b'[') {
-            Ok(PdfObject::Array(self.parse_array()?))
+            Ok(PdfObject::Array(self.nested(Self::parse_array)?))
         } else if peek == Some(b'<') {
             Ok(PdfObject::String(self.parse_hex_string()?))
         } else if peek == Some(b'/') {
@@ -642,13 +820,30 @@ impl<'a> PdfParser<'a> {
                     } else if let Ok(f) = s.parse::<f64>() {
                         Ok(PdfObject::Float(f))
                     } else {
-                        Err(OdeError::PdfParseError(format!("Unknown token: {}", token)))
+                        Err(OdeError::malformed(format!("Unknown token: {}", token)))
                     }
                 }
             }
         }
     }
 
+    /// Run `parse` one array or dictionary level deeper.
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, OdeError>,
+    ) -> Result<T, OdeError> {
+        if self.depth >= self.max_depth {
+            return Err(OdeError::malformed(format!(
+                "Object nested more than {} levels deep at offset {}",
+                self.max_depth, self.pos
+            )));
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
     fn parse_array(&mut self) -> Result<Vec<PdfObject>, OdeError> {
         self.consume(b"[")?;
 
@@ -824,7 +1019,7 @@ impl<'a> PdfParser<'a> {
         }
 
         if start >= self.pos {
-            return Err(OdeError::PdfParseError("Expected token".to_string()));
+            return Err(OdeError::malformed("Expected token".to_string()));
         }
 
         Ok(std::str::from_utf8(&self.data[start..self.pos])
@@ -836,7 +1031,7 @@ impl<'a> PdfParser<'a> {
         let token = self.parse_token()?;
         token
             .parse::<i64>()
-            .map_err(|_| OdeError::PdfParseError(format!("Expected number, got: {}", token)))
+            .map_err(|_| OdeError::malformed(format!("Expected number, got: {}", token)))
     }
 
     fn parse_keyword(&mut self) -> Result<String, OdeError> {
@@ -865,8 +1060,9 @@ impl<'a> PdfParser<'a> {
         self.skip_whitespace();
 
         if self.pos + expected.len() > self.data.len() {
-            return Err(OdeError::PdfParseError(
-                "Unexpected end of file".to_string(),
+            return Err(OdeError::parse(
+                ParseErrorKind::TruncatedStream,
+                "Unexpected end of file",
             ));
         }
 
@@ -874,7 +1070,7 @@ impl<'a> PdfParser<'a> {
             self.pos += expected.len();
             Ok(())
         } else {
-            Err(OdeError::PdfParseError(format!(
+            Err(OdeError::malformed(format!(
                 "Expected {:?}, found different",
                 String::from_utf8_lossy(expected)
             )))
@@ -901,6 +1097,105 @@ impl<'a> PdfParser<'a> {
     }
 }
 
+/// The `/Prev` offset of the xref section `trailer` belongs to.
+fn prev_section_offset(trailer: Option<&Dictionary>) -> Option<usize> {
+    trailer?.get("Prev")?.as_number().map(|n| n as usize)
+}
+
+/// Every `N G obj` header in `data`, as (object number, generation, offset).
+fn scan_object_headers(data: &[u8]) -> Vec<(u64, u16, usize)> {
+    let is_space = |b: u8| b.is_ascii_whitespace() || b == 0;
+    let digits_before = |end: usize| {
+        let start = data[..end]
+            .iter()
+            .rposition(|b| !b.is_ascii_digit())
+            .map_or(0, |p| p + 1);
+        (start < end).then_some(start)
+    };
+    let spaces_before = |end: usize| {
+        let start = data[..end]
+            .iter()
+            .rposition(|b| !is_space(*b))
+            .map_or(0, |p| p + 1);
+        (start < end).then_some(start)
+    };
+
+    let mut headers = Vec::new();
+    for keyword in find_all(data, b"obj") {
+        // "obj" must be a whole keyword, not part of "endobj" or "objstm"
+        if data.get(keyword + 3).is_some_and(|b| b.is_ascii_alphanumeric()) {
+            continue;
+        }
+        let header = (|| {
+            let gen_start = digits_before(spaces_before(keyword)?)?;
+            let id_start = digits_before(spaces_before(gen_start)?)?;
+            if id_start > 0 && !is_space(data[id_start - 1]) {
+                return None;
+            }
+            let gen_end = spaces_before(keyword)?;
+            let id_end = spaces_before(gen_start)?;
+            let obj_id = std::str::from_utf8(&data[id_start..id_end]).ok()?.parse().ok()?;
+            let gen = std::str::from_utf8(&data[gen_start..gen_end]).ok()?.parse().ok()?;
+            Some((obj_id, gen, id_start))
+        })();
+        headers.extend(header);
+    }
+    headers
+}
+
+/// The objects packed in an object stream, in index order.
+fn object_stream_members(dict: &Dictionary, stream: &[u8]) -> Vec<(u64, PdfObject)> {
+    let count = dict.get("N").and_then(|v| v.as_number()).unwrap_or(0.0) as usize;
+    let first = dict.get("First").and_then(|v| v.as_number()).unwrap_or(0.0) as usize;
+    let mut header = PdfParser::new(stream);
+    let mut members = Vec::new();
+    for _ in 0..count {
+        let (Ok(obj_id), Ok(offset)) = (header.parse_number(), header.parse_number()) else {
+            break;
+        };
+        let obj = first
+            .checked_add(offset as usize)
+            .filter(|&pos| pos < stream.len())
+            .and_then(|pos| PdfParser::with_position(stream, pos).parse_object().ok())
+            .unwrap_or(PdfObject::Null);
+        members.push((obj_id as u64, obj));
+    }
+    members
+}
+
+fn is_catalog(obj: &PdfObject) -> bool {
+    matches!(obj, PdfObject::Dictionary(dict)
+        if dict.get("Type").and_then(|t| t.as_name()) == Some("Catalog"))
+}
+
+/// Offsets of every occurrence of `needle` in `data`.
+fn find_all<'a>(data: &'a [u8], needle: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+    data.windows(needle.len())
+        .enumerate()
+        .filter(move |(_, window)| *window == needle)
+        .map(|(i, _)| i)
+}
+
+/// Add to a hybrid-reference file's table `entries` the `stream_entries`
+/// for objects the table leaves out or marks free, such as those packed in
+/// object streams. Objects the table has in use keep the table's entry.
+fn merge_hybrid_entries(entries: &mut Vec<XRefEntry>, stream_entries: Vec<XRefEntry>) {
+    let in_table: std::collections::HashSet<u64> = entries
+        .iter()
+        .filter(|e| e.in_use)
+        .map(|e| e.object_id)
+        .collect();
+    let filling: Vec<XRefEntry> = stream_entries
+        .into_iter()
+        .filter(|e| e.in_use && !in_table.contains(&e.object_id))
+        .collect();
+    // Lookups take the first entry for an object, so a free one would hide
+    // the stream's
+    let filled: std::collections::HashSet<u64> = filling.iter().map(|e| e.object_id).collect();
+    entries.retain(|e| e.in_use || !filled.contains(&e.object_id));
+    entries.extend(filling);
+}
+
 /// Read a big-endian unsigned integer from n bytes
 fn read_be_uint(bytes: &[u8]) -> u64 {
     let mut result = 0u64;
@@ -929,6 +1224,65 @@ mod tests {
         assert!(parser.pos > 0);
     }
 
+    #[test]
+    fn test_xref_stream_with_png_predictor() {
+        use flate2::write::ZlibEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        // Rows [0 0 0 255] [1 0 15 0] [1 1 44 0], each encoded as Up
+        let rows = [2, 0, 0, 0, 255, 2, 1, 0, 15, 1, 2, 0, 1, 29, 0];
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&rows).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let mut data = format!(
+            "1 0 obj\n<</Type/XRef/Size 3/W[1 2 1]/Filter/FlateDecode\
+/DecodeParms<</Predictor 12/Columns 4>>/Length {}>>stream\n",
+            compressed.len()
+        )
+        .into_bytes();
+        data.extend_from_slice(&compressed);
+        data.extend_from_slice(b"\nendstream\nendobj\n");
+
+        let mut parser = PdfParser::new(&data);
+        let entries = parser.parse_xref_stream_object_entries().unwrap();
+        let offsets: Vec<(u64, bool)> = entries.iter().map(|e| (e.offset, e.in_use)).collect();
+        assert_eq!(offsets, [(0, false), (15, true), (300, true)]);
+    }
+
+    #[test]
+    fn test_xref_stream_with_a_huge_length_is_truncated() {
+        let data = b"1 0 obj\n<</Type/XRef/Size 1/W[1 2 1]/Length 99999999999999999999999>>stream\n\
+\x00\x00\x00\xff\nendstream\nendobj\n";
+        let err = PdfParser::new(data).parse_xref_stream_object_entries().unwrap_err();
+        assert_eq!(err.parse_error_kind(), Some(&ParseErrorKind::TruncatedStream));
+    }
+
+    #[test]
+    fn test_xref_stream_held_to_the_parsers_decompression_limits() {
+        use flate2::write::ZlibEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        let rows = [0u8, 0, 0, 255, 1, 0, 15, 0, 1, 0, 44, 0];
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&rows).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let mut data = format!(
+            "1 0 obj\n<</Type/XRef/Size 3/W[1 2 1]/Filter/FlateDecode/Length {}>>stream\n",
+            compressed.len()
+        )
+        .into_bytes();
+        data.extend_from_slice(&compressed);
+        data.extend_from_slice(b"\nendstream\nendobj\n");
+
+        assert!(PdfParser::new(&data).parse_xref_stream_object_entries().is_ok());
+
+        let limits = ZipBombDetector::default().with_max_size(rows.len() - 1);
+        let mut parser = PdfParser::new(&data).with_decompression_limits(limits);
+        assert!(parser.parse_xref_stream_object_entries().is_err());
+    }
+
     #[test]
     fn test_find_xref_offset() {
         let data = b"startxref\n42\n%%EOF";
@@ -972,4 +1326,74 @@ mod tests {
         let dict = result.unwrap();
         assert!(!dict.entries.is_empty());
     }
+
+    #[test]
+    fn test_text_strings_decode_utf16_and_pdfdoc() {
+        let data = br"<</Title <FEFF004B00E400790074007400F6> /Subject (\215Caf\351\216)>>";
+        let dict = PdfParser::new(data).parse_dictionary().unwrap();
+        let text = |key: &str| dict.get(key).and_then(|v| v.as_text_string());
+        assert_eq!(text("Title").as_deref(), Some("K\u{E4}ytt\u{F6}"));
+        assert_eq!(
+            text("Subject").as_deref(),
+            Some("\u{201C}Caf\u{E9}\u{201D}")
+        );
+    }
+
+    #[test]
+    fn test_deeply_nested_array_is_an_error() {
+        let mut data = vec![b'['; 100_000];
+        data.extend(vec![b']'; 100_000]);
+        let result = PdfParser::new(&data).parse_object();
+        assert!(matches!(result, Err(OdeError::PdfParseError { .. })));
+
+        let nested = |depth: usize| [vec![b'['; depth], vec![b']'; depth]].concat();
+        let at_limit = nested(DEFAULT_MAX_NESTING_DEPTH);
+        assert!(PdfParser::new(&at_limit).parse_object().is_ok());
+        let past_limit = nested(DEFAULT_MAX_NESTING_DEPTH + 1);
+        assert!(PdfParser::new(&past_limit).parse_object().is_err());
+
+        let data = b"<</Kids[[[1 0 R]]]>>";
+        assert!(PdfParser::new(data).with_max_depth(4).parse_object().is_ok());
+        assert!(PdfParser::new(data).with_max_depth(3).parse_object().is_err());
+    }
+
+    #[test]
+    fn test_reconstruct_finds_catalog_in_object_stream() {
+        let members = b"1 0 2 30 <</Type/Catalog/Pages 2 0 R>> <</Type/Pages/Kids[]/Count 0>>";
+        let mut data = b"%PDF-1.5\n".to_vec();
+        data.extend_from_slice(
+            format!("7 0 obj\n<</Type/ObjStm/N 2/First 9/Length {}>>\nstream\n", members.len())
+                .as_bytes(),
+        );
+        data.extend_from_slice(members);
+        data.extend_from_slice(b"\nendstream\nendobj\n%%EOF");
+
+        let xref = PdfParser::new(&data).parse().unwrap();
+        let root = xref.trailer.as_ref().and_then(|t| t.get("Root")).and_then(|r| r.as_reference());
+        assert_eq!(root, Some(crate::parser::ObjectReference(1, 0)));
+        let packed: Vec<_> = xref
+            .entries
+            .iter()
+            .filter_map(|e| Some((e.object_id, e.objstm_num?, e.objstm_idx?)))
+            .collect();
+        assert_eq!(packed, [(1, 7, 0), (2, 7, 1)]);
+    }
+
+    #[test]
+    fn test_reconstruct_survives_huge_lengths_and_offsets() {
+        let huge = "99999999999999999999999";
+        let members = format!("1 0 2 {huge} <</Type/Catalog/Pages 3 0 R>>");
+        let first = members.find("<<").unwrap();
+        let mut data = b"%PDF-1.5\n".to_vec();
+        data.extend_from_slice(
+            format!("7 0 obj\n<</Type/ObjStm/N 2/First {first}/Length {huge}>>\nstream\n")
+                .as_bytes(),
+        );
+        data.extend_from_slice(members.as_bytes());
+        data.extend_from_slice(b"\nendstream\nendobj\n%%EOF");
+
+        let xref = PdfParser::new(&data).parse().unwrap();
+        let root = xref.trailer.as_ref().and_then(|t| t.get("Root")).and_then(|r| r.as_reference());
+        assert_eq!(root, Some(crate::parser::ObjectReference(1, 0)));
+    }
 }
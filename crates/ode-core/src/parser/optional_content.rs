@@ -0,0 +1,108 @@
+use super::{Dictionary, ObjectReference, PdfObject, PdfRefResolver};
+use std::collections::HashSet;
+
+/// The optional content groups (layers) a document hides, from the default
+/// configuration in the catalog's `/OCProperties`.
+#[derive(Debug, Clone, Default)]
+pub struct OptionalContent {
+    hidden: HashSet<ObjectReference>,
+}
+
+impl OptionalContent {
+    /// Read `/OCProperties /D`: every group is shown unless `/OFF` lists it,
+    /// or, with `/BaseState /OFF`, hidden unless `/ON` does. Groups whose
+    /// `/Name` is in `enabled` are shown regardless.
+    pub fn from_catalog(
+        resolver: &PdfRefResolver,
+        catalog: Option<&Dictionary>,
+        enabled: &[String],
+    ) -> Self {
+        let dict = |obj: Option<&PdfObject>| match obj.and_then(|o| resolve(o, resolver)) {
+            Some(PdfObject::Dictionary(dict)) => Some(dict),
+            _ => None,
+        };
+        let refs = |obj: Option<&PdfObject>| -> Vec<ObjectReference> {
+            match obj.and_then(|o| resolve(o, resolver)) {
+                Some(PdfObject::Array(items)) => items
+                    .iter()
+                    .filter_map(|item| item.as_reference())
+                    .collect(),
+                _ => Vec::new(),
+            }
+        };
+
+        let Some(properties) = dict(catalog.and_then(|c| c.get("OCProperties"))) else {
+            return Self::default();
+        };
+        let Some(default) = dict(properties.get("D")) else {
+            return Self::default();
+        };
+        let mut hidden: HashSet<ObjectReference> =
+            match default.get("BaseState").and_then(|s| s.as_name()) {
+                Some("OFF") => {
+                    let on = refs(default.get("ON"));
+                    refs(properties.get("OCGs"))
+                        .into_iter()
+                        .filter(|group| !on.contains(group))
+                        .collect()
+                }
+                _ => refs(default.get("OFF")).into_iter().collect(),
+            };
+
+        hidden.retain(|group| {
+            let name = match resolver.dereference(*group) {
+                Some(PdfObject::Dictionary(group)) => group.get("Name").and_then(group_name),
+                _ => None,
+            };
+            !name.is_some_and(|name| enabled.contains(&name))
+        });
+        Self { hidden }
+    }
+
+    /// Whether content marked as belonging to `membership`, a group or an
+    /// `/OCMD` membership dictionary, is hidden.
+    pub fn hides(&self, membership: &PdfObject, resolver: &PdfRefResolver) -> bool {
+        if let Some(group) = membership.as_reference() {
+            if self.hidden.contains(&group) {
+                return true;
+            }
+        }
+        let Some(PdfObject::Dictionary(dict)) = resolve(membership, resolver) else {
+            return false;
+        };
+        if dict.get("Type").and_then(|t| t.as_name()) != Some("OCMD") {
+            return false;
+        }
+
+        let groups: Vec<ObjectReference> = match dict.get("OCGs") {
+            Some(PdfObject::Array(items)) => {
+                items.iter().filter_map(|g| g.as_reference()).collect()
+            }
+            Some(group) => group.as_reference().into_iter().collect(),
+            None => Vec::new(),
+        };
+        if groups.is_empty() {
+            return false;
+        }
+        let off = groups.iter().filter(|g| self.hidden.contains(g)).count();
+        match dict.get("P").and_then(|p| p.as_name()) {
+            Some("AllOn") => off > 0,
+            Some("AnyOff") => off == 0,
+            Some("AllOff") => off < groups.len(),
+            // AnyOn, the default
+            _ => off == groups.len(),
+        }
+    }
+}
+
+fn group_name(name: &PdfObject) -> Option<String> {
+    name.as_text_string()
+        .or_else(|| name.as_name().map(str::to_string))
+}
+
+fn resolve(obj: &PdfObject, resolver: &PdfRefResolver) -> Option<PdfObject> {
+    match obj.as_reference() {
+        Some(r) => resolver.dereference(r),
+        None => Some(obj.clone()),
+    }
+}
@@ -0,0 +1,75 @@
+use super::annotations::DestinationResolver;
+use super::{Dictionary, ObjectReference, PdfObject, PdfRefResolver};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Maximum nesting of outline items.
+const MAX_OUTLINE_DEPTH: usize = 32;
+
+/// A bookmark from the document outline.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Outline {
+    pub title: String,
+    /// The 1-based page the bookmark jumps to, when it targets one.
+    pub dest_page: Option<usize>,
+    pub children: Vec<Outline>,
+}
+
+/// The bookmarks under the catalog's `/Outlines`, in display order.
+pub fn extract_outline(
+    resolver: &PdfRefResolver,
+    destinations: &DestinationResolver,
+    catalog: &Dictionary,
+) -> Vec<Outline> {
+    let root = match catalog.get("Outlines").and_then(|o| resolve(o, resolver)) {
+        Some(PdfObject::Dictionary(root)) => root,
+        _ => return Vec::new(),
+    };
+    let mut visited = HashSet::new();
+    outline_items(resolver, destinations, &root, &mut visited, 0)
+}
+
+/// The children of `parent`, from its `/First` along their `/Next` chain.
+/// `visited` stops items that link back into the chain being walked.
+fn outline_items(
+    resolver: &PdfRefResolver,
+    destinations: &DestinationResolver,
+    parent: &Dictionary,
+    visited: &mut HashSet<ObjectReference>,
+    depth: usize,
+) -> Vec<Outline> {
+    let mut items = Vec::new();
+    if depth > MAX_OUTLINE_DEPTH {
+        return items;
+    }
+    let mut next = parent.get("First").and_then(|f| f.as_reference());
+    while let Some(item_ref) = next {
+        if !visited.insert(item_ref) {
+            break;
+        }
+        let item = match resolver.dereference(item_ref) {
+            Some(PdfObject::Dictionary(item)) => item,
+            _ => break,
+        };
+        let title = item
+            .get("Title")
+            .and_then(|t| resolve(t, resolver))
+            .and_then(|t| t.as_text_string())
+            .map(|t| t.trim().to_string())
+            .unwrap_or_default();
+        items.push(Outline {
+            title,
+            dest_page: destinations.goto_page(&item),
+            children: outline_items(resolver, destinations, &item, visited, depth + 1),
+        });
+        next = item.get("Next").and_then(|n| n.as_reference());
+    }
+    items
+}
+
+fn resolve(obj: &PdfObject, resolver: &PdfRefResolver) -> Option<PdfObject> {
+    match obj.as_reference() {
+        Some(r) => resolver.dereference(r),
+        None => Some(obj.clone()),
+    }
+}
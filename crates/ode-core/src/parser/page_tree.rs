@@ -1,6 +1,17 @@
+use crate::config::PageBox;
 use crate::error::OdeError;
+use crate::fonts::GlyphWidths;
 
-use super::{Dictionary, ObjectReference, PdfObject, PdfPage, PdfRefResolver};
+use super::annotations::{
+    button_appearance, calculation_order, extract_form_widgets, extract_notes, field_position,
+    DestinationResolver,
+};
+use super::outline::{extract_outline, Outline};
+use super::structure::{extract_structure, StructElement};
+use super::{
+    normalize_rotation, ColorSpace, Dictionary, FormValue, ImageOptions, ObjectReference,
+    OptionalContent, Pattern, PdfObject, PdfPage, PdfRefResolver, Shading,
+};
 
 /// Inherited properties from parent Pages nodes in the page tree.
 /// Per the PDF spec, Resources, MediaBox, CropBox, and Rotate are inheritable.
@@ -9,41 +20,189 @@ struct InheritedProps {
     resources: Option<Dictionary>,
     mediabox: Option<Vec<PdfObject>>,
     cropbox: Option<Vec<PdfObject>>,
-    rotate: Option<i32>,
+    rotate: Option<f64>,
 }
 
 pub struct PageTreeParser<'a> {
     resolver: &'a PdfRefResolver<'a>,
+    catalog: Option<&'a Dictionary>,
+    images: ImageOptions,
+    layers: OptionalContent,
+    structure: bool,
+    page_box: PageBox,
+}
+
+/// The pages, and what the catalog says about them by page number.
+pub struct PageTree {
+    pub pages: Vec<PdfPage>,
+    /// The `/Outlines` bookmarks.
+    pub outline: Vec<Outline>,
+    /// The `/StructTreeRoot` elements, when asked for.
+    pub structure: Vec<StructElement>,
 }
 
 impl<'a> PageTreeParser<'a> {
     pub fn new(resolver: &'a PdfRefResolver<'a>) -> Self {
-        Self { resolver }
+        Self {
+            resolver,
+            catalog: None,
+            images: ImageOptions::default(),
+            layers: OptionalContent::default(),
+            structure: false,
+            page_box: PageBox::default(),
+        }
+    }
+
+    /// Use the document catalog to resolve named link destinations.
+    pub fn with_catalog(mut self, catalog: Option<&'a Dictionary>) -> Self {
+        self.catalog = catalog;
+        self
+    }
+
+    /// Size and encode images as `images` says.
+    pub fn with_image_options(mut self, images: ImageOptions) -> Self {
+        self.images = images;
+        self
+    }
+
+    /// Leave out the marked content of the layers `layers` hides.
+    pub fn with_optional_content(mut self, layers: OptionalContent) -> Self {
+        self.layers = layers;
+        self
+    }
+
+    /// Also read a tagged document's structure tree.
+    pub fn with_structure(mut self, structure: bool) -> Self {
+        self.structure = structure;
+        self
+    }
+
+    /// Size and place pages by `page_box`.
+    pub fn with_page_box(mut self, page_box: PageBox) -> Self {
+        self.page_box = page_box;
+        self
     }
 
     pub fn parse_all_pages(&self, root_ref: ObjectReference) -> Result<Vec<PdfPage>, OdeError> {
+        Ok(self.parse_page_tree(root_ref)?.pages)
+    }
+
+    /// The pages, and the catalog's `/Outlines` and, when asked for,
+    /// `/StructTreeRoot` with their references to pages resolved to page
+    /// numbers.
+    pub fn parse_page_tree(&self, root_ref: ObjectReference) -> Result<PageTree, OdeError> {
         let mut pages = Vec::new();
+        let mut page_refs = Vec::new();
         let inherited = InheritedProps::default();
-        self.traverse_page_tree(root_ref, &mut pages, 0, &inherited)?;
-        Ok(pages)
+        self.traverse_page_tree(root_ref, &mut pages, &mut page_refs, 0, &inherited)?;
+        let destinations = DestinationResolver::new(self.resolver, &page_refs, self.catalog);
+        self.attach_links(&mut pages, &destinations);
+        self.attach_form_widgets(&mut pages);
+        let outline = self
+            .catalog
+            .map(|catalog| extract_outline(self.resolver, &destinations, catalog))
+            .unwrap_or_default();
+        let structure = self
+            .catalog
+            .filter(|_| self.structure)
+            .map(|catalog| extract_structure(self.resolver, &destinations, catalog))
+            .unwrap_or_default();
+        Ok(PageTree {
+            pages,
+            outline,
+            structure,
+        })
+    }
+
+    /// Give each page the AcroForm widgets listed in its /Annots.
+    fn attach_form_widgets(&self, pages: &mut [PdfPage]) {
+        let Some(catalog) = self.catalog else {
+            return;
+        };
+        let widgets = extract_form_widgets(self.resolver, catalog);
+        if widgets.is_empty() {
+            return;
+        }
+        let order = calculation_order(self.resolver, catalog);
+        // (position in /CO, page, position on the page) of every widget
+        let mut tab_order = Vec::new();
+        for (page_index, page) in pages.iter_mut().enumerate() {
+            let annots = page.dict.as_ref().and_then(|d| d.get("Annots"));
+            let annots = match annots.and_then(|a| self.resolve(a)) {
+                Some(PdfObject::Array(annots)) => annots,
+                _ => continue,
+            };
+            let mut on_page = 0;
+            page.form_widgets = annots
+                .iter()
+                .filter_map(|annot| {
+                    let annot = annot.as_reference()?;
+                    let mut widget = widgets.get(&annot)?.clone();
+                    let position = field_position(self.resolver, annot, &order);
+                    tab_order.push((position.unwrap_or(usize::MAX), page_index, on_page));
+                    on_page += 1;
+                    let on = match widget.value {
+                        FormValue::CheckBox(on) | FormValue::RadioButton(on) => on,
+                        FormValue::Text(_) => return Some(widget),
+                    };
+                    if let Some(PdfObject::Dictionary(dict)) = self.resolver.dereference(annot) {
+                        widget.appearance = button_appearance(self.resolver, &dict, on)
+                            .map(|(data, stream_dict)| {
+                                self.form_from_stream(&widget.name, &data, &stream_dict)
+                            });
+                    }
+                    Some(widget)
+                })
+                .collect();
+        }
+
+        tab_order.sort();
+        for (tab_index, (_, page, index)) in tab_order.into_iter().enumerate() {
+            pages[page].form_widgets[index].tab_index = Some(tab_index + 1);
+        }
+    }
+
+    /// Parse each page's /Annots once every page is known, so internal links
+    /// can point at pages later in the tree.
+    fn attach_links(&self, pages: &mut [PdfPage], destinations: &DestinationResolver) {
+        for page in pages.iter_mut() {
+            if let Some(annots) = page.dict.as_ref().and_then(|d| d.get("Annots")) {
+                page.links = destinations.extract_links(annots);
+                page.notes = extract_notes(self.resolver, annots);
+            }
+        }
     }
 
     fn traverse_page_tree(
         &self,
         node_ref: ObjectReference,
         pages: &mut Vec<PdfPage>,
+        page_refs: &mut Vec<Option<ObjectReference>>,
         depth: u32,
         inherited: &InheritedProps,
     ) -> Result<(), OdeError> {
-        if depth > 100 {
-            return Err(OdeError::PdfParseError("Page tree too deep".to_string()));
-        }
-
         let node_obj = self.resolver.dereference(node_ref).ok_or_else(|| {
-            OdeError::PdfParseError(format!("Cannot dereference node {:?}", node_ref))
+            OdeError::malformed(format!("Cannot dereference node {:?}", node_ref))
         })?;
+        self.traverse_node(&node_obj, Some(node_ref), pages, page_refs, depth, inherited)
+    }
 
-        match &node_obj {
+    /// Parse the page tree node `node_obj`, the object `node_ref` refers to
+    /// or, without one, a node written directly into its parent's /Kids.
+    fn traverse_node(
+        &self,
+        node_obj: &PdfObject,
+        node_ref: Option<ObjectReference>,
+        pages: &mut Vec<PdfPage>,
+        page_refs: &mut Vec<Option<ObjectReference>>,
+        depth: u32,
+        inherited: &InheritedProps,
+    ) -> Result<(), OdeError> {
+        if depth > 100 {
+            return Err(OdeError::malformed("Page tree too deep".to_string()));
+        }
+
+        match node_obj {
             PdfObject::Dictionary(dict) => {
                 let type_name = dict.get("Type").and_then(|v| v.as_name());
 
@@ -51,12 +210,13 @@ impl<'a> PageTreeParser<'a> {
                     Some("Page") => {
                         let page = self.parse_page(dict, pages.len(), inherited)?;
                         pages.push(page);
+                        page_refs.push(node_ref);
                     }
                     Some("Pages") => {
-                        self.parse_pages_node(dict, pages, depth, inherited)?;
+                        self.parse_pages_node(dict, pages, page_refs, depth, inherited)?;
                     }
                     _ => {
-                        return Err(OdeError::PdfParseError(format!(
+                        return Err(OdeError::malformed(format!(
                             "Invalid page tree node type: {:?}",
                             type_name
                         )))
@@ -64,7 +224,7 @@ impl<'a> PageTreeParser<'a> {
                 }
             }
             _ => {
-                return Err(OdeError::PdfParseError(
+                return Err(OdeError::malformed(
                     "Page tree node is not a dictionary".to_string(),
                 ))
             }
@@ -77,6 +237,7 @@ impl<'a> PageTreeParser<'a> {
         &self,
         dict: &Dictionary,
         pages: &mut Vec<PdfPage>,
+        page_refs: &mut Vec<Option<ObjectReference>>,
         depth: u32,
         parent_inherited: &InheritedProps,
     ) -> Result<(), OdeError> {
@@ -103,7 +264,7 @@ impl<'a> PageTreeParser<'a> {
 
         if let Some(rot) = dict.get("Rotate") {
             if let Some(r) = rot.as_number() {
-                child_inherited.rotate = Some(r as i32);
+                child_inherited.rotate = Some(r);
             }
         }
 
@@ -128,7 +289,10 @@ impl<'a> PageTreeParser<'a> {
         if let Some(kids) = kids_array {
             for kid in &kids {
                 if let Some(kid_ref) = kid.as_reference() {
-                    self.traverse_page_tree(kid_ref, pages, depth + 1, &child_inherited)?;
+                    self.traverse_page_tree(kid_ref, pages, page_refs, depth + 1, &child_inherited)?;
+                } else if let PdfObject::Dictionary(_) = kid {
+                    // Some writers inline a node instead of referring to it
+                    self.traverse_node(kid, None, pages, page_refs, depth + 1, &child_inherited)?;
                 }
             }
         }
@@ -137,6 +301,13 @@ impl<'a> PageTreeParser<'a> {
     }
 
     /// Resolve a PdfObject to a Dictionary, dereferencing indirect references if needed
+    fn resolve(&self, obj: &PdfObject) -> Option<PdfObject> {
+        match obj.as_reference() {
+            Some(r) => self.resolver.dereference(r),
+            None => Some(obj.clone()),
+        }
+    }
+
     fn resolve_dict(&self, obj: &PdfObject) -> Option<Dictionary> {
         match obj {
             PdfObject::Dictionary(d) => Some(d.clone()),
@@ -160,59 +331,71 @@ impl<'a> PageTreeParser<'a> {
         page_number: usize,
         inherited: &InheritedProps,
     ) -> Result<PdfPage, OdeError> {
-        let mut width = 612.0;
-        let mut height = 792.0;
+        // Left at zero without a MediaBox; `size_unsized_pages` fills it in
+        let mut width = 0.0;
+        let mut height = 0.0;
+        let mut origin = (0.0, 0.0);
         let mut contents = Vec::new();
         let mut fonts = Vec::new();
-        let mut rotation = inherited.rotate.unwrap_or(0);
+        let mut font_refs = std::collections::HashMap::new();
+        let mut rotation = inherited.rotate.unwrap_or(0.0);
 
         // MediaBox: page's own or inherited
-        let mediabox = dict.get("MediaBox")
+        let mediabox = dict
+            .get("MediaBox")
             .and_then(|v| v.as_array().cloned())
-            .or_else(|| inherited.mediabox.clone());
-
-        if let Some(box_array) = mediabox {
-            if box_array.len() >= 4 {
-                if let (Some(x0), Some(y0), Some(x1), Some(y1)) = (
-                    box_array.get(0).and_then(|v| v.as_number()),
-                    box_array.get(1).and_then(|v| v.as_number()),
-                    box_array.get(2).and_then(|v| v.as_number()),
-                    box_array.get(3).and_then(|v| v.as_number()),
-                ) {
-                    width = (x1 - x0).max(1.0);
-                    height = (y1 - y0).max(1.0);
-                }
-            }
-        }
-
-        // CropBox: page's own or inherited (overrides MediaBox dimensions)
-        let cropbox = dict.get("CropBox")
+            .or_else(|| inherited.mediabox.clone())
+            .and_then(|b| page_rect(&b));
+        // CropBox: page's own or inherited, clipped to the MediaBox
+        let cropbox = dict
+            .get("CropBox")
             .and_then(|v| v.as_array().cloned())
-            .or_else(|| inherited.cropbox.clone());
-
-        if let Some(box_array) = cropbox {
-            if box_array.len() >= 4 {
-                if let (Some(x0), Some(y0), Some(x1), Some(y1)) = (
-                    box_array.get(0).and_then(|v| v.as_number()),
-                    box_array.get(1).and_then(|v| v.as_number()),
-                    box_array.get(2).and_then(|v| v.as_number()),
-                    box_array.get(3).and_then(|v| v.as_number()),
-                ) {
-                    width = (x1 - x0).max(1.0);
-                    height = (y1 - y0).max(1.0);
-                }
-            }
+            .or_else(|| inherited.cropbox.clone())
+            .and_then(|b| page_rect(&b));
+        let clip = |rect: [f64; 4]| match mediabox {
+            Some(media) => [
+                rect[0].max(media[0]),
+                rect[1].max(media[1]),
+                rect[2].min(media[2]),
+                rect[3].min(media[3]),
+            ],
+            None => rect,
+        };
+        let cropbox = cropbox.map(clip).or(mediabox);
+        // The other boxes aren't inherited, and default to the CropBox
+        let shown = match self.page_box {
+            PageBox::MediaBox => mediabox,
+            PageBox::CropBox => cropbox,
+            other => dict
+                .get(other.key())
+                .and_then(|v| v.as_array())
+                .and_then(|b| page_rect(b))
+                .map(clip)
+                .or(cropbox),
+        };
+        if let Some([x0, y0, x1, y1]) = shown {
+            width = (x1 - x0).max(1.0);
+            height = (y1 - y0).max(1.0);
+            origin = (x0, y0);
         }
 
         if let Some(rot) = dict.get("Rotate") {
             if let Some(r) = rot.as_number() {
-                rotation = r as i32;
+                rotation = r;
             }
         }
 
+        // Not inheritable, unlike the boxes
+        let user_unit = dict
+            .get("UserUnit")
+            .and_then(|u| u.as_number())
+            .filter(|u| u.is_finite() && *u > 0.0)
+            .unwrap_or(1.0);
+
         if let Some(contents_obj) = dict.get("Contents") {
             contents = self.extract_content_stream(contents_obj)?;
         }
+        let contents_ref = dict.get("Contents").and_then(|c| c.as_reference());
 
         // Resources: page's own or inherited
         let resources_dict = dict.get("Resources")
@@ -220,59 +403,395 @@ impl<'a> PageTreeParser<'a> {
             .or_else(|| inherited.resources.clone());
 
         // Extract fonts from resources
-        if let Some(ref res_dict) = resources_dict {
-            let font_dict_obj = res_dict.get("Font");
-            let font_dict = font_dict_obj.and_then(|obj| self.resolve_dict(obj));
-            if let Some(fd) = font_dict {
-                for (_key, value) in &fd.entries {
-                    if let Some(font_ref) = value.as_reference() {
-                        fonts.push(font_ref);
-                    }
-                }
-            }
+        for (key, font_ref) in self.extract_font_refs(&resources_dict) {
+            fonts.push(font_ref);
+            font_refs.insert(key, font_ref);
         }
 
-        // Extract ToUnicode CMaps for each font
-        let mut font_cmaps = std::collections::HashMap::new();
-        if let Some(ref res_dict) = resources_dict {
-            let font_dict = res_dict.get("Font").and_then(|obj| self.resolve_dict(obj));
-            if let Some(fd) = font_dict {
-                for (font_name, font_ref_obj) in &fd.entries {
-                    if let Some(font_ref) = font_ref_obj.as_reference() {
-                        if let Some(font_obj) = self.resolver.dereference(font_ref) {
-                            if let PdfObject::Dictionary(ref font_d) = font_obj {
-                                if let Some(tounicode_ref_obj) = font_d.get("ToUnicode") {
-                                    if let Some(tounicode_ref) = tounicode_ref_obj.as_reference() {
-                                        if let Some(PdfObject::Stream(cmap_data, _)) = self.resolver.dereference(tounicode_ref) {
-                                            let cmap = super::ToUnicodeCMap::parse(&cmap_data);
-                                            font_cmaps.insert(font_name.clone(), cmap);
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        }
+        let font_cmaps = self.extract_font_cmaps(&resources_dict);
 
         // Extract XObjects (images and forms) from Resources
         let (images, form_xobjects) = self.extract_xobjects(&resources_dict);
+        let color_spaces = self.extract_color_spaces(&resources_dict);
+        let font_widths = self.extract_font_widths(&resources_dict);
+        let type3_fonts = self.extract_type3_fonts(&resources_dict);
+        let shadings = self.extract_shadings(&resources_dict);
+        let patterns = self.extract_patterns(&resources_dict);
+        let hidden_content = self.extract_hidden_content(&resources_dict);
 
         Ok(PdfPage {
             page_number,
             width,
             height,
             contents,
+            contents_ref,
             fonts,
-            rotation,
+            font_refs,
+            rotation: normalize_rotation(rotation),
+            user_unit,
+            origin,
             dict: Some(dict.clone()),
             font_cmaps,
+            font_widths,
+            type3_fonts,
             images,
             form_xobjects,
+            color_spaces,
+            shadings,
+            patterns,
+            hidden_content,
+            links: Vec::new(),
+            notes: Vec::new(),
+            form_widgets: Vec::new(),
         })
     }
 
+    /// Resolve every entry of the /ColorSpace resource dictionary so the
+    /// renderer can look up names selected by `cs`/`CS`.
+    fn extract_color_spaces(
+        &self,
+        resources_dict: &Option<Dictionary>,
+    ) -> std::collections::HashMap<String, ColorSpace> {
+        let mut color_spaces = std::collections::HashMap::new();
+        let cs_dict = resources_dict
+            .as_ref()
+            .and_then(|res| res.get("ColorSpace"))
+            .and_then(|obj| self.resolve_dict(obj));
+        if let Some(cs_dict) = cs_dict {
+            for (name, value) in &cs_dict.entries {
+                if let Some(cs) = ColorSpace::resolve(value, self.resolver, resources_dict.as_ref()) {
+                    color_spaces.insert(name.clone(), cs);
+                }
+            }
+        }
+        color_spaces
+    }
+
+    /// Resolve every axial and radial shading in the /Shading resource
+    /// dictionary so `sh` can paint them by name.
+    fn extract_shadings(
+        &self,
+        resources_dict: &Option<Dictionary>,
+    ) -> std::collections::HashMap<String, Shading> {
+        let mut shadings = std::collections::HashMap::new();
+        let shading_dict = resources_dict
+            .as_ref()
+            .and_then(|res| res.get("Shading"))
+            .and_then(|obj| self.resolve_dict(obj));
+        if let Some(shading_dict) = shading_dict {
+            for (name, value) in &shading_dict.entries {
+                let shading = Shading::resolve(value, self.resolver, resources_dict.as_ref());
+                if let Some(shading) = shading {
+                    shadings.insert(name.clone(), shading);
+                }
+            }
+        }
+        shadings
+    }
+
+    /// Resolve every tiling and shading pattern in the /Pattern resource
+    /// dictionary so `scn` can select them by name.
+    fn extract_patterns(
+        &self,
+        resources_dict: &Option<Dictionary>,
+    ) -> std::collections::HashMap<String, Pattern> {
+        let mut patterns = std::collections::HashMap::new();
+        let pattern_dict = resources_dict
+            .as_ref()
+            .and_then(|res| res.get("Pattern"))
+            .and_then(|obj| self.resolve_dict(obj));
+        if let Some(pattern_dict) = pattern_dict {
+            for (name, value) in &pattern_dict.entries {
+                let pattern = Pattern::resolve(value, self.resolver, resources_dict.as_ref());
+                if let Some(pattern) = pattern {
+                    patterns.insert(name.clone(), pattern);
+                }
+            }
+        }
+        patterns
+    }
+
+    /// The names in the /Properties resource dictionary that `BDC` can mark
+    /// hidden optional content with.
+    fn extract_hidden_content(
+        &self,
+        resources_dict: &Option<Dictionary>,
+    ) -> std::collections::HashSet<String> {
+        let properties = resources_dict
+            .as_ref()
+            .and_then(|res| res.get("Properties"))
+            .and_then(|obj| self.resolve_dict(obj));
+        properties
+            .map(|properties| {
+                properties
+                    .entries
+                    .iter()
+                    .filter(|(_, membership)| self.layers.hides(membership, self.resolver))
+                    .map(|(name, _)| name.clone())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Read the advance widths of every font in the /Font resource dictionary
+    /// that declares them.
+    /// The `/ToUnicode` CMap of each font in the resources, keyed by
+    /// resource name. Simple fonts without a usable one get a single-byte
+    /// map built from their `/Encoding` instead.
+    fn extract_font_cmaps(
+        &self,
+        resources_dict: &Option<Dictionary>,
+    ) -> std::collections::HashMap<String, super::ToUnicodeCMap> {
+        let mut font_cmaps = std::collections::HashMap::new();
+        let font_dict = resources_dict
+            .as_ref()
+            .and_then(|res| res.get("Font"))
+            .and_then(|obj| self.resolve_dict(obj));
+        if let Some(font_dict) = font_dict {
+            for (name, value) in &font_dict.entries {
+                let Some(font) = self.resolve_dict(value) else {
+                    continue;
+                };
+                let to_unicode = font
+                    .get("ToUnicode")
+                    .and_then(|obj| obj.as_reference())
+                    .and_then(|r| self.resolver.dereference(r));
+                let cmap = match to_unicode {
+                    Some(PdfObject::Stream(cmap_data, _)) => {
+                        Some(super::ToUnicodeCMap::parse(&cmap_data))
+                    }
+                    _ => None,
+                };
+                let cmap = cmap
+                    .filter(|cmap| !cmap.char_map.is_empty())
+                    .or_else(|| self.encoding_cmap(&font));
+                if let Some(cmap) = cmap {
+                    font_cmaps.insert(name.clone(), cmap);
+                }
+            }
+        }
+        font_cmaps
+    }
+
+    /// The text a simple font's codes stand for by its `/Encoding`: a base
+    /// encoding name, or a dictionary with an optional `/BaseEncoding`
+    /// (`StandardEncoding` when absent) and `/Differences` naming glyphs.
+    /// Type3 fonts have no built-in encoding, so only the `/BaseEncoding`
+    /// they name sits under their `/Differences`. `None` for Type0 fonts,
+    /// encodings this can't read and encodings naming no known character.
+    fn encoding_cmap(&self, font: &Dictionary) -> Option<super::ToUnicodeCMap> {
+        use crate::fonts::encoding::{simple_font_unicode, BaseEncoding};
+
+        let subtype = font.get("Subtype").and_then(|s| s.as_name());
+        if subtype == Some("Type0") {
+            return None;
+        }
+        let encoding = font.get("Encoding")?;
+        let (base, differences) = match encoding.as_name() {
+            Some(name) => (Some(BaseEncoding::from_name(name)?), Vec::new()),
+            None => {
+                let encoding = self.resolve_dict(encoding)?;
+                let base = encoding
+                    .get("BaseEncoding")
+                    .and_then(|b| b.as_name())
+                    .and_then(BaseEncoding::from_name);
+                let base = match subtype {
+                    Some("Type3") => base,
+                    _ => Some(base.unwrap_or(BaseEncoding::Standard)),
+                };
+                (base, encoding_differences(&encoding))
+            }
+        };
+        let char_map = simple_font_unicode(base, &differences);
+        (!char_map.is_empty()).then_some(super::ToUnicodeCMap {
+            char_map,
+            is_single_byte: true,
+        })
+    }
+
+    fn extract_font_widths(
+        &self,
+        resources_dict: &Option<Dictionary>,
+    ) -> std::collections::HashMap<String, GlyphWidths> {
+        let mut font_widths = std::collections::HashMap::new();
+        let font_dict = resources_dict
+            .as_ref()
+            .and_then(|res| res.get("Font"))
+            .and_then(|obj| self.resolve_dict(obj));
+        if let Some(font_dict) = font_dict {
+            for (name, value) in &font_dict.entries {
+                let widths = self
+                    .resolve_dict(value)
+                    .and_then(|font| GlyphWidths::from_font_dict(&font, self.resolver));
+                if let Some(widths) = widths {
+                    font_widths.insert(name.clone(), widths);
+                }
+            }
+        }
+        font_widths
+    }
+
+    /// The font objects in `resources_dict`'s `/Font`, by resource name, in
+    /// the order the dictionary lists them.
+    fn extract_font_refs(
+        &self,
+        resources_dict: &Option<Dictionary>,
+    ) -> Vec<(String, super::ObjectReference)> {
+        let font_dict = resources_dict
+            .as_ref()
+            .and_then(|res| res.get("Font"))
+            .and_then(|obj| self.resolve_dict(obj));
+        let Some(font_dict) = font_dict else {
+            return Vec::new();
+        };
+        font_dict
+            .entries
+            .iter()
+            .filter_map(|(key, value)| Some((key.clone(), value.as_reference()?)))
+            .collect()
+    }
+
+    /// A form XObject stream with its `/BBox`, `/Matrix` and `/Resources`.
+    fn form_from_stream(
+        &self,
+        name: &str,
+        data: &[u8],
+        stream_dict: &Dictionary,
+    ) -> super::FormXObject {
+        // Extract BBox
+        let bbox = stream_dict.get("BBox")
+            .and_then(|v| if let PdfObject::Array(arr) = v {
+                if arr.len() >= 4 {
+                    Some([
+                        arr[0].as_number().unwrap_or(0.0),
+                        arr[1].as_number().unwrap_or(0.0),
+                        arr[2].as_number().unwrap_or(0.0),
+                        arr[3].as_number().unwrap_or(0.0),
+                    ])
+                } else { None }
+            } else { None })
+            .unwrap_or([0.0, 0.0, 0.0, 0.0]);
+
+        // Extract Matrix (optional)
+        let matrix = stream_dict.get("Matrix")
+            .and_then(|v| if let PdfObject::Array(arr) = v {
+                if arr.len() >= 6 {
+                    Some([
+                        arr[0].as_number().unwrap_or(1.0),
+                        arr[1].as_number().unwrap_or(0.0),
+                        arr[2].as_number().unwrap_or(0.0),
+                        arr[3].as_number().unwrap_or(1.0),
+                        arr[4].as_number().unwrap_or(0.0),
+                        arr[5].as_number().unwrap_or(0.0),
+                    ])
+                } else { None }
+            } else { None });
+
+        // Extract form's own Resources
+        let form_resources = stream_dict.get("Resources")
+            .and_then(|obj| self.resolve_dict(obj));
+
+        self.form_xobject(name, data, form_resources, bbox, matrix)
+    }
+
+    /// A form drawing `content_stream`, with everything its `resources`
+    /// provide already extracted.
+    fn form_xobject(
+        &self,
+        name: &str,
+        content_stream: &[u8],
+        resources: Option<Dictionary>,
+        bbox: [f64; 4],
+        matrix: Option<[f64; 6]>,
+    ) -> super::FormXObject {
+        let font_refs = self.extract_font_refs(&resources).into_iter().collect();
+        let font_cmaps = self.extract_font_cmaps(&resources);
+
+        // Recursively extract nested XObjects from form's resources
+        let (images, form_xobjects) = self.extract_xobjects(&resources);
+        let color_spaces = self.extract_color_spaces(&resources);
+        let font_widths = self.extract_font_widths(&resources);
+        let hidden_content = self.extract_hidden_content(&resources);
+
+        super::FormXObject {
+            name: name.to_string(),
+            content_stream: content_stream.to_vec(),
+            resources,
+            bbox,
+            matrix,
+            font_refs,
+            font_cmaps,
+            font_widths,
+            images,
+            color_spaces,
+            form_xobjects,
+            hidden_content,
+        }
+    }
+
+    /// The page's Type3 fonts, with each glyph procedure the encoding names
+    /// turned into a form drawn through the font's `/FontMatrix`.
+    fn extract_type3_fonts(
+        &self,
+        resources_dict: &Option<Dictionary>,
+    ) -> std::collections::HashMap<String, super::Type3Font> {
+        let mut type3_fonts = std::collections::HashMap::new();
+        let font_dict = resources_dict
+            .as_ref()
+            .and_then(|res| res.get("Font"))
+            .and_then(|obj| self.resolve_dict(obj));
+        let Some(font_dict) = font_dict else {
+            return type3_fonts;
+        };
+
+        for (name, value) in &font_dict.entries {
+            let Some(font) = self.resolve_dict(value) else {
+                continue;
+            };
+            if font.get("Subtype").and_then(|s| s.as_name()) != Some("Type3") {
+                continue;
+            }
+            let numbers = |key: &str| match font.get(key).and_then(|v| self.resolve(v)) {
+                Some(PdfObject::Array(values)) => {
+                    values.iter().map(|v| v.as_number().unwrap_or(0.0)).collect()
+                }
+                _ => Vec::new(),
+            };
+            let matrix = match numbers("FontMatrix")[..] {
+                [a, b, c, d, e, f, ..] => [a, b, c, d, e, f],
+                _ => [0.001, 0.0, 0.0, 0.001, 0.0, 0.0],
+            };
+            let bbox = match numbers("FontBBox")[..] {
+                [x0, y0, x1, y1, ..] => [x0, y0, x1, y1],
+                _ => [0.0; 4],
+            };
+            let char_procs = font.get("CharProcs").and_then(|obj| self.resolve_dict(obj));
+            let encoding = font.get("Encoding").and_then(|obj| self.resolve_dict(obj));
+            let (Some(char_procs), Some(encoding)) = (char_procs, encoding) else {
+                continue;
+            };
+            let resources = font.get("Resources").and_then(|obj| self.resolve_dict(obj));
+
+            let mut glyphs = std::collections::HashMap::new();
+            for (code, glyph_name) in encoding_differences(&encoding) {
+                let procedure = char_procs.get(&glyph_name).and_then(|p| self.resolve(p));
+                if let Some(PdfObject::Stream(data, _)) = procedure {
+                    let glyph = self.form_xobject(
+                        &glyph_name,
+                        &data,
+                        resources.clone(),
+                        bbox,
+                        Some(matrix),
+                    );
+                    glyphs.insert(code, glyph);
+                }
+            }
+            let has_to_unicode = font.get("ToUnicode").is_some();
+            type3_fonts.insert(name.clone(), super::Type3Font { glyphs, has_to_unicode });
+        }
+        type3_fonts
+    }
+
     fn extract_xobjects(
         &self,
         resources_dict: &Option<Dictionary>,
@@ -306,23 +825,66 @@ impl<'a> PageTreeParser<'a> {
                 let subtype = stream_dict.get("Subtype").and_then(|v| v.as_name());
 
                 if subtype == Some("Image") {
-                    let img_w = stream_dict.get("Width").and_then(|v| v.as_number()).unwrap_or(0.0) as u32;
-                    let img_h = stream_dict.get("Height").and_then(|v| v.as_number()).unwrap_or(0.0) as u32;
+                    let mut img_w = stream_dict.get("Width").and_then(|v| v.as_number()).unwrap_or(0.0) as u32;
+                    let mut img_h = stream_dict.get("Height").and_then(|v| v.as_number()).unwrap_or(0.0) as u32;
                     let filter = stream_dict.get("Filter").and_then(|v| v.as_name()).unwrap_or("");
+                    // A registered JBIG2 decoder leaves one bit per pixel with
+                    // 1 for black, the opposite of DeviceGray
+                    let jbig2_samples: Option<Vec<u8>> =
+                        (filter == "JBIG2Decode" && self.resolver.has_decoder(filter))
+                            .then(|| data.iter().map(|byte| !byte).collect());
+                    let data = jbig2_samples.as_ref().unwrap_or(data);
+                    let mut unsupported_filter = None;
 
                     let (img_data, mime) = match filter {
-                        "DCTDecode" => (data.clone(), "image/jpeg"),
+                        "DCTDecode" => match self.images.shrink_jpeg(data) {
+                            Some((jpeg, width, height)) => {
+                                (img_w, img_h) = (width, height);
+                                (jpeg, "image/jpeg")
+                            }
+                            None => (data.clone(), "image/jpeg"),
+                        },
                         "JPXDecode" => (data.clone(), "image/jp2"),
-                        "FlateDecode" => {
-                            let colorspace = stream_dict.get("ColorSpace").and_then(|v| v.as_name()).unwrap_or("DeviceRGB");
-                            let channels: u8 = match colorspace {
-                                "DeviceGray" => 1,
-                                "DeviceRGB" => 3,
-                                "DeviceCMYK" => 4,
-                                _ => 3,
+                        "JBIG2Decode" if jbig2_samples.is_none() => {
+                            // Draw a gray box where the scan goes rather than
+                            // nothing, and say why
+                            unsupported_filter = Some(filter.to_string());
+                            (img_w, img_h) = (1, 1);
+                            (encode_raw_pixels_as_png(&[0xC0], 1, 1, 1), "image/png")
+                        }
+                        "FlateDecode" | "JBIG2Decode" => {
+                            let decode: Vec<f64> = stream_dict.get("Decode")
+                                .and_then(|v| v.as_array())
+                                .map(|arr| arr.iter().filter_map(|n| n.as_number()).collect())
+                                .unwrap_or_default();
+                            let image_mask = matches!(stream_dict.get("ImageMask"), Some(PdfObject::Boolean(true)));
+
+                            let (encoded, mime, width, height) = if image_mask {
+                                // Stencil masks are always 1 bit per sample
+                                let samples = ColorSpace::DeviceGray
+                                    .apply_decode(data, img_w, 1, &decode)
+                                    .unwrap_or_else(|| data.clone());
+                                self.images.encode_pixels(&stencil_mask_pixels(&samples, img_w, img_h), img_w, img_h, 4)
+                            } else {
+                                let colorspace = stream_dict.get("ColorSpace")
+                                    .and_then(|cs| ColorSpace::resolve(cs, self.resolver, Some(res_dict)))
+                                    .unwrap_or(ColorSpace::DeviceRGB);
+                                let bpc = stream_dict.get("BitsPerComponent").and_then(|v| v.as_integer()).unwrap_or(8) as u8;
+                                let remapped = colorspace.apply_decode(data, img_w, bpc, &decode);
+                                let samples = remapped.as_deref().unwrap_or(data);
+                                let components = colorspace.components();
+                                match colorspace.expand_indexed(samples, img_w, img_h, bpc) {
+                                    Some((pixels, channels)) => self.images.encode_pixels(&pixels, img_w, img_h, channels),
+                                    // Only 8-bit samples can be scaled down
+                                    None if bpc == 8 => self.images.encode_pixels(samples, img_w, img_h, components),
+                                    None => {
+                                        let png = encode_raw_pixels_as_png(samples, img_w, img_h, components);
+                                        (png, "image/png", img_w, img_h)
+                                    }
+                                }
                             };
-                            let png_data = encode_raw_pixels_as_png(data, img_w, img_h, channels);
-                            (png_data, "image/png")
+                            (img_w, img_h) = (width, height);
+                            (encoded, mime)
                         }
                         _ => continue,
                     };
@@ -333,78 +895,13 @@ impl<'a> PageTreeParser<'a> {
                         width: img_w,
                         height: img_h,
                         mime_type: mime.to_string(),
+                        unsupported_filter,
                     });
                 } else if subtype == Some("Form") {
-                    // Extract BBox
-                    let bbox = stream_dict.get("BBox")
-                        .and_then(|v| if let PdfObject::Array(arr) = v {
-                            if arr.len() >= 4 {
-                                Some([
-                                    arr[0].as_number().unwrap_or(0.0),
-                                    arr[1].as_number().unwrap_or(0.0),
-                                    arr[2].as_number().unwrap_or(0.0),
-                                    arr[3].as_number().unwrap_or(0.0),
-                                ])
-                            } else { None }
-                        } else { None })
-                        .unwrap_or([0.0, 0.0, 0.0, 0.0]);
-
-                    // Extract Matrix (optional)
-                    let matrix = stream_dict.get("Matrix")
-                        .and_then(|v| if let PdfObject::Array(arr) = v {
-                            if arr.len() >= 6 {
-                                Some([
-                                    arr[0].as_number().unwrap_or(1.0),
-                                    arr[1].as_number().unwrap_or(0.0),
-                                    arr[2].as_number().unwrap_or(0.0),
-                                    arr[3].as_number().unwrap_or(1.0),
-                                    arr[4].as_number().unwrap_or(0.0),
-                                    arr[5].as_number().unwrap_or(0.0),
-                                ])
-                            } else { None }
-                        } else { None });
-
-                    // Extract form's own Resources
-                    let form_resources = stream_dict.get("Resources")
-                        .and_then(|obj| self.resolve_dict(obj));
-
-                    // Extract font CMaps from form's resources
-                    let mut form_font_cmaps = std::collections::HashMap::new();
-                    if let Some(ref form_res) = form_resources {
-                        let font_dict = form_res.get("Font").and_then(|obj| self.resolve_dict(obj));
-                        if let Some(fd) = font_dict {
-                            for (font_name, font_ref_obj) in &fd.entries {
-                                if let Some(font_ref) = font_ref_obj.as_reference() {
-                                    if let Some(font_obj) = self.resolver.dereference(font_ref) {
-                                        if let PdfObject::Dictionary(ref font_d) = font_obj {
-                                            if let Some(tounicode_ref_obj) = font_d.get("ToUnicode") {
-                                                if let Some(tounicode_ref) = tounicode_ref_obj.as_reference() {
-                                                    if let Some(PdfObject::Stream(cmap_data, _)) = self.resolver.dereference(tounicode_ref) {
-                                                        let cmap = super::ToUnicodeCMap::parse(&cmap_data);
-                                                        form_font_cmaps.insert(font_name.clone(), cmap);
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-
-                    // Recursively extract nested XObjects from form's resources
-                    let (form_images, nested_forms) = self.extract_xobjects(&form_resources);
-
-                    form_xobjects.insert(xobj_name.clone(), super::FormXObject {
-                        name: xobj_name.clone(),
-                        content_stream: data.clone(),
-                        resources: form_resources,
-                        bbox,
-                        matrix,
-                        font_cmaps: form_font_cmaps,
-                        images: form_images,
-                        form_xobjects: nested_forms,
-                    });
+                    form_xobjects.insert(
+                        xobj_name.clone(),
+                        self.form_from_stream(xobj_name, data, stream_dict),
+                    );
                 }
             }
         }
@@ -417,7 +914,7 @@ impl<'a> PageTreeParser<'a> {
             PdfObject::Stream(ref data, _dict) => Ok(data.clone()),
             PdfObject::IndirectReference { obj_id, gen } => {
                 let obj = self.resolver.dereference(super::ObjectReference(*obj_id, *gen))
-                    .ok_or_else(|| OdeError::PdfParseError(
+                    .ok_or_else(|| OdeError::malformed(
                         format!("Cannot dereference /Contents {} {} R", obj_id, gen)
                     ))?;
                 match obj {
@@ -445,8 +942,68 @@ impl<'a> PageTreeParser<'a> {
     }
 }
 
+/// A box array as `[x0, y0, x1, y1]` with the lower-left corner first,
+/// whichever corners it names.
+fn page_rect(array: &[PdfObject]) -> Option<[f64; 4]> {
+    let [x0, y0, x1, y1] = [0, 1, 2, 3].map(|i| array.get(i).and_then(|v| v.as_number()));
+    let (x0, y0, x1, y1) = (x0?, y0?, x1?, y1?);
+    Some([x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)])
+}
+
+/// The glyph names an `/Encoding` dictionary's `/Differences` array gives
+/// codes: each number starts a run of names for consecutive codes.
+fn encoding_differences(encoding: &Dictionary) -> Vec<(u8, String)> {
+    let mut names = Vec::new();
+    let Some(differences) = encoding.get("Differences").and_then(|d| d.as_array()) else {
+        return names;
+    };
+    let mut code = 0usize;
+    for item in differences {
+        match item {
+            PdfObject::Name(name) => {
+                if let Ok(byte) = u8::try_from(code) {
+                    names.push((byte, name.clone()));
+                }
+                code = code.saturating_add(1);
+            }
+            other => {
+                if let Some(start) = other.as_number().filter(|start| start.is_finite()) {
+                    code = start.max(0.0) as usize;
+                }
+            }
+        }
+    }
+    names
+}
+
+/// Expand a 1-bit stencil mask into RGBA pixels.
+///
+/// Samples of 0 are painted and 1 are left transparent (after `/Decode`).
+/// The paint color is the fill color at `Do` time, which isn't known here,
+/// so painted samples use opaque black.
+fn stencil_mask_pixels(samples: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let row_bytes = (width as usize).div_ceil(8);
+    let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+    for row in 0..height as usize {
+        let row_data = samples.get(row * row_bytes..).unwrap_or(&[]);
+        for col in 0..width as usize {
+            let painted = row_data
+                .get(col / 8)
+                .map(|&byte| (byte >> (7 - col % 8)) & 1 == 0)
+                .unwrap_or(false);
+            pixels.extend_from_slice(if painted { &[0, 0, 0, 255] } else { &[0, 0, 0, 0] });
+        }
+    }
+    pixels
+}
+
 /// Encode raw pixel data as a valid PNG file.
-fn encode_raw_pixels_as_png(pixels: &[u8], width: u32, height: u32, channels: u8) -> Vec<u8> {
+pub(super) fn encode_raw_pixels_as_png(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    channels: u8,
+) -> Vec<u8> {
     use flate2::write::ZlibEncoder;
     use flate2::Compression;
     use std::io::Write;
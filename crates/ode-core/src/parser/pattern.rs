@@ -0,0 +1,111 @@
+use super::{ContentStreamParser, Dictionary, PdfObject, PdfRefResolver, Shading};
+use crate::renderer::ContentOp;
+use crate::types::color::Color;
+
+/// A `/Pattern` resource, selected with `scn`/`SCN` in a Pattern color space.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    /// `PatternType` 1. Colored tiles are approximated by the average of the
+    /// colors their cell fills with; uncolored ones (`PaintType` 2) are
+    /// painted in the color `scn` gives before the name, so have none.
+    Tiling { color: Option<Color> },
+    /// `PatternType` 2, whose `/Matrix` maps pattern space onto the page's
+    /// default user space.
+    Shading {
+        shading: Box<Shading>,
+        matrix: [f64; 6],
+    },
+}
+
+impl Pattern {
+    /// Read a pattern; `resources` resolves a shading's named `/ColorSpace`.
+    pub fn resolve(
+        obj: &PdfObject,
+        resolver: &PdfRefResolver,
+        resources: Option<&Dictionary>,
+    ) -> Option<Self> {
+        let resolved = match obj.as_reference() {
+            Some(r) => resolver.dereference(r)?,
+            None => obj.clone(),
+        };
+        let (data, dict) = match resolved {
+            PdfObject::Stream(data, dict) => (data, dict),
+            PdfObject::Dictionary(dict) => (Vec::new(), dict),
+            _ => return None,
+        };
+        match dict.get("PatternType").and_then(|t| t.as_integer())? {
+            1 if dict.get("PaintType").and_then(|t| t.as_integer()) == Some(2) => {
+                Some(Pattern::Tiling { color: None })
+            }
+            1 => Some(Pattern::Tiling {
+                color: Some(average_fill_color(&data)),
+            }),
+            2 => {
+                let matrix = match dict.get("Matrix").and_then(|m| m.as_array()) {
+                    Some(m) if m.len() >= 6 => {
+                        let n = |i: usize| m[i].as_number().unwrap_or(0.0);
+                        [n(0), n(1), n(2), n(3), n(4), n(5)]
+                    }
+                    _ => [1.0, 0.0, 0.0, 1.0, 0.0, 0.0],
+                };
+                Some(Pattern::Shading {
+                    shading: Box::new(Shading::resolve(dict.get("Shading")?, resolver, resources)?),
+                    matrix,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// One color standing in for the whole pattern: a tiling pattern's
+    /// average, or the middle of a shading's blend.
+    pub fn color(&self) -> Option<Color> {
+        match self {
+            Pattern::Tiling { color } => *color,
+            Pattern::Shading { shading, .. } => {
+                let [t0, t1] = shading.domain;
+                shading.color_at((t0 + t1) / 2.0)
+            }
+        }
+    }
+}
+
+/// The average of the fill colors a tiling cell's content sets, read by
+/// operand count; black, the initial fill, when it sets none.
+fn average_fill_color(content: &[u8]) -> Color {
+    let ops = ContentStreamParser::new(content).parse().unwrap_or_default();
+    let colors: Vec<Color> = ops
+        .iter()
+        .filter(|op| {
+            matches!(
+                op.operator,
+                ContentOp::RGfill
+                    | ContentOp::Gfill
+                    | ContentOp::Kfill
+                    | ContentOp::SC
+                    | ContentOp::SCN
+            )
+        })
+        .filter_map(|op| super::ColorSpace::DeviceRGB.to_rgb(&op.operands))
+        .collect();
+    if colors.is_empty() {
+        return Color::new(0, 0, 0);
+    }
+    let mean = |channel: fn(&Color) -> u8| {
+        let sum: usize = colors.iter().map(|c| channel(c) as usize).sum();
+        (sum / colors.len()) as u8
+    };
+    Color::new(mean(|c| c.r), mean(|c| c.g), mean(|c| c.b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tiling_color_averages_the_cell_fills() {
+        let cell = b"1 0 0 rg 0 0 5 5 re f 0 0 1 rg 5 0 5 5 re f";
+        assert_eq!(average_fill_color(cell), Color::new(127, 0, 127));
+        assert_eq!(average_fill_color(b"0 0 5 5 re f"), Color::new(0, 0, 0));
+    }
+}
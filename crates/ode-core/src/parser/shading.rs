@@ -0,0 +1,236 @@
+use super::colorspace::TintTransform;
+use super::{ColorSpace, Dictionary, PdfObject, PdfRefResolver};
+use crate::types::color::Color;
+
+/// Maximum nesting of stitching functions.
+const MAX_FUNCTION_DEPTH: usize = 8;
+
+/// An axial or radial shading from a `/Shading` resource, painted with `sh`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shading {
+    pub geometry: ShadingGeometry,
+    pub color_space: ColorSpace,
+    /// `/Domain`: the parameter values at the start and end of the blend.
+    pub domain: [f64; 2],
+    pub function: ShadingFunction,
+    /// `/BBox` in shading space; nothing outside it is painted.
+    pub bbox: Option<[f64; 4]>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShadingGeometry {
+    /// `ShadingType` 2: `[x0 y0 x1 y1]`, blending along the line between
+    /// the two points.
+    Axial([f64; 4]),
+    /// `ShadingType` 3: `[x0 y0 r0 x1 y1 r1]`, blending from the first
+    /// circle to the second.
+    Radial([f64; 6]),
+}
+
+/// A shading's `/Function`, mapping its parameter to color components.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShadingFunction {
+    /// Type 2.
+    Exponential(TintTransform),
+    /// Type 3: `functions[i]` covers the part of `domain` from `bounds[i - 1]`
+    /// to `bounds[i]`, with its input mapped onto `encode[2i]..encode[2i + 1]`.
+    Stitching {
+        domain: [f64; 2],
+        functions: Vec<ShadingFunction>,
+        bounds: Vec<f64>,
+        encode: Vec<f64>,
+    },
+    /// An array of one-output functions, one per color component.
+    PerComponent(Vec<ShadingFunction>),
+}
+
+impl ShadingFunction {
+    fn resolve(obj: &PdfObject, resolver: &PdfRefResolver, depth: usize) -> Option<Self> {
+        if depth > MAX_FUNCTION_DEPTH {
+            return None;
+        }
+        let dict = match resolve(obj, resolver)? {
+            PdfObject::Array(functions) => {
+                let functions = functions
+                    .iter()
+                    .map(|f| Self::resolve(f, resolver, depth + 1))
+                    .collect::<Option<Vec<_>>>()?;
+                return Some(ShadingFunction::PerComponent(functions));
+            }
+            PdfObject::Dictionary(dict) | PdfObject::Stream(_, dict) => dict,
+            _ => return None,
+        };
+        match dict.get("FunctionType").and_then(|t| t.as_integer())? {
+            2 => TintTransform::from_dict(&dict).map(ShadingFunction::Exponential),
+            3 => {
+                let functions = match dict.get("Functions").and_then(|f| resolve(f, resolver))? {
+                    PdfObject::Array(functions) => functions
+                        .iter()
+                        .map(|f| Self::resolve(f, resolver, depth + 1))
+                        .collect::<Option<Vec<_>>>()?,
+                    _ => return None,
+                };
+                let bounds = numbers(&dict, "Bounds", resolver).unwrap_or_default();
+                let encode = numbers(&dict, "Encode", resolver)
+                    .unwrap_or_else(|| [0.0, 1.0].repeat(functions.len()));
+                if functions.is_empty()
+                    || bounds.len() + 1 != functions.len()
+                    || encode.len() < 2 * functions.len()
+                {
+                    return None;
+                }
+                Some(ShadingFunction::Stitching {
+                    domain: domain(&dict, resolver),
+                    functions,
+                    bounds,
+                    encode,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    pub fn evaluate(&self, t: f64) -> Vec<f64> {
+        match self {
+            ShadingFunction::Exponential(function) => function.evaluate(t),
+            ShadingFunction::Stitching {
+                domain,
+                functions,
+                bounds,
+                encode,
+            } => {
+                let t = t.clamp(domain[0].min(domain[1]), domain[0].max(domain[1]));
+                let i = bounds.iter().position(|b| t < *b).unwrap_or(bounds.len());
+                let low = if i == 0 { domain[0] } else { bounds[i - 1] };
+                let high = bounds.get(i).copied().unwrap_or(domain[1]);
+                let (e0, e1) = (encode[2 * i], encode[2 * i + 1]);
+                let t = if high > low {
+                    e0 + (t - low) / (high - low) * (e1 - e0)
+                } else {
+                    e0
+                };
+                functions[i].evaluate(t)
+            }
+            ShadingFunction::PerComponent(functions) => functions
+                .iter()
+                .filter_map(|f| f.evaluate(t).first().copied())
+                .collect(),
+        }
+    }
+}
+
+impl Shading {
+    /// Read a shading dictionary; `resources` resolves a named `/ColorSpace`.
+    /// Only axial and radial shadings are supported.
+    pub fn resolve(
+        obj: &PdfObject,
+        resolver: &PdfRefResolver,
+        resources: Option<&Dictionary>,
+    ) -> Option<Self> {
+        let dict = match resolve(obj, resolver)? {
+            PdfObject::Dictionary(dict) | PdfObject::Stream(_, dict) => dict,
+            _ => return None,
+        };
+        let coords = numbers(&dict, "Coords", resolver)?;
+        let geometry = match dict.get("ShadingType").and_then(|t| t.as_integer())? {
+            2 if coords.len() >= 4 => {
+                ShadingGeometry::Axial([coords[0], coords[1], coords[2], coords[3]])
+            }
+            3 if coords.len() >= 6 => ShadingGeometry::Radial([
+                coords[0], coords[1], coords[2], coords[3], coords[4], coords[5],
+            ]),
+            _ => return None,
+        };
+        Some(Shading {
+            geometry,
+            color_space: ColorSpace::resolve(dict.get("ColorSpace")?, resolver, resources)?,
+            domain: domain(&dict, resolver),
+            function: ShadingFunction::resolve(dict.get("Function")?, resolver, 0)?,
+            bbox: numbers(&dict, "BBox", resolver)
+                .filter(|b| b.len() >= 4)
+                .map(|b| [b[0], b[1], b[2], b[3]]),
+        })
+    }
+
+    /// The color at parameter `t` of the domain.
+    pub fn color_at(&self, t: f64) -> Option<Color> {
+        self.color_space.to_rgb(&self.function.evaluate(t))
+    }
+}
+
+fn resolve(obj: &PdfObject, resolver: &PdfRefResolver) -> Option<PdfObject> {
+    match obj.as_reference() {
+        Some(r) => resolver.dereference(r),
+        None => Some(obj.clone()),
+    }
+}
+
+fn numbers(dict: &Dictionary, key: &str, resolver: &PdfRefResolver) -> Option<Vec<f64>> {
+    match resolve(dict.get(key)?, resolver)? {
+        PdfObject::Array(items) => Some(
+            items
+                .iter()
+                .filter_map(|n| resolve(n, resolver)?.as_number())
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+fn domain(dict: &Dictionary, resolver: &PdfRefResolver) -> [f64; 2] {
+    match numbers(dict, "Domain", resolver).as_deref() {
+        Some([t0, t1, ..]) => [*t0, *t1],
+        _ => [0.0, 1.0],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::XRef;
+
+    fn exponential(c0: f64, c1: f64) -> PdfObject {
+        PdfObject::Dictionary(Dictionary {
+            entries: vec![
+                ("FunctionType".to_string(), PdfObject::Integer(2)),
+                (
+                    "C0".to_string(),
+                    PdfObject::Array(vec![PdfObject::Float(c0)]),
+                ),
+                (
+                    "C1".to_string(),
+                    PdfObject::Array(vec![PdfObject::Float(c1)]),
+                ),
+                ("N".to_string(), PdfObject::Integer(1)),
+            ],
+        })
+    }
+
+    #[test]
+    fn test_stitching_function_picks_the_subfunction_for_its_bounds() {
+        let xref = XRef::default();
+        let resolver = PdfRefResolver::new(b"", &xref);
+        let stitching = PdfObject::Dictionary(Dictionary {
+            entries: vec![
+                ("FunctionType".to_string(), PdfObject::Integer(3)),
+                (
+                    "Domain".to_string(),
+                    PdfObject::Array(vec![PdfObject::Integer(0), PdfObject::Integer(1)]),
+                ),
+                (
+                    "Functions".to_string(),
+                    PdfObject::Array(vec![exponential(0.0, 1.0), exponential(1.0, 0.0)]),
+                ),
+                (
+                    "Bounds".to_string(),
+                    PdfObject::Array(vec![PdfObject::Float(0.5)]),
+                ),
+            ],
+        });
+
+        let function = ShadingFunction::resolve(&stitching, &resolver, 0).unwrap();
+        assert_eq!(function.evaluate(0.25), vec![0.5]);
+        assert_eq!(function.evaluate(0.5), vec![1.0]);
+        assert_eq!(function.evaluate(0.75), vec![0.5]);
+    }
+}
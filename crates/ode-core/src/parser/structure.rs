@@ -0,0 +1,183 @@
+use super::annotations::DestinationResolver;
+use super::{language_tag, Dictionary, ObjectReference, PdfObject, PdfRefResolver};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Maximum nesting of structure elements.
+const MAX_STRUCTURE_DEPTH: usize = 64;
+
+/// Most `/RoleMap` steps followed from a custom type to a standard one.
+const MAX_ROLE_MAP_STEPS: usize = 8;
+
+/// An element of a tagged PDF's logical structure, such as a heading,
+/// paragraph or table cell.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StructElement {
+    /// The structure type, such as `H1` or `P`, with custom types mapped to
+    /// standard ones through the `/RoleMap`.
+    pub role: String,
+    /// Replacement text from `/Alt`, given for figures and formulas.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alt: Option<String>,
+    /// The element's language from `/Lang`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lang: Option<String>,
+    pub children: Vec<StructNode>,
+}
+
+/// What a structure element holds, in reading order.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum StructNode {
+    Element(StructElement),
+    /// The marked content with `mcid` on the 1-based `page`.
+    Content {
+        page: usize,
+        mcid: u32,
+    },
+}
+
+/// The elements under the catalog's `/StructTreeRoot`, in reading order,
+/// or none for an untagged document.
+pub fn extract_structure(
+    resolver: &PdfRefResolver,
+    destinations: &DestinationResolver,
+    catalog: &Dictionary,
+) -> Vec<StructElement> {
+    let root = match catalog
+        .get("StructTreeRoot")
+        .and_then(|r| resolve(r, resolver))
+    {
+        Some(PdfObject::Dictionary(root)) => root,
+        _ => return Vec::new(),
+    };
+    let role_map = match root.get("RoleMap").and_then(|m| resolve(m, resolver)) {
+        Some(PdfObject::Dictionary(role_map)) => Some(role_map),
+        _ => None,
+    };
+    let mut walk = StructureWalk {
+        resolver,
+        destinations,
+        role_map,
+        visited: HashSet::new(),
+    };
+    let mut nodes = Vec::new();
+    if let Some(kids) = root.get("K") {
+        walk.kids(kids, None, &mut nodes, 0);
+    }
+    // Content straight under the root belongs to no element
+    nodes
+        .into_iter()
+        .filter_map(|node| match node {
+            StructNode::Element(element) => Some(element),
+            StructNode::Content { .. } => None,
+        })
+        .collect()
+}
+
+struct StructureWalk<'a> {
+    resolver: &'a PdfRefResolver<'a>,
+    destinations: &'a DestinationResolver<'a>,
+    role_map: Option<Dictionary>,
+    /// Elements already read, so one linked back into the tree is read once.
+    visited: HashSet<ObjectReference>,
+}
+
+impl StructureWalk<'_> {
+    /// Read a `/K` entry, one kid or an array of them, onto `nodes`. `page`
+    /// is the `/Pg` of the nearest element that has one.
+    fn kids(
+        &mut self,
+        kids: &PdfObject,
+        page: Option<usize>,
+        nodes: &mut Vec<StructNode>,
+        depth: usize,
+    ) {
+        if depth > MAX_STRUCTURE_DEPTH {
+            return;
+        }
+        if let Some(kid_ref) = kids.as_reference() {
+            if !self.visited.insert(kid_ref) {
+                return;
+            }
+        }
+        let Some(kids) = resolve(kids, self.resolver) else {
+            return;
+        };
+        match kids {
+            PdfObject::Array(kids) => {
+                for kid in &kids {
+                    self.kids(kid, page, nodes, depth + 1);
+                }
+            }
+            PdfObject::Integer(mcid) => {
+                if let (Some(page), Ok(mcid)) = (page, u32::try_from(mcid)) {
+                    nodes.push(StructNode::Content { page, mcid });
+                }
+            }
+            PdfObject::Dictionary(kid) => {
+                let page = self.page(&kid).or(page);
+                match kid.get("Type").and_then(|t| t.as_name()) {
+                    Some("MCR") => {
+                        let mcid = kid.get("MCID").and_then(|m| m.as_integer());
+                        if let (Some(page), Some(Ok(mcid))) = (page, mcid.map(u32::try_from)) {
+                            nodes.push(StructNode::Content { page, mcid });
+                        }
+                    }
+                    // Annotations and XObjects the structure refers to whole
+                    Some("OBJR") => {}
+                    _ => nodes.push(StructNode::Element(self.element(&kid, page, depth))),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn element(&mut self, dict: &Dictionary, page: Option<usize>, depth: usize) -> StructElement {
+        let text = |key: &str| {
+            let value = resolve(dict.get(key)?, self.resolver)?.as_text_string()?;
+            Some(value.trim().to_string()).filter(|value| !value.is_empty())
+        };
+        let alt = text("Alt");
+        let lang = text("Lang").and_then(|lang| language_tag(&lang));
+        let role = dict
+            .get("S")
+            .and_then(|s| s.as_name())
+            .map(|s| self.standard_role(s))
+            .unwrap_or_default();
+        let mut children = Vec::new();
+        if let Some(kids) = dict.get("K") {
+            self.kids(kids, page, &mut children, depth + 1);
+        }
+        StructElement {
+            role,
+            alt,
+            lang,
+            children,
+        }
+    }
+
+    /// `role` followed through the `/RoleMap` as far as it goes.
+    fn standard_role(&self, role: &str) -> String {
+        let mut role = role.to_string();
+        for _ in 0..MAX_ROLE_MAP_STEPS {
+            let mapped = self.role_map.as_ref().and_then(|map| map.get(&role));
+            match mapped.and_then(|r| r.as_name()) {
+                Some(mapped) if mapped != role => role = mapped.to_string(),
+                _ => break,
+            }
+        }
+        role
+    }
+
+    fn page(&self, dict: &Dictionary) -> Option<usize> {
+        self.destinations
+            .page_number(dict.get("Pg")?.as_reference()?)
+    }
+}
+
+fn resolve(obj: &PdfObject, resolver: &PdfRefResolver) -> Option<PdfObject> {
+    match obj.as_reference() {
+        Some(r) => resolver.dereference(r),
+        None => Some(obj.clone()),
+    }
+}
@@ -1,5 +1,6 @@
+use crate::parser::ColorSpace;
 use crate::types::color::Color;
-use crate::util::math::TransformMatrix;
+use crate::util::math::{BoundingBox, TransformMatrix};
 use std::fmt;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -35,10 +36,30 @@ pub struct GraphicsState {
     pub font_size: f64,
     pub fill_color: Color,
     pub stroke_color: Color,
+    pub fill_color_space: ColorSpace,
+    pub stroke_color_space: ColorSpace,
+    /// The pattern `scn` last named in a Pattern fill space.
+    pub fill_pattern: Option<String>,
     pub letter_space: f64,
     pub word_space: f64,
+    /// Text leading set with `TL` or `TD`: how far `T*` moves down a line.
+    pub leading: f64,
+    /// Text rendering mode set with `Tr`. Modes 4 to 7 also add the glyphs
+    /// to the clip; mode 7 only does that, painting nothing.
+    pub render_mode: u8,
+    /// Stroke width set with `w`, in user space units.
+    pub line_width: f64,
     pub transform_matrix: TransformMatrix,
+    /// Whether the current font writes top to bottom (`/WMode 1`).
+    pub vertical: bool,
     pub clipping_enabled: bool,
+    /// Bounds of the clipping path set with `W`/`W*`, in the page's
+    /// displayed user space.
+    pub clip: ClipState,
+    /// Language of the marked content being drawn, from a `BDC`'s `/Lang`.
+    pub lang: Option<String>,
+    /// The marked-content id of what is being drawn, from a `BDC`'s `/MCID`.
+    pub mcid: Option<u32>,
 }
 
 impl GraphicsState {
@@ -48,10 +69,20 @@ impl GraphicsState {
             font_size: 12.0,
             fill_color: Color::new(0, 0, 0),
             stroke_color: Color::new(0, 0, 0),
+            fill_color_space: ColorSpace::DeviceGray,
+            stroke_color_space: ColorSpace::DeviceGray,
+            fill_pattern: None,
             letter_space: 0.0,
             word_space: 0.0,
+            leading: 0.0,
+            render_mode: 0,
+            line_width: 1.0,
             transform_matrix: TransformMatrix::identity(),
+            vertical: false,
             clipping_enabled: false,
+            clip: ClipState::new(),
+            lang: None,
+            mcid: None,
         }
     }
 
@@ -123,6 +154,25 @@ impl ClipState {
         }
     }
 
+    pub fn from_bbox(bbox: &BoundingBox) -> Self {
+        Self {
+            xmin: bbox.x0,
+            xmax: bbox.x1,
+            ymin: bbox.y0,
+            ymax: bbox.y1,
+        }
+    }
+
+    /// Whether nothing is left inside the clip.
+    pub fn is_empty(&self) -> bool {
+        self.xmin >= self.xmax || self.ymin >= self.ymax
+    }
+
+    /// Whether `bbox` lies wholly outside the clip, touching edges aside.
+    pub fn excludes(&self, bbox: &BoundingBox) -> bool {
+        bbox.x1 < self.xmin || bbox.x0 > self.xmax || bbox.y1 < self.ymin || bbox.y0 > self.ymax
+    }
+
     pub fn intersect(&mut self, other: &ClipState) {
         self.xmin = self.xmin.max(other.xmin);
         self.xmax = self.xmax.min(other.xmax);
@@ -141,6 +191,108 @@ impl Default for ClipState {
     }
 }
 
+/// The `q`/`Q` save stack of a single content stream.
+///
+/// Each stream (a page or a form XObject) gets its own stack, so saves left
+/// open by a form end with the form. A `Q` with nothing saved is ignored and
+/// counted, and `imbalance` reports what was left over.
+#[derive(Debug)]
+pub struct SaveStack<T> {
+    saved: Vec<T>,
+    unmatched_restores: usize,
+}
+
+impl<T> SaveStack<T> {
+    pub fn new() -> Self {
+        Self {
+            saved: Vec::new(),
+            unmatched_restores: 0,
+        }
+    }
+
+    pub fn save(&mut self, state: T) {
+        self.saved.push(state);
+    }
+
+    pub fn restore(&mut self) -> Option<T> {
+        let state = self.saved.pop();
+        if state.is_none() {
+            self.unmatched_restores += 1;
+        }
+        state
+    }
+
+    /// Describe the q/Q imbalance at the end of the stream, if any.
+    pub fn imbalance(&self) -> Option<String> {
+        match (self.saved.len(), self.unmatched_restores) {
+            (0, 0) => None,
+            (open, 0) => Some(format!("content stream ended with {} unclosed q", open)),
+            (0, extra) => Some(format!("content stream has {} Q without a matching q", extra)),
+            (open, extra) => Some(format!(
+                "content stream ended with {} unclosed q and {} unmatched Q",
+                open, extra
+            )),
+        }
+    }
+}
+
+/// The open `BMC`/`BDC` sequences of a content stream, and whether what is
+/// drawn now is in hidden optional content. Content stays hidden inside
+/// any sequence nested in a hidden one.
+#[derive(Debug, Default)]
+pub struct MarkedContent {
+    hidden: Vec<bool>,
+    /// The language and marked-content id in effect before each open
+    /// sequence.
+    outer: Vec<(Option<String>, Option<u32>)>,
+}
+
+impl MarkedContent {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open a sequence, itself hiding its content or not, and giving what
+    /// `state` draws its `lang` and `mcid` if it has them.
+    pub fn begin(
+        &mut self,
+        hidden: bool,
+        lang: Option<&String>,
+        mcid: Option<u32>,
+        state: &mut GraphicsState,
+    ) {
+        let hidden = hidden || self.is_hidden();
+        self.hidden.push(hidden);
+        self.outer.push((state.lang.clone(), state.mcid));
+        if let Some(lang) = lang {
+            state.lang = Some(lang.clone());
+        }
+        if mcid.is_some() {
+            state.mcid = mcid;
+        }
+    }
+
+    /// Close the innermost sequence, restoring the language and id in
+    /// effect before it; an `EMC` with none open is ignored.
+    pub fn end(&mut self, state: &mut GraphicsState) {
+        self.hidden.pop();
+        if let Some((lang, mcid)) = self.outer.pop() {
+            state.lang = lang;
+            state.mcid = mcid;
+        }
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        self.hidden.last().copied().unwrap_or(false)
+    }
+}
+
+impl<T> Default for SaveStack<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -177,4 +329,20 @@ mod tests {
         assert!((clip1.xmin - 50.0).abs() < 0.001);
         assert!((clip1.xmax - 100.0).abs() < 0.001);
     }
+
+    #[test]
+    fn test_save_stack_reports_imbalance() {
+        let mut stack = SaveStack::new();
+        stack.save(1);
+        assert_eq!(stack.restore(), Some(1));
+        assert_eq!(stack.imbalance(), None);
+
+        assert_eq!(stack.restore(), None);
+        stack.save(2);
+        stack.save(3);
+        assert_eq!(
+            stack.imbalance().unwrap(),
+            "content stream ended with 2 unclosed q and 1 unmatched Q"
+        );
+    }
 }
@@ -0,0 +1,223 @@
+use super::RenderedPage;
+use crate::config::ConversionConfig;
+use crate::util::hash::ContentHasher;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Config fields that select or schedule pages without changing how a page renders.
+const PAGE_INDEPENDENT_FIELDS: &[&str] = &[
+    "page_range",
+    "pages",
+    "timeout_ms",
+    "timeout",
+    "quiet",
+    "clean_tmp",
+    "tmp_file_size_limit",
+    "parallel",
+    "strict",
+    // Applied to pages as they are handed over, after caching
+    "max_output_bytes",
+    "output_limit_action",
+    // Fonts are named by their embedded program, so subsetting changes the
+    // font files but not the pages that name them
+    "subset_fonts",
+    // Only the shared CSS depends on it
+    "preserve_spaces",
+    // Pages mark their text's MCIDs either way
+    "reflow",
+];
+
+/// Identifies one rendered page: the PDF bytes, the page index and the
+/// subset of the config that influences page output.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PageCacheKey {
+    pub document_hash: String,
+    pub page_index: usize,
+    pub config_hash: String,
+}
+
+impl PageCacheKey {
+    pub fn new(document_hash: &str, page_index: usize, config_hash: &str) -> Self {
+        Self {
+            document_hash: document_hash.to_string(),
+            page_index,
+            config_hash: config_hash.to_string(),
+        }
+    }
+
+    /// Hash of the config fields that affect a page's rendered output.
+    pub fn config_hash(config: &ConversionConfig) -> String {
+        let mut value = serde_json::to_value(config).unwrap_or_default();
+        if let Some(fields) = value.as_object_mut() {
+            for field in PAGE_INDEPENDENT_FIELDS {
+                fields.remove(*field);
+            }
+        }
+        // Custom decoders can change what a stream decodes to
+        let filters = config.filters.names().join(",");
+        ContentHasher::hash_string(&format!("{}|{}", value, filters))
+    }
+}
+
+/// Rendered pages kept across conversions so a reconversion only renders
+/// pages whose document or relevant config changed.
+///
+/// Shared through `ConversionConfig::page_cache`. When `max_pages` is set the
+/// oldest entries are evicted first.
+#[derive(Debug, Default)]
+pub struct PageCache {
+    entries: Mutex<CacheEntries>,
+    max_pages: Option<usize>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+#[derive(Debug, Default)]
+struct CacheEntries {
+    pages: HashMap<PageCacheKey, RenderedPage>,
+    order: VecDeque<PageCacheKey>,
+}
+
+impl PageCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_pages(max_pages: usize) -> Self {
+        Self {
+            max_pages: Some(max_pages),
+            ..Self::default()
+        }
+    }
+
+    pub fn get(&self, key: &PageCacheKey) -> Option<RenderedPage> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        match entries.pages.get(key) {
+            Some(page) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(page.clone())
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    pub fn insert(&self, key: PageCacheKey, page: RenderedPage) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.pages.insert(key.clone(), page).is_none() {
+            entries.order.push_back(key);
+        }
+        if let Some(max) = self.max_pages {
+            while entries.pages.len() > max {
+                match entries.order.pop_front() {
+                    Some(oldest) => {
+                        entries.pages.remove(&oldest);
+                    }
+                    None => break,
+                }
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pages
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.pages.clear();
+        entries.order.clear();
+    }
+
+    /// Lookups answered from the cache.
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Lookups that had to render the page.
+    pub fn misses(&self) -> usize {
+        self.misses.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(page_number: usize) -> RenderedPage {
+        RenderedPage {
+            page_number,
+            width: 612.0,
+            height: 792.0,
+            html: format!("<p>{}</p>", page_number),
+            css: String::new(),
+            text_spans: Vec::new(),
+            reading_order: Vec::new(),
+            font_ids: Vec::new(),
+            background_color: None,
+            images: Vec::new(),
+            filled_rects: Vec::new(),
+            strokes: Vec::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_config_hash_ignores_page_selection() {
+        let base = ConversionConfig::default();
+        let narrowed = ConversionConfig {
+            page_range: (2, 3),
+            timeout_ms: Some(1000),
+            ..Default::default()
+        };
+        let zoomed = ConversionConfig {
+            zoom: 2.0,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            PageCacheKey::config_hash(&base),
+            PageCacheKey::config_hash(&narrowed)
+        );
+        assert_ne!(
+            PageCacheKey::config_hash(&base),
+            PageCacheKey::config_hash(&zoomed)
+        );
+    }
+
+    #[test]
+    fn test_cache_counts_hits_and_misses() {
+        let cache = PageCache::new();
+        let key = PageCacheKey::new("doc", 0, "cfg");
+
+        assert!(cache.get(&key).is_none());
+        cache.insert(key.clone(), page(1));
+        assert_eq!(cache.get(&key).unwrap().page_number, 1);
+
+        assert_eq!(cache.hits(), 1);
+        assert_eq!(cache.misses(), 1);
+    }
+
+    #[test]
+    fn test_cache_evicts_oldest_page() {
+        let cache = PageCache::with_max_pages(2);
+        for i in 0..3 {
+            cache.insert(PageCacheKey::new("doc", i, "cfg"), page(i + 1));
+        }
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&PageCacheKey::new("doc", 0, "cfg")).is_none());
+        assert!(cache.get(&PageCacheKey::new("doc", 2, "cfg")).is_some());
+    }
+}
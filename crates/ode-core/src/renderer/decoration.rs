@@ -0,0 +1,141 @@
+use super::path::PathSegment;
+use super::{FilledRect, StrokedPath, TextSpan};
+use std::collections::HashSet;
+
+/// Thickest a rule may be, as a fraction of the font size, to decorate text.
+const MAX_THICKNESS: f64 = 0.15;
+
+/// How far above the baseline, as a fraction of the font size, an underline
+/// may sit; anything higher is a strike-through, up to `STRIKE_TOP`.
+const UNDERLINE_TOP: f64 = 0.1;
+const UNDERLINE_BOTTOM: f64 = 0.35;
+const STRIKE_TOP: f64 = 0.6;
+
+/// Where a line of text's baseline sits above the bottom of its box, as a
+/// fraction of the font size, taking the usual descent.
+const BASELINE_ABOVE_BOTTOM: f64 = 0.2;
+
+/// A thin horizontal fill or stroke that may be a text decoration.
+struct Rule {
+    x0: f64,
+    x1: f64,
+    /// The rule's center line.
+    y: f64,
+    thickness: f64,
+    color: String,
+    source: RuleSource,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum RuleSource {
+    Rect(usize),
+    Stroke(usize),
+}
+
+/// Turn rules drawn under or through text into its `text-decoration`.
+///
+/// A rule decorates the spans in its color whose baseline it sits just under
+/// (underline) or whose middle it crosses (strike-through) and whose ends it
+/// reaches within `tolerance`. It is then dropped, unless it runs more than
+/// `tolerance` past the spans it decorates, which makes it a rule of its own,
+/// a table border say, and leaves the spans as they are.
+pub fn apply_text_decorations(
+    spans: &mut [TextSpan],
+    filled_rects: &mut Vec<FilledRect>,
+    strokes: &mut Vec<StrokedPath>,
+    tolerance: f64,
+) {
+    let mut used = HashSet::new();
+    for rule in horizontal_rules(filled_rects, strokes) {
+        let mut decorated: Vec<(usize, bool)> = Vec::new();
+        for (i, span) in spans.iter().enumerate() {
+            if let Some(underline) = decoration(&rule, span, tolerance) {
+                decorated.push((i, underline));
+            }
+        }
+        let Some(start) = decorated.iter().map(|&(i, _)| spans[i].x).reduce(f64::min) else {
+            continue;
+        };
+        let end = decorated
+            .iter()
+            .map(|&(i, _)| spans[i].x + spans[i].width)
+            .fold(start, f64::max);
+        if rule.x0 < start - tolerance || rule.x1 > end + tolerance {
+            continue;
+        }
+        for (i, underline) in decorated {
+            if underline {
+                spans[i].underline = true;
+            } else {
+                spans[i].line_through = true;
+            }
+        }
+        used.insert(rule.source);
+    }
+
+    let mut index = 0;
+    filled_rects.retain(|_| {
+        index += 1;
+        !used.contains(&RuleSource::Rect(index - 1))
+    });
+    let mut index = 0;
+    strokes.retain(|_| {
+        index += 1;
+        !used.contains(&RuleSource::Stroke(index - 1))
+    });
+}
+
+/// Whether `rule` underlines `span` (`Some(true)`), strikes it through
+/// (`Some(false)`) or is no decoration of it.
+fn decoration(rule: &Rule, span: &TextSpan, tolerance: f64) -> Option<bool> {
+    let size = span.font_size;
+    if span.vertical || span.width <= 0.0 || size <= 0.0 || rule.color != span.color {
+        return None;
+    }
+    if rule.thickness > size * MAX_THICKNESS {
+        return None;
+    }
+    if rule.x0 > span.x + tolerance || rule.x1 < span.x + span.width - tolerance {
+        return None;
+    }
+    let baseline = span.y + span.height - size * BASELINE_ABOVE_BOTTOM;
+    // Above the baseline is negative
+    let below = rule.y - baseline;
+    if below >= -size * UNDERLINE_TOP && below <= size * UNDERLINE_BOTTOM + tolerance {
+        Some(true)
+    } else if below >= -size * STRIKE_TOP && below < -size * UNDERLINE_TOP {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Fills wider than tall and single straight horizontal strokes.
+fn horizontal_rules(filled_rects: &[FilledRect], strokes: &[StrokedPath]) -> Vec<Rule> {
+    let rects = filled_rects
+        .iter()
+        .enumerate()
+        .filter(|(_, rect)| rect.width > rect.height * 4.0)
+        .map(|(i, rect)| Rule {
+            x0: rect.x,
+            x1: rect.x + rect.width,
+            y: rect.y + rect.height / 2.0,
+            thickness: rect.height,
+            color: rect.color.clone(),
+            source: RuleSource::Rect(i),
+        });
+    let lines = strokes.iter().enumerate().filter_map(|(i, stroke)| {
+        let [PathSegment::MoveTo(x0, y0), PathSegment::LineTo(x1, y1)] = stroke.segments[..] else {
+            return None;
+        };
+        ((y1 - y0).abs() < 1e-6).then(|| Rule {
+            x0: x0.min(x1),
+            x1: x0.max(x1),
+            y: y0,
+            thickness: stroke.width,
+            color: stroke.color.clone(),
+            source: RuleSource::Stroke(i),
+        })
+    });
+    rects.chain(lines).collect()
+}
@@ -0,0 +1,157 @@
+use crate::parser::shading::{Shading, ShadingGeometry};
+use crate::util::math::{hypot, BoundingBox, TransformMatrix};
+
+/// Intervals the shading's domain is sampled at for gradient stops.
+const STOP_INTERVALS: usize = 8;
+
+/// A CSS `background` approximating `shading`, painted with `ctm`, for a
+/// box covering `area` of the page's displayed user space.
+///
+/// Axial shadings become a `linear-gradient` and radial ones a
+/// `radial-gradient` centered on their end circle; both run the function's
+/// colors from start to end and extend the end colors past them.
+pub fn css_gradient(
+    shading: &Shading,
+    ctm: &TransformMatrix,
+    area: &BoundingBox,
+) -> Option<String> {
+    let (width, height) = (area.width(), area.height());
+    if width <= 0.0 || height <= 0.0 {
+        return None;
+    }
+    let stops = color_stops(shading)?;
+    // Distances from the box's top-left, y down as in CSS
+    let in_box = |x: f64, y: f64| {
+        let (x, y) = ctm.transform_point(x, y);
+        (x - area.x0, area.y1 - y)
+    };
+
+    match shading.geometry {
+        ShadingGeometry::Axial([x0, y0, x1, y1]) => {
+            let (start, end) = (in_box(x0, y0), in_box(x1, y1));
+            let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+            let length = hypot(dx, dy);
+            if length < 1e-9 {
+                return None;
+            }
+            // CSS angles turn clockwise from "to top"; the gradient line
+            // runs through the box's center and just reaches its corners
+            let (ux, uy) = (dx / length, dy / length);
+            let line = (width * ux).abs() + (height * uy).abs();
+            let along = |(x, y): (f64, f64)| {
+                ((x - width / 2.0) * ux + (y - height / 2.0) * uy) / line + 0.5
+            };
+            let (from, to) = (along(start), along(end));
+            let stops: Vec<String> = stops
+                .iter()
+                .map(|(s, color)| format!("{} {}%", color, round((from + s * (to - from)) * 100.0)))
+                .collect();
+            let angle = dx.atan2(-dy).to_degrees();
+            Some(format!(
+                "linear-gradient({}deg, {})",
+                round(angle),
+                stops.join(", ")
+            ))
+        }
+        ShadingGeometry::Radial([_, _, r0, x1, y1, r1]) => {
+            if r1 <= 0.0 {
+                return None;
+            }
+            let (cx, cy) = in_box(x1, y1);
+            let radius = r1 * (ctm.a * ctm.d - ctm.b * ctm.c).abs().sqrt();
+            let stops: Vec<String> = stops
+                .iter()
+                .map(|(s, color)| {
+                    format!("{} {}%", color, round((r0 + s * (r1 - r0)) / r1 * 100.0))
+                })
+                .collect();
+            // Percentages keep the gradient in step with the box when it is scaled
+            Some(format!(
+                "radial-gradient({}% {}% at {}% {}%, {})",
+                round(radius / width * 100.0),
+                round(radius / height * 100.0),
+                round(cx / width * 100.0),
+                round(cy / height * 100.0),
+                stops.join(", ")
+            ))
+        }
+    }
+}
+
+/// CSS colors at evenly spaced fractions of the domain, without the ones a
+/// linear blend between their neighbors already gives.
+fn color_stops(shading: &Shading) -> Option<Vec<(f64, String)>> {
+    let [t0, t1] = shading.domain;
+    let samples = (0..=STOP_INTERVALS)
+        .map(|i| {
+            let s = i as f64 / STOP_INTERVALS as f64;
+            Some((s, shading.color_at(t0 + s * (t1 - t0))?))
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    let channels = |c: &crate::types::color::Color| [c.r as f64, c.g as f64, c.b as f64];
+    let stops = samples
+        .iter()
+        .enumerate()
+        .filter(|(i, (_, color))| {
+            if *i == 0 || *i == STOP_INTERVALS {
+                return true;
+            }
+            let (before, after) = (channels(&samples[i - 1].1), channels(&samples[i + 1].1));
+            let color = channels(color);
+            (0..3).any(|c| (color[c] - (before[c] + after[c]) / 2.0).abs() > 1.0)
+        })
+        .map(|(_, (s, color))| (*s, color.to_css_string()))
+        .collect();
+    Some(stops)
+}
+
+fn round(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::colorspace::TintTransform;
+    use crate::parser::shading::ShadingFunction;
+    use crate::parser::ColorSpace;
+
+    fn white_to_black(geometry: ShadingGeometry) -> Shading {
+        Shading {
+            geometry,
+            color_space: ColorSpace::DeviceGray,
+            domain: [0.0, 1.0],
+            function: ShadingFunction::Exponential(TintTransform {
+                c0: vec![1.0],
+                c1: vec![0.0],
+                exponent: 1.0,
+            }),
+            bbox: None,
+        }
+    }
+
+    #[test]
+    fn test_axial_gradient_points_along_its_coords() {
+        let shading = white_to_black(ShadingGeometry::Axial([0.0, 0.0, 0.0, 50.0]));
+        let area = BoundingBox::new(0.0, 0.0, 100.0, 100.0);
+
+        assert_eq!(
+            css_gradient(&shading, &TransformMatrix::identity(), &area).unwrap(),
+            "linear-gradient(0deg, rgb(255, 255, 255) 0%, rgb(0, 0, 0) 50%)"
+        );
+    }
+
+    #[test]
+    fn test_radial_gradient_is_centered_on_its_end_circle() {
+        let shading = white_to_black(ShadingGeometry::Radial([
+            306.0, 396.0, 0.0, 306.0, 396.0, 50.0,
+        ]));
+        let area = BoundingBox::new(206.0, 346.0, 406.0, 446.0);
+
+        assert_eq!(
+            css_gradient(&shading, &TransformMatrix::identity(), &area).unwrap(),
+            "radial-gradient(25% 50% at 50% 50%, rgb(255, 255, 255) 0%, rgb(0, 0, 0) 100%)"
+        );
+    }
+}
@@ -1,12 +1,55 @@
+pub mod cache;
+pub mod decoration;
+pub mod gradient;
+pub mod path;
+pub mod reading_order;
+pub mod reflow;
 pub mod text;
 
-use crate::config::ConversionConfig;
+use crate::config::{
+    AssetEmbedding, ConversionConfig, CoveredText, OutputFormat, UnmappedGlyph,
+};
 use crate::error::OdeError;
-use crate::fonts::FontProcessor;
-use crate::parser::{content_stream::ContentStreamParser, ParsedOp, PdfDocument};
-use crate::render::state::GraphicsState;
+use crate::fonts::{FontProcessor, GlyphWidths};
+use crate::parser::{
+    content_stream::ContentStreamParser, ColorSpace, DocumentMetadata, LinkAnnotation,
+    NoteAnnotation, ObjectReference, Outline, ParsedOp, Pattern, PdfDocument, StructElement,
+};
+use crate::render::state::{ClipState, GraphicsState, MarkedContent, SaveStack};
 use crate::util::hash::ContentHasher;
+use crate::util::Deadline;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::ops::Range;
+
+pub use cache::{PageCache, PageCacheKey};
+pub use path::StrokedPath;
+
+/// Ids of the extracted fonts, keyed by their font object.
+pub type FontIds = std::collections::HashMap<ObjectReference, ExtractedFontRef>;
+
+/// What the converted pages show in each font, keyed by font object.
+pub type UsedGlyphs = std::collections::HashMap<ObjectReference, crate::fonts::GlyphUsage>;
+
+/// What text drawn in an extracted font needs of it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExtractedFontRef {
+    pub id: u64,
+    /// The font program's ascent as a fraction of the em, when it has one.
+    pub ascent: Option<f64>,
+    /// Its descent, negative below the baseline, likewise.
+    pub descent: Option<f64>,
+}
+
+/// Where the top of a line of text sits above its baseline, as a fraction
+/// of the font size, for fonts without metrics of their own.
+const DEFAULT_ASCENT: f64 = 0.85;
+
+/// Where the bottom of a line sits below its baseline, likewise.
+const DEFAULT_DESCENT: f64 = -0.2;
+
+/// Operators run between checks of the conversion deadline.
+const DEADLINE_CHECK_INTERVAL: usize = 1024;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TextSpan {
@@ -16,6 +59,50 @@ pub struct TextSpan {
     pub font_size: f64,
     pub font_id: Option<u64>,
     pub color: String,
+    /// Text later drawing hides, kept under `CoveredText::Mark`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub covered: bool,
+    /// Horizontal scale from `Tz`, 1.0 for unscaled text.
+    #[serde(default = "unit_scale", skip_serializing_if = "is_unit_scale")]
+    pub scale_x: f64,
+    /// Text in a vertical font, running top to bottom from `x`, `y`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub vertical: bool,
+    /// Size of the text's box from `x`, `y`: its glyphs' advance across and
+    /// the font's ascent to descent down, guessed when the font has no
+    /// widths.
+    #[serde(default)]
+    pub width: f64,
+    #[serde(default)]
+    pub height: f64,
+    /// Underlined by a rule drawn under the text; see
+    /// [`decoration::apply_text_decorations`].
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub underline: bool,
+    /// Struck through by a rule drawn across the text, likewise.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub line_through: bool,
+    /// Extra space after each character, from `Tc`, and after each space,
+    /// from `Tw`, at the span's scale.
+    #[serde(default)]
+    pub letter_spacing: f64,
+    #[serde(default)]
+    pub word_spacing: f64,
+    /// Language of the marked content the text is in, from a `BDC`'s
+    /// `/Lang`, when it sets one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lang: Option<String>,
+    /// The marked-content id the structure tree knows the text by.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mcid: Option<u32>,
+}
+
+fn unit_scale() -> f64 {
+    1.0
+}
+
+fn is_unit_scale(scale: &f64) -> bool {
+    *scale == 1.0
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -36,7 +123,132 @@ pub struct PageImageRef {
     pub y: f64,
     pub width: f64,
     pub height: f64,
-    pub data_uri: String,
+    /// Decoded size of the image in pixels (0 when unknown).
+    #[serde(default)]
+    pub pixel_width: u32,
+    #[serde(default)]
+    pub pixel_height: u32,
+    /// `<sha256>.<ext>` of the image's bytes. The page's HTML loads the
+    /// image by this name, which every drawing of the same image shares.
+    pub filename: String,
+    pub mime_type: String,
+    /// The encoded image; moved to [`OutputBundle::images`] when the page
+    /// is added to a bundle.
+    #[serde(skip)]
+    pub data: Vec<u8>,
+    /// Where the page's HTML holds this drawing's `<img>` tag, if it has one.
+    #[serde(skip)]
+    pub(crate) html_range: Option<Range<usize>>,
+}
+
+impl PageImageRef {
+    /// `image` drawn at `x`, `y` (top left) and `width` by `height`.
+    fn new(image: &crate::parser::PageImage, x: f64, y: f64, width: f64, height: f64) -> Self {
+        let extension = image_extension(&image.mime_type).unwrap_or("bin");
+        Self {
+            name: image.name.clone(),
+            x,
+            y,
+            width,
+            height,
+            pixel_width: image.width,
+            pixel_height: image.height,
+            filename: ContentHasher::generate_content_addressed_filename(&image.data, extension),
+            mime_type: image.mime_type.clone(),
+            data: image.data.clone(),
+            html_range: None,
+        }
+    }
+}
+
+/// Why `image` is drawn as a placeholder, if it is.
+fn unsupported_image(image: &crate::parser::PageImage) -> Option<String> {
+    let filter = image.unsupported_filter.as_ref()?;
+    Some(format!(
+        "image /{}: unsupported filter {}, drawn as a placeholder",
+        image.name, filter
+    ))
+}
+
+/// An image file of the output, stored once however many times the
+/// document draws it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenderedImage {
+    pub filename: String,
+    pub mime_type: String,
+    #[serde(skip)]
+    pub data: Vec<u8>,
+}
+
+impl RenderedImage {
+    fn data_uri(&self) -> String {
+        image_data_uri(&self.mime_type, &self.data)
+    }
+}
+
+fn image_data_uri(mime_type: &str, data: &[u8]) -> String {
+    use base64::Engine;
+    let data = base64::engine::general_purpose::STANDARD.encode(data);
+    format!("data:{};base64,{}", mime_type, data)
+}
+
+/// A filled area drawn behind a page's text: a vector fill, a shading, or a
+/// form widget's background or border.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilledRect {
+    /// Top-left corner, in output pixels from the page's top-left.
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    /// The CSS `background`: a color or a gradient.
+    pub color: String,
+}
+
+impl FilledRect {
+    pub fn new(x: f64, y: f64, width: f64, height: f64, color: String) -> Self {
+        Self { x, y, width, height, color }
+    }
+}
+
+/// An image drawn on a page, decoded the way the HTML output embeds it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtractedImage {
+    /// Zero-based index of the page the image is drawn on.
+    pub page_index: usize,
+    /// The image's resource name in the page's `/XObject` dictionary.
+    pub name: String,
+    /// File extension matching `data`: `png`, `jpg` or `jp2`.
+    pub format: String,
+    /// Where the image is drawn, in output pixels from the page's top-left.
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    /// Decoded size of the image in pixels (0 when unknown).
+    pub pixel_width: u32,
+    pub pixel_height: u32,
+    #[serde(skip)]
+    pub data: Vec<u8>,
+}
+
+impl ExtractedImage {
+    /// `None` when the image isn't in a format the output embeds.
+    pub fn from_page_image(page_index: usize, image: &PageImageRef) -> Option<Self> {
+        let format = image_extension(&image.mime_type)?;
+        Some(Self {
+            page_index,
+            name: image.name.clone(),
+            format: format.to_string(),
+            x: image.x,
+            y: image.y,
+            width: image.width,
+            height: image.height,
+            pixel_width: image.pixel_width,
+            pixel_height: image.pixel_height,
+            data: image.data.clone(),
+        })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -45,22 +257,154 @@ pub struct RenderedPage {
     pub width: f64,
     pub height: f64,
     pub html: String,
+    /// Rules only this page needs; those every page uses are in
+    /// [`OutputBundle::css`].
     pub css: String,
     pub text_spans: Vec<TextSpan>,
+    /// Indices into `text_spans` in the order the text is read, column by
+    /// column; see [`reading_order::reading_order`]. Empty without a
+    /// `column_gap`, leaving the text to read top to bottom.
+    #[serde(default)]
+    pub reading_order: Vec<usize>,
     pub font_ids: Vec<u64>,
     pub background_color: Option<String>,
     pub images: Vec<PageImageRef>,
+    /// Filled rectangles, painted behind images and text.
+    #[serde(default)]
+    pub filled_rects: Vec<FilledRect>,
+    /// Stroked lines and shapes, painted over the filled rectangles.
+    #[serde(default)]
+    pub strokes: Vec<StrokedPath>,
+    /// Problems in the source that rendering worked around, e.g. q/Q imbalance.
+    #[serde(default)]
+    pub diagnostics: Vec<String>,
+}
+
+impl RenderedPage {
+    /// Take the image stored as `filename` off the page, HTML and all.
+    pub(crate) fn drop_image(&mut self, filename: &str) {
+        let (dropped, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut self.images)
+            .into_iter()
+            .partition(|image| image.filename == filename);
+        self.images = kept;
+        let mut ranges: Vec<Range<usize>> =
+            dropped.into_iter().filter_map(|image| image.html_range).collect();
+        // Last first, so the tags still to cut stay where they were recorded
+        ranges.sort_by_key(|range| std::cmp::Reverse(range.start));
+        for range in ranges {
+            self.html.replace_range(range.clone(), "");
+            let later = self.images.iter_mut().filter_map(|image| image.html_range.as_mut());
+            for kept in later.filter(|kept| kept.start >= range.end) {
+                *kept = kept.start - range.len()..kept.end - range.len();
+            }
+        }
+    }
+
+    /// The page's HTML with its images inlined as data URIs, for showing
+    /// the page on its own. Images whose data went to an `OutputBundle`
+    /// stay loaded by filename.
+    pub fn standalone_html(&self) -> String {
+        let mut html = self.html.clone();
+        let mut inlined: Vec<&str> = Vec::new();
+        for image in self.images.iter().filter(|image| !image.data.is_empty()) {
+            if !inlined.contains(&image.filename.as_str()) {
+                inlined.push(&image.filename);
+                let data_uri = image_data_uri(&image.mime_type, &image.data);
+                let src = format!("src=\"{}\"", image.filename);
+                html = html.replace(&src, &format!("src=\"{}\"", data_uri));
+            }
+        }
+        html
+    }
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct OutputBundle {
     pub pages: Vec<RenderedPage>,
     pub fonts: Vec<RenderedFont>,
+    /// The rules every page uses, from [`shared_css`], then the
+    /// `@font-face` rules that load the fonts.
     pub css: String,
+    /// Whether the source PDF was encrypted.
+    #[serde(default)]
+    pub encrypted: bool,
+    /// The document language from the catalog's `/Lang`.
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Wall-clock time the conversion took, in milliseconds.
+    #[serde(default)]
+    pub duration_ms: u64,
+    /// Title, author and dates from the document's `/Info` and XMP metadata.
+    #[serde(default)]
+    pub metadata: DocumentMetadata,
+    /// The document's bookmarks; the combined HTML shows them in a sidebar.
+    #[serde(default)]
+    pub outline: Vec<Outline>,
+    /// Every image the pages draw, each once, in the order first drawn.
+    #[serde(default)]
+    pub images: Vec<RenderedImage>,
+    /// Pages that failed to render, which `pages` holds empty.
+    #[serde(default)]
+    pub page_errors: Vec<PageError>,
+    /// A tagged document's structure, read when converting with `reflow`;
+    /// the combined HTML then follows it instead of the page layout.
+    #[serde(default)]
+    pub structure: Vec<StructElement>,
+}
+
+/// A page that failed to render, and why.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PageError {
+    pub page_number: usize,
+    pub message: String,
+}
+
+/// Headline numbers for a finished conversion.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConversionSummary {
+    pub page_count: usize,
+    pub span_count: usize,
+    pub font_count: usize,
+    pub image_count: usize,
+    pub language: Option<String>,
+    pub encrypted: bool,
+    pub duration_ms: u64,
+    #[serde(default)]
+    pub metadata: DocumentMetadata,
+}
+
+impl ConversionSummary {
+    /// Count a page and its text spans and images.
+    pub fn record_page(&mut self, page: &RenderedPage) {
+        self.page_count += 1;
+        self.span_count += page.text_spans.len();
+        self.image_count += page.images.len();
+    }
+
+    /// Take the document-level numbers from `bundle`; its pages are not counted.
+    pub fn record_document(&mut self, bundle: &OutputBundle) {
+        self.font_count = bundle.fonts.len();
+        self.language = bundle.language.clone();
+        self.encrypted = bundle.encrypted;
+        self.duration_ms = bundle.duration_ms;
+        self.metadata = bundle.metadata.clone();
+    }
 }
 
 impl OutputBundle {
-    pub fn add_page(&mut self, page: RenderedPage) {
+    /// Add `page`, moving the data of images not yet in [`Self::images`]
+    /// there; the page keeps referring to them by filename.
+    pub fn add_page(&mut self, mut page: RenderedPage) {
+        for image in &mut page.images {
+            let data = std::mem::take(&mut image.data);
+            if !data.is_empty() && !self.images.iter().any(|i| i.filename == image.filename) {
+                self.images.push(RenderedImage {
+                    filename: image.filename.clone(),
+                    mime_type: image.mime_type.clone(),
+                    data,
+                });
+            }
+        }
         self.pages.push(page);
     }
 
@@ -94,6 +438,332 @@ impl OutputBundle {
     pub fn get_font_by_id(&self, font_id: u64) -> Option<&RenderedFont> {
         self.fonts.iter().find(|f| f.font_id == font_id)
     }
+
+    pub fn summary(&self) -> ConversionSummary {
+        let mut summary = ConversionSummary::default();
+        for page in &self.pages {
+            summary.record_page(page);
+        }
+        summary.record_document(self);
+        summary
+    }
+
+    /// A self-contained HTML document with every page and every font inlined
+    /// as a base64 data URI.
+    ///
+    /// An image drawn once is inlined where it is drawn. One drawn more often,
+    /// like a logo on every page, is inlined once as the background of a CSS
+    /// class the images share.
+    pub fn to_single_file_html(&self) -> String {
+        use base64::Engine;
+        let font_src = |font: &RenderedFont| {
+            format!(
+                "data:{};base64,{}",
+                font.format.mime_type(),
+                base64::engine::general_purpose::STANDARD.encode(&font.data)
+            )
+        };
+        self.document_html(font_src, true)
+    }
+
+    /// The document HTML with its fonts and images carried as `embedding`
+    /// says, and the files it loads: none when they are inlined.
+    pub fn to_html(&self, embedding: AssetEmbedding) -> (String, Vec<(String, Vec<u8>)>) {
+        match embedding {
+            AssetEmbedding::Inline => (self.to_single_file_html(), Vec::new()),
+            AssetEmbedding::External => self.to_linked_html(),
+        }
+    }
+
+    /// The fonts and images as separate content-addressed files, plus an
+    /// `index.html` that loads them by filename.
+    pub fn to_asset_files(&self) -> Vec<(String, Vec<u8>)> {
+        let (index, mut files) = self.to_linked_html();
+        files.push(("index.html".to_string(), index.into_bytes()));
+        files
+    }
+
+    /// The document HTML loading its fonts and images by filename, and those
+    /// files in the order the document first uses them.
+    pub(crate) fn to_linked_html(&self) -> (String, Vec<(String, Vec<u8>)>) {
+        let images = self.image_files();
+        let mut files: Vec<(String, Vec<u8>)> = Vec::with_capacity(self.fonts.len() + images.len());
+        for font in &self.fonts {
+            // Identical font programs share a filename; write each only once
+            if !files.iter().any(|(name, _)| *name == font.filename) {
+                files.push((font.filename.clone(), font.data.clone()));
+            }
+        }
+        files.extend(images.into_iter().map(|image| {
+            let image = image.into_owned();
+            (image.filename, image.data)
+        }));
+
+        (self.document_html(|font| font.filename.clone(), false), files)
+    }
+
+    /// [`Self::images`], then the images of pages that still hold their own
+    /// data, each once.
+    fn image_files(&self) -> Vec<Cow<'_, RenderedImage>> {
+        let mut files: Vec<Cow<'_, RenderedImage>> =
+            self.images.iter().map(Cow::Borrowed).collect();
+        for image in self.pages.iter().flat_map(|p| &p.images) {
+            if !image.data.is_empty() && !files.iter().any(|f| f.filename == image.filename) {
+                files.push(Cow::Owned(RenderedImage {
+                    filename: image.filename.clone(),
+                    mime_type: image.mime_type.clone(),
+                    data: image.data.clone(),
+                }));
+            }
+        }
+        files
+    }
+
+    /// The whole document as `format`: single-file HTML, plain text or JSON.
+    pub fn render(&self, format: OutputFormat) -> String {
+        match format {
+            OutputFormat::Html => self.to_single_file_html(),
+            OutputFormat::Text => self.to_plain_text(),
+            OutputFormat::Json => self.to_json(),
+        }
+    }
+
+    /// The text of every page in reading order: column by column when the
+    /// conversion had a `column_gap`, lines top to bottom, spans left to right
+    /// within a line, a blank line where the gap to the next line is over
+    /// twice the font size or a new column starts, and a form feed between
+    /// pages.
+    pub fn to_plain_text(&self) -> String {
+        let pages: Vec<String> = self.pages.iter().map(page_plain_text).collect();
+        pages.join("\u{c}")
+    }
+
+    /// The pages' sizes and text spans as JSON.
+    ///
+    /// The schema is `{"language", "encrypted", "pages": [{"page_number",
+    /// "width", "height", "spans": [{"text", "x", "y", "font_size",
+    /// "font_id", "color"}]}]}`, geometry in output pixels from each page's
+    /// top-left.
+    pub fn to_json(&self) -> String {
+        #[derive(Serialize)]
+        struct JsonPage<'a> {
+            page_number: usize,
+            width: f64,
+            height: f64,
+            spans: &'a [TextSpan],
+        }
+        #[derive(Serialize)]
+        struct JsonDocument<'a> {
+            language: Option<&'a str>,
+            encrypted: bool,
+            pages: Vec<JsonPage<'a>>,
+        }
+
+        let document = JsonDocument {
+            language: self.language.as_deref(),
+            encrypted: self.encrypted,
+            pages: self
+                .pages
+                .iter()
+                .map(|page| JsonPage {
+                    page_number: page.page_number,
+                    width: page.width,
+                    height: page.height,
+                    spans: &page.text_spans,
+                })
+                .collect(),
+        };
+        serde_json::to_string(&document).unwrap_or_default()
+    }
+
+    /// The whole document, loading fonts from `font_src` and images either
+    /// by filename or, with `inline_images`, from data URIs.
+    fn document_html(
+        &self,
+        font_src: impl Fn(&RenderedFont) -> String,
+        inline_images: bool,
+    ) -> String {
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html{}>\n<head>\n<meta charset=\"UTF-8\">\n",
+            lang_attribute(self.language.as_deref())
+        );
+        if let Some(title) = &self.metadata.title {
+            html.push_str(&format!("<title>{}</title>\n", escape_html(title)));
+        }
+        html.push_str("<style>\n");
+        let mut css = self.css.clone();
+        for font in &self.fonts {
+            let url = format!("url('{}')", font.filename);
+            if css.contains(&url) {
+                // The conversion's own rule loads the font by filename; repoint it
+                css = css.replace(&url, &format!("url('{}')", font_src(font)));
+            } else {
+                html.push_str(&format!(
+                    "@font-face {{\n  font-family: 'ff{}';\n  src: url('{}') format('{}');\n}}\n",
+                    font.font_id,
+                    font_src(font),
+                    font.format.css_format()
+                ));
+            }
+        }
+        if !css.is_empty() {
+            html.push_str(&css);
+            html.push('\n');
+        }
+        // Pages that need the same rules of their own get them once
+        let mut page_css: Vec<&str> = Vec::new();
+        for page in &self.pages {
+            if !page.css.is_empty() && !page_css.contains(&page.css.as_str()) {
+                page_css.push(&page.css);
+                html.push_str(&page.css);
+                html.push('\n');
+            }
+        }
+        // Reflowed text has no pages for bookmarks to jump to
+        let reflow = !self.structure.is_empty();
+        if reflow {
+            html.push_str(reflow::REFLOW_CSS);
+        } else if !self.outline.is_empty() {
+            html.push_str(OUTLINE_CSS);
+        }
+        // What each image's `src="<filename>"` becomes
+        let mut image_sources: Vec<(String, String)> = Vec::new();
+        if inline_images {
+            for image in self.image_files() {
+                let src = format!("src=\"{}\"", image.filename);
+                let uses: usize = self.pages.iter().map(|p| p.html.matches(&src).count()).sum();
+                let inlined = if uses > 1 {
+                    let class = format!("img-{}", &image.filename[..16.min(image.filename.len())]);
+                    html.push_str(&format!(
+                        ".{} {{ background: url('{}') 0 0 / 100% 100% no-repeat; }}\n",
+                        class,
+                        image.data_uri()
+                    ));
+                    format!("src=\"{}\" class=\"{}\"", TRANSPARENT_PIXEL, class)
+                } else {
+                    format!("src=\"{}\"", image.data_uri())
+                };
+                image_sources.push((src, inlined));
+            }
+        }
+        html.push_str("</style>\n</head>\n<body>\n");
+
+        if reflow {
+            html.push_str(&reflow::reflow_html(&self.structure, &self.pages));
+            html.push_str("</body>\n</html>\n");
+            return html;
+        }
+        if !self.outline.is_empty() {
+            html.push_str("<nav class=\"outline\">\n");
+            outline_html(&self.outline, &mut html);
+            html.push_str("</nav>\n");
+        }
+        for page in &self.pages {
+            html.push_str(&format!(
+                "<div class=\"page\" id=\"page-{}\" style=\"position:relative;width:{}px;height:{}px;background:{};overflow:hidden;\">\n",
+                page.page_number,
+                page.width,
+                page.height,
+                page.background_color.as_deref().unwrap_or("white")
+            ));
+            let mut page_html = Cow::Borrowed(page.html.as_str());
+            for (src, inlined) in &image_sources {
+                if page_html.contains(src.as_str()) {
+                    page_html = Cow::Owned(page_html.replace(src.as_str(), inlined));
+                }
+            }
+            html.push_str(&page_html);
+            html.push_str("\n</div>\n");
+        }
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+}
+
+/// A 1x1 transparent GIF, the `src` of images drawn by their CSS background.
+const TRANSPARENT_PIXEL: &str =
+    "data:image/gif;base64,R0lGODlhAQABAIAAAAAAAP///yH5BAEAAAAALAAAAAABAAEAAAIBRAA7";
+
+/// Fixes the outline sidebar to the left edge and moves the pages clear of it.
+const OUTLINE_CSS: &str = "nav.outline { position:fixed; top:0; left:0; bottom:0; width:16em; \
+overflow:auto; padding:8px; background:#f7f7f7; border-right:1px solid #ddd; font:14px sans-serif; }
+nav.outline ul { list-style:none; margin:0; padding-left:1em; }
+nav.outline ~ .page { margin-left:calc(16em + 17px); }
+";
+
+/// Bookmarks as nested lists; those with children collapse behind a
+/// `<details>` toggle.
+fn outline_html(items: &[Outline], html: &mut String) {
+    html.push_str("<ul>\n");
+    for item in items {
+        let label = match item.dest_page {
+            Some(page_number) => format!(
+                "<a href=\"#page-{}\">{}</a>",
+                page_number,
+                escape_html(&item.title)
+            ),
+            None => escape_html(&item.title),
+        };
+        if item.children.is_empty() {
+            html.push_str(&format!("<li>{}</li>\n", label));
+        } else {
+            html.push_str(&format!("<li><details><summary>{}</summary>\n", label));
+            outline_html(&item.children, html);
+            html.push_str("</details></li>\n");
+        }
+    }
+    html.push_str("</ul>\n");
+}
+
+/// A page's spans as lines of text, in the page's reading order or else top
+/// to bottom. Spans belong to the same line when their tops are within half
+/// a font size of the line's first span.
+fn page_plain_text(page: &RenderedPage) -> String {
+    let mut spans: Vec<&TextSpan> = if page.reading_order.is_empty() {
+        let mut spans: Vec<&TextSpan> = page.text_spans.iter().collect();
+        spans.sort_by(|a, b| a.y.total_cmp(&b.y));
+        spans
+    } else {
+        page.reading_order.iter().filter_map(|&i| page.text_spans.get(i)).collect()
+    };
+    spans.retain(|span| !span.text.trim().is_empty());
+
+    let mut lines: Vec<Vec<&TextSpan>> = Vec::new();
+    for span in spans {
+        match lines.last_mut() {
+            Some(line) if (span.y - line[0].y).abs() <= line[0].font_size / 2.0 => line.push(span),
+            _ => lines.push(vec![span]),
+        }
+    }
+
+    let mut text = String::new();
+    let mut previous: Option<&TextSpan> = None;
+    for mut line in lines {
+        line.sort_by(|a, b| a.x.total_cmp(&b.x));
+        if let Some(previous) = previous {
+            text.push('\n');
+            // A wide gap ends a paragraph, and going back up starts a column
+            let gap = line[0].y - previous.y;
+            if gap > previous.font_size * 2.0 || gap < 0.0 {
+                text.push('\n');
+            }
+        }
+        let words: Vec<&str> = line.iter().map(|span| span.text.trim()).collect();
+        text.push_str(&words.join(" "));
+        previous = Some(line[0]);
+    }
+    text
+}
+
+/// The file extension for an image of `mime_type`, when it is one the
+/// output embeds.
+fn image_extension(mime_type: &str) -> Option<&'static str> {
+    match mime_type {
+        "image/png" => Some("png"),
+        "image/jpeg" => Some("jpg"),
+        "image/jp2" => Some("jp2"),
+        _ => None,
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -110,9 +780,17 @@ pub enum ContentOp {
     Tz,
     TL,
     Tstar,
+    /// `'`: move to the next line and show a string.
+    Quote,
+    /// `"`: set word and character spacing, then do as `'`.
+    DoubleQuote,
     Tf,
+    Cs,
+    CS,
     SC,
     SCN,
+    SCstroke,
+    SCNstroke,
     RG,
     RGfill,
     Gstroke,
@@ -140,6 +818,14 @@ pub enum ContentOp {
     RE,
     Wstar,
     Do,
+    D0,
+    D1,
+    Sh,
+    Tr,
+    LineWidth,
+    BDC,
+    BMC,
+    EMC,
 }
 
 impl ContentOp {
@@ -157,6 +843,8 @@ impl ContentOp {
             "Tz" => Some(ContentOp::Tz),
             "TL" => Some(ContentOp::TL),
             "T*" => Some(ContentOp::Tstar),
+            "'" => Some(ContentOp::Quote),
+            "\"" => Some(ContentOp::DoubleQuote),
             "Tf" => Some(ContentOp::Tf),
             "RG" => Some(ContentOp::RG),
             "rg" => Some(ContentOp::RGfill),
@@ -164,10 +852,12 @@ impl ContentOp {
             "g" => Some(ContentOp::Gfill),
             "K" => Some(ContentOp::Kstroke),
             "k" => Some(ContentOp::Kfill),
+            "cs" => Some(ContentOp::Cs),
+            "CS" => Some(ContentOp::CS),
             "sc" => Some(ContentOp::SC),
-            "SC" => Some(ContentOp::SC),
+            "SC" => Some(ContentOp::SCstroke),
             "scn" => Some(ContentOp::SCN),
-            "SCN" => Some(ContentOp::SCN),
+            "SCN" => Some(ContentOp::SCNstroke),
             "q" => Some(ContentOp::GsSave),
             "Q" => Some(ContentOp::GsRestore),
             "cm" => Some(ContentOp::CM),
@@ -190,9 +880,113 @@ impl ContentOp {
             "Ws" => Some(ContentOp::Ws),
             "re" => Some(ContentOp::RE),
             "Do" => Some(ContentOp::Do),
+            "d0" => Some(ContentOp::D0),
+            "d1" => Some(ContentOp::D1),
+            "sh" => Some(ContentOp::Sh),
+            "Tr" => Some(ContentOp::Tr),
+            "w" => Some(ContentOp::LineWidth),
+            "BDC" => Some(ContentOp::BDC),
+            "BMC" => Some(ContentOp::BMC),
+            "EMC" => Some(ContentOp::EMC),
             _ => None,
         }
     }
+
+    /// The operator as written in a content stream (`F` is reported as `f`).
+    pub fn name(&self) -> &'static str {
+        match self {
+            ContentOp::BT => "BT",
+            ContentOp::ET => "ET",
+            ContentOp::Td => "Td",
+            ContentOp::TD => "TD",
+            ContentOp::Tm => "Tm",
+            ContentOp::Tj => "Tj",
+            ContentOp::TJ => "TJ",
+            ContentOp::Tc => "Tc",
+            ContentOp::Tw => "Tw",
+            ContentOp::Tz => "Tz",
+            ContentOp::TL => "TL",
+            ContentOp::Tstar => "T*",
+            ContentOp::Quote => "'",
+            ContentOp::DoubleQuote => "\"",
+            ContentOp::Tf => "Tf",
+            ContentOp::Cs => "cs",
+            ContentOp::CS => "CS",
+            ContentOp::SC => "sc",
+            ContentOp::SCN => "scn",
+            ContentOp::SCstroke => "SC",
+            ContentOp::SCNstroke => "SCN",
+            ContentOp::RG => "RG",
+            ContentOp::RGfill => "rg",
+            ContentOp::Gstroke => "G",
+            ContentOp::Gfill => "g",
+            ContentOp::Kstroke => "K",
+            ContentOp::Kfill => "k",
+            ContentOp::GsSave => "q",
+            ContentOp::GsRestore => "Q",
+            ContentOp::CM => "cm",
+            ContentOp::M => "m",
+            ContentOp::L => "l",
+            ContentOp::C => "c",
+            ContentOp::H => "h",
+            ContentOp::S => "S",
+            ContentOp::Ss => "s",
+            ContentOp::F => "f",
+            ContentOp::Fs => "f*",
+            ContentOp::B => "B",
+            ContentOp::Bs => "b",
+            ContentOp::Bx => "B*",
+            ContentOp::Bxs => "b*",
+            ContentOp::N => "n",
+            ContentOp::W => "W",
+            ContentOp::Ws => "Ws",
+            ContentOp::RE => "re",
+            ContentOp::Wstar => "W*",
+            ContentOp::Do => "Do",
+            ContentOp::D0 => "d0",
+            ContentOp::D1 => "d1",
+            ContentOp::Sh => "sh",
+            ContentOp::Tr => "Tr",
+            ContentOp::LineWidth => "w",
+            ContentOp::BDC => "BDC",
+            ContentOp::BMC => "BMC",
+            ContentOp::EMC => "EMC",
+        }
+    }
+
+    /// Whether the operator sets a fill or stroke color or color space.
+    pub fn sets_color(&self) -> bool {
+        matches!(
+            self,
+            ContentOp::RG
+                | ContentOp::RGfill
+                | ContentOp::Gstroke
+                | ContentOp::Gfill
+                | ContentOp::Kstroke
+                | ContentOp::Kfill
+                | ContentOp::Cs
+                | ContentOp::CS
+                | ContentOp::SC
+                | ContentOp::SCN
+                | ContentOp::SCstroke
+                | ContentOp::SCNstroke
+        )
+    }
+
+    /// Whether the operator paints the current path, which it then ends.
+    pub fn paints_path(&self) -> bool {
+        matches!(
+            self,
+            ContentOp::S
+                | ContentOp::Ss
+                | ContentOp::F
+                | ContentOp::Fs
+                | ContentOp::B
+                | ContentOp::Bs
+                | ContentOp::Bx
+                | ContentOp::Bxs
+        )
+    }
 }
 
 pub fn render_pdf_page(
@@ -201,36 +995,97 @@ pub fn render_pdf_page(
     page_number: usize,
     config: &ConversionConfig,
 ) -> Result<RenderedPage, OdeError> {
-    use crate::util::math::TransformMatrix;
+    render_pdf_page_with_fonts(document, page_id, page_number, config, &FontIds::new())
+}
+
+/// Render a page, tagging text with the ids of the extracted fonts it uses.
+///
+/// `font_ids` comes from [`extract_fonts_from_document`]. Text in fonts that
+/// are missing from it (not embedded, or not extracted) gets no font id.
+pub fn render_pdf_page_with_fonts(
+    document: &PdfDocument,
+    page_id: usize,
+    page_number: usize,
+    config: &ConversionConfig,
+    font_ids: &FontIds,
+) -> Result<RenderedPage, OdeError> {
+    render_pdf_page_until(document, page_id, page_number, config, font_ids, None)
+}
+
+/// [`render_pdf_page_with_fonts`], giving up with `OdeError::Timeout` once
+/// `deadline` passes.
+pub(crate) fn render_pdf_page_until(
+    document: &PdfDocument,
+    page_id: usize,
+    page_number: usize,
+    config: &ConversionConfig,
+    font_ids: &FontIds,
+    deadline: Option<Deadline>,
+) -> Result<RenderedPage, OdeError> {
+    use crate::util::math::{BoundingBox, TransformMatrix};
+
+    let scope = RenderScope { config, font_ids, deadline, page_number };
 
     let page = document
         .get_page(page_id as u32)
-        .ok_or_else(|| OdeError::PdfParseError(format!("Cannot fetch page {}", page_number)))?;
+        .ok_or_else(|| OdeError::malformed(format!("Cannot fetch page {}", page_number)))?;
 
-    let page_width = page.width;
-    let page_height = page.height;
+    let (page_width, page_height) = page.displayed_size();
 
-    let mut text_extractor = text::TextExtractor::new();
+    // Text is held back until the page is drawn, to know what ends up covered
+    let mut page_text = text::PageText::new(config.covered_text);
 
-    let ops = parse_content_stream(&page.contents)?;
+    let ops = document.content_ops.get_or_parse(page.contents_ref, &page.contents, |data| {
+        parse_content_stream(data, config)
+    })?;
 
     let mut graphics_state = GraphicsState::new();
-    let mut ctm = TransformMatrix::identity(); // Current Transformation Matrix
+    let mut ctm = page.user_space_matrix(); // Current Transformation Matrix
     let mut text_matrix = TransformMatrix::identity();
-    let mut state_stack: Vec<(TransformMatrix, GraphicsState, Option<String>)> = Vec::new();
+    // Start of the current line; Td, TD and T* move from here, not from
+    // where the last shown string left the text matrix
+    let mut line_matrix = TransformMatrix::identity();
+    let mut state_stack: SaveStack<(TransformMatrix, GraphicsState, Option<String>)> =
+        SaveStack::new();
     let mut current_font_name: Option<String> = None;
     let mut background_color: Option<String> = None;
     let mut pending_rect: Option<(f64, f64, f64, f64)> = None;
+    let mut path = path::PathBuilder::default();
+    let mut strokes: Vec<StrokedPath> = Vec::new();
     let mut rendered_images: Vec<PageImageRef> = Vec::new();
-    let mut filled_rects: Vec<(f64, f64, f64, f64, String)> = Vec::new(); // (x, y, w, h, color)
+    let mut filled_rects: Vec<FilledRect> = Vec::new();
+    let mut diagnostics: Vec<String> = Vec::new();
+    let mut marked_content = MarkedContent::new();
 
-    for op in ops {
+    for (i, op) in ops.iter().enumerate() {
+        if i % DEADLINE_CHECK_INTERVAL == 0 {
+            scope.check_deadline()?;
+        }
+        // Hidden layers draw nothing, but still move the text and end paths
+        if marked_content.is_hidden() {
+            match op.operator {
+                ContentOp::Tj | ContentOp::TJ => {
+                    let widths = current_font_name.as_ref().and_then(|n| page.font_widths.get(n));
+                    if let (Some(raw), Some(widths)) = (&op.text_raw, widths) {
+                        advance_text_matrix(&mut text_matrix, widths, raw, &graphics_state);
+                    }
+                    continue;
+                }
+                ContentOp::Do | ContentOp::Sh => continue,
+                operator if operator.paints_path() => {
+                    pending_rect = None;
+                    path.clear();
+                    continue;
+                }
+                _ => {}
+            }
+        }
         match op.operator {
             ContentOp::GsSave => {
-                state_stack.push((ctm, graphics_state.clone(), current_font_name.clone()));
+                state_stack.save((ctm, graphics_state.clone(), current_font_name.clone()));
             }
             ContentOp::GsRestore => {
-                if let Some((saved_ctm, saved_state, saved_font)) = state_stack.pop() {
+                if let Some((saved_ctm, saved_state, saved_font)) = state_stack.restore() {
                     ctm = saved_ctm;
                     graphics_state = saved_state;
                     current_font_name = saved_font;
@@ -251,11 +1106,12 @@ pub fn render_pdf_page(
                 }
             }
             ContentOp::BT => {
-                text_extractor.finalize_segment();
+                page_text.finalize_segment();
                 text_matrix = TransformMatrix::identity();
+                line_matrix = text_matrix;
             }
             ContentOp::ET => {
-                text_extractor.finalize_segment();
+                page_text.finalize_segment();
             }
             ContentOp::Tf => {
                 // Tf operands: font_size (the font name was already consumed by content stream parser)
@@ -267,18 +1123,10 @@ pub fn render_pdf_page(
                 }
                 if let Some(ref name) = op.font_name {
                     current_font_name = Some(name.clone());
-                }
-                if graphics_state.font_info.is_none() {
-                    graphics_state.font_info = Some(crate::render::state::FontInfo {
-                        id: 0,
-                        use_tounicode: true,
-                        em_size: 1000.0,
-                        space_width: 250.0,
-                        ascent: 0.8,
-                        descent: -0.2,
-                        is_type3: false,
-                        font_size_scale: 1.0,
-                    });
+                    graphics_state.font_info =
+                        resolve_font_info(name, &page.font_refs, page, font_ids);
+                    graphics_state.vertical =
+                        page.font_widths.get(name).is_some_and(GlyphWidths::is_vertical);
                 }
             }
             ContentOp::Tm => {
@@ -291,43 +1139,76 @@ pub fn render_pdf_page(
                         e: op.operands[4],
                         f: op.operands[5],
                     };
+                    line_matrix = text_matrix;
                 }
             }
             ContentOp::Td => {
                 if op.operands.len() >= 2 {
                     let tx = op.operands[0];
                     let ty = op.operands[1];
-                    text_matrix.e += tx * text_matrix.a + ty * text_matrix.c;
-                    text_matrix.f += tx * text_matrix.b + ty * text_matrix.d;
+                    line_matrix.e += tx * line_matrix.a + ty * line_matrix.c;
+                    line_matrix.f += tx * line_matrix.b + ty * line_matrix.d;
+                    text_matrix = line_matrix;
                 }
             }
             ContentOp::TD => {
                 if op.operands.len() >= 2 {
                     let tx = op.operands[0];
                     let ty = op.operands[1];
-                    text_matrix.e += tx * text_matrix.a + ty * text_matrix.c;
-                    text_matrix.f += tx * text_matrix.b + ty * text_matrix.d;
+                    line_matrix.e += tx * line_matrix.a + ty * line_matrix.c;
+                    line_matrix.f += tx * line_matrix.b + ty * line_matrix.d;
+                    text_matrix = line_matrix;
+                    graphics_state.leading = -ty;
+                }
+            }
+            ContentOp::TL => {
+                if let Some(&leading) = op.operands.first() {
+                    graphics_state.leading = leading;
                 }
             }
+            ContentOp::Tstar => {
+                let ty = -graphics_state.leading;
+                line_matrix.e += ty * line_matrix.c;
+                line_matrix.f += ty * line_matrix.d;
+                text_matrix = line_matrix;
+            }
             ContentOp::Tj | ContentOp::TJ => {
                 // Decode text through ToUnicode CMap if available
-                let decoded_text = if let Some(ref raw) = op.text_raw {
-                    if let Some(ref font_name) = current_font_name {
-                        if let Some(cmap) = page.font_cmaps.get(font_name) {
-                            if !cmap.char_map.is_empty() {
-                                Some(cmap.decode_bytes(raw))
-                            } else {
-                                op.text.clone()
-                            }
-                        } else {
-                            op.text.clone()
+                let cmap = current_font_name.as_ref().and_then(|n| page.font_cmaps.get(n));
+                let mut decoded_text = decode_shown_text(op, cmap, config.unmapped_glyph);
+
+                let widths = current_font_name.as_ref().and_then(|n| page.font_widths.get(n));
+                let mut next_text_matrix = text_matrix;
+                if let (Some(raw), Some(widths)) = (&op.text_raw, widths) {
+                    advance_text_matrix(&mut next_text_matrix, widths, raw, &graphics_state);
+                }
+
+                // Type3 glyphs are drawn by their own procedures; text those
+                // show stands in for the codes when there is no ToUnicode map
+                let type3 = current_font_name.as_ref().and_then(|n| page.type3_fonts.get(n));
+                if let (Some(font), Some(raw)) = (type3, &op.text_raw) {
+                    let mut shown = String::new();
+                    let state = &graphics_state;
+                    let placements =
+                        type3_glyph_placements(font, raw, widths, &text_matrix, &ctm, state);
+                    for (glyph, glyph_ctm) in placements {
+                        if let Ok(drawn) = render_form_xobject(
+                            glyph, &glyph_ctm, &graphics_state, &current_font_name, page, &scope,
+                        ) {
+                            shown.extend(drawn.text_spans.iter().map(|span| span.text.as_str()));
+                            rendered_images.extend(drawn.images);
+                            diagnostics.extend(
+                                drawn
+                                    .diagnostics
+                                    .iter()
+                                    .map(|d| format!("Type3 glyph /{}: {}", glyph.name, d)),
+                            );
                         }
-                    } else {
-                        op.text.clone()
                     }
-                } else {
-                    op.text.clone()
-                };
+                    if !font.has_to_unicode && !shown.is_empty() {
+                        decoded_text = Some(shown);
+                    }
+                }
 
                 if let Some(text) = &decoded_text {
                     // Apply CTM to the text position from Tm
@@ -342,18 +1223,48 @@ pub fn render_pdf_page(
                     // PDF coordinate system has Y=0 at bottom, increasing upward.
                     // HTML/CSS has Y=0 at top, increasing downward.
                     // Also adjust for baseline: PDF positions text at baseline,
-                    // but CSS top positions the top of the element. Subtract
-                    // approximate ascent (~85% of font size) to align correctly.
-                    let html_y = page_height - page_y - effective_font_size * 0.85;
+                    // but CSS top positions the top of the element, so subtract
+                    // the font's ascent.
+                    let ascent = effective_font_size * text_ascent(&graphics_state);
+                    let html_y = page_height - page_y - ascent;
 
                     let mut state_for_text = graphics_state.clone();
                     state_for_text.font_size = effective_font_size;
+                    // Tc and Tw scale to the page like the font size
+                    let spacing_scale =
+                        if config.text_spacing { tm_scale_y * ctm_scale_y } else { 0.0 };
+                    state_for_text.letter_space *= spacing_scale;
+                    state_for_text.word_space *= spacing_scale;
+                    // Clip-only text stays in the output for search, unseen
+                    if graphics_state.render_mode == 7 {
+                        state_for_text.fill_color = crate::types::color::Color::transparent();
+                    }
 
-                    text_extractor.update_state(&state_for_text);
-                    text_extractor
-                        .add_text(text, page_x, html_y)
-                        .map_err(|e| OdeError::TextError(format!("Failed to add text: {}", e)))?;
+                    let (x, y, extent, text_box) = if graphics_state.vertical {
+                        let (x, y, text_box) = vertical_text_placement(
+                            (page_x, page_y),
+                            &next_text_matrix,
+                            &ctm,
+                            effective_font_size,
+                            page_height,
+                        );
+                        (x, y, None, text_box)
+                    } else {
+                        // Tz squeezes the gaps between words along with the glyphs
+                        let space_size = effective_font_size * graphics_state.transform_matrix.a;
+                        let extent =
+                            widths.map(|w| text_extent(w, &next_text_matrix, &ctm, space_size));
+                        let end_x = extent.as_ref().map_or(page_x, |e| e.end_x);
+                        let text_box =
+                            BoundingBox::new(page_x, page_y, end_x, page_y + effective_font_size);
+                        (page_x, html_y, extent, text_box)
+                    };
+                    if !graphics_state.clip.excludes(&text_box) {
+                        page_text.update_state(&state_for_text);
+                        page_text.add_text_with_extent(text, x, y, extent);
+                    }
                 }
+                text_matrix = next_text_matrix;
             }
             ContentOp::Tc => {
                 if !op.operands.is_empty() {
@@ -365,105 +1276,138 @@ pub fn render_pdf_page(
                     graphics_state.word_space = op.operands[0];
                 }
             }
+            ContentOp::Tr => {
+                if let Some(mode) = op.operands.first() {
+                    graphics_state.render_mode = mode.clamp(0.0, 7.0) as u8;
+                }
+            }
             ContentOp::Tz => {
                 if !op.operands.is_empty() {
                     let scale = op.operands[0];
                     graphics_state.transform_matrix.a = scale / 100.0;
                 }
             }
-            ContentOp::TL => {}
-            ContentOp::Tstar => {
-                if let Some(ref font_info) = graphics_state.font_info {
-                    if crate::util::math::equal(font_info.em_size, 0.0) {
-                        text_matrix.f += -text_matrix.d;
-                    }
-                }
-            }
-            ContentOp::RGfill => {
-                if op.operands.len() >= 3 {
-                    let r = (op.operands[0].clamp(0.0, 1.0) * 255.0) as u8;
-                    let g = (op.operands[1].clamp(0.0, 1.0) * 255.0) as u8;
-                    let b = (op.operands[2].clamp(0.0, 1.0) * 255.0) as u8;
-                    graphics_state.fill_color = crate::types::color::Color::new(r, g, b);
-                }
-            }
-            ContentOp::RG => {
-                if op.operands.len() >= 3 {
-                    let r = (op.operands[0].clamp(0.0, 1.0) * 255.0) as u8;
-                    let g = (op.operands[1].clamp(0.0, 1.0) * 255.0) as u8;
-                    let b = (op.operands[2].clamp(0.0, 1.0) * 255.0) as u8;
-                    graphics_state.stroke_color = crate::types::color::Color::new(r, g, b);
-                }
-            }
-            ContentOp::Gfill => {
-                if !op.operands.is_empty() {
-                    let v = (op.operands[0].clamp(0.0, 1.0) * 255.0) as u8;
-                    graphics_state.fill_color = crate::types::color::Color::new(v, v, v);
-                }
+            operator if operator.sets_color() => {
+                apply_color_op(&mut graphics_state, op, &[&page.color_spaces], &page.patterns);
             }
-            ContentOp::Gstroke => {
-                if !op.operands.is_empty() {
-                    let v = (op.operands[0].clamp(0.0, 1.0) * 255.0) as u8;
-                    graphics_state.stroke_color = crate::types::color::Color::new(v, v, v);
+            ContentOp::LineWidth => {
+                if let Some(&width) = op.operands.first() {
+                    graphics_state.line_width = width;
                 }
             }
-            ContentOp::Kfill => {
-                if op.operands.len() >= 4 {
-                    let c = op.operands[0];
-                    let m = op.operands[1];
-                    let y = op.operands[2];
-                    let k = op.operands[3];
-                    let r = ((1.0 - c) * (1.0 - k) * 255.0) as u8;
-                    let g = ((1.0 - m) * (1.0 - k) * 255.0) as u8;
-                    let b = ((1.0 - y) * (1.0 - k) * 255.0) as u8;
-                    graphics_state.fill_color = crate::types::color::Color::new(r, g, b);
-                }
-            }
-            ContentOp::SCN | ContentOp::SC => {
-                // sc/SC with 3 operands = DeviceRGB color
-                // sc/SC with 1 operand = DeviceGray color
-                // scn/SCN with 0 operands = Pattern fill (name consumed as font_name) — skip
-                if op.operands.len() >= 3 {
-                    let r = (op.operands[0].clamp(0.0, 1.0) * 255.0) as u8;
-                    let g = (op.operands[1].clamp(0.0, 1.0) * 255.0) as u8;
-                    let b = (op.operands[2].clamp(0.0, 1.0) * 255.0) as u8;
-                    graphics_state.fill_color = crate::types::color::Color::new(r, g, b);
-                } else if op.operands.len() == 1 {
-                    let v = (op.operands[0].clamp(0.0, 1.0) * 255.0) as u8;
-                    graphics_state.fill_color = crate::types::color::Color::new(v, v, v);
-                }
-                // 0 operands = Pattern fill — don't change fill_color
+            ContentOp::M | ContentOp::L | ContentOp::C | ContentOp::H => {
+                path.apply(op.operator, &op.operands, &ctm);
             }
             ContentOp::RE => {
                 // Track rectangle for background detection
                 if op.operands.len() >= 4 {
                     pending_rect = Some((op.operands[0], op.operands[1], op.operands[2], op.operands[3]));
                 }
+                path.apply(op.operator, &op.operands, &ctm);
             }
-            ContentOp::F | ContentOp::Fs => {
-                // Fill operation — render filled rectangles
-                if let Some((rx, ry, rw, rh)) = pending_rect {
-                    let (x1, y1) = ctm.transform_point(rx, ry);
-                    let (x2, y2) = ctm.transform_point(rx + rw, ry + rh);
-                    let abs_x = x1.min(x2);
-                    let abs_w = (x2 - x1).abs();
-                    let abs_h = (y2 - y1).abs();
-                    let abs_y = page_height - y1.max(y2);
-
+            ContentOp::F
+            | ContentOp::Fs
+            | ContentOp::B
+            | ContentOp::Bs
+            | ContentOp::Bx
+            | ContentOp::Bxs => {
+                let color = graphics_state.fill_color.to_css_string();
+                let mut fill = pending_rect.and_then(|rect| {
+                    clipped_fill(rect, &ctm, &graphics_state.clip, page_height, color)
+                });
+                // A shading pattern fills the rect with its gradient, laid
+                // out in the page's default space wherever the rect is drawn
+                let pattern = match graphics_state.fill_color_space {
+                    ColorSpace::Pattern { .. } => graphics_state
+                        .fill_pattern
+                        .as_ref()
+                        .and_then(|name| page.patterns.get(name)),
+                    _ => None,
+                };
+                if let (Some(fill), Some(Pattern::Shading { shading, matrix })) =
+                    (&mut fill, pattern)
+                {
+                    let [a, b, c, d, e, f] = *matrix;
+                    let pattern_ctm =
+                        page.user_space_matrix() * TransformMatrix { a, b, c, d, e, f };
+                    let (bottom, top) = (page_height - fill.y - fill.height, page_height - fill.y);
+                    let area = BoundingBox::new(fill.x, bottom, fill.x + fill.width, top);
+                    if let Some(css) = gradient::css_gradient(shading, &pattern_ctm, &area) {
+                        fill.color = css;
+                    }
+                }
+                if let Some(fill) = fill {
+                    page_text.cover(fill.x, fill.y, fill.width, fill.height);
                     // Full-page rect becomes background_color
                     if background_color.is_none()
-                        && abs_w >= page_width * 0.9
-                        && abs_h >= page_height * 0.9
+                        && fill.width >= page_width * 0.9
+                        && fill.height >= page_height * 0.9
                     {
-                        background_color = Some(graphics_state.fill_color.to_css_string());
-                    } else if abs_w >= 5.0 && abs_h >= 5.0 {
-                        // Significant rectangles rendered as colored divs
-                        let color = graphics_state.fill_color.to_css_string();
-                        filled_rects.push((abs_x, abs_y, abs_w, abs_h, color));
+                        background_color = Some(fill.color);
+                    } else {
+                        filled_rects.push(fill);
+                    }
+                }
+                if !matches!(op.operator, ContentOp::F | ContentOp::Fs) {
+                    if matches!(op.operator, ContentOp::Bs | ContentOp::Bxs) {
+                        path.close();
                     }
+                    strokes.extend(path.stroke(&graphics_state, &ctm, page_height));
+                }
+                pending_rect = None;
+                path.clear();
+            }
+            ContentOp::W | ContentOp::Wstar => {
+                if let Some((rx, ry, rw, rh)) = pending_rect {
+                    let bbox = BoundingBox::new(rx, ry, rx + rw, ry + rh).transform(&ctm);
+                    graphics_state.clip.intersect(&ClipState::from_bbox(&bbox));
                 }
+            }
+            ContentOp::S | ContentOp::Ss => {
+                if op.operator == ContentOp::Ss {
+                    path.close();
+                }
+                strokes.extend(path.stroke(&graphics_state, &ctm, page_height));
+                pending_rect = None;
+                path.clear();
+            }
+            ContentOp::N => {
                 pending_rect = None;
+                path.clear();
+            }
+            ContentOp::Sh => {
+                // Paints the clip region, or as much of it as the /BBox covers
+                let shading = op.font_name.as_ref().and_then(|name| page.shadings.get(name));
+                if let Some(shading) = shading {
+                    let mut area = graphics_state.clip.clone();
+                    let page_box = BoundingBox::new(0.0, 0.0, page_width, page_height);
+                    area.intersect(&ClipState::from_bbox(&page_box));
+                    if let Some([x0, y0, x1, y1]) = shading.bbox {
+                        let bbox = BoundingBox::new(x0, y0, x1, y1).transform(&ctm);
+                        area.intersect(&ClipState::from_bbox(&bbox));
+                    }
+                    if !area.is_empty() {
+                        let area = BoundingBox::new(area.xmin, area.ymin, area.xmax, area.ymax);
+                        if let Some(css) = gradient::css_gradient(shading, &ctm, &area) {
+                            let top = page_height - area.y1;
+                            filled_rects.push(FilledRect::new(
+                                area.x0,
+                                top,
+                                area.width(),
+                                area.height(),
+                                css,
+                            ));
+                        }
+                    }
+                }
+            }
+            ContentOp::BDC => {
+                let name = op.font_name.as_ref();
+                let hidden = name.is_some_and(|name| page.hidden_content.contains(name));
+                marked_content.begin(hidden, op.lang.as_ref(), op.mcid, &mut graphics_state);
             }
+            ContentOp::BMC => marked_content.begin(false, None, None, &mut graphics_state),
+            ContentOp::EMC => marked_content.end(&mut graphics_state),
             ContentOp::Do => {
                 if let Some(ref xobj_name) = op.font_name {
                     // Check if it's an image XObject
@@ -474,31 +1418,32 @@ pub fn render_pdf_page(
                         let w = (x2 - x).abs();
                         let h = (y2 - y).abs();
                         let img_y = page_height - y.max(y2);
-
-                        use base64::Engine;
-                        let b64 = base64::engine::general_purpose::STANDARD.encode(&img.data);
-                        let data_uri = format!("data:{};base64,{}", img.mime_type, b64);
-
-                        rendered_images.push(PageImageRef {
-                            name: xobj_name.clone(),
-                            x,
-                            y: img_y,
-                            width: w,
-                            height: h,
-                            data_uri,
-                        });
+                        rendered_images.push(PageImageRef::new(img, x, img_y, w, h));
+                        diagnostics.extend(unsupported_image(img));
                     }
                     // Check if it's a Form XObject — render its content recursively
                     else if let Some(form) = page.form_xobjects.get(xobj_name) {
-                        if let Ok(form_result) = render_form_xobject(
-                            form, &ctm, page_width, page_height,
-                            &graphics_state, &current_font_name, page,
+                        match render_form_xobject(
+                            form, &ctm, &graphics_state, &current_font_name, page, &scope,
                         ) {
-                            text_extractor.merge_spans(&form_result.text_spans);
-                            rendered_images.extend(form_result.images);
-                            if background_color.is_none() {
-                                background_color = form_result.background_color;
+                            Ok(form_result) => {
+                                // The form's text is drawn over its own fills
+                                for fill in &form_result.filled_rects {
+                                    page_text.cover(fill.x, fill.y, fill.width, fill.height);
+                                }
+                                page_text.merge_spans(&form_result.text_spans);
+                                rendered_images.extend(form_result.images);
+                                filled_rects.extend(form_result.filled_rects);
+                                strokes.extend(form_result.strokes);
+                                diagnostics.extend(
+                                    form_result
+                                        .diagnostics
+                                        .iter()
+                                        .map(|d| format!("form /{}: {}", xobj_name, d)),
+                                );
                             }
+                            Err(e @ OdeError::Timeout(_)) => return Err(e),
+                            Err(_) => {}
                         }
                     }
                 }
@@ -507,9 +1452,46 @@ pub fn render_pdf_page(
         }
     }
 
-    text_extractor.finalize_segment();
+    let mut text_extractor = text::TextExtractor::new().with_merging(config.merge_spans);
+    page_text
+        .replay(&mut text_extractor, config.covered_text)
+        .map_err(|e| OdeError::TextError(format!("Failed to add text: {}", e)))?;
+    text_extractor.coalesce();
+    if let Some(imbalance) = state_stack.imbalance() {
+        diagnostics.push(imbalance);
+    }
 
-    let text_spans = text_extractor.get_spans();
+    let mut text_spans = text_extractor.get_spans();
+    render_form_widgets(
+        page,
+        &scope,
+        &mut text_spans,
+        &mut rendered_images,
+        &mut filled_rects,
+        &mut strokes,
+    );
+    if let Some(tolerance) = config.decoration_tolerance {
+        decoration::apply_text_decorations(
+            &mut text_spans,
+            &mut filled_rects,
+            &mut strokes,
+            tolerance,
+        );
+    }
+    let reading_order = config
+        .column_gap
+        .map(|gap| reading_order::reading_order(&text_spans, gap))
+        .unwrap_or_default();
+    let scale = config.output_scale;
+    let mut overlays = displayed_overlays(page, config);
+    scale_page_geometry(
+        scale,
+        &mut text_spans,
+        &mut rendered_images,
+        &mut filled_rects,
+        &mut strokes,
+        &mut overlays,
+    );
     let font_ids: Vec<u64> = text_spans
         .iter()
         .filter_map(|span| span.font_id)
@@ -517,41 +1499,452 @@ pub fn render_pdf_page(
         .into_iter()
         .collect();
 
-    let html = generate_page_html_with_images_and_text(
-        page_number, page_width, page_height, config, &text_spans, &rendered_images, &filled_rects,
-    );
-    let css = generate_page_css(config);
-
+    let (width, height) = (page_width * scale, page_height * scale);
+    let html = match config.output_format {
+        OutputFormat::Html => {
+            let (html, image_ranges) = generate_page_html_with_images_and_text(
+                width,
+                height,
+                config,
+                &PageContent {
+                    filled_rects: &filled_rects,
+                    strokes: &strokes,
+                    images: &rendered_images,
+                    text_spans: &text_spans,
+                },
+                &overlays,
+            );
+            for (image, range) in rendered_images.iter_mut().zip(image_ranges) {
+                image.html_range = Some(range);
+            }
+            html
+        }
+        OutputFormat::Text | OutputFormat::Json => String::new(),
+    };
     Ok(RenderedPage {
         page_number,
-        width: page_width,
-        height: page_height,
+        width,
+        height,
         html,
-        css,
+        // Every rule pages need so far is in `shared_css`
+        css: String::new(),
         text_spans,
+        reading_order,
         font_ids,
         background_color,
         images: rendered_images,
+        filled_rects,
+        strokes,
+        diagnostics,
+    })
+}
+
+/// An annotation rect turned with the page, the same way content is.
+fn displayed_rect(page: &crate::parser::PdfPage, rect: [f64; 4]) -> [f64; 4] {
+    use crate::util::math::BoundingBox;
+
+    let [x0, y0, x1, y1] = rect;
+    let turned = BoundingBox::new(x0, y0, x1, y1).transform(&page.user_space_matrix());
+    [turned.x0, turned.y0, turned.x1, turned.y1]
+}
+
+/// Side of the marker drawn for an annotation's comment, in points.
+const NOTE_MARKER_SIZE: f64 = 12.0;
+
+/// What is drawn over the page content, with rects in displayed space.
+struct PageOverlays {
+    links: Vec<LinkAnnotation>,
+    /// Form fields, as empty elements keyboard users can tab to.
+    fields: Vec<FieldTarget>,
+    /// Comment markers, each at the top left corner of its annotation.
+    notes: Vec<NoteAnnotation>,
+}
+
+struct FieldTarget {
+    rect: [f64; 4],
+    name: String,
+    tab_index: usize,
+}
+
+/// The page's links, form fields and, with `config.note_tooltips`, comment
+/// markers, with their rects turned with the page.
+fn displayed_overlays(page: &crate::parser::PdfPage, config: &ConversionConfig) -> PageOverlays {
+    let links = page
+        .links
+        .iter()
+        .map(|link| LinkAnnotation {
+            rect: displayed_rect(page, link.rect),
+            ..link.clone()
+        })
+        .collect();
+    let fields = page
+        .form_widgets
+        .iter()
+        .filter_map(|widget| {
+            Some(FieldTarget {
+                rect: displayed_rect(page, widget.rect),
+                name: widget.name.clone(),
+                tab_index: widget.tab_index?,
+            })
+        })
+        .collect();
+    let notes = page
+        .notes
+        .iter()
+        .filter(|_| config.note_tooltips)
+        .map(|note| {
+            let [x0, _, _, y1] = displayed_rect(page, note.rect);
+            NoteAnnotation {
+                rect: [x0, y1 - NOTE_MARKER_SIZE, x0 + NOTE_MARKER_SIZE, y1],
+                contents: note.contents.clone(),
+            }
+        })
+        .collect();
+    PageOverlays {
+        links,
+        fields,
+        notes,
+    }
+}
+
+/// Draw each form widget inside its rect. A button with an appearance for its
+/// state draws it, mapped from its `/BBox` onto the rect; other widgets, and
+/// appearances that leave nothing the page can show, get their `/MK`
+/// background and border and a span with their value: text fields their
+/// text, check boxes and radio buttons a mark for their state. Widgets
+/// without a `/DA` font size get one that fits their height.
+fn render_form_widgets(
+    page: &crate::parser::PdfPage,
+    scope: &RenderScope,
+    text_spans: &mut Vec<TextSpan>,
+    images: &mut Vec<PageImageRef>,
+    filled_rects: &mut Vec<FilledRect>,
+    strokes: &mut Vec<StrokedPath>,
+) {
+    use crate::parser::FormValue;
+    use crate::util::math::{BoundingBox, TransformMatrix};
+
+    let (_, page_height) = page.displayed_size();
+    for widget in &page.form_widgets {
+        if let Some(appearance) = &widget.appearance {
+            let [bx0, by0, bx1, by1] = appearance.bbox;
+            let bbox = match appearance.matrix {
+                Some([a, b, c, d, e, f]) => BoundingBox::new(bx0, by0, bx1, by1)
+                    .transform(&TransformMatrix { a, b, c, d, e, f }),
+                None => BoundingBox::new(bx0, by0, bx1, by1),
+            };
+            let [x0, y0, x1, y1] = widget.rect;
+            let drawn = (bbox.width() > 0.0 && bbox.height() > 0.0)
+                .then(|| {
+                    let (sx, sy) = ((x1 - x0) / bbox.width(), (y1 - y0) / bbox.height());
+                    let fit = TransformMatrix {
+                        a: sx, b: 0.0, c: 0.0, d: sy, e: x0 - bbox.x0 * sx, f: y0 - bbox.y0 * sy,
+                    };
+                    let ctm = page.user_space_matrix() * fit;
+                    render_form_xobject(
+                        appearance, &ctm, &GraphicsState::new(), &None, page, scope,
+                    )
+                    .ok()
+                })
+                .flatten()
+                .filter(|drawn| !drawn.text_spans.is_empty() || !drawn.images.is_empty());
+            if let Some(drawn) = drawn {
+                // Buttons draw their marks in ZapfDingbats
+                text_spans.extend(drawn.text_spans.into_iter().map(|span| TextSpan {
+                    text: span.text.chars().map(|c| dingbat_mark(c).unwrap_or(c)).collect(),
+                    ..span
+                }));
+                images.extend(drawn.images);
+                filled_rects.extend(drawn.filled_rects);
+                strokes.extend(drawn.strokes);
+                continue;
+            }
+        }
+
+        let [x0, y0, x1, y1] = displayed_rect(page, widget.rect);
+        let (width, height) = (x1 - x0, y1 - y0);
+        if let Some(background) = widget.background {
+            let color = background.to_css_string();
+            filled_rects.push(FilledRect::new(x0, page_height - y1, width, height, color));
+        }
+        if let Some(border) = widget.border {
+            let color = border.to_css_string();
+            let top = page_height - y1;
+            filled_rects.push(FilledRect::new(x0, top, width, 1.0, color.clone()));
+            filled_rects.push(FilledRect::new(x0, top + height - 1.0, width, 1.0, color.clone()));
+            filled_rects.push(FilledRect::new(x0, top, 1.0, height, color.clone()));
+            filled_rects.push(FilledRect::new(x1 - 1.0, top, 1.0, height, color));
+        }
+
+        // An on button shows its /MK caption when it has one
+        let mark = |on: bool, checked: &str, unchecked: &str| {
+            let caption = widget.caption.as_deref().and_then(|c| c.chars().next());
+            match caption.filter(|_| on) {
+                Some(caption) => dingbat_mark(caption).unwrap_or(caption).to_string(),
+                None if on => checked.to_string(),
+                None => unchecked.to_string(),
+            }
+        };
+        let text = match &widget.value {
+            FormValue::Text(text) if text.trim().is_empty() => continue,
+            FormValue::Text(text) => text.replace(['\r', '\n'], " "),
+            FormValue::CheckBox(on) => mark(*on, "\u{2611}", "\u{2610}"),
+            FormValue::RadioButton(on) => mark(*on, "\u{25C9}", "\u{25CB}"),
+        };
+        let font_size = if widget.font_size > 0.0 {
+            widget.font_size
+        } else {
+            (height * 0.7).max(1.0)
+        };
+        let text_width = text::estimated_advance(&text, font_size);
+        // The line's box, centered vertically in the widget's rect
+        let line_height = (font_size * (DEFAULT_ASCENT - DEFAULT_DESCENT)).min(height);
+        text_spans.push(TextSpan {
+            text,
+            x: x0 + 2.0,
+            y: page_height - y1 + (height - line_height) / 2.0,
+            font_size,
+            font_id: None,
+            color: crate::types::color::Color::new(0, 0, 0).to_css_string(),
+            covered: false,
+            scale_x: 1.0,
+            vertical: false,
+            width: text_width,
+            height: line_height,
+            underline: false,
+            line_through: false,
+            letter_spacing: 0.0,
+            word_spacing: 0.0,
+            lang: None,
+            mcid: None,
+        });
+    }
+}
+
+/// The character a ZapfDingbats check box or radio button style shows:
+/// check, circle, cross, diamond, square or star.
+fn dingbat_mark(code: char) -> Option<char> {
+    match code {
+        '4' => Some('\u{2714}'),
+        'l' => Some('\u{25CF}'),
+        '8' => Some('\u{2718}'),
+        'u' => Some('\u{25C6}'),
+        'n' => Some('\u{25A0}'),
+        'H' => Some('\u{2605}'),
+        _ => None,
+    }
+}
+
+/// Convert the geometry collected in PDF points to output pixels.
+fn scale_page_geometry(
+    scale: f64,
+    text_spans: &mut [TextSpan],
+    images: &mut [PageImageRef],
+    filled_rects: &mut [FilledRect],
+    strokes: &mut [StrokedPath],
+    overlays: &mut PageOverlays,
+) {
+    if scale == 1.0 {
+        return;
+    }
+    for span in text_spans {
+        span.x *= scale;
+        span.y *= scale;
+        span.font_size *= scale;
+        span.width *= scale;
+        span.height *= scale;
+        span.letter_spacing *= scale;
+        span.word_spacing *= scale;
+    }
+    for img in images {
+        img.x *= scale;
+        img.y *= scale;
+        img.width *= scale;
+        img.height *= scale;
+    }
+    for rect in filled_rects {
+        rect.x *= scale;
+        rect.y *= scale;
+        rect.width *= scale;
+        rect.height *= scale;
+    }
+    for stroke in strokes {
+        stroke.scale(scale);
+    }
+    for link in &mut overlays.links {
+        link.rect = link.rect.map(|v| v * scale);
+    }
+    for field in &mut overlays.fields {
+        field.rect = field.rect.map(|v| v * scale);
+    }
+    for note in &mut overlays.notes {
+        note.rect = note.rect.map(|v| v * scale);
+    }
+}
+
+/// What the clip leaves of a filled `re` rectangle `(x, y, w, h)`, placed on
+/// a page `page_height` tall; `None` when nothing shows.
+fn clipped_fill(
+    (x, y, w, h): (f64, f64, f64, f64),
+    ctm: &crate::util::math::TransformMatrix,
+    clip: &ClipState,
+    page_height: f64,
+    color: String,
+) -> Option<FilledRect> {
+    use crate::util::math::BoundingBox;
+
+    let mut area = ClipState::from_bbox(&BoundingBox::new(x, y, x + w, y + h).transform(ctm));
+    area.intersect(clip);
+    (!area.is_empty()).then(|| {
+        let (width, height) = (area.xmax - area.xmin, area.ymax - area.ymin);
+        FilledRect::new(area.xmin, page_height - area.ymax, width, height, color)
     })
 }
 
 struct FormRenderResult {
     text_spans: Vec<TextSpan>,
     images: Vec<PageImageRef>,
-    background_color: Option<String>,
+    filled_rects: Vec<FilledRect>,
+    strokes: Vec<StrokedPath>,
+    diagnostics: Vec<String>,
+}
+
+/// The color `sc`, `scn` or their stroking forms select in `space`. A
+/// pattern named in a Pattern space stands in with one color, see
+/// [`Pattern::color`]; an uncolored one takes the operands' color.
+fn selected_color(
+    space: &ColorSpace,
+    op: &ParsedOp,
+    patterns: &std::collections::HashMap<String, Pattern>,
+) -> Option<crate::types::color::Color> {
+    let pattern = match space {
+        ColorSpace::Pattern { .. } => op.font_name.as_ref().and_then(|name| patterns.get(name)),
+        _ => None,
+    };
+    pattern
+        .and_then(Pattern::color)
+        .or_else(|| space.to_rgb(&op.operands))
+}
+
+/// Apply a color operator to `graphics_state`, for both the page and the
+/// forms it draws. `cs`/`CS` look a named space up in `color_spaces` in
+/// order, so a form's own resources come before the page's.
+fn apply_color_op(
+    graphics_state: &mut GraphicsState,
+    op: &ParsedOp,
+    color_spaces: &[&std::collections::HashMap<String, ColorSpace>],
+    patterns: &std::collections::HashMap<String, Pattern>,
+) {
+    let device = match op.operator {
+        ContentOp::RGfill | ContentOp::RG => Some(ColorSpace::DeviceRGB),
+        ContentOp::Gfill | ContentOp::Gstroke => Some(ColorSpace::DeviceGray),
+        ContentOp::Kfill | ContentOp::Kstroke => Some(ColorSpace::DeviceCMYK),
+        _ => None,
+    };
+    let fill = matches!(
+        op.operator,
+        ContentOp::RGfill
+            | ContentOp::Gfill
+            | ContentOp::Kfill
+            | ContentOp::Cs
+            | ContentOp::SC
+            | ContentOp::SCN
+    );
+
+    if let Some(space) = device {
+        // rg, g and k set the space along with the color
+        let Some(color) = op
+            .operands
+            .get(..space.components() as usize)
+            .and_then(|operands| space.to_rgb(operands))
+        else {
+            return;
+        };
+        if fill {
+            graphics_state.fill_color = color;
+            graphics_state.fill_color_space = space;
+            graphics_state.fill_pattern = None;
+        } else {
+            graphics_state.stroke_color = color;
+            graphics_state.stroke_color_space = space;
+        }
+        return;
+    }
+
+    match op.operator {
+        ContentOp::Cs | ContentOp::CS => {
+            // cs/CS select a device space or a named /ColorSpace resource
+            let space = op.font_name.as_ref().and_then(|name| {
+                ColorSpace::from_name(name)
+                    .or_else(|| color_spaces.iter().find_map(|spaces| spaces.get(name).cloned()))
+            });
+            if let Some(space) = space {
+                if fill {
+                    graphics_state.fill_color = space.initial_color();
+                    graphics_state.fill_color_space = space;
+                    graphics_state.fill_pattern = None;
+                } else {
+                    graphics_state.stroke_color = space.initial_color();
+                    graphics_state.stroke_color_space = space;
+                }
+            }
+        }
+        _ if fill => {
+            // Operands are interpreted in the space set by cs
+            let space = &graphics_state.fill_color_space;
+            if let Some(color) = selected_color(space, op, patterns) {
+                graphics_state.fill_color = color;
+            }
+            if matches!(space, ColorSpace::Pattern { .. }) {
+                graphics_state.fill_pattern = op.font_name.clone();
+            }
+        }
+        _ => {
+            let space = &graphics_state.stroke_color_space;
+            if let Some(color) = selected_color(space, op, patterns) {
+                graphics_state.stroke_color = color;
+            }
+        }
+    }
+}
+
+/// What every form drawn on a page is rendered with.
+struct RenderScope<'a> {
+    config: &'a ConversionConfig,
+    font_ids: &'a FontIds,
+    deadline: Option<Deadline>,
+    page_number: usize,
+}
+
+impl RenderScope<'_> {
+    /// `OdeError::Timeout` once the conversion's deadline, if any, has passed.
+    fn check_deadline(&self) -> Result<(), OdeError> {
+        check_deadline(self.deadline, self.page_number)
+    }
+}
+
+fn check_deadline(deadline: Option<Deadline>, page_number: usize) -> Result<(), OdeError> {
+    match deadline {
+        Some(deadline) => deadline.check(page_number),
+        None => Ok(()),
+    }
 }
 
 /// Recursively render a Form XObject's content stream.
 fn render_form_xobject(
     form: &crate::parser::FormXObject,
     parent_ctm: &crate::util::math::TransformMatrix,
-    page_width: f64,
-    page_height: f64,
     parent_gs: &GraphicsState,
     parent_font_name: &Option<String>,
     page: &crate::parser::PdfPage,
+    scope: &RenderScope,
 ) -> Result<FormRenderResult, OdeError> {
-    use crate::util::math::TransformMatrix;
+    use crate::util::math::{BoundingBox, TransformMatrix};
+
+    let (config, font_ids) = (scope.config, scope.font_ids);
+    // Forms nest and repeat, so this is where runaway content spends its time
+    scope.check_deadline()?;
+    let (_, page_height) = page.displayed_size();
 
     // Apply the form's own Matrix to the parent CTM
     let form_ctm = if let Some(m) = form.matrix {
@@ -563,17 +1956,31 @@ fn render_form_xobject(
         *parent_ctm
     };
 
-    let mut text_extractor = text::TextExtractor::new();
-    let ops = parse_content_stream(&form.content_stream)?;
+    let mut text_extractor = text::TextExtractor::new().with_merging(config.merge_spans);
+    let ops = parse_content_stream(&form.content_stream, config)?;
 
     let mut ctm = form_ctm;
     let mut graphics_state = parent_gs.clone();
+    // Nothing the form draws shows outside its /BBox; forms without one
+    // (and Type3 glyphs with an all-zero /FontBBox) go unclipped
+    let [bx0, by0, bx1, by1] = form.bbox;
+    let bbox = BoundingBox::new(bx0, by0, bx1, by1);
+    if bbox.width() > 0.0 && bbox.height() > 0.0 {
+        graphics_state.clip.intersect(&ClipState::from_bbox(&bbox.transform(&form_ctm)));
+    }
     let mut text_matrix = TransformMatrix::identity();
-    let mut state_stack: Vec<(TransformMatrix, GraphicsState, Option<String>)> = Vec::new();
+    // Start of the current line; Td, TD and T* move from here, not from
+    // where the last shown string left the text matrix
+    let mut line_matrix = TransformMatrix::identity();
+    let mut state_stack: SaveStack<(TransformMatrix, GraphicsState, Option<String>)> =
+        SaveStack::new();
     let mut current_font_name: Option<String> = parent_font_name.clone();
-    let mut background_color: Option<String> = None;
     let mut pending_rect: Option<(f64, f64, f64, f64)> = None;
+    let mut path = path::PathBuilder::default();
+    let mut strokes: Vec<StrokedPath> = Vec::new();
+    let mut filled_rects: Vec<FilledRect> = Vec::new();
     let mut rendered_images: Vec<PageImageRef> = Vec::new();
+    let mut diagnostics: Vec<String> = Vec::new();
 
     // Use form's font_cmaps if available, fall back to page's
     let font_cmaps = if !form.font_cmaps.is_empty() {
@@ -581,15 +1988,45 @@ fn render_form_xobject(
     } else {
         &page.font_cmaps
     };
+    let font_widths = if !form.font_widths.is_empty() {
+        &form.font_widths
+    } else {
+        &page.font_widths
+    };
+    let hidden_content = if !form.hidden_content.is_empty() {
+        &form.hidden_content
+    } else {
+        &page.hidden_content
+    };
+    let mut marked_content = MarkedContent::new();
 
     // Merge image sources: form's own + page's
     for op in ops {
+        // Hidden layers draw nothing, but still move the text and end paths
+        if marked_content.is_hidden() {
+            match op.operator {
+                ContentOp::Tj | ContentOp::TJ => {
+                    let widths = current_font_name.as_ref().and_then(|n| font_widths.get(n));
+                    if let (Some(raw), Some(widths)) = (&op.text_raw, widths) {
+                        advance_text_matrix(&mut text_matrix, widths, raw, &graphics_state);
+                    }
+                    continue;
+                }
+                ContentOp::Do | ContentOp::Sh => continue,
+                operator if operator.paints_path() => {
+                    pending_rect = None;
+                    path.clear();
+                    continue;
+                }
+                _ => {}
+            }
+        }
         match op.operator {
             ContentOp::GsSave => {
-                state_stack.push((ctm, graphics_state.clone(), current_font_name.clone()));
+                state_stack.save((ctm, graphics_state.clone(), current_font_name.clone()));
             }
             ContentOp::GsRestore => {
-                if let Some((saved_ctm, saved_state, saved_font)) = state_stack.pop() {
+                if let Some((saved_ctm, saved_state, saved_font)) = state_stack.restore() {
                     ctm = saved_ctm;
                     graphics_state = saved_state;
                     current_font_name = saved_font;
@@ -608,6 +2045,7 @@ fn render_form_xobject(
             ContentOp::BT => {
                 text_extractor.finalize_segment();
                 text_matrix = TransformMatrix::identity();
+                line_matrix = text_matrix;
             }
             ContentOp::ET => {
                 text_extractor.finalize_segment();
@@ -621,13 +2059,10 @@ fn render_form_xobject(
                 }
                 if let Some(ref name) = op.font_name {
                     current_font_name = Some(name.clone());
-                }
-                if graphics_state.font_info.is_none() {
-                    graphics_state.font_info = Some(crate::render::state::FontInfo {
-                        id: 0, use_tounicode: true, em_size: 1000.0,
-                        space_width: 250.0, ascent: 0.8, descent: -0.2,
-                        is_type3: false, font_size_scale: 1.0,
-                    });
+                    graphics_state.font_info =
+                        resolve_font_info(name, &form.font_refs, page, font_ids);
+                    graphics_state.vertical =
+                        font_widths.get(name).is_some_and(GlyphWidths::is_vertical);
                 }
             }
             ContentOp::Tm => {
@@ -637,34 +2072,48 @@ fn render_form_xobject(
                         c: op.operands[2], d: op.operands[3],
                         e: op.operands[4], f: op.operands[5],
                     };
+                    line_matrix = text_matrix;
                 }
             }
             ContentOp::Td => {
                 if op.operands.len() >= 2 {
                     let tx = op.operands[0];
                     let ty = op.operands[1];
-                    text_matrix.e += tx * text_matrix.a + ty * text_matrix.c;
-                    text_matrix.f += tx * text_matrix.b + ty * text_matrix.d;
+                    line_matrix.e += tx * line_matrix.a + ty * line_matrix.c;
+                    line_matrix.f += tx * line_matrix.b + ty * line_matrix.d;
+                    text_matrix = line_matrix;
                 }
             }
             ContentOp::TD => {
                 if op.operands.len() >= 2 {
                     let tx = op.operands[0];
                     let ty = op.operands[1];
-                    text_matrix.e += tx * text_matrix.a + ty * text_matrix.c;
-                    text_matrix.f += tx * text_matrix.b + ty * text_matrix.d;
+                    line_matrix.e += tx * line_matrix.a + ty * line_matrix.c;
+                    line_matrix.f += tx * line_matrix.b + ty * line_matrix.d;
+                    text_matrix = line_matrix;
+                    graphics_state.leading = -ty;
+                }
+            }
+            ContentOp::TL => {
+                if let Some(&leading) = op.operands.first() {
+                    graphics_state.leading = leading;
                 }
             }
+            ContentOp::Tstar => {
+                let ty = -graphics_state.leading;
+                line_matrix.e += ty * line_matrix.c;
+                line_matrix.f += ty * line_matrix.d;
+                text_matrix = line_matrix;
+            }
             ContentOp::Tj | ContentOp::TJ => {
-                let decoded_text = if let Some(ref raw) = op.text_raw {
-                    if let Some(ref fname) = current_font_name {
-                        if let Some(cmap) = font_cmaps.get(fname) {
-                            if !cmap.char_map.is_empty() {
-                                Some(cmap.decode_bytes(raw))
-                            } else { op.text.clone() }
-                        } else { op.text.clone() }
-                    } else { op.text.clone() }
-                } else { op.text.clone() };
+                let cmap = current_font_name.as_ref().and_then(|n| font_cmaps.get(n));
+                let decoded_text = decode_shown_text(&op, cmap, config.unmapped_glyph);
+
+                let widths = current_font_name.as_ref().and_then(|n| font_widths.get(n));
+                let mut next_text_matrix = text_matrix;
+                if let (Some(raw), Some(widths)) = (&op.text_raw, widths) {
+                    advance_text_matrix(&mut next_text_matrix, widths, raw, &graphics_state);
+                }
 
                 if let Some(text) = &decoded_text {
                     let (tm_x, tm_y) = text_matrix.transform_point(0.0, 0.0);
@@ -674,16 +2123,48 @@ fn render_form_xobject(
                     let tm_scale_y = (text_matrix.b * text_matrix.b + text_matrix.d * text_matrix.d).sqrt();
                     let ctm_scale_y = (ctm.b * ctm.b + ctm.d * ctm.d).sqrt();
                     let effective_font_size = graphics_state.font_size * tm_scale_y * ctm_scale_y;
-                    let html_y = page_height - page_y - effective_font_size * 0.85;
+                    let ascent = effective_font_size * text_ascent(&graphics_state);
+                    let html_y = page_height - page_y - ascent;
 
                     let mut state_for_text = graphics_state.clone();
                     state_for_text.font_size = effective_font_size;
+                    // Tc and Tw scale to the page like the font size
+                    let spacing_scale =
+                        if config.text_spacing { tm_scale_y * ctm_scale_y } else { 0.0 };
+                    state_for_text.letter_space *= spacing_scale;
+                    state_for_text.word_space *= spacing_scale;
+                    // Clip-only text stays in the output for search, unseen
+                    if graphics_state.render_mode == 7 {
+                        state_for_text.fill_color = crate::types::color::Color::transparent();
+                    }
 
-                    text_extractor.update_state(&state_for_text);
-                    text_extractor
-                        .add_text(text, page_x, html_y)
-                        .map_err(|e| OdeError::TextError(format!("Form text: {}", e)))?;
+                    let (x, y, extent, text_box) = if graphics_state.vertical {
+                        let (x, y, text_box) = vertical_text_placement(
+                            (page_x, page_y),
+                            &next_text_matrix,
+                            &ctm,
+                            effective_font_size,
+                            page_height,
+                        );
+                        (x, y, None, text_box)
+                    } else {
+                        // Tz squeezes the gaps between words along with the glyphs
+                        let space_size = effective_font_size * graphics_state.transform_matrix.a;
+                        let extent =
+                            widths.map(|w| text_extent(w, &next_text_matrix, &ctm, space_size));
+                        let end_x = extent.as_ref().map_or(page_x, |e| e.end_x);
+                        let text_box =
+                            BoundingBox::new(page_x, page_y, end_x, page_y + effective_font_size);
+                        (page_x, html_y, extent, text_box)
+                    };
+                    if !graphics_state.clip.excludes(&text_box) {
+                        text_extractor.update_state(&state_for_text);
+                        text_extractor
+                            .add_text_with_extent(text, x, y, extent)
+                            .map_err(|e| OdeError::TextError(format!("Form text: {}", e)))?;
+                    }
                 }
+                text_matrix = next_text_matrix;
             }
             ContentOp::Tc => {
                 if !op.operands.is_empty() { graphics_state.letter_space = op.operands[0]; }
@@ -691,75 +2172,115 @@ fn render_form_xobject(
             ContentOp::Tw => {
                 if !op.operands.is_empty() { graphics_state.word_space = op.operands[0]; }
             }
-            ContentOp::RGfill => {
-                if op.operands.len() >= 3 {
-                    let r = (op.operands[0].clamp(0.0, 1.0) * 255.0) as u8;
-                    let g = (op.operands[1].clamp(0.0, 1.0) * 255.0) as u8;
-                    let b = (op.operands[2].clamp(0.0, 1.0) * 255.0) as u8;
-                    graphics_state.fill_color = crate::types::color::Color::new(r, g, b);
+            ContentOp::Tr => {
+                if let Some(mode) = op.operands.first() {
+                    graphics_state.render_mode = mode.clamp(0.0, 7.0) as u8;
                 }
             }
-            ContentOp::Gfill => {
+            ContentOp::Tz => {
                 if !op.operands.is_empty() {
-                    let v = (op.operands[0].clamp(0.0, 1.0) * 255.0) as u8;
-                    graphics_state.fill_color = crate::types::color::Color::new(v, v, v);
+                    graphics_state.transform_matrix.a = op.operands[0] / 100.0;
                 }
             }
-            ContentOp::Kfill => {
-                if op.operands.len() >= 4 {
-                    let c = op.operands[0]; let m = op.operands[1];
-                    let y = op.operands[2]; let k = op.operands[3];
-                    let r = ((1.0 - c) * (1.0 - k) * 255.0) as u8;
-                    let g = ((1.0 - m) * (1.0 - k) * 255.0) as u8;
-                    let b = ((1.0 - y) * (1.0 - k) * 255.0) as u8;
-                    graphics_state.fill_color = crate::types::color::Color::new(r, g, b);
-                }
+            operator if operator.sets_color() => {
+                let color_spaces = [&form.color_spaces, &page.color_spaces];
+                apply_color_op(&mut graphics_state, &op, &color_spaces, &page.patterns);
             }
-            ContentOp::SCN | ContentOp::SC => {
-                if op.operands.len() >= 3 {
-                    let r = (op.operands[0].clamp(0.0, 1.0) * 255.0) as u8;
-                    let g = (op.operands[1].clamp(0.0, 1.0) * 255.0) as u8;
-                    let b = (op.operands[2].clamp(0.0, 1.0) * 255.0) as u8;
-                    graphics_state.fill_color = crate::types::color::Color::new(r, g, b);
-                } else if op.operands.len() == 1 {
-                    let v = (op.operands[0].clamp(0.0, 1.0) * 255.0) as u8;
-                    graphics_state.fill_color = crate::types::color::Color::new(v, v, v);
+            ContentOp::LineWidth => {
+                if let Some(&width) = op.operands.first() {
+                    graphics_state.line_width = width;
                 }
             }
+            ContentOp::M | ContentOp::L | ContentOp::C | ContentOp::H => {
+                path.apply(op.operator, &op.operands, &ctm);
+            }
             ContentOp::RE => {
                 if op.operands.len() >= 4 {
                     pending_rect = Some((op.operands[0], op.operands[1], op.operands[2], op.operands[3]));
                 }
+                path.apply(op.operator, &op.operands, &ctm);
             }
-            ContentOp::F | ContentOp::Fs => { pending_rect = None; }
+            ContentOp::W | ContentOp::Wstar => {
+                if let Some((rx, ry, rw, rh)) = pending_rect {
+                    let bbox = BoundingBox::new(rx, ry, rx + rw, ry + rh).transform(&ctm);
+                    graphics_state.clip.intersect(&ClipState::from_bbox(&bbox));
+                }
+            }
+            ContentOp::F
+            | ContentOp::Fs
+            | ContentOp::B
+            | ContentOp::Bs
+            | ContentOp::Bx
+            | ContentOp::Bxs => {
+                let color = graphics_state.fill_color.to_css_string();
+                let fill = pending_rect.and_then(|rect| {
+                    clipped_fill(rect, &ctm, &graphics_state.clip, page_height, color)
+                });
+                filled_rects.extend(fill);
+                if !matches!(op.operator, ContentOp::F | ContentOp::Fs) {
+                    if matches!(op.operator, ContentOp::Bs | ContentOp::Bxs) {
+                        path.close();
+                    }
+                    strokes.extend(path.stroke(&graphics_state, &ctm, page_height));
+                }
+                pending_rect = None;
+                path.clear();
+            }
+            ContentOp::S | ContentOp::Ss => {
+                if op.operator == ContentOp::Ss {
+                    path.close();
+                }
+                strokes.extend(path.stroke(&graphics_state, &ctm, page_height));
+                pending_rect = None;
+                path.clear();
+            }
+            ContentOp::N => {
+                pending_rect = None;
+                path.clear();
+            }
+            ContentOp::BDC => {
+                let name = op.font_name.as_ref();
+                let hidden = name.is_some_and(|name| hidden_content.contains(name));
+                marked_content.begin(hidden, op.lang.as_ref(), op.mcid, &mut graphics_state);
+            }
+            ContentOp::BMC => marked_content.begin(false, None, None, &mut graphics_state),
+            ContentOp::EMC => marked_content.end(&mut graphics_state),
             ContentOp::Do => {
                 // Nested Form XObjects or images within the form
                 if let Some(ref nested_name) = op.font_name {
                     if let Some(img) = form.images.get(nested_name).or_else(|| page.images.get(nested_name)) {
+                        let image_box = BoundingBox::new(0.0, 0.0, 1.0, 1.0).transform(&ctm);
+                        if graphics_state.clip.excludes(&image_box) {
+                            continue;
+                        }
                         let (x, y) = ctm.transform_point(0.0, 0.0);
                         let (x2, _) = ctm.transform_point(1.0, 0.0);
                         let (_, y2) = ctm.transform_point(0.0, 1.0);
                         let w = (x2 - x).abs();
                         let h = (y2 - y).abs();
                         let img_y = page_height - y.max(y2);
-
-                        use base64::Engine;
-                        let b64 = base64::engine::general_purpose::STANDARD.encode(&img.data);
-                        let data_uri = format!("data:{};base64,{}", img.mime_type, b64);
-
-                        rendered_images.push(PageImageRef {
-                            name: nested_name.clone(),
-                            x, y: img_y, width: w, height: h, data_uri,
-                        });
+                        rendered_images.push(PageImageRef::new(img, x, img_y, w, h));
+                        diagnostics.extend(unsupported_image(img));
                     }
                     // Nested form XObject
                     else if let Some(nested_form) = form.form_xobjects.get(nested_name) {
-                        if let Ok(nested_result) = render_form_xobject(
-                            nested_form, &ctm, page_width, page_height,
-                            &graphics_state, &current_font_name, page,
+                        match render_form_xobject(
+                            nested_form, &ctm, &graphics_state, &current_font_name, page, scope,
                         ) {
-                            text_extractor.merge_spans(&nested_result.text_spans);
-                            rendered_images.extend(nested_result.images);
+                            Ok(nested_result) => {
+                                text_extractor.merge_spans(&nested_result.text_spans);
+                                rendered_images.extend(nested_result.images);
+                                filled_rects.extend(nested_result.filled_rects);
+                                strokes.extend(nested_result.strokes);
+                                diagnostics.extend(
+                                    nested_result
+                                        .diagnostics
+                                        .iter()
+                                        .map(|d| format!("form /{}: {}", nested_name, d)),
+                                );
+                            }
+                            Err(e @ OdeError::Timeout(_)) => return Err(e),
+                            Err(_) => {}
                         }
                     }
                 }
@@ -769,54 +2290,461 @@ fn render_form_xobject(
     }
 
     text_extractor.finalize_segment();
+    // Anything the form left saved is dropped with its stack; the caller
+    // carries on from the state it had before `Do`
+    if let Some(imbalance) = state_stack.imbalance() {
+        diagnostics.push(imbalance);
+    }
 
     Ok(FormRenderResult {
         text_spans: text_extractor.get_spans(),
         images: rendered_images,
-        background_color,
+        filled_rects,
+        strokes,
+        diagnostics,
     })
 }
 
+/// Extract the document's embedded fonts into `output_bundle`, along with
+/// the `@font-face` rules that load them from their content-addressed files.
+///
+/// Each font object is extracted once, however many pages use it, and font
+/// objects that embed the same file share it. A font's id comes from the
+/// embedded file's contents, before any subsetting, so converting a document
+/// twice assigns the same ids whatever pages it covers. Fonts in
+/// `used_glyphs`, from [`collect_used_glyphs`], are subset to the glyphs
+/// recorded for them; others, and files several font objects share, are
+/// embedded whole. Returns the id
+/// assigned to each extracted font, for [`render_pdf_page_with_fonts`].
 pub fn extract_fonts_from_document(
     document: &PdfDocument,
     output_bundle: &mut OutputBundle,
     pdf_data: &[u8],
-) -> Result<(), OdeError> {
+    config: &ConversionConfig,
+    used_glyphs: &UsedGlyphs,
+) -> Result<FontIds, OdeError> {
     let mut font_processor = FontProcessor::new();
+    let mut font_ids = FontIds::new();
+    let mut seen = std::collections::HashSet::new();
 
     if let Some(xref) = &document.xref {
+        let resolver = crate::parser::PdfRefResolver::new(pdf_data, xref)
+            .with_filters(&config.filters)
+            .with_decompression_limits(crate::util::ZipBombDetector::from_config(config));
         for page in &document.pages {
-            let resolver = crate::parser::PdfRefResolver::new(pdf_data, xref);
-            for font_ref in &page.fonts {
-                if let Ok(font_info) = font_processor.extract_font_from_pdf(*font_ref, &resolver) {
-                    if font_info.embedded {
-                        if let Some(extracted_font) = font_processor.get_font(font_info.id) {
-                            output_bundle.add_font(
-                                font_info.id,
-                                font_info.name.clone(),
-                                extracted_font.data.clone(),
-                                extracted_font.format,
-                            );
-                        }
+            for font_ref in &page_fonts(page) {
+                if !seen.insert(*font_ref) {
+                    continue;
+                }
+                let usage = used_glyphs.get(font_ref);
+                if let Ok(font_info) =
+                    font_processor.extract_font_from_pdf(*font_ref, &resolver, usage)
+                {
+                    if !font_info.embedded {
+                        continue;
                     }
+                    font_ids.insert(
+                        *font_ref,
+                        ExtractedFontRef {
+                            id: font_info.id,
+                            ascent: font_info.ascent.map(f64::from),
+                            descent: font_info.descent.map(f64::from),
+                        },
+                    );
                 }
             }
         }
     }
 
-    Ok(())
+    // Added once all are extracted, as a program shared by several font
+    // objects ends up embedded whole rather than as the first one's subset
+    for font in font_processor.get_all_fonts() {
+        if output_bundle.get_font_by_id(font.id).is_none() {
+            output_bundle.add_font(font.id, font.name.clone(), font.data.clone(), font.format);
+            let filename = &output_bundle.fonts[output_bundle.fonts.len() - 1].filename;
+            output_bundle
+                .css
+                .push_str(&font_processor.generate_font_face(font.id, filename)?);
+        }
+    }
+
+    Ok(font_ids)
+}
+
+/// The fonts `page` can draw text in: its own, then those of the forms it
+/// draws and of its form widgets' appearances, nested forms included.
+fn page_fonts(page: &crate::parser::PdfPage) -> Vec<ObjectReference> {
+    fn add_form_fonts(form: &crate::parser::FormXObject, fonts: &mut Vec<ObjectReference>) {
+        // Sorted by name, so fonts are extracted in the same order every time
+        let mut names: Vec<&String> = form.font_refs.keys().collect();
+        names.sort();
+        fonts.extend(names.into_iter().map(|name| form.font_refs[name]));
+        let mut forms: Vec<_> = form.form_xobjects.iter().collect();
+        forms.sort_by_key(|(name, _)| *name);
+        for (_, nested) in forms {
+            add_form_fonts(nested, fonts);
+        }
+    }
+
+    let mut fonts = page.fonts.clone();
+    let mut forms: Vec<_> = page.form_xobjects.iter().collect();
+    forms.sort_by_key(|(name, _)| *name);
+    for (_, form) in forms {
+        add_form_fonts(form, &mut fonts);
+    }
+    for appearance in page.form_widgets.iter().filter_map(|w| w.appearance.as_ref()) {
+        add_form_fonts(appearance, &mut fonts);
+    }
+    fonts
+}
+
+/// Record what the pages `page_ids` show in each font, for subsetting the
+/// fonts that [`extract_fonts_from_document`] embeds.
+///
+/// Walks the same content the renderer draws text from: the page, the form
+/// XObjects it draws and its form widgets' appearances. Each form is walked
+/// once per font it starts out in, however often it is drawn.
+pub fn collect_used_glyphs(
+    document: &PdfDocument,
+    page_ids: &[usize],
+    config: &ConversionConfig,
+    deadline: Option<Deadline>,
+) -> Result<UsedGlyphs, OdeError> {
+    let mut used = UsedGlyphs::new();
+    for &page_id in page_ids {
+        let Some(page) = document.get_page(page_id as u32) else {
+            continue;
+        };
+        check_deadline(deadline, page_id + 1)?;
+        let mut visited = std::collections::HashSet::new();
+        let parse = |data: &[u8]| parse_content_stream(data, config);
+        let ops = document.content_ops.get_or_parse(page.contents_ref, &page.contents, parse);
+        if let Ok(ops) = ops {
+            let content = GlyphContent {
+                ops: &ops,
+                font_refs: &page.font_refs,
+                font_cmaps: &page.font_cmaps,
+                font_widths: &page.font_widths,
+                form_xobjects: &page.form_xobjects,
+            };
+            content.record(page, None, config, &mut visited, &mut used);
+        }
+        for appearance in page.form_widgets.iter().filter_map(|w| w.appearance.as_ref()) {
+            GlyphContent::record_form(appearance, page, None, config, &mut visited, &mut used);
+        }
+    }
+    Ok(used)
+}
+
+/// Forms already walked, with the font each started out in.
+type VisitedForms =
+    std::collections::HashSet<(*const crate::parser::FormXObject, Option<String>)>;
+
+/// A content stream walked by [`collect_used_glyphs`], with the resources
+/// its text and forms are looked up in.
+struct GlyphContent<'a> {
+    ops: &'a [ParsedOp],
+    font_refs: &'a FontRefs,
+    font_cmaps: &'a std::collections::HashMap<String, crate::parser::ToUnicodeCMap>,
+    font_widths: &'a std::collections::HashMap<String, GlyphWidths>,
+    form_xobjects: &'a std::collections::HashMap<String, crate::parser::FormXObject>,
 }
 
-fn parse_content_stream(content_stream: &[u8]) -> Result<Vec<ParsedOp>, OdeError> {
+impl GlyphContent<'_> {
+    fn record(
+        &self,
+        page: &crate::parser::PdfPage,
+        initial_font: Option<&str>,
+        config: &ConversionConfig,
+        visited: &mut VisitedForms,
+        used: &mut UsedGlyphs,
+    ) {
+        let mut font_name = initial_font.map(str::to_string);
+        let mut saved = Vec::new();
+        for op in self.ops {
+            match op.operator {
+                ContentOp::GsSave => saved.push(font_name.clone()),
+                ContentOp::GsRestore => {
+                    if let Some(restored) = saved.pop() {
+                        font_name = restored;
+                    }
+                }
+                ContentOp::Tf if op.font_name.is_some() => font_name = op.font_name.clone(),
+                ContentOp::Tj | ContentOp::TJ => {
+                    let (Some(name), Some(raw)) = (&font_name, &op.text_raw) else {
+                        continue;
+                    };
+                    let Some(font_ref) = font_ref(name, self.font_refs, page) else {
+                        continue;
+                    };
+                    let cmap = self.font_cmaps.get(name).or_else(|| page.font_cmaps.get(name));
+                    let text = decode_shown_text(op, cmap, config.unmapped_glyph);
+                    let widths = self.font_widths.get(name).or_else(|| page.font_widths.get(name));
+                    let two_byte =
+                        matches!(widths, Some(GlyphWidths::Cid(_) | GlyphWidths::VerticalCid(_)));
+                    used.entry(*font_ref).or_default().record(
+                        raw,
+                        two_byte,
+                        text.as_deref().unwrap_or_default(),
+                    );
+                }
+                ContentOp::Do => {
+                    let form = op.font_name.as_ref().and_then(|name| self.form_xobjects.get(name));
+                    if let Some(form) = form {
+                        Self::record_form(form, page, font_name.as_deref(), config, visited, used);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn record_form(
+        form: &crate::parser::FormXObject,
+        page: &crate::parser::PdfPage,
+        initial_font: Option<&str>,
+        config: &ConversionConfig,
+        visited: &mut VisitedForms,
+        used: &mut UsedGlyphs,
+    ) {
+        if !visited.insert((form as *const _, initial_font.map(str::to_string))) {
+            return;
+        }
+        let Ok(ops) = parse_content_stream(&form.content_stream, config) else {
+            return;
+        };
+        let content = GlyphContent {
+            ops: &ops,
+            font_refs: &form.font_refs,
+            font_cmaps: &form.font_cmaps,
+            font_widths: &form.font_widths,
+            form_xobjects: &form.form_xobjects,
+        };
+        content.record(page, initial_font, config, visited, used);
+    }
+}
+
+/// Move the text matrix past a shown string: `Tm = [tx 0 0 1 0 0] × Tm`,
+/// where `tx` is the string's advance under the current text state, or
+/// `Tm = [1 0 0 1 0 ty] × Tm` in a vertical font.
+fn advance_text_matrix(
+    text_matrix: &mut crate::util::math::TransformMatrix,
+    widths: &GlyphWidths,
+    raw: &[u8],
+    state: &GraphicsState,
+) {
+    // `Tz` is kept in the state's transform as the horizontal scale
+    let tx = widths.string_advance(
+        raw,
+        state.font_size,
+        state.letter_space,
+        state.word_space,
+        state.transform_matrix.a,
+    );
+    if widths.is_vertical() {
+        text_matrix.e += tx * text_matrix.c;
+        text_matrix.f += tx * text_matrix.d;
+    } else {
+        text_matrix.e += tx * text_matrix.a;
+        text_matrix.f += tx * text_matrix.b;
+    }
+}
+
+/// Where each code of a string shown in a Type3 font draws its glyph: the
+/// glyph procedure and the matrix it is drawn with, which places glyph
+/// space (before the procedure's `/FontMatrix`) at the code's position along
+/// the text matrix, scaled by the font size and `Tz`. Codes without a
+/// procedure still advance.
+fn type3_glyph_placements<'f>(
+    font: &'f crate::parser::Type3Font,
+    raw: &[u8],
+    widths: Option<&GlyphWidths>,
+    text_matrix: &crate::util::math::TransformMatrix,
+    ctm: &crate::util::math::TransformMatrix,
+    state: &GraphicsState,
+) -> Vec<(&'f crate::parser::FormXObject, crate::util::math::TransformMatrix)> {
+    use crate::util::math::TransformMatrix;
+
+    let horizontal_scaling = state.transform_matrix.a;
+    let mut advance = 0.0;
+    let mut placements = Vec::new();
+    for code in raw {
+        if let Some(glyph) = font.glyphs.get(code) {
+            let glyph_space = TransformMatrix {
+                a: state.font_size * horizontal_scaling,
+                b: 0.0,
+                c: 0.0,
+                d: state.font_size,
+                e: advance,
+                f: 0.0,
+            };
+            placements.push((glyph, *ctm * *text_matrix * glyph_space));
+        }
+        if let Some(widths) = widths {
+            advance += widths.string_advance(
+                std::slice::from_ref(code),
+                state.font_size,
+                state.letter_space,
+                state.word_space,
+                horizontal_scaling,
+            );
+        }
+    }
+    placements
+}
+
+/// The text of a shown string: decoded through the font's `/ToUnicode` map
+/// when it has one, else the string's bytes as the content parser read them.
+/// Either way, glyphs with no character are handled as `unmapped` says.
+fn decode_shown_text(
+    op: &crate::parser::ParsedOp,
+    cmap: Option<&crate::parser::ToUnicodeCMap>,
+    unmapped: UnmappedGlyph,
+) -> Option<String> {
+    let cmap = cmap.filter(|cmap| !cmap.char_map.is_empty());
+    if let (Some(raw), Some(cmap)) = (&op.text_raw, cmap) {
+        return Some(cmap.decode_bytes_with(raw, unmapped));
+    }
+    let text = op.text.as_ref()?;
+    if unmapped == UnmappedGlyph::Fallback {
+        return Some(text.clone());
+    }
+    // The parser reads bytes that aren't UTF-8 as U+FFFD
+    let text = text
+        .chars()
+        .filter_map(|c| match c {
+            '\u{FFFD}' | '\0' => unmapped.substitute(None),
+            c => Some(c),
+        })
+        .collect();
+    Some(text)
+}
+
+/// Where text ends on the page once the text matrix has advanced past it to
+/// `end_matrix`, with the font's space width at `font_size`.
+fn text_extent(
+    widths: &GlyphWidths,
+    end_matrix: &crate::util::math::TransformMatrix,
+    ctm: &crate::util::math::TransformMatrix,
+    font_size: f64,
+) -> text::TextExtent {
+    let (tm_x, tm_y) = end_matrix.transform_point(0.0, 0.0);
+    let (end_x, _) = ctm.transform_point(tm_x, tm_y);
+    text::TextExtent {
+        end_x,
+        space_width: widths.space_width() / 1000.0 * font_size,
+    }
+}
+
+/// Where text in a vertical font, shown from `origin` on the page until the
+/// text matrix reaches `end_matrix`, goes: its left edge and top in HTML,
+/// and the box it covers on the page. The origin is the top centre of the
+/// first glyph, and each glyph hangs below the one before.
+fn vertical_text_placement(
+    origin: (f64, f64),
+    end_matrix: &crate::util::math::TransformMatrix,
+    ctm: &crate::util::math::TransformMatrix,
+    font_size: f64,
+    page_height: f64,
+) -> (f64, f64, crate::util::math::BoundingBox) {
+    let (page_x, page_y) = origin;
+    let (tm_x, tm_y) = end_matrix.transform_point(0.0, 0.0);
+    let (_, end_y) = ctm.transform_point(tm_x, tm_y);
+    let left = page_x - font_size / 2.0;
+    let text_box =
+        crate::util::math::BoundingBox::new(left, end_y.min(page_y), left + font_size, page_y);
+    (left, page_height - page_y, text_box)
+}
+
+/// Fonts by resource name, from a page's or form's /Resources /Font.
+type FontRefs = std::collections::HashMap<String, ObjectReference>;
+
+/// The font object resource `name` selects in content whose own resources
+/// have `font_refs`, falling back to the page's for names they lack.
+fn font_ref<'a>(
+    name: &str,
+    font_refs: &'a FontRefs,
+    page: &'a crate::parser::PdfPage,
+) -> Option<&'a ObjectReference> {
+    font_refs.get(name).or_else(|| page.font_refs.get(name))
+}
+
+/// The ascent text in the current font is placed by, as a fraction of the
+/// font size.
+fn text_ascent(graphics_state: &GraphicsState) -> f64 {
+    graphics_state
+        .font_info
+        .as_ref()
+        .map_or(DEFAULT_ASCENT, |font| font.ascent)
+}
+
+/// The text state for a `Tf` selecting resource `name`, or `None` when the
+/// font wasn't extracted and text in it should use the browser's fallback.
+fn resolve_font_info(
+    name: &str,
+    font_refs: &FontRefs,
+    page: &crate::parser::PdfPage,
+    font_ids: &FontIds,
+) -> Option<crate::render::state::FontInfo> {
+    let font = font_ref(name, font_refs, page).and_then(|r| font_ids.get(r))?;
+    Some(crate::render::state::FontInfo {
+        id: font.id,
+        ascent: font.ascent.unwrap_or(DEFAULT_ASCENT),
+        descent: font.descent.unwrap_or(DEFAULT_DESCENT),
+        ..Default::default()
+    })
+}
+
+fn parse_content_stream(
+    content_stream: &[u8],
+    config: &ConversionConfig,
+) -> Result<Vec<ParsedOp>, OdeError> {
     // Detect zlib compression by checking for the zlib header magic bytes.
     // Common zlib headers: 0x78 0x01 (low), 0x78 0x9C (default), 0x78 0xDA (best).
-    let compression = if content_stream.len() >= 2 && content_stream[0] == 0x78 {
-        Some("FlateDecode")
+    let mut parser = if content_stream.len() >= 2 && content_stream[0] == 0x78 {
+        let limits = crate::util::ZipBombDetector::from_config(config);
+        ContentStreamParser::compressed(content_stream, "FlateDecode", &limits)?
     } else {
-        None
+        ContentStreamParser::new(content_stream)
     };
-    let mut parser = ContentStreamParser::new(content_stream, compression)?;
-    parser.parse()
+    Ok(expand_next_line_shows(parser.parse()?))
+}
+
+/// Spell out `'` and `"` as the `Tw`, `Tc`, `T*` and `Tj` they stand for,
+/// so only those need handling when rendering.
+fn expand_next_line_shows(ops: Vec<ParsedOp>) -> Vec<ParsedOp> {
+    let op = |operator, operands| ParsedOp {
+        operator,
+        operands,
+        text: None,
+        text_raw: None,
+        font_name: None,
+        lang: None,
+        mcid: None,
+    };
+    let mut expanded = Vec::with_capacity(ops.len());
+    for shown in ops {
+        match shown.operator {
+            ContentOp::Quote => {}
+            ContentOp::DoubleQuote => {
+                if let [word_space, char_space, ..] = shown.operands[..] {
+                    expanded.push(op(ContentOp::Tw, vec![word_space]));
+                    expanded.push(op(ContentOp::Tc, vec![char_space]));
+                }
+            }
+            _ => {
+                expanded.push(shown);
+                continue;
+            }
+        }
+        expanded.push(op(ContentOp::Tstar, Vec::new()));
+        expanded.push(ParsedOp {
+            operator: ContentOp::Tj,
+            operands: Vec::new(),
+            ..shown
+        });
+    }
+    expanded
 }
 
 fn generate_page_html_with_text(
@@ -826,19 +2754,7 @@ fn generate_page_html_with_text(
     _config: &ConversionConfig,
     text_spans: &Vec<TextSpan>,
 ) -> String {
-    let spans_html: Vec<String> = text_spans
-        .iter()
-        .map(|span| {
-            format!(
-                "<span style=\"left:{}px;top:{}px;font-size:{}px;color:{};\">{}</span>",
-                span.x,
-                span.y,
-                span.font_size,
-                span.color,
-                escape_html(&span.text)
-            )
-        })
-        .collect();
+    let spans_html: Vec<String> = text_spans.iter().map(span_html).collect();
 
     format!(
         "<div style=\"width:{}px;height:{}px;position:relative;\">{}</div>",
@@ -848,49 +2764,164 @@ fn generate_page_html_with_text(
     )
 }
 
+/// What is drawn on the page, in the order it is painted.
+struct PageContent<'a> {
+    filled_rects: &'a [FilledRect],
+    strokes: &'a [StrokedPath],
+    images: &'a [PageImageRef],
+    text_spans: &'a [TextSpan],
+}
+
 fn generate_page_html_with_images_and_text(
-    _page_number: usize,
     width: f64,
     height: f64,
-    _config: &ConversionConfig,
-    text_spans: &[TextSpan],
-    images: &[PageImageRef],
-    filled_rects: &[(f64, f64, f64, f64, String)],
-) -> String {
-    let mut inner_html = String::new();
+    config: &ConversionConfig,
+    content: &PageContent,
+    overlays: &PageOverlays,
+) -> (String, Vec<Range<usize>>) {
+    let mut html = format!(
+        "<div style=\"width:{}px;height:{}px;position:relative;\">",
+        width, height
+    );
+    // Where each image's tag sits, for dropping the image later
+    let mut image_ranges = Vec::with_capacity(content.images.len());
 
     // Render filled rectangles first (background elements)
-    for (x, y, w, h, color) in filled_rects {
-        inner_html.push_str(&format!(
+    for rect in content.filled_rects {
+        html.push_str(&format!(
             "<div style=\"position:absolute;left:{}px;top:{}px;width:{}px;height:{}px;background:{};\"></div>",
-            x, y, w, h, color
+            rect.x, rect.y, rect.width, rect.height, rect.color
         ));
     }
+    // Rules and borders over the fills they edge
+    for stroke in content.strokes {
+        html.push_str(&stroke.to_html(width, height));
+    }
 
     // Render images (on top of rects, behind text)
-    for img in images {
-        inner_html.push_str(&format!(
-            "<img style=\"position:absolute;left:{}px;top:{}px;width:{}px;height:{}px;\" src=\"{}\">",
-            img.x, img.y, img.width, img.height, img.data_uri
+    for img in content.images {
+        let mut attrs = String::new();
+        if config.lazy_images {
+            attrs.push_str(" loading=\"lazy\"");
+        }
+        if img.pixel_width > 0 && img.pixel_height > 0 {
+            attrs.push_str(&format!(
+                " width=\"{}\" height=\"{}\"",
+                img.pixel_width, img.pixel_height
+            ));
+        }
+        let start = html.len();
+        html.push_str(&format!(
+            "<img{} style=\"position:absolute;left:{}px;top:{}px;width:{}px;height:{}px;\" src=\"{}\">",
+            attrs, img.x, img.y, img.width, img.height, img.filename
         ));
+        image_ranges.push(start..html.len());
     }
 
     // Render text spans on top
-    for span in text_spans {
-        inner_html.push_str(&format!(
-            "<span style=\"left:{}px;top:{}px;font-size:{}px;color:{};\">{}</span>",
-            span.x, span.y, span.font_size, span.color, escape_html(&span.text)
+    for span in content.text_spans {
+        html.push_str(&span_html(span));
+    }
+
+    // Link overlays last so they receive clicks over the content
+    for link in &overlays.links {
+        let [x0, y0, x1, y1] = link.rect;
+        html.push_str(&format!(
+            "<a href=\"{}\" style=\"position:absolute;left:{}px;top:{}px;width:{}px;height:{}px;\"></a>",
+            escape_html(&link.target.href()),
+            x0,
+            height - y1,
+            x1 - x0,
+            y1 - y0
+        ));
+    }
+    // Fields keep the document's tab order
+    for field in &overlays.fields {
+        let [x0, y0, x1, y1] = field.rect;
+        html.push_str(&format!(
+            "<div tabindex=\"{}\" aria-label=\"{}\" style=\"position:absolute;left:{}px;top:{}px;width:{}px;height:{}px;\"></div>",
+            field.tab_index,
+            escape_html(&field.name),
+            x0,
+            height - y1,
+            x1 - x0,
+            y1 - y0
+        ));
+    }
+    for note in &overlays.notes {
+        let [x0, y0, x1, y1] = note.rect;
+        html.push_str(&format!(
+            "<div title=\"{}\" style=\"position:absolute;left:{}px;top:{}px;width:{}px;height:{}px;background:rgb(255, 204, 0);\"></div>",
+            escape_html(&note.contents),
+            x0,
+            height - y1,
+            x1 - x0,
+            y1 - y0
         ));
     }
 
+    html.push_str("</div>");
+    (html, image_ranges)
+}
+
+fn span_html(span: &TextSpan) -> String {
+    let font_family = span
+        .font_id
+        .map(|id| format!("font-family:'ff{}';", id))
+        .unwrap_or_default();
+    // Condensed or expanded by `Tz`, from the left edge the text starts at
+    let mut layout = if is_unit_scale(&span.scale_x) {
+        String::new()
+    } else {
+        format!("transform:scaleX({});transform-origin:left;", span.scale_x)
+    };
+    if span.vertical {
+        layout.push_str("writing-mode:vertical-rl;");
+    }
+    if span.letter_spacing != 0.0 {
+        layout.push_str(&format!("letter-spacing:{}px;", span.letter_spacing));
+    }
+    if span.word_spacing != 0.0 {
+        layout.push_str(&format!("word-spacing:{}px;", span.word_spacing));
+    }
+    match (span.underline, span.line_through) {
+        (true, true) => layout.push_str("text-decoration:underline line-through;"),
+        (true, false) => layout.push_str("text-decoration:underline;"),
+        (false, true) => layout.push_str("text-decoration:line-through;"),
+        (false, false) => {}
+    }
     format!(
-        "<div style=\"width:{}px;height:{}px;position:relative;\">{}</div>",
-        width, height, inner_html
+        "<span{}{} style=\"left:{}px;top:{}px;font-size:{}px;{}color:{};{}\">{}</span>",
+        if span.covered { " class=\"covered\"" } else { "" },
+        lang_attribute(span.lang.as_deref()),
+        span.x,
+        span.y,
+        span.font_size,
+        font_family,
+        span.color,
+        layout,
+        escape_html(&span.text)
     )
 }
 
-fn generate_page_css(_config: &ConversionConfig) -> String {
-    String::from(".page span { position:absolute; white-space: nowrap; }")
+/// ` lang="en-US"` for an element in `lang`, or nothing when the language
+/// isn't known.
+pub fn lang_attribute(lang: Option<&str>) -> String {
+    lang.map(|lang| format!(" lang=\"{}\"", escape_html(lang)))
+        .unwrap_or_default()
+}
+
+/// The CSS every page of a conversion with `config` uses, which output
+/// combining pages emits once, ahead of them.
+pub fn shared_css(config: &ConversionConfig) -> String {
+    // Both keep a span on one line; `pre` also keeps its runs of spaces
+    let white_space = if config.preserve_spaces { "pre" } else { "nowrap" };
+    let mut css = format!(".page span {{ position:absolute; white-space: {}; }}\n", white_space);
+    if config.covered_text == CoveredText::Mark {
+        // Hidden in the PDF, so unseen here too, but still selectable
+        css.push_str(".page span.covered { color: transparent !important; }\n");
+    }
+    css
 }
 
 fn escape_html(s: &str) -> String {
@@ -904,6 +2935,296 @@ fn escape_html(s: &str) -> String {
 mod tests {
     use super::*;
 
+    fn document_with_contents(contents: &[u8]) -> PdfDocument {
+        let mut doc = PdfDocument::new();
+        doc.pages.push(crate::parser::PdfPage {
+            page_number: 1,
+            width: 612.0,
+            height: 792.0,
+            contents: contents.to_vec(),
+            contents_ref: None,
+            fonts: Vec::new(),
+            font_refs: std::collections::HashMap::new(),
+            rotation: 0,
+            user_unit: 1.0,
+            origin: (0.0, 0.0),
+            dict: None,
+            font_cmaps: std::collections::HashMap::new(),
+            font_widths: std::collections::HashMap::new(),
+            type3_fonts: std::collections::HashMap::new(),
+            images: std::collections::HashMap::new(),
+            form_xobjects: std::collections::HashMap::new(),
+            color_spaces: std::collections::HashMap::new(),
+            shadings: std::collections::HashMap::new(),
+            patterns: std::collections::HashMap::new(),
+            hidden_content: std::collections::HashSet::new(),
+            links: Vec::new(),
+            notes: Vec::new(),
+            form_widgets: Vec::new(),
+        });
+        doc
+    }
+
+    fn first_span_color(doc: &PdfDocument) -> String {
+        let page = render_pdf_page(doc, 0, 1, &ConversionConfig::default()).unwrap();
+        page.text_spans[0].color.clone()
+    }
+
+    #[test]
+    fn test_scn_uses_current_color_space() {
+        let cmyk = document_with_contents(
+            b"/DeviceCMYK cs 0 1 1 0 scn BT /F1 12 Tf 72 700 Td (Red) Tj ET",
+        );
+        assert_eq!(first_span_color(&cmyk), "rgb(255, 46, 23)");
+
+        let gray = document_with_contents(b"/DeviceGray cs 1 sc BT /F1 12 Tf 72 700 Td (White) Tj ET");
+        assert_eq!(first_span_color(&gray), "rgb(255, 255, 255)");
+    }
+
+    #[test]
+    fn test_cs_named_separation_resource() {
+        let mut doc = document_with_contents(b"/CS0 cs 0.5 scn BT /F1 12 Tf 72 700 Td (Spot) Tj ET");
+        doc.pages[0].color_spaces.insert(
+            "CS0".to_string(),
+            ColorSpace::Separation {
+                components: 1,
+                alternate: Box::new(ColorSpace::DeviceCMYK),
+                tint: None,
+            },
+        );
+        assert_eq!(first_span_color(&doc), "rgb(127, 127, 127)");
+    }
+
+    #[test]
+    fn test_scn_indexed_palette_lookup() {
+        let mut doc = document_with_contents(b"/CS0 cs 3 scn BT /F1 12 Tf 72 700 Td (Palette) Tj ET");
+        doc.pages[0].color_spaces.insert(
+            "CS0".to_string(),
+            ColorSpace::Indexed {
+                base: Box::new(ColorSpace::DeviceRGB),
+                hival: 3,
+                lookup: vec![0, 0, 0, 255, 255, 255, 10, 20, 30, 200, 100, 50],
+            },
+        );
+        assert_eq!(first_span_color(&doc), "rgb(200, 100, 50)");
+    }
+
+    #[test]
+    fn test_stroke_scn_leaves_fill_color() {
+        let doc = document_with_contents(
+            b"/DeviceRGB CS 1 0 0 SCN BT /F1 12 Tf 72 700 Td (Black) Tj ET",
+        );
+        assert_eq!(first_span_color(&doc), "rgb(0, 0, 0)");
+    }
+
+    #[test]
+    fn test_scn_in_pattern_space_selects_the_named_pattern() {
+        use crate::parser::colorspace::TintTransform;
+        use crate::parser::shading::{Shading, ShadingFunction, ShadingGeometry};
+
+        let mut doc = document_with_contents(
+            b"1 0 0 rg /Pattern cs /P1 scn BT /F1 12 Tf 72 700 Td (Tiled) Tj ET \
+/P2 scn 0 0 100 50 re f /CS0 cs 0 0 1 /P3 scn 100 0 50 50 re f",
+        );
+        let patterns = &mut doc.pages[0].patterns;
+        patterns.insert(
+            "P1".to_string(),
+            Pattern::Tiling {
+                color: Some(crate::types::color::Color::new(0, 128, 0)),
+            },
+        );
+        let red_to_blue = Shading {
+            geometry: ShadingGeometry::Axial([0.0, 0.0, 100.0, 0.0]),
+            color_space: ColorSpace::DeviceRGB,
+            domain: [0.0, 1.0],
+            function: ShadingFunction::Exponential(TintTransform {
+                c0: vec![1.0, 0.0, 0.0],
+                c1: vec![0.0, 0.0, 1.0],
+                exponent: 1.0,
+            }),
+            bbox: None,
+        };
+        patterns.insert(
+            "P2".to_string(),
+            Pattern::Shading {
+                shading: Box::new(red_to_blue),
+                matrix: [1.0, 0.0, 0.0, 1.0, 0.0, 0.0],
+            },
+        );
+        patterns.insert("P3".to_string(), Pattern::Tiling { color: None });
+        doc.pages[0].color_spaces.insert(
+            "CS0".to_string(),
+            ColorSpace::Pattern {
+                base: Some(Box::new(ColorSpace::DeviceRGB)),
+            },
+        );
+
+        let page = render_pdf_page(&doc, 0, 1, &ConversionConfig::default()).unwrap();
+        // A tiling pattern's color, not the red before it or the name as numbers
+        assert_eq!(page.text_spans[0].color, "rgb(0, 128, 0)");
+        assert_eq!(page.filled_rects.len(), 2);
+        assert!(page.filled_rects[0].color.starts_with("linear-gradient(90deg"));
+        assert!(page.filled_rects[0].color.contains("rgb(255, 0, 0)"));
+        // An uncolored pattern is painted in the operands' color
+        assert_eq!(page.filled_rects[1].color, "rgb(0, 0, 255)");
+    }
+
+    #[test]
+    fn test_form_colors_follow_the_page_color_space_and_stroke_operators() {
+        let mut doc = document_with_contents(b"/CS0 cs /Fm0 Do");
+        doc.pages[0].color_spaces.insert(
+            "CS0".to_string(),
+            ColorSpace::Indexed {
+                base: Box::new(ColorSpace::DeviceRGB),
+                hival: 1,
+                lookup: vec![0, 0, 0, 255, 0, 0],
+            },
+        );
+        doc.pages[0].form_xobjects.insert(
+            "Fm0".to_string(),
+            crate::parser::FormXObject {
+                name: "Fm0".to_string(),
+                content_stream: b"1 sc BT /F1 12 Tf 0 0 Td (Stamp) Tj ET \
+0 0 1 RG 0 0 m 100 0 l S 0 1 1 0 K 0 10 m 100 10 l S"
+                    .to_vec(),
+                resources: None,
+                bbox: [0.0, 0.0, 612.0, 792.0],
+                matrix: None,
+                font_refs: std::collections::HashMap::new(),
+                font_cmaps: std::collections::HashMap::new(),
+                font_widths: std::collections::HashMap::new(),
+                images: std::collections::HashMap::new(),
+                color_spaces: std::collections::HashMap::new(),
+                form_xobjects: std::collections::HashMap::new(),
+                hidden_content: std::collections::HashSet::new(),
+            },
+        );
+
+        let page = render_pdf_page(&doc, 0, 1, &ConversionConfig::default()).unwrap();
+        // Index 1 of the page's space, not white from DeviceGray
+        assert_eq!(page.text_spans[0].color, "rgb(255, 0, 0)");
+        let colors: Vec<&str> = page.strokes.iter().map(|s| s.color.as_str()).collect();
+        assert_eq!(colors, vec!["rgb(0, 0, 255)", "rgb(255, 46, 23)"]);
+    }
+
+    #[test]
+    fn test_unbalanced_form_does_not_leak_state() {
+        let mut doc = document_with_contents(b"q /Fm0 Do Q BT /F1 12 Tf 72 700 Td (After) Tj ET");
+        doc.pages[0].form_xobjects.insert(
+            "Fm0".to_string(),
+            crate::parser::FormXObject {
+                name: "Fm0".to_string(),
+                // Leaves a q open and pops one it never saved
+                content_stream: b"Q q 1 0 0 rg 3 0 0 3 0 0 cm BT /F1 12 Tf 0 0 Td (In) Tj ET".to_vec(),
+                resources: None,
+                bbox: [0.0, 0.0, 612.0, 792.0],
+                matrix: None,
+                font_refs: std::collections::HashMap::new(),
+                font_cmaps: std::collections::HashMap::new(),
+                font_widths: std::collections::HashMap::new(),
+                images: std::collections::HashMap::new(),
+                color_spaces: std::collections::HashMap::new(),
+                form_xobjects: std::collections::HashMap::new(),
+                hidden_content: std::collections::HashSet::new(),
+            },
+        );
+
+        let page = render_pdf_page(&doc, 0, 1, &ConversionConfig::default()).unwrap();
+        let after = page.text_spans.iter().find(|s| s.text == "After").unwrap();
+        assert_eq!(after.color, "rgb(0, 0, 0)");
+        assert_eq!(after.font_size, 12.0);
+        assert_eq!(after.x, 72.0);
+        assert_eq!(
+            page.diagnostics,
+            vec!["form /Fm0: content stream ended with 1 unclosed q and 1 unmatched Q"]
+        );
+    }
+
+    #[test]
+    fn test_unclosed_q_on_page_is_reported() {
+        let doc = document_with_contents(b"q 0.5 g BT /F1 12 Tf 72 700 Td (Open) Tj ET");
+        let page = render_pdf_page(&doc, 0, 1, &ConversionConfig::default()).unwrap();
+        assert_eq!(
+            page.diagnostics,
+            vec!["content stream ended with 1 unclosed q"]
+        );
+    }
+
+    #[test]
+    fn test_links_render_as_positioned_anchors() {
+        use crate::parser::LinkTarget;
+
+        let mut doc = document_with_contents(b"");
+        doc.pages[0].links = vec![
+            LinkAnnotation {
+                rect: [72.0, 700.0, 172.0, 720.0],
+                target: LinkTarget::Uri("https://example.com/?a=1&b=2".to_string()),
+            },
+            LinkAnnotation {
+                rect: [72.0, 100.0, 100.0, 110.0],
+                target: LinkTarget::Page(3),
+            },
+        ];
+
+        let page = render_pdf_page(&doc, 0, 1, &ConversionConfig::default()).unwrap();
+        assert!(page.html.contains(
+            "<a href=\"https://example.com/?a=1&amp;b=2\" style=\"position:absolute;left:72px;top:72px;width:100px;height:20px;\"></a>"
+        ));
+        assert!(page.html.contains("<a href=\"#page-3\""));
+    }
+
+    #[test]
+    fn test_links_turn_with_rotated_page_content() {
+        use crate::parser::LinkTarget;
+
+        let mut doc = document_with_contents(b"BT /F1 12 Tf 72 700 Td (Linked) Tj ET");
+        doc.pages[0].rotation = 90;
+        doc.pages[0].links = vec![LinkAnnotation {
+            rect: [70.0, 695.0, 170.0, 715.0],
+            target: LinkTarget::Page(2),
+        }];
+
+        let page = render_pdf_page(&doc, 0, 1, &ConversionConfig::default()).unwrap();
+        assert_eq!((page.width, page.height), (792.0, 612.0));
+        // The text origin (72, 700) turns to (700, 72) from the top left,
+        // inside the anchor the link rect turns into
+        let span = &page.text_spans[0];
+        assert_eq!((span.x, span.y + 12.0 * 0.85), (700.0, 72.0));
+        assert!(page.html.contains(
+            "<a href=\"#page-2\" style=\"position:absolute;left:695px;top:70px;width:20px;height:100px;\"></a>"
+        ));
+    }
+
+    #[test]
+    fn test_output_scale_applies_to_all_geometry() {
+        use crate::parser::LinkTarget;
+
+        let mut doc = document_with_contents(
+            b"0 0 1 rg 72 600 100 50 re f BT /F1 12 Tf 72 700 Td (Scaled) Tj ET",
+        );
+        doc.pages[0].links = vec![LinkAnnotation {
+            rect: [72.0, 700.0, 172.0, 720.0],
+            target: LinkTarget::Page(2),
+        }];
+        let config = ConversionConfig::default().with_target_dpi(144.0);
+        assert_eq!(config.output_scale, 2.0);
+
+        let plain = render_pdf_page(&doc, 0, 1, &ConversionConfig::default()).unwrap();
+        let page = render_pdf_page(&doc, 0, 1, &config).unwrap();
+        assert_eq!((page.width, page.height), (1224.0, 1584.0));
+        let (span, plain_span) = (&page.text_spans[0], &plain.text_spans[0]);
+        assert_eq!(span.x, plain_span.x * 2.0);
+        assert_eq!(span.y, plain_span.y * 2.0);
+        assert_eq!(span.font_size, 24.0);
+        assert!(page.html.starts_with("<div style=\"width:1224px;height:1584px;"));
+        assert!(page
+            .html
+            .contains("left:144px;top:284px;width:200px;height:100px;background:"));
+        assert!(page
+            .html
+            .contains("style=\"position:absolute;left:144px;top:144px;width:200px;height:40px;\""));
+    }
+
     #[test]
     fn test_content_addressed_file_naming() {
         let font_data = b"test font data";
@@ -935,4 +3256,466 @@ mod tests {
         assert!(font.filename.ends_with(".woff2"));
         assert_eq!(font.filename.len(), 64 + 1 + 5); // 64 char SHA256 + "." + "woff2"
     }
+
+    fn bundle_with_font() -> OutputBundle {
+        let mut doc = document_with_contents(b"BT /F1 12 Tf 72 720 Td (Hi) Tj ET");
+        doc.pages[0]
+            .font_refs
+            .insert("F1".to_string(), ObjectReference(10, 0));
+        doc.pages.push(doc.pages[0].clone());
+        doc.pages[1].page_number = 2;
+        let font_ids = FontIds::from([(
+            ObjectReference(10, 0),
+            ExtractedFontRef { id: 0, ascent: None, descent: None },
+        )]);
+
+        let config = ConversionConfig::default();
+        let mut bundle = OutputBundle {
+            css: shared_css(&config),
+            ..Default::default()
+        };
+        for page_index in 0..2 {
+            let page =
+                render_pdf_page_with_fonts(&doc, page_index, page_index + 1, &config, &font_ids)
+                    .unwrap();
+            bundle.add_page(page);
+        }
+        bundle.add_font(
+            0,
+            "TestFont".to_string(),
+            vec![1, 2, 3],
+            crate::config::FontFormat::Woff,
+        );
+        bundle
+    }
+
+    #[test]
+    fn test_images_are_lazy_with_intrinsic_dimensions() {
+        let mut doc = document_with_contents(b"q 100 0 0 50 72 600 cm /Im0 Do Q");
+        doc.pages[0].images.insert(
+            "Im0".to_string(),
+            crate::parser::PageImage {
+                name: "Im0".to_string(),
+                data: vec![0xFF, 0xD8],
+                width: 400,
+                height: 200,
+                mime_type: "image/jpeg".to_string(),
+                unsupported_filter: None,
+            },
+        );
+
+        let page = render_pdf_page(&doc, 0, 1, &ConversionConfig::default()).unwrap();
+        assert!(page
+            .html
+            .contains("<img loading=\"lazy\" width=\"400\" height=\"200\" style="));
+
+        let eager = ConversionConfig {
+            lazy_images: false,
+            ..Default::default()
+        };
+        let page = render_pdf_page(&doc, 0, 1, &eager).unwrap();
+        assert!(page.html.contains("<img width=\"400\" height=\"200\" style="));
+    }
+
+    #[test]
+    fn test_spans_use_the_extracted_font_for_their_resource() {
+        let mut doc = document_with_contents(b"BT /F1 12 Tf 72 720 Td (A) Tj /F2 12 Tf (B) Tj ET");
+        doc.pages[0]
+            .font_refs
+            .insert("F1".to_string(), ObjectReference(10, 0));
+        doc.pages[0]
+            .font_refs
+            .insert("F2".to_string(), ObjectReference(11, 0));
+        let font_ids = FontIds::from([(
+            ObjectReference(11, 0),
+            ExtractedFontRef { id: 3, ascent: None, descent: None },
+        )]);
+
+        let page = render_pdf_page_with_fonts(&doc, 0, 1, &ConversionConfig::default(), &font_ids)
+            .unwrap();
+        let fonts: Vec<_> = page
+            .text_spans
+            .iter()
+            .map(|s| (s.text.as_str(), s.font_id))
+            .collect();
+        // /F1 was not extracted, so its text keeps the browser's font
+        assert_eq!(fonts, vec![("A", None), ("B", Some(3))]);
+        assert!(page.html.contains("font-family:'ff3';"));
+        assert_eq!(page.html.matches("font-family").count(), 1);
+    }
+
+    #[test]
+    fn test_span_tops_sit_one_font_ascent_above_the_baseline() {
+        let mut doc = document_with_contents(
+            b"BT /F1 20 Tf 72 700 Td (Tall) Tj /F2 20 Tf (Short) Tj /F3 20 Tf (Plain) Tj ET",
+        );
+        for (name, id) in [("F1", 10), ("F2", 11), ("F3", 12)] {
+            doc.pages[0]
+                .font_refs
+                .insert(name.to_string(), ObjectReference(id, 0));
+        }
+        let font_ids = FontIds::from([
+            (ObjectReference(10, 0), ExtractedFontRef { id: 0, ascent: Some(0.95), descent: None }),
+            (ObjectReference(11, 0), ExtractedFontRef { id: 1, ascent: Some(0.7), descent: None }),
+            (ObjectReference(12, 0), ExtractedFontRef { id: 2, ascent: None, descent: None }),
+        ]);
+        let config = ConversionConfig {
+            merge_spans: false,
+            ..Default::default()
+        };
+
+        let page = render_pdf_page_with_fonts(&doc, 0, 1, &config, &font_ids).unwrap();
+        let baselines: Vec<(&str, f64)> = page
+            .text_spans
+            .iter()
+            .map(|s| (s.text.as_str(), s.y))
+            .collect();
+        // 792 - 700 = 92 is the baseline; fonts without metrics assume 85%
+        assert_eq!(
+            baselines,
+            vec![("Tall", 92.0 - 19.0), ("Short", 92.0 - 14.0), ("Plain", 92.0 - 17.0)]
+        );
+    }
+
+    #[test]
+    fn test_single_file_html_replaces_font_file_urls() {
+        let mut bundle = bundle_with_font();
+        let filename = bundle.fonts[0].filename.clone();
+        bundle.css = format!(
+            "@font-face {{\n  font-family: 'ff0';\n  src: url('{}') format('woff');\n}}\n",
+            filename
+        );
+
+        let html = bundle.to_single_file_html();
+        assert_eq!(html.matches("@font-face").count(), 1);
+        assert!(!html.contains(&filename));
+        assert!(html.contains("url('data:font/woff;base64,AQID')"));
+    }
+
+    #[test]
+    fn test_single_file_html_inlines_fonts() {
+        let bundle = bundle_with_font();
+        let html = bundle.to_single_file_html();
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains(
+            "font-family: 'ff0';\n  src: url('data:font/woff;base64,AQID') format('woff');"
+        ));
+        assert!(html.contains("font-family:'ff0';"));
+        assert!(html.contains("id=\"page-1\""));
+        assert!(html.contains("id=\"page-2\""));
+        assert_eq!(html.matches(".page span").count(), 1);
+    }
+
+    fn page_with_spans(page_number: usize, spans: &[(&str, f64, f64)]) -> RenderedPage {
+        RenderedPage {
+            page_number,
+            width: 612.0,
+            height: 792.0,
+            html: String::new(),
+            css: String::new(),
+            text_spans: spans
+                .iter()
+                .map(|(text, x, y)| TextSpan {
+                    text: text.to_string(),
+                    x: *x,
+                    y: *y,
+                    font_size: 10.0,
+                    font_id: None,
+                    color: "rgb(0, 0, 0)".to_string(),
+                    covered: false,
+                    scale_x: 1.0,
+                    vertical: false,
+                    width: 5.0 * text.len() as f64,
+                    height: 10.5,
+                    underline: false,
+                    line_through: false,
+                    letter_spacing: 0.0,
+                    word_spacing: 0.0,
+                    lang: None,
+                    mcid: None,
+                })
+                .collect(),
+            reading_order: Vec::new(),
+            font_ids: Vec::new(),
+            background_color: None,
+            images: Vec::new(),
+            filled_rects: Vec::new(),
+            strokes: Vec::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_reflow_puts_structure_roles_in_semantic_tags() {
+        use crate::parser::{StructElement, StructNode};
+
+        let element = |role: &str, children: Vec<StructNode>| {
+            StructNode::Element(StructElement {
+                role: role.to_string(),
+                alt: None,
+                lang: None,
+                children,
+            })
+        };
+        let content = |mcid| StructNode::Content { page: 1, mcid };
+        let table = element("Table", vec![element("TR", vec![element("TD", vec![content(2)])])]);
+        let StructNode::Element(document) = element(
+            "Document",
+            vec![
+                element("H1", vec![content(0)]),
+                element("P", vec![content(1)]),
+                table,
+            ],
+        ) else {
+            unreachable!()
+        };
+        let spans = [
+            ("Title", 72.0, 60.0),
+            ("One", 72.0, 100.0),
+            ("line", 72.0, 112.0),
+            ("Cell", 72.0, 200.0),
+        ];
+        let mut page = page_with_spans(1, &spans);
+        for (span, mcid) in page.text_spans.iter_mut().zip([0, 1, 1, 2]) {
+            span.mcid = Some(mcid);
+        }
+
+        assert_eq!(
+            reflow::reflow_html(&[document], &[page]),
+            "<main class=\"reflow\">\n<div><h1>Title</h1>\n<p>One line</p>\n\
+<table><tr><td>Cell</td>\n</tr>\n</table>\n</div>\n</main>\n"
+        );
+    }
+
+    #[test]
+    fn test_plain_text_reads_lines_top_to_bottom() {
+        let mut bundle = OutputBundle::default();
+        bundle.add_page(page_with_spans(
+            1,
+            &[
+                ("world", 120.0, 100.0),
+                ("Second line", 72.0, 112.0),
+                ("Hello", 72.0, 101.0),
+                ("New paragraph", 72.0, 150.0),
+            ],
+        ));
+        bundle.add_page(page_with_spans(2, &[("Page two", 72.0, 100.0)]));
+
+        assert_eq!(
+            bundle.render(OutputFormat::Text),
+            "Hello world\nSecond line\n\nNew paragraph\u{c}Page two"
+        );
+    }
+
+    #[test]
+    fn test_plain_text_reads_two_columns_one_after_the_other() {
+        // Each row shows its left line, then its right one
+        let doc = document_with_contents(
+            b"BT /F1 12 Tf 72 720 Td (Left one) Tj 248 0 Td (Right one) Tj \
+-248 -14 Td (Left two) Tj 248 0 Td (Right two) Tj ET",
+        );
+        let text = |column_gap| {
+            let config = ConversionConfig {
+                column_gap,
+                ..Default::default()
+            };
+            let mut bundle = OutputBundle::default();
+            bundle.add_page(render_pdf_page(&doc, 0, 1, &config).unwrap());
+            bundle.to_plain_text()
+        };
+
+        assert_eq!(text(Some(8.0)), "Left one\nLeft two\n\nRight one\nRight two");
+        assert_eq!(text(None), "Left one Right one\nLeft two Right two");
+    }
+
+    #[test]
+    fn test_plain_text_keeps_key_value_rows_together_by_default() {
+        let doc = document_with_contents(
+            b"BT /F1 12 Tf 72 720 Td (Name:) Tj 120 0 Td (Alice) Tj \
+-120 -14 Td (Role:) Tj 120 0 Td (Editor) Tj ET",
+        );
+        let mut bundle = OutputBundle::default();
+        bundle.add_page(render_pdf_page(&doc, 0, 1, &ConversionConfig::default()).unwrap());
+
+        assert_eq!(bundle.to_plain_text(), "Name: Alice\nRole: Editor");
+    }
+
+    #[test]
+    fn test_json_output_lists_spans_with_page_size() {
+        let mut bundle = OutputBundle::default();
+        bundle.add_page(page_with_spans(1, &[("Total", 72.0, 100.0)]));
+
+        let json: serde_json::Value = serde_json::from_str(&bundle.to_json()).unwrap();
+        assert_eq!(json["pages"][0]["width"], 612.0);
+        assert_eq!(json["pages"][0]["height"], 792.0);
+        assert_eq!(json["pages"][0]["spans"][0]["text"], "Total");
+        assert_eq!(json["pages"][0]["spans"][0]["x"], 72.0);
+        assert_eq!(json["encrypted"], false);
+    }
+
+    #[test]
+    fn test_text_output_skips_page_html() {
+        let doc = document_with_contents(b"BT /F1 12 Tf 72 720 Td (Hi) Tj ET");
+        let config = ConversionConfig {
+            output_format: OutputFormat::Text,
+            ..Default::default()
+        };
+        let page = render_pdf_page(&doc, 0, 1, &config).unwrap();
+        assert!(page.html.is_empty());
+        assert_eq!(page.text_spans[0].text, "Hi");
+    }
+
+    #[test]
+    fn test_horizontal_scaling_carries_to_the_span() {
+        let doc = document_with_contents(b"BT /F1 12 Tf 50 Tz 72 720 Td (Hi) Tj ET");
+        let page = render_pdf_page(&doc, 0, 1, &ConversionConfig::default()).unwrap();
+        assert_eq!(page.text_spans[0].scale_x, 0.5);
+        assert!(page
+            .html
+            .contains("transform:scaleX(0.5);transform-origin:left;"));
+
+        let doc = document_with_contents(b"BT /F1 12 Tf 72 720 Td (Hi) Tj ET");
+        let page = render_pdf_page(&doc, 0, 1, &ConversionConfig::default()).unwrap();
+        assert_eq!(page.text_spans[0].scale_x, 1.0);
+        assert!(!page.html.contains("scaleX"));
+    }
+
+    fn png_image() -> PageImageRef {
+        let image = crate::parser::PageImage {
+            name: "Im0".to_string(),
+            data: vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A],
+            width: 1,
+            height: 1,
+            mime_type: "image/png".to_string(),
+            unsupported_filter: None,
+        };
+        PageImageRef::new(&image, 0.0, 0.0, 10.0, 10.0)
+    }
+
+    #[test]
+    fn test_dropping_an_image_cuts_only_its_own_tags() {
+        let mut doc = document_with_contents(
+            b"q 10 0 0 10 0 0 cm /A Do Q q 10 0 0 10 20 0 cm /B Do Q q 10 0 0 10 40 0 cm /A Do Q",
+        );
+        let image = |fill: u8| crate::parser::PageImage {
+            name: "Im".to_string(),
+            data: vec![0x89, b'P', b'N', b'G', fill],
+            width: 1,
+            height: 1,
+            mime_type: "image/png".to_string(),
+            unsupported_filter: None,
+        };
+        doc.pages[0].images.insert("A".to_string(), image(1));
+        doc.pages[0].images.insert("B".to_string(), image(2));
+        let mut page = render_pdf_page(&doc, 0, 1, &ConversionConfig::default()).unwrap();
+        let filename = |i: usize| page.images[i].filename.clone();
+        let (dropped, kept) = (filename(0), filename(1));
+
+        page.drop_image(&dropped);
+
+        assert!(!page.html.contains(&dropped));
+        assert_eq!(page.html.matches("<img").count(), 1);
+        assert_eq!(page.images.len(), 1);
+        let range = page.images[0].html_range.clone().unwrap();
+        let tag = &page.html[range];
+        assert!(tag.starts_with("<img") && tag.ends_with(&format!("src=\"{}\">", kept)));
+        assert!(page.html.starts_with("<div") && page.html.ends_with("</div>"));
+    }
+
+    /// `bundle_with_font`'s pages, each drawing the same image, added the
+    /// way a conversion adds them.
+    fn bundle_with_shared_image() -> OutputBundle {
+        let mut bundle = bundle_with_font();
+        for mut page in std::mem::take(&mut bundle.pages) {
+            let image = png_image();
+            page.html.push_str(&format!("<img src=\"{}\">", image.filename));
+            page.images.push(image);
+            bundle.add_page(page);
+        }
+        bundle
+    }
+
+    #[test]
+    fn test_asset_files_write_images_once() {
+        let bundle = bundle_with_shared_image();
+        let image_name = ContentHasher::generate_content_addressed_filename(
+            &[0x89, b'P', b'N', b'G', 0x0D, 0x0A],
+            "png",
+        );
+        assert_eq!(bundle.images.len(), 1);
+        assert_eq!(bundle.images[0].filename, image_name);
+        assert!(bundle.pages.iter().all(|p| p.images[0].data.is_empty()));
+
+        let files = bundle.to_asset_files();
+        let images = files.iter().filter(|(name, _)| *name == image_name);
+        assert_eq!(images.count(), 1);
+
+        let index = String::from_utf8(files.last().unwrap().1.clone()).unwrap();
+        assert_eq!(index.matches(&format!("src=\"{}\"", image_name)).count(), 2);
+        assert!(!index.contains("data:image"));
+    }
+
+    #[test]
+    fn test_single_file_html_inlines_a_shared_image_once() {
+        let bundle = bundle_with_shared_image();
+        let html = bundle.to_single_file_html();
+        assert_eq!(html.matches("data:image/png;base64,").count(), 1);
+        assert!(!html.contains(&bundle.images[0].filename));
+
+        let class = format!("img-{}", &bundle.images[0].filename[..16]);
+        assert!(html.contains(&format!(".{} {{ background: url('data:image/png", class)));
+        assert_eq!(html.matches(&format!("class=\"{}\"", class)).count(), 2);
+
+        // Drawn once, an image is inlined where it is drawn
+        let mut bundle = bundle_with_shared_image();
+        bundle.pages.truncate(1);
+        let html = bundle.to_single_file_html();
+        assert!(html.contains("<img src=\"data:image/png;base64,"));
+        assert!(!html.contains("img-"));
+    }
+
+    #[test]
+    fn test_standalone_page_html_inlines_its_images() {
+        let mut page = bundle_with_font().pages.remove(0);
+        let image = png_image();
+        page.html.push_str(&format!("<img src=\"{}\">", image.filename));
+        page.images.push(image);
+        assert!(page.standalone_html().contains("<img src=\"data:image/png;base64,"));
+    }
+
+    #[test]
+    fn test_summary_counts_match_bundle() {
+        let mut bundle = bundle_with_font();
+        bundle.pages[0].images.push(png_image());
+        bundle.language = Some("fi-FI".to_string());
+
+        let summary = bundle.summary();
+        assert_eq!(summary.page_count, bundle.pages.len());
+        assert_eq!(summary.font_count, bundle.fonts.len());
+        assert_eq!(summary.image_count, 1);
+        assert_eq!(
+            summary.span_count,
+            bundle.pages.iter().map(|p| p.text_spans.len()).sum::<usize>()
+        );
+        assert_eq!(summary.language.as_deref(), Some("fi-FI"));
+        assert!(!summary.encrypted);
+    }
+
+    #[test]
+    fn test_asset_files_reference_fonts_by_filename() {
+        let bundle = bundle_with_font();
+        let files = bundle.to_asset_files();
+        let filename = &bundle.fonts[0].filename;
+
+        assert_eq!(files.len(), 2);
+        assert_eq!(&files[0].0, filename);
+        assert_eq!(files[0].1, vec![1, 2, 3]);
+
+        let (name, index) = &files[1];
+        assert_eq!(name, "index.html");
+        let index = String::from_utf8(index.clone()).unwrap();
+        assert!(index.contains(&format!("src: url('{}') format('woff');", filename)));
+        assert!(!index.contains("base64"));
+    }
 }
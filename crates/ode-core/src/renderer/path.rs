@@ -0,0 +1,366 @@
+use super::ContentOp;
+use crate::render::state::{ClipState, GraphicsState};
+use crate::util::math::{BoundingBox, TransformMatrix};
+use serde::{Deserialize, Serialize};
+
+/// A step of a path, in output pixels from the page's top-left.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PathSegment {
+    MoveTo(f64, f64),
+    LineTo(f64, f64),
+    /// Two control points, then the end point.
+    CurveTo(f64, f64, f64, f64, f64, f64),
+    Close,
+}
+
+/// A path painted with `S`, `s` or one of the `B` operators.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StrokedPath {
+    pub segments: Vec<PathSegment>,
+    /// The CSS stroke color.
+    pub color: String,
+    /// The stroke width in output pixels.
+    pub width: f64,
+}
+
+impl StrokedPath {
+    pub fn scale(&mut self, scale: f64) {
+        for segment in &mut self.segments {
+            *segment = match *segment {
+                PathSegment::MoveTo(x, y) => PathSegment::MoveTo(x * scale, y * scale),
+                PathSegment::LineTo(x, y) => PathSegment::LineTo(x * scale, y * scale),
+                PathSegment::CurveTo(x1, y1, x2, y2, x, y) => PathSegment::CurveTo(
+                    x1 * scale,
+                    y1 * scale,
+                    x2 * scale,
+                    y2 * scale,
+                    x * scale,
+                    y * scale,
+                ),
+                PathSegment::Close => PathSegment::Close,
+            };
+        }
+        self.width *= scale;
+    }
+
+    /// The stroke as HTML for a page `width` by `height` pixels.
+    ///
+    /// Rectangles and horizontal or vertical lines become bordered `<div>`s,
+    /// which is what table rules and underlines are; the subpaths left over
+    /// are drawn by one inline `<svg>` covering the page.
+    pub fn to_html(&self, width: f64, height: f64) -> String {
+        let mut html = String::new();
+        let mut curved = String::new();
+        for subpath in self.subpaths() {
+            if let Some([x0, y0, x1, y1]) = axis_aligned_rect(subpath) {
+                let w = self.width;
+                html.push_str(&format!(
+                    "<div style=\"position:absolute;left:{}px;top:{}px;width:{}px;height:{}px;\
+box-sizing:border-box;border:{}px solid {};\"></div>",
+                    round(x0 - w / 2.0),
+                    round(y0 - w / 2.0),
+                    round(x1 - x0 + w),
+                    round(y1 - y0 + w),
+                    round(w),
+                    self.color
+                ));
+            } else if let Some(lines) = axis_aligned_lines(subpath) {
+                for line in lines {
+                    html.push_str(&self.line_html(line));
+                }
+            } else {
+                curved.push_str(&svg_path_data(subpath));
+            }
+        }
+        if !curved.is_empty() {
+            html.push_str(&format!(
+                "<svg width=\"{}\" height=\"{}\" style=\"position:absolute;left:0;top:0;\
+overflow:visible;\"><path d=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\"/></svg>",
+                round(width),
+                round(height),
+                curved.trim_end(),
+                self.color,
+                round(self.width)
+            ));
+        }
+        html
+    }
+
+    /// A horizontal or vertical line `(x0, y0, x1, y1)` as a `<div>` whose
+    /// border is centered on it.
+    fn line_html(&self, (x0, y0, x1, y1): (f64, f64, f64, f64)) -> String {
+        let w = self.width;
+        let (left, top, div_width, div_height, side) = if y0 == y1 {
+            (x0.min(x1), y0 - w / 2.0, (x1 - x0).abs(), 0.0, "top")
+        } else {
+            (x0 - w / 2.0, y0.min(y1), 0.0, (y1 - y0).abs(), "left")
+        };
+        format!(
+            "<div style=\"position:absolute;left:{}px;top:{}px;width:{}px;height:{}px;\
+border-{}:{}px solid {};\"></div>",
+            round(left),
+            round(top),
+            round(div_width),
+            round(div_height),
+            side,
+            round(w),
+            self.color
+        )
+    }
+
+    /// The segments split at each `MoveTo`.
+    fn subpaths(&self) -> Vec<&[PathSegment]> {
+        let mut subpaths = Vec::new();
+        let mut start = 0;
+        for (i, segment) in self.segments.iter().enumerate().skip(1) {
+            if matches!(segment, PathSegment::MoveTo(..)) {
+                subpaths.push(&self.segments[start..i]);
+                start = i;
+            }
+        }
+        if start < self.segments.len() {
+            subpaths.push(&self.segments[start..]);
+        }
+        subpaths
+    }
+}
+
+/// The path under construction, in the page's displayed user space.
+#[derive(Debug, Default)]
+pub struct PathBuilder {
+    segments: Vec<PathSegment>,
+    /// Whether a subpath is open, giving `l`, `c` and `h` a current point.
+    started: bool,
+}
+
+impl PathBuilder {
+    /// Add the segment a path construction operator draws; other operators
+    /// are ignored.
+    pub fn apply(&mut self, operator: ContentOp, operands: &[f64], ctm: &TransformMatrix) {
+        let point = |x: f64, y: f64| ctm.transform_point(x, y);
+        match (operator, operands) {
+            (ContentOp::M, [x, y, ..]) => {
+                let (x, y) = point(*x, *y);
+                self.segments.push(PathSegment::MoveTo(x, y));
+                self.started = true;
+            }
+            (ContentOp::L, [x, y, ..]) if self.started => {
+                let (x, y) = point(*x, *y);
+                self.segments.push(PathSegment::LineTo(x, y));
+            }
+            (ContentOp::C, [x1, y1, x2, y2, x, y, ..]) if self.started => {
+                let (x1, y1) = point(*x1, *y1);
+                let (x2, y2) = point(*x2, *y2);
+                let (x, y) = point(*x, *y);
+                self.segments
+                    .push(PathSegment::CurveTo(x1, y1, x2, y2, x, y));
+            }
+            (ContentOp::RE, [x, y, w, h, ..]) => {
+                let corners = [(*x, *y), (x + w, *y), (x + w, y + h), (*x, y + h)];
+                let (x0, y0) = point(corners[0].0, corners[0].1);
+                self.segments.push(PathSegment::MoveTo(x0, y0));
+                for (cx, cy) in &corners[1..] {
+                    let (cx, cy) = point(*cx, *cy);
+                    self.segments.push(PathSegment::LineTo(cx, cy));
+                }
+                self.segments.push(PathSegment::Close);
+                self.started = true;
+            }
+            (ContentOp::H, _) => self.close(),
+            _ => {}
+        }
+    }
+
+    /// Close the current subpath, as `h`, `s` and `b` do.
+    pub fn close(&mut self) {
+        if self.started && !matches!(self.segments.last(), Some(PathSegment::Close)) {
+            self.segments.push(PathSegment::Close);
+        }
+    }
+
+    /// End the path, as every painting operator does.
+    pub fn clear(&mut self) {
+        self.segments.clear();
+        self.started = false;
+    }
+
+    /// The path stroked with the graphics state's color and `line_width`,
+    /// placed on a page `page_height` tall. `None` when nothing would draw
+    /// or the path lies outside the clip.
+    pub fn stroke(
+        &self,
+        graphics_state: &GraphicsState,
+        ctm: &TransformMatrix,
+        page_height: f64,
+    ) -> Option<StrokedPath> {
+        if !self
+            .segments
+            .iter()
+            .any(|s| matches!(s, PathSegment::LineTo(..) | PathSegment::CurveTo(..)))
+        {
+            return None;
+        }
+        // A width of 0 is the thinnest line the device can draw
+        let scale = (ctm.a * ctm.d - ctm.b * ctm.c).abs().sqrt();
+        let width = match graphics_state.line_width * scale {
+            w if w > 0.0 => w,
+            _ => 1.0,
+        };
+
+        let points: Vec<(f64, f64)> = self.segments.iter().flat_map(points).collect();
+        let (xs, ys): (Vec<f64>, Vec<f64>) = points.into_iter().unzip();
+        let half = width / 2.0;
+        let mut area = ClipState::from_bbox(&BoundingBox::new(
+            min(&xs) - half,
+            min(&ys) - half,
+            max(&xs) + half,
+            max(&ys) + half,
+        ));
+        area.intersect(&graphics_state.clip);
+        if area.is_empty() {
+            return None;
+        }
+
+        let flip = |y: f64| page_height - y;
+        let segments = self
+            .segments
+            .iter()
+            .map(|segment| match *segment {
+                PathSegment::MoveTo(x, y) => PathSegment::MoveTo(x, flip(y)),
+                PathSegment::LineTo(x, y) => PathSegment::LineTo(x, flip(y)),
+                PathSegment::CurveTo(x1, y1, x2, y2, x, y) => {
+                    PathSegment::CurveTo(x1, flip(y1), x2, flip(y2), x, flip(y))
+                }
+                PathSegment::Close => PathSegment::Close,
+            })
+            .collect();
+        Some(StrokedPath {
+            segments,
+            color: graphics_state.stroke_color.to_css_string(),
+            width,
+        })
+    }
+}
+
+fn points(segment: &PathSegment) -> Vec<(f64, f64)> {
+    match *segment {
+        PathSegment::MoveTo(x, y) | PathSegment::LineTo(x, y) => vec![(x, y)],
+        PathSegment::CurveTo(x1, y1, x2, y2, x, y) => vec![(x1, y1), (x2, y2), (x, y)],
+        PathSegment::Close => Vec::new(),
+    }
+}
+
+/// The straight segments of a subpath with no curves, including the one
+/// `Close` draws back to its start.
+fn straight_lines(subpath: &[PathSegment]) -> Option<Vec<(f64, f64, f64, f64)>> {
+    let start = match subpath.first()? {
+        PathSegment::MoveTo(x, y) => (*x, *y),
+        _ => return None,
+    };
+    let mut current = start;
+    let mut lines = Vec::new();
+    for segment in &subpath[1..] {
+        let next = match *segment {
+            PathSegment::LineTo(x, y) => (x, y),
+            PathSegment::Close => start,
+            PathSegment::MoveTo(..) | PathSegment::CurveTo(..) => return None,
+        };
+        if next != current {
+            lines.push((current.0, current.1, next.0, next.1));
+        }
+        current = next;
+    }
+    Some(lines)
+}
+
+/// The subpath's lines, when every one of them is horizontal or vertical.
+fn axis_aligned_lines(subpath: &[PathSegment]) -> Option<Vec<(f64, f64, f64, f64)>> {
+    let lines = straight_lines(subpath)?;
+    lines
+        .iter()
+        .all(|&(x0, y0, x1, y1)| x0 == x1 || y0 == y1)
+        .then_some(lines)
+}
+
+/// `[left, top, right, bottom]` of a subpath tracing an upright rectangle:
+/// four sides turning at each corner and ending where they began.
+fn axis_aligned_rect(subpath: &[PathSegment]) -> Option<[f64; 4]> {
+    let lines = axis_aligned_lines(subpath)?;
+    if lines.len() != 4 || (lines[3].2, lines[3].3) != (lines[0].0, lines[0].1) {
+        return None;
+    }
+    let turning = lines
+        .windows(2)
+        .all(|pair| (pair[0].0 == pair[0].2) != (pair[1].0 == pair[1].2));
+    let (xs, ys): (Vec<f64>, Vec<f64>) = lines.iter().map(|l| (l.0, l.1)).unzip();
+    let (x0, x1) = (min(&xs), max(&xs));
+    let (y0, y1) = (min(&ys), max(&ys));
+    (turning && x1 > x0 && y1 > y0).then_some([x0, y0, x1, y1])
+}
+
+fn min(values: &[f64]) -> f64 {
+    values.iter().copied().fold(f64::INFINITY, f64::min)
+}
+
+fn max(values: &[f64]) -> f64 {
+    values.iter().copied().fold(f64::NEG_INFINITY, f64::max)
+}
+
+fn svg_path_data(subpath: &[PathSegment]) -> String {
+    let mut data = String::new();
+    for segment in subpath {
+        match *segment {
+            PathSegment::MoveTo(x, y) => data.push_str(&format!("M{} {} ", round(x), round(y))),
+            PathSegment::LineTo(x, y) => data.push_str(&format!("L{} {} ", round(x), round(y))),
+            PathSegment::CurveTo(x1, y1, x2, y2, x, y) => data.push_str(&format!(
+                "C{} {} {} {} {} {} ",
+                round(x1),
+                round(y1),
+                round(x2),
+                round(y2),
+                round(x),
+                round(y)
+            )),
+            PathSegment::Close => data.push_str("Z "),
+        }
+    }
+    data
+}
+
+fn round(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stroked(segments: Vec<PathSegment>) -> StrokedPath {
+        StrokedPath {
+            segments,
+            color: "rgb(0, 0, 0)".to_string(),
+            width: 2.0,
+        }
+    }
+
+    #[test]
+    fn test_straight_rules_become_bordered_divs_and_curves_svg() {
+        let rule = stroked(vec![
+            PathSegment::MoveTo(10.0, 50.0),
+            PathSegment::LineTo(110.0, 50.0),
+        ]);
+        assert_eq!(
+            rule.to_html(600.0, 800.0),
+            "<div style=\"position:absolute;left:10px;top:49px;width:100px;height:0px;\
+border-top:2px solid rgb(0, 0, 0);\"></div>"
+        );
+
+        let arc = stroked(vec![
+            PathSegment::MoveTo(0.0, 0.0),
+            PathSegment::CurveTo(10.0, 0.0, 20.0, 10.0, 20.0, 20.0),
+        ]);
+        assert!(arc
+            .to_html(600.0, 800.0)
+            .contains("<path d=\"M0 0 C10 0 20 10 20 20\" fill=\"none\""));
+    }
+}
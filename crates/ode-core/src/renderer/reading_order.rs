@@ -0,0 +1,142 @@
+use super::TextSpan;
+
+/// Lines read together, and the columns they fall into.
+struct Block {
+    lines: Vec<Vec<usize>>,
+    columns: Vec<(f64, f64)>,
+}
+
+/// Indices into `spans` in reading order. Lines side by side in columns
+/// form a block, read column by column, left to right, and top to bottom
+/// within each column; a line running across the columns, such as a title
+/// or a footer, starts a block of its own. Columns are told apart by a
+/// horizontal gap of at least `column_gap` that no span of the block crosses.
+pub fn reading_order(spans: &[TextSpan], column_gap: f64) -> Vec<usize> {
+    let extent = |i: usize| (spans[i].x, spans[i].x + spans[i].width.max(0.0));
+
+    let mut blocks: Vec<Block> = Vec::new();
+    for line in lines(spans) {
+        let line_columns = columns(line.iter().map(|&i| extent(i)).collect(), column_gap);
+        if let Some(block) = blocks.last_mut() {
+            let together = columns(
+                block.columns.iter().chain(&line_columns).copied().collect(),
+                column_gap,
+            );
+            if together.len() > 1 || (block.columns.len() == 1 && line_columns.len() == 1) {
+                block.lines.push(line);
+                block.columns = together;
+                continue;
+            }
+        }
+        blocks.push(Block {
+            lines: vec![line],
+            columns: line_columns,
+        });
+    }
+
+    let mut order = Vec::with_capacity(spans.len());
+    for block in blocks {
+        for (start, end) in block.columns {
+            for line in &block.lines {
+                order.extend(line.iter().copied().filter(|&i| {
+                    let x = spans[i].x;
+                    x >= start && x <= end
+                }));
+            }
+        }
+    }
+    order
+}
+
+/// The spans top to bottom, as lines of spans whose tops are within half a
+/// font size of the line's first span, each left to right.
+fn lines(spans: &[TextSpan]) -> Vec<Vec<usize>> {
+    let mut indices: Vec<usize> = (0..spans.len()).collect();
+    indices.sort_by(|&a, &b| spans[a].y.total_cmp(&spans[b].y));
+
+    let mut lines: Vec<Vec<usize>> = Vec::new();
+    for i in indices {
+        let on_line = |line: &[usize]| {
+            let first = &spans[line[0]];
+            (spans[i].y - first.y).abs() <= first.font_size / 2.0
+        };
+        match lines.last_mut() {
+            Some(line) if on_line(line) => line.push(i),
+            _ => lines.push(vec![i]),
+        }
+    }
+    for line in &mut lines {
+        line.sort_by(|&a, &b| spans[a].x.total_cmp(&spans[b].x));
+    }
+    lines
+}
+
+/// Horizontal extents merged into columns, left to right, wherever less
+/// than `column_gap` separates them.
+fn columns(mut extents: Vec<(f64, f64)>, column_gap: f64) -> Vec<(f64, f64)> {
+    extents.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut columns: Vec<(f64, f64)> = Vec::new();
+    for (start, end) in extents {
+        match columns.last_mut() {
+            Some(column) if start - column.1 < column_gap => column.1 = column.1.max(end),
+            _ => columns.push((start, end)),
+        }
+    }
+    columns
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(text: &str, x: f64, y: f64, width: f64) -> TextSpan {
+        TextSpan {
+            text: text.to_string(),
+            x,
+            y,
+            font_size: 10.0,
+            font_id: None,
+            color: "rgb(0, 0, 0)".to_string(),
+            covered: false,
+            scale_x: 1.0,
+            vertical: false,
+            width,
+            height: 10.5,
+            underline: false,
+            line_through: false,
+            letter_spacing: 0.0,
+            word_spacing: 0.0,
+            lang: None,
+            mcid: None,
+        }
+    }
+
+    fn read(spans: &[TextSpan], column_gap: f64) -> Vec<&str> {
+        reading_order(spans, column_gap)
+            .into_iter()
+            .map(|i| spans[i].text.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_title_and_footer_across_columns_are_read_in_place() {
+        let spans = vec![
+            span("Left 1", 72.0, 100.0, 200.0),
+            span("Right 1", 320.0, 100.0, 200.0),
+            span("Title", 72.0, 60.0, 448.0),
+            span("Left 2", 72.0, 112.0, 200.0),
+            span("Right 2", 320.0, 112.0, 200.0),
+            span("Footer", 72.0, 700.0, 448.0),
+        ];
+        assert_eq!(
+            read(&spans, 18.0),
+            vec!["Title", "Left 1", "Left 2", "Right 1", "Right 2", "Footer"]
+        );
+        // With a threshold wider than the gutter, rows are read across
+        assert_eq!(
+            read(&spans, 60.0),
+            vec!["Title", "Left 1", "Right 1", "Left 2", "Right 2", "Footer"]
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use super::{escape_html, lang_attribute, RenderedPage};
+use crate::parser::{StructElement, StructNode};
+use std::collections::HashMap;
+
+/// Styles for [`reflow_html`], readable at any width.
+pub const REFLOW_CSS: &str = "main.reflow { max-width:48em; margin:0 auto; padding:1em; \
+line-height:1.5; }\nmain.reflow table { border-collapse:collapse; }\n\
+main.reflow th, main.reflow td { border:1px solid #ccc; padding:0.25em 0.5em; }\n";
+
+/// The text of `pages` laid out by the document's structure tree: each
+/// element in the HTML tag for its role, and each piece of marked content
+/// it holds as the text of the spans carrying that content's id.
+pub fn reflow_html(structure: &[StructElement], pages: &[RenderedPage]) -> String {
+    let mut content: HashMap<(usize, u32), Vec<&str>> = HashMap::new();
+    for page in pages {
+        for span in &page.text_spans {
+            if let Some(mcid) = span.mcid {
+                let texts = content.entry((page.page_number, mcid)).or_default();
+                texts.push(span.text.trim());
+            }
+        }
+    }
+
+    let mut html = String::from("<main class=\"reflow\">\n");
+    for element in structure {
+        element_html(element, "", &content, &mut html);
+    }
+    html.push_str("</main>\n");
+    html
+}
+
+fn element_html(
+    element: &StructElement,
+    parent_role: &str,
+    content: &HashMap<(usize, u32), Vec<&str>>,
+    html: &mut String,
+) {
+    let tag = html_tag(&element.role, parent_role);
+    html.push('<');
+    html.push_str(tag);
+    html.push_str(&lang_attribute(element.lang.as_deref()));
+    if let Some(alt) = element.alt.as_ref().filter(|_| tag == "figure") {
+        html.push_str(&format!(
+            " role=\"img\" aria-label=\"{}\"",
+            escape_html(alt)
+        ));
+    }
+    html.push('>');
+
+    let mut after_text = false;
+    for child in &element.children {
+        match child {
+            StructNode::Element(child) => {
+                element_html(child, &element.role, content, html);
+                after_text = false;
+            }
+            StructNode::Content { page, mcid } => {
+                let Some(texts) = content.get(&(*page, *mcid)) else {
+                    continue;
+                };
+                for text in texts.iter().filter(|text| !text.is_empty()) {
+                    if after_text {
+                        html.push(' ');
+                    }
+                    html.push_str(&escape_html(text));
+                    after_text = true;
+                }
+            }
+        }
+    }
+
+    html.push_str("</");
+    html.push_str(tag);
+    html.push('>');
+    if !is_inline(tag) {
+        html.push('\n');
+    }
+}
+
+/// The HTML element for a standard structure type; custom types the
+/// `/RoleMap` doesn't map, and grouping types, become `div`s.
+fn html_tag(role: &str, parent_role: &str) -> &'static str {
+    match role {
+        "Art" => "article",
+        "Sect" => "section",
+        "H1" => "h1",
+        "H2" => "h2",
+        "H3" => "h3",
+        "H4" => "h4",
+        "H5" => "h5",
+        "H6" => "h6",
+        "Title" => "h1",
+        // A heading whose level only its nesting gives
+        "H" => "h2",
+        "P" | "BibEntry" => "p",
+        "L" | "TOC" => "ul",
+        "LI" | "TOCI" => "li",
+        "Lbl" | "Span" | "Link" | "Annot" | "Reference" | "Ruby" | "Warichu" => "span",
+        "Table" => "table",
+        "THead" => "thead",
+        "TBody" => "tbody",
+        "TFoot" => "tfoot",
+        "TR" => "tr",
+        "TH" => "th",
+        "TD" => "td",
+        "Caption" if parent_role == "Table" => "caption",
+        "Caption" => "figcaption",
+        "Figure" | "Formula" => "figure",
+        "BlockQuote" => "blockquote",
+        "Quote" => "q",
+        "Code" => "code",
+        "Em" => "em",
+        "Strong" => "strong",
+        "Sub" => "sub",
+        "Note" | "FENote" | "Aside" => "aside",
+        _ => "div",
+    }
+}
+
+fn is_inline(tag: &str) -> bool {
+    matches!(tag, "span" | "q" | "code" | "em" | "strong" | "sub")
+}
@@ -1,5 +1,8 @@
+use crate::config::CoveredText;
 use crate::error::OdeError;
 use crate::render::state::GraphicsState;
+use crate::render::CoveredTextDetector;
+use crate::renderer::TextSpan;
 use crate::types::color::Color;
 
 #[derive(Debug, Clone)]
@@ -19,6 +22,15 @@ pub struct TextPosition {
     pub y: f64,
 }
 
+/// Where shown text ends on the page, known when its font declares widths.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextExtent {
+    /// Page x just past the text's last glyph.
+    pub end_x: f64,
+    /// Advance of a space in the text's font, in page units.
+    pub space_width: f64,
+}
+
 #[derive(Debug, Clone)]
 pub struct TextSegment {
     pub text: String,
@@ -26,7 +38,86 @@ pub struct TextSegment {
     pub y: f64,
     pub font_size: f64,
     pub color: Color,
-    pub font_id: u64,
+    /// Id of the extracted font, when the text's font was extracted
+    pub font_id: Option<u64>,
+    /// Extent of the segment's last text
+    pub extent: Option<TextExtent>,
+    /// Whether the segment is text that later drawing covers.
+    pub covered: bool,
+    /// Horizontal scale from `Tz`.
+    pub scale_x: f64,
+    /// Whether the text runs top to bottom.
+    pub vertical: bool,
+    /// Advance of the text along its line, from `x` or, vertical, `y`.
+    pub width: f64,
+    /// Height of the text's line, from the font's ascent to its descent.
+    pub height: f64,
+    /// `Tc` and `Tw` at the text's scale.
+    pub letter_spacing: f64,
+    pub word_spacing: f64,
+    /// Language of the marked content the text is in.
+    pub lang: Option<String>,
+    /// The marked-content id of the text.
+    pub mcid: Option<u32>,
+}
+
+/// Height of a line of text in `state`'s font from its ascent to its
+/// descent.
+fn line_height(state: &GraphicsState) -> f64 {
+    let (ascent, descent) = state
+        .font_info
+        .as_ref()
+        .map_or((super::DEFAULT_ASCENT, super::DEFAULT_DESCENT), |f| {
+            (f.ascent, f.descent)
+        });
+    state.font_size * (ascent - descent)
+}
+
+/// Advance `text` is guessed to take at `font_size` when its font has no
+/// widths: half an em a character.
+pub fn estimated_advance(text: &str, font_size: f64) -> f64 {
+    text.chars().count() as f64 * font_size * 0.5
+}
+
+impl TextSegment {
+    /// Whether `other` looks the same, so the two may share a span.
+    fn same_style(&self, other: &TextSegment) -> bool {
+        self.font_id == other.font_id
+            && self.color == other.color
+            && (self.font_size - other.font_size).abs() <= 0.01
+            && self.covered == other.covered
+            && (self.scale_x - other.scale_x).abs() <= 0.001
+            && self.vertical == other.vertical
+            && (self.letter_spacing - other.letter_spacing).abs() <= 0.001
+            && (self.word_spacing - other.word_spacing).abs() <= 0.001
+            && self.lang == other.lang
+            && self.mcid == other.mcid
+    }
+
+    /// How `text` shown at `x` would continue this segment on its line, if
+    /// at all. Text overlapping the end by up to a space, or starting up to
+    /// 1em past it, joins; a gap over half a space wide is a word break.
+    fn join(&self, x: f64, y: f64) -> Option<&'static str> {
+        let extent = self.extent?;
+        let gap = x - extent.end_x;
+        if (y - self.y).abs() >= 0.5 || gap < -extent.space_width || gap > self.font_size {
+            None
+        } else if gap > extent.space_width / 2.0 && !self.text.ends_with(' ') {
+            Some(" ")
+        } else {
+            Some("")
+        }
+    }
+
+    /// Continue the segment with `other`, which `join` placed after it,
+    /// growing its box to cover both.
+    fn append(&mut self, separator: &str, other: &TextSegment) {
+        self.text.push_str(separator);
+        self.text.push_str(&other.text);
+        self.extent = other.extent;
+        self.width = self.width.max(other.x + other.width - self.x);
+        self.height = self.height.max(other.y + other.height - self.y);
+    }
 }
 
 pub struct TextExtractor {
@@ -34,6 +125,8 @@ pub struct TextExtractor {
     current_segment: Option<TextSegment>,
     current_state: GraphicsState,
     last_position: Option<TextPosition>,
+    merge_spans: bool,
+    covered: bool,
 }
 
 impl TextExtractor {
@@ -43,9 +136,17 @@ impl TextExtractor {
             current_segment: None,
             current_state: GraphicsState::new(),
             last_position: None,
+            merge_spans: true,
+            covered: false,
         }
     }
 
+    /// With `merge_spans` off, every shown string becomes its own segment.
+    pub fn with_merging(mut self, merge_spans: bool) -> Self {
+        self.merge_spans = merge_spans;
+        self
+    }
+
     pub fn update_state(&mut self, state: &GraphicsState) {
         if self.state_changed(state) {
             self.finalize_segment();
@@ -53,19 +154,69 @@ impl TextExtractor {
         self.current_state = state.clone();
     }
 
+    /// Mark the text added from now on as covered or not. Covered text
+    /// never shares a segment with uncovered text.
+    pub fn set_covered(&mut self, covered: bool) {
+        if covered != self.covered {
+            self.finalize_segment();
+            self.covered = covered;
+        }
+    }
+
     pub fn add_text(&mut self, text: &str, x: f64, y: f64) -> Result<(), OdeError> {
+        self.add_text_with_extent(text, x, y, None)
+    }
+
+    /// Add shown text, continuing the current segment when it sits on the
+    /// same line. With an `extent`, consecutive text is joined by the gap
+    /// between them, with a space for word-sized gaps, into line-level
+    /// segments; without one, text is joined when it starts within 2em.
+    pub fn add_text_with_extent(
+        &mut self,
+        text: &str,
+        x: f64,
+        y: f64,
+        extent: Option<TextExtent>,
+    ) -> Result<(), OdeError> {
         if text.is_empty() {
             return Ok(());
         }
 
         let font_size = self.current_state.font_size;
         let color = self.current_state.fill_color.clone();
-        let font_id = self
-            .current_state
-            .font_info
-            .as_ref()
-            .map(|f| f.id)
-            .unwrap_or(0);
+        let font_id = self.current_state.font_info.as_ref().map(|f| f.id);
+        let scale_x = self.current_state.transform_matrix.a;
+        let vertical = self.current_state.vertical;
+        // Vertical glyphs stand a full em apart
+        let (width, height) = match extent {
+            _ if vertical => (font_size, text.chars().count() as f64 * font_size),
+            Some(extent) => (
+                (extent.end_x - x).max(0.0),
+                line_height(&self.current_state),
+            ),
+            None => (
+                estimated_advance(text, font_size) * scale_x,
+                line_height(&self.current_state),
+            ),
+        };
+        let segment = TextSegment {
+            text: text.to_string(),
+            x,
+            y,
+            font_size,
+            color,
+            font_id,
+            extent,
+            covered: self.covered,
+            scale_x,
+            vertical,
+            width,
+            height,
+            letter_spacing: self.current_state.letter_space,
+            word_spacing: self.current_state.word_space,
+            lang: self.current_state.lang.clone(),
+            mcid: self.current_state.mcid,
+        };
 
         let position_diff = if let Some(ref last) = self.last_position {
             let dx = (x - last.x).abs();
@@ -76,23 +227,18 @@ impl TextExtractor {
         };
 
         let is_same_line = position_diff.1 < 0.5;
-        let is_continuation = position_diff.0 < (font_size * 2.0) && is_same_line;
+        let join = match &self.current_segment {
+            _ if !self.merge_spans => None,
+            Some(seg) if seg.extent.is_some() => seg.join(x, y),
+            Some(_) if position_diff.0 < (font_size * 2.0) && is_same_line => Some(""),
+            _ => None,
+        };
 
-        if self.current_segment.is_some() && is_continuation {
-            if let Some(ref mut seg) = self.current_segment {
-                seg.text.push_str(text);
-            }
+        if let (Some(separator), Some(seg)) = (join, self.current_segment.as_mut()) {
+            seg.append(separator, &segment);
         } else {
             self.finalize_segment();
-
-            self.current_segment = Some(TextSegment {
-                text: text.to_string(),
-                x,
-                y,
-                font_size,
-                color,
-                font_id,
-            });
+            self.current_segment = Some(segment);
         }
 
         self.last_position = Some(TextPosition { x, y });
@@ -105,12 +251,44 @@ impl TextExtractor {
         }
 
         let segment = self.current_segment.take()?;
-        if !segment.text.trim().is_empty() {
-            self.segments.push(segment.clone());
-            Some(segment)
-        } else {
-            None
+        if segment.text.trim().is_empty() {
+            return None;
+        }
+        // A line split by BT/ET or a state change that comes back joins up again
+        let merge_spans = self.merge_spans;
+        if let Some(last) = self.segments.last_mut().filter(|_| merge_spans) {
+            let same_style = last.same_style(&segment);
+            if let Some(separator) = last.join(segment.x, segment.y).filter(|_| same_style) {
+                last.append(separator, &segment);
+                return Some(last.clone());
+            }
         }
+        self.segments.push(segment.clone());
+        Some(segment)
+    }
+
+    /// Join each finished segment onto an earlier one it continues: same
+    /// style, on the same line and starting where that one ends, with a
+    /// space for a word-sized gap. This catches the fragments of a line that
+    /// other text was shown between, which merging while adding can't see.
+    pub fn coalesce(&mut self) {
+        self.finalize_segment();
+        if !self.merge_spans {
+            return;
+        }
+        let mut coalesced: Vec<TextSegment> = Vec::with_capacity(self.segments.len());
+        for segment in self.segments.drain(..) {
+            let continued = coalesced.iter_mut().rev().find_map(|earlier| {
+                let same_style = earlier.same_style(&segment);
+                let separator = earlier.join(segment.x, segment.y).filter(|_| same_style)?;
+                Some((earlier, separator))
+            });
+            match continued {
+                Some((earlier, separator)) => earlier.append(separator, &segment),
+                None => coalesced.push(segment),
+            }
+        }
+        self.segments = coalesced;
     }
 
     pub fn get_segments(&self) -> &[TextSegment] {
@@ -125,8 +303,19 @@ impl TextExtractor {
                 x: seg.x,
                 y: seg.y,
                 font_size: seg.font_size,
-                font_id: Some(seg.font_id),
+                font_id: seg.font_id,
                 color: seg.color.to_css_string(),
+                covered: seg.covered,
+                scale_x: seg.scale_x,
+                vertical: seg.vertical,
+                width: seg.width,
+                height: seg.height,
+                underline: false,
+                line_through: false,
+                letter_spacing: seg.letter_spacing,
+                word_spacing: seg.word_spacing,
+                lang: seg.lang.clone(),
+                mcid: seg.mcid,
             })
             .collect()
     }
@@ -141,7 +330,17 @@ impl TextExtractor {
                 y: span.y,
                 font_size: span.font_size,
                 color: Color::from_css_string(&span.color),
-                font_id: span.font_id.unwrap_or(0),
+                font_id: span.font_id,
+                extent: None,
+                covered: span.covered,
+                scale_x: span.scale_x,
+                vertical: span.vertical,
+                width: span.width,
+                height: span.height,
+                letter_spacing: span.letter_spacing,
+                word_spacing: span.word_spacing,
+                lang: span.lang.clone(),
+                mcid: span.mcid,
             });
         }
     }
@@ -156,8 +355,144 @@ impl TextExtractor {
         let font_changed = self.current_state.font_info != new_state.font_info;
         let color_changed = self.current_state.fill_color != new_state.fill_color;
         let size_changed = (self.current_state.font_size - new_state.font_size).abs() > 0.01;
+        let scale_changed =
+            (self.current_state.transform_matrix.a - new_state.transform_matrix.a).abs() > 0.001;
+
+        let mode_changed = self.current_state.vertical != new_state.vertical;
+        let spacing_changed = (self.current_state.letter_space - new_state.letter_space).abs()
+            > 0.001
+            || (self.current_state.word_space - new_state.word_space).abs() > 0.001;
+        let marked_changed =
+            self.current_state.lang != new_state.lang || self.current_state.mcid != new_state.mcid;
+
+        font_changed
+            || color_changed
+            || size_changed
+            || scale_changed
+            || mode_changed
+            || spacing_changed
+            || marked_changed
+    }
+}
+
+/// A call on a page's `TextExtractor`, held back until the page is drawn.
+enum TextEvent {
+    Break,
+    State(Box<GraphicsState>),
+    Text {
+        text: String,
+        x: f64,
+        y: f64,
+        extent: Option<TextExtent>,
+    },
+    Spans(Vec<TextSpan>),
+}
 
-        font_changed || color_changed || size_changed
+/// The text a page shows, recorded in drawing order so that text the
+/// drawing after it covers can be dropped or marked once the whole page has
+/// been seen. Each shown string and each merged span is one character to the
+/// `CoveredTextDetector`, and every opaque fill a non-character.
+pub struct PageText {
+    events: Vec<TextEvent>,
+    detector: Option<CoveredTextDetector>,
+    font_size: f64,
+}
+
+impl PageText {
+    /// Record text for `covered_text`; with `CoveredText::Keep` nothing is
+    /// checked for coverage.
+    pub fn new(covered_text: CoveredText) -> Self {
+        Self {
+            events: Vec::new(),
+            detector: (covered_text != CoveredText::Keep).then(CoveredTextDetector::new),
+            font_size: 0.0,
+        }
+    }
+
+    pub fn finalize_segment(&mut self) {
+        self.events.push(TextEvent::Break);
+    }
+
+    pub fn update_state(&mut self, state: &GraphicsState) {
+        self.font_size = state.font_size;
+        self.events.push(TextEvent::State(Box::new(state.clone())));
+    }
+
+    /// Text shown at `x` with its top at `y`, as for
+    /// [`TextExtractor::add_text_with_extent`].
+    pub fn add_text_with_extent(&mut self, text: &str, x: f64, y: f64, extent: Option<TextExtent>) {
+        if let Some(detector) = &mut self.detector {
+            let width = extent.map_or(0.0, |e| e.end_x - x);
+            detector.add_character(x, y, width, self.font_size);
+        }
+        self.events.push(TextEvent::Text {
+            text: text.to_string(),
+            x,
+            y,
+            extent,
+        });
+    }
+
+    pub fn merge_spans(&mut self, spans: &[TextSpan]) {
+        if let Some(detector) = &mut self.detector {
+            for span in spans {
+                detector.add_character(span.x, span.y, 0.0, span.font_size);
+            }
+        }
+        self.events.push(TextEvent::Spans(spans.to_vec()));
+    }
+
+    /// An opaque fill over `x`, `y` (top left), `width` and `height`.
+    pub fn cover(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        if let Some(detector) = &mut self.detector {
+            detector.add_non_character(x, y, width, height);
+        }
+    }
+
+    /// Replay the recorded text into `extractor`, dropping or marking the
+    /// fully covered pieces as `covered_text` says.
+    pub fn replay(
+        self,
+        extractor: &mut TextExtractor,
+        covered_text: CoveredText,
+    ) -> Result<(), OdeError> {
+        let detector = self.detector;
+        let mut char_index = 0;
+        let mut next_covered = || {
+            let covered = detector
+                .as_ref()
+                .is_some_and(|d| d.is_char_fully_covered(char_index));
+            char_index += 1;
+            covered
+        };
+        for event in self.events {
+            match event {
+                TextEvent::Break => {
+                    extractor.finalize_segment();
+                }
+                TextEvent::State(state) => extractor.update_state(&state),
+                TextEvent::Text { text, x, y, extent } => {
+                    let covered = next_covered();
+                    if covered && covered_text == CoveredText::Drop {
+                        continue;
+                    }
+                    extractor.set_covered(covered);
+                    extractor.add_text_with_extent(&text, x, y, extent)?;
+                }
+                TextEvent::Spans(spans) => {
+                    let mut kept = Vec::with_capacity(spans.len());
+                    for mut span in spans {
+                        span.covered |= next_covered();
+                        if !(span.covered && covered_text == CoveredText::Drop) {
+                            kept.push(span);
+                        }
+                    }
+                    extractor.merge_spans(&kept);
+                }
+            }
+        }
+        extractor.set_covered(false);
+        Ok(())
     }
 }
 
@@ -233,4 +568,62 @@ mod tests {
         assert_eq!(extractor.segments.len(), 2);
         Ok(())
     }
+
+    fn extent(end_x: f64) -> Option<TextExtent> {
+        Some(TextExtent {
+            end_x,
+            space_width: 3.0,
+        })
+    }
+
+    #[test]
+    fn test_merges_line_with_word_spaces() -> Result<(), OdeError> {
+        let mut extractor = TextExtractor::new();
+
+        extractor.add_text_with_extent("Hello", 10.0, 20.0, extent(40.0))?;
+        extractor.add_text_with_extent("world", 43.0, 20.0, extent(70.0))?;
+        extractor.finalize_segment();
+        // A new BT block further along the same line
+        extractor.add_text_with_extent("again", 70.5, 20.0, extent(90.0))?;
+        extractor.finalize_segment();
+        // Too far past the end to be the same run of text
+        extractor.add_text_with_extent("Column", 200.0, 20.0, extent(240.0))?;
+        extractor.finalize_segment();
+
+        let texts: Vec<&str> = extractor.segments.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(texts, ["Hello worldagain", "Column"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_coalesces_fragments_interleaved_with_other_lines() -> Result<(), OdeError> {
+        let mut extractor = TextExtractor::new();
+
+        // Each line's fragments are shown alternately with the other line's
+        extractor.add_text_with_extent("Net", 10.0, 20.0, extent(30.0))?;
+        extractor.add_text_with_extent("Total", 10.0, 40.0, extent(40.0))?;
+        extractor.add_text_with_extent("income", 33.0, 20.0, extent(60.0))?;
+        extractor.add_text_with_extent("due", 100.0, 40.0, extent(120.0))?;
+        extractor.add_text_with_extent("rose", 63.0, 20.0, extent(83.0))?;
+        extractor.finalize_segment();
+        assert_eq!(extractor.segments.len(), 5);
+
+        extractor.coalesce();
+        let texts: Vec<&str> = extractor.segments.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(texts, ["Net income rose", "Total", "due"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_merging_can_be_turned_off() -> Result<(), OdeError> {
+        let mut extractor = TextExtractor::new().with_merging(false);
+
+        extractor.add_text_with_extent("Hello", 10.0, 20.0, extent(40.0))?;
+        extractor.add_text_with_extent("world", 43.0, 20.0, extent(70.0))?;
+        extractor.add_text("!", 70.0, 20.0)?;
+        extractor.finalize_segment();
+
+        assert_eq!(extractor.segments.len(), 3);
+        Ok(())
+    }
 }
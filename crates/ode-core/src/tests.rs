@@ -115,7 +115,7 @@ mod acceptance_criteria {
         assert!(result.is_err(), "Corrupted PDF should return error");
 
         if let Err(e) = result {
-            assert!(matches!(e, OdeError::PdfParseError(_)));
+            assert_eq!(e.parse_error_kind(), Some(&crate::ParseErrorKind::NotPdf));
         }
     }
 
@@ -1,3 +1,56 @@
+/// What each quadratic term of the ink coverages, in the order
+/// [`Color::from_cmyk`] lists them, adds to red, green and blue.
+const CMYK_TO_RGB: [[f64; 14]; 3] = [
+    [
+        -4.387332384609988,
+        54.48615194189176,
+        18.82290502165302,
+        212.25662451639585,
+        -285.2331026137004,
+        1.7149763477362134,
+        -5.6096736904047315,
+        -17.873870861415444,
+        -5.497006427196366,
+        -2.5217340131683033,
+        -21.248923337353073,
+        17.5119270841813,
+        -21.86122147463605,
+        -189.48180835922747,
+    ],
+    [
+        8.841041422036149,
+        60.118027045597366,
+        6.871425592049007,
+        31.159100130055922,
+        -79.2970844816548,
+        -15.310361306967817,
+        17.575251261109482,
+        131.35250912493976,
+        -190.9453302588951,
+        4.444339102852739,
+        9.8632861493405,
+        -24.86741582555878,
+        -20.737325471181034,
+        -187.80453709719578,
+    ],
+    [
+        0.8842522430003296,
+        8.078677503112928,
+        30.89978309703729,
+        -0.23883238689178934,
+        -14.183576799673286,
+        10.49593273432072,
+        63.02378494754052,
+        50.606957656360734,
+        -112.23884253719248,
+        0.03296041114873217,
+        115.60384449646641,
+        -193.58209356861505,
+        -22.33816807309886,
+        -180.12613974708367,
+    ],
+];
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Color {
     pub transparent: bool,
@@ -34,6 +87,38 @@ impl Color {
         }
     }
 
+    /// The sRGB color that ink coverages `c`, `m`, `y` and `k`, each 0 to
+    /// 1, print as on coated paper.
+    ///
+    /// Inks aren't the ideal filters `(1 - c) * (1 - k)` takes them for:
+    /// black ink alone prints a dark gray, and a rich black, black over
+    /// the other inks, prints darker still. This is a quadratic fit of the
+    /// US Web Coated (SWOP) v2 profile, the one pdf.js renders with.
+    pub fn from_cmyk(c: f64, m: f64, y: f64, k: f64) -> Self {
+        let [c, m, y, k] = [c, m, y, k].map(|v| v.clamp(0.0, 1.0));
+        let terms = [
+            c * c,
+            c * m,
+            c * y,
+            c * k,
+            c,
+            m * m,
+            m * y,
+            m * k,
+            m,
+            y * y,
+            y * k,
+            y,
+            k * k,
+            k,
+        ];
+        let [r, g, b] = CMYK_TO_RGB.map(|coefficients| {
+            let v: f64 = coefficients.iter().zip(&terms).map(|(a, t)| a * t).sum();
+            (255.0 + v).round().clamp(0.0, 255.0) as u8
+        });
+        Self::new(r, g, b)
+    }
+
     pub fn to_rgb_normalized(&self) -> (f64, f64, f64) {
         (
             self.r as f64 / 255.0,
@@ -121,6 +206,38 @@ mod tests {
         assert!((dist - 1.732).abs() < 0.01);
     }
 
+    #[test]
+    fn test_cmyk_prints_as_on_coated_paper() {
+        let near = |color: Color, (r, g, b): (u8, u8, u8)| {
+            let off = [(color.r, r), (color.g, g), (color.b, b)]
+                .iter()
+                .map(|&(got, want)| got.abs_diff(want))
+                .max()
+                .unwrap();
+            assert!(
+                off <= 24,
+                "{:?} is not near rgb({}, {}, {})",
+                color,
+                r,
+                g,
+                b
+            );
+        };
+        assert_eq!(
+            Color::from_cmyk(0.0, 0.0, 0.0, 0.0),
+            Color::new(255, 255, 255)
+        );
+        // As Photoshop converts them with the SWOP profile
+        near(Color::from_cmyk(1.0, 0.0, 0.0, 0.0), (0, 174, 239));
+        near(Color::from_cmyk(0.0, 1.0, 1.0, 0.0), (237, 28, 36));
+
+        // Black ink alone is a dark gray; under the other inks it's darker
+        let black = Color::from_cmyk(0.0, 0.0, 0.0, 1.0);
+        let rich_black = Color::from_cmyk(0.6, 0.4, 0.4, 1.0);
+        assert!(black.r > 30 && black.r < 60, "{:?}", black);
+        assert!(rich_black.r < black.r / 2 && rich_black.b < black.b / 2);
+    }
+
     #[test]
     fn test_color_css_string() {
         let c = Color::new(255, 128, 64);
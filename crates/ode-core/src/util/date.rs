@@ -0,0 +1,83 @@
+use chrono::{DateTime, FixedOffset, NaiveDate, TimeZone, Utc};
+
+/// Parses a PDF date string, `D:YYYYMMDDHHmmSSOHH'mm'`, as in the `/Info`
+/// dictionary's `/CreationDate` and `/ModDate`.
+///
+/// Everything after the year is optional, as is the `D:` prefix. `O` is `+`,
+/// `-` or `Z`; without one the time is taken as UTC. The apostrophes around
+/// the offset's minutes are accepted with or without the trailing one.
+pub fn parse_pdf_date(date: &str) -> Option<DateTime<Utc>> {
+    let date = date.trim();
+    let date = date.strip_prefix("D:").unwrap_or(date);
+    let digits_end = date
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(date.len());
+    let (digits, zone) = date.split_at(digits_end);
+    if digits.len() < 4 || digits.len() > 14 || digits.len() % 2 != 0 {
+        return None;
+    }
+
+    let field = |start: usize, default: u32| match digits.get(start..start + 2) {
+        Some(field) => field.parse().ok(),
+        None => Some(default),
+    };
+    let year = digits[..4].parse().ok()?;
+    let local = NaiveDate::from_ymd_opt(year, field(4, 1)?, field(6, 1)?)?.and_hms_opt(
+        field(8, 0)?,
+        field(10, 0)?,
+        field(12, 0)?,
+    )?;
+
+    let offset_seconds = match zone.chars().next() {
+        None | Some('Z') => 0,
+        Some(sign @ ('+' | '-')) => {
+            let offset: String = zone[1..].chars().filter(|c| *c != '\'').collect();
+            let hours: i32 = offset.get(..2)?.parse().ok()?;
+            let minutes: i32 = match offset.get(2..4) {
+                Some(minutes) => minutes.parse().ok()?,
+                None => 0,
+            };
+            let seconds = hours * 3600 + minutes * 60;
+            if sign == '-' {
+                -seconds
+            } else {
+                seconds
+            }
+        }
+        _ => return None,
+    };
+
+    let offset = FixedOffset::east_opt(offset_seconds)?;
+    Some(offset.from_local_datetime(&local).single()?.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_offset_date_is_converted_to_utc() {
+        assert_eq!(parse_pdf_date("D:20230115120000+01'00'"), Some(utc(2023, 1, 15, 11, 0)));
+        assert_eq!(parse_pdf_date("D:20230115120000-05'30"), Some(utc(2023, 1, 15, 17, 30)));
+        assert_eq!(parse_pdf_date("D:20230115120000Z"), Some(utc(2023, 1, 15, 12, 0)));
+    }
+
+    #[test]
+    fn test_missing_components_take_their_defaults() {
+        assert_eq!(parse_pdf_date("D:2023"), Some(utc(2023, 1, 1, 0, 0)));
+        assert_eq!(parse_pdf_date("20230115"), Some(utc(2023, 1, 15, 0, 0)));
+        assert_eq!(parse_pdf_date("D:202301151230+02"), Some(utc(2023, 1, 15, 10, 30)));
+    }
+
+    #[test]
+    fn test_malformed_dates_are_rejected() {
+        assert_eq!(parse_pdf_date("D:20231315"), None);
+        assert_eq!(parse_pdf_date("D:202"), None);
+        assert_eq!(parse_pdf_date("January 2023"), None);
+        assert_eq!(parse_pdf_date("D:20230115120000+1"), None);
+    }
+}
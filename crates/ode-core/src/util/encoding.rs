@@ -63,6 +63,78 @@ pub fn write_escaped_html<W: Write>(writer: &mut W, text: &str) -> std::io::Resu
     Ok(())
 }
 
+/// PDFDocEncoding code points that differ from ISO Latin-1 (PDF 32000-1, Annex D.2).
+fn pdf_doc_encoding_char(byte: u8) -> char {
+    match byte {
+        0x18 => '\u{02D8}',
+        0x19 => '\u{02C7}',
+        0x1A => '\u{02C6}',
+        0x1B => '\u{02D9}',
+        0x1C => '\u{02DD}',
+        0x1D => '\u{02DB}',
+        0x1E => '\u{02DA}',
+        0x1F => '\u{02DC}',
+        0x80 => '\u{2022}',
+        0x81 => '\u{2020}',
+        0x82 => '\u{2021}',
+        0x83 => '\u{2026}',
+        0x84 => '\u{2014}',
+        0x85 => '\u{2013}',
+        0x86 => '\u{0192}',
+        0x87 => '\u{2044}',
+        0x88 => '\u{2039}',
+        0x89 => '\u{203A}',
+        0x8A => '\u{2212}',
+        0x8B => '\u{2030}',
+        0x8C => '\u{201E}',
+        0x8D => '\u{201C}',
+        0x8E => '\u{201D}',
+        0x8F => '\u{2018}',
+        0x90 => '\u{2019}',
+        0x91 => '\u{201A}',
+        0x92 => '\u{2122}',
+        0x93 => '\u{FB01}',
+        0x94 => '\u{FB02}',
+        0x95 => '\u{0141}',
+        0x96 => '\u{0152}',
+        0x97 => '\u{0160}',
+        0x98 => '\u{0178}',
+        0x99 => '\u{017D}',
+        0x9A => '\u{0131}',
+        0x9B => '\u{0142}',
+        0x9C => '\u{0153}',
+        0x9D => '\u{0161}',
+        0x9E => '\u{017E}',
+        0xA0 => '\u{20AC}',
+        0x7F | 0x9F | 0xAD => '\u{FFFD}',
+        b => b as char,
+    }
+}
+
+/// Decodes a PDF text string (document info, outline titles, annotation text).
+///
+/// Strings starting with the `FE FF` BOM are UTF-16BE per the spec. Some
+/// non-conforming producers emit UTF-16LE with an `FF FE` BOM instead, so that
+/// is accepted too, as is the PDF 2.0 UTF-8 BOM. Anything else is PDFDocEncoding.
+pub fn decode_pdf_text_string(bytes: &[u8]) -> String {
+    if let Some(rest) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        decode_utf16(rest, u16::from_be_bytes)
+    } else if let Some(rest) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+        decode_utf16(rest, u16::from_le_bytes)
+    } else if let Some(rest) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        String::from_utf8_lossy(rest).into_owned()
+    } else {
+        bytes.iter().map(|&b| pdf_doc_encoding_char(b)).collect()
+    }
+}
+
+fn decode_utf16(data: &[u8], to_unit: fn([u8; 2]) -> u16) -> String {
+    let units = data.chunks_exact(2).map(|pair| to_unit([pair[0], pair[1]]));
+    char::decode_utf16(units)
+        .map(|r| r.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -94,6 +166,32 @@ mod tests {
         assert_eq!(escape_html_attribute("<script>`"), "&lt;script&gt;&#x60;");
     }
 
+    #[test]
+    fn test_decode_pdf_text_string_utf16be() {
+        let bytes = [
+            0xFE, 0xFF, 0x00, b'T', 0x00, b'i', 0x00, b't', 0x00, b'l', 0x00, b'e',
+        ];
+        assert_eq!(decode_pdf_text_string(&bytes), "Title");
+    }
+
+    #[test]
+    fn test_decode_pdf_text_string_utf16le() {
+        let bytes = [
+            0xFF, 0xFE, b'T', 0x00, b'i', 0x00, b't', 0x00, b'l', 0x00, b'e', 0x00, 0xAC, 0x20,
+        ];
+        assert_eq!(decode_pdf_text_string(&bytes), "Title\u{20AC}");
+    }
+
+    #[test]
+    fn test_decode_pdf_text_string_pdfdoc() {
+        assert_eq!(decode_pdf_text_string(b"Plain"), "Plain");
+        assert_eq!(
+            decode_pdf_text_string(&[0x93, b'x', 0xA0]),
+            "\u{FB01}x\u{20AC}"
+        );
+        assert_eq!(decode_pdf_text_string(&[0xE9]), "\u{E9}");
+    }
+
     #[test]
     fn test_html_escaping_roundtrip() {
         let original = "<div class=\"test\">Hello & welcome</div>";
@@ -24,12 +24,28 @@ impl ContentHasher {
         let full_hash = Self::hash_bytes(data);
         full_hash.chars().take(length).collect()
     }
+
+    /// A number identifying `data`, from the first 48 bits of its hash so
+    /// it stays exact for JSON readers that hold numbers as doubles.
+    pub fn hash_id(data: &[u8]) -> u64 {
+        Sha256::digest(data)[..6]
+            .iter()
+            .fold(0, |id, &byte| (id << 8) | byte as u64)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_hash_id_fits_in_a_double() {
+        let id = ContentHasher::hash_id(b"test data");
+        assert_eq!(id, ContentHasher::hash_id(b"test data"));
+        assert_ne!(id, ContentHasher::hash_id(b"other data"));
+        assert!(id < 1 << 48);
+    }
+
     #[test]
     fn test_hash_bytes() {
         let data = b"test data";
@@ -1,3 +1,4 @@
+pub mod date;
 pub mod encoding;
 pub mod hash;
 pub mod math;
@@ -5,9 +6,10 @@ pub mod timeout;
 pub mod unicode;
 pub mod zip_bomb;
 
-pub use encoding::{escape_html, escape_html_attribute, escape_json};
+pub use date::parse_pdf_date;
+pub use encoding::{decode_pdf_text_string, escape_html, escape_html_attribute, escape_json};
 pub use hash::ContentHasher;
 pub use math::{equal, hypot, is_positive, BoundingBox, TransformMatrix};
-pub use timeout::TimeoutWrapper;
+pub use timeout::{Deadline, TimeoutWrapper};
 pub use unicode::LigatureMapper;
 pub use zip_bomb::{CompressionMethod, ZipBombDetector};
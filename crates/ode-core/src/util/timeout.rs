@@ -1,5 +1,5 @@
-use crate::error::OdeResult;
-
+use crate::error::{OdeError, OdeResult};
+use std::time::{Duration, Instant};
 
 pub struct TimeoutWrapper {
     pub timeout_ms: u64,
@@ -10,6 +10,15 @@ impl TimeoutWrapper {
         Self { timeout_ms }
     }
 
+    /// A deadline `timeout_ms` from now, for synchronous work that can't be
+    /// interrupted and has to check for itself whether to give up.
+    pub fn deadline(&self) -> Deadline {
+        Deadline {
+            expires: Instant::now() + Duration::from_millis(self.timeout_ms),
+            timeout_ms: self.timeout_ms,
+        }
+    }
+
     #[cfg(feature = "tokio")]
     pub async fn run<F, Fut, R>(&self, f: F) -> OdeResult<R>
     where
@@ -58,10 +67,34 @@ impl TimeoutWrapper {
     }
 }
 
+/// The moment a conversion started under a [`TimeoutWrapper`] runs out of
+/// time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deadline {
+    expires: Instant,
+    timeout_ms: u64,
+}
+
+impl Deadline {
+    pub fn expired(&self) -> bool {
+        Instant::now() >= self.expires
+    }
+
+    /// `OdeError::Timeout` naming the page being worked on, once expired.
+    pub fn check(&self, page_number: usize) -> OdeResult<()> {
+        if self.expired() {
+            return Err(OdeError::Timeout(format!(
+                "Conversion timed out after {}ms on page {}",
+                self.timeout_ms, page_number
+            )));
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::error::OdeError;
 
     #[test]
     fn test_timeout_wrapper_creation() {
@@ -75,4 +108,16 @@ mod tests {
         let result = wrapper.run_sync(|| Ok::<_, OdeError>(42));
         assert_eq!(result.unwrap(), 42);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_expired_deadline_names_the_page() {
+        assert!(TimeoutWrapper::new(60_000).deadline().check(1).is_ok());
+
+        let deadline = TimeoutWrapper::new(0).deadline();
+        assert!(deadline.expired());
+        match deadline.check(7) {
+            Err(OdeError::Timeout(message)) => assert!(message.contains("page 7")),
+            other => panic!("expected a timeout, got {:?}", other),
+        }
+    }
+}
@@ -1,27 +1,83 @@
-use crate::error::{OdeError, OdeResult};
+use crate::config::ConversionConfig;
+use crate::error::{OdeError, OdeResult, ParseErrorKind};
 use flate2::read::{DeflateDecoder, ZlibDecoder};
 use std::io::{Cursor, Read};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ZipBombDetector {
     max_ratio: u32,
+    max_size: usize,
 }
 
 impl ZipBombDetector {
     pub fn new(max_ratio: u32) -> Self {
-        Self { max_ratio }
+        Self {
+            max_ratio,
+            max_size: MAX_DECOMPRESSED_SIZE,
+        }
     }
 
     pub fn with_limit_100_to_1() -> Self {
         Self::new(100)
     }
 
+    /// Cap the bytes a single stream may decompress to.
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// The decompression limits set in `config`.
+    pub fn from_config(config: &ConversionConfig) -> Self {
+        Self::new(config.max_compression_ratio).with_max_size(config.max_decompressed_size)
+    }
+
+    /// Inflate a zlib (`FlateDecode`) stream, giving up with
+    /// `OdeError::ZipBomb` as soon as the output breaks the limits instead
+    /// of after all of it has been allocated.
+    pub fn inflate(&self, compressed: &[u8]) -> OdeResult<Vec<u8>> {
+        let mut decoder = ZlibDecoder::new(compressed);
+        let mut output = Vec::new();
+        let mut chunk = [0u8; 16 * 1024];
+        loop {
+            let bytes_read = decoder.read(&mut chunk).map_err(|e| {
+                OdeError::parse(
+                    ParseErrorKind::CorruptStream,
+                    format!("FlateDecode decompression failed: {}", e),
+                )
+            })?;
+            if bytes_read == 0 {
+                return Ok(output);
+            }
+            output.extend_from_slice(&chunk[..bytes_read]);
+            self.check_output(compressed.len(), output.len())?;
+        }
+    }
+
+    /// Fail once `decompressed` bytes decoded from `compressed` bytes break
+    /// the limits. The ratio only counts past `RATIO_CHECK_FLOOR`, since
+    /// small streams of blank rows or repeated operators routinely compress
+    /// far better than any sane limit.
+    pub fn check_output(&self, compressed: usize, decompressed: usize) -> OdeResult<()> {
+        let ratio = (decompressed / compressed.max(1)).min(u32::MAX as usize) as u32;
+        let too_large = decompressed > self.max_size;
+        let too_dense = decompressed > RATIO_CHECK_FLOOR && ratio > self.max_ratio;
+        if too_large || too_dense {
+            return Err(OdeError::ZipBomb { ratio });
+        }
+        Ok(())
+    }
+
     pub fn check_compressed_data(
         &self,
         compressed: &[u8],
         method: CompressionMethod,
     ) -> OdeResult<usize> {
         if compressed.is_empty() {
-            return Err(OdeError::PdfParseError("Empty compressed data".to_string()));
+            return Err(OdeError::parse(
+                ParseErrorKind::CorruptStream,
+                "Empty compressed data",
+            ));
         }
 
         let decompressed_size = self.measure_decompressed_size(compressed, method)?;
@@ -73,9 +129,12 @@ impl ZipBombDetector {
 
         let mut temp_buf = [0u8; 4096];
         loop {
-            let bytes_read = decoder
-                .read(&mut temp_buf)
-                .map_err(|e| OdeError::PdfParseError(format!("Decompression error: {}", e)))?;
+            let bytes_read = decoder.read(&mut temp_buf).map_err(|e| {
+                OdeError::parse(
+                    ParseErrorKind::CorruptStream,
+                    format!("Decompression error: {}", e),
+                )
+            })?;
 
             if bytes_read == 0 {
                 break;
@@ -170,6 +229,7 @@ impl ZipBombDetector {
 }
 
 const MAX_DECOMPRESSED_SIZE: usize = 100 * 1024 * 1024; // 100MB hard limit
+const RATIO_CHECK_FLOOR: usize = 10 * 1024 * 1024;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CompressionMethod {
@@ -210,7 +270,7 @@ mod tests {
         let detector = ZipBombDetector::with_limit_100_to_1();
         let compressed = vec![0, 1, 2, 3, 4];
         let result = detector.check_compressed_data(&compressed, CompressionMethod::ASCII85Decode);
-        assert!(result.is_ok() || matches!(result.unwrap_err(), OdeError::PdfParseError(_)));
+        assert!(result.is_ok() || matches!(result.unwrap_err(), OdeError::PdfParseError { .. }));
     }
 
     #[test]
@@ -236,6 +296,21 @@ mod tests {
         assert!(matches!(result, Err(OdeError::ZipBomb { .. })));
     }
 
+    #[test]
+    fn test_inflate_stops_at_the_limits() {
+        use flate2::{write::ZlibEncoder, Compression};
+        use std::io::Write;
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(&vec![0u8; 64 * 1024]).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let detector = ZipBombDetector::default();
+        assert_eq!(detector.inflate(&compressed).unwrap().len(), 64 * 1024);
+        let capped = detector.with_max_size(32 * 1024);
+        assert!(matches!(capped.inflate(&compressed), Err(OdeError::ZipBomb { .. })));
+    }
+
     #[test]
     fn test_ascii85_measurement() {
         let detector = ZipBombDetector::default();